@@ -0,0 +1,131 @@
+use crate::config::ProxyConfig;
+use crate::network::glob_match;
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use pnet::ipnetwork::IpNetwork;
+use std::net::IpAddr;
+
+/// Who may use the proxy and where it connects to, from `[proxy]` and the
+/// `proxy` options
+pub struct AccessControl {
+	allow_clients: Vec<IpNetwork>,
+	deny_clients: Vec<IpNetwork>,
+	allow_hosts: Vec<String>,
+	block_hosts: Vec<String>,
+	/// Expected `Proxy-Authorization` credentials, base64 of `user:password`
+	credentials: Option<String>,
+}
+
+/// Why a request was turned away
+#[derive(Debug)]
+pub enum Denial {
+	Client,
+	/// 407: no or wrong `Proxy-Authorization`
+	Authentication,
+	Host,
+}
+
+impl AccessControl {
+	pub fn new(config: &ProxyConfig) -> Result<Self> {
+		if let Some(auth) = &config.auth
+			&& !auth.contains(':')
+		{
+			return Err(anyhow!("Proxy credentials must be given as USER:PASSWORD"));
+		}
+		Ok(Self {
+			allow_clients: parse_networks(&config.allow_clients)?,
+			deny_clients: parse_networks(&config.deny_clients)?,
+			allow_hosts: config.allow_hosts.iter().map(|host| host.to_lowercase()).collect(),
+			block_hosts: config.block_hosts.iter().map(|host| host.to_lowercase()).collect(),
+			credentials: config.auth.as_ref().map(|auth| base64::engine::general_purpose::STANDARD.encode(auth)),
+		})
+	}
+
+	/// Whether anything restricts the proxy, so exposing it is not open relaying
+	pub fn is_restricted(&self) -> bool {
+		!self.allow_clients.is_empty() || !self.deny_clients.is_empty() || self.credentials.is_some()
+	}
+
+	pub fn requires_auth(&self) -> bool {
+		self.credentials.is_some()
+	}
+
+	/// Check only the client address, for what the proxy serves itself
+	/// (the PAC file) rather than relays
+	pub fn check_client(&self, client: IpAddr) -> Result<(), Denial> {
+		// An IPv4 client on a dual-stack socket shows up as ::ffff:a.b.c.d
+		let client = match client {
+			IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(client, IpAddr::V4),
+			v4 => v4,
+		};
+		if self.deny_clients.iter().any(|network| network.contains(client))
+			|| (!self.allow_clients.is_empty() && !self.allow_clients.iter().any(|network| network.contains(client)))
+		{
+			return Err(Denial::Client);
+		}
+		Ok(())
+	}
+
+	/// Check the client address, the `Proxy-Authorization` header of `headers`
+	/// (raw header lines) and the destination host, in that order
+	pub fn check(&self, client: IpAddr, headers: &[String], host: &str) -> Result<(), Denial> {
+		self.check_client(client)?;
+
+		if let Some(expected) = &self.credentials {
+			let supplied = headers.iter()
+				.filter_map(|line| line.split_once(':'))
+				.find(|(name, _)| name.trim().eq_ignore_ascii_case("proxy-authorization"))
+				.and_then(|(_, value)| {
+					let (scheme, credentials) = value.trim().split_once(' ')?;
+					scheme.eq_ignore_ascii_case("basic").then(|| credentials.trim().to_string())
+				});
+			if !supplied.is_some_and(|supplied| constant_time_eq(supplied.as_bytes(), expected.as_bytes())) {
+				return Err(Denial::Authentication);
+			}
+		}
+
+		self.check_host(host)
+	}
+
+	/// Check only the destination host, for connections that carry no proxy
+	/// credentials (`proxy --transparent`)
+	pub fn check_host(&self, host: &str) -> Result<(), Denial> {
+		let host = host.trim_start_matches('[').trim_end_matches(']').to_lowercase();
+		if self.block_hosts.iter().any(|pattern| glob_match(pattern, &host))
+			|| (!self.allow_hosts.is_empty() && !self.allow_hosts.iter().any(|pattern| glob_match(pattern, &host)))
+		{
+			return Err(Denial::Host);
+		}
+		Ok(())
+	}
+}
+
+impl Denial {
+	/// The raw response sent to the client instead of proxying
+	pub fn response(&self) -> Vec<u8> {
+		let (status, extra, body) = match self {
+			Denial::Client => ("403 Forbidden", "", "Client not allowed to use the proxy"),
+			Denial::Authentication => (
+				"407 Proxy Authentication Required",
+				"Proxy-Authenticate: Basic realm=\"Riddler\"\r\n",
+				"Proxy authentication required",
+			),
+			Denial::Host => ("403 Forbidden", "", "Destination blocked by the proxy"),
+		};
+		format!(
+			"HTTP/1.1 {}\r\n{}Content-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			status, extra, body.len(), body
+		).into_bytes()
+	}
+}
+
+fn parse_networks(values: &[String]) -> Result<Vec<IpNetwork>> {
+	values.iter()
+		.map(|value| value.parse::<IpNetwork>().map_err(|e| anyhow!("Invalid client address or CIDR '{}': {}", value, e)))
+		.collect()
+}
+
+/// Compare credentials without revealing through timing how much matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,266 @@
+use crate::http_client::HttpResponseInfo;
+use crate::jsonpath::{self, JsonPath};
+use crate::say;
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A check on a response, written as `SUBJECT OP VALUE`:
+///
+/// * subjects: `status`, `time` (ms, or with an `ms`/`s` suffix), `size` (body
+///   bytes), `body`, `header:NAME` and JSONPath expressions such as `$.data.id`
+/// * operators: `=`, `!=`, `<`, `<=`, `>`, `>=`, `contains`, `matches` (regex)
+///   and `exists`, the last three negated with a leading `not`
+///
+/// e.g. `status=200`, `status=2xx`, `time<1500ms`, `body contains ok`,
+/// `header:content-type contains json`, `$.items[0].id exists`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Assertion {
+	source: String,
+	subject: Subject,
+	operator: Operator,
+	negated: bool,
+	expected: String,
+}
+
+#[derive(Debug, Clone)]
+enum Subject {
+	Status,
+	Time,
+	Size,
+	Body,
+	Header(String),
+	Json(JsonPath),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+	Equal,
+	NotEqual,
+	Less,
+	LessOrEqual,
+	Greater,
+	GreaterOrEqual,
+	Contains,
+	Matches,
+	Exists,
+}
+
+/// Outcome of one assertion against one response
+#[derive(Debug, Clone, Serialize)]
+pub struct AssertionResult {
+	pub assertion: String,
+	pub passed: bool,
+	/// What the response actually had (shortened), or why the check could not be made
+	pub actual: String,
+}
+
+impl Assertion {
+	pub fn parse(source: &str) -> Result<Self> {
+		let invalid = |reason: &str| anyhow!("Invalid assertion '{}': {}", source, reason);
+		let text = source.trim();
+
+		let subject_end = text
+			.find(|c: char| c.is_whitespace() || matches!(c, '=' | '!' | '<' | '>'))
+			.unwrap_or(text.len());
+		let (subject_text, rest) = text.split_at(subject_end);
+		let subject = match subject_text.to_ascii_lowercase().as_str() {
+			"status" => Subject::Status,
+			"time" => Subject::Time,
+			"size" => Subject::Size,
+			"body" => Subject::Body,
+			"" => return Err(invalid("missing subject")),
+			lower => {
+				if let Some(name) = lower.strip_prefix("header:").filter(|name| !name.is_empty()) {
+					Subject::Header(name.to_string())
+				} else if subject_text.starts_with('$') {
+					Subject::Json(JsonPath::parse(subject_text)?)
+				} else {
+					return Err(invalid("subject must be status, time, size, body, header:NAME or a JSONPath"));
+				}
+			}
+		};
+
+		let rest = rest.trim_start();
+		let (negated, rest) = match rest.strip_prefix("not ") {
+			Some(after) => (true, after.trim_start()),
+			None => (false, rest),
+		};
+		const OPERATORS: [(&str, Operator); 11] = [
+			("==", Operator::Equal),
+			("!=", Operator::NotEqual),
+			("<=", Operator::LessOrEqual),
+			(">=", Operator::GreaterOrEqual),
+			("=", Operator::Equal),
+			("<", Operator::Less),
+			(">", Operator::Greater),
+			("contains", Operator::Contains),
+			("matches", Operator::Matches),
+			("exists", Operator::Exists),
+			("!contains", Operator::Contains),
+		];
+		let (token, operator) = OPERATORS
+			.iter()
+			.find(|(token, _)| rest.starts_with(token))
+			.copied()
+			.ok_or_else(|| invalid("expected an operator (=, !=, <, <=, >, >=, contains, matches or exists)"))?;
+		let negated = negated || token == "!contains";
+		if negated && !matches!(operator, Operator::Contains | Operator::Matches | Operator::Exists) {
+			return Err(invalid("only contains, matches and exists can be negated"));
+		}
+
+		let expected = rest[token.len()..].trim();
+		let expected = expected
+			.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+			.or_else(|| expected.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+			.unwrap_or(expected)
+			.to_string();
+		match operator {
+			Operator::Exists if !expected.is_empty() => return Err(invalid("exists takes no value")),
+			Operator::Exists => {}
+			_ if expected.is_empty() => return Err(invalid("missing value")),
+			Operator::Matches => {
+				Regex::new(&expected).map_err(|e| invalid(&e.to_string()))?;
+			}
+			Operator::Less | Operator::LessOrEqual | Operator::Greater | Operator::GreaterOrEqual => {
+				parse_number(&subject, &expected).ok_or_else(|| invalid("expected a number"))?;
+			}
+			_ => {}
+		}
+
+		Ok(Self { source: text.to_string(), subject, operator, negated, expected })
+	}
+
+	pub fn evaluate(&self, response: &HttpResponseInfo) -> AssertionResult {
+		let (passed, actual) = match self.actual(response) {
+			Ok(actual) => match self.compare(actual.as_deref()) {
+				Ok(passed) => (passed, actual.unwrap_or_else(|| "missing".to_string())),
+				Err(e) => (false, e.to_string()),
+			},
+			Err(e) => (false, e.to_string()),
+		};
+		AssertionResult { assertion: self.source.clone(), passed, actual: truncate(&actual, 80) }
+	}
+
+	/// The value the subject has in `response`, `None` when it is absent
+	fn actual(&self, response: &HttpResponseInfo) -> Result<Option<String>> {
+		Ok(match &self.subject {
+			Subject::Status => Some(response.status.to_string()),
+			Subject::Time => Some(response.response_time_ms.to_string()),
+			Subject::Size => Some(response.body.len().to_string()),
+			Subject::Body => Some(response.body.clone()),
+			Subject::Header(name) => response.headers
+				.iter()
+				.find(|(key, _)| key.eq_ignore_ascii_case(name))
+				.map(|(_, value)| value.clone()),
+			Subject::Json(path) => {
+				let json: serde_json::Value = serde_json::from_str(&response.body)
+					.map_err(|_| anyhow!("body is not JSON"))?;
+				path.select(&json).first().map(|value| jsonpath::value_to_string(value))
+			}
+		})
+	}
+
+	fn compare(&self, actual: Option<&str>) -> Result<bool> {
+		if self.operator == Operator::Exists {
+			return Ok(actual.is_some() != self.negated);
+		}
+		let Some(actual) = actual else {
+			return Ok(self.operator == Operator::NotEqual || self.negated);
+		};
+
+		let passed = match self.operator {
+			Operator::Equal | Operator::NotEqual => {
+				let equal = match (&self.subject, self.expected.as_bytes()) {
+					// `2xx` matches any status of the class
+					(Subject::Status, [class, b'x' | b'X', b'x' | b'X']) => actual.as_bytes().first() == Some(class),
+					_ => match (actual.parse::<f64>(), parse_number(&self.subject, &self.expected)) {
+						(Ok(actual), Some(expected)) => actual == expected,
+						_ => actual == self.expected,
+					},
+				};
+				equal == (self.operator == Operator::Equal)
+			}
+			Operator::Less | Operator::LessOrEqual | Operator::Greater | Operator::GreaterOrEqual => {
+				let actual: f64 = actual.parse().map_err(|_| anyhow!("'{}' is not a number", actual))?;
+				let expected = parse_number(&self.subject, &self.expected).unwrap_or_default();
+				match self.operator {
+					Operator::Less => actual < expected,
+					Operator::LessOrEqual => actual <= expected,
+					Operator::Greater => actual > expected,
+					_ => actual >= expected,
+				}
+			}
+			Operator::Contains => actual.contains(&self.expected) != self.negated,
+			Operator::Matches => {
+				let regex = Regex::new(&self.expected)?;
+				regex.is_match(actual) != self.negated
+			}
+			Operator::Exists => unreachable!("handled above"),
+		};
+		Ok(passed)
+	}
+}
+
+/// A number to compare against, with `ms`/`s` suffixes for `time`
+fn parse_number(subject: &Subject, value: &str) -> Option<f64> {
+	let value = value.trim();
+	if matches!(subject, Subject::Time) {
+		if let Some(ms) = value.strip_suffix("ms") {
+			return ms.trim().parse().ok();
+		}
+		if let Some(seconds) = value.strip_suffix('s') {
+			return seconds.trim().parse::<f64>().ok().map(|s| s * 1000.0);
+		}
+	}
+	value.parse().ok()
+}
+
+impl fmt::Display for Assertion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.source)
+	}
+}
+
+impl TryFrom<String> for Assertion {
+	type Error = anyhow::Error;
+
+	fn try_from(source: String) -> Result<Self> {
+		Self::parse(&source)
+	}
+}
+
+impl From<Assertion> for String {
+	fn from(assertion: Assertion) -> Self {
+		assertion.source
+	}
+}
+
+/// Evaluate every assertion against `response`
+pub fn evaluate_all(assertions: &[Assertion], response: &HttpResponseInfo) -> Vec<AssertionResult> {
+	assertions.iter().map(|assertion| assertion.evaluate(response)).collect()
+}
+
+/// Print one line per result and return the number of failures
+pub fn print_report(results: &[AssertionResult]) -> usize {
+	let mut failed = 0;
+	for result in results {
+		if result.passed {
+			say!("  ✅ {}", result.assertion);
+		} else {
+			failed += 1;
+			say!("  ❌ {} (actual: {})", result.assertion, result.actual);
+		}
+	}
+	failed
+}
+
+fn truncate(text: &str, max_chars: usize) -> String {
+	if text.chars().count() > max_chars {
+		format!("{}...", text.chars().take(max_chars).collect::<String>())
+	} else {
+		text.to_string()
+	}
+}
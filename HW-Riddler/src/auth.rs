@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Credentials `HttpClient` adds to a request
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Auth {
+	Basic { user: String, password: String },
+	Bearer { token: String },
+	/// Sent only after the server answers 401 with a Digest challenge
+	Digest { user: String, password: String },
+}
+
+impl Auth {
+	/// Parse `user:password`; a missing password is empty, as with curl
+	pub fn parse_credentials(credentials: &str, digest: bool) -> Result<Self> {
+		let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+		if user.is_empty() {
+			return Err(anyhow!("Credentials must be given as user:password"));
+		}
+		let (user, password) = (user.to_string(), password.to_string());
+		Ok(if digest { Auth::Digest { user, password } } else { Auth::Basic { user, password } })
+	}
+
+	/// `Authorization` value sent with the first request, `None` for Digest
+	pub fn preemptive_header(&self) -> Option<String> {
+		match self {
+			Auth::Basic { user, password } => Some(format!(
+				"Basic {}",
+				base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, password))
+			)),
+			Auth::Bearer { token } => Some(format!("Bearer {}", token)),
+			Auth::Digest { .. } => None,
+		}
+	}
+
+	/// `Authorization` value answering a `WWW-Authenticate` header, if this is
+	/// Digest auth and the header holds a Digest challenge we support
+	pub fn answer_challenge(&self, www_authenticate: &str, method: &str, uri: &str) -> Option<String> {
+		let Auth::Digest { user, password } = self else {
+			return None;
+		};
+		let challenge = DigestChallenge::parse(www_authenticate)?;
+		Some(challenge.authorization(user, password, method, uri, &random_cnonce()))
+	}
+}
+
+/// Digest challenge from a `WWW-Authenticate` header (RFC 7616)
+#[derive(Debug)]
+struct DigestChallenge {
+	realm: String,
+	nonce: String,
+	opaque: Option<String>,
+	algorithm: DigestAlgorithm,
+	/// The server offered `qop=auth`; `auth-int` alone is not supported
+	qop_auth: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DigestAlgorithm {
+	Md5,
+	Md5Sess,
+	Sha256,
+	Sha256Sess,
+}
+
+impl DigestAlgorithm {
+	fn name(self) -> &'static str {
+		match self {
+			DigestAlgorithm::Md5 => "MD5",
+			DigestAlgorithm::Md5Sess => "MD5-sess",
+			DigestAlgorithm::Sha256 => "SHA-256",
+			DigestAlgorithm::Sha256Sess => "SHA-256-sess",
+		}
+	}
+
+	fn hash(self, data: &str) -> String {
+		match self {
+			DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => format!("{:x}", md5::compute(data)),
+			DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => format!("{:x}", Sha256::digest(data)),
+		}
+	}
+}
+
+impl DigestChallenge {
+	fn parse(header: &str) -> Option<Self> {
+		let (scheme, params) = header.trim().split_once(char::is_whitespace)?;
+		if !scheme.eq_ignore_ascii_case("digest") {
+			return None;
+		}
+		let params = parse_params(params);
+
+		let algorithm = match params.get("algorithm").map(|a| a.to_ascii_uppercase()).as_deref() {
+			None | Some("MD5") => DigestAlgorithm::Md5,
+			Some("MD5-SESS") => DigestAlgorithm::Md5Sess,
+			Some("SHA-256") => DigestAlgorithm::Sha256,
+			Some("SHA-256-SESS") => DigestAlgorithm::Sha256Sess,
+			Some(_) => return None,
+		};
+		let qop_auth = params.get("qop")
+			.is_some_and(|qop| qop.split(',').any(|q| q.trim().eq_ignore_ascii_case("auth")));
+
+		Some(Self {
+			realm: params.get("realm").cloned().unwrap_or_default(),
+			nonce: params.get("nonce")?.clone(),
+			opaque: params.get("opaque").cloned(),
+			algorithm,
+			qop_auth,
+		})
+	}
+
+	fn authorization(&self, user: &str, password: &str, method: &str, uri: &str, cnonce: &str) -> String {
+		const NONCE_COUNT: &str = "00000001";
+		let hash = |data: &str| self.algorithm.hash(data);
+
+		let mut ha1 = hash(&format!("{}:{}:{}", user, self.realm, password));
+		if matches!(self.algorithm, DigestAlgorithm::Md5Sess | DigestAlgorithm::Sha256Sess) {
+			ha1 = hash(&format!("{}:{}:{}", ha1, self.nonce, cnonce));
+		}
+		let ha2 = hash(&format!("{}:{}", method.to_uppercase(), uri));
+		let response = if self.qop_auth {
+			hash(&format!("{}:{}:{}:{}:auth:{}", ha1, self.nonce, NONCE_COUNT, cnonce, ha2))
+		} else {
+			// RFC 2069 compatibility
+			hash(&format!("{}:{}:{}", ha1, self.nonce, ha2))
+		};
+
+		let mut header = format!(
+			"Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+			user, self.realm, self.nonce, uri, self.algorithm.name(), response
+		);
+		if self.qop_auth {
+			header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", NONCE_COUNT, cnonce));
+		}
+		if let Some(opaque) = &self.opaque {
+			header.push_str(&format!(", opaque=\"{}\"", opaque));
+		}
+		header
+	}
+}
+
+/// `key=value, key="quoted, value"` auth parameters with lower-cased keys
+fn parse_params(params: &str) -> HashMap<String, String> {
+	let mut result = HashMap::new();
+	let mut rest = params.trim();
+	while !rest.is_empty() {
+		let Some((key, after_key)) = rest.split_once('=') else {
+			break;
+		};
+		let key = key.trim().trim_start_matches(',').trim().to_ascii_lowercase();
+		let after_key = after_key.trim_start();
+
+		let (value, remaining) = if let Some(quoted) = after_key.strip_prefix('"') {
+			let mut value = String::new();
+			let mut chars = quoted.char_indices();
+			let mut end = quoted.len();
+			while let Some((i, c)) = chars.next() {
+				match c {
+					'\\' => {
+						if let Some((_, escaped)) = chars.next() {
+							value.push(escaped);
+						}
+					}
+					'"' => {
+						end = i + 1;
+						break;
+					}
+					c => value.push(c),
+				}
+			}
+			(value, &quoted[end..])
+		} else {
+			let end = after_key.find(',').unwrap_or(after_key.len());
+			(after_key[..end].trim().to_string(), &after_key[end..])
+		};
+
+		result.insert(key, value);
+		rest = remaining.trim_start().trim_start_matches(',').trim_start();
+	}
+	result
+}
+
+fn random_cnonce() -> String {
+	format!("{:016x}", rand::random::<u64>())
+}
@@ -0,0 +1,114 @@
+use crate::performance_analyzer::{LatencyStats, TargetSummary};
+use crate::say;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Latency percentiles per URL saved by `analyze --save-baseline`, so a later
+/// run can be checked against them with `--compare`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+	pub created_at: chrono::DateTime<chrono::Utc>,
+	pub targets: BTreeMap<String, BaselineTarget>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaselineTarget {
+	pub latency: LatencyStats,
+	pub error_rate: f64,
+}
+
+/// One metric of one target, then and now
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricChange {
+	pub url: String,
+	pub metric: &'static str,
+	pub baseline_ms: u64,
+	pub current_ms: u64,
+	/// Relative change in percent, positive when slower
+	pub change_pct: f64,
+	pub regressed: bool,
+}
+
+impl Baseline {
+	/// Targets without a single response are left out
+	pub fn from_summaries(summaries: &[TargetSummary]) -> Self {
+		let targets = summaries
+			.iter()
+			.filter_map(|summary| {
+				let latency = summary.latency.clone()?;
+				Some((summary.url.clone(), BaselineTarget { latency, error_rate: summary.error_rate() }))
+			})
+			.collect();
+		Self { created_at: chrono::Utc::now(), targets }
+	}
+
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.with_context(|| format!("Unable to read baseline {}", path.display()))?;
+		serde_json::from_str(&content)
+			.with_context(|| format!("{} is not a valid baseline", path.display()))
+	}
+
+	pub fn save(&self, path: &Path) -> Result<()> {
+		std::fs::write(path, serde_json::to_string_pretty(self)?)
+			.with_context(|| format!("Unable to write baseline {}", path.display()))
+	}
+
+	/// p50/p95/p99 of every target that is also in the baseline. A metric
+	/// regressed when it got slower by more than `threshold_pct` percent.
+	pub fn compare(&self, summaries: &[TargetSummary], threshold_pct: f64) -> Vec<MetricChange> {
+		let mut changes = Vec::new();
+		for summary in summaries {
+			let (Some(baseline), Some(current)) = (self.targets.get(&summary.url), &summary.latency) else {
+				continue;
+			};
+			let metrics = [
+				("p50", baseline.latency.p50_ms, current.p50_ms),
+				("p95", baseline.latency.p95_ms, current.p95_ms),
+				("p99", baseline.latency.p99_ms, current.p99_ms),
+			];
+			for (metric, baseline_ms, current_ms) in metrics {
+				let change_pct = (current_ms as f64 - baseline_ms as f64) * 100.0 / baseline_ms.max(1) as f64;
+				changes.push(MetricChange {
+					url: summary.url.clone(),
+					metric,
+					baseline_ms,
+					current_ms,
+					change_pct,
+					regressed: change_pct > threshold_pct,
+				});
+			}
+		}
+		changes
+	}
+}
+
+/// Print the comparison and return the number of regressed metrics
+pub fn print_comparison(baseline: &Baseline, changes: &[MetricChange], threshold_pct: f64) -> usize {
+	say!("=== BASELINE COMPARISON (from {}, threshold +{}%) ===\n",
+		baseline.created_at.format("%Y-%m-%d %H:%M:%S UTC"), threshold_pct);
+	if changes.is_empty() {
+		say!("⚠️  None of the analyzed URLs are in the baseline");
+		return 0;
+	}
+
+	let mut regressions = 0;
+	let mut current_url = "";
+	for change in changes {
+		if change.url != current_url {
+			current_url = &change.url;
+			say!("{}", change.url);
+		}
+		let marker = if change.regressed {
+			regressions += 1;
+			"❌"
+		} else {
+			"✅"
+		};
+		say!("  {} {:<4} {:>6}ms → {:>6}ms ({:+.1}%)",
+			marker, change.metric, change.baseline_ms, change.current_ms, change.change_pct);
+	}
+	regressions
+}
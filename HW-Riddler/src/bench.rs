@@ -0,0 +1,379 @@
+use crate::hosts::format_bytes;
+use crate::http_client::{HttpClient, HttpRequestBuilder};
+use crate::say;
+use crate::stats::percentile;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Fall in throughput from the first to the last third of the run that is
+/// reported as the server not keeping up
+const DEGRADATION: f64 = 0.10;
+
+pub struct BenchOptions {
+	pub connections: usize,
+	pub duration: Duration,
+	/// Requests sent before measuring starts, so connections are open and caches warm
+	pub warmup: Duration,
+	pub timeout: Duration,
+}
+
+/// Why a request got no complete response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ErrorKind {
+	Connect,
+	Timeout,
+	/// The connection broke while the body was read
+	Read,
+	Other,
+}
+
+impl ErrorKind {
+	fn of(error: &reqwest::Error) -> Self {
+		if error.is_timeout() {
+			ErrorKind::Timeout
+		} else if error.is_connect() {
+			ErrorKind::Connect
+		} else if error.is_body() || error.is_decode() {
+			ErrorKind::Read
+		} else {
+			ErrorKind::Other
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			ErrorKind::Connect => "connect",
+			ErrorKind::Timeout => "timeout",
+			ErrorKind::Read => "read",
+			ErrorKind::Other => "other",
+		}
+	}
+}
+
+/// What one connection measured
+#[derive(Default)]
+struct Recorder {
+	/// Microseconds per completed request
+	latencies_us: Vec<u64>,
+	/// Completed requests per second of the measurement
+	per_second: Vec<u64>,
+	status_counts: BTreeMap<u16, u64>,
+	errors: BTreeMap<ErrorKind, u64>,
+	first_error: Option<String>,
+	bytes: u64,
+	http_version: Option<String>,
+}
+
+impl Recorder {
+	fn completed(&mut self, second: usize) {
+		if self.per_second.len() <= second {
+			self.per_second.resize(second + 1, 0);
+		}
+		self.per_second[second] += 1;
+	}
+
+	fn merge(&mut self, other: Recorder) {
+		self.latencies_us.extend(other.latencies_us);
+		if self.per_second.len() < other.per_second.len() {
+			self.per_second.resize(other.per_second.len(), 0);
+		}
+		for (total, count) in self.per_second.iter_mut().zip(other.per_second) {
+			*total += count;
+		}
+		for (status, count) in other.status_counts {
+			*self.status_counts.entry(status).or_default() += count;
+		}
+		for (kind, count) in other.errors {
+			*self.errors.entry(kind).or_default() += count;
+		}
+		self.first_error = self.first_error.take().or(other.first_error);
+		self.bytes += other.bytes;
+		self.http_version = self.http_version.take().or(other.http_version);
+	}
+}
+
+/// Latency over the requests that got a response, in milliseconds
+#[derive(Debug, Serialize)]
+pub struct LatencySummary {
+	pub mean_ms: f64,
+	pub std_dev_ms: f64,
+	pub min_ms: f64,
+	pub max_ms: f64,
+	pub p50_ms: f64,
+	pub p75_ms: f64,
+	pub p90_ms: f64,
+	pub p99_ms: f64,
+	pub p999_ms: f64,
+}
+
+impl LatencySummary {
+	fn new(latencies_us: &mut [u64]) -> Option<Self> {
+		if latencies_us.is_empty() {
+			return None;
+		}
+		latencies_us.sort_unstable();
+		let ms = |us: u64| us as f64 / 1000.0;
+		let count = latencies_us.len() as f64;
+		let mean = latencies_us.iter().map(|&us| us as f64).sum::<f64>() / count;
+		let variance = latencies_us.iter().map(|&us| (us as f64 - mean).powi(2)).sum::<f64>() / count;
+		Some(Self {
+			mean_ms: mean / 1000.0,
+			std_dev_ms: variance.sqrt() / 1000.0,
+			min_ms: ms(latencies_us[0]),
+			max_ms: ms(latencies_us[latencies_us.len() - 1]),
+			p50_ms: ms(percentile(latencies_us, 0.50)),
+			p75_ms: ms(percentile(latencies_us, 0.75)),
+			p90_ms: ms(percentile(latencies_us, 0.90)),
+			p99_ms: ms(percentile(latencies_us, 0.99)),
+			p999_ms: ms(percentile(latencies_us, 0.999)),
+		})
+	}
+}
+
+/// Completed requests per whole second of the measurement
+#[derive(Debug, Serialize)]
+pub struct ThroughputSummary {
+	pub mean: f64,
+	pub std_dev: f64,
+	pub min: u64,
+	pub max: u64,
+	/// Median second: the rate the server kept up for most of the run
+	pub sustained: u64,
+	/// Mean of the first and the last third of the run
+	pub first_third: f64,
+	pub last_third: f64,
+}
+
+impl ThroughputSummary {
+	fn new(per_second: &[u64]) -> Option<Self> {
+		if per_second.is_empty() {
+			return None;
+		}
+		let mean_of = |seconds: &[u64]| seconds.iter().sum::<u64>() as f64 / seconds.len().max(1) as f64;
+		let mean = mean_of(per_second);
+		let variance = per_second.iter().map(|&n| (n as f64 - mean).powi(2)).sum::<f64>() / per_second.len() as f64;
+		let mut sorted = per_second.to_vec();
+		sorted.sort_unstable();
+		let third = per_second.len().div_ceil(3);
+		Some(Self {
+			mean,
+			std_dev: variance.sqrt(),
+			min: sorted[0],
+			max: sorted[sorted.len() - 1],
+			sustained: sorted[sorted.len() / 2],
+			first_third: mean_of(&per_second[..third]),
+			last_third: mean_of(&per_second[per_second.len() - third..]),
+		})
+	}
+
+	/// Whether throughput fell noticeably while the run went on
+	fn degraded(&self) -> bool {
+		self.last_third < self.first_third * (1.0 - DEGRADATION)
+	}
+}
+
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+	pub method: String,
+	pub url: String,
+	pub connections: usize,
+	/// Protocol of the responses, e.g. `HTTP/1.1`
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub http_version: Option<String>,
+	pub elapsed_ms: u64,
+	/// Stopped early with Ctrl+C
+	pub interrupted: bool,
+	/// Requests that got a complete response
+	pub requests: u64,
+	/// Response body bytes read
+	pub bytes: u64,
+	pub requests_per_second: f64,
+	pub bytes_per_second: f64,
+	pub latency: Option<LatencySummary>,
+	pub throughput: Option<ThroughputSummary>,
+	pub status_counts: BTreeMap<u16, u64>,
+	/// Responses with a status of 400 or above
+	pub error_responses: u64,
+	/// Requests without a complete response, per cause
+	pub errors: BTreeMap<String, u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub first_error: Option<String>,
+}
+
+/// Send `request` back to back over `options.connections` keep-alive
+/// connections for the warmup and then the measured duration. Each
+/// connection is a worker reusing one pooled reqwest client, and requests
+/// still running when the time is up are not counted.
+pub async fn run(request: &HttpRequestBuilder, options: &BenchOptions, http_client: &HttpClient) -> Result<BenchReport> {
+	let (client, prepared) = http_client.prepare(request, options.timeout)?;
+	if prepared.try_clone().is_none() {
+		return Err(anyhow!("The request body cannot be sent more than once"));
+	}
+	let connections = options.connections.max(1);
+
+	let shutdown = CancellationToken::new();
+	crate::shutdown::cancel_on_signal(shutdown.clone());
+
+	say!(
+		"🏋️  Benchmarking {} {} for {}s over {} connection(s){}",
+		request.method,
+		request.url,
+		options.duration.as_secs_f64(),
+		connections,
+		if options.warmup.is_zero() { String::new() } else { format!(" after {}s of warmup", options.warmup.as_secs_f64()) }
+	);
+
+	let measure_from = Instant::now() + options.warmup;
+	let deadline = measure_from + options.duration;
+	let mut workers = JoinSet::new();
+	for _ in 0..connections {
+		let (client, prepared, shutdown) = (client.clone(), prepared.try_clone().expect("checked above"), shutdown.clone());
+		workers.spawn(async move {
+			let mut recorder = Recorder::default();
+			loop {
+				let request = prepared.try_clone().expect("checked above");
+				let start = Instant::now();
+				let result = tokio::select! {
+					result = exchange(&client, request) => result,
+					_ = tokio::time::sleep_until(deadline) => break,
+					_ = shutdown.cancelled() => break,
+				};
+				// Warmup requests only open connections
+				if start < measure_from {
+					continue;
+				}
+				let second = (Instant::now() - measure_from).as_secs() as usize;
+				match result {
+					Ok((status, version, bytes)) => {
+						recorder.latencies_us.push(start.elapsed().as_micros() as u64);
+						recorder.completed(second);
+						*recorder.status_counts.entry(status).or_default() += 1;
+						recorder.bytes += bytes;
+						recorder.http_version.get_or_insert(version);
+					}
+					Err(e) => {
+						*recorder.errors.entry(ErrorKind::of(&e)).or_default() += 1;
+						recorder.first_error.get_or_insert_with(|| e.to_string());
+						// Do not spin on a server that refuses connections
+						if e.is_connect() {
+							tokio::time::sleep(Duration::from_millis(10)).await;
+						}
+					}
+				}
+			}
+			recorder
+		});
+	}
+
+	let mut total = Recorder::default();
+	while let Some(recorder) = workers.join_next().await {
+		total.merge(recorder.map_err(|e| anyhow!("Benchmark worker failed: {}", e))?);
+	}
+
+	let interrupted = shutdown.is_cancelled();
+	let elapsed = Instant::now().min(deadline).saturating_duration_since(measure_from);
+	// Only whole seconds say anything about the rate; an interrupted run ends in a partial one
+	let whole_seconds = (elapsed.as_secs() as usize).min(total.per_second.len());
+	let requests = total.latencies_us.len() as u64;
+	let seconds = elapsed.as_secs_f64().max(0.001);
+	Ok(BenchReport {
+		method: request.method.clone(),
+		url: request.url.clone(),
+		connections,
+		http_version: total.http_version,
+		elapsed_ms: elapsed.as_millis() as u64,
+		interrupted,
+		requests,
+		bytes: total.bytes,
+		requests_per_second: requests as f64 / seconds,
+		bytes_per_second: total.bytes as f64 / seconds,
+		latency: LatencySummary::new(&mut total.latencies_us),
+		throughput: ThroughputSummary::new(&total.per_second[..whole_seconds]),
+		error_responses: total.status_counts.range(400..).map(|(_, count)| count).sum(),
+		status_counts: total.status_counts,
+		errors: total.errors.into_iter().map(|(kind, count)| (kind.name().to_string(), count)).collect(),
+		first_error: total.first_error,
+	})
+}
+
+/// One request with its body read to the end: status, protocol and body size
+async fn exchange(client: &reqwest::Client, request: reqwest::Request) -> reqwest::Result<(u16, String, u64)> {
+	let mut response = client.execute(request).await?;
+	let status = response.status().as_u16();
+	let version = format!("{:?}", response.version());
+	let mut bytes = 0;
+	while let Some(chunk) = response.chunk().await? {
+		bytes += chunk.len() as u64;
+	}
+	Ok((status, version, bytes))
+}
+
+pub fn print_report(report: &BenchReport) {
+	println!(
+		"\n  {} connection(s){}, {:.2}s{}",
+		report.connections,
+		report.http_version.as_ref().map(|version| format!(" over {}", version)).unwrap_or_default(),
+		report.elapsed_ms as f64 / 1000.0,
+		if report.interrupted { " (interrupted)" } else { "" }
+	);
+	println!(
+		"  Requests      {} ({:.1} req/s), {} read ({}/s)",
+		report.requests,
+		report.requests_per_second,
+		format_bytes(report.bytes),
+		format_bytes(report.bytes_per_second as u64)
+	);
+	if let Some(throughput) = &report.throughput {
+		println!(
+			"  Per second    mean {:.0} ± {:.0}, min {}, max {}, sustained {}",
+			throughput.mean, throughput.std_dev, throughput.min, throughput.max, throughput.sustained
+		);
+	}
+	if let Some(latency) = &report.latency {
+		println!(
+			"  Latency       mean {:.2}ms ± {:.2}ms, min {:.2}ms, max {:.2}ms",
+			latency.mean_ms, latency.std_dev_ms, latency.min_ms, latency.max_ms
+		);
+		for (label, value) in [
+			("p50", latency.p50_ms),
+			("p75", latency.p75_ms),
+			("p90", latency.p90_ms),
+			("p99", latency.p99_ms),
+			("p99.9", latency.p999_ms),
+		] {
+			println!("    {:<6} {:>10.2}ms", label, value);
+		}
+	}
+	if !report.status_counts.is_empty() {
+		let statuses: Vec<String> = report.status_counts.iter().map(|(status, count)| format!("{}: {}", status, count)).collect();
+		println!("  Status        {}", statuses.join(", "));
+	}
+	if !report.errors.is_empty() {
+		let errors: Vec<String> = report.errors.iter().map(|(kind, count)| format!("{} {}", kind, count)).collect();
+		println!("  Errors        {}", errors.join(", "));
+		if let Some(first) = &report.first_error {
+			println!("    first: {}", first);
+		}
+	}
+
+	if report.requests == 0 {
+		println!("\n⚠️  No request completed");
+	} else if report.error_responses > 0 {
+		println!(
+			"\n⚠️  {:.1}% of the responses had an error status",
+			report.error_responses as f64 * 100.0 / report.requests as f64
+		);
+	}
+	if let Some(throughput) = report.throughput.as_ref().filter(|throughput| throughput.degraded()) {
+		println!(
+			"⚠️  Throughput fell from {:.0} req/s in the first third of the run to {:.0} req/s in the last; \
+			 the server does not sustain this load",
+			throughput.first_third, throughput.last_third
+		);
+	}
+}
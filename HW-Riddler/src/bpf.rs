@@ -0,0 +1,66 @@
+use anyhow::{anyhow, Result};
+
+/// Named filters for `--preset`, as (name, BPF expression, description)
+pub const PRESETS: &[(&str, &str, &str)] = &[
+	("http", "tcp port 80 or tcp port 8080", "plaintext HTTP"),
+	("https", "tcp port 443", "HTTPS over TCP"),
+	("web", "tcp port 80 or tcp port 8080 or tcp port 443", "HTTP and HTTPS"),
+	("dns", "port 53", "DNS over UDP and TCP"),
+	("quic", "udp port 443", "QUIC / HTTP/3"),
+	("tls", "tcp port 443 or tcp port 8443", "TLS on the usual ports"),
+];
+
+/// Presets that take a value after a colon, e.g. `host:example.com`
+const PARAMETERIZED: &[(&str, &str)] = &[
+	("host", "name or address"),
+	("port", "number"),
+	("net", "CIDR"),
+];
+
+/// BPF expression of one `--preset` value
+pub fn expand_preset(preset: &str) -> Result<String> {
+	if let Some((name, value)) = preset.split_once(':') {
+		let value = value.trim();
+		if value.is_empty() || value.contains(char::is_whitespace) {
+			return Err(anyhow!("Preset '{}' needs a single value after the colon", preset));
+		}
+		return match name {
+			"host" => Ok(format!("host {}", value)),
+			"port" => value.parse::<u16>()
+				.map(|port| format!("port {}", port))
+				.map_err(|_| anyhow!("Invalid port in preset '{}'", preset)),
+			"net" => Ok(format!("net {}", value)),
+			_ => Err(unknown_preset(preset)),
+		};
+	}
+
+	PRESETS.iter()
+		.find(|(name, _, _)| *name == preset)
+		.map(|(_, expression, _)| expression.to_string())
+		.ok_or_else(|| unknown_preset(preset))
+}
+
+fn unknown_preset(preset: &str) -> anyhow::Error {
+	let names: Vec<String> = PRESETS.iter().map(|(name, _, description)| format!("{} ({})", name, description))
+		.chain(PARAMETERIZED.iter().map(|(name, value)| format!("{}:<{}>", name, value)))
+		.collect();
+	anyhow!("Unknown filter preset '{}'. Available presets: {}", preset, names.join(", "))
+}
+
+/// The capture filter for `--preset` and `--filter`: presets are combined
+/// with `or`, then narrowed by the filter expression. `default` applies when
+/// neither is given.
+pub fn build_filter(presets: &[String], filter: Option<String>, default: &str) -> Result<String> {
+	let expressions = presets.iter().map(|preset| expand_preset(preset)).collect::<Result<Vec<_>>>()?;
+	let presets = match expressions.as_slice() {
+		[] => None,
+		[expression] => Some(expression.clone()),
+		expressions => Some(expressions.iter().map(|e| format!("({})", e)).collect::<Vec<_>>().join(" or ")),
+	};
+	Ok(match (presets, filter) {
+		(Some(presets), Some(filter)) => format!("({}) and ({})", presets, filter),
+		(Some(presets), None) => presets,
+		(None, Some(filter)) => filter,
+		(None, None) => default.to_string(),
+	})
+}
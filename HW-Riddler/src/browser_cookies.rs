@@ -0,0 +1,285 @@
+use crate::cookie_manager::CookieEntry;
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use anyhow::{anyhow, bail, Result};
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+/// Seconds between 1601-01-01 (Chrome's epoch) and 1970-01-01
+const CHROME_EPOCH_OFFSET: u64 = 11_644_473_600;
+/// From this `meta.version` on, Chrome prefixes decrypted values with a
+/// SHA-256 of the host
+const CHROME_HASH_PREFIX_VERSION: i64 = 24;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum Browser {
+	Chrome,
+	Chromium,
+	Firefox,
+}
+
+impl Browser {
+	fn name(self) -> &'static str {
+		match self {
+			Browser::Chrome => "chrome",
+			Browser::Chromium => "chromium",
+			Browser::Firefox => "firefox",
+		}
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct BrowserImport {
+	pub cookies: Vec<CookieEntry>,
+	pub database: PathBuf,
+	/// Skipped because they already expired
+	pub expired: usize,
+	/// Skipped because the value could not be decrypted
+	pub undecryptable: usize,
+}
+
+/// Read the cookie database of `browser`. `profile` is a profile directory or
+/// the database file itself; without it the default profile is used.
+/// `domain` keeps cookies for that domain, its subdomains and parent domains.
+pub fn import(browser: Browser, profile: Option<&Path>, domain: Option<&str>) -> Result<BrowserImport> {
+	let database = match profile {
+		Some(path) if path.is_file() => path.to_path_buf(),
+		Some(path) => cookie_database_in(browser, path)
+			.ok_or_else(|| anyhow!("No {} cookie database in {}", browser.name(), path.display()))?,
+		None => default_cookie_database(browser)?,
+	};
+
+	// The browser keeps the database locked while it runs, so work on a copy
+	let copy = TempCopy::new(&database)?;
+	let connection = Connection::open_with_flags(&copy.path, OpenFlags::SQLITE_OPEN_READ_WRITE)
+		.map_err(|e| anyhow!("Unable to open {}: {}", database.display(), e))?;
+
+	let mut import = match browser {
+		Browser::Firefox => read_firefox(&connection)?,
+		Browser::Chrome | Browser::Chromium => read_chrome(&connection, browser)?,
+	};
+	import.database = database;
+
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+	let before = import.cookies.len();
+	import.cookies.retain(|c| c.expires.is_none_or(|expires| expires > now));
+	import.expired = before - import.cookies.len();
+
+	if let Some(domain) = domain {
+		import.cookies.retain(|c| c.applies_to(domain) || c.domain.ends_with(&format!(".{}", domain)));
+	}
+	Ok(import)
+}
+
+fn read_firefox(connection: &Connection) -> Result<BrowserImport> {
+	let mut statement = connection.prepare(
+		"SELECT host, name, value, path, expiry, isSecure, isHttpOnly, sameSite, creationTime FROM moz_cookies",
+	)?;
+	let cookies = statement
+		.query_map([], |row| {
+			let expiry: i64 = row.get(4)?;
+			let host: String = row.get(0)?;
+			Ok(CookieEntry {
+				domain: host.trim_start_matches('.').to_string(),
+				name: row.get(1)?,
+				value: row.get(2)?,
+				path: row.get(3)?,
+				// Seconds in older profiles, milliseconds in newer ones
+				expires: Some((if expiry > 100_000_000_000 { expiry / 1000 } else { expiry }) as u64),
+				secure: row.get::<_, i64>(5)? != 0,
+				http_only: row.get::<_, i64>(6)? != 0,
+				same_site: match row.get::<_, i64>(7)? {
+					1 => Some("Lax".to_string()),
+					2 => Some("Strict".to_string()),
+					_ => Some("None".to_string()),
+				},
+				// Browsers mark domain cookies with a leading dot
+				host_only: !host.starts_with('.'),
+				created: (row.get::<_, i64>(8)? / 1_000_000) as u64,
+			})
+		})?
+		.collect::<rusqlite::Result<Vec<_>>>()?;
+
+	Ok(BrowserImport { cookies, ..Default::default() })
+}
+
+fn read_chrome(connection: &Connection, browser: Browser) -> Result<BrowserImport> {
+	let version: i64 = connection
+		.query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| row.get::<_, String>(0))
+		.ok()
+		.and_then(|v| v.parse().ok())
+		.unwrap_or(0);
+	let decryptor = ChromeDecryptor::new(browser, version >= CHROME_HASH_PREFIX_VERSION)?;
+
+	let mut statement = connection.prepare(
+		"SELECT host_key, name, value, encrypted_value, path, expires_utc, is_secure, is_httponly, samesite, creation_utc FROM cookies",
+	)?;
+	let mut rows = statement.query([])?;
+
+	let mut import = BrowserImport::default();
+	while let Some(row) = rows.next()? {
+		let plain: String = row.get(2)?;
+		let encrypted: Vec<u8> = row.get(3)?;
+		let value = if encrypted.is_empty() {
+			plain
+		} else {
+			match decryptor.decrypt(&encrypted) {
+				Ok(value) => value,
+				Err(_) => {
+					import.undecryptable += 1;
+					continue;
+				}
+			}
+		};
+
+		// Microseconds since 1601, 0 for session cookies
+		let expires_utc: i64 = row.get(5)?;
+		let host: String = row.get(0)?;
+		import.cookies.push(CookieEntry {
+			domain: host.trim_start_matches('.').to_string(),
+			name: row.get(1)?,
+			value,
+			path: row.get(4)?,
+			expires: (expires_utc > 0).then(|| (expires_utc as u64 / 1_000_000).saturating_sub(CHROME_EPOCH_OFFSET)),
+			secure: row.get::<_, i64>(6)? != 0,
+			http_only: row.get::<_, i64>(7)? != 0,
+			same_site: match row.get::<_, i64>(8)? {
+				0 => Some("None".to_string()),
+				1 => Some("Lax".to_string()),
+				2 => Some("Strict".to_string()),
+				_ => None,
+			},
+			host_only: !host.starts_with('.'),
+			created: (row.get::<_, i64>(9)? as u64 / 1_000_000).saturating_sub(CHROME_EPOCH_OFFSET),
+		});
+	}
+	Ok(import)
+}
+
+/// Decrypts `v10` / `v11` cookie values: AES-128-CBC with a PBKDF2-SHA1 key
+/// derived from a per-platform password
+struct ChromeDecryptor {
+	v10: [u8; 16],
+	/// Linux only: the key stored in the desktop keyring, if it could be read
+	v11: Option<[u8; 16]>,
+	strip_host_hash: bool,
+}
+
+impl ChromeDecryptor {
+	fn new(browser: Browser, strip_host_hash: bool) -> Result<Self> {
+		if cfg!(target_os = "macos") {
+			let service = match browser {
+				Browser::Chromium => "Chromium Safe Storage",
+				_ => "Chrome Safe Storage",
+			};
+			let password = command_output("security", &["find-generic-password", "-w", "-s", service])
+				.ok_or_else(|| anyhow!("Unable to read \"{}\" from the keychain", service))?;
+			let key = derive_key(password.as_bytes(), 1003);
+			Ok(Self { v10: key, v11: Some(key), strip_host_hash })
+		} else if cfg!(target_os = "linux") {
+			let password = command_output("secret-tool", &["lookup", "application", browser.name()]);
+			Ok(Self {
+				v10: derive_key(b"peanuts", 1),
+				v11: password.map(|p| derive_key(p.as_bytes(), 1)),
+				strip_host_hash,
+			})
+		} else {
+			bail!("Decrypting {} cookies is only supported on Linux and macOS", browser.name())
+		}
+	}
+
+	fn decrypt(&self, encrypted: &[u8]) -> Result<String> {
+		let (key, data) = if let Some(data) = encrypted.strip_prefix(b"v10") {
+			(Some(self.v10), data)
+		} else if let Some(data) = encrypted.strip_prefix(b"v11") {
+			(self.v11, data)
+		} else {
+			bail!("Unknown cookie encryption version")
+		};
+		let key = key.ok_or_else(|| anyhow!("Keyring password is not available"))?;
+
+		let mut buffer = data.to_vec();
+		let plain = Aes128CbcDec::new(&key.into(), &[b' '; 16].into())
+			.decrypt_padded_mut::<Pkcs7>(&mut buffer)
+			.map_err(|_| anyhow!("Wrong key or corrupt cookie value"))?;
+		let plain = if self.strip_host_hash && plain.len() >= 32 { &plain[32..] } else { plain };
+		Ok(String::from_utf8(plain.to_vec())?)
+	}
+}
+
+fn derive_key(password: &[u8], iterations: u32) -> [u8; 16] {
+	let mut key = [0u8; 16];
+	pbkdf2::pbkdf2_hmac::<sha1::Sha1>(password, b"saltysalt", iterations, &mut key);
+	key
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+	let output = Command::new(program).args(args).output().ok()?;
+	let text = String::from_utf8(output.stdout).ok()?.trim().to_string();
+	(output.status.success() && !text.is_empty()).then_some(text)
+}
+
+fn default_cookie_database(browser: Browser) -> Result<PathBuf> {
+	let home = PathBuf::from(std::env::var("HOME").map_err(|_| anyhow!("HOME is not set"))?);
+	let roots: Vec<PathBuf> = match (browser, cfg!(target_os = "macos")) {
+		(Browser::Chrome, true) => vec![home.join("Library/Application Support/Google/Chrome/Default")],
+		(Browser::Chromium, true) => vec![home.join("Library/Application Support/Chromium/Default")],
+		(Browser::Firefox, true) => vec![home.join("Library/Application Support/Firefox/Profiles")],
+		(Browser::Chrome, false) => vec![home.join(".config/google-chrome/Default")],
+		(Browser::Chromium, false) => vec![home.join(".config/chromium/Default"), home.join("snap/chromium/common/chromium/Default")],
+		(Browser::Firefox, false) => vec![home.join(".mozilla/firefox"), home.join("snap/firefox/common/.mozilla/firefox")],
+	};
+
+	let found = match browser {
+		// Pick the profile whose cookies changed most recently
+		Browser::Firefox => roots
+			.iter()
+			.filter_map(|root| std::fs::read_dir(root).ok())
+			.flatten()
+			.filter_map(|dir| cookie_database_in(browser, &dir.ok()?.path()))
+			.max_by_key(|db| db.metadata().and_then(|m| m.modified()).ok()),
+		_ => roots.iter().find_map(|root| cookie_database_in(browser, root)),
+	};
+	found.ok_or_else(|| anyhow!("No {} cookie database found, pass --profile", browser.name()))
+}
+
+fn cookie_database_in(browser: Browser, profile: &Path) -> Option<PathBuf> {
+	let candidates: &[&str] = match browser {
+		Browser::Firefox => &["cookies.sqlite"],
+		// Chrome 96 moved the database into Network/
+		_ => &["Network/Cookies", "Cookies"],
+	};
+	candidates.iter().map(|name| profile.join(name)).find(|path| path.is_file())
+}
+
+/// Copy of a database and its WAL files, removed on drop
+struct TempCopy {
+	dir: PathBuf,
+	path: PathBuf,
+}
+
+impl TempCopy {
+	fn new(database: &Path) -> Result<Self> {
+		let dir = std::env::temp_dir().join(format!("riddler-cookies-{}", std::process::id()));
+		std::fs::create_dir_all(&dir)?;
+		let path = dir.join("cookies.sqlite");
+		std::fs::copy(database, &path)
+			.map_err(|e| anyhow!("Unable to read {}: {}", database.display(), e))?;
+		for suffix in ["-wal", "-shm"] {
+			let side = PathBuf::from(format!("{}{}", database.display(), suffix));
+			if side.is_file() {
+				std::fs::copy(&side, format!("{}{}", path.display(), suffix))?;
+			}
+		}
+		Ok(Self { dir, path })
+	}
+}
+
+impl Drop for TempCopy {
+	fn drop(&mut self) {
+		let _ = std::fs::remove_dir_all(&self.dir);
+	}
+}
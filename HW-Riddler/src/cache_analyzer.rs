@@ -0,0 +1,387 @@
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo, HttpVersion, RetryPolicy};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+/// Status codes a cache may store without explicit freshness information (RFC 9110 §15.1)
+pub(crate) const HEURISTICALLY_CACHEABLE: [u16; 12] = [200, 203, 204, 206, 300, 301, 308, 404, 405, 410, 414, 501];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheHeaders {
+	pub cache_control: HashMap<String, Option<String>>,
+	pub etag: Option<String>,
+	pub last_modified: Option<String>,
+	pub expires: Option<String>,
+	pub date: Option<String>,
+	pub age: Option<u64>,
+	pub vary: Vec<String>,
+	pub pragma_no_cache: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FreshnessSource {
+	SMaxAge,
+	MaxAge,
+	Expires,
+	Heuristic,
+	None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevalidationResult {
+	pub sent_headers: Vec<String>,
+	pub status: u16,
+	pub not_modified: bool,
+	pub etag_changed: bool,
+	pub response_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheAnalysis {
+	pub url: String,
+	pub status: u16,
+	pub headers: CacheHeaders,
+	pub storable: bool,
+	pub shared_storable: bool,
+	pub must_revalidate: bool,
+	pub freshness_lifetime_secs: Option<u64>,
+	pub freshness_source: FreshnessSource,
+	pub current_age_secs: u64,
+	pub revalidation: Option<RevalidationResult>,
+	pub notes: Vec<String>,
+	pub recommendations: Vec<String>,
+}
+
+impl CacheHeaders {
+	pub fn from_response(headers: &HashMap<String, String>) -> Self {
+		let get = |name: &str| {
+			headers.iter()
+				.find(|(k, _)| k.eq_ignore_ascii_case(name))
+				.map(|(_, v)| v.trim().to_string())
+		};
+
+		let cache_control = get("cache-control")
+			.map(|value| {
+				value.split(',')
+					.filter_map(|directive| {
+						let directive = directive.trim();
+						if directive.is_empty() {
+							return None;
+						}
+						let mut parts = directive.splitn(2, '=');
+						let name = parts.next()?.trim().to_lowercase();
+						let arg = parts.next().map(|v| v.trim().trim_matches('"').to_string());
+						Some((name, arg))
+					})
+					.collect()
+			})
+			.unwrap_or_default();
+
+		let vary = get("vary")
+			.map(|v| v.split(',').map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty()).collect())
+			.unwrap_or_default();
+
+		Self {
+			cache_control,
+			etag: get("etag"),
+			last_modified: get("last-modified"),
+			expires: get("expires"),
+			date: get("date"),
+			age: get("age").and_then(|v| v.parse().ok()),
+			vary,
+			pragma_no_cache: get("pragma").is_some_and(|v| v.to_lowercase().contains("no-cache")),
+		}
+	}
+
+	pub(crate) fn has(&self, directive: &str) -> bool {
+		self.cache_control.contains_key(directive)
+	}
+
+	pub(crate) fn seconds(&self, directive: &str) -> Option<u64> {
+		self.cache_control.get(directive)?.as_ref()?.parse().ok()
+	}
+
+	/// How long a response with these headers and `status` stays fresh, and
+	/// what that is based on; `s-maxage` only counts for shared caches
+	pub(crate) fn freshness_lifetime(&self, status: u16) -> (Option<u64>, FreshnessSource) {
+		let date = self.date.as_deref().and_then(parse_http_date).unwrap_or_else(Utc::now);
+		if let Some(s) = self.seconds("s-maxage").filter(|_| !self.has("private")) {
+			(Some(s), FreshnessSource::SMaxAge)
+		} else if let Some(s) = self.seconds("max-age") {
+			(Some(s), FreshnessSource::MaxAge)
+		} else if let Some(ref expires) = self.expires {
+			// An invalid Expires value (often "0" or "-1") means already expired
+			let lifetime = parse_http_date(expires)
+				.map(|expires| (expires - date).num_seconds().max(0) as u64)
+				.unwrap_or(0);
+			(Some(lifetime), FreshnessSource::Expires)
+		} else if let Some(last_modified) = self.last_modified.as_deref().and_then(parse_http_date)
+			&& HEURISTICALLY_CACHEABLE.contains(&status)
+		{
+			// Common heuristic: 10% of the time since the resource last changed
+			let lifetime = ((date - last_modified).num_seconds().max(0) / 10) as u64;
+			(Some(lifetime), FreshnessSource::Heuristic)
+		} else {
+			(None, FreshnessSource::None)
+		}
+	}
+}
+
+pub(crate) fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+	DateTime::parse_from_rfc2822(value).ok().map(|d| d.with_timezone(&Utc))
+}
+
+pub struct CacheAnalyzer {
+	http_client: Arc<HttpClient>,
+}
+
+impl CacheAnalyzer {
+	pub fn new(http_client: Arc<HttpClient>) -> Self {
+		Self { http_client }
+	}
+
+	/// Fetch `url`, evaluate its caching headers and optionally revalidate it
+	pub async fn analyze_url(&self, url: &str, revalidate: bool) -> Result<CacheAnalysis> {
+		info!("Starting cache analysis for: {}", url);
+
+		let response = self.http_client.send_request(get_request(url, HashMap::new())).await?;
+		let mut analysis = analyze_response(url, &response);
+
+		if revalidate {
+			self.revalidate(&mut analysis).await?;
+		}
+
+		Ok(analysis)
+	}
+
+	/// Send a conditional request using the validators of the analysed response
+	/// and record whether the origin answers it with 304 Not Modified
+	pub async fn revalidate(&self, analysis: &mut CacheAnalysis) -> Result<()> {
+		analysis.revalidation = self.conditional_request(&analysis.url, &analysis.headers).await?;
+		add_revalidation_notes(analysis);
+		Ok(())
+	}
+
+	async fn conditional_request(&self, url: &str, headers: &CacheHeaders) -> Result<Option<RevalidationResult>> {
+		let mut conditional = HashMap::new();
+		if let Some(ref etag) = headers.etag {
+			conditional.insert("If-None-Match".to_string(), etag.clone());
+		}
+		if let Some(ref last_modified) = headers.last_modified {
+			conditional.insert("If-Modified-Since".to_string(), last_modified.clone());
+		}
+		if conditional.is_empty() {
+			return Ok(None);
+		}
+
+		let sent_headers = conditional.iter().map(|(k, v)| format!("{}: {}", k, v)).collect();
+		let response = self.http_client.send_request(get_request(url, conditional)).await?;
+		let new_etag = CacheHeaders::from_response(&response.headers).etag;
+
+		Ok(Some(RevalidationResult {
+			sent_headers,
+			status: response.status,
+			not_modified: response.status == 304,
+			etag_changed: new_etag.is_some() && new_etag != headers.etag,
+			response_time_ms: response.response_time_ms,
+		}))
+	}
+}
+
+fn get_request(url: &str, headers: HashMap<String, String>) -> HttpRequestBuilder {
+	HttpRequestBuilder {
+		method: "GET".to_string(),
+		url: url.to_string(),
+		headers,
+		body: None,
+		timeout_seconds: 30,
+		follow_redirects: true,
+		verify_ssl: true,
+		retry: RetryPolicy::default(),
+		auth: None,
+		http_version: HttpVersion::Auto,
+	}
+}
+
+/// Evaluate an already received response, e.g. one taken from the request log
+pub fn analyze_response(url: &str, response: &HttpResponseInfo) -> CacheAnalysis {
+	let headers = CacheHeaders::from_response(&response.headers);
+	let mut notes = Vec::new();
+	let mut recommendations = Vec::new();
+
+	let no_store = headers.has("no-store");
+	let private = headers.has("private");
+	let vary_star = headers.vary.iter().any(|v| v == "*");
+	let must_revalidate = headers.has("no-cache") || headers.pragma_no_cache
+		|| headers.has("must-revalidate") || headers.has("proxy-revalidate");
+
+	let date = headers.date.as_deref().and_then(parse_http_date).unwrap_or_else(Utc::now);
+
+	let (freshness_lifetime_secs, freshness_source) = headers.freshness_lifetime(response.status);
+
+	let explicitly_cacheable = headers.has("public") || headers.has("max-age")
+		|| headers.has("s-maxage") || headers.expires.is_some();
+	let storable = !no_store && !vary_star
+		&& (HEURISTICALLY_CACHEABLE.contains(&response.status) || explicitly_cacheable);
+	let shared_storable = storable && !private;
+
+	let current_age_secs = headers.age.unwrap_or(0)
+		.max((Utc::now() - date).num_seconds().max(0) as u64);
+
+	if no_store {
+		notes.push("Cache-Control: no-store forbids any cache from storing the response".to_string());
+	}
+	if private {
+		notes.push("Cache-Control: private allows only the browser cache, not CDNs or proxies".to_string());
+	}
+	if vary_star {
+		notes.push("Vary: * makes every request unique, so the response is effectively uncacheable".to_string());
+	}
+	if headers.has("no-cache") || headers.pragma_no_cache {
+		notes.push("no-cache: caches may store the response but must revalidate before each reuse".to_string());
+	}
+	if headers.has("immutable") {
+		notes.push("immutable: browsers skip revalidation while the response is fresh".to_string());
+	}
+	if let Some(age) = headers.age {
+		notes.push(format!("Age: {}s — served from an intermediate cache", age));
+	}
+	for header in &headers.vary {
+		match header.as_str() {
+			"user-agent" => notes.push("Vary: User-Agent fragments the cache into one entry per browser version".to_string()),
+			"cookie" => notes.push("Vary: Cookie effectively disables shared caching for logged-in users".to_string()),
+			_ => {}
+		}
+	}
+
+	if storable && headers.etag.is_none() && headers.last_modified.is_none() {
+		recommendations.push("Add an ETag or Last-Modified header so stale copies can be revalidated cheaply".to_string());
+	}
+	if storable && matches!(freshness_source, FreshnessSource::Heuristic | FreshnessSource::None) && !no_store {
+		recommendations.push("Set an explicit Cache-Control max-age instead of relying on heuristic freshness".to_string());
+	}
+	if headers.expires.is_some() && headers.has("max-age") {
+		notes.push("Both Expires and max-age are present; max-age takes precedence".to_string());
+	}
+	if headers.pragma_no_cache && headers.cache_control.is_empty() {
+		recommendations.push("Pragma: no-cache is HTTP/1.0 only; use Cache-Control: no-cache".to_string());
+	}
+	if headers.vary.iter().any(|v| v == "user-agent") {
+		recommendations.push("Avoid Vary: User-Agent; vary on a normalised client hint or serve responsive content".to_string());
+	}
+	if is_static_asset(url) && freshness_lifetime_secs.unwrap_or(0) < 86_400 {
+		recommendations.push("Static asset with a short lifetime: use fingerprinted file names with max-age=31536000, immutable".to_string());
+	}
+	if !storable && !no_store && !private {
+		recommendations.push("Response is not cacheable by default; add Cache-Control if it can be reused".to_string());
+	}
+
+	CacheAnalysis {
+		url: url.to_string(),
+		status: response.status,
+		headers,
+		storable,
+		shared_storable,
+		must_revalidate,
+		freshness_lifetime_secs,
+		freshness_source,
+		current_age_secs,
+		revalidation: None,
+		notes,
+		recommendations,
+	}
+}
+
+fn add_revalidation_notes(analysis: &mut CacheAnalysis) {
+	match analysis.revalidation {
+		Some(ref result) if result.not_modified => {
+			analysis.notes.push(format!("Conditional request answered with 304 Not Modified in {}ms", result.response_time_ms));
+		}
+		Some(ref result) => {
+			analysis.notes.push(format!("Conditional request returned {} instead of 304", result.status));
+			if !result.etag_changed && result.status == 200 {
+				analysis.recommendations.push("Validators are sent but ignored: the server should answer If-None-Match/If-Modified-Since with 304".to_string());
+			}
+		}
+		None => {
+			analysis.notes.push("No validators present, revalidation not possible".to_string());
+		}
+	}
+}
+
+fn is_static_asset(url: &str) -> bool {
+	const EXTENSIONS: [&str; 12] = [".js", ".css", ".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp", ".woff", ".woff2", ".ico", ".mp4"];
+	let path = url.split(['?', '#']).next().unwrap_or(url).to_lowercase();
+	EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+}
+
+pub fn format_duration(secs: u64) -> String {
+	match secs {
+		0..=59 => format!("{}s", secs),
+		60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+		3600..=86_399 => format!("{}h {}m", secs / 3600, (secs % 3600) / 60),
+		_ => format!("{}d {}h", secs / 86_400, (secs % 86_400) / 3600),
+	}
+}
+
+pub fn print_analysis(analysis: &CacheAnalysis) {
+	let headers = &analysis.headers;
+	println!("=== Cache Analysis: {} ===", analysis.url);
+	println!("Status: {}", analysis.status);
+
+	let cache_control = if headers.cache_control.is_empty() {
+		"-".to_string()
+	} else {
+		let mut directives: Vec<String> = headers.cache_control.iter()
+			.map(|(k, v)| v.as_ref().map_or_else(|| k.clone(), |v| format!("{}={}", k, v)))
+			.collect();
+		directives.sort();
+		directives.join(", ")
+	};
+	println!("Cache-Control: {}", cache_control);
+	println!("ETag: {}", headers.etag.as_deref().unwrap_or("-"));
+	println!("Last-Modified: {}", headers.last_modified.as_deref().unwrap_or("-"));
+	println!("Expires: {}", headers.expires.as_deref().unwrap_or("-"));
+	println!("Vary: {}", if headers.vary.is_empty() { "-".to_string() } else { headers.vary.join(", ") });
+
+	println!();
+	println!("{} Browser cache: {}", if analysis.storable { "✅" } else { "❌" },
+		if analysis.storable { "cacheable" } else { "not cacheable" });
+	println!("{} Shared cache (CDN/proxy): {}", if analysis.shared_storable { "✅" } else { "❌" },
+		if analysis.shared_storable { "cacheable" } else { "not cacheable" });
+
+	match analysis.freshness_lifetime_secs {
+		Some(lifetime) if analysis.storable => {
+			println!("⏱️  Fresh for {} ({:?}), current age {}",
+				format_duration(lifetime), analysis.freshness_source, format_duration(analysis.current_age_secs));
+			if analysis.must_revalidate {
+				println!("🔁 Must be revalidated with the origin before reuse");
+			}
+		}
+		_ => println!("⏱️  No freshness lifetime"),
+	}
+
+	if let Some(ref result) = analysis.revalidation {
+		println!("🔁 Revalidation ({}): {} {}",
+			result.sent_headers.join(", "),
+			result.status,
+			if result.not_modified { "Not Modified ✅" } else { "⚠️" });
+	}
+
+	if !analysis.notes.is_empty() {
+		println!("\nNotes:");
+		for note in &analysis.notes {
+			println!("  • {}", note);
+		}
+	}
+	if !analysis.recommendations.is_empty() {
+		println!("\nRecommendations:");
+		for recommendation in &analysis.recommendations {
+			println!("  • {}", recommendation);
+		}
+	}
+	println!();
+}
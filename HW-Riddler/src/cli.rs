@@ -1,5 +1,14 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub use crate::browser_cookies::Browser;
+pub use crate::export::ExportFormat;
+pub use crate::flows::FlowSort;
+pub use crate::hosts::HostSort;
+pub use crate::http_client::HttpVersion;
+use crate::auth::Auth;
+use crate::http_client::{ProxySettings, ResolveOverride, RetryPolicy};
 
 #[derive(Parser)]
 #[command(name = "riddler")]
@@ -22,6 +31,44 @@ pub struct Cli {
 
 	#[arg(long, help = "Show verbose network traffic (all packets)")]
 	pub verbose_network: bool,
+
+	#[arg(long, value_name = "PATH", env = "RIDDLER_CONFIG", global = true,
+		help = "TOML config file (default: ./riddler.toml if present)")]
+	pub config: Option<PathBuf>,
+
+	#[arg(long = "watch-cookie-domain", value_name = "DOMAIN", env = "RIDDLER_WATCH_COOKIE_DOMAINS",
+		value_delimiter = ',', global = true,
+		help = "Warn at startup when cookies for this domain expire within 24h (repeatable or comma-separated)")]
+	pub watch_cookie_domains: Vec<String>,
+
+	#[arg(long, global = true,
+		help = "Write secrets (Authorization, cookies, password/token fields, card numbers) to the request log unmasked")]
+	pub no_redact: bool,
+
+	#[arg(long, global = true,
+		help = "Print the result of request, logs, cookie list, analyze, replay and interfaces as JSON on stdout; \
+				human-readable text goes to stderr")]
+	pub json: bool,
+
+	#[arg(long, global = true,
+		help = "Keep gzip/deflate/br response bodies as received instead of decoding them for logs, previews and diffs")]
+	pub raw_body: bool,
+
+	#[arg(long, value_name = "URL", global = true,
+		help = "Export request spans (DNS, TTFB, download) to this OTLP/HTTP traces endpoint, e.g. http://localhost:4318/v1/traces")]
+	pub otlp_endpoint: Option<String>,
+
+	#[arg(long, value_name = "FILE", global = true,
+		help = "Rhai script whose on_request/on_response functions rewrite or abort requests sent by Riddler and exchanges through proxy --mitm")]
+	pub script: Option<PathBuf>,
+
+	#[arg(long, value_name = "NAME", env = "RIDDLER_SESSION", global = true,
+		help = "Use the cookie jar and default headers of this named session instead of the shared cookie file")]
+	pub session: Option<String>,
+
+	#[arg(long, value_name = "PATH", env = "RIDDLER_ENV_FILE", global = true,
+		help = "Resolve {{NAME}} placeholders in requests and collections from this NAME=VALUE file (e.g. staging.env)")]
+	pub env_file: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -30,37 +77,136 @@ pub enum Commands {
 						Requires administrator privileges. Supports BPF filters for packet filtering. \
 						Use --replay to enable automatic request replay functionality.")]
 	Monitor {
-		#[arg(short, long, default_value = "en0", help = "Network interface for packet monitoring")]
-		interface: String,
+		#[arg(short, long,
+			help = "Network interface for packet monitoring; repeat to capture on several at once, or use 'any' on Linux \
+					[default: network.interface from config]")]
+		interface: Vec<String>,
 
-		#[arg(short, long, default_value = "tcp port 80 or tcp port 443",
-			help = "BPF filter expression (e.g., 'host example.com', 'tcp port 443')")]
-		filter: String,
+		#[arg(short, long,
+			help = "BPF filter expression (e.g., 'host example.com', 'tcp port 443') [default: network.monitor_filter from config]")]
+		filter: Option<String>,
+
+		#[arg(long, value_name = "PRESET",
+			help = "Named filter: http, https, web, dns, quic, tls, host:NAME, port:N or net:CIDR; repeat to combine with 'or', \
+					narrowed further by --filter")]
+		preset: Vec<String>,
 
 		#[arg(short, long, help = "Automatically replay monitored HTTP requests")]
 		replay: bool,
+
+		#[arg(long, value_name = "PATH", help = "Also write every matched packet to a pcap file (opens in Wireshark)")]
+		save_pcap: Option<PathBuf>,
+
+		#[arg(long, value_name = "MB", requires = "save_pcap",
+			help = "Start a new pcap file (name.1.pcap, name.2.pcap, ...) after this many megabytes")]
+		rotate_size: Option<u64>,
+
+		#[arg(long, value_name = "PATTERN", help = "Only handle requests to this host, '*' allowed (e.g. '*.example.com'); repeatable")]
+		match_host: Vec<String>,
+
+		#[arg(long, value_name = "PATTERN", help = "Only handle requests whose path starts with this, or matches it when it has a '*'; repeatable")]
+		match_path: Vec<String>,
+
+		#[arg(long, value_name = "METHOD", help = "Only handle requests with this method; repeatable")]
+		match_method: Vec<String>,
+
+		#[arg(long, value_name = "NAME[:VALUE]",
+			help = "Only handle requests that have this header, optionally with a value matching the pattern; repeatable")]
+		match_header: Vec<String>,
+
+		#[arg(short, long, value_enum, default_value = "log",
+			help = "Also stream every parsed request and response as NDJSON (one JSON object per line)")]
+		output: crate::ndjson::MonitorOutput,
+
+		#[arg(long, value_name = "TARGET", default_value = "-",
+			help = "Where --output ndjson goes: '-' for stdout, a file to append to, tcp://HOST:PORT or unix:PATH")]
+		output_to: String,
+
+		#[arg(long, value_name = "PATH",
+			help = "MaxMind GeoLite2/GeoIP2 City, Country or ASN database (.mmdb) to annotate requests with the location and AS of the server; repeatable")]
+		geoip_db: Vec<PathBuf>,
+
+		#[arg(long, value_name = "PATH", help = "YAML file of alerting rules checked against every monitored request")]
+		rules: Option<PathBuf>,
+
+		#[arg(long, value_name = "PATH", env = "SSLKEYLOGFILE",
+			help = "TLS key log file (NSS format, written by browsers and curl when SSLKEYLOGFILE is set) \
+					used to decrypt captured TLS 1.2/1.3 sessions and parse the HTTP inside")]
+		tls_keylog: Option<PathBuf>,
+
+		#[arg(long, value_name = "PATH", help = "Read packets from a saved pcap file instead of capturing live")]
+		read_pcap: Option<PathBuf>,
 	},
 
 	#[clap(long_about = "Send HTTP/HTTPS requests with custom methods, headers, and body content. \
 						Supports all standard HTTP methods (GET, POST, PUT, DELETE, PATCH, etc.). \
 						Automatically manages cookies and handles SSL/TLS verification. \
-						Includes timeout protection to prevent hanging requests.")]
+						Includes timeout protection to prevent hanging requests. \
+						URL, headers and body may contain {{env:NAME}}, {{uuid}}, {{timestamp}} \
+						and {{random:int:MIN-MAX}} placeholders.")]
 	Request {
 		#[arg(short, long, default_value = "GET",
 			help = "HTTP method (GET, POST, PUT, DELETE, PATCH, HEAD, OPTIONS)")]
 		method: String,
 
-		#[arg(short, long, help = "Target URL (http:// or https://)")]
+		#[arg(short, long, help = "Target URL (http:// or https://), supports {{...}} placeholders")]
 		url: String,
 
-		#[arg(short = 'H', long, help = "Custom headers (format: 'Name:Value')")]
+		#[arg(short = 'H', long, help = "Custom headers (format: 'Name:Value'), supports {{...}} placeholders")]
 		headers: Vec<String>,
 
-		#[arg(short, long, help = "Request body content")]
+		#[arg(short, long, help = "Request body content, supports {{...}} placeholders; '-' reads the raw body from stdin")]
 		body: Option<String>,
 
-		#[arg(short, long, default_value = "30", help = "Request timeout in seconds")]
-		timeout: u64,
+		#[arg(long, value_name = "PATH", conflicts_with = "body",
+			help = "Send the contents of this file as the body, byte for byte (no placeholder expansion)")]
+		body_file: Option<PathBuf>,
+
+		#[arg(short, long, help = "Request timeout in seconds [default: http.timeout_seconds from config, 30]")]
+		timeout: Option<u64>,
+
+		#[arg(long, help = "Pretty-print JSON response bodies")]
+		json_pretty: bool,
+
+		#[arg(short = 'x', long, value_name = "JSONPATH",
+			help = "Print only the value(s) selected by a JSONPath expression, e.g. '$.data.items[0].id'")]
+		extract: Option<String>,
+
+		#[arg(long, help = "Follow next-page links (Link header or --next-field) and aggregate all pages")]
+		follow_pagination: bool,
+
+		#[arg(long, default_value = "Link", help = "Response header holding RFC 5988 links with rel=\"next\"")]
+		next_link_header: String,
+
+		#[arg(long, value_name = "JSONPATH", help = "JSONPath of the next-page URL in the body, e.g. '$.next' (used when no Link header is present)")]
+		next_field: Option<String>,
+
+		#[arg(long, default_value = "20", help = "Maximum number of pages to fetch with --follow-pagination")]
+		max_pages: usize,
+
+		#[arg(long, help = "Do not follow redirects: stop at the first 3xx response and show its Location")]
+		no_follow: bool,
+
+		#[arg(short, long, value_name = "FILE", conflicts_with_all = ["extract", "follow_pagination", "json_pretty"],
+			help = "Stream the response body to FILE with a progress bar and print its SHA-256")]
+		output: Option<PathBuf>,
+
+		#[arg(long, requires = "output", help = "Continue a partial --output file with a Range request")]
+		resume: bool,
+
+		#[arg(long = "assert", value_name = "CHECK", conflicts_with_all = ["output", "follow_pagination"],
+			help = "Check the response, e.g. 'status=200', 'body contains ok', 'time<1500ms', 'header:NAME exists' or '$.id=5'; \
+				exits non-zero if any check fails (repeatable)")]
+		assertions: Vec<String>,
+
+		#[command(flatten)]
+		auth: AuthArgs,
+
+		#[command(flatten)]
+		retry: RetryArgs,
+
+		#[command(flatten)]
+		connection: ConnectionArgs,
 	},
 
 	#[clap(long_about = "Manage HTTP cookies with persistent JSON storage. \
@@ -81,19 +227,68 @@ pub enum Commands {
 		#[arg(short, long, help = "Filter by source: monitored, manual, or replay")]
 		source: Option<String>,
 
-		#[arg(short, long, help = "Search query to filter logs")]
-		query: Option<String>,
+		#[arg(short, long, help = "Search term, repeatable (all must match): 'text', or 'FIELD~pattern' with FIELD url, method, body, header:NAME, status or response")]
+		query: Vec<String>,
+
+		#[arg(long, help = "Treat -q patterns as regular expressions instead of case-insensitive substrings")]
+		regex: bool,
+
+		#[arg(long, help = "Show one page of matching logs, 1 being the most recent (overrides --limit)")]
+		page: Option<usize>,
+
+		#[arg(long, default_value = "20", help = "Number of logs per page when using --page")]
+		page_size: usize,
+
+		#[arg(long, help = "Browse matching logs in an interactive pager (j/k to move, enter to expand)")]
+		pager: bool,
+
+		#[arg(long, help = "Show only the entry with this id (the #id printed in each log header)")]
+		id: Option<String>,
+
+		#[arg(long, help = "Print entries as runnable curl commands (method, headers, body and cookies)")]
+		as_curl: bool,
+
+		#[arg(short, long, conflicts_with_all = ["page", "pager", "id"],
+			help = "After the last --limit entries, keep printing new entries as they are logged (like tail -f)")]
+		follow: bool,
+
+		#[arg(long, value_name = "N", conflicts_with_all = ["page", "pager", "id", "follow"],
+			help = "Report the N most requested and N slowest endpoints (method + path with ids normalized)")]
+		top: Option<usize>,
+
+		#[arg(long, conflicts_with_all = ["page", "pager", "id", "follow", "top"],
+			help = "Report credentials and secrets (Basic auth, bearer tokens, API keys, passwords) found in logged requests, by severity")]
+		security_report: bool,
 
 		#[arg(long, help = "Show detailed statistics about requests")]
 		stats: bool,
 
 		#[arg(short = 'p', long, help = "Specify custom log file path (overrides config setting)")]
 		path: Option<String>,
+
+		#[arg(long, value_name = "FILE", help = "Write matching logs as an HTTP Archive (HAR 1.2) file for browser dev tools")]
+		export_har: Option<PathBuf>,
+
+		#[arg(long, value_enum, help = "Export matching logs as csv, json (array) or ndjson with columns timestamp, method, url, status, time_ms, source")]
+		format: Option<ExportFormat>,
+
+		#[arg(long, value_name = "FILE", requires = "format", help = "File to write the --format export to (default: stdout)")]
+		output: Option<PathBuf>,
+
+		#[arg(long, value_name = "FILE", conflicts_with_all = ["page", "pager", "id", "follow", "top", "export_har", "format"],
+			help = "Write an OpenAPI 3.0 document (YAML, or JSON for a .json file) inferred from the matching logs: \
+				path templates with numeric/UUID segments as parameters, query parameters, and JSON request/response schemas")]
+		infer_openapi: Option<PathBuf>,
+
+		#[command(subcommand)]
+		action: Option<LogsAction>,
 	},
 
 	#[clap(long_about = "Replay HTTP requests from the request log with customizable repetition and timing. \
 						Supports filtering by request source and batch processing with configurable delays. \
-						Useful for load testing and request pattern analysis.")]
+						Useful for load testing and request pattern analysis. With --every or --cron the process \
+						stays alive and replays the same set on schedule, appending each run's success rate and \
+						latency to storage.replay_history_path and printing the trend: simple synthetic monitoring.")]
 	Replay {
 		#[arg(short, long, default_value = "1", help = "Number of recent requests to replay")]
 		limit: usize,
@@ -109,31 +304,712 @@ pub enum Commands {
 
 		#[arg(short, long, default_value = "sequential", value_enum, help = "Replay mode: 'sequential' (A1->A2->B1->B2) or 'interleaved' (A1->B1->A1->B2)")]
 		mode: ReplayMode,
+
+		#[arg(long, visible_alias = "concurrency", default_value = "1", help = "Maximum number of requests in flight at once")]
+		parallel: usize,
+
+		#[arg(long, conflicts_with = "preserve_timing", help = "Start at most this many requests per second (fractions allowed)")]
+		rps: Option<f64>,
+
+		#[arg(long, value_name = "X", help = "Safety cap on requests started per second, also for --respect-rate-limits")]
+		max_rps: Option<f64>,
+
+		#[arg(long, help = "On 429, or 503 with Retry-After, pause for Retry-After (or an exponential backoff) and halve the rate, \
+			raising it again while responses are not throttled; reports the sustainable rate")]
+		respect_rate_limits: bool,
+
+		#[arg(long, help = "Replay the selected requests in random order")]
+		shuffle: bool,
+
+		#[arg(long, help = "Reproduce the gaps between requests observed in the original capture timestamps")]
+		preserve_timing: bool,
+
+		#[arg(short = 'H', long, help = "Extra header for every replayed request (format: 'Name:Value', supports {{...}} placeholders)")]
+		headers: Vec<String>,
+
+		#[arg(long, value_name = "HOST", help = "Send to this host instead: 'host[:port]' keeps the scheme, 'scheme://host[:port]' replaces it; a logged Host header follows")]
+		rewrite_host: Option<String>,
+
+		#[arg(long, value_name = "NAME:VALUE", help = "Replace a header (case-insensitive) or add it when missing, e.g. a staging auth token")]
+		rewrite_header: Vec<String>,
+
+		#[arg(long, value_name = "NAME", help = "Remove a header (case-insensitive) before sending")]
+		strip_header: Vec<String>,
+
+		#[arg(long, help = "Compare each response with the originally captured one (status, headers, JSON-aware body) and flag regressions")]
+		diff: bool,
+
+		#[arg(long, value_name = "FILE", conflicts_with_all = ["limit", "source"],
+			help = "Replay every entry of a HAR file (e.g. saved from browser dev tools) instead of the request log")]
+		har: Option<PathBuf>,
+
+		#[arg(long, value_name = "INTERVAL", group = "schedule",
+			help = "Keep running and replay the selected set again at this interval (e.g. 30s, 5m, 1h), starting now")]
+		every: Option<String>,
+
+		#[arg(long, value_name = "EXPR", group = "schedule",
+			help = "Keep running and replay the selected set on this cron schedule in local time, e.g. '*/5 * * * *'")]
+		cron: Option<String>,
+
+		#[arg(long, value_name = "N", requires = "schedule", help = "Stop after N scheduled runs [default: until interrupted]")]
+		runs: Option<usize>,
+	},
+
+	#[clap(long_about = "Fuzz recently logged requests: each is sent once unmodified as a baseline, then \
+						with one header value, query parameter or JSON body field at a time replaced by values \
+						from the chosen generators (and --payloads). Responses that deviate from the baseline are \
+						reported: 5xx statuses, timeouts, failed connections, and bodies whose size differs by more \
+						than --size-threshold while the status stays the same. Mutated sends are not logged. \
+						Only fuzz services you are allowed to test; --rewrite-host points the requests at staging.")]
+	Fuzz {
+		#[arg(long, value_name = "N", default_value = "1", help = "Number of recent logged requests to fuzz")]
+		from_log: usize,
+
+		#[arg(short, long, help = "Filter by source: monitored, proxy, manual or replay")]
+		source: Option<String>,
+
+		#[arg(short, long = "generator", value_enum, value_delimiter = ',',
+			default_value = "empty,long,boundary,special,types,unicode",
+			help = "Value generators to use (comma-separated)")]
+		generators: Vec<crate::fuzz::FuzzGenerator>,
+
+		#[arg(short, long = "target", value_enum, value_delimiter = ',', default_value = "header,query,body",
+			help = "Request parts to mutate (comma-separated)")]
+		targets: Vec<crate::fuzz::FuzzTarget>,
+
+		#[arg(long, value_name = "FILE", help = "Also try every non-empty line of this file as a value")]
+		payloads: Option<PathBuf>,
+
+		#[arg(long, value_name = "NAME", value_delimiter = ',', help = "Never mutate this header, e.g. Authorization")]
+		skip_header: Vec<String>,
+
+		#[arg(long, default_value = "300", help = "Maximum number of mutations per request")]
+		max_mutations: usize,
+
+		#[arg(long, visible_alias = "parallel", default_value = "4", help = "Maximum number of requests in flight at once")]
+		concurrency: usize,
+
+		#[arg(long, help = "Start at most this many requests per second (fractions allowed)")]
+		rps: Option<f64>,
+
+		#[arg(long, default_value = "10", help = "Seconds after which a request counts as timed out")]
+		timeout: u64,
+
+		#[arg(long, default_value = "0.5", help = "Report responses whose body size differs from the baseline by more than this fraction")]
+		size_threshold: f64,
+
+		#[arg(long, value_name = "HOST", help = "Send to this host instead: 'host[:port]' keeps the scheme, 'scheme://host[:port]' replaces it")]
+		rewrite_host: Option<String>,
+
+		#[arg(long, help = "List the mutations that would be sent without sending anything")]
+		dry_run: bool,
+
+		#[arg(long, help = "Exit with an error when any anomaly was found, e.g. in CI")]
+		fail_on_findings: bool,
+	},
+
+	#[clap(long_about = "Aggregate the request log into per-destination-host statistics: request count, \
+						error rate, mean/p95 latency, bytes in/out and first/last seen. \
+						With --interface, live traffic captured for --duration seconds is merged in. \
+						With --session, show the counters (requests, methods, status codes, bytes, mean latency) \
+						a monitor or proxy run kept per host as the traffic arrived.")]
+	Hosts {
+		#[arg(short, long, help = "Filter by source: monitored, manual, or replay")]
+		source: Option<String>,
+
+		#[arg(long, default_value = "requests", value_enum, help = "Sort hosts by: requests, errors, latency (p95) or bytes")]
+		sort: HostSort,
+
+		#[arg(short = 'n', long, help = "Only show the first N hosts")]
+		top: Option<usize>,
+
+		#[arg(short, long, help = "Also capture live traffic on this interface (requires root)")]
+		interface: Option<String>,
+
+		#[arg(short, long, default_value = "10", help = "Seconds of live capture when --interface is given")]
+		duration: u64,
+
+		#[arg(short = 'p', long, help = "Specify custom log file path (overrides config setting)")]
+		path: Option<String>,
+
+		#[arg(long, value_name = "ID", num_args = 0..=1, default_missing_value = "latest",
+			conflicts_with_all = ["source", "interface", "path"],
+			help = "Show the per-host counters of the latest monitor/proxy run, or of the session with this ID, \
+				instead of reading the log; a running session is updated every 2s")]
+		session: Option<String>,
+
+		#[arg(long, conflicts_with = "session", help = "List the recorded monitor/proxy sessions (storage.sessions_path)")]
+		sessions: bool,
+	},
+
+	#[clap(long_about = "Capture traffic and keep a table of TCP connections and UDP flows keyed by \
+						protocol, addresses and ports, with packet and byte counters, connection state and duration. \
+						The busiest flows and the addresses sending and receiving the most are redrawn every --refresh seconds. \
+						Requires administrator privileges.")]
+	Flows {
+		#[arg(short, long,
+			help = "Network interface to capture on; repeat for several, or use 'any' on Linux [default: network.interface from config]")]
+		interface: Vec<String>,
+
+		#[arg(short, long, help = "BPF filter expression [default: network.monitor_filter from config]")]
+		filter: Option<String>,
+
+		#[arg(long, value_name = "PRESET",
+			help = "Named filter: http, https, web, dns, quic, tls, host:NAME, port:N or net:CIDR; repeat to combine with 'or', \
+					narrowed further by --filter")]
+		preset: Vec<String>,
+
+		#[arg(short, long, default_value = "0", help = "Seconds to capture, 0 until Ctrl+C")]
+		duration: u64,
+
+		#[arg(short, long, default_value = "2", help = "Seconds between table redraws")]
+		refresh: u64,
+
+		#[arg(long, default_value = "bytes", value_enum, help = "Sort flows by: bytes, packets, duration or recent")]
+		sort: FlowSort,
+
+		#[arg(short = 'n', long, default_value = "20", help = "Number of flows and top talkers shown")]
+		top: usize,
+
+		#[arg(long, default_value = "60", help = "Forget flows without packets for this many seconds")]
+		idle_timeout: u64,
+	},
+
+	#[clap(long_about = "Inspect Cache-Control, ETag, Last-Modified, Expires and Vary headers of a URL, \
+						send a conditional revalidation request and report whether the response is cacheable, \
+						for how long, and what could be improved. With --from-logs, analyses responses already in the request log.")]
+	AnalyzeCache {
+		#[arg(short, long, required_unless_present = "from_logs", help = "URL to analyze")]
+		url: Option<String>,
+
+		#[arg(long, value_name = "N", conflicts_with = "url", help = "Analyze the N most recent logged responses (one per URL)")]
+		from_logs: Option<usize>,
+
+		#[arg(long, help = "Skip the conditional If-None-Match/If-Modified-Since request")]
+		no_revalidate: bool,
+
+		#[arg(short = 'p', long, help = "Specify custom log file path (overrides config setting)")]
+		path: Option<String>,
+	},
+
+	#[clap(long_about = "Compare two responses: fetch --url-a and --url-b with the same method, headers and body, \
+						or load two logged entries with --log-id A --log-id B. Prints the differences in status, \
+						headers (ignoring volatile ones such as Date and ETag) and body, JSON bodies compared \
+						structurally by path regardless of key order and whitespace. \
+						Handy when validating a migration between API versions.")]
+	Diff {
+		#[arg(long, value_name = "URL", requires = "url_b", conflicts_with = "log_id", help = "First URL to fetch, supports {{...}} placeholders")]
+		url_a: Option<String>,
+
+		#[arg(long, value_name = "URL", requires = "url_a", help = "Second URL to fetch, supports {{...}} placeholders")]
+		url_b: Option<String>,
+
+		#[arg(long, value_name = "ID", required_unless_present = "url_a",
+			help = "Id (or unique prefix) of a logged entry whose response is compared; give it twice")]
+		log_id: Vec<String>,
+
+		#[arg(short, long, default_value = "GET", help = "HTTP method for both URLs")]
+		method: String,
+
+		#[arg(short = 'H', long, help = "Header for both URLs (format: 'Name:Value'), supports {{...}} placeholders")]
+		headers: Vec<String>,
+
+		#[arg(short, long, help = "Request body for both URLs, supports {{...}} placeholders")]
+		body: Option<String>,
+
+		#[arg(long, value_name = "NAME", value_delimiter = ',', help = "Also ignore this response header, e.g. X-Api-Version")]
+		ignore_header: Vec<String>,
+
+		#[arg(long, value_name = "PATH", value_delimiter = ',',
+			help = "Ignore this JSON body path and everything below it, e.g. '$.meta' or '$.items[0].updated_at'")]
+		ignore_path: Vec<String>,
+
+		#[arg(short = 'p', long, help = "Specify custom log file path for --log-id (overrides config setting)")]
+		path: Option<String>,
+
+		#[arg(long, help = "Exit with an error when the responses differ, e.g. in CI")]
+		fail_on_diff: bool,
+	},
+
+	#[clap(long_about = "Connect to a TLS server and print the full certificate chain (subject, issuer, SANs, \
+						validity, key type), the negotiated protocol, cipher suite and ALPN, OCSP stapling status, \
+						TCP connect and TLS handshake times and days until expiry. \
+						Exits with an error if --fail-within-days is reached.")]
+	#[command(visible_alias = "tls")]
+	TlsInfo {
+		#[arg(required_unless_present = "url", help = "Target as https://host[:port] or host[:port]")]
+		target: Option<String>,
+
+		#[arg(short, long, conflicts_with = "target", help = "Target URL, as an alternative to the positional TARGET")]
+		url: Option<String>,
+
+		#[arg(long, value_name = "DAYS", help = "Fail with a non-zero exit code if the leaf certificate expires within DAYS")]
+		fail_within_days: Option<i64>,
+
+		#[arg(short, long, default_value = "10", help = "Connection and handshake timeout in seconds")]
+		timeout: u64,
+	},
+
+	#[clap(long_about = "Convert a Postman v2.1 collection export (requests, headers, variables, auth and folders) \
+						into a riddler collection file that can be executed with 'riddler run'.")]
+	Import {
+		#[arg(help = "Postman collection JSON file (v2.1)")]
+		file: PathBuf,
+
+		#[arg(short, long, help = "Output collection path (default: <input>.riddler.json)")]
+		output: Option<PathBuf>,
+
+		#[arg(long, help = "Run the imported collection immediately")]
+		run: bool,
+	},
+
+	#[clap(long_about = "Execute the requests of a riddler collection file in order. \
+						Collection variables are substituted as {{name}}, every response is logged, \
+						and the exit code is non-zero if any request fails.")]
+	Run {
+		#[arg(help = "Collection file (JSON, or YAML for .yaml/.yml), e.g. created by 'riddler import'")]
+		file: PathBuf,
+
+		#[arg(short, long, help = "Only run requests in this folder (e.g. 'Users/Admin')")]
+		folder: Option<String>,
+
+		#[arg(long = "var", value_name = "NAME=VALUE", help = "Override a collection variable")]
+		vars: Vec<String>,
+
+		#[arg(short, long, default_value = "0", help = "Delay between requests (ms)")]
+		delay: u64,
+
+		#[arg(short, long, help = "Request timeout in seconds [default: http.timeout_seconds from config, 30]")]
+		timeout: Option<u64>,
+	},
+
+	#[clap(long_about = "Measure how long each resolver takes to answer A, AAAA and other record queries \
+						for a name, next to the operating system's own lookup, and point out slow resolvers. \
+						Without --server the nameservers from /etc/resolv.conf are queried.")]
+	Dns {
+		#[arg(help = "Domain name to resolve")]
+		name: String,
+
+		#[arg(short, long = "server", value_name = "IP[:PORT]", help = "Resolver to query; repeat to compare several")]
+		servers: Vec<String>,
+
+		#[arg(short = 't', long = "type", value_enum, value_delimiter = ',', default_value = "a,aaaa",
+			help = "Record types to query, comma separated")]
+		types: Vec<crate::dns::RecordType>,
+
+		#[arg(short = 'n', long, default_value = "3", help = "Queries per resolver and record type")]
+		count: u32,
+
+		#[arg(long, default_value = "5", help = "Timeout per query in seconds")]
+		timeout: u64,
+	},
+
+	#[clap(long_about = "List the network interfaces available for capture with their addresses, \
+						up/running/loopback/wireless flags and data link type, to pick a value for --interface. \
+						Link types are only shown for devices that can be opened, which usually requires administrator privileges.")]
+	Interfaces,
+
+	#[clap(long_about = "Check the runtime environment: pcap availability and capture privileges, \
+						interface and BPF filter validity, writability of the log and cookie files, \
+						proxy port availability and DNS/connectivity. Prints a fix for every problem found.")]
+	Doctor {
+		#[arg(short, long, help = "Interface to check instead of the configured default")]
+		interface: Option<String>,
+	},
+
+	#[clap(long_about = "Create or inspect the TOML config file. Settings for the interface, BPF filter, \
+						storage paths, proxy and request timeout are read from --config, or from \
+						./riddler.toml when present; command-line options still take precedence.")]
+	Config {
+		#[command(subcommand)]
+		action: ConfigAction,
 	},
 
 	#[clap(long_about = "Launch an HTTP/HTTPS proxy server that intercepts and logs traffic. \
-						Supports both HTTP requests and HTTPS CONNECT tunneling. \
-						All proxied requests are automatically logged for later analysis.")]
+						Plain HTTP requests are forwarded and logged with source 'proxy', and HTTPS is \
+						tunneled with CONNECT. With --mitm, HTTPS tunnels are decrypted and every request/response pair \
+						is logged with source 'proxy'; clients must trust the generated CA certificate. \
+						With --transparent, connections redirected by the firewall are served instead.")]
 	Proxy {
-		#[arg(short, long, default_value = "127.0.0.1", help = "Bind address (0.0.0.0 for all interfaces)")]
-		address: String,
+		#[arg(short, long, help = "Bind address (0.0.0.0 for all interfaces) [default: proxy.bind_address from config, 127.0.0.1]")]
+		address: Option<String>,
+
+		#[arg(short, long, help = "Port number for proxy server [default: proxy.bind_port from config, 8080]")]
+		port: Option<u16>,
+
+		#[arg(long, help = "Decrypt HTTPS: terminate CONNECT tunnels with certificates signed by a local CA \
+			(proxy.ca_cert_path, generated on first use) and log the decrypted traffic")]
+		mitm: bool,
+
+		#[arg(long, conflicts_with_all = ["serve_pac", "system_proxy"],
+			help = "Serve connections redirected to the proxy by iptables/nftables REDIRECT (Linux) or pf rdr (macOS/BSD), \
+				recovering their original destination: plain HTTP is forwarded and logged, TLS is decrypted with --mitm \
+				and relayed untouched otherwise. For apps that ignore proxy settings; needs root")]
+		transparent: bool,
+
+		#[arg(long, value_name = "FILE",
+			help = "YAML file of canned responses (status, headers, body, latency) for requests matching a method, \
+				host and path glob, returned without contacting the origin; HTTPS needs --mitm")]
+		mock: Option<PathBuf>,
+
+		#[arg(long, value_name = "FILE",
+			help = "YAML file of rules that set, remove or regex-replace request/response headers, rewrite URLs \
+				(e.g. send a host to localhost) and map status codes for matching requests")]
+		rewrite: Option<PathBuf>,
+
+		#[arg(long, value_name = "LATENCY", help = "Hold back every server-to-client flight by this long, adding it to each round trip (e.g. 300ms)")]
+		delay: Option<String>,
 
-		#[arg(short, long, default_value = "8080", help = "Port number for proxy server")]
-		port: u16,
+		#[arg(long, value_name = "LATENCY", help = "Vary --delay by up to this much either way (e.g. 100ms)")]
+		jitter: Option<String>,
+
+		#[arg(long, value_name = "RATE", help = "Limit each direction of every connection to this bandwidth (e.g. 512kbps, 2mbps, 64KB/s)")]
+		throttle: Option<String>,
+
+		#[arg(long, value_name = "PERCENT", help = "Close this percentage of connections as soon as they are accepted (e.g. 1%)")]
+		drop_rate: Option<String>,
+
+		#[arg(long, value_name = "N", default_value = "0", help = "Seed for --jitter and --drop-rate, so a degraded run can be repeated")]
+		seed: u64,
+
+		#[arg(long, value_name = "CIDR", value_delimiter = ',',
+			help = "Only serve clients from these addresses or networks (e.g. 192.168.1.0/24); added to proxy.allow_clients")]
+		allow_client: Vec<String>,
+
+		#[arg(long, value_name = "CIDR", value_delimiter = ',', help = "Refuse clients from these addresses or networks; added to proxy.deny_clients")]
+		deny_client: Vec<String>,
+
+		#[arg(long, value_name = "GLOB", value_delimiter = ',',
+			help = "Only connect to destination hosts matching these globs (e.g. *.example.com); added to proxy.allow_hosts")]
+		allow_host: Vec<String>,
+
+		#[arg(long, value_name = "GLOB", value_delimiter = ',',
+			help = "Refuse destination hosts matching these globs, CONNECT targets included (e.g. *.doubleclick.net); added to proxy.block_hosts")]
+		block_host: Vec<String>,
+
+		#[arg(long, value_name = "USER:PASSWORD", env = "RIDDLER_PROXY_AUTH",
+			help = "Require these credentials as Basic Proxy-Authorization, answering 407 otherwise [default: proxy.auth from config]")]
+		auth: Option<String>,
+
+		#[arg(long,
+			help = "Cache responses in proxy.cache_dir following Cache-Control, Expires and Vary, revalidate stale ones \
+				with ETag/Last-Modified and answer conditional requests; HTTPS needs --mitm. See the cache command")]
+		cache: bool,
+
+		#[arg(long, help = "Also answer GET /proxy.pac with a proxy auto-config file pointing at this proxy \
+			(only hosts of proxy.allow_hosts when set)")]
+		serve_pac: bool,
+
+		#[arg(long, help = "Point the OS proxy settings at this proxy while it runs (as enable-system) \
+			and restore them on exit; with --serve-pac the PAC URL is configured instead")]
+		system_proxy: bool,
+
+		#[command(subcommand)]
+		action: Option<ProxyAction>,
+	},
+
+	#[clap(long_about = "Inspect or empty the response cache of 'proxy --cache' in proxy.cache_dir. \
+						stats shows stored entries, how many are still fresh, hits, revalidations and misses, \
+						and why responses could not be cached.")]
+	Cache {
+		#[command(subcommand)]
+		action: CacheAction,
 	},
 
 	#[clap(long_about = "Comprehensive performance analysis tool for HTTP requests with intelligent diagnostics. \
 						Provides detailed bottleneck analysis, performance classification, and optimization recommendations. \
 						Generates both console output and optional JSON reports.")]
 	Analyze {
-		#[arg(short, long, help = "URL to analyze for performance issues")]
-		url: String,
+		#[arg(short, long, required_unless_present = "url_file",
+			help = "URL to analyze for performance issues; repeat to compare several targets")]
+		url: Vec<String>,
+
+		#[arg(long, value_name = "PATH", help = "Also analyze the URLs listed in this file, one per line ('#' starts a comment)")]
+		url_file: Option<PathBuf>,
 
 		#[arg(short, long, default_value = "5", help = "Number of test iterations (more = better accuracy)")]
 		iterations: u32,
 
 		#[arg(short, long, help = "Generate detailed JSON report file")]
 		report: bool,
+
+		#[arg(long, value_name = "DURATION",
+			help = "Load test instead: send requests back to back for this long (e.g. 30s, 5m) and report RPS, errors and latency")]
+		duration: Option<String>,
+
+		#[arg(short, long, default_value = "1", requires = "duration", help = "Number of parallel workers during a --duration load test")]
+		concurrency: usize,
+
+		#[arg(long, value_name = "N", default_value = "0", help = "Send N unmeasured requests first to warm up DNS, connections and caches")]
+		warmup: u32,
+
+		#[arg(long, help = "Open a new connection for every request instead of reusing pooled ones")]
+		fresh_connections: bool,
+
+		#[arg(long, conflicts_with_all = ["duration", "fresh_connections"],
+			help = "Also run the iterations on fresh connections and report how much of the latency is connection setup")]
+		cold_vs_warm: bool,
+
+		#[arg(long, conflicts_with = "duration",
+			help = "Trace the network route (needs root or CAP_NET_RAW) when responses are critically slow, \
+				to tell network-path problems from server problems")]
+		traceroute: bool,
+
+		#[arg(long, conflicts_with_all = ["duration", "cold_vs_warm", "save_baseline", "compare"],
+			help = "Keep probing the targets at --interval and alert when the SLA is breached, until Ctrl+C")]
+		watch: bool,
+
+		#[arg(long, value_name = "DURATION", default_value = "60s", requires = "watch", help = "Time between probes in --watch mode (e.g. 30s, 5m)")]
+		interval: String,
+
+		#[arg(long, value_name = "N", default_value = "20", requires = "watch", help = "Number of most recent probes the SLA is evaluated over")]
+		window: usize,
+
+		#[arg(long, value_name = "LATENCY", requires = "watch", help = "Alert when the p95 latency of the window exceeds this (e.g. 800ms, 1.5s)")]
+		sla_p95: Option<String>,
+
+		#[arg(long, value_name = "PERCENT", requires = "watch", help = "Alert when more than this percentage of the window failed")]
+		sla_error_rate: Option<f64>,
+
+		#[arg(long, value_name = "URL", requires = "watch", help = "POST a JSON alert to this URL when the SLA is breached or recovers, next to the [notifications] webhooks")]
+		webhook: Option<String>,
+
+		#[arg(long, requires = "watch", help = "Also show alerts as desktop notifications, even if [notifications] desktop is off")]
+		notify: bool,
+
+		#[arg(long, value_name = "PATH", help = "Save the p50/p95/p99 latency of every target to this baseline file")]
+		save_baseline: Option<PathBuf>,
+
+		#[arg(long, value_name = "PATH",
+			help = "Compare latency percentiles with this baseline and exit non-zero if any regressed beyond --regression-threshold")]
+		compare: Option<PathBuf>,
+
+		#[arg(long, value_name = "PERCENT", default_value = "10", requires = "compare",
+			help = "Allowed slowdown of a percentile against the baseline, in percent")]
+		regression_threshold: f64,
+
+		#[command(flatten)]
+		connection: ConnectionArgs,
+	},
+
+	#[clap(long_about = "Measure the maximum throughput and the latency distribution of one endpoint under keep-alive \
+						load, like wrk: every connection sends the same request back to back for --duration and the \
+						report gives requests/s, transfer rate, per-second throughput and latency percentiles. \
+						Unlike analyze, nothing is logged and no per-request diagnostics are collected, so the client \
+						adds as little as possible to what is measured. With HTTP/2 the connections share one \
+						TCP connection as concurrent streams.")]
+	Bench {
+		#[arg(short, long, help = "URL to benchmark, supports {{...}} placeholders")]
+		url: String,
+
+		#[arg(short, long, default_value = "10", help = "Number of keep-alive connections sending requests concurrently")]
+		connections: usize,
+
+		#[arg(short, long, value_name = "DURATION", default_value = "10s", help = "How long to measure (e.g. 30s, 2m)")]
+		duration: String,
+
+		#[arg(long, value_name = "DURATION",
+			help = "Send unmeasured requests for this long first, to open the connections and warm up caches")]
+		warmup: Option<String>,
+
+		#[arg(short, long, default_value = "GET", help = "HTTP method")]
+		method: String,
+
+		#[arg(short = 'H', long, help = "Request header (format: 'Name:Value'), supports {{...}} placeholders")]
+		headers: Vec<String>,
+
+		#[arg(short, long, help = "Request body, supports {{...}} placeholders")]
+		body: Option<String>,
+
+		#[arg(long, value_name = "SECONDS", default_value = "10", help = "Count a request as failed when it takes longer than this")]
+		timeout: u64,
+
+		#[command(flatten)]
+		connection: ConnectionArgs,
+	},
+}
+
+#[derive(Args)]
+pub struct AuthArgs {
+	#[arg(long, value_name = "USER:PASS", help = "Authenticate with HTTP Basic auth (or Digest with --digest)")]
+	pub auth: Option<String>,
+
+	#[arg(long, requires = "auth", help = "Use Digest auth for --auth: answer the server's 401 challenge (MD5 or SHA-256)")]
+	pub digest: bool,
+
+	#[arg(long, value_name = "TOKEN", conflicts_with = "auth", help = "Send 'Authorization: Bearer TOKEN'")]
+	pub bearer: Option<String>,
+}
+
+impl AuthArgs {
+	pub fn auth(&self) -> anyhow::Result<Option<Auth>> {
+		if let Some(token) = &self.bearer {
+			return Ok(Some(Auth::Bearer { token: token.clone() }));
+		}
+		self.auth.as_deref().map(|credentials| Auth::parse_credentials(credentials, self.digest)).transpose()
+	}
+}
+
+#[derive(Args)]
+pub struct RetryArgs {
+	#[arg(long, value_name = "N", default_value = "0",
+		help = "Retry up to N more times on connection errors, timeouts and --retry-on statuses")]
+	pub retry: u32,
+
+	#[arg(long, value_name = "MS", default_value = "500", help = "Delay before the first retry, doubled for each further one")]
+	pub retry_backoff: u64,
+
+	#[arg(long, value_name = "MS", default_value = "30000", help = "Longest single delay between retries, also caps Retry-After")]
+	pub retry_max_backoff: u64,
+
+	#[arg(long, value_name = "STATUSES", value_delimiter = ',', default_value = "429,502,503,504",
+		help = "Comma-separated response statuses worth retrying")]
+	pub retry_on: Vec<u16>,
+
+	#[arg(long, help = "Ignore Retry-After headers and always use the backoff delay")]
+	pub no_retry_after: bool,
+}
+
+impl RetryArgs {
+	pub fn policy(&self) -> RetryPolicy {
+		RetryPolicy {
+			max_attempts: self.retry.saturating_add(1),
+			backoff_base_ms: self.retry_backoff,
+			max_backoff_ms: self.retry_max_backoff,
+			retry_on: self.retry_on.clone(),
+			honor_retry_after: !self.no_retry_after,
+		}
+	}
+}
+
+/// How requests reach the server: upstream proxy and TLS trust
+#[derive(Args)]
+pub struct ConnectionArgs {
+	#[arg(long, value_name = "URL",
+		help = "Send requests through this proxy: http://, https://, socks5:// or socks5h:// (remote DNS), optionally with user:pass@")]
+	pub proxy: Option<String>,
+
+	#[arg(long, value_name = "USER:PASS", requires = "proxy", help = "Proxy credentials (override any in the --proxy URL)")]
+	pub proxy_user: Option<String>,
+
+	#[arg(long, value_name = "HOSTS", requires = "proxy",
+		help = "Comma-separated hosts, domains or CIDRs that bypass --proxy [default: NO_PROXY]")]
+	pub no_proxy: Option<String>,
+
+	#[arg(long, value_name = "PATH", help = "Also trust the root CA certificate(s) in this PEM or DER file")]
+	pub cacert: Option<PathBuf>,
+
+	#[arg(short = 'k', long, help = "Do not verify TLS certificates (self-signed or expired certificates are accepted)")]
+	pub insecure: bool,
+
+	#[arg(long, value_enum, default_value = "auto",
+		help = "HTTP version: auto (HTTP/2 if the server offers it), 1.1, 2 (prior knowledge) or 3 (QUIC, needs the http3 build)")]
+	pub http_version: HttpVersion,
+
+	#[arg(long, value_name = "HOST:PORT:IP",
+		help = "Connect to IP for HOST instead of resolving it, like curl --resolve (repeatable)")]
+	pub resolve: Vec<String>,
+}
+
+impl ConnectionArgs {
+	pub fn proxy_settings(&self) -> Option<ProxySettings> {
+		self.proxy.clone().map(|url| ProxySettings {
+			url,
+			credentials: self.proxy_user.clone(),
+			no_proxy: self.no_proxy.clone(),
+		})
+	}
+
+	pub fn resolve_overrides(&self) -> anyhow::Result<Vec<ResolveOverride>> {
+		self.resolve.iter().map(|value| ResolveOverride::parse(value)).collect()
+	}
+}
+
+#[derive(Subcommand)]
+pub enum LogsAction {
+	#[clap(long_about = "Export matching requests as a Gantt-style timeline showing start time, \
+						duration and status color, so bursts and slow outliers stand out. \
+						Writes SVG, or an HTML page with a legend when the output ends in .html. \
+						The --source, --query and --path options of 'logs' apply, e.g. \
+						'riddler logs -s replay timeline --since 30m'.")]
+	Timeline {
+		#[arg(long, default_value = "1h", help = "Only include requests from this recent window (e.g. 30s, 15m, 1h, 2d)")]
+		since: String,
+
+		#[arg(short, long, default_value = "timeline.svg", help = "Output file (.svg or .html)")]
+		output: PathBuf,
+	},
+
+	#[clap(long_about = "Export the chronological request/response flow between clients and hosts as a \
+						Mermaid or PlantUML sequence diagram, e.g. to document how a mobile app talks to its \
+						backends. Clients are the addresses seen by the monitor or proxy, or Riddler itself for \
+						'request' and 'replay'. The format follows the output extension (.puml/.plantuml for \
+						PlantUML, Mermaid otherwise; .md wraps it in a code fence) unless --format is given. \
+						The --source, --query and --path options of 'logs' apply.")]
+	Sequence {
+		#[arg(long, default_value = "1h", help = "Only include requests from this recent window (e.g. 30s, 15m, 1h, 2d)")]
+		since: String,
+
+		#[arg(short, long, default_value = "sequence.mmd", help = "Output file (.mmd, .md, .puml)")]
+		output: PathBuf,
+
+		#[arg(long, value_enum, help = "Diagram syntax [default: from the output extension]")]
+		format: Option<crate::sequence::SequenceFormat>,
+
+		#[arg(short = 'n', long, default_value = "200", help = "Keep only the N most recent requests")]
+		limit: usize,
+	},
+
+	#[clap(long_about = "Compact the request log, including rotated files, by dropping entries older than \
+						--older-than and/or all but the --keep-last most recent ones. Unparseable lines are dropped too. \
+						Remaining entries keep their ids. Use it to stop long-running monitors from filling the disk.")]
+	Prune {
+		#[arg(long, value_name = "AGE", required_unless_present = "keep_last",
+			help = "Remove entries older than this (e.g. 12h, 30d)")]
+		older_than: Option<String>,
+
+		#[arg(long, value_name = "N", help = "Keep at most the N most recent entries")]
+		keep_last: Option<usize>,
+	},
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+	#[clap(long_about = "Write a config file containing every setting with its default value.")]
+	Init {
+		#[arg(short, long, help = "Overwrite an existing file")]
+		force: bool,
+	},
+
+	#[clap(long_about = "Print the effective configuration after loading the config file.")]
+	Show,
+}
+
+#[derive(Subcommand)]
+pub enum ProxyAction {
+	#[clap(long_about = "Point the OS proxy settings at the proxy on --address/--port (macOS networksetup for every \
+						enabled network service, the Windows Internet Settings registry key, or GNOME gsettings) \
+						without starting it. The previous settings are saved to proxy.system_proxy_backup \
+						for disable-system.")]
+	EnableSystem {
+		#[arg(long, help = "Configure the auto-config URL http://ADDRESS:PORT/proxy.pac of 'proxy --serve-pac' instead of a fixed proxy")]
+		pac: bool,
+	},
+
+	#[clap(long_about = "Restore the OS proxy settings saved by enable-system or 'proxy --system-proxy'.")]
+	DisableSystem,
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+	#[clap(long_about = "Show the entries, their freshness and size per host, and the hit, revalidation and miss \
+						counters with the reasons responses were not stored.")]
+	Stats,
+
+	#[clap(long_about = "Remove cached responses, and the counters unless --host is given.")]
+	Clear {
+		#[arg(long, value_name = "GLOB", help = "Only remove entries of hosts matching this glob (e.g. *.example.com)")]
+		host: Option<String>,
 	},
 }
 
@@ -156,6 +1032,69 @@ pub enum CookieAction {
 		url: String,
 	},
 
+	#[clap(long_about = "Remove a single cookie identified by its domain and name.")]
+	Delete {
+		#[arg(short, long, help = "Cookie domain (e.g., 'example.com' or '.example.com')")]
+		domain: String,
+
+		#[arg(short, long, help = "Cookie name")]
+		name: String,
+	},
+
+	#[clap(long_about = "Modify a stored cookie, or create it when it does not exist yet. \
+						Only the given attributes are changed.")]
+	Set {
+		#[arg(short, long, help = "Cookie domain (e.g., 'example.com' or '.example.com')")]
+		domain: String,
+
+		#[arg(short, long, help = "Cookie name")]
+		name: String,
+
+		#[arg(short, long, help = "New value (required when creating a cookie)")]
+		value: Option<String>,
+
+		#[arg(short, long, help = "Expiry: 'session', an RFC 3339 time, or a duration from now such as 7d")]
+		expires: Option<String>,
+
+		#[arg(short, long, help = "Cookie path [default for new cookies: /]")]
+		path: Option<String>,
+	},
+
+	#[clap(long_about = "Import cookies from a local browser profile. Chrome and Chromium values \
+						are decrypted with the key from the system keyring (Linux and macOS); \
+						the database is copied first, so the browser may keep running.")]
+	ImportBrowser {
+		#[arg(short, long, value_enum, help = "Browser to import from")]
+		browser: Browser,
+
+		#[arg(short, long, help = "Only import cookies for this domain and its subdomains")]
+		domain: Option<String>,
+
+		#[arg(long, help = "Profile directory or cookie database file (default: the browser's default profile)")]
+		profile: Option<PathBuf>,
+	},
+
+	#[clap(long_about = "Show or change the default headers sent with every request. \
+						Combine with --session to give each session its own headers, e.g. an Authorization token per user. \
+						Headers given on the command line take precedence.")]
+	Headers {
+		#[arg(short = 'H', long = "set", value_name = "NAME:VALUE", help = "Add or replace a default header")]
+		set: Vec<String>,
+
+		#[arg(long, value_name = "NAME", help = "Remove a default header (case-insensitive)")]
+		unset: Vec<String>,
+	},
+
+	#[clap(long_about = "Report stored cookies grouped by domain with their time to expiry. \
+						Session cookies and cookies expiring soon are flagged.")]
+	Report {
+		#[arg(short, long, help = "Filter cookies by domain (e.g., 'example.com')")]
+		domain: Option<String>,
+
+		#[arg(long, default_value = "24", help = "Flag cookies expiring within this many hours")]
+		within_hours: u64,
+	},
+
 	#[clap(long_about = "Clean up expired cookies from the persistent storage. \
 						Automatically removes cookies that have passed their expiration date.")]
 	Clean,
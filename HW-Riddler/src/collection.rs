@@ -0,0 +1,214 @@
+use crate::assertion::{self, Assertion};
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpVersion, RequestBody, RetryPolicy};
+use crate::jsonpath;
+use crate::logger::RequestLogger;
+use crate::template::TemplateContext;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+use tracing::error;
+
+/// An ordered list of requests that can be executed with `riddler run`,
+/// stored as JSON or, for `.yaml`/`.yml` files, YAML.
+///
+/// URLs, headers and bodies may use `{{name}}` for collection variables as
+/// well as the built-in placeholders of [`TemplateContext`]. Values extracted
+/// from a response become variables for the requests after it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Collection {
+	pub name: String,
+	#[serde(default)]
+	pub variables: BTreeMap<String, String>,
+	#[serde(default)]
+	pub requests: Vec<CollectionRequest>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionRequest {
+	pub name: String,
+	/// Slash-separated folder path, e.g. `Users/Admin`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub folder: Option<String>,
+	#[serde(default = "default_method")]
+	pub method: String,
+	pub url: String,
+	#[serde(default)]
+	pub headers: BTreeMap<String, String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub body: Option<String>,
+	/// Variable name to JSONPath of the value to take from the response body
+	#[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+	pub extract: BTreeMap<String, String>,
+	/// Checks the response must pass, in `--assert` syntax
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub assertions: Vec<Assertion>,
+}
+
+fn default_method() -> String {
+	"GET".to_string()
+}
+
+#[derive(Debug, Clone)]
+pub struct RunOptions {
+	/// Only run requests whose folder starts with this path
+	pub folder: Option<String>,
+	/// Variables from `--env-file`, e.g. the base URL of one environment;
+	/// they take precedence over the collection's own variables
+	pub env: HashMap<String, String>,
+	/// Overrides for collection variables
+	pub variables: HashMap<String, String>,
+	pub delay: u64,
+	pub timeout: u64,
+}
+
+impl Collection {
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.with_context(|| format!("Unable to read collection {}", path.display()))?;
+		let collection = if is_yaml(path) {
+			serde_yaml::from_str(&content).map_err(anyhow::Error::from)
+		} else {
+			serde_json::from_str(&content).map_err(anyhow::Error::from)
+		};
+		collection.with_context(|| format!("{} is not a valid riddler collection", path.display()))
+	}
+
+	pub fn save(&self, path: &Path) -> Result<()> {
+		let content = if is_yaml(path) {
+			serde_yaml::to_string(self)?
+		} else {
+			serde_json::to_string_pretty(self)?
+		};
+		std::fs::write(path, content)
+			.with_context(|| format!("Unable to write collection {}", path.display()))
+	}
+}
+
+fn is_yaml(path: &Path) -> bool {
+	path.extension()
+		.and_then(|ext| ext.to_str())
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml"))
+}
+
+impl CollectionRequest {
+	pub fn display_name(&self) -> String {
+		match self.folder {
+			Some(ref folder) => format!("{}/{}", folder, self.name),
+			None => self.name.clone(),
+		}
+	}
+
+	fn in_folder(&self, folder: &str) -> bool {
+		let folder = folder.trim_matches('/');
+		self.folder.as_deref().is_some_and(|f| f == folder || f.starts_with(&format!("{}/", folder)))
+	}
+}
+
+/// Execute the collection in order, logging every response. Returns the
+/// number of requests that failed or answered with a 4xx/5xx status.
+pub async fn run(
+	collection: &Collection,
+	options: &RunOptions,
+	http_client: &HttpClient,
+	logger: &RequestLogger,
+) -> Result<usize> {
+	let mut context = TemplateContext::new();
+	for (name, value) in &collection.variables {
+		context.set(name, value.clone());
+	}
+	for (name, value) in options.env.iter().chain(&options.variables) {
+		context.set(name, value.clone());
+	}
+
+	let selected: Vec<&CollectionRequest> = collection.requests
+		.iter()
+		.filter(|r| options.folder.as_deref().is_none_or(|folder| r.in_folder(folder)))
+		.collect();
+
+	println!("=== Running collection '{}' ({} requests) ===", collection.name, selected.len());
+
+	let mut failures = 0;
+	for (i, item) in selected.iter().enumerate() {
+		println!("\n[{}/{}] {}", i + 1, selected.len(), item.display_name());
+
+		let request = context.expand_request(&HttpRequestBuilder {
+			method: item.method.clone(),
+			url: item.url.clone(),
+			headers: item.headers.clone().into_iter().collect(),
+			body: item.body.clone().map(RequestBody::Text),
+			timeout_seconds: options.timeout,
+			follow_redirects: true,
+			verify_ssl: true,
+			retry: RetryPolicy::default(),
+			auth: None,
+			http_version: HttpVersion::Auto,
+		});
+		let request = match request {
+			Ok(request) => request,
+			Err(e) => {
+				println!("❌ {}", e);
+				failures += 1;
+				continue;
+			}
+		};
+
+		println!("{} {}", request.method, request.url);
+		match http_client.send_request(request.clone()).await {
+			Ok(response) => {
+				// With assertions the request defines for itself which statuses are fine
+				let status_failed = item.assertions.is_empty() && response.status >= 400;
+				let marker = if status_failed { "⚠️" } else { "✅" };
+				println!("{} Response: {} ({}ms)", marker, response.status, response.response_time_ms);
+
+				let results = assertion::evaluate_all(&item.assertions, &response);
+				let assertions_failed = assertion::print_report(&results) > 0;
+
+				let mut extract_failed = false;
+				for (name, expression) in &item.extract {
+					match jsonpath::extract(&response.body, expression) {
+						Ok(values) if !values.is_empty() => {
+							let value = jsonpath::value_to_string(&values[0]);
+							println!("  📌 {} = {}", name, value);
+							context.set(name, value);
+						}
+						Ok(_) => {
+							println!("  ❌ {}: '{}' matched nothing", name, expression);
+							extract_failed = true;
+						}
+						Err(e) => {
+							println!("  ❌ {}: {}", name, e);
+							extract_failed = true;
+						}
+					}
+				}
+
+				if status_failed || assertions_failed || extract_failed {
+					failures += 1;
+				}
+
+				if let Err(e) = logger.log_manual_request_response(
+					&request.method,
+					&request.url,
+					request.headers.clone(),
+					&request.body_text(),
+					&response,
+				).await {
+					error!("Failed to log collection request: {}", e);
+				}
+			}
+			Err(e) => {
+				println!("❌ Error: {}", e);
+				failures += 1;
+			}
+		}
+
+		if i + 1 < selected.len() && options.delay > 0 {
+			tokio::time::sleep(Duration::from_millis(options.delay)).await;
+		}
+	}
+
+	println!("\n✓ Collection finished: {} succeeded, {} failed", selected.len() - failures, failures);
+	Ok(failures)
+}
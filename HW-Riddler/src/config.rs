@@ -1,35 +1,126 @@
+use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use std::net::IpAddr;
+use std::path::Path;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Config file read from the working directory when `--config` is not given
+pub const DEFAULT_CONFIG_FILE: &str = "riddler.toml";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
 	pub network: NetworkConfig,
 	pub proxy: ProxyConfig,
 	pub storage: StorageConfig,
+	pub http: HttpConfig,
+	pub redaction: RedactionConfig,
+	pub telemetry: TelemetryConfig,
+	pub notifications: NotificationsConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct NetworkConfig {
 	pub interface: String,
 	pub monitor_filter: String,
 	pub buffer_size: usize,
+	/// Captured packets waiting for processing; beyond this they are dropped
+	pub queue_capacity: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ProxyConfig {
 	pub bind_address: IpAddr,
 	pub bind_port: u16,
 	pub upstream_proxy: Option<String>,
+	/// CA used by `proxy --mitm`, generated on first use
+	pub ca_cert_path: String,
+	pub ca_key_path: String,
+	/// Client addresses or CIDRs that may use the proxy; anyone when empty
+	pub allow_clients: Vec<String>,
+	/// Client addresses or CIDRs refused even if allowed
+	pub deny_clients: Vec<String>,
+	/// Destination host globs the proxy connects to; any when empty
+	pub allow_hosts: Vec<String>,
+	/// Destination host globs the proxy refuses, e.g. `*.doubleclick.net`
+	pub block_hosts: Vec<String>,
+	/// `user:password` clients must send as Basic `Proxy-Authorization`
+	pub auth: Option<String>,
+	/// Where `proxy --cache` stores responses
+	pub cache_dir: String,
+	/// Where `proxy enable-system` keeps the OS proxy settings it replaced
+	pub system_proxy_backup: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct StorageConfig {
 	pub cookie_cache_path: String,
 	pub request_log_path: String,
+	/// Responses kept by `proxy --cache`; the oldest are evicted beyond this
 	pub max_cache_size: usize,
+	/// Rotate the request log once it would exceed this size; 0 disables size rotation
+	pub log_max_size_mb: u64,
+	/// Also rotate when the first entry of a new (UTC) day is written
+	pub log_rotate_daily: bool,
+	/// Number of rotated files to keep (`requests.log.1` is the newest)
+	pub log_keep_files: usize,
+	/// Gzip rotated files
+	pub log_compress: bool,
+	/// Per-host counters of recent monitor and proxy runs, for `hosts --session`
+	pub sessions_path: String,
+	/// One JSON line per run of `replay --every`/`--cron`
+	pub replay_history_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+	/// Default timeout for `request` and `run`
+	pub timeout_seconds: u64,
+}
+
+/// What the request logger masks before writing entries to disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionConfig {
+	pub enabled: bool,
+	/// Request and response headers whose values are masked (case-insensitive)
+	pub headers: Vec<String>,
+	/// Body fields and query parameters whose name contains one of these words
+	pub body_fields: Vec<String>,
+	/// Mask digit sequences that pass the Luhn check, keeping the last four digits
+	pub mask_card_numbers: bool,
 }
 
-impl Default for Config {
+/// OTLP export of the spans of sent requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+	pub enabled: bool,
+	/// OTLP/HTTP traces URL; unset uses `OTEL_EXPORTER_OTLP_ENDPOINT` or the local collector
+	pub endpoint: Option<String>,
+	pub service_name: String,
+	/// Sent with every export, e.g. an authorization header for a hosted backend
+	pub headers: std::collections::HashMap<String, String>,
+}
+
+/// Where monitor rule alerts, `analyze --watch` SLA breaches and long replays are announced
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+	/// Show a desktop notification
+	pub desktop: bool,
+	/// POST every notification as JSON to these URLs
+	pub webhooks: Vec<String>,
+	/// Slack incoming webhook URL
+	pub slack_webhook: Option<String>,
+	/// Announce replays that ran at least this long; 0 announces every replay
+	pub replay_min_seconds: u64,
+}
+
+impl Default for NetworkConfig {
 	fn default() -> Self {
 		let default_interface = match std::env::consts::OS {
 			"macos" => "en0",
@@ -41,25 +132,115 @@ impl Default for Config {
 		}.to_string();
 
 		Self {
-			network: NetworkConfig {
-				interface: default_interface,
-				monitor_filter: "tcp port 80 or tcp port 443".to_string(),
-				buffer_size: 65536,
-			},
-			proxy: ProxyConfig {
-				bind_address: "127.0.0.1".parse().unwrap(),
-				bind_port: 8080,
-				upstream_proxy: None,
-			},
-			storage: StorageConfig {
-				cookie_cache_path: "./cookies.json".to_string(),
-				request_log_path: "./requests.log".to_string(),
-				max_cache_size: 1000,
-			},
+			interface: default_interface,
+			monitor_filter: "tcp port 80 or tcp port 443".to_string(),
+			buffer_size: 65536,
+			queue_capacity: crate::network::DEFAULT_QUEUE_CAPACITY,
+		}
+	}
+}
+
+impl Default for ProxyConfig {
+	fn default() -> Self {
+		Self {
+			bind_address: "127.0.0.1".parse().unwrap(),
+			bind_port: 8080,
+			upstream_proxy: None,
+			ca_cert_path: "./riddler-ca.pem".to_string(),
+			ca_key_path: "./riddler-ca-key.pem".to_string(),
+			allow_clients: Vec::new(),
+			deny_clients: Vec::new(),
+			allow_hosts: Vec::new(),
+			block_hosts: Vec::new(),
+			auth: None,
+			cache_dir: "./riddler-cache".to_string(),
+			system_proxy_backup: "./riddler-system-proxy.json".to_string(),
 		}
 	}
 }
 
+impl Default for StorageConfig {
+	fn default() -> Self {
+		Self {
+			cookie_cache_path: "./cookies.json".to_string(),
+			request_log_path: "./requests.log".to_string(),
+			max_cache_size: 1000,
+			log_max_size_mb: 100,
+			log_rotate_daily: false,
+			log_keep_files: 7,
+			log_compress: true,
+			sessions_path: "./riddler-sessions.json".to_string(),
+			replay_history_path: "./riddler-replay-runs.jsonl".to_string(),
+		}
+	}
+}
+
+impl Default for RedactionConfig {
+	fn default() -> Self {
+		let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+		Self {
+			enabled: true,
+			headers: strings(&["authorization", "proxy-authorization", "cookie", "set-cookie", "x-api-key", "x-auth-token"]),
+			body_fields: strings(&["password", "passwd", "token", "secret", "api_key", "apikey"]),
+			mask_card_numbers: true,
+		}
+	}
+}
+
+impl Default for TelemetryConfig {
+	fn default() -> Self {
+		Self {
+			enabled: false,
+			endpoint: None,
+			service_name: "riddler".to_string(),
+			headers: std::collections::HashMap::new(),
+		}
+	}
+}
+
+impl Default for NotificationsConfig {
+	fn default() -> Self {
+		Self {
+			desktop: false,
+			webhooks: Vec::new(),
+			slack_webhook: None,
+			replay_min_seconds: 60,
+		}
+	}
+}
+
+impl Default for HttpConfig {
+	fn default() -> Self {
+		Self { timeout_seconds: 30 }
+	}
+}
+
+impl Config {
+	/// Read a TOML config file. Missing sections and keys keep their defaults.
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.with_context(|| format!("Unable to read config file {}", path.display()))?;
+		toml::from_str(&content)
+			.map_err(|e| anyhow!("Invalid config file {}: {}", path.display(), e))
+	}
+
+	/// Load `path` if it exists. A missing file is an error only when `required`
+	/// (the path was given explicitly); otherwise the defaults are used.
+	pub fn load_or_default(path: &Path, required: bool) -> Result<Self> {
+		if path.exists() {
+			Self::load(path)
+		} else if required {
+			Err(anyhow!("Config file {} not found (create it with 'riddler config init')", path.display()))
+		} else {
+			Ok(Self::default())
+		}
+	}
+
+	pub fn to_toml(&self) -> Result<String> {
+		toml::to_string_pretty(self).map_err(|e| anyhow!("Unable to serialize config: {}", e))
+	}
+}
+
 pub fn list_available_interfaces() -> Vec<String> {
 	match pcap::Device::list() {
 		Ok(devices) => devices.into_iter().map(|d| d.name).collect(),
@@ -74,15 +255,10 @@ pub fn interface_exists(interface: &str) -> bool {
 	}
 }
 
-pub fn validate_bpf_filter(filter: &str) -> bool {
-	if let Ok(devices) = pcap::Device::list() {
-		if let Some(device) = devices.first() {
-			if let Ok(cap_result) = pcap::Capture::from_device(device.clone()) {
-				if let Ok(mut cap) = cap_result.open() {
-					return cap.filter(filter, true).is_ok();
-				}
-			}
-		}
-	}
-	true
+/// Compile `filter` for Ethernet without opening a device, so that no capture
+/// privileges are needed; the error carries libpcap's message
+pub fn validate_bpf_filter(filter: &str) -> Result<()> {
+	let cap = pcap::Capture::dead(pcap::Linktype::ETHERNET)
+		.map_err(|e| anyhow!("Unable to compile BPF filter: {}", e))?;
+	cap.compile(filter, true).map(|_| ()).map_err(|e| anyhow!("{}", e))
 }
\ No newline at end of file
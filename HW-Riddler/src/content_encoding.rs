@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+/// Decoded bodies are cut off here so a compression bomb cannot exhaust memory
+const MAX_DECODED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// `body` with the codings of its `Content-Encoding` header undone (gzip,
+/// deflate and br, several applied in the order listed). `None` when the body
+/// is not encoded, uses another coding or does not decode at all. A body
+/// truncated mid-stream, e.g. the first segments of a captured response,
+/// yields whatever decodes before the cut.
+pub fn decode_body(headers: &HashMap<String, String>, body: &[u8]) -> Option<Vec<u8>> {
+	let encoding = headers.iter()
+		.find(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+		.map(|(_, value)| value.to_ascii_lowercase())?;
+	let codings: Vec<&str> = encoding.split(',')
+		.map(str::trim)
+		.filter(|coding| !coding.is_empty() && *coding != "identity")
+		.collect();
+	if codings.is_empty() || body.is_empty() {
+		return None;
+	}
+
+	// Codings are listed in the order they were applied, so undo them backwards
+	let mut decoded = body.to_vec();
+	for coding in codings.iter().rev() {
+		decoded = decode(coding, &decoded)?;
+	}
+	Some(decoded)
+}
+
+fn decode(coding: &str, data: &[u8]) -> Option<Vec<u8>> {
+	match coding {
+		"gzip" | "x-gzip" => read_lenient(flate2::read::MultiGzDecoder::new(data)),
+		// Meant to be zlib-wrapped, but some servers send a raw deflate stream
+		"deflate" => read_lenient(flate2::read::ZlibDecoder::new(data))
+			.or_else(|| read_lenient(flate2::read::DeflateDecoder::new(data))),
+		"br" => read_lenient(brotli::Decompressor::new(data, 4096)),
+		_ => None,
+	}
+}
+
+/// Everything `reader` produces before it ends or fails; `None` if nothing
+fn read_lenient(reader: impl Read) -> Option<Vec<u8>> {
+	let mut decoded = Vec::new();
+	// On an error the bytes read so far are kept in `decoded`
+	let result = reader.take(MAX_DECODED_SIZE).read_to_end(&mut decoded);
+	(result.is_ok() || !decoded.is_empty()).then_some(decoded)
+}
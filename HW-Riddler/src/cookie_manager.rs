@@ -1,27 +1,124 @@
-use anyhow::Result;
-use cookie_store::Cookie;
+use anyhow::{anyhow, Result};
+use cookie_store::{Cookie, CookieDomain, CookieExpiration};
 use dashmap::DashMap;
+use once_cell::sync::Lazy;
+use publicsuffix::Psl;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::fs;
+use tracing::debug;
 use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CookieEntry {
 	pub name: String,
 	pub value: String,
+	/// Lower-cased, without a leading dot
 	pub domain: String,
 	pub path: String,
 	pub expires: Option<u64>,
 	pub secure: bool,
 	pub http_only: bool,
 	pub same_site: Option<String>,
+	/// Set-Cookie without a Domain attribute: only sent to exactly `domain`,
+	/// not to its subdomains
+	#[serde(default)]
+	pub host_only: bool,
+	/// Unix time the cookie was first stored; breaks ties when ordering the
+	/// Cookie header
+	#[serde(default)]
+	pub created: u64,
+}
+
+impl CookieEntry {
+	/// Seconds until expiry (negative once expired), `None` for session cookies
+	pub fn seconds_until_expiry(&self, now: u64) -> Option<i64> {
+		self.expires.map(|expires| expires as i64 - now as i64)
+	}
+
+	/// Whether this cookie would be sent to `host` (RFC 6265 section 5.1.3)
+	pub fn applies_to(&self, host: &str) -> bool {
+		let host = host.to_lowercase();
+		if self.host_only {
+			host == self.domain
+		} else {
+			domain_match(&host, &self.domain)
+		}
+	}
+
+	/// Identity of a cookie: a new one with the same name, domain and path
+	/// replaces the old one (RFC 6265 section 5.3, step 11)
+	fn key(&self) -> String {
+		format!("{}:{}:{}", self.domain, self.path, self.name)
+	}
+}
+
+/// The public suffix list, so `Domain=co.uk` cannot set a cookie for every
+/// site under `.co.uk`
+static PUBLIC_SUFFIXES: Lazy<publicsuffix::List> = Lazy::new(|| {
+	include_str!("../data/public_suffix_list.dat").parse().expect("valid public suffix list")
+});
+
+fn is_public_suffix(domain: &str) -> bool {
+	PUBLIC_SUFFIXES
+		.suffix(domain.as_bytes())
+		.is_some_and(|suffix| suffix.is_known() && suffix.as_bytes() == domain.as_bytes())
+}
+
+/// `host` equals `domain` or is a subdomain of it; IP addresses only match exactly
+fn domain_match(host: &str, domain: &str) -> bool {
+	host == domain
+		|| (host.ends_with(domain)
+			&& host[..host.len() - domain.len()].ends_with('.')
+			&& host.parse::<std::net::IpAddr>().is_err())
+}
+
+/// RFC 6265 section 5.1.4: `/api` matches `/api`, `/api/` and `/api/users`
+/// but not `/apis`
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+	request_path == cookie_path
+		|| (request_path.starts_with(cookie_path)
+			&& (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+fn now_secs() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.unwrap()
+		.as_secs()
+}
+
+/// Cookie file of the named session `session`: `sessions/<session>.json`
+/// next to the default cookie file
+pub fn session_path(cookie_cache_path: &str, session: &str) -> Result<String> {
+	if session.is_empty() || !session.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+		return Err(anyhow!("Invalid session name '{}': use letters, digits, '-' and '_'", session));
+	}
+	let dir = Path::new(cookie_cache_path).parent().unwrap_or(Path::new("."));
+	Ok(dir.join("sessions").join(format!("{}.json", session)).to_string_lossy().into_owned())
+}
+
+/// On-disk form of a cookie file: a bare array of cookies, or an object that
+/// also holds the default headers of a session
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum CookieFile {
+	Cookies(Vec<CookieEntry>),
+	Session {
+		#[serde(default)]
+		headers: BTreeMap<String, String>,
+		cookies: Vec<CookieEntry>,
+	},
 }
 
 #[derive(Debug)]
 pub struct CookieManager {
 	store: Arc<DashMap<String, CookieEntry>>,
+	/// Headers sent with every request unless the request sets them itself
+	headers: DashMap<String, String>,
 	file_path: String,
 }
 
@@ -29,17 +126,22 @@ impl CookieManager {
 	pub fn new(file_path: String) -> Self {
 		Self {
 			store: Arc::new(DashMap::new()),
+			headers: DashMap::new(),
 			file_path,
 		}
 	}
 
 	pub async fn load_from_file(&self) -> Result<()> {
 		if let Ok(content) = fs::read_to_string(&self.file_path).await {
-			if let Ok(cookies) = serde_json::from_str::<Vec<CookieEntry>>(&content) {
-				for cookie in cookies {
-					let key = format!("{}:{}", cookie.domain, cookie.name);
-					self.store.insert(key, cookie);
+			match serde_json::from_str::<CookieFile>(&content) {
+				Ok(CookieFile::Cookies(cookies)) => cookies.into_iter().for_each(|cookie| self.insert(cookie)),
+				Ok(CookieFile::Session { headers, cookies }) => {
+					cookies.into_iter().for_each(|cookie| self.insert(cookie));
+					for (name, value) in headers {
+						self.headers.insert(name, value);
+					}
 				}
+				Err(_) => {}
 			}
 		}
 		Ok(())
@@ -47,78 +149,145 @@ impl CookieManager {
 
 	pub async fn save_to_file(&self) -> Result<()> {
 		let cookies: Vec<CookieEntry> = self.store.iter().map(|entry| entry.value().clone()).collect();
-		let content = serde_json::to_string_pretty(&cookies)?;
+		// Without headers the plain array format is kept, as older versions wrote it
+		let file = if self.headers.is_empty() {
+			CookieFile::Cookies(cookies)
+		} else {
+			let headers = self.headers.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect();
+			CookieFile::Session { headers, cookies }
+		};
+		let content = serde_json::to_string_pretty(&file)?;
+		if let Some(dir) = Path::new(&self.file_path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+			fs::create_dir_all(dir).await?;
+		}
 		fs::write(&self.file_path, content).await?;
 		Ok(())
 	}
 
+	/// Default headers as `(name, value)` pairs, sorted by name
+	pub fn default_headers(&self) -> Vec<(String, String)> {
+		let mut headers: Vec<(String, String)> = self.headers
+			.iter()
+			.map(|entry| (entry.key().clone(), entry.value().clone()))
+			.collect();
+		headers.sort();
+		headers
+	}
+
+	/// Set a default header, replacing one whose name differs only in case
+	pub fn set_default_header(&self, name: &str, value: &str) {
+		self.remove_default_header(name);
+		self.headers.insert(name.to_string(), value.to_string());
+	}
+
+	/// Remove a default header by case-insensitive name; `false` if none was set
+	pub fn remove_default_header(&self, name: &str) -> bool {
+		let before = self.headers.len();
+		self.headers.retain(|existing, _| !existing.eq_ignore_ascii_case(name));
+		self.headers.len() != before
+	}
+
+	/// Store a cookie from a `Set-Cookie` header received from `url`,
+	/// following the storage model of RFC 6265 section 5.3. Cookies the
+	/// browser would reject are ignored.
 	pub fn add_cookie(&self, url: &Url, cookie_str: &str) -> Result<()> {
-		if let Ok(cookie) = Cookie::parse(cookie_str, url) {
-			let entry = CookieEntry {
-				name: cookie.name().to_string(),
-				value: cookie.value().to_string(),
-				domain: cookie.domain().unwrap_or("").to_string(),
-				path: cookie.path().unwrap_or("/").to_string(),
-				expires: cookie.expires_datetime().map(|dt| {
-					dt.unix_timestamp() as u64
-				}),
-				secure: cookie.secure().unwrap_or(false),
-				http_only: cookie.http_only().unwrap_or(false),
-				same_site: cookie.same_site().map(|s| format!("{:?}", s)),
-			};
-
-			let key = format!("{}:{}", entry.domain, entry.name);
-			self.store.insert(key, entry);
+		let Ok(cookie) = Cookie::parse(cookie_str, url) else {
+			return Ok(());
+		};
+		let host = url.host_str().unwrap_or("").to_lowercase();
+
+		let (domain, host_only) = match cookie.domain {
+			CookieDomain::Suffix(ref domain) if is_public_suffix(domain) => {
+				// A public suffix is only acceptable as the request host itself
+				if *domain != host {
+					debug!("Ignoring cookie {} for public suffix {}", cookie.name(), domain);
+					return Ok(());
+				}
+				(host, true)
+			}
+			CookieDomain::Suffix(ref domain) => (domain.clone(), false),
+			_ => (host, true),
+		};
+
+		let mut entry = CookieEntry {
+			name: cookie.name().to_string(),
+			value: cookie.value().to_string(),
+			domain,
+			path: String::from(&cookie.path),
+			// Max-Age takes precedence over Expires
+			expires: match cookie.expires {
+				CookieExpiration::AtUtc(ref time) => Some(time.unix_timestamp().max(0) as u64),
+				CookieExpiration::SessionEnd => None,
+			},
+			secure: cookie.secure().unwrap_or(false),
+			http_only: cookie.http_only().unwrap_or(false),
+			same_site: cookie.same_site().map(|s| format!("{:?}", s)),
+			host_only,
+			created: now_secs(),
+		};
+
+		// A replaced cookie keeps its original creation time
+		if let Some(old) = self.store.get(&entry.key()) {
+			entry.created = old.created;
 		}
+		self.store.insert(entry.key(), entry);
 		Ok(())
 	}
 
-	pub fn get_cookies_for_url(&self, url: &Url) -> Vec<String> {
-		let domain = url.domain().unwrap_or("");
-		let path = url.path();
-		let is_secure = url.scheme() == "https";
+	/// Store a cookie as-is, replacing one with the same name, domain and
+	/// path. A leading dot on the domain marks a domain cookie, as in
+	/// browser cookie databases.
+	pub fn insert(&self, mut entry: CookieEntry) {
+		if let Some(domain) = entry.domain.strip_prefix('.') {
+			entry.domain = domain.to_string();
+			entry.host_only = false;
+		}
+		entry.domain = entry.domain.to_lowercase();
+		if entry.created == 0 {
+			entry.created = now_secs();
+		}
+		self.store.insert(entry.key(), entry);
+	}
 
-		let now = SystemTime::now()
-			.duration_since(UNIX_EPOCH)
-			.unwrap()
-			.as_secs();
+	/// Remove a cookie by domain and name, whatever its path; a leading dot
+	/// on `domain` is ignored
+	pub fn remove(&self, domain: &str, name: &str) -> Option<CookieEntry> {
+		self.find_key(domain, name)
+			.and_then(|key| self.store.remove(&key).map(|(_, entry)| entry))
+	}
 
+	fn find_key(&self, domain: &str, name: &str) -> Option<String> {
+		let domain = domain.trim_start_matches('.').to_lowercase();
 		self.store
 			.iter()
-			.filter_map(|entry| {
-				let cookie = entry.value();
-
-				let domain_match = domain == cookie.domain ||
-					(cookie.domain.starts_with(".") && domain.ends_with(&cookie.domain[1..]));
-
-				if !domain_match {
-					return None;
-				}
-
-				if !path.starts_with(&cookie.path) {
-					return None;
-				}
+			.filter(|entry| entry.value().domain == domain && entry.value().name == name)
+			.map(|entry| entry.key().clone())
+			.min()
+	}
 
-				if let Some(expires) = cookie.expires {
-					if now > expires {
-						return None;
-					}
-				}
+	/// `name=value` pairs to send to `url`, in the order RFC 6265 section 5.4
+	/// asks for: longer paths first, then older cookies first
+	pub fn get_cookies_for_url(&self, url: &Url) -> Vec<String> {
+		let host = url.host_str().unwrap_or("").to_lowercase();
+		let path = url.path();
+		let is_secure = matches!(url.scheme(), "https" | "wss");
+		let now = now_secs();
 
-				if cookie.secure && !is_secure {
-					return None;
-				}
+		let mut cookies: Vec<CookieEntry> = self.store
+			.iter()
+			.map(|entry| entry.value().clone())
+			.filter(|cookie| cookie.applies_to(&host))
+			.filter(|cookie| path_match(path, &cookie.path))
+			.filter(|cookie| cookie.expires.is_none_or(|expires| now <= expires))
+			.filter(|cookie| !cookie.secure || is_secure)
+			.collect();
 
-				Some(format!("{}={}", cookie.name, cookie.value))
-			})
-			.collect()
+		cookies.sort_by(|a, b| b.path.len().cmp(&a.path.len()).then(a.created.cmp(&b.created)));
+		cookies.into_iter().map(|cookie| format!("{}={}", cookie.name, cookie.value)).collect()
 	}
 
 	pub fn clear_expired(&self) {
-		let now = SystemTime::now()
-			.duration_since(UNIX_EPOCH)
-			.unwrap()
-			.as_secs();
+		let now = now_secs();
 
 		self.store.retain(|_, cookie| {
 			if let Some(expires) = cookie.expires {
@@ -144,7 +313,50 @@ impl CookieManager {
 			.collect()
 	}
 
+	/// Persistent cookies for `domain` that expire within `within_secs`,
+	/// including ones that already expired
+	pub fn expiring_cookies(&self, domain: &str, within_secs: u64) -> Vec<CookieEntry> {
+		let now = now_secs();
+
+		self.store
+			.iter()
+			.filter(|entry| entry.value().applies_to(domain))
+			.filter(|entry| entry.value().seconds_until_expiry(now).is_some_and(|left| left <= within_secs as i64))
+			.map(|entry| entry.value().clone())
+			.collect()
+	}
+
 	pub fn clear_all(&self) {
 		self.store.clear();
 	}
-}
\ No newline at end of file
+}
+
+/// Parse a cookie expiry: `session`, an RFC 3339 timestamp, or a duration
+/// from now such as `30m`, `12h` or `7d`. Returns the Unix time, `None` for
+/// a session cookie.
+pub fn parse_expires(value: &str) -> Result<Option<u64>> {
+	if value.eq_ignore_ascii_case("session") {
+		return Ok(None);
+	}
+	if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+		return Ok(Some(time.timestamp().max(0) as u64));
+	}
+	let duration = crate::timeline::parse_since(value)
+		.map_err(|_| anyhow!("Invalid expiry '{}': use 'session', an RFC 3339 time or a duration such as 7d", value))?;
+	Ok(Some((chrono::Utc::now() + duration).timestamp().max(0) as u64))
+}
+
+/// Compact time-to-expiry such as `3d 4h`, `5h 12m` or `40s`
+pub fn format_time_left(seconds: i64) -> String {
+	let seconds = seconds.unsigned_abs();
+	let (days, hours, minutes) = (seconds / 86_400, seconds % 86_400 / 3_600, seconds % 3_600 / 60);
+	if days > 0 {
+		format!("{}d {}h", days, hours)
+	} else if hours > 0 {
+		format!("{}h {}m", hours, minutes)
+	} else if minutes > 0 {
+		format!("{}m", minutes)
+	} else {
+		format!("{}s", seconds)
+	}
+}
@@ -0,0 +1,44 @@
+use crate::logger::RequestLogEntry;
+
+/// Headers curl computes itself
+const SKIPPED_HEADERS: [&str; 3] = ["content-length", "host", "connection"];
+
+/// Render a logged request as a runnable `curl` command line.
+///
+/// `cookies` are stored cookies for the URL; they are added with `-b` when
+/// the logged request did not carry its own `Cookie` header.
+pub fn to_curl(entry: &RequestLogEntry, cookies: &[String]) -> String {
+	let request = &entry.request;
+	let mut parts = vec![format!("curl -X {} {}", request.method, quote(&request.url))];
+
+	let mut headers: Vec<(&String, &String)> = request.headers
+		.iter()
+		.filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.to_lowercase().as_str()))
+		.collect();
+	headers.sort();
+	for (name, value) in &headers {
+		parts.push(format!("-H {}", quote(&format!("{}: {}", name, value))));
+	}
+
+	let has_cookie_header = headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("cookie"));
+	if !has_cookie_header && !cookies.is_empty() {
+		parts.push(format!("-b {}", quote(&cookies.join("; "))));
+	}
+
+	let mut command = String::new();
+	if !request.body_preview.is_empty() {
+		parts.push(format!("--data-raw {}", quote(&request.body_preview)));
+		// The log keeps only the first 1000 bytes of a body
+		if request.body_preview.len() > 1000 && request.body_preview.ends_with("...") {
+			command.push_str("# warning: body was truncated in the log\n");
+		}
+	}
+
+	command.push_str(&parts.join(" \\\n  "));
+	command
+}
+
+/// POSIX shell single-quoting
+fn quote(value: &str) -> String {
+	format!("'{}'", value.replace('\'', r"'\''"))
+}
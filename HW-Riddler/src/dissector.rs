@@ -0,0 +1,128 @@
+use crate::dns::{self, DnsMessage};
+use crate::network::NetworkPacket;
+use once_cell::sync::Lazy;
+
+/// Recognizes one application protocol carried over UDP. Dissectors are tried
+/// in the order of [`UDP_DISSECTORS`] and the first that claims a datagram
+/// names its protocol; add new protocols by implementing this and listing
+/// the implementation there.
+pub trait UdpDissector: Send + Sync {
+	/// Protocol name recorded in `NetworkPacket::application_protocol`
+	fn name(&self) -> &'static str;
+
+	/// Whether the datagram belongs to this protocol. Called for every UDP
+	/// packet captured, so it should only look at ports and a few bytes.
+	fn matches(&self, packet: &NetworkPacket) -> bool;
+
+	/// One line describing the datagram for the monitor output
+	fn summarize(&self, packet: &NetworkPacket) -> Option<String>;
+}
+
+pub static UDP_DISSECTORS: Lazy<Vec<Box<dyn UdpDissector>>> = Lazy::new(|| vec![
+	Box::new(Dns),
+	Box::new(Quic),
+	Box::new(Syslog),
+]);
+
+/// The dissector that claims `packet`, if any
+pub fn dissect(packet: &NetworkPacket) -> Option<&'static dyn UdpDissector> {
+	if packet.protocol != "UDP" {
+		return None;
+	}
+	UDP_DISSECTORS.iter().map(Box::as_ref).find(|dissector| dissector.matches(packet))
+}
+
+fn uses_port(packet: &NetworkPacket, port: u16) -> bool {
+	packet.src_port == port || packet.dst_port == port
+}
+
+struct Dns;
+
+impl UdpDissector for Dns {
+	fn name(&self) -> &'static str {
+		"DNS"
+	}
+
+	fn matches(&self, packet: &NetworkPacket) -> bool {
+		// mDNS (5353) uses the same message format
+		(uses_port(packet, 53) || uses_port(packet, 5353)) && packet.payload.len() >= 12
+	}
+
+	fn summarize(&self, packet: &NetworkPacket) -> Option<String> {
+		let message = DnsMessage::parse(&packet.payload).ok()?;
+		let (name, kind) = message.questions.first()?;
+		Some(if message.is_response {
+			format!("response {:#06x} {} {}: {} answer(s), {}", message.id, dns::type_name(*kind), name,
+				message.answers.len(), message.rcode_name())
+		} else {
+			format!("query {:#06x} {} {}", message.id, dns::type_name(*kind), name)
+		})
+	}
+}
+
+/// QUIC long-header packets (handshake) on UDP 443, with the server name of
+/// client Initial packets; short-header packets carry no cleartext fields
+/// worth reporting
+struct Quic;
+
+impl Quic {
+	fn version(payload: &[u8]) -> Option<u32> {
+		let version = payload.get(1..5)?;
+		Some(u32::from_be_bytes([version[0], version[1], version[2], version[3]]))
+	}
+}
+
+impl UdpDissector for Quic {
+	fn name(&self) -> &'static str {
+		"QUIC"
+	}
+
+	fn matches(&self, packet: &NetworkPacket) -> bool {
+		// Fixed bit set; long or short header
+		uses_port(packet, 443) && packet.payload.first().is_some_and(|&first| first & 0x40 != 0)
+	}
+
+	fn summarize(&self, packet: &NetworkPacket) -> Option<String> {
+		let first = *packet.payload.first()?;
+		if first & 0x80 == 0 {
+			return Some("short header (1-RTT data)".to_string());
+		}
+		let version = Quic::version(&packet.payload)?;
+		if version == 0 {
+			return Some("version negotiation".to_string());
+		}
+		let kind = match (first >> 4) & 0x03 {
+			0 => "Initial",
+			1 => "0-RTT",
+			2 => "Handshake",
+			_ => "Retry",
+		};
+		if let Some(initial) = crate::quic::parse_client_initial(&packet.payload) {
+			return Some(format!("{} (version 0x{:08x}) SNI {} ALPN {}", kind, version,
+				initial.server_name.as_deref().unwrap_or("-"), initial.alpn.join(",")));
+		}
+		Some(format!("{} (version 0x{:08x})", kind, version))
+	}
+}
+
+/// BSD syslog (RFC 3164) and RFC 5424 messages on UDP 514
+struct Syslog;
+
+impl UdpDissector for Syslog {
+	fn name(&self) -> &'static str {
+		"Syslog"
+	}
+
+	fn matches(&self, packet: &NetworkPacket) -> bool {
+		uses_port(packet, 514) && packet.payload.first() == Some(&b'<')
+	}
+
+	fn summarize(&self, packet: &NetworkPacket) -> Option<String> {
+		let text = String::from_utf8_lossy(&packet.payload);
+		let (priority, message) = text.strip_prefix('<')?.split_once('>')?;
+		let priority: u8 = priority.parse().ok()?;
+		const SEVERITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+		let message: String = message.trim().chars().take(120).collect();
+		Some(format!("facility {} {}: {}", priority / 8, SEVERITIES[(priority % 8) as usize], message))
+	}
+}
@@ -0,0 +1,430 @@
+use anyhow::{anyhow, Result};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tracing::debug;
+
+/// Resolvers slower than this on average are flagged in the summary
+const SLOW_RESOLVER_MS: f64 = 100.0;
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordType {
+	A,
+	Aaaa,
+	Cname,
+	Mx,
+	Ns,
+	Txt,
+}
+
+impl RecordType {
+	fn code(self) -> u16 {
+		match self {
+			RecordType::A => 1,
+			RecordType::Ns => 2,
+			RecordType::Cname => 5,
+			RecordType::Mx => 15,
+			RecordType::Txt => 16,
+			RecordType::Aaaa => 28,
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			RecordType::A => "A",
+			RecordType::Aaaa => "AAAA",
+			RecordType::Cname => "CNAME",
+			RecordType::Mx => "MX",
+			RecordType::Ns => "NS",
+			RecordType::Txt => "TXT",
+		}
+	}
+}
+
+/// One query answered by one resolver
+#[derive(Debug, Clone)]
+pub struct DnsResponse {
+	/// Response code, e.g. `NOERROR` or `NXDOMAIN`
+	pub rcode: String,
+	pub truncated: bool,
+	/// Records of the requested type, rendered as text
+	pub answers: Vec<String>,
+	pub min_ttl: Option<u32>,
+}
+
+/// Timings of repeated queries for one record type against one resolver
+#[derive(Debug, Clone)]
+pub struct QueryStats {
+	pub record_type: RecordType,
+	pub times_ms: Vec<u64>,
+	/// The last response, or why the last query failed
+	pub response: std::result::Result<DnsResponse, String>,
+}
+
+impl QueryStats {
+	pub fn average_ms(&self) -> Option<f64> {
+		(!self.times_ms.is_empty()).then(|| self.times_ms.iter().sum::<u64>() as f64 / self.times_ms.len() as f64)
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolverReport {
+	pub server: SocketAddr,
+	pub queries: Vec<QueryStats>,
+}
+
+impl ResolverReport {
+	/// Mean over all successful queries of every record type
+	pub fn average_ms(&self) -> Option<f64> {
+		let times: Vec<u64> = self.queries.iter().flat_map(|q| q.times_ms.iter().copied()).collect();
+		(!times.is_empty()).then(|| times.iter().sum::<u64>() as f64 / times.len() as f64)
+	}
+}
+
+/// `1.1.1.1`, `1.1.1.1:5353` or `[2606:4700::1111]:53`
+pub fn parse_server(value: &str) -> Result<SocketAddr> {
+	if let Ok(address) = value.parse::<SocketAddr>() {
+		return Ok(address);
+	}
+	let ip: IpAddr = value.trim_start_matches('[').trim_end_matches(']').parse()
+		.map_err(|_| anyhow!("Invalid DNS server '{}': expected an IP address, optionally with a port", value))?;
+	Ok(SocketAddr::new(ip, 53))
+}
+
+/// The `nameserver` entries of /etc/resolv.conf
+pub fn system_resolvers() -> Vec<SocketAddr> {
+	let content = std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default();
+	content
+		.lines()
+		.filter_map(|line| line.trim().strip_prefix("nameserver"))
+		// Link-local IPv6 resolvers carry a `%iface` zone we cannot express here
+		.filter_map(|server| server.trim().split('%').next()?.parse::<IpAddr>().ok())
+		.map(|ip| SocketAddr::new(ip, 53))
+		.collect()
+}
+
+/// Query every resolver for every record type `count` times
+pub async fn diagnose(
+	name: &str,
+	servers: &[SocketAddr],
+	types: &[RecordType],
+	count: u32,
+	timeout: Duration,
+) -> Vec<ResolverReport> {
+	let mut reports = Vec::new();
+	for &server in servers {
+		let mut queries = Vec::new();
+		for &record_type in types {
+			let mut times_ms = Vec::new();
+			let mut response = Err("not queried".to_string());
+			for _ in 0..count.max(1) {
+				let start = Instant::now();
+				response = query(server, name, record_type, timeout).await.map_err(|e| e.to_string());
+				if response.is_ok() {
+					times_ms.push(start.elapsed().as_millis() as u64);
+				}
+			}
+			queries.push(QueryStats { record_type, times_ms, response });
+		}
+		reports.push(ResolverReport { server, queries });
+	}
+	reports
+}
+
+/// Time the operating system's resolver (what HTTP requests actually use),
+/// returning the elapsed time and the addresses found
+pub async fn system_lookup(name: &str, timeout: Duration) -> Result<(u64, Vec<IpAddr>)> {
+	let start = Instant::now();
+	let addresses = tokio::time::timeout(timeout, tokio::net::lookup_host((name, 0)))
+		.await
+		.map_err(|_| anyhow!("timed out after {}s", timeout.as_secs()))??;
+	let elapsed = start.elapsed().as_millis() as u64;
+	let mut ips: Vec<IpAddr> = addresses.map(|address| address.ip()).collect();
+	ips.dedup();
+	Ok((elapsed, ips))
+}
+
+/// Send one recursive query over UDP and parse the answer
+pub async fn query(server: SocketAddr, name: &str, record_type: RecordType, timeout: Duration) -> Result<DnsResponse> {
+	let id: u16 = rand::random();
+	let packet = encode_query(id, name, record_type)?;
+
+	let bind: SocketAddr = if server.is_ipv4() {
+		(Ipv4Addr::UNSPECIFIED, 0).into()
+	} else {
+		(Ipv6Addr::UNSPECIFIED, 0).into()
+	};
+	let socket = UdpSocket::bind(bind).await?;
+	socket.connect(server).await?;
+	socket.send(&packet).await?;
+
+	let mut buffer = [0u8; 4096];
+	let deadline = tokio::time::Instant::now() + timeout;
+	loop {
+		let len = tokio::time::timeout_at(deadline, socket.recv(&mut buffer))
+			.await
+			.map_err(|_| anyhow!("no answer from {} within {}s", server, timeout.as_secs()))??;
+		let message = &buffer[..len];
+		// Ignore stray datagrams that answer some other query
+		if message.len() >= 2 && u16::from_be_bytes([message[0], message[1]]) == id {
+			return decode_response(message, record_type);
+		}
+		debug!("Ignoring DNS datagram with unexpected id from {}", server);
+	}
+}
+
+fn encode_query(id: u16, name: &str, record_type: RecordType) -> Result<Vec<u8>> {
+	let mut packet = Vec::with_capacity(512);
+	packet.extend_from_slice(&id.to_be_bytes());
+	// Standard query with recursion desired, one question
+	packet.extend_from_slice(&[0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+	for label in name.trim_end_matches('.').split('.') {
+		if label.is_empty() || label.len() > 63 {
+			return Err(anyhow!("Invalid domain name '{}'", name));
+		}
+		packet.push(label.len() as u8);
+		packet.extend_from_slice(label.as_bytes());
+	}
+	packet.push(0);
+	packet.extend_from_slice(&record_type.code().to_be_bytes());
+	packet.extend_from_slice(&1u16.to_be_bytes());
+	Ok(packet)
+}
+
+fn decode_response(message: &[u8], record_type: RecordType) -> Result<DnsResponse> {
+	let message = DnsMessage::parse(message)?;
+	let mut answers = Vec::new();
+	let mut min_ttl: Option<u32> = None;
+	// CNAMEs followed on the way to the requested type are not answers
+	for record in message.answers.iter().filter(|record| record.kind == record_type.code()) {
+		answers.push(record.data.clone());
+		min_ttl = Some(min_ttl.map_or(record.ttl, |min| min.min(record.ttl)));
+	}
+	Ok(DnsResponse { rcode: message.rcode_name(), truncated: message.truncated, answers, min_ttl })
+}
+
+/// A decoded DNS message, as sent by `query` or seen on the wire by the monitor
+#[derive(Debug, Clone)]
+pub struct DnsMessage {
+	pub id: u16,
+	pub is_response: bool,
+	pub rcode: u8,
+	pub truncated: bool,
+	/// Name and type of every question
+	pub questions: Vec<(String, u16)>,
+	pub answers: Vec<ResourceRecord>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResourceRecord {
+	pub name: String,
+	pub kind: u16,
+	pub ttl: u32,
+	/// The record data rendered as text, e.g. an address or a target name
+	pub data: String,
+}
+
+impl ResourceRecord {
+	/// The address of an A or AAAA record
+	pub fn address(&self) -> Option<IpAddr> {
+		match self.kind {
+			1 | 28 => self.data.parse().ok(),
+			_ => None,
+		}
+	}
+}
+
+impl DnsMessage {
+	pub fn parse(message: &[u8]) -> Result<Self> {
+		let truncated_message = || anyhow!("truncated DNS message");
+		let header = message.get(..12).ok_or_else(truncated_message)?;
+		let flags = u16::from_be_bytes([header[2], header[3]]);
+		let question_count = u16::from_be_bytes([header[4], header[5]]);
+		let answer_count = u16::from_be_bytes([header[6], header[7]]);
+
+		let mut offset = 12;
+		let mut questions = Vec::new();
+		for _ in 0..question_count {
+			let name = read_name(message, offset)?;
+			offset = skip_name(message, offset)?;
+			let kind = message.get(offset..offset + 2).ok_or_else(truncated_message)?;
+			questions.push((name, u16::from_be_bytes([kind[0], kind[1]])));
+			offset += 4;
+		}
+
+		let mut answers = Vec::new();
+		for _ in 0..answer_count {
+			let name = read_name(message, offset)?;
+			offset = skip_name(message, offset)?;
+			let fixed = message.get(offset..offset + 10).ok_or_else(truncated_message)?;
+			let kind = u16::from_be_bytes([fixed[0], fixed[1]]);
+			let ttl = u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]);
+			let length = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+			let data_start = offset + 10;
+			let data = message.get(data_start..data_start + length).ok_or_else(truncated_message)?;
+			offset = data_start + length;
+
+			let data = match kind {
+				1 if data.len() == 4 => Ipv4Addr::new(data[0], data[1], data[2], data[3]).to_string(),
+				28 if data.len() == 16 => Ipv6Addr::from(<[u8; 16]>::try_from(data).expect("length checked")).to_string(),
+				2 | 5 => read_name(message, data_start)?,
+				15 if data.len() > 2 => format!("{} {}", u16::from_be_bytes([data[0], data[1]]), read_name(message, data_start + 2)?),
+				16 => read_character_strings(data),
+				1 | 28 | 15 => return Err(anyhow!("malformed record of type {}", kind)),
+				_ => format!("<{} bytes>", data.len()),
+			};
+			answers.push(ResourceRecord { name, kind, ttl, data });
+		}
+
+		Ok(Self {
+			id: u16::from_be_bytes([header[0], header[1]]),
+			is_response: flags & 0x8000 != 0,
+			rcode: (flags & 0x000f) as u8,
+			truncated: flags & 0x0200 != 0,
+			questions,
+			answers,
+		})
+	}
+
+	pub fn rcode_name(&self) -> String {
+		match self.rcode {
+			0 => "NOERROR".to_string(),
+			1 => "FORMERR".to_string(),
+			2 => "SERVFAIL".to_string(),
+			3 => "NXDOMAIN".to_string(),
+			4 => "NOTIMP".to_string(),
+			5 => "REFUSED".to_string(),
+			other => format!("RCODE{}", other),
+		}
+	}
+}
+
+/// Name of a record type code, e.g. `AAAA` for 28
+pub fn type_name(kind: u16) -> String {
+	match kind {
+		1 => "A".to_string(),
+		2 => "NS".to_string(),
+		5 => "CNAME".to_string(),
+		15 => "MX".to_string(),
+		16 => "TXT".to_string(),
+		28 => "AAAA".to_string(),
+		65 => "HTTPS".to_string(),
+		other => format!("TYPE{}", other),
+	}
+}
+
+/// Host names learned from DNS answers seen by the monitor, by address, so
+/// that traffic to an IP can be shown with the name that was looked up
+#[derive(Debug, Default)]
+pub struct HostnameCache {
+	names: std::collections::HashMap<IpAddr, String>,
+	/// Insertion order, to evict the oldest entries first
+	order: std::collections::VecDeque<IpAddr>,
+}
+
+impl HostnameCache {
+	const CAPACITY: usize = 10_000;
+
+	/// Remember every address in the answers of `message` under the name that
+	/// was asked for (not the CNAME target it was resolved through)
+	pub fn record(&mut self, message: &DnsMessage) {
+		for record in &message.answers {
+			let Some(address) = record.address() else { continue };
+			let name = message.questions.first().map_or(&record.name, |(name, _)| name).clone();
+			if self.names.insert(address, name).is_none() {
+				self.order.push_back(address);
+			}
+			if self.order.len() > Self::CAPACITY
+				&& let Some(oldest) = self.order.pop_front()
+			{
+				self.names.remove(&oldest);
+			}
+		}
+	}
+
+	pub fn lookup(&self, address: &str) -> Option<&str> {
+		self.names.get(&address.parse().ok()?).map(String::as_str)
+	}
+}
+
+/// Offset just past the (possibly compressed) name starting at `offset`
+fn skip_name(message: &[u8], mut offset: usize) -> Result<usize> {
+	loop {
+		let len = *message.get(offset).ok_or_else(|| anyhow!("truncated DNS name"))?;
+		match len {
+			0 => return Ok(offset + 1),
+			_ if len & 0xc0 == 0xc0 => return Ok(offset + 2),
+			_ => offset += 1 + len as usize,
+		}
+	}
+}
+
+fn read_name(message: &[u8], mut offset: usize) -> Result<String> {
+	let mut labels = Vec::new();
+	// Bound the number of compression pointers followed so loops terminate
+	for _ in 0..128 {
+		let len = *message.get(offset).ok_or_else(|| anyhow!("truncated DNS name"))? as usize;
+		if len == 0 {
+			return Ok(labels.join("."));
+		}
+		if len & 0xc0 == 0xc0 {
+			let low = *message.get(offset + 1).ok_or_else(|| anyhow!("truncated DNS name"))? as usize;
+			offset = ((len & 0x3f) << 8) | low;
+			continue;
+		}
+		let label = message.get(offset + 1..offset + 1 + len).ok_or_else(|| anyhow!("truncated DNS name"))?;
+		labels.push(String::from_utf8_lossy(label).into_owned());
+		offset += 1 + len;
+	}
+	Err(anyhow!("DNS name compression loop"))
+}
+
+fn read_character_strings(data: &[u8]) -> String {
+	let mut parts = Vec::new();
+	let mut offset = 0;
+	while let Some(&len) = data.get(offset) {
+		let end = (offset + 1 + len as usize).min(data.len());
+		parts.push(String::from_utf8_lossy(&data[offset + 1..end]).into_owned());
+		offset = end;
+	}
+	format!("\"{}\"", parts.concat())
+}
+
+pub fn print_report(name: &str, reports: &[ResolverReport]) {
+	for report in reports {
+		println!("Resolver {}", report.server);
+		for query in &report.queries {
+			let timing = match (query.average_ms(), query.times_ms.iter().min(), query.times_ms.iter().max()) {
+				(Some(average), Some(min), Some(max)) => format!("{:>6.1}ms (min {}ms, max {}ms)", average, min, max),
+				_ => format!("{:>8}", "failed"),
+			};
+			let result = match &query.response {
+				Ok(response) if response.answers.is_empty() => format!("{} (no {} records)", response.rcode, query.record_type.name()),
+				Ok(response) => format!("{} {}{}",
+					response.rcode,
+					response.answers.join(", "),
+					response.min_ttl.map(|ttl| format!(" (ttl {}s)", ttl)).unwrap_or_default()),
+				Err(e) => e.clone(),
+			};
+			let truncated = matches!(&query.response, Ok(r) if r.truncated);
+			println!("  {:<6} {}  {}{}", query.record_type.name(), timing, result,
+				if truncated { " ⚠️ truncated (UDP)" } else { "" });
+		}
+		println!();
+	}
+
+	let mut ranked: Vec<(SocketAddr, Option<f64>)> = reports.iter().map(|r| (r.server, r.average_ms())).collect();
+	ranked.sort_by(|a, b| a.1.unwrap_or(f64::MAX).total_cmp(&b.1.unwrap_or(f64::MAX)));
+	println!("=== RESOLVER SUMMARY: {} ===", name);
+	for (server, average) in &ranked {
+		match average {
+			Some(average) if *average > SLOW_RESOLVER_MS => println!("  {:<40} {:>7.1}ms ⚠️ slow", server.to_string(), average),
+			Some(average) => println!("  {:<40} {:>7.1}ms", server.to_string(), average),
+			None => println!("  {:<40} ❌ no answers", server.to_string()),
+		}
+	}
+	if let [(fastest, Some(best)), .., (slowest, Some(worst))] = ranked.as_slice()
+		&& *worst > best * 2.0 && worst - best > 20.0 {
+		println!("\n{} is {:.1}x slower than {}", slowest, worst / best.max(1.0), fastest);
+	}
+}
@@ -0,0 +1,218 @@
+use crate::config::{self, Config};
+use std::path::Path;
+use std::time::Duration;
+
+const CONNECTIVITY_HOST: &str = "example.com:443";
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CheckStatus {
+	Ok,
+	Warn,
+	Fail,
+}
+
+#[derive(Debug, Clone)]
+pub struct Check {
+	pub name: &'static str,
+	pub status: CheckStatus,
+	pub detail: String,
+	/// What the user can do about a warning or failure
+	pub fix: Option<String>,
+}
+
+impl Check {
+	fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+		Self { name, status: CheckStatus::Ok, detail: detail.into(), fix: None }
+	}
+
+	fn warn(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+		Self { name, status: CheckStatus::Warn, detail: detail.into(), fix: Some(fix.into()) }
+	}
+
+	fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+		Self { name, status: CheckStatus::Fail, detail: detail.into(), fix: Some(fix.into()) }
+	}
+}
+
+/// Run every environment check against `config`
+pub async fn run_checks(config: &Config) -> Vec<Check> {
+	let mut checks = vec![
+		check_pcap(),
+		check_privileges(),
+		check_interface(&config.network.interface),
+		check_filter(&config.network.monitor_filter),
+		check_writable("Request log", &config.storage.request_log_path),
+		check_writable("Cookie store", &config.storage.cookie_cache_path),
+		check_proxy_port(config).await,
+	];
+	checks.extend(check_network().await);
+	checks
+}
+
+pub fn print_checks(checks: &[Check]) {
+	for check in checks {
+		let marker = match check.status {
+			CheckStatus::Ok => "✅",
+			CheckStatus::Warn => "⚠️ ",
+			CheckStatus::Fail => "❌",
+		};
+		println!("{} {:<16} {}", marker, check.name, check.detail);
+		if let Some(ref fix) = check.fix {
+			println!("   {:<16} → {}", "", fix);
+		}
+	}
+}
+
+/// Privilege and interface-name hints printed before monitoring starts, so
+/// `monitor` and `doctor` give the same advice
+pub fn monitor_warnings(interface: &str) -> Vec<String> {
+	let mut warnings = Vec::new();
+
+	if let Some(hint) = privilege_hint() {
+		warnings.push(hint);
+	}
+
+	if interface == "en0" && cfg!(target_os = "windows") {
+		warnings.push("在Windows上默认使用'en0'接口名称可能无效。建议使用--interface参数指定正确的接口名称。\n\
+			常见Windows网络接口名称通常是UUID格式，例如'\\Device\\NPF_{GUID}'\n\
+			请运行 'riddler monitor --help' 获取更多信息".to_string());
+	}
+	if interface == "en0" && cfg!(target_os = "linux") {
+		warnings.push("在Linux上默认使用'en0'接口名称可能无效。建议使用--interface参数指定正确的接口名称。\n\
+			常见Linux网络接口名称: 'eth0', 'wlan0', 'ens33' 等。\n\
+			可以通过'ip link'命令查看系统上的可用接口".to_string());
+	}
+
+	warnings
+}
+
+#[cfg(unix)]
+fn privilege_hint() -> Option<String> {
+	if !cfg!(target_os = "macos") && unsafe { libc::geteuid() } != 0 {
+		Some("在 Linux 上监控网络通常需要 root 权限！请使用 sudo 运行此命令，\n\
+			或授予抓包能力: sudo setcap cap_net_raw,cap_net_admin=eip <riddler 可执行文件>".to_string())
+	} else {
+		None
+	}
+}
+
+#[cfg(not(unix))]
+fn privilege_hint() -> Option<String> {
+	None
+}
+
+fn check_pcap() -> Check {
+	match pcap::Device::list() {
+		Ok(devices) if devices.is_empty() => Check::warn(
+			"pcap",
+			"libpcap loaded but no capture devices are visible",
+			"Run with sudo; unprivileged users often see no devices",
+		),
+		Ok(devices) => Check::ok("pcap", format!("libpcap available, {} devices visible", devices.len())),
+		Err(e) => Check::fail("pcap", format!("Cannot list devices: {}", e), pcap_install_hint()),
+	}
+}
+
+fn pcap_install_hint() -> &'static str {
+	match std::env::consts::OS {
+		"windows" => "Install Npcap (https://npcap.com) with 'WinPcap API-compatible mode' enabled",
+		"macos" => "libpcap ships with macOS; run with sudo or install Wireshark's ChmodBPF helper",
+		_ => "Install libpcap (e.g. 'sudo apt install libpcap-dev' or 'sudo dnf install libpcap-devel')",
+	}
+}
+
+fn check_privileges() -> Check {
+	match privilege_hint() {
+		None => Check::ok("Privileges", "Sufficient for packet capture"),
+		Some(_) if cfg!(target_os = "linux") => Check::warn(
+			"Privileges",
+			"Not running as root; capture may fail with 'permission denied'",
+			"Use sudo, or 'sudo setcap cap_net_raw,cap_net_admin=eip $(which Riddler)'",
+		),
+		Some(hint) => Check::warn("Privileges", "Capture may need elevated privileges", hint),
+	}
+}
+
+fn check_interface(interface: &str) -> Check {
+	if config::interface_exists(interface) {
+		return Check::ok("Interface", format!("'{}' exists", interface));
+	}
+
+	let available = config::list_available_interfaces();
+	let fix = if available.is_empty() {
+		"Pass --interface <name>; no interfaces could be listed (see pcap/privileges above)".to_string()
+	} else {
+		format!("Pass --interface with one of: {}", available.join(", "))
+	};
+	Check::fail("Interface", format!("Default interface '{}' not found", interface), fix)
+}
+
+fn check_filter(filter: &str) -> Check {
+	match config::validate_bpf_filter(filter) {
+		Ok(()) => Check::ok("BPF filter", format!("'{}' compiles", filter)),
+		Err(e) => Check::fail("BPF filter", format!("'{}' does not compile: {}", filter, e),
+			"Fix the --filter expression (see 'man pcap-filter')"),
+	}
+}
+
+/// Verify a file can be appended to, or created if missing, without
+/// modifying existing content
+fn check_writable(name: &'static str, path: &str) -> Check {
+	let file_path = Path::new(path);
+	let result = if file_path.exists() {
+		std::fs::OpenOptions::new().append(true).open(file_path).map(|_| ())
+	} else {
+		let dir = file_path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+		let probe = dir.join(format!(".riddler-doctor-{}", std::process::id()));
+		std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe))
+	};
+
+	match result {
+		Ok(()) => Check::ok(name, format!("{} is writable", path)),
+		Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => Check::fail(
+			name,
+			format!("{} is not writable", path),
+			"Fix ownership (files created under sudo belong to root: 'sudo chown $USER <file>') or use --path",
+		),
+		Err(e) => Check::fail(name, format!("{}: {}", path, e), "Create the directory or choose another path"),
+	}
+}
+
+async fn check_proxy_port(config: &Config) -> Check {
+	let address = (config.proxy.bind_address, config.proxy.bind_port);
+	match tokio::net::TcpListener::bind(address).await {
+		Ok(_) => Check::ok("Proxy port", format!("{}:{} is free", address.0, address.1)),
+		Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => Check::warn(
+			"Proxy port",
+			format!("{}:{} is already in use", address.0, address.1),
+			"Stop the other process or start the proxy with --port <other>",
+		),
+		Err(e) => Check::warn(
+			"Proxy port",
+			format!("Cannot bind {}:{}: {}", address.0, address.1, e),
+			"Ports below 1024 need root; choose a higher --port",
+		),
+	}
+}
+
+async fn check_network() -> Vec<Check> {
+	let resolved = tokio::time::timeout(NETWORK_TIMEOUT, tokio::net::lookup_host(CONNECTIVITY_HOST)).await;
+	let addr = match resolved {
+		Ok(Ok(mut addrs)) => match addrs.next() {
+			Some(addr) => addr,
+			None => return vec![Check::fail("DNS", format!("{} resolved to no addresses", CONNECTIVITY_HOST), "Check /etc/resolv.conf or your DNS server")],
+		},
+		Ok(Err(e)) => return vec![Check::fail("DNS", format!("Cannot resolve {}: {}", CONNECTIVITY_HOST, e), "Check your DNS settings or network connection")],
+		Err(_) => return vec![Check::fail("DNS", format!("Resolving {} timed out", CONNECTIVITY_HOST), "Check your DNS server is reachable")],
+	};
+
+	let dns = Check::ok("DNS", format!("{} → {}", CONNECTIVITY_HOST, addr.ip()));
+	let connectivity = match tokio::time::timeout(NETWORK_TIMEOUT, tokio::net::TcpStream::connect(addr)).await {
+		Ok(Ok(_)) => Check::ok("Connectivity", format!("TCP connection to {} succeeded", addr)),
+		Ok(Err(e)) => Check::fail("Connectivity", format!("Cannot connect to {}: {}", addr, e), "Check firewall rules or whether an HTTP proxy is required"),
+		Err(_) => Check::fail("Connectivity", format!("Connecting to {} timed out", addr), "Check firewall rules or whether an HTTP proxy is required"),
+	};
+
+	vec![dns, connectivity]
+}
@@ -0,0 +1,199 @@
+use crate::hosts::format_bytes;
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo};
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+	pub output: PathBuf,
+	/// Continue a partial file with a `Range` request instead of starting over
+	pub resume: bool,
+}
+
+pub struct DownloadSummary {
+	/// Status and headers of the response; the body is on disk, not here
+	pub response: HttpResponseInfo,
+	/// Bytes already on disk that the server let us skip
+	pub resumed_from: u64,
+	/// Bytes received in this run
+	pub downloaded: u64,
+	pub sha256: String,
+}
+
+impl DownloadSummary {
+	pub fn file_size(&self) -> u64 {
+		self.resumed_from + self.downloaded
+	}
+}
+
+/// Stream the response to `request` into `options.output`. A failed download
+/// leaves the partial file in place so it can be continued with `resume`.
+pub async fn download(
+	http_client: &HttpClient,
+	mut request: HttpRequestBuilder,
+	options: &DownloadOptions,
+) -> Result<DownloadSummary> {
+	let start_time = Instant::now();
+	let existing = if options.resume {
+		tokio::fs::metadata(&options.output).await.map(|m| m.len()).unwrap_or(0)
+	} else {
+		0
+	};
+	if existing > 0 {
+		request.headers.retain(|name, _| !name.eq_ignore_ascii_case("range"));
+		request.headers.insert("Range".to_string(), format!("bytes={}-", existing));
+		info!("Resuming {} at byte {}", options.output.display(), existing);
+	}
+
+	let mut response = http_client.send_streaming(&request).await?;
+	let mut head = HttpResponseInfo::from_head(&response, 0);
+
+	let (offset, total) = match head.status {
+		// The file is already complete
+		416 if existing > 0 => {
+			head.response_time_ms = start_time.elapsed().as_millis() as u64;
+			return Ok(DownloadSummary {
+				response: head,
+				resumed_from: existing,
+				downloaded: 0,
+				sha256: format!("{:x}", hash_contents(&options.output, Sha256::new()).await?.finalize()),
+			});
+		}
+		206 if existing > 0 => {
+			let (start, total) = head.headers.get("content-range")
+				.and_then(|range| parse_content_range(range))
+				.ok_or_else(|| anyhow!("Partial response without a usable Content-Range header"))?;
+			if start != existing {
+				return Err(anyhow!("Server resumed at byte {} instead of {}; rerun without --resume", start, existing));
+			}
+			(existing, total)
+		}
+		200..=299 => {
+			if existing > 0 {
+				warn!("Server ignored the range request, downloading {} from the start", options.output.display());
+			}
+			(0, response.content_length())
+		}
+		status => return Err(anyhow!("Download failed with HTTP {}, nothing was written", status)),
+	};
+
+	let (mut file, mut hasher) = if offset > 0 {
+		let hasher = hash_contents(&options.output, Sha256::new()).await?;
+		let file = OpenOptions::new().append(true).open(&options.output).await?;
+		(file, hasher)
+	} else {
+		let file = File::create(&options.output).await
+			.with_context(|| format!("Unable to create {}", options.output.display()))?;
+		(file, Sha256::new())
+	};
+
+	// A stall of a whole timeout period fails the download; slow but steady transfers do not
+	let idle_timeout = Duration::from_secs(request.timeout_seconds.max(5));
+	let mut progress = Progress::new(offset, total);
+	let mut downloaded = 0u64;
+	while let Some(chunk) = tokio::time::timeout(idle_timeout, response.chunk())
+		.await
+		.map_err(|_| anyhow!("Download stalled: no data for {} seconds", idle_timeout.as_secs()))??
+	{
+		file.write_all(&chunk).await?;
+		hasher.update(&chunk);
+		downloaded += chunk.len() as u64;
+		progress.update(offset + downloaded);
+	}
+	file.flush().await?;
+	progress.finish(offset + downloaded);
+
+	if let Some(total) = total
+		&& offset + downloaded < total {
+		return Err(anyhow!("Connection closed after {} of {} bytes", offset + downloaded, total));
+	}
+
+	head.response_time_ms = start_time.elapsed().as_millis() as u64;
+	Ok(DownloadSummary {
+		response: head,
+		resumed_from: offset,
+		downloaded,
+		sha256: format!("{:x}", hasher.finalize()),
+	})
+}
+
+/// `bytes START-END/TOTAL` to `(START, TOTAL)`; TOTAL may be `*`
+fn parse_content_range(value: &str) -> Option<(u64, Option<u64>)> {
+	let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+	let start = range.split_once('-')?.0.trim().parse().ok()?;
+	Some((start, total.trim().parse().ok()))
+}
+
+/// Feed the current contents of `path` into `hasher`
+async fn hash_contents(path: &Path, mut hasher: Sha256) -> Result<Sha256> {
+	let mut file = File::open(path).await?;
+	let mut buffer = vec![0u8; 64 * 1024];
+	loop {
+		let read = file.read(&mut buffer).await?;
+		if read == 0 {
+			return Ok(hasher);
+		}
+		hasher.update(&buffer[..read]);
+	}
+}
+
+/// One-line progress bar on stderr, redrawn at most ten times a second and
+/// only when stderr is a terminal
+struct Progress {
+	total: Option<u64>,
+	start_bytes: u64,
+	start_time: Instant,
+	last_draw: Option<Instant>,
+	enabled: bool,
+}
+
+impl Progress {
+	fn new(start_bytes: u64, total: Option<u64>) -> Self {
+		Self {
+			total,
+			start_bytes,
+			start_time: Instant::now(),
+			last_draw: None,
+			enabled: std::io::stderr().is_terminal(),
+		}
+	}
+
+	fn update(&mut self, bytes: u64) {
+		if !self.enabled || self.last_draw.is_some_and(|last| last.elapsed() < Duration::from_millis(100)) {
+			return;
+		}
+		self.last_draw = Some(Instant::now());
+		self.draw(bytes);
+	}
+
+	fn finish(&self, bytes: u64) {
+		if self.enabled {
+			self.draw(bytes);
+			eprintln!();
+		}
+	}
+
+	fn draw(&self, bytes: u64) {
+		let elapsed = self.start_time.elapsed().as_secs_f64().max(0.001);
+		let rate = format_bytes(((bytes - self.start_bytes) as f64 / elapsed) as u64);
+		let line = match self.total {
+			Some(total) if total > 0 => {
+				const WIDTH: usize = 30;
+				let ratio = (bytes as f64 / total as f64).min(1.0);
+				let filled = (ratio * WIDTH as f64) as usize;
+				format!("[{}{}] {:>3}% {} / {}  {}/s",
+					"#".repeat(filled), "-".repeat(WIDTH - filled), (ratio * 100.0) as u32,
+					format_bytes(bytes), format_bytes(total), rate)
+			}
+			_ => format!("{}  {}/s", format_bytes(bytes), rate),
+		};
+		eprint!("\r{:<79}", line);
+		let _ = std::io::stderr().flush();
+	}
+}
@@ -0,0 +1,152 @@
+use crate::logger::RequestLogEntry;
+use crate::stats::TrafficStats;
+use std::collections::HashMap;
+
+/// Aggregated traffic for one method + normalized URL path
+#[derive(Debug, Clone)]
+pub struct EndpointStats {
+	pub method: String,
+	/// `host/path` with ids replaced by placeholders, see [`normalize_path`]
+	pub endpoint: String,
+	pub traffic: TrafficStats,
+}
+
+/// Group entries by method and normalized endpoint
+pub fn aggregate(entries: &[RequestLogEntry]) -> Vec<EndpointStats> {
+	let mut endpoints: HashMap<(String, String), EndpointStats> = HashMap::new();
+
+	for entry in entries {
+		let endpoint = match url::Url::parse(&entry.request.url) {
+			Ok(url) => match url.port() {
+				Some(port) => format!("{}:{}{}", url.host_str().unwrap_or(""), port, normalize_path(url.path())),
+				None => format!("{}{}", url.host_str().unwrap_or(""), normalize_path(url.path())),
+			},
+			Err(_) => normalize_path(entry.request.url.split('?').next().unwrap_or("")),
+		};
+		let method = entry.request.method.to_uppercase();
+
+		let stats = endpoints
+			.entry((method.clone(), endpoint.clone()))
+			.or_insert_with(|| EndpointStats {
+				method,
+				endpoint,
+				traffic: TrafficStats::default(),
+			});
+		stats.traffic.record(entry.response.as_ref());
+	}
+
+	endpoints.into_values().collect()
+}
+
+/// Replace path segments that look like identifiers so `/users/42` and
+/// `/users/43` count as one endpoint: numbers become `{id}`, UUIDs `{uuid}`
+/// and long hex strings (hashes, object ids) `{hex}`
+pub fn normalize_path(path: &str) -> String {
+	let segments: Vec<&str> = path
+		.split('/')
+		.map(|segment| match identifier_kind(segment) {
+			Some(IdentifierKind::Number) => "{id}",
+			Some(IdentifierKind::Uuid) => "{uuid}",
+			Some(IdentifierKind::Hex) => "{hex}",
+			None => segment,
+		})
+		.collect();
+
+	let path = segments.join("/");
+	if path.is_empty() { "/".to_string() } else { path }
+}
+
+/// What a path segment that identifies a resource looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdentifierKind {
+	Number,
+	Uuid,
+	/// Hashes and object ids: 16 or more hex digits
+	Hex,
+}
+
+/// The kind of identifier `segment` is, `None` for a literal segment
+pub fn identifier_kind(segment: &str) -> Option<IdentifierKind> {
+	if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+		Some(IdentifierKind::Number)
+	} else if is_uuid(segment) {
+		Some(IdentifierKind::Uuid)
+	} else if segment.len() >= 16 && segment.chars().all(|c| c.is_ascii_hexdigit()) {
+		Some(IdentifierKind::Hex)
+	} else {
+		None
+	}
+}
+
+pub fn is_uuid(segment: &str) -> bool {
+	let groups: Vec<&str> = segment.split('-').collect();
+	groups.len() == 5
+		&& groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+		&& groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Print the `top` most requested endpoints and the `top` slowest by p95
+pub fn print_report(endpoints: Vec<EndpointStats>, top: usize) {
+	if endpoints.is_empty() {
+		println!("No matching log entries");
+		return;
+	}
+
+	let (most_requested, slowest) = rank(endpoints, top);
+	println!("=== Most requested endpoints ===");
+	print_table(&most_requested);
+
+	println!("\n=== Slowest endpoints (p95) ===");
+	print_table(&slowest);
+}
+
+/// The report of [`print_report`] for `--json`
+pub fn json_report(endpoints: Vec<EndpointStats>, top: usize) -> serde_json::Value {
+	let rows = |endpoints: &[EndpointStats]| endpoints.iter()
+		.map(|e| serde_json::json!({
+			"method": e.method,
+			"endpoint": e.endpoint,
+			"requests": e.traffic.requests,
+			"error_rate": e.traffic.error_rate(),
+			"mean_ms": e.traffic.mean_latency(),
+			"p95_ms": e.traffic.p95_latency(),
+		}))
+		.collect::<Vec<_>>();
+	let (most_requested, slowest) = rank(endpoints, top);
+	serde_json::json!({ "most_requested": rows(&most_requested), "slowest": rows(&slowest) })
+}
+
+/// The `top` most requested endpoints and the `top` slowest by p95
+fn rank(mut endpoints: Vec<EndpointStats>, top: usize) -> (Vec<EndpointStats>, Vec<EndpointStats>) {
+	endpoints.sort_by(|a, b| b.traffic.requests.cmp(&a.traffic.requests).then_with(|| a.endpoint.cmp(&b.endpoint)));
+	let most_requested = endpoints[..top.min(endpoints.len())].to_vec();
+
+	endpoints.retain(|e| !e.traffic.latencies_ms.is_empty());
+	endpoints.sort_by_key(|e| std::cmp::Reverse(e.traffic.p95_latency()));
+	endpoints.truncate(top);
+	(most_requested, endpoints)
+}
+
+fn print_table(endpoints: &[EndpointStats]) {
+	let ms = |v: Option<u64>| v.map_or_else(|| "-".to_string(), |v| format!("{}ms", v));
+
+	println!("{:<7} {:<56} {:>6} {:>6} {:>8} {:>8}", "Method", "Endpoint", "Reqs", "Err%", "Avg", "P95");
+	for e in endpoints {
+		println!("{:<7} {:<56} {:>6} {:>5.1}% {:>8} {:>8}",
+			e.method,
+			truncate(&e.endpoint, 56),
+			e.traffic.requests,
+			e.traffic.error_rate(),
+			ms(e.traffic.mean_latency()),
+			ms(e.traffic.p95_latency()));
+	}
+}
+
+fn truncate(text: &str, width: usize) -> String {
+	if text.chars().count() <= width {
+		text.to_string()
+	} else {
+		let head: String = text.chars().take(width - 1).collect();
+		format!("{}…", head)
+	}
+}
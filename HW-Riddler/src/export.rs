@@ -0,0 +1,94 @@
+use crate::logger::RequestLogEntry;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum ExportFormat {
+	Csv,
+	/// A single JSON array
+	Json,
+	/// One JSON object per line
+	Ndjson,
+}
+
+/// Flat, stable view of a log entry for spreadsheets and other tools. Columns
+/// are only ever appended, so existing consumers keep working.
+#[derive(Debug, Serialize)]
+pub struct ExportRow {
+	pub timestamp: String,
+	pub method: String,
+	pub url: String,
+	/// Empty / null when no response was captured
+	pub status: Option<u16>,
+	pub time_ms: Option<u64>,
+	pub source: String,
+}
+
+const CSV_COLUMNS: [&str; 6] = ["timestamp", "method", "url", "status", "time_ms", "source"];
+
+impl From<&RequestLogEntry> for ExportRow {
+	fn from(entry: &RequestLogEntry) -> Self {
+		Self {
+			timestamp: entry.timestamp.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			method: entry.request.method.clone(),
+			url: entry.request.url.clone(),
+			status: entry.response.as_ref().map(|r| r.status),
+			time_ms: entry.response.as_ref().map(|r| r.response_time_ms),
+			source: entry.source.clone(),
+		}
+	}
+}
+
+pub fn render(entries: &[RequestLogEntry], format: &ExportFormat) -> Result<String> {
+	let rows: Vec<ExportRow> = entries.iter().map(ExportRow::from).collect();
+
+	let mut output = match format {
+		ExportFormat::Json => serde_json::to_string_pretty(&rows)?,
+		ExportFormat::Ndjson => rows
+			.iter()
+			.map(serde_json::to_string)
+			.collect::<serde_json::Result<Vec<_>>>()?
+			.join("\n"),
+		ExportFormat::Csv => {
+			let mut lines = vec![CSV_COLUMNS.join(",")];
+			for row in &rows {
+				let fields = [
+					row.timestamp.clone(),
+					row.method.clone(),
+					row.url.clone(),
+					row.status.map_or(String::new(), |s| s.to_string()),
+					row.time_ms.map_or(String::new(), |t| t.to_string()),
+					row.source.clone(),
+				];
+				lines.push(fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+			}
+			lines.join("\n")
+		}
+	};
+
+	if !output.is_empty() {
+		output.push('\n');
+	}
+	Ok(output)
+}
+
+/// Write to `output`, or stdout when no path is given; returns the row count
+pub fn export(entries: &[RequestLogEntry], format: &ExportFormat, output: Option<&Path>) -> Result<usize> {
+	let content = render(entries, format)?;
+	match output {
+		Some(path) => std::fs::write(path, content)
+			.map_err(|e| anyhow!("Unable to write {}: {}", path.display(), e))?,
+		None => print!("{}", content),
+	}
+	Ok(entries.len())
+}
+
+/// RFC 4180 quoting
+fn csv_field(value: &str) -> String {
+	if value.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
@@ -0,0 +1,47 @@
+use crate::hosts::format_bytes;
+use crate::network::{Flow, FlowTable};
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum FlowSort {
+	Bytes,
+	Packets,
+	Duration,
+	/// Most recently active first
+	Recent,
+}
+
+/// The flows of `table` in the requested order, at most `top` of them
+pub fn sorted_flows<'a>(table: &'a FlowTable, sort: &FlowSort, top: usize) -> Vec<&'a Flow> {
+	let mut flows: Vec<&Flow> = table.flows().collect();
+	match sort {
+		FlowSort::Bytes => flows.sort_by_key(|flow| std::cmp::Reverse(flow.bytes())),
+		FlowSort::Packets => flows.sort_by_key(|flow| std::cmp::Reverse(flow.packets())),
+		FlowSort::Duration => flows.sort_by_key(|flow| std::cmp::Reverse(flow.duration())),
+		FlowSort::Recent => flows.sort_by_key(|flow| std::cmp::Reverse(flow.last_seen)),
+	}
+	flows.truncate(top);
+	flows
+}
+
+pub fn print_flows(flows: &[&Flow]) {
+	println!("{:<5} {:<22}    {:<22} {:<12} {:>8} {:>9} {:>9} {:>9}",
+		"Proto", "Client", "Server", "State", "Packets", "Out", "In", "Duration");
+	for flow in flows {
+		println!("{:<5} {:<22} →  {:<22} {:<12} {:>8} {:>9} {:>9} {:>8.1}s",
+			flow.protocol,
+			format!("{}:{}", flow.client.0, flow.client.1),
+			format!("{}:{}", flow.server.0, flow.server.1),
+			flow.state.to_string(),
+			flow.packets(),
+			format_bytes(flow.bytes_out),
+			format_bytes(flow.bytes_in),
+			flow.duration().num_milliseconds() as f64 / 1000.0);
+	}
+}
+
+pub fn print_top_talkers(talkers: &[(String, u64, u64, usize)]) {
+	println!("{:<40} {:>9} {:>9} {:>6}", "Address", "Sent", "Received", "Flows");
+	for (address, sent, received, flows) in talkers {
+		println!("{:<40} {:>9} {:>9} {:>6}", address, format_bytes(*sent), format_bytes(*received), flows);
+	}
+}
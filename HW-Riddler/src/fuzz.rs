@@ -0,0 +1,522 @@
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo, RequestBody};
+use crate::replay::{ReplayTarget, RewriteRules};
+use crate::say;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+/// Headers that describe the connection or the body rather than the request,
+/// and are never mutated or copied into a mutated request
+const TRANSPORT_HEADERS: [&str; 4] = ["host", "content-length", "transfer-encoding", "connection"];
+
+/// Responses differing from the baseline by fewer bytes are never a size anomaly
+const MIN_SIZE_DIFFERENCE: usize = 100;
+
+/// Families of values substituted into a request
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum FuzzGenerator {
+	/// The empty string
+	Empty,
+	/// 1 KiB and 16 KiB strings
+	Long,
+	/// Integer limits, huge and tiny numbers
+	Boundary,
+	/// Quotes, SQL/script/template injection and path traversal probes
+	Special,
+	/// A value of another JSON type: null, boolean, array, object, number, string
+	Types,
+	/// Emoji, right-to-left override, combining characters, NUL and BOM
+	Unicode,
+}
+
+/// Parts of a request that are mutated
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum FuzzTarget {
+	Header,
+	Query,
+	/// Leaves of a JSON request body
+	Body,
+}
+
+#[derive(Debug, Clone)]
+pub struct FuzzOptions {
+	pub generators: Vec<FuzzGenerator>,
+	pub targets: Vec<FuzzTarget>,
+	/// Extra values from `--payloads`, tried everywhere
+	pub payloads: Vec<String>,
+	/// Headers left alone, matched case-insensitively
+	pub skip_headers: Vec<String>,
+	/// Upper bound on mutations per request
+	pub max_mutations: usize,
+	pub concurrency: usize,
+	pub rps: Option<f64>,
+	/// Seconds before a send counts as a timeout
+	pub timeout: u64,
+	/// Relative body size difference to the baseline that is reported
+	pub size_threshold: f64,
+	pub rewrite: RewriteRules,
+	/// List the mutations without sending anything
+	pub dry_run: bool,
+}
+
+/// One changed value in a request
+#[derive(Debug, Clone, Serialize)]
+pub struct Mutation {
+	/// e.g. `header User-Agent`, `query id` or `body $.user.name`
+	pub location: String,
+	/// Generator name, or `custom` for `--payloads`
+	pub generator: String,
+	pub payload: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyKind {
+	/// 5xx where the unmodified request did not get one
+	ServerError,
+	Timeout,
+	/// No response for another reason, e.g. the connection was reset
+	Failed,
+	/// Same status as the baseline but a body of a very different size
+	Size,
+}
+
+impl AnomalyKind {
+	fn label(&self) -> &'static str {
+		match self {
+			AnomalyKind::ServerError => "5xx",
+			AnomalyKind::Timeout => "timeout",
+			AnomalyKind::Failed => "failed",
+			AnomalyKind::Size => "size",
+		}
+	}
+}
+
+/// Response to the unmodified request, which mutated responses are compared with
+#[derive(Debug, Clone, Serialize)]
+pub struct Baseline {
+	pub status: u16,
+	pub size: usize,
+	pub response_time_ms: u64,
+}
+
+/// A mutation whose response deviated from the baseline
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+	pub kind: AnomalyKind,
+	#[serde(flatten)]
+	pub mutation: Mutation,
+	pub status: Option<u16>,
+	pub size: Option<usize>,
+	pub response_time_ms: Option<u64>,
+	pub detail: String,
+}
+
+/// Results for one captured request
+#[derive(Debug, Clone, Serialize)]
+pub struct RequestReport {
+	pub method: String,
+	pub url: String,
+	pub baseline: Option<Baseline>,
+	/// Mutations sent (or, with `--dry-run`, planned)
+	pub sent: usize,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub mutations: Vec<Mutation>,
+	pub findings: Vec<Finding>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+impl FuzzGenerator {
+	fn name(&self) -> &'static str {
+		match self {
+			FuzzGenerator::Empty => "empty",
+			FuzzGenerator::Long => "long",
+			FuzzGenerator::Boundary => "boundary",
+			FuzzGenerator::Special => "special",
+			FuzzGenerator::Types => "types",
+			FuzzGenerator::Unicode => "unicode",
+		}
+	}
+
+	fn payloads(&self) -> Vec<String> {
+		let values: &[&str] = match self {
+			FuzzGenerator::Empty => &[""],
+			FuzzGenerator::Long => return vec!["A".repeat(1024), "A".repeat(16 * 1024)],
+			FuzzGenerator::Boundary => &[
+				"0", "-1", "2147483647", "2147483648", "-2147483649",
+				"9223372036854775808", "1e309", "0.000001", "NaN",
+			],
+			FuzzGenerator::Special => &[
+				"'", "\"", "' OR '1'='1", "<script>alert(1)</script>", "../../../../etc/passwd",
+				"%00", "{{7*7}}", "${7*7}", "; id", "%s%s%s%n",
+			],
+			FuzzGenerator::Types => &["null", "true", "[]", "{}", "0", "\"riddler\""],
+			FuzzGenerator::Unicode => &["💥", "\u{202e}gnp.exe", "Z\u{0351}\u{0345}\u{0364}a\u{0352}", "\u{0}", "\u{feff}value"],
+		};
+		values.iter().map(|value| value.to_string()).collect()
+	}
+
+	/// Whether the payload keeps its JSON type inside a JSON body, e.g. `0`
+	/// as a number rather than the string "0"
+	fn typed(&self) -> bool {
+		matches!(self, FuzzGenerator::Boundary | FuzzGenerator::Types)
+	}
+}
+
+/// Every value tried, with the generator it came from and whether it is typed
+fn payload_set(options: &FuzzOptions) -> Vec<(String, String, bool)> {
+	let mut payloads: Vec<(String, String, bool)> = options.generators.iter()
+		.flat_map(|generator| generator.payloads().into_iter().map(|payload| (generator.name().to_string(), payload, generator.typed())))
+		.collect();
+	payloads.extend(options.payloads.iter().map(|payload| ("custom".to_string(), payload.clone(), false)));
+	payloads
+}
+
+/// The unmodified request as it is sent: rewritten, with the transport
+/// headers left for the client to fill in and the fuzzing timeout
+fn prepare(request: &HttpRequestBuilder, options: &FuzzOptions) -> Result<HttpRequestBuilder> {
+	let mut request = request.clone();
+	options.rewrite.apply(&mut request)?;
+	request.headers.retain(|name, _| !is_transport_header(name));
+	request.timeout_seconds = options.timeout;
+	// A retry would hide exactly the failures being looked for
+	request.retry = Default::default();
+	Ok(request)
+}
+
+fn is_transport_header(name: &str) -> bool {
+	TRANSPORT_HEADERS.iter().any(|header| header.eq_ignore_ascii_case(name))
+}
+
+/// The mutated copies of `request`: query parameters first, then JSON body
+/// leaves, then headers, at most `max_mutations` of them
+fn mutations(request: &HttpRequestBuilder, options: &FuzzOptions) -> Vec<(Mutation, HttpRequestBuilder)> {
+	let payloads = payload_set(options);
+	let mut result = Vec::new();
+
+	if options.targets.contains(&FuzzTarget::Query)
+		&& let Ok(url) = url::Url::parse(&request.url)
+	{
+		let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+		for (index, (name, original)) in pairs.iter().enumerate() {
+			for (generator, payload, _) in &payloads {
+				if payload == original {
+					continue;
+				}
+				let mut mutated_url = url.clone();
+				mutated_url.query_pairs_mut().clear().extend_pairs(
+					pairs.iter().enumerate().map(|(i, (n, v))| (n.as_str(), if i == index { payload.as_str() } else { v.as_str() }))
+				);
+				let mut mutated = request.clone();
+				mutated.url = mutated_url.to_string();
+				result.push((mutation(format!("query {}", name), generator, payload), mutated));
+			}
+		}
+	}
+
+	if options.targets.contains(&FuzzTarget::Body)
+		&& let Some(body) = request.body.as_ref().and_then(RequestBody::as_text)
+		&& let Ok(json) = serde_json::from_str::<Value>(body)
+	{
+		let mut leaves = Vec::new();
+		json_leaves(&json, &mut Vec::new(), &mut leaves);
+		for path in leaves {
+			let pointer = pointer(&path);
+			let original = json.pointer(&pointer);
+			for (generator, payload, typed) in &payloads {
+				let value = if *typed {
+					serde_json::from_str(payload).unwrap_or_else(|_| Value::String(payload.clone()))
+				} else {
+					Value::String(payload.clone())
+				};
+				if original == Some(&value) {
+					continue;
+				}
+				let mut mutated_json = json.clone();
+				if let Some(slot) = mutated_json.pointer_mut(&pointer) {
+					*slot = value;
+				}
+				let mut mutated = request.clone();
+				mutated.body = Some(RequestBody::Text(mutated_json.to_string()));
+				result.push((mutation(format!("body {}", display_path(&path)), generator, payload), mutated));
+			}
+		}
+	}
+
+	if options.targets.contains(&FuzzTarget::Header) {
+		let mut names: Vec<&String> = request.headers.keys()
+			.filter(|name| !is_transport_header(name) && !options.skip_headers.iter().any(|skip| skip.eq_ignore_ascii_case(name)))
+			.collect();
+		names.sort();
+		for name in names {
+			for (generator, payload, _) in &payloads {
+				// The client silently drops values that are not valid in a header
+				if Some(payload) == request.headers.get(name) || reqwest::header::HeaderValue::from_str(payload).is_err() {
+					continue;
+				}
+				let mut mutated = request.clone();
+				mutated.headers.insert(name.clone(), payload.clone());
+				result.push((mutation(format!("header {}", name), generator, payload), mutated));
+			}
+		}
+	}
+
+	result.truncate(options.max_mutations);
+	result
+}
+
+fn mutation(location: String, generator: &str, payload: &str) -> Mutation {
+	Mutation { location, generator: generator.to_string(), payload: payload.to_string() }
+}
+
+/// Step into a JSON document: an object key or an array index
+#[derive(Clone)]
+enum PathSegment {
+	Key(String),
+	Index(usize),
+}
+
+/// Paths of the scalar values in `value`; only the first element of an array
+/// is visited, the others usually share its shape
+fn json_leaves(value: &Value, path: &mut Vec<PathSegment>, leaves: &mut Vec<Vec<PathSegment>>) {
+	match value {
+		Value::Object(map) => {
+			for (key, child) in map {
+				path.push(PathSegment::Key(key.clone()));
+				json_leaves(child, path, leaves);
+				path.pop();
+			}
+		}
+		Value::Array(items) => {
+			if let Some(first) = items.first() {
+				path.push(PathSegment::Index(0));
+				json_leaves(first, path, leaves);
+				path.pop();
+			}
+		}
+		_ => leaves.push(path.clone()),
+	}
+}
+
+/// RFC 6901 pointer for `serde_json::Value::pointer`
+fn pointer(path: &[PathSegment]) -> String {
+	path.iter().map(|segment| match segment {
+		PathSegment::Key(key) => format!("/{}", key.replace('~', "~0").replace('/', "~1")),
+		PathSegment::Index(index) => format!("/{}", index),
+	}).collect()
+}
+
+/// `$.user.tags[0]`
+fn display_path(path: &[PathSegment]) -> String {
+	let mut display = String::from("$");
+	for segment in path {
+		match segment {
+			PathSegment::Key(key) => {
+				display.push('.');
+				display.push_str(key);
+			}
+			PathSegment::Index(index) => display.push_str(&format!("[{}]", index)),
+		}
+	}
+	display
+}
+
+/// Fuzz each target: send it unmodified for a baseline, then every mutation
+/// with at most `concurrency` in flight, and report the deviating responses.
+/// Mutated sends are not written to the request log.
+pub async fn run(targets: Vec<ReplayTarget>, options: FuzzOptions, http_client: Arc<HttpClient>) -> Result<Vec<RequestReport>> {
+	if options.generators.is_empty() && options.payloads.is_empty() {
+		return Err(anyhow!("No generators or payloads to fuzz with"));
+	}
+
+	let started = tokio::time::Instant::now();
+	let mut reports = Vec::new();
+	for (i, target) in targets.iter().enumerate() {
+		let request = prepare(&target.request, &options)?;
+		let planned = mutations(&request, &options);
+		say!("\n=== Fuzzing Request {}: {} {} ({} mutations) ===", i + 1, request.method, request.url, planned.len());
+		if options.targets.contains(&FuzzTarget::Body)
+			&& let Some(body) = request.body.as_ref().and_then(RequestBody::as_text)
+			&& serde_json::from_str::<Value>(body).is_err()
+		{
+			say!("   Body is not JSON (or was truncated in the log), leaving it unchanged");
+		}
+
+		let mut report = RequestReport {
+			method: request.method.clone(),
+			url: request.url.clone(),
+			baseline: None,
+			sent: planned.len(),
+			mutations: Vec::new(),
+			findings: Vec::new(),
+			error: None,
+		};
+
+		if options.dry_run {
+			for (mutation, _) in &planned {
+				say!("   {} ← {} {}", mutation.location, mutation.generator, preview(&mutation.payload));
+			}
+			report.mutations = planned.into_iter().map(|(mutation, _)| mutation).collect();
+			reports.push(report);
+			continue;
+		}
+
+		match http_client.send_request(request.clone()).await {
+			Ok(response) => {
+				let baseline = Baseline { status: response.status, size: response.body.len(), response_time_ms: response.response_time_ms };
+				say!("Baseline: {} ({} bytes, {}ms)", baseline.status, baseline.size, baseline.response_time_ms);
+				report.findings = send_mutations(planned, &baseline, &options, &http_client).await?;
+				report.baseline = Some(baseline);
+			}
+			Err(e) => {
+				// Without a working original there is nothing to compare against
+				say!("❌ Unmodified request failed, skipping: {:#}", e);
+				report.sent = 0;
+				report.error = Some(format!("{:#}", e));
+			}
+		}
+		reports.push(report);
+	}
+
+	if !options.dry_run {
+		print_summary(&reports, started.elapsed());
+	}
+	Ok(reports)
+}
+
+async fn send_mutations(
+	planned: Vec<(Mutation, HttpRequestBuilder)>,
+	baseline: &Baseline,
+	options: &FuzzOptions,
+	http_client: &Arc<HttpClient>,
+) -> Result<Vec<Finding>> {
+	// Same pacing as a scheduled replay: delay rather than burst after a stall
+	let mut ticker = options.rps.map(|rps| {
+		let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rps));
+		ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		ticker
+	});
+	let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+	let mut tasks = JoinSet::new();
+
+	for (mutation, request) in planned {
+		let permit = semaphore.clone().acquire_owned().await?;
+		if let Some(ticker) = ticker.as_mut() {
+			ticker.tick().await;
+		}
+		let http_client = http_client.clone();
+		let baseline = baseline.clone();
+		let size_threshold = options.size_threshold;
+
+		tasks.spawn(async move {
+			let _permit = permit;
+			let result = http_client.send_request(request).await;
+			let finding = classify(&result, &baseline, size_threshold).map(|(kind, detail)| Finding {
+				kind,
+				mutation,
+				status: result.as_ref().ok().map(|response| response.status),
+				size: result.as_ref().ok().map(|response| response.body.len()),
+				response_time_ms: result.as_ref().ok().map(|response| response.response_time_ms),
+				detail,
+			});
+			if let Some(finding) = &finding {
+				say!("⚠️  [{}] {} ← {} {}: {}",
+					finding.kind.label(), finding.mutation.location, finding.mutation.generator, preview(&finding.mutation.payload), finding.detail);
+			}
+			finding
+		});
+	}
+
+	let mut findings = Vec::new();
+	while let Some(result) = tasks.join_next().await {
+		match result {
+			Ok(Some(finding)) => findings.push(finding),
+			Ok(None) => {}
+			Err(e) => tracing::error!("Fuzz task panicked: {}", e),
+		}
+	}
+	Ok(findings)
+}
+
+/// How a response deviates from the baseline, if it does. Size is only
+/// compared when the status matches: an error page for rejected input being
+/// smaller than the real response is expected, not interesting.
+fn classify(result: &Result<HttpResponseInfo>, baseline: &Baseline, size_threshold: f64) -> Option<(AnomalyKind, String)> {
+	let response = match result {
+		Ok(response) => response,
+		Err(e) if is_timeout(e) => return Some((AnomalyKind::Timeout, format!("{:#}", e))),
+		Err(e) => return Some((AnomalyKind::Failed, format!("{:#}", e))),
+	};
+
+	if response.status >= 500 && baseline.status < 500 {
+		return Some((AnomalyKind::ServerError, format!("{} (baseline {})", response.status, baseline.status)));
+	}
+
+	let size = response.body.len();
+	let difference = size.abs_diff(baseline.size);
+	if response.status == baseline.status
+		&& difference >= MIN_SIZE_DIFFERENCE
+		&& difference as f64 > baseline.size.max(1) as f64 * size_threshold
+	{
+		return Some((AnomalyKind::Size, format!("{} bytes (baseline {})", size, baseline.size)));
+	}
+	None
+}
+
+fn is_timeout(error: &anyhow::Error) -> bool {
+	error.chain().any(|cause| {
+		cause.downcast_ref::<reqwest::Error>().is_some_and(reqwest::Error::is_timeout)
+			|| cause.to_string().to_lowercase().contains("timed out")
+	})
+}
+
+/// A payload short and printable enough for one line
+fn preview(payload: &str) -> String {
+	let escaped = payload.escape_debug().to_string();
+	if escaped.chars().count() > 40 {
+		format!("\"{}…\" ({} chars)", escaped.chars().take(32).collect::<String>(), payload.chars().count())
+	} else {
+		format!("\"{}\"", escaped)
+	}
+}
+
+fn print_summary(reports: &[RequestReport], elapsed: Duration) {
+	let sent: usize = reports.iter().map(|report| report.sent).sum();
+	let findings: Vec<&Finding> = reports.iter().flat_map(|report| &report.findings).collect();
+	say!("\nSent {} mutations of {} requests in {:.2}s", sent, reports.len(), elapsed.as_secs_f64());
+
+	if findings.is_empty() {
+		say!("✓ No anomalies: no 5xx, timeouts, failures or unusual response sizes");
+		return;
+	}
+
+	let mut by_kind: BTreeMap<&str, usize> = BTreeMap::new();
+	for finding in &findings {
+		*by_kind.entry(finding.kind.label()).or_default() += 1;
+	}
+	let counts: Vec<String> = by_kind.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+	say!("⚠️  {} anomalies: {}", findings.len(), counts.join(", "));
+
+	for (i, report) in reports.iter().enumerate().filter(|(_, report)| !report.findings.is_empty()) {
+		say!("\n#{} {} {}", i + 1, report.method, report.url);
+		// One line per location and kind, listing the generators that triggered it
+		let mut grouped: BTreeMap<(&str, &str), Vec<&str>> = BTreeMap::new();
+		for finding in &report.findings {
+			let generators = grouped.entry((finding.mutation.location.as_str(), finding.kind.label())).or_default();
+			if !generators.contains(&finding.mutation.generator.as_str()) {
+				generators.push(&finding.mutation.generator);
+			}
+		}
+		for ((location, kind), generators) in grouped {
+			let count = report.findings.iter().filter(|f| f.mutation.location == location && f.kind.label() == kind).count();
+			say!("   {:<8} {:<32} {}× ({})", kind, location, count, generators.join(", "));
+		}
+	}
+}
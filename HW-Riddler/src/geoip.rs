@@ -0,0 +1,103 @@
+use anyhow::{anyhow, Context, Result};
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::Path;
+use tracing::info;
+
+/// Location and network of a remote address
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GeoInfo {
+	/// ISO 3166 country code
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub country: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub city: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub asn: Option<u32>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub organization: Option<String>,
+}
+
+impl std::fmt::Display for GeoInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let mut parts = Vec::new();
+		match (&self.city, &self.country) {
+			(Some(city), Some(country)) => parts.push(format!("{}, {}", city, country)),
+			(None, Some(country)) => parts.push(country.clone()),
+			(Some(city), None) => parts.push(city.clone()),
+			(None, None) => {}
+		}
+		if let Some(asn) = self.asn {
+			parts.push(format!("AS{}", asn));
+		}
+		if let Some(organization) = &self.organization {
+			parts.push(organization.clone());
+		}
+		f.write_str(&parts.join(" "))
+	}
+}
+
+/// MaxMind GeoLite2/GeoIP2 databases given with `--geoip-db`. The City (or
+/// Country) and ASN editions are separate files; either or both may be loaded.
+#[derive(Default)]
+pub struct GeoIp {
+	location: Option<Reader<Vec<u8>>>,
+	asn: Option<Reader<Vec<u8>>>,
+}
+
+impl GeoIp {
+	pub fn open(paths: &[impl AsRef<Path>]) -> Result<Self> {
+		let mut geoip = GeoIp::default();
+		for path in paths {
+			let path = path.as_ref();
+			let reader = Reader::open_readfile(path)
+				.with_context(|| format!("Unable to open GeoIP database {}", path.display()))?;
+			let kind = reader.metadata.database_type.clone();
+			info!("Loaded GeoIP database {} ({})", path.display(), kind);
+			if kind.contains("ASN") {
+				geoip.asn = Some(reader);
+			} else if kind.contains("City") || kind.contains("Country") {
+				geoip.location = Some(reader);
+			} else {
+				return Err(anyhow!("{} is a {} database; expected a City, Country or ASN edition", path.display(), kind));
+			}
+		}
+		Ok(geoip)
+	}
+
+	/// `None` for private and other addresses the databases do not cover
+	pub fn lookup(&self, address: &str) -> Option<GeoInfo> {
+		let address: IpAddr = address.parse().ok()?;
+		let mut geo = GeoInfo::default();
+
+		if let Some(reader) = &self.location
+			&& let Some(city) = found(reader.lookup::<geoip2::City>(address))
+		{
+			geo.country = city.country.and_then(|country| country.iso_code).map(str::to_string);
+			geo.city = city.city
+				.and_then(|city| city.names)
+				.and_then(|names| names.get("en").map(|name| name.to_string()));
+		}
+		if let Some(reader) = &self.asn
+			&& let Some(asn) = found(reader.lookup::<geoip2::Asn>(address))
+		{
+			geo.asn = asn.autonomous_system_number;
+			geo.organization = asn.autonomous_system_organization.map(str::to_string);
+		}
+
+		(geo != GeoInfo::default()).then_some(geo)
+	}
+}
+
+/// Record of a lookup; addresses missing from the database are expected
+fn found<T>(result: Result<T, MaxMindDBError>) -> Option<T> {
+	match result {
+		Ok(record) => Some(record),
+		Err(MaxMindDBError::AddressNotFoundError(_)) => None,
+		Err(e) => {
+			tracing::debug!("GeoIP lookup failed: {}", e);
+			None
+		}
+	}
+}
@@ -0,0 +1,347 @@
+use crate::http_client::{HttpRequestBuilder, HttpResponseInfo, HttpVersion, RequestBody, RetryPolicy};
+use crate::logger::RequestLogEntry;
+use crate::replay::ReplayTarget;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::warn;
+
+/// HTTP Archive 1.2 document (http://www.softwareishard.com/blog/har-12-spec/).
+/// Only the fields riddler reads or writes are modelled.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Har {
+	pub log: HarLog,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarLog {
+	pub version: String,
+	pub creator: HarCreator,
+	#[serde(default)]
+	pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarCreator {
+	pub name: String,
+	pub version: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+	pub started_date_time: String,
+	/// Total elapsed time in milliseconds
+	#[serde(default)]
+	pub time: f64,
+	pub request: HarRequest,
+	pub response: HarResponse,
+	#[serde(default)]
+	pub cache: serde_json::Value,
+	#[serde(default)]
+	pub timings: HarTimings,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarRequest {
+	pub method: String,
+	pub url: String,
+	#[serde(default = "http_version")]
+	pub http_version: String,
+	#[serde(default)]
+	pub cookies: Vec<HarNameValue>,
+	#[serde(default)]
+	pub headers: Vec<HarNameValue>,
+	#[serde(default)]
+	pub query_string: Vec<HarNameValue>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub post_data: Option<HarPostData>,
+	#[serde(default = "unknown_size")]
+	pub headers_size: i64,
+	#[serde(default = "unknown_size")]
+	pub body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarResponse {
+	pub status: u16,
+	#[serde(default)]
+	pub status_text: String,
+	#[serde(default = "http_version")]
+	pub http_version: String,
+	#[serde(default)]
+	pub cookies: Vec<HarNameValue>,
+	#[serde(default)]
+	pub headers: Vec<HarNameValue>,
+	#[serde(default)]
+	pub content: HarContent,
+	#[serde(default, rename = "redirectURL")]
+	pub redirect_url: String,
+	#[serde(default = "unknown_size")]
+	pub headers_size: i64,
+	#[serde(default = "unknown_size")]
+	pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HarNameValue {
+	pub name: String,
+	pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarPostData {
+	#[serde(default)]
+	pub mime_type: String,
+	#[serde(default)]
+	pub text: String,
+	/// Form fields, which some tools record instead of `text`
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub params: Vec<HarParam>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarParam {
+	pub name: String,
+	#[serde(default)]
+	pub value: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+	#[serde(default)]
+	pub size: i64,
+	#[serde(default)]
+	pub mime_type: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub text: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub encoding: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HarTimings {
+	pub send: f64,
+	pub wait: f64,
+	pub receive: f64,
+}
+
+impl Default for HarTimings {
+	fn default() -> Self {
+		Self { send: 0.0, wait: -1.0, receive: 0.0 }
+	}
+}
+
+fn http_version() -> String {
+	"HTTP/1.1".to_string()
+}
+
+fn unknown_size() -> i64 {
+	-1
+}
+
+impl HarEntry {
+	/// Convert a logged exchange. Entries are written when the response
+	/// arrives, so the request started `response_time_ms` earlier.
+	pub fn from_log_entry(entry: &RequestLogEntry) -> Self {
+		let elapsed_ms = entry.response.as_ref().map_or(0, |r| r.response_time_ms);
+		let protocol = entry.response.as_ref()
+			.map(|r| r.http_version.clone())
+			.filter(|version| !version.is_empty())
+			.unwrap_or_else(http_version);
+		let started = entry.timestamp - chrono::Duration::milliseconds(elapsed_ms as i64);
+
+		let request_headers = sorted_pairs(&entry.request.headers);
+		let request_cookies = header_value(&entry.request.headers, "cookie")
+			.map(parse_cookie_pairs)
+			.unwrap_or_default();
+		let query_string = url::Url::parse(&entry.request.url)
+			.map(|url| url.query_pairs().map(|(name, value)| HarNameValue { name: name.into_owned(), value: value.into_owned() }).collect())
+			.unwrap_or_default();
+		let post_data = (!entry.request.body_preview.is_empty()).then(|| HarPostData {
+			mime_type: header_value(&entry.request.headers, "content-type").unwrap_or("").to_string(),
+			text: entry.request.body_preview.clone(),
+			params: Vec::new(),
+		});
+
+		let response = match entry.response {
+			Some(ref response) => HarResponse {
+				status: response.status,
+				status_text: reqwest::StatusCode::from_u16(response.status)
+					.ok()
+					.and_then(|s| s.canonical_reason())
+					.unwrap_or("")
+					.to_string(),
+				http_version: protocol.clone(),
+				cookies: response.cookies
+					.iter()
+					.filter_map(|c| parse_cookie_pairs(c.split(';').next().unwrap_or("")).into_iter().next())
+					.collect(),
+				headers: sorted_pairs(&response.headers),
+				content: HarContent {
+					size: response.body.len() as i64,
+					mime_type: header_value(&response.headers, "content-type").unwrap_or("").to_string(),
+					text: Some(response.body.clone()),
+					encoding: None,
+				},
+				redirect_url: header_value(&response.headers, "location").unwrap_or("").to_string(),
+				headers_size: -1,
+				body_size: response.body.len() as i64,
+			},
+			// HAR requires a response; status 0 marks a request that got none
+			None => HarResponse {
+				status: 0,
+				status_text: String::new(),
+				http_version: http_version(),
+				cookies: Vec::new(),
+				headers: Vec::new(),
+				content: HarContent::default(),
+				redirect_url: String::new(),
+				headers_size: -1,
+				body_size: -1,
+			},
+		};
+
+		Self {
+			started_date_time: started.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+			time: elapsed_ms as f64,
+			request: HarRequest {
+				method: entry.request.method.clone(),
+				url: entry.request.url.clone(),
+				http_version: protocol,
+				cookies: request_cookies,
+				headers: request_headers,
+				query_string,
+				body_size: entry.request.body_preview.len() as i64,
+				post_data,
+				headers_size: -1,
+			},
+			response,
+			cache: serde_json::json!({}),
+			timings: HarTimings { send: 0.0, wait: elapsed_ms as f64, receive: 0.0 },
+			comment: Some(format!("riddler source: {}", entry.source)),
+		}
+	}
+}
+
+/// Write `entries` as a HAR 1.2 file
+pub fn export(entries: &[RequestLogEntry], output: &Path) -> Result<usize> {
+	let har = Har {
+		log: HarLog {
+			version: "1.2".to_string(),
+			creator: HarCreator {
+				name: "HW-Riddler".to_string(),
+				version: env!("CARGO_PKG_VERSION").to_string(),
+			},
+			entries: entries.iter().map(HarEntry::from_log_entry).collect(),
+		},
+	};
+
+	std::fs::write(output, serde_json::to_string_pretty(&har)?)
+		.map_err(|e| anyhow!("Unable to write {}: {}", output.display(), e))?;
+	Ok(har.log.entries.len())
+}
+
+/// Read a HAR file, e.g. one saved from browser dev tools, into replayable
+/// requests in file order. Non-HTTP entries (data:, ws:, extensions) are skipped.
+pub fn replay_targets(path: &Path) -> Result<Vec<ReplayTarget>> {
+	let content = std::fs::read_to_string(path)
+		.map_err(|e| anyhow!("Unable to read {}: {}", path.display(), e))?;
+	let har: Har = serde_json::from_str(&content)
+		.map_err(|e| anyhow!("{} is not a valid HAR file: {}", path.display(), e))?;
+
+	let mut targets = Vec::new();
+	for entry in har.log.entries {
+		let request = entry.request;
+		if !(request.url.starts_with("http://") || request.url.starts_with("https://")) {
+			warn!("Skipping non-HTTP HAR entry {}", request.url);
+			continue;
+		}
+
+		// HTTP/2 pseudo-headers and framing headers are recomputed when sending
+		let headers = request.headers
+			.into_iter()
+			.filter(|h| !h.name.starts_with(':'))
+			.filter(|h| !["content-length", "host", "connection"].contains(&h.name.to_lowercase().as_str()))
+			.map(|h| (h.name, h.value))
+			.collect();
+
+		let body = request.post_data.and_then(|post| {
+			if !post.text.is_empty() {
+				Some(post.text)
+			} else if !post.params.is_empty() {
+				let mut form = url::form_urlencoded::Serializer::new(String::new());
+				for param in &post.params {
+					form.append_pair(&param.name, param.value.as_deref().unwrap_or(""));
+				}
+				Some(form.finish())
+			} else {
+				None
+			}
+		});
+
+		let captured_at = chrono::DateTime::parse_from_rfc3339(&entry.started_date_time)
+			.map(|t| t.with_timezone(&chrono::Utc))
+			.unwrap_or_else(|_| chrono::Utc::now());
+
+		targets.push(ReplayTarget {
+			request: HttpRequestBuilder {
+				method: request.method,
+				url: request.url,
+				headers,
+				body: body.map(RequestBody::Text),
+				timeout_seconds: 30,
+				follow_redirects: true,
+				verify_ssl: true,
+				retry: RetryPolicy::default(),
+				auth: None,
+				http_version: HttpVersion::Auto,
+			},
+			captured_at,
+			original: (entry.response.status != 0).then(|| HttpResponseInfo {
+				status: entry.response.status,
+				headers: entry.response.headers.into_iter().map(|h| (h.name, h.value)).collect(),
+				body: entry.response.content.text.unwrap_or_default(),
+				cookies: Vec::new(),
+				response_time_ms: entry.time.max(0.0) as u64,
+				final_url: String::new(),
+				attempts: Vec::new(),
+				http_version: entry.response.http_version,
+				remote_addr: None,
+			}),
+		});
+	}
+
+	Ok(targets)
+}
+
+fn sorted_pairs(headers: &HashMap<String, String>) -> Vec<HarNameValue> {
+	let mut pairs: Vec<HarNameValue> = headers
+		.iter()
+		.map(|(name, value)| HarNameValue { name: name.clone(), value: value.clone() })
+		.collect();
+	pairs.sort_by(|a, b| a.name.cmp(&b.name));
+	pairs
+}
+
+fn header_value<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+	headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
+
+/// `a=1; b=2` → name/value pairs
+fn parse_cookie_pairs(header: &str) -> Vec<HarNameValue> {
+	header
+		.split(';')
+		.filter_map(|pair| {
+			let (name, value) = pair.trim().split_once('=')?;
+			Some(HarNameValue { name: name.trim().to_string(), value: value.trim().to_string() })
+		})
+		.collect()
+}
@@ -0,0 +1,383 @@
+use crate::logger::RequestLogEntry;
+use crate::network::{HttpParser, NetworkPacket};
+use crate::stats::TrafficStats;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Sessions kept in the sessions file; the oldest are dropped beyond this
+const MAX_SESSIONS: usize = 20;
+
+/// How often a running session is written out, so `hosts --session` can
+/// follow a capture that is still going
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(clap::ValueEnum, Clone, Debug)]
+pub enum HostSort {
+	Requests,
+	Errors,
+	Latency,
+	Bytes,
+}
+
+/// Aggregated traffic for one destination host
+#[derive(Debug, Clone)]
+pub struct HostStats {
+	pub host: String,
+	pub traffic: TrafficStats,
+	pub bytes_out: u64,
+	pub bytes_in: u64,
+	pub first_seen: DateTime<Utc>,
+	pub last_seen: DateTime<Utc>,
+}
+
+impl HostStats {
+	fn new(host: String, seen: DateTime<Utc>) -> Self {
+		Self {
+			host,
+			traffic: TrafficStats::default(),
+			bytes_out: 0,
+			bytes_in: 0,
+			first_seen: seen,
+			last_seen: seen,
+		}
+	}
+
+	fn touch(&mut self, seen: DateTime<Utc>) {
+		self.first_seen = self.first_seen.min(seen);
+		self.last_seen = self.last_seen.max(seen);
+	}
+
+	fn merge(&mut self, other: HostStats) {
+		self.traffic.merge(other.traffic);
+		self.bytes_out += other.bytes_out;
+		self.bytes_in += other.bytes_in;
+		self.touch(other.first_seen);
+		self.touch(other.last_seen);
+	}
+}
+
+/// Per-host table built from log entries and, optionally, live captured packets
+#[derive(Default)]
+pub struct HostTable {
+	hosts: HashMap<String, HostStats>,
+	/// Live flows are keyed by server IP until an HTTP request reveals its name
+	flows: HashMap<String, HostStats>,
+	ip_names: HashMap<String, String>,
+}
+
+impl HostTable {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn record_entry(&mut self, entry: &RequestLogEntry) {
+		let Some(host) = url::Url::parse(&entry.request.url)
+			.ok()
+			.and_then(|u| u.host_str().map(str::to_string))
+		else {
+			return;
+		};
+
+		let stats = self.hosts
+			.entry(host.clone())
+			.or_insert_with(|| HostStats::new(host, entry.timestamp));
+		stats.touch(entry.timestamp);
+		stats.traffic.record(entry.response.as_ref());
+		stats.bytes_out += headers_size(&entry.request.headers) + entry.request.body_preview.len() as u64;
+
+		if let Some(ref response) = entry.response {
+			stats.bytes_in += headers_size(&response.headers) + response.body.len() as u64;
+		}
+	}
+
+	/// Account a captured TCP segment to the server side of its connection.
+	/// The endpoint with the lower port is taken to be the server.
+	pub fn record_packet(&mut self, packet: &NetworkPacket) {
+		// Names from DNS answers, until a Host header says otherwise
+		if let Some(message) = HttpParser::parse_dns(packet).filter(|message| message.is_response)
+			&& let Some((name, _)) = message.questions.first()
+		{
+			for address in message.answers.iter().filter_map(|record| record.address()) {
+				self.ip_names.entry(address.to_string()).or_insert_with(|| name.clone());
+			}
+		}
+
+		let outbound = packet.dst_port <= packet.src_port;
+		let server_ip = if outbound { &packet.dst_ip } else { &packet.src_ip };
+		let now = Utc::now();
+
+		let stats = self.flows
+			.entry(server_ip.clone())
+			.or_insert_with(|| HostStats::new(server_ip.clone(), now));
+		stats.touch(now);
+
+		if outbound {
+			stats.bytes_out += packet.payload.len() as u64;
+			if let Some(request) = HttpParser::parse_http_request(packet) {
+				stats.traffic.requests += 1;
+				if let Some(name) = request.headers.iter()
+					.find(|(k, _)| k.eq_ignore_ascii_case("host"))
+					.map(|(_, v)| v.split(':').next().unwrap_or(v).to_string())
+				{
+					self.ip_names.insert(server_ip.clone(), name);
+				}
+			}
+		} else {
+			stats.bytes_in += packet.payload.len() as u64;
+		}
+	}
+
+	/// Merge live flows into the log stats and return the hosts in the requested order
+	pub fn into_sorted(mut self, sort: &HostSort) -> Vec<HostStats> {
+		for (ip, mut flow) in self.flows.drain() {
+			let host = self.ip_names.get(&ip).cloned().unwrap_or(ip);
+			flow.host = host.clone();
+			match self.hosts.get_mut(&host) {
+				Some(stats) => stats.merge(flow),
+				None => {
+					self.hosts.insert(host, flow);
+				}
+			}
+		}
+
+		let mut hosts: Vec<HostStats> = self.hosts.into_values().collect();
+		match sort {
+			HostSort::Requests => hosts.sort_by_key(|h| std::cmp::Reverse(h.traffic.requests)),
+			HostSort::Errors => hosts.sort_by(|a, b| b.traffic.error_rate().total_cmp(&a.traffic.error_rate())),
+			HostSort::Latency => hosts.sort_by_key(|h| std::cmp::Reverse(h.traffic.p95_latency())),
+			HostSort::Bytes => hosts.sort_by_key(|h| std::cmp::Reverse(h.bytes_in + h.bytes_out)),
+		}
+		hosts
+	}
+}
+
+fn headers_size(headers: &HashMap<String, String>) -> u64 {
+	// "Name: value\r\n" per header
+	headers.iter().map(|(k, v)| (k.len() + v.len() + 4) as u64).sum()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+	const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+	let mut value = bytes as f64;
+	let mut unit = 0;
+	while value >= 1024.0 && unit < UNITS.len() - 1 {
+		value /= 1024.0;
+		unit += 1;
+	}
+	if unit == 0 {
+		format!("{}{}", bytes, UNITS[0])
+	} else {
+		format!("{:.1}{}", value, UNITS[unit])
+	}
+}
+
+pub fn print_table(hosts: &[HostStats]) {
+	let ms = |v: Option<u64>| v.map_or_else(|| "-".to_string(), |v| format!("{}ms", v));
+
+	println!("{:<32} {:>6} {:>6} {:>8} {:>8} {:>9} {:>9}  {:<19}  {:<19}",
+		"Host", "Reqs", "Err%", "Mean", "P95", "Out", "In", "First seen", "Last seen");
+	for h in hosts {
+		println!("{:<32} {:>6} {:>5.1}% {:>8} {:>8} {:>9} {:>9}  {:<19}  {:<19}",
+			truncate_host(&h.host, 32),
+			h.traffic.requests,
+			h.traffic.error_rate(),
+			ms(h.traffic.mean_latency()),
+			ms(h.traffic.p95_latency()),
+			format_bytes(h.bytes_out),
+			format_bytes(h.bytes_in),
+			h.first_seen.format("%Y-%m-%d %H:%M:%S"),
+			h.last_seen.format("%Y-%m-%d %H:%M:%S"));
+	}
+}
+
+fn truncate_host(host: &str, width: usize) -> String {
+	if host.chars().count() <= width {
+		host.to_string()
+	} else {
+		let head: String = host.chars().take(width - 1).collect();
+		format!("{}…", head)
+	}
+}
+
+/// Counters of one destination host, updated with every exchange as it is logged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostCounters {
+	pub requests: u64,
+	pub methods: BTreeMap<String, u64>,
+	/// Responses per status code
+	pub statuses: BTreeMap<u16, u64>,
+	pub bytes_out: u64,
+	pub bytes_in: u64,
+	/// Sum of the response times, for the mean
+	pub latency_total_ms: u64,
+}
+
+impl HostCounters {
+	pub fn responses(&self) -> u64 {
+		self.statuses.values().sum()
+	}
+
+	/// Share of answered requests with a 4xx/5xx status, in percent
+	pub fn error_rate(&self) -> f64 {
+		let errors: u64 = self.statuses.iter().filter(|(status, _)| **status >= 400).map(|(_, count)| count).sum();
+		crate::stats::error_rate(errors, self.responses())
+	}
+
+	pub fn mean_latency(&self) -> Option<u64> {
+		match self.responses() {
+			0 => None,
+			responses => Some(self.latency_total_ms / responses),
+		}
+	}
+}
+
+/// Per-host counters of one `monitor` or `proxy` run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureSession {
+	/// Start time as `YYYYMMDD-HHMMSS`
+	pub id: String,
+	pub command: String,
+	pub started: DateTime<Utc>,
+	pub updated: DateTime<Utc>,
+	/// Unset while the run is going on
+	pub finished: Option<DateTime<Utc>>,
+	pub hosts: BTreeMap<String, HostCounters>,
+}
+
+impl CaptureSession {
+	/// The hosts in the requested order
+	pub fn sorted_hosts(&self, sort: &HostSort) -> Vec<(&str, &HostCounters)> {
+		let mut hosts: Vec<(&str, &HostCounters)> = self.hosts.iter().map(|(host, counters)| (host.as_str(), counters)).collect();
+		match sort {
+			HostSort::Requests => hosts.sort_by_key(|(_, h)| std::cmp::Reverse(h.requests)),
+			HostSort::Errors => hosts.sort_by(|(_, a), (_, b)| b.error_rate().total_cmp(&a.error_rate())),
+			HostSort::Latency => hosts.sort_by_key(|(_, h)| std::cmp::Reverse(h.mean_latency())),
+			HostSort::Bytes => hosts.sort_by_key(|(_, h)| std::cmp::Reverse(h.bytes_in + h.bytes_out)),
+		}
+		hosts
+	}
+}
+
+/// Keeps the session of this run as the logger writes exchanges, instead of
+/// `hosts` rebuilding the numbers from the log afterwards
+pub struct SessionRecorder {
+	path: PathBuf,
+	session: Mutex<CaptureSession>,
+	last_saved: Mutex<Instant>,
+}
+
+impl SessionRecorder {
+	pub fn new(path: &str, command: &str) -> Self {
+		let started = Utc::now();
+		Self {
+			path: PathBuf::from(path),
+			session: Mutex::new(CaptureSession {
+				id: started.format("%Y%m%d-%H%M%S").to_string(),
+				command: command.to_string(),
+				started,
+				updated: started,
+				finished: None,
+				hosts: BTreeMap::new(),
+			}),
+			last_saved: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Count one exchange; `response` is the status, size and response time
+	/// when one was received
+	pub fn record(&self, url: &str, method: &str, bytes_out: u64, response: Option<(u16, u64, u64)>) {
+		let Some(host) = url::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string)) else {
+			return;
+		};
+		{
+			let mut session = self.session.lock().unwrap_or_else(|e| e.into_inner());
+			session.updated = Utc::now();
+			let counters = session.hosts.entry(host).or_default();
+			counters.requests += 1;
+			*counters.methods.entry(method.to_uppercase()).or_default() += 1;
+			counters.bytes_out += bytes_out;
+			if let Some((status, bytes_in, latency_ms)) = response {
+				*counters.statuses.entry(status).or_default() += 1;
+				counters.bytes_in += bytes_in;
+				counters.latency_total_ms += latency_ms;
+			}
+		}
+
+		let mut last_saved = self.last_saved.lock().unwrap_or_else(|e| e.into_inner());
+		if last_saved.elapsed() >= SAVE_INTERVAL {
+			*last_saved = Instant::now();
+			if let Err(e) = self.save() {
+				tracing::warn!("Failed to save the capture session: {}", e);
+			}
+		}
+	}
+
+	/// Mark the session finished and write it out
+	pub fn finish(&self) -> Result<()> {
+		self.session.lock().unwrap_or_else(|e| e.into_inner()).finished = Some(Utc::now());
+		self.save()
+	}
+
+	fn save(&self) -> Result<()> {
+		let session = self.session.lock().unwrap_or_else(|e| e.into_inner()).clone();
+		let mut sessions = load_sessions(&self.path)?;
+		match sessions.iter_mut().find(|saved| saved.id == session.id && saved.command == session.command) {
+			Some(saved) => *saved = session,
+			None => sessions.push(session),
+		}
+		let excess = sessions.len().saturating_sub(MAX_SESSIONS);
+		sessions.drain(..excess);
+
+		// Readers never see a half-written file
+		let tmp = self.path.with_extension(format!("{}.tmp", std::process::id()));
+		std::fs::write(&tmp, serde_json::to_string_pretty(&sessions)?)?;
+		std::fs::rename(&tmp, &self.path)?;
+		Ok(())
+	}
+}
+
+/// Sessions saved in `path`, oldest first
+pub fn load_sessions(path: &Path) -> Result<Vec<CaptureSession>> {
+	if !path.exists() {
+		return Ok(Vec::new());
+	}
+	let content = std::fs::read_to_string(path)?;
+	serde_json::from_str(&content).map_err(|e| anyhow!("{} is not a capture sessions file: {}", path.display(), e))
+}
+
+pub fn print_sessions(sessions: &[CaptureSession]) {
+	println!("{:<16} {:<8} {:<19}  {:<19}  {:>6} {:>8}", "Session", "Command", "Started", "Finished", "Hosts", "Reqs");
+	for session in sessions.iter().rev() {
+		println!("{:<16} {:<8} {:<19}  {:<19}  {:>6} {:>8}",
+			session.id,
+			session.command,
+			session.started.format("%Y-%m-%d %H:%M:%S"),
+			session.finished.map_or_else(|| "running".to_string(), |finished| finished.format("%Y-%m-%d %H:%M:%S").to_string()),
+			session.hosts.len(),
+			session.hosts.values().map(|h| h.requests).sum::<u64>());
+	}
+}
+
+pub fn print_session_table(hosts: &[(&str, &HostCounters)]) {
+	let ms = |v: Option<u64>| v.map_or_else(|| "-".to_string(), |v| format!("{}ms", v));
+	let counts = |counts: Vec<String>| if counts.is_empty() { "-".to_string() } else { counts.join(" ") };
+
+	println!("{:<32} {:>6} {:>6} {:>8} {:>9} {:>9}  {:<20}  Statuses",
+		"Host", "Reqs", "Err%", "Mean", "Out", "In", "Methods");
+	for (host, h) in hosts {
+		println!("{:<32} {:>6} {:>5.1}% {:>8} {:>9} {:>9}  {:<20}  {}",
+			truncate_host(host, 32),
+			h.requests,
+			h.error_rate(),
+			ms(h.mean_latency()),
+			format_bytes(h.bytes_out),
+			format_bytes(h.bytes_in),
+			counts(h.methods.iter().map(|(method, count)| format!("{}×{}", method, count)).collect()),
+			counts(h.statuses.iter().map(|(status, count)| format!("{}×{}", status, count)).collect()));
+	}
+}
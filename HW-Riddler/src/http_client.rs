@@ -1,11 +1,16 @@
+use crate::auth::Auth;
 use crate::cookie_manager::CookieManager;
-use anyhow::Result;
-use reqwest::{header::HeaderMap, Client, Method};
+use crate::script::{ScriptHooks, ScriptRequest, ScriptResponse};
+use anyhow::{anyhow, Context, Result};
+use dashmap::DashMap;
+use reqwest::{header::HeaderMap, Certificate, Client, Method, NoProxy, Proxy};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -13,10 +18,135 @@ pub struct HttpRequestBuilder {
 	pub method: String,
 	pub url: String,
 	pub headers: HashMap<String, String>,
-	pub body: Option<String>,
+	pub body: Option<RequestBody>,
 	pub timeout_seconds: u64,
 	pub follow_redirects: bool,
 	pub verify_ssl: bool,
+	#[serde(default)]
+	pub retry: RetryPolicy,
+	#[serde(default)]
+	pub auth: Option<Auth>,
+	#[serde(default)]
+	pub http_version: HttpVersion,
+}
+
+impl HttpRequestBuilder {
+	/// The body as text for logs and previews; raw bytes are decoded lossily
+	pub fn body_text(&self) -> String {
+		match &self.body {
+			Some(RequestBody::Text(text)) => text.clone(),
+			Some(RequestBody::Bytes(bytes)) => String::from_utf8_lossy(bytes).into_owned(),
+			None => String::new(),
+		}
+	}
+}
+
+/// Request body, either text that templates and fuzzing may rewrite or raw
+/// bytes (a file, stdin, a script's output) that are sent as they are
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RequestBody {
+	Text(String),
+	Bytes(Vec<u8>),
+}
+
+impl RequestBody {
+	pub fn as_bytes(&self) -> &[u8] {
+		match self {
+			RequestBody::Text(text) => text.as_bytes(),
+			RequestBody::Bytes(bytes) => bytes,
+		}
+	}
+
+	/// The body when it is text; raw bytes are never treated as text
+	pub fn as_text(&self) -> Option<&str> {
+		match self {
+			RequestBody::Text(text) => Some(text),
+			RequestBody::Bytes(_) => None,
+		}
+	}
+}
+
+/// HTTP version to speak to the server
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum HttpVersion {
+	/// HTTP/2 when the server offers it through ALPN, HTTP/1.1 otherwise
+	#[default]
+	#[serde(rename = "auto")]
+	Auto,
+	#[value(name = "1.1")]
+	#[serde(rename = "1.1")]
+	Http1,
+	/// HTTP/2 without negotiation, also over plain http:// (prior knowledge)
+	#[value(name = "2")]
+	#[serde(rename = "2")]
+	Http2,
+	/// HTTP/3 over QUIC; needs a build with the `http3` feature
+	#[value(name = "3")]
+	#[serde(rename = "3")]
+	Http3,
+}
+
+/// When `send_request` tries a request again. Connection errors and timeouts
+/// are always retried; responses only when their status is in `retry_on`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+	/// Total attempts including the first; 1 disables retrying
+	pub max_attempts: u32,
+	/// Delay before the first retry, doubled for every further one
+	pub backoff_base_ms: u64,
+	/// Cap for a single delay, including one asked for by `Retry-After`
+	pub max_backoff_ms: u64,
+	pub retry_on: Vec<u16>,
+	/// Wait as long as a `Retry-After` header asks instead of the backoff delay
+	pub honor_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+	fn default() -> Self {
+		Self {
+			max_attempts: 1,
+			backoff_base_ms: 500,
+			max_backoff_ms: 30_000,
+			retry_on: vec![429, 502, 503, 504],
+			honor_retry_after: true,
+		}
+	}
+}
+
+impl RetryPolicy {
+	/// How long to wait after failed attempt number `attempt` (1-based)
+	fn delay(&self, attempt: u32, retry_after: Option<&String>) -> Duration {
+		let backoff = self.backoff_base_ms.saturating_mul(1u64 << (attempt - 1).min(20));
+		let requested = retry_after
+			.filter(|_| self.honor_retry_after)
+			.and_then(|value| parse_retry_after(value));
+		Duration::from_millis(requested.unwrap_or(backoff).min(self.max_backoff_ms))
+	}
+}
+
+/// `Retry-After` in milliseconds: delay-seconds or an HTTP date
+pub fn parse_retry_after(value: &str) -> Option<u64> {
+	let value = value.trim();
+	if let Ok(seconds) = value.parse::<u64>() {
+		return Some(seconds.saturating_mul(1000));
+	}
+	let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+	Some((date.with_timezone(&chrono::Utc) - chrono::Utc::now()).num_milliseconds().max(0) as u64)
+}
+
+/// Outcome of one try of a request sent with a retry policy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttemptOutcome {
+	pub attempt: u32,
+	/// `None` when no response arrived
+	pub status: Option<u16>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	pub elapsed_ms: u64,
+	/// Wait before the next attempt; 0 for the last one
+	pub delay_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,32 +157,486 @@ pub struct HttpResponseInfo {
 	pub cookies: Vec<String>,
 	pub response_time_ms: u64,
 	pub final_url: String,
+	/// Every try when the request had a retry policy, the last one being this response
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub attempts: Vec<AttemptOutcome>,
+	/// Protocol the response arrived over, e.g. `HTTP/2.0`; empty when unknown
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub http_version: String,
+	/// Address of the server (or proxy) the connection went to
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub remote_addr: Option<String>,
+}
+
+impl HttpResponseInfo {
+	/// Status, headers and cookies of `response`, with an empty body
+	pub fn from_head(response: &reqwest::Response, response_time_ms: u64) -> Self {
+		let mut headers = HashMap::new();
+		for (key, value) in response.headers() {
+			if let Ok(value_str) = value.to_str() {
+				headers.insert(key.to_string(), value_str.to_string());
+			}
+		}
+		let cookies = response.headers()
+			.get_all(reqwest::header::SET_COOKIE)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.map(|value| value.to_string())
+			.collect();
+
+		Self {
+			status: response.status().as_u16(),
+			headers,
+			body: String::new(),
+			cookies,
+			response_time_ms,
+			final_url: response.url().to_string(),
+			attempts: Vec::new(),
+			http_version: format!("{:?}", response.version()),
+			remote_addr: response.remote_addr().map(|addr| addr.to_string()),
+		}
+	}
+}
+
+/// Upper bound for streamed responses, which replaces the client's 30 second
+/// total timeout; stalls are caught by the caller's per-chunk timeout instead
+const STREAMING_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Upstream proxy used for every request of an `HttpClient`
+#[derive(Debug, Clone)]
+pub struct ProxySettings {
+	/// `http://`, `https://`, `socks5://` or `socks5h://` URL, optionally with `user:pass@`
+	pub url: String,
+	/// `user:password`, takes precedence over credentials in the URL
+	pub credentials: Option<String>,
+	/// Comma-separated hosts, domains or CIDRs reached directly; `NO_PROXY` when unset
+	pub no_proxy: Option<String>,
+}
+
+impl ProxySettings {
+	fn to_proxy(&self) -> Result<Proxy> {
+		let url = Url::parse(&self.url)
+			.map_err(|e| anyhow!("Invalid proxy URL '{}': {}", self.url, e))?;
+		if !matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h") {
+			return Err(anyhow!("Unsupported proxy scheme '{}': use http, https, socks5 or socks5h", url.scheme()));
+		}
+
+		let mut proxy = Proxy::all(url.as_str())?;
+		if let Some(credentials) = &self.credentials {
+			let (user, password) = credentials.split_once(':').unwrap_or((credentials, ""));
+			proxy = proxy.basic_auth(user, password);
+		}
+		let no_proxy = match &self.no_proxy {
+			Some(list) => NoProxy::from_string(list),
+			None => NoProxy::from_env(),
+		};
+		Ok(proxy.no_proxy(no_proxy))
+	}
+}
+
+/// Fixed address for a host name, like curl's `--resolve host:port:ip`
+#[derive(Debug, Clone)]
+pub struct ResolveOverride {
+	pub host: String,
+	pub port: u16,
+	pub ip: IpAddr,
+}
+
+impl ResolveOverride {
+	/// Parse `host:port:ip`; an IPv6 address may be bracketed
+	pub fn parse(value: &str) -> Result<Self> {
+		let mut parts = value.splitn(3, ':');
+		let (Some(host), Some(port), Some(ip)) = (parts.next(), parts.next(), parts.next()) else {
+			return Err(anyhow!("Invalid --resolve '{}': expected host:port:ip", value));
+		};
+		if host.is_empty() {
+			return Err(anyhow!("Invalid --resolve '{}': missing host", value));
+		}
+		let port = port.parse()
+			.map_err(|_| anyhow!("Invalid --resolve '{}': bad port '{}'", value, port))?;
+		let ip = ip.trim_start_matches('[').trim_end_matches(']').parse()
+			.map_err(|_| anyhow!("Invalid --resolve '{}': bad IP address '{}'", value, ip))?;
+		Ok(Self { host: host.to_ascii_lowercase(), port, ip })
+	}
+}
+
+/// Request settings that reqwest fixes per client rather than per request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct ClientKind {
+	verify_ssl: bool,
+	follow_redirects: bool,
+	http_version: HttpVersion,
+}
+
+impl ClientKind {
+	const DEFAULT: ClientKind = ClientKind { verify_ssl: true, follow_redirects: true, http_version: HttpVersion::Auto };
 }
 
 pub struct HttpClient {
-	client: Client,
+	/// One reqwest client per `ClientKind`, built on first use
+	clients: DashMap<ClientKind, Client>,
+	proxy: Option<Proxy>,
+	root_certificates: Vec<Certificate>,
+	resolve: Vec<ResolveOverride>,
+	/// Keep idle connections for later requests to the same host
+	reuse_connections: bool,
+	/// Leave response bodies as received instead of undoing their Content-Encoding
+	raw_bodies: bool,
+	/// Trace requests for the OTLP exporter set up by `telemetry::Telemetry`
+	trace_export: bool,
+	/// `--script` hooks run on every attempt of `send_request`
+	scripts: Option<Arc<ScriptHooks>>,
 	cookie_manager: Arc<CookieManager>,
 }
 
 impl HttpClient {
 	pub fn new(cookie_manager: Arc<CookieManager>) -> Result<Self> {
-		let client = Client::builder()
+		let http_client = Self {
+			clients: DashMap::new(),
+			proxy: None,
+			root_certificates: Vec::new(),
+			resolve: Vec::new(),
+			reuse_connections: true,
+			raw_bodies: false,
+			trace_export: false,
+			scripts: None,
+			cookie_manager,
+		};
+		// Fail early on a broken TLS setup rather than on the first request
+		http_client.client(ClientKind::DEFAULT)?;
+		Ok(http_client)
+	}
+
+	/// Send every request through `proxy` instead of the proxies from the
+	/// environment (`HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY`)
+	pub fn with_proxy(mut self, proxy: &ProxySettings) -> Result<Self> {
+		self.proxy = Some(proxy.to_proxy()?);
+		self.clients.clear();
+		self.client(ClientKind::DEFAULT)?;
+		Ok(self)
+	}
+
+	/// Also trust the root certificates in `path` (PEM, possibly several, or DER),
+	/// e.g. the CA of a self-signed development server
+	pub fn with_ca_cert(mut self, path: &Path) -> Result<Self> {
+		let content = std::fs::read(path)
+			.with_context(|| format!("Unable to read CA certificate {}", path.display()))?;
+		let certificates = if content.starts_with(b"-----BEGIN") {
+			Certificate::from_pem_bundle(&content)?
+		} else {
+			vec![Certificate::from_der(&content)?]
+		};
+		if certificates.is_empty() {
+			return Err(anyhow!("No certificates found in {}", path.display()));
+		}
+		self.root_certificates.extend(certificates);
+		self.clients.clear();
+		self.client(ClientKind::DEFAULT)?;
+		Ok(self)
+	}
+
+	/// Connect to fixed addresses instead of looking the hosts up in DNS, e.g. to
+	/// send requests for a production host name to a canary box
+	pub fn with_resolve(mut self, overrides: Vec<ResolveOverride>) -> Result<Self> {
+		self.resolve.extend(overrides);
+		self.clients.clear();
+		self.client(ClientKind::DEFAULT)?;
+		Ok(self)
+	}
+
+	/// Keep gzip/deflate/br response bodies as received (`--raw-body`)
+	pub fn with_raw_bodies(mut self, raw: bool) -> Self {
+		self.raw_bodies = raw;
+		self
+	}
+
+	pub fn keeps_raw_bodies(&self) -> bool {
+		self.raw_bodies
+	}
+
+	/// Record a span with DNS, TTFB and download phases for every request sent
+	/// through `send_request` and pass its trace context to the server
+	pub fn with_trace_export(mut self, enabled: bool) -> Result<Self> {
+		self.trace_export = enabled;
+		self.clients.clear();
+		self.client(ClientKind::DEFAULT)?;
+		Ok(self)
+	}
+
+	pub fn exports_traces(&self) -> bool {
+		self.trace_export
+	}
+
+	/// Let the `on_request`/`on_response` hooks of a `--script` rewrite or abort
+	/// the exchanges of `send_request`, and the request of `send_streaming`
+	pub fn with_scripts(mut self, scripts: Option<Arc<ScriptHooks>>) -> Self {
+		self.scripts = scripts;
+		self
+	}
+
+	pub fn scripts(&self) -> Option<Arc<ScriptHooks>> {
+		self.scripts.clone()
+	}
+
+	/// A client with the same settings and cookies that opens a new connection
+	/// for every request, so each one pays for DNS, TCP and TLS setup
+	pub fn with_fresh_connections(&self) -> Self {
+		Self {
+			clients: DashMap::new(),
+			proxy: self.proxy.clone(),
+			root_certificates: self.root_certificates.clone(),
+			resolve: self.resolve.clone(),
+			reuse_connections: false,
+			raw_bodies: self.raw_bodies,
+			trace_export: self.trace_export,
+			scripts: self.scripts.clone(),
+			cookie_manager: self.cookie_manager.clone(),
+		}
+	}
+
+	pub fn reuses_connections(&self) -> bool {
+		self.reuse_connections
+	}
+
+	fn client(&self, kind: ClientKind) -> Result<Client> {
+		if let Some(client) = self.clients.get(&kind) {
+			return Ok(client.clone());
+		}
+
+		// Without redirects the 3xx response itself, with its Location header, is returned
+		let redirect = if kind.follow_redirects {
+			reqwest::redirect::Policy::limited(10)
+		} else {
+			reqwest::redirect::Policy::none()
+		};
+		let mut builder = Client::builder()
 			.timeout(Duration::from_secs(30))
 			.connect_timeout(Duration::from_secs(10))
-			.danger_accept_invalid_certs(false)
-			.redirect(reqwest::redirect::Policy::limited(10))
-			.user_agent("HW-Riddler/1.0")
-			.build()?;
+			.danger_accept_invalid_certs(!kind.verify_ssl)
+			.redirect(redirect)
+			.user_agent("HW-Riddler/1.0");
+		if let Some(proxy) = &self.proxy {
+			builder = builder.proxy(proxy.clone());
+		}
+		for certificate in &self.root_certificates {
+			builder = builder.add_root_certificate(certificate.clone());
+		}
+		// reqwest overrides the name for every port; `send` warns when the ports differ
+		for entry in &self.resolve {
+			builder = builder.resolve(&entry.host, SocketAddr::new(entry.ip, entry.port));
+		}
+		if !self.reuse_connections {
+			builder = builder.pool_max_idle_per_host(0);
+		}
+		if self.trace_export {
+			builder = builder.dns_resolver(Arc::new(crate::telemetry::TimedResolver));
+		}
+		builder = match kind.http_version {
+			HttpVersion::Auto => builder,
+			HttpVersion::Http1 => builder.http1_only(),
+			HttpVersion::Http2 => builder.http2_prior_knowledge(),
+			#[cfg(feature = "http3")]
+			HttpVersion::Http3 => builder.http3_prior_knowledge(),
+			#[cfg(not(feature = "http3"))]
+			HttpVersion::Http3 => return Err(anyhow!(
+				"HTTP/3 support is not compiled in: rebuild with RUSTFLAGS='--cfg reqwest_unstable' cargo build --features http3"
+			)),
+		};
 
-		Ok(Self {
-			client,
-			cookie_manager,
-		})
+		let client = builder.build()?;
+		self.clients.insert(kind, client.clone());
+		Ok(client)
 	}
 
+	/// Send `request`, trying again according to its retry policy
 	pub async fn send_request(&self, request: HttpRequestBuilder) -> Result<HttpResponseInfo> {
+		if !self.trace_export {
+			return self.send_with_retries(request).await;
+		}
+		let context = crate::telemetry::request_context(&request);
+		let result = opentelemetry::context::FutureExt::with_context(self.send_with_retries(request), context.clone()).await;
+		crate::telemetry::end_request(&context, &result);
+		result
+	}
+
+	async fn send_with_retries(&self, request: HttpRequestBuilder) -> Result<HttpResponseInfo> {
+		let max_attempts = request.retry.max_attempts.max(1);
+		let mut attempts = Vec::new();
+
+		for attempt in 1..=max_attempts {
+			let start_time = std::time::Instant::now();
+			let result = self.send_once(&request, attempt).await;
+			let elapsed_ms = start_time.elapsed().as_millis() as u64;
+
+			let retryable = match &result {
+				Ok(response) => request.retry.retry_on.contains(&response.status),
+				Err(_) => true,
+			};
+			let delay = if retryable && attempt < max_attempts {
+				let retry_after = result.as_ref().ok().and_then(|response| response.headers.get("retry-after"));
+				Some(request.retry.delay(attempt, retry_after))
+			} else {
+				None
+			};
+
+			if max_attempts > 1 {
+				attempts.push(AttemptOutcome {
+					attempt,
+					status: result.as_ref().ok().map(|response| response.status),
+					error: result.as_ref().err().map(|e| e.to_string()),
+					elapsed_ms,
+					delay_ms: delay.map_or(0, |delay| delay.as_millis() as u64),
+				});
+			}
+
+			let Some(delay) = delay else {
+				return match result {
+					Ok(mut response) => {
+						response.attempts = attempts;
+						Ok(response)
+					}
+					Err(e) if max_attempts > 1 => Err(e.context(format!("Giving up after {} attempts", attempt))),
+					Err(e) => Err(e),
+				};
+			};
+			match &result {
+				Ok(response) => warn!("Attempt {}/{} returned {}, retrying in {}ms", attempt, max_attempts, response.status, delay.as_millis()),
+				Err(e) => warn!("Attempt {}/{} failed: {}, retrying in {}ms", attempt, max_attempts, e, delay.as_millis()),
+			}
+			tokio::time::sleep(delay).await;
+		}
+		unreachable!("the last attempt always returns")
+	}
+
+	async fn send_once(&self, request: &HttpRequestBuilder, attempt: u32) -> Result<HttpResponseInfo> {
+		// Hooks run again on every attempt, so a signature or token is never stale
+		let Some(scripts) = &self.scripts else {
+			return self.timed_exchange(request, attempt).await;
+		};
+		let (scripted, seen) = scripted_request(scripts, request)?;
+		let mut response = self.timed_exchange(&scripted, attempt).await?;
+
+		let mut parts = ScriptResponse {
+			status: response.status,
+			headers: response.headers.clone(),
+			body: response.body.clone().into_bytes(),
+		};
+		scripts.on_response(&seen, &mut parts)?;
+		response.status = parts.status;
+		response.headers = parts.headers;
+		response.body = String::from_utf8_lossy(&parts.body).into_owned();
+		Ok(response)
+	}
+
+	async fn timed_exchange(&self, request: &HttpRequestBuilder, attempt: u32) -> Result<HttpResponseInfo> {
+		let mut timings = crate::telemetry::AttemptTimings::start();
+		let result = crate::telemetry::record_lookups(timings.lookups(), self.exchange(request, &mut timings)).await;
+		if self.trace_export {
+			timings.export(attempt, result.as_ref().err());
+		}
+		result
+	}
+
+	/// One round trip, plus the one answering a digest challenge
+	async fn exchange(&self, request: &HttpRequestBuilder, timings: &mut crate::telemetry::AttemptTimings) -> Result<HttpResponseInfo> {
 		let start_time = std::time::Instant::now();
 
+		let timeout = Duration::from_secs(request.timeout_seconds);
+		let mut response = self.send(request, timeout).await?;
+
+		// Digest auth needs the nonce from a 401 challenge, then one more round trip
+		if response.status() == reqwest::StatusCode::UNAUTHORIZED
+			&& let Some(auth) = &request.auth {
+			let url = Url::parse(&request.url)?;
+			let uri = match url.query() {
+				Some(query) => format!("{}?{}", url.path(), query),
+				None => url.path().to_string(),
+			};
+			let authorization = response.headers()
+				.get_all(reqwest::header::WWW_AUTHENTICATE)
+				.iter()
+				.filter_map(|value| value.to_str().ok())
+				.find_map(|challenge| auth.answer_challenge(challenge, &request.method, &uri));
+			if let Some(authorization) = authorization {
+				let mut answered = request.clone();
+				answered.headers.retain(|name, _| !name.eq_ignore_ascii_case("authorization"));
+				answered.headers.insert("Authorization".to_string(), authorization);
+				response = self.send(&answered, timeout).await?;
+			}
+		}
+		let mut info = HttpResponseInfo::from_head(&response, 0);
+		timings.head_received();
+
+		// reqwest is built without its decompression features, so compressed
+		// bodies arrive as is; `text()` keeps charset handling for the rest
+		let encoded = !self.raw_bodies && response.headers().contains_key(reqwest::header::CONTENT_ENCODING);
+		info.body = tokio::time::timeout(Duration::from_secs(30), async {
+			if !encoded {
+				return response.text().await;
+			}
+			let body = response.bytes().await?;
+			let decoded = crate::content_encoding::decode_body(&info.headers, &body);
+			Ok(String::from_utf8_lossy(decoded.as_deref().unwrap_or(&body)).into_owned())
+		}).await
+		.map_err(|_| anyhow::anyhow!("Timed out reading response body"))?
+		.map_err(|e| anyhow::anyhow!("Failed to read response body: {}", e))?;
+
+		info.response_time_ms = start_time.elapsed().as_millis() as u64;
+
+		info!(
+			"Received response: {} {} ({}ms)",
+			info.status, info.final_url, info.response_time_ms
+		);
+
+		Ok(info)
+	}
+
+	/// Send `request` and return as soon as the response headers arrive, leaving
+	/// the body to be streamed by the caller (e.g. to a file). The request
+	/// timeout only covers the wait for the headers.
+	pub async fn send_streaming(&self, request: &HttpRequestBuilder) -> Result<reqwest::Response> {
+		match &self.scripts {
+			Some(scripts) => self.send(&scripted_request(scripts, request)?.0, STREAMING_TIMEOUT).await,
+			None => self.send(request, STREAMING_TIMEOUT).await,
+		}
+	}
+
+	/// Send `request` with the session headers and cookies, store the cookies
+	/// it sets and return the response with its body unread. `total_timeout`
+	/// covers the whole exchange including reading the body.
+	async fn send(&self, request: &HttpRequestBuilder, total_timeout: Duration) -> Result<reqwest::Response> {
+		let (url, req_builder) = self.request_builder(request, total_timeout)?;
+		info!("Sending {} request to {}", request.method, request.url);
+
+		let response = tokio::time::timeout(
+			Duration::from_secs(request.timeout_seconds.max(5)),
+			req_builder.send()
+		).await
+		.map_err(|_| anyhow::anyhow!("Request timed out after {} seconds", request.timeout_seconds))??;
+
+		for cookie_header in response.headers().get_all(reqwest::header::SET_COOKIE) {
+			if let Ok(cookie_str) = cookie_header.to_str()
+				&& let Err(e) = self.cookie_manager.add_cookie(&url, cookie_str) {
+				error!("Failed to store cookie: {}", e);
+			}
+		}
+
+		Ok(response)
+	}
+
+	/// `request` built once with the session headers and cookies on the pooled
+	/// client for its settings, for load generators that execute it over and
+	/// over on the same keep-alive connections. `timeout` covers each whole
+	/// exchange; script hooks do not run and cookies set by responses are not stored.
+	pub fn prepare(&self, request: &HttpRequestBuilder, timeout: Duration) -> Result<(Client, reqwest::Request)> {
+		let (_, builder) = self.request_builder(request, timeout)?;
+		let (client, request) = builder.build_split();
+		Ok((client, request?))
+	}
+
+	/// The request with the session headers, auth and cookies on the pooled
+	/// client matching its TLS, redirect and HTTP version settings
+	fn request_builder(&self, request: &HttpRequestBuilder, total_timeout: Duration) -> Result<(Url, reqwest::RequestBuilder)> {
 		let url = Url::parse(&request.url)?;
 
 		let method = match request.method.to_uppercase().as_str() {
@@ -76,6 +660,34 @@ impl HttpClient {
 			}
 		}
 
+		// Session defaults never override a header the request sets itself
+		for (key, value) in self.cookie_manager.default_headers() {
+			if let (Ok(header_name), Ok(header_value)) = (
+				reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+				reqwest::header::HeaderValue::from_str(&value),
+			) {
+				headers.entry(header_name).or_insert(header_value);
+			}
+		}
+
+		// Replaces a traceparent copied from a captured request, so the server's
+		// spans join the trace of this send
+		if self.trace_export {
+			for (key, value) in crate::telemetry::propagation_headers() {
+				if let (Ok(header_name), Ok(header_value)) = (
+					reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+					reqwest::header::HeaderValue::from_str(&value),
+				) {
+					headers.insert(header_name, header_value);
+				}
+			}
+		}
+
+		if let Some(value) = request.auth.as_ref().and_then(Auth::preemptive_header)
+			&& let Ok(value) = reqwest::header::HeaderValue::from_str(&value) {
+			headers.insert(reqwest::header::AUTHORIZATION, value);
+		}
+
 		let cookies = self.cookie_manager.get_cookies_for_url(&url);
 		if !cookies.is_empty() {
 			let cookie_header = cookies.join("; ");
@@ -84,73 +696,41 @@ impl HttpClient {
 			}
 		}
 
+		if !request.verify_ssl {
+			warn!("TLS certificate verification disabled for {}", request.url);
+		}
+		if let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) {
+			for entry in self.resolve.iter().filter(|entry| entry.host.eq_ignore_ascii_case(host)) {
+				if entry.port == port {
+					info!("Resolving {}:{} to {}", host, port, entry.ip);
+				} else {
+					warn!("--resolve {}:{}:{} also applies to port {}", entry.host, entry.port, entry.ip, port);
+				}
+			}
+		}
+		let kind = ClientKind {
+			verify_ssl: request.verify_ssl,
+			follow_redirects: request.follow_redirects,
+			http_version: request.http_version,
+		};
 		let mut req_builder = self
-			.client
+			.client(kind)?
 			.request(method, url.clone())
 			.headers(headers)
-			.timeout(Duration::from_secs(request.timeout_seconds));
-
-		if let Some(body) = &request.body {
-			req_builder = req_builder.body(body.clone());
+			.timeout(total_timeout);
+		#[cfg(feature = "http3")]
+		if request.http_version == HttpVersion::Http3 {
+			req_builder = req_builder.version(reqwest::Version::HTTP_3);
 		}
-		info!("Sending {} request to {}", request.method, request.url);
 
-		let response = tokio::time::timeout(
-			Duration::from_secs(request.timeout_seconds.max(5)),
-			req_builder.send()
-		).await
-		.map_err(|_| anyhow::anyhow!("Request timed out after {} seconds", request.timeout_seconds))??;
-		let final_url = response.url().to_string();
-		let status = response.status().as_u16();
-
-		let mut response_headers = HashMap::new();
-		for (key, value) in response.headers() {
-			if let Ok(value_str) = value.to_str() {
-				response_headers.insert(key.to_string(), value_str.to_string());
-			}
-		}
-
-		let mut response_cookies = Vec::new();
-		for cookie_header in response.headers().get_all(reqwest::header::SET_COOKIE) {
-			if let Ok(cookie_str) = cookie_header.to_str() {
-				response_cookies.push(cookie_str.to_string());
-
-				if let Err(e) = self.cookie_manager.add_cookie(&url, cookie_str) {
-					error!("Failed to store cookie: {}", e);
-				}
-			}
+		if let Some(body) = &request.body {
+			req_builder = req_builder.body(body.as_bytes().to_vec());
 		}
-
-		let body = tokio::time::timeout(
-			Duration::from_secs(30),
-			response.text()
-		).await
-		.map_err(|_| anyhow::anyhow!("Timed out reading response body"))?
-		.map_err(|e| anyhow::anyhow!("Failed to read response body: {}", e))?;
-
-		let response_time = start_time.elapsed().as_millis() as u64;
-
-		info!(
-			"Received response: {} {} ({}ms)",
-			status, final_url, response_time
-		);
-
-		Ok(HttpResponseInfo {
-			status,
-			headers: response_headers,
-			body,
-			cookies: response_cookies,
-			response_time_ms: response_time,
-			final_url,
-		})
+		Ok((url, req_builder))
 	}
 
 	pub async fn replay_request(&self, monitored_request: &crate::network::HttpRequest) -> Result<HttpResponseInfo> {
-		let body = if monitored_request.body.is_empty() {
-			None
-		} else {
-			Some(String::from_utf8_lossy(&monitored_request.body).to_string())
-		};
+		let body = (!monitored_request.body.is_empty()).then(|| RequestBody::Bytes(monitored_request.body.clone()));
 
 		self.send_request(HttpRequestBuilder {
 			method: monitored_request.method.clone(),
@@ -160,7 +740,31 @@ impl HttpClient {
 			timeout_seconds: 30,
 			follow_redirects: true,
 			verify_ssl: true,
+			retry: RetryPolicy::default(),
+			auth: None,
+			http_version: HttpVersion::Auto,
 		})
 		.await
 	}
-}
\ No newline at end of file
+}
+/// `request` as the `on_request` hook of `scripts` leaves it, and the same
+/// request in the form `on_response` gets to see it
+fn scripted_request(scripts: &ScriptHooks, request: &HttpRequestBuilder) -> Result<(HttpRequestBuilder, ScriptRequest)> {
+	let original_body = request.body.as_ref().map(|body| body.as_bytes().to_vec()).unwrap_or_default();
+	let mut parts = ScriptRequest {
+		method: request.method.clone(),
+		url: request.url.clone(),
+		headers: request.headers.clone(),
+		body: original_body.clone(),
+	};
+	scripts.on_request(&mut parts)?;
+
+	let mut scripted = request.clone();
+	scripted.method = parts.method.clone();
+	scripted.url = parts.url.clone();
+	scripted.headers = parts.headers.clone();
+	if parts.body != original_body {
+		scripted.body = (!parts.body.is_empty()).then(|| RequestBody::Bytes(parts.body.clone()));
+	}
+	Ok((scripted, parts))
+}
@@ -0,0 +1,94 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::net::IpAddr;
+
+/// A capture device as `riddler interfaces` shows it
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceInfo {
+	pub name: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub description: Option<String>,
+	/// Addresses in CIDR notation where the netmask is known
+	pub addresses: Vec<String>,
+	pub up: bool,
+	pub running: bool,
+	pub loopback: bool,
+	pub wireless: bool,
+	/// Data link type, e.g. `EN10MB`; `None` when the device could not be
+	/// opened, usually for lack of capture privileges
+	pub link_type: Option<String>,
+}
+
+/// Every device pcap can capture on
+pub fn list() -> Result<Vec<InterfaceInfo>> {
+	let devices = pcap::Device::list().map_err(|e| anyhow!("Failed to list network interfaces: {}", e))?;
+	Ok(devices.into_iter().map(|device| {
+		let link_type = pcap::Capture::from_device(device.clone())
+			.and_then(|cap| cap.open())
+			.ok()
+			.and_then(|cap| cap.get_datalink().get_name().ok());
+		InterfaceInfo {
+			addresses: device.addresses.iter()
+				.map(|address| match address.netmask.and_then(prefix_length) {
+					Some(prefix) => format!("{}/{}", address.addr, prefix),
+					None => address.addr.to_string(),
+				})
+				.collect(),
+			up: device.flags.is_up(),
+			running: device.flags.is_running(),
+			loopback: device.flags.is_loopback(),
+			wireless: device.flags.is_wireless(),
+			link_type,
+			description: device.desc,
+			name: device.name,
+		}
+	}).collect())
+}
+
+/// `255.255.255.0` → 24; `None` for non-contiguous masks
+fn prefix_length(netmask: IpAddr) -> Option<u32> {
+	let (bits, width) = match netmask {
+		IpAddr::V4(mask) => (u32::from(mask) as u128, 32),
+		IpAddr::V6(mask) => (u128::from(mask), 128),
+	};
+	let ones = bits.count_ones();
+	let expected = if ones == 0 { 0 } else { (u128::MAX << (128 - ones)) >> (128 - width) };
+	(bits == expected).then_some(ones)
+}
+
+pub fn print_interfaces(interfaces: &[InterfaceInfo], default_interface: &str) {
+	println!("=== Network Interfaces ({}) ===", interfaces.len());
+	if interfaces.is_empty() {
+		println!("No capture devices found. Is libpcap/Npcap installed?");
+		return;
+	}
+
+	for interface in interfaces {
+		let mut flags = Vec::new();
+		flags.push(if interface.up { "UP" } else { "DOWN" });
+		if interface.running {
+			flags.push("RUNNING");
+		}
+		if interface.loopback {
+			flags.push("LOOPBACK");
+		}
+		if interface.wireless {
+			flags.push("WIRELESS");
+		}
+		println!("{}{}  [{}]  link: {}",
+			interface.name,
+			if interface.name == default_interface { " (default)" } else { "" },
+			flags.join(", "),
+			interface.link_type.as_deref().unwrap_or("unknown"));
+		if let Some(description) = &interface.description {
+			println!("   {}", description);
+		}
+		for address in &interface.addresses {
+			println!("   {}", address);
+		}
+	}
+
+	if interfaces.iter().any(|interface| interface.link_type.is_none()) {
+		println!("\nLink types are only known for devices that could be opened; run with sudo/administrator privileges to see all of them.");
+	}
+}
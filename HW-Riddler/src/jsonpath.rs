@@ -0,0 +1,166 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+
+/// One step of a parsed JSONPath expression
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+	Key(String),
+	Index(i64),
+	Wildcard,
+	/// `..key`: `key` at any depth below the current node
+	Descendant(String),
+}
+
+/// A compiled JSONPath expression.
+///
+/// Supports the subset commonly used for pulling values out of API responses:
+/// `$.data.items[0].id`, `$['key with spaces']`, negative indexes (`[-1]`),
+/// wildcards (`[*]`, `.*`) and recursive descent (`$..id`). jq-style paths such
+/// as `.data.items[].id` or `.[0]` are accepted as well.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+	segments: Vec<Segment>,
+}
+
+impl JsonPath {
+	pub fn parse(expression: &str) -> Result<Self> {
+		let invalid = |reason: &str| anyhow!("Invalid JSONPath '{}': {}", expression, reason);
+
+		let rest = expression.trim();
+		let rest = rest.strip_prefix('$').unwrap_or(rest);
+		let chars: Vec<char> = rest.chars().collect();
+		let mut segments = Vec::new();
+		let mut i = 0;
+
+		// Allow a bare leading key: "data.items" == "$.data.items"
+		if !chars.is_empty() && chars[0] != '.' && chars[0] != '[' {
+			let (key, next) = read_key(&chars, 0);
+			segments.push(Segment::Key(key));
+			i = next;
+		}
+
+		while i < chars.len() {
+			match chars[i] {
+				'.' if chars.get(i + 1) == Some(&'.') => {
+					let (key, next) = read_key(&chars, i + 2);
+					if key.is_empty() {
+						return Err(invalid("expected a key after '..'"));
+					}
+					segments.push(Segment::Descendant(key));
+					i = next;
+				}
+				// jq style `.[0]` / `.[]`: the dot before a bracket adds nothing
+				'.' if chars.get(i + 1) == Some(&'[') => i += 1,
+				'.' => {
+					let (key, next) = read_key(&chars, i + 1);
+					match key.as_str() {
+						"" => return Err(invalid("expected a key after '.'")),
+						"*" => segments.push(Segment::Wildcard),
+						_ => segments.push(Segment::Key(key)),
+					}
+					i = next;
+				}
+				'[' => {
+					let close = chars[i..]
+						.iter()
+						.position(|&c| c == ']')
+						.map(|p| i + p)
+						.ok_or_else(|| invalid("unclosed '['"))?;
+					let inner: String = chars[i + 1..close].iter().collect();
+					let inner = inner.trim();
+
+					let segment = if inner == "*" || inner.is_empty() {
+						Segment::Wildcard
+					} else if let Some(quoted) = inner
+						.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+						.or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+					{
+						Segment::Key(quoted.to_string())
+					} else {
+						Segment::Index(inner.parse().map_err(|_| invalid("expected an index, '*', '[]' or a quoted key in brackets"))?)
+					};
+					segments.push(segment);
+					i = close + 1;
+				}
+				c => bail!("Invalid JSONPath '{}': unexpected '{}'", expression, c),
+			}
+		}
+
+		Ok(Self { segments })
+	}
+
+	/// All values matched by the expression, in document order
+	pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+		let mut current = vec![root];
+
+		for segment in &self.segments {
+			let mut next = Vec::new();
+			for value in current {
+				match segment {
+					Segment::Key(key) => next.extend(value.get(key.as_str())),
+					Segment::Index(index) => {
+						if let Value::Array(items) = value {
+							let position = if *index < 0 { items.len() as i64 + index } else { *index };
+							if position >= 0 {
+								next.extend(items.get(position as usize));
+							}
+						}
+					}
+					Segment::Wildcard => match value {
+						Value::Array(items) => next.extend(items.iter()),
+						Value::Object(map) => next.extend(map.values()),
+						_ => {}
+					},
+					Segment::Descendant(key) => collect_descendants(value, key, &mut next),
+				}
+			}
+			current = next;
+		}
+
+		current
+	}
+}
+
+fn read_key(chars: &[char], start: usize) -> (String, usize) {
+	let end = chars[start..]
+		.iter()
+		.position(|&c| c == '.' || c == '[')
+		.map_or(chars.len(), |p| start + p);
+	(chars[start..end].iter().collect(), end)
+}
+
+fn collect_descendants<'a>(value: &'a Value, key: &str, out: &mut Vec<&'a Value>) {
+	match value {
+		Value::Object(map) => {
+			if let Some(found) = map.get(key) {
+				out.push(found);
+			}
+			for child in map.values() {
+				collect_descendants(child, key, out);
+			}
+		}
+		Value::Array(items) => {
+			for child in items {
+				collect_descendants(child, key, out);
+			}
+		}
+		_ => {}
+	}
+}
+
+/// Text form for printing or substituting an extracted value: strings without
+/// quotes, everything else as compact JSON
+pub fn value_to_string(value: &Value) -> String {
+	match value {
+		Value::String(s) => s.clone(),
+		other => other.to_string(),
+	}
+}
+
+/// Parse `body` as JSON and evaluate `expression` against it
+pub fn extract(body: &str, expression: &str) -> Result<Vec<Value>> {
+	let path = JsonPath::parse(expression)?;
+	let json: Value = serde_json::from_str(body)
+		.map_err(|e| anyhow!("Response body is not valid JSON: {}", e))?;
+	Ok(path.select(&json).into_iter().cloned().collect())
+}
@@ -2,14 +2,23 @@ use crate::http_client::HttpResponseInfo;
 use crate::network::HttpRequest;
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 use tokio::sync::Mutex;
 use tracing::error;
 
+/// How often `follow` checks the log file for new data
+const FOLLOW_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RequestLogEntry {
+	/// Short id derived from the stored log line, see [`RequestLogEntry::from_line`]
+	#[serde(skip)]
+	pub id: String,
 	pub timestamp: chrono::DateTime<chrono::Utc>,
 	pub request: HttpRequestInfo,
 	pub response: Option<HttpResponseInfo>,
@@ -24,6 +33,14 @@ pub struct HttpRequestInfo {
 	pub body_preview: String,
 	pub source_ip: String,
 	pub source_port: u16,
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub destination_ip: String,
+	/// Host name of `destination_ip` from DNS traffic captured by the monitor
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub resolved_host: Option<String>,
+	/// Country, city and AS of `destination_ip` when the monitor runs with `--geoip-db`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub geo: Option<crate::geoip::GeoInfo>,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
@@ -39,6 +56,136 @@ pub struct RequestStats {
 	pub average_response_time: u64,
 }
 
+/// Structured filter over log entries shared by the `logs` and `replay` commands
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+	/// Exact match on the entry source (monitored, manual, replay)
+	pub source: Option<String>,
+	/// Every term must match
+	pub terms: Vec<SearchTerm>,
+}
+
+/// Part of an entry a search term looks at
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchField {
+	/// URL, method, request body and request header values
+	Any,
+	Url,
+	Method,
+	/// Request body preview
+	Body,
+	/// Value of the named request header (case-insensitive name)
+	Header(String),
+	Status,
+	/// Response body
+	Response,
+}
+
+#[derive(Debug, Clone)]
+pub enum SearchPattern {
+	/// Case-insensitive substring, stored lower-cased
+	Substring(String),
+	Regex(regex::Regex),
+}
+
+impl SearchPattern {
+	fn is_match(&self, value: &str) -> bool {
+		match self {
+			SearchPattern::Substring(text) => value.to_lowercase().contains(text),
+			SearchPattern::Regex(regex) => regex.is_match(value),
+		}
+	}
+}
+
+/// One `-q` argument: `text`, or `field~pattern` with field one of `url`,
+/// `method`, `body`, `header:NAME`, `status` or `response`
+#[derive(Debug, Clone)]
+pub struct SearchTerm {
+	pub field: SearchField,
+	pub pattern: SearchPattern,
+}
+
+impl SearchTerm {
+	pub fn parse(term: &str, regex: bool) -> Result<Self> {
+		let (field, pattern) = match term.split_once('~') {
+			Some((field, pattern)) => match parse_field(field) {
+				Some(field) => (field, pattern),
+				// Not a field selector, e.g. a literal '~' in the search text
+				None => (SearchField::Any, term),
+			},
+			None => (SearchField::Any, term),
+		};
+
+		let pattern = if regex {
+			SearchPattern::Regex(regex::Regex::new(pattern)
+				.map_err(|e| anyhow::anyhow!("Invalid regex '{}': {}", pattern, e))?)
+		} else {
+			SearchPattern::Substring(pattern.to_lowercase())
+		};
+		Ok(Self { field, pattern })
+	}
+
+	pub fn matches(&self, entry: &RequestLogEntry) -> bool {
+		let request = &entry.request;
+		match self.field {
+			SearchField::Any => {
+				self.pattern.is_match(&request.url)
+					|| self.pattern.is_match(&request.method)
+					|| self.pattern.is_match(&request.body_preview)
+					|| request.headers.values().any(|v| self.pattern.is_match(v))
+			}
+			SearchField::Url => self.pattern.is_match(&request.url),
+			SearchField::Method => self.pattern.is_match(&request.method),
+			SearchField::Body => self.pattern.is_match(&request.body_preview),
+			SearchField::Header(ref name) => request.headers
+				.iter()
+				.any(|(k, v)| k.eq_ignore_ascii_case(name) && self.pattern.is_match(v)),
+			SearchField::Status => entry.response
+				.as_ref()
+				.is_some_and(|r| self.pattern.is_match(&r.status.to_string())),
+			SearchField::Response => entry.response
+				.as_ref()
+				.is_some_and(|r| self.pattern.is_match(&r.body)),
+		}
+	}
+}
+
+fn parse_field(field: &str) -> Option<SearchField> {
+	let field = field.trim();
+	if let Some(name) = field.strip_prefix("header:").or_else(|| field.strip_prefix("h:")) {
+		return (!name.is_empty()).then(|| SearchField::Header(name.to_string()));
+	}
+	match field.to_lowercase().as_str() {
+		"url" => Some(SearchField::Url),
+		"method" => Some(SearchField::Method),
+		"body" => Some(SearchField::Body),
+		"status" => Some(SearchField::Status),
+		"response" => Some(SearchField::Response),
+		_ => None,
+	}
+}
+
+impl LogQuery {
+	/// Build a query from `-s` and the `-q` terms
+	pub fn new(source: Option<String>, terms: &[String], regex: bool) -> Result<Self> {
+		let terms = terms
+			.iter()
+			.map(|term| SearchTerm::parse(term, regex))
+			.collect::<Result<Vec<_>>>()?;
+		Ok(Self { source, terms })
+	}
+
+	pub fn matches(&self, entry: &RequestLogEntry) -> bool {
+		if let Some(ref source) = self.source
+			&& entry.source != *source
+		{
+			return false;
+		}
+
+		self.terms.iter().all(|term| term.matches(entry))
+	}
+}
+
 impl From<&HttpRequest> for HttpRequestInfo {
 	fn from(req: &HttpRequest) -> Self {
 		let body_preview = if req.body.len() > 1000 {
@@ -54,13 +201,116 @@ impl From<&HttpRequest> for HttpRequestInfo {
 			body_preview,
 			source_ip: req.source_ip.clone(),
 			source_port: req.source_port,
+			destination_ip: req.destination_ip.clone(),
+			resolved_host: req.resolved_host.clone(),
+			geo: req.geo.clone(),
 		}
 	}
 }
 
+impl RequestLogEntry {
+	/// Parse one log line. The id is a hash of the line, so it is stable for
+	/// as long as the entry stays in the file.
+	pub fn from_line(line: &str) -> Option<Self> {
+		let mut entry: Self = serde_json::from_str(line).ok()?;
+		entry.id = format!("{:x}", md5::compute(line))[..8].to_string();
+		Some(entry)
+	}
+}
+
+/// What `logs prune` removed
+#[derive(Debug, Default)]
+pub struct PruneSummary {
+	pub kept: usize,
+	pub removed: usize,
+	/// Lines that could not be parsed, dropped while compacting
+	pub invalid: usize,
+	/// Rotated files left empty and deleted
+	pub files_deleted: usize,
+}
+
+impl PruneSummary {
+	fn add(&mut self, other: PruneSummary) {
+		self.kept += other.kept;
+		self.removed += other.removed;
+		self.invalid += other.invalid;
+		self.files_deleted += other.files_deleted;
+	}
+}
+
+/// Which lines survive a prune. Entries are numbered in log order across all
+/// files; everything before `first_kept` and everything older than `cutoff` goes.
+#[derive(Debug, Clone, Copy)]
+struct PrunePolicy {
+	first_kept: usize,
+	cutoff: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Just the part of an entry pruning looks at
+#[derive(Deserialize)]
+struct EntryTimestamp {
+	timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl PrunePolicy {
+	/// Decide for the next line, advancing the entry counter `index`
+	fn keep(&self, line: &str, index: &mut usize, summary: &mut PruneSummary) -> bool {
+		let Ok(entry) = serde_json::from_str::<EntryTimestamp>(line) else {
+			summary.invalid += 1;
+			return false;
+		};
+		let keep = *index >= self.first_kept && self.cutoff.is_none_or(|cutoff| entry.timestamp >= cutoff);
+		*index += 1;
+		if keep {
+			summary.kept += 1;
+		} else {
+			summary.removed += 1;
+		}
+		keep
+	}
+}
+
+/// When the active log file is moved aside and how many old ones are kept
+#[derive(Debug, Clone)]
+pub struct LogRotation {
+	pub max_bytes: Option<u64>,
+	pub daily: bool,
+	pub keep: usize,
+	pub compress: bool,
+}
+
+impl LogRotation {
+	pub fn from_config(storage: &crate::config::StorageConfig) -> Option<Self> {
+		let max_bytes = (storage.log_max_size_mb > 0).then_some(storage.log_max_size_mb * 1024 * 1024);
+		(max_bytes.is_some() || storage.log_rotate_daily).then_some(Self {
+			max_bytes,
+			daily: storage.log_rotate_daily,
+			keep: storage.log_keep_files,
+			compress: storage.log_compress,
+		})
+	}
+
+	fn is_due(&self, file: &ActiveLog, incoming: usize) -> bool {
+		file.size > 0
+			&& (self.max_bytes.is_some_and(|max| file.size + incoming as u64 > max)
+				|| (self.daily && file.day != chrono::Utc::now().date_naive()))
+	}
+}
+
+/// The file currently appended to
+struct ActiveLog {
+	file: tokio::fs::File,
+	size: u64,
+	/// UTC day of the last write, for daily rotation
+	day: chrono::NaiveDate,
+}
+
 pub struct RequestLogger {
-	log_file: Arc<Mutex<tokio::fs::File>>,
+	log_file: Arc<Mutex<ActiveLog>>,
 	log_file_path: String,
+	rotation: Option<LogRotation>,
+	redactor: Option<crate::redact::Redactor>,
+	session: Option<Arc<crate::hosts::SessionRecorder>>,
 }
 
 impl RequestLogger {
@@ -82,24 +332,139 @@ impl RequestLogger {
 			.await
 			.map_err(|e| anyhow::anyhow!("Cannot open log file (permission issue?): {}", e))?;
 
+		let metadata = file.metadata().await?;
+		let day = metadata
+			.modified()
+			.map(|t| chrono::DateTime::<chrono::Utc>::from(t).date_naive())
+			.unwrap_or_else(|_| chrono::Utc::now().date_naive());
+
 		Ok(Self {
-			log_file: Arc::new(Mutex::new(file)),
+			log_file: Arc::new(Mutex::new(ActiveLog { file, size: metadata.len(), day })),
 			log_file_path: log_file_path.to_string(),
+			rotation: None,
+			redactor: None,
+			session: None,
 		})
 	}
 
-	async fn log_entry<T: Serialize>(&self, entry: &T) -> Result<()> {
-		let log_line = format!("{}\n", serde_json::to_string(entry)?);
+	pub fn with_rotation(mut self, rotation: Option<LogRotation>) -> Self {
+		self.rotation = rotation;
+		self
+	}
+
+	/// Mask secrets in every entry before it is written
+	pub fn with_redaction(mut self, redactor: Option<crate::redact::Redactor>) -> Self {
+		self.redactor = redactor;
+		self
+	}
+
+	/// The redactor applied to entries, unless `--no-redact` or the config disabled it
+	pub fn redactor(&self) -> Option<&crate::redact::Redactor> {
+		self.redactor.as_ref()
+	}
+
+	/// Also count every captured or proxied exchange into this capture session
+	pub fn with_session(mut self, session: Option<Arc<crate::hosts::SessionRecorder>>) -> Self {
+		self.session = session;
+		self
+	}
+
+	fn serialize_entry(&self, entry: &RequestLogEntry) -> serde_json::Result<String> {
+		match self.redactor {
+			Some(ref redactor) => {
+				let mut entry = entry.clone();
+				redactor.apply(&mut entry);
+				serde_json::to_string(&entry)
+			}
+			None => serde_json::to_string(entry),
+		}
+	}
+
+	async fn log_entry(&self, entry: &RequestLogEntry) -> Result<()> {
+		let log_line = format!("{}\n", self.serialize_entry(entry)?);
 
-		let mut file = self.log_file.lock().await;
-		file.write_all(log_line.as_bytes()).await?;
-		file.flush().await?;
+		let mut log = self.log_file.lock().await;
+		self.rotate_if_due(&mut log, log_line.len()).await;
+		log.file.write_all(log_line.as_bytes()).await?;
+		log.file.flush().await?;
+		log.size += log_line.len() as u64;
+		log.day = chrono::Utc::now().date_naive();
 
 		Ok(())
 	}
 
+	/// Move the active file aside before writing `incoming` bytes if the policy
+	/// says so. Failures are logged and the write goes to the current file.
+	async fn rotate_if_due(&self, log: &mut ActiveLog, incoming: usize) {
+		let Some(ref rotation) = self.rotation else { return };
+		if !rotation.is_due(log, incoming) {
+			return;
+		}
+
+		let result: Result<()> = async {
+			log.file.flush().await?;
+			let path = PathBuf::from(&self.log_file_path);
+			let policy = rotation.clone();
+			tokio::task::spawn_blocking(move || rotate_files(&path, &policy)).await??;
+			log.file = OpenOptions::new().create(true).append(true).open(&self.log_file_path).await?;
+			log.size = 0;
+			Ok(())
+		}.await;
+
+		if let Err(e) = result {
+			error!("Failed to rotate log file {}: {}", self.log_file_path, e);
+		}
+	}
+
+	/// Call `f` with every line of the rotated files (oldest first) and then the
+	/// active file. Lines are streamed, so memory use does not grow with the log.
+	async fn for_each_line(&self, mut f: impl FnMut(&str)) -> Result<()> {
+		for path in log_files(Path::new(&self.log_file_path)) {
+			if path.extension().is_some_and(|e| e == "gz") {
+				// flate2 is synchronous: decompress on a blocking thread and hand lines over
+				let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(1024);
+				let reader_path = path.clone();
+				let reader = tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+					let file = std::fs::File::open(&reader_path)?;
+					let mut reader = std::io::BufReader::new(flate2::read::GzDecoder::new(file));
+					let mut buf = Vec::new();
+					while reader.read_until(b'\n', &mut buf)? > 0 {
+						if tx.blocking_send(String::from_utf8_lossy(trim_newline(&buf)).into_owned()).is_err() {
+							break;
+						}
+						buf.clear();
+					}
+					Ok(())
+				});
+				while let Some(line) = rx.recv().await {
+					f(&line);
+				}
+				reader.await?
+					.map_err(|e| anyhow::anyhow!("Cannot decompress {}: {}", path.display(), e))?;
+				continue;
+			}
+
+			let file = match tokio::fs::File::open(&path).await {
+				Ok(file) => file,
+				Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+				Err(e) => return Err(anyhow::anyhow!("Cannot read {}: {}", path.display(), e)),
+			};
+			let mut reader = tokio::io::BufReader::new(file);
+			let mut buf = Vec::new();
+			while reader.read_until(b'\n', &mut buf).await? > 0 {
+				f(&String::from_utf8_lossy(trim_newline(&buf)));
+				buf.clear();
+			}
+		}
+		Ok(())
+	}
+
 	pub async fn log_request(&self, request: &HttpRequest, source: &str) -> Result<()> {
+		if let Some(session) = &self.session {
+			session.record(&request.url, &request.method, request_size(request), None);
+		}
 		let entry = RequestLogEntry {
+			id: String::new(),
 			timestamp: chrono::Utc::now(),
 			request: HttpRequestInfo::from(request),
 			response: None,
@@ -115,7 +480,15 @@ impl RequestLogger {
 		response: &HttpResponseInfo,
 		source: &str,
 	) -> Result<()> {
+		// Requests the monitor replays itself are not traffic it observed
+		if let Some(session) = &self.session
+			&& source != "replay"
+		{
+			let bytes_in = response.headers.iter().map(|(k, v)| (k.len() + v.len() + 4) as u64).sum::<u64>() + response.body.len() as u64;
+			session.record(&request.url, &request.method, request_size(request), Some((response.status, bytes_in, response.response_time_ms)));
+		}
 		let entry = RequestLogEntry {
+			id: String::new(),
 			timestamp: chrono::Utc::now(),
 			request: HttpRequestInfo::from(request),
 			response: Some(response.clone()),
@@ -137,16 +510,16 @@ impl RequestLogger {
 			method: method.to_string(),
 			url: url.to_string(),
 			headers,
-			body_preview: if body.len() > 1000 {
-				format!("{}...", &body[..1000])
-			} else {
-				body.to_string()
-			},
+			body_preview: body_preview(body),
 			source_ip: "manual".to_string(),
 			source_port: 0,
+			destination_ip: String::new(),
+			resolved_host: None,
+			geo: None,
 		};
 
 		let entry = RequestLogEntry {
+			id: String::new(),
 			timestamp: chrono::Utc::now(),
 			request: request_info,
 			response: Some(response.clone()),
@@ -165,18 +538,16 @@ impl RequestLogger {
 			method: request.method.clone(),
 			url: request.url.clone(),
 			headers: request.headers.clone(),
-			body_preview: request.body.as_ref().map_or(String::new(), |b| {
-				if b.len() > 1000 {
-					format!("{}...", &b[..1000])
-				} else {
-					b.clone()
-				}
-			}),
+			body_preview: body_preview(&request.body_text()),
 			source_ip: "replay".to_string(),
 			source_port: 0,
+			destination_ip: String::new(),
+			resolved_host: None,
+			geo: None,
 		};
 
 		let entry = RequestLogEntry {
+			id: String::new(),
 			timestamp: chrono::Utc::now(),
 			request: request_info,
 			response: Some(response.clone()),
@@ -187,24 +558,25 @@ impl RequestLogger {
 	}
 
 	async fn write_log_entry(&self, entry: &RequestLogEntry) -> Result<()> {
-		let log_line = match serde_json::to_string(entry) {
+		let log_line = match self.serialize_entry(entry) {
 			Ok(s) => format!("{}\n", s),
 			Err(e) => return Err(anyhow::anyhow!("Failed to serialize log entry: {}", e)),
 		};
 
-		let mut file = match self.log_file.lock().await {
-			f => f,
-		};
+		let mut log = self.log_file.lock().await;
+		self.rotate_if_due(&mut log, log_line.len()).await;
 
-		match file.write_all(log_line.as_bytes()).await {
+		match log.file.write_all(log_line.as_bytes()).await {
 			Ok(_) => {}
 			Err(e) => {
 				error!("Failed to write to log file: {} ({})", e, std::io::Error::last_os_error());
 				return Err(anyhow::anyhow!("Failed to write to log file: {}", e));
 			}
 		}
+		log.size += log_line.len() as u64;
+		log.day = chrono::Utc::now().date_naive();
 
-		if let Err(e) = file.flush().await {
+		if let Err(e) = log.file.flush().await {
 			error!("Failed to flush log file: {} ({})", e, std::io::Error::last_os_error());
 		}
 
@@ -214,75 +586,188 @@ impl RequestLogger {
 	pub async fn read_recent_logs(&self, limit: usize) -> Result<Vec<RequestLogEntry>> {
 		let _file_guard = self.log_file.lock().await;
 
-		if !tokio::fs::metadata(&self.log_file_path).await.is_ok() {
-			return Ok(Vec::new());
-		}
-
-		let content = match tokio::fs::read_to_string(&self.log_file_path).await {
-			Ok(content) => content,
-			Err(e) => {
-				error!("无法读取日志文件 {}: {}", self.log_file_path, e);
-				return Ok(Vec::new());
+		// Keep only the last `limit` lines while streaming
+		let mut lines = VecDeque::with_capacity(limit);
+		let scanned = self.for_each_line(|line| {
+			if limit == 0 {
+				return;
 			}
-		};
+			if lines.len() == limit {
+				lines.pop_front();
+			}
+			lines.push_back(line.to_string());
+		}).await;
 
-		if content.is_empty() {
+		if let Err(e) = scanned {
+			error!("无法读取日志文件 {}: {}", self.log_file_path, e);
 			return Ok(Vec::new());
 		}
 
-		let lines: Vec<&str> = content.lines().collect();
-		let mut entries = Vec::with_capacity(limit);
-
-		for line in lines.iter().rev().take(limit) {
-			match serde_json::from_str::<RequestLogEntry>(line) {
-				Ok(entry) => entries.push(entry),
-				Err(e) => {
-					error!("跳过无法解析的日志条目: {}", e);
+		let mut entries = Vec::with_capacity(lines.len());
+		for line in lines {
+			match RequestLogEntry::from_line(&line) {
+				Some(entry) => entries.push(entry),
+				None => {
+					error!("跳过无法解析的日志条目: {}", line.chars().take(80).collect::<String>());
 					continue;
 				}
 			}
 		}
 
-		entries.reverse();
 		Ok(entries)
 	}
 
-	pub async fn search_logs(&self, query: &str, limit: usize) -> Result<Vec<RequestLogEntry>> {
+	/// Entries matching `query`, newest first, skipping the `offset` most recent
+	/// matches. The returned page is in chronological order.
+	pub async fn query_logs(&self, query: &LogQuery, offset: usize, limit: usize) -> Result<Vec<RequestLogEntry>> {
 		let _file_guard = self.log_file.lock().await;
 
-		let content = tokio::fs::read_to_string(&self.log_file_path).await?;
-		let lines: Vec<&str> = content.lines().collect();
+		// The page lies within the last `offset + limit` matches
+		let window = offset.saturating_add(limit);
+		let mut recent = VecDeque::new();
+		self.for_each_line(|line| {
+			if window == 0 {
+				return;
+			}
+			if let Some(entry) = RequestLogEntry::from_line(line).filter(|entry| query.matches(entry)) {
+				if recent.len() == window {
+					recent.pop_front();
+				}
+				recent.push_back(entry);
+			}
+		}).await?;
 
-		let mut matching_entries = Vec::new();
-		let query_lower = query.to_lowercase();
+		let keep = recent.len().saturating_sub(offset);
+		Ok(recent.into_iter().take(keep).collect())
+	}
+
+	/// Number of entries matching `query`, for computing page counts
+	pub async fn count_logs(&self, query: &LogQuery) -> Result<usize> {
+		let _file_guard = self.log_file.lock().await;
 
-		for line in lines.iter().rev() {
-			if matching_entries.len() >= limit {
-				break;
+		let mut count = 0;
+		self.for_each_line(|line| {
+			if RequestLogEntry::from_line(line).is_some_and(|entry| query.matches(&entry)) {
+				count += 1;
 			}
+		}).await?;
+		Ok(count)
+	}
 
-			if let Ok(entry) = serde_json::from_str::<RequestLogEntry>(line) {
-				if entry.request.url.to_lowercase().contains(&query_lower)
-					|| entry.request.method.to_lowercase().contains(&query_lower)
-					|| entry.request.body_preview.to_lowercase().contains(&query_lower)
-					|| entry.request.headers.values().any(|v| v.to_lowercase().contains(&query_lower))
-				{
-					matching_entries.push(entry);
-				}
+	/// All entries matching `query` in chronological order
+	pub async fn matching_entries(&self, query: &LogQuery) -> Result<Vec<RequestLogEntry>> {
+		let _file_guard = self.log_file.lock().await;
+
+		let mut entries = Vec::new();
+		self.for_each_line(|line| {
+			if let Some(entry) = RequestLogEntry::from_line(line).filter(|entry| query.matches(entry)) {
+				entries.push(entry);
 			}
+		}).await?;
+		Ok(entries)
+	}
+
+	/// Rewrite the log (rotated files included) keeping only entries newer than
+	/// `cutoff` and, of those, at most the `keep_last` most recent. Kept lines are
+	/// copied unchanged, so their ids stay the same.
+	pub async fn prune(
+		&self,
+		cutoff: Option<chrono::DateTime<chrono::Utc>>,
+		keep_last: Option<usize>,
+	) -> Result<PruneSummary> {
+		let mut log = self.log_file.lock().await;
+		log.file.flush().await?;
+
+		let mut total = 0;
+		self.for_each_line(|line| {
+			if serde_json::from_str::<EntryTimestamp>(line).is_ok() {
+				total += 1;
+			}
+		}).await?;
+		let policy = PrunePolicy {
+			first_kept: keep_last.map_or(0, |keep| total - keep.min(total)),
+			cutoff,
+		};
+
+		let active = PathBuf::from(&self.log_file_path);
+		let mut summary = PruneSummary::default();
+		let mut index = 0;
+		for path in log_files(&active) {
+			if !path.exists() {
+				continue;
+			}
+
+			let (file_summary, next_index) = if path.extension().is_some_and(|e| e == "gz") {
+				let gz_path = path.clone();
+				tokio::task::spawn_blocking(move || prune_gz_file(&gz_path, policy, index)).await??
+			} else {
+				prune_plain_file(&path, policy, index).await?
+			};
+			index = next_index;
+
+			if path != active && file_summary.kept == 0 {
+				tokio::fs::remove_file(&path).await?;
+				summary.files_deleted += 1;
+			}
+			summary.add(file_summary);
 		}
 
-		matching_entries.reverse();
-		Ok(matching_entries)
+		// The active file was replaced, so the append handle must be reopened
+		log.file = OpenOptions::new().create(true).append(true).open(&self.log_file_path).await?;
+		log.size = log.file.metadata().await?.len();
+		Ok(summary)
 	}
 
-	pub async fn get_request_stats(&self) -> Result<RequestStats> {
-		let content = tokio::fs::read_to_string(&self.log_file_path).await?;
-		let lines: Vec<&str> = content.lines().collect();
+	/// Poll the active file and pass entries matching `query` to `on_entry` as
+	/// they are appended, e.g. by a monitor or proxy running in another process.
+	/// Runs until the future is dropped. A file that shrinks was rotated or
+	/// truncated, so reading restarts at its beginning.
+	pub async fn follow(&self, query: &LogQuery, mut on_entry: impl FnMut(&RequestLogEntry)) -> Result<()> {
+		let file_len = || async {
+			match tokio::fs::metadata(&self.log_file_path).await {
+				Ok(metadata) => Ok(metadata.len()),
+				Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+				Err(e) => Err(e),
+			}
+		};
+
+		let mut offset = file_len().await?;
+		let mut pending: Vec<u8> = Vec::new();
+		let mut ticker = tokio::time::interval(FOLLOW_POLL_INTERVAL);
 
+		loop {
+			ticker.tick().await;
+
+			let len = file_len().await?;
+			if len < offset {
+				offset = 0;
+				pending.clear();
+			}
+			if len == offset {
+				continue;
+			}
+
+			let mut file = tokio::fs::File::open(&self.log_file_path).await?;
+			file.seek(std::io::SeekFrom::Start(offset)).await?;
+			let read = file.take(len - offset).read_to_end(&mut pending).await?;
+			offset += read as u64;
+
+			// A line may still be half written; keep the tail for the next poll
+			while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+				let line: Vec<u8> = pending.drain(..=end).collect();
+				let line = String::from_utf8_lossy(trim_newline(&line)).into_owned();
+				if let Some(entry) = RequestLogEntry::from_line(&line).filter(|entry| query.matches(entry)) {
+					on_entry(&entry);
+				}
+			}
+		}
+	}
+
+	pub async fn get_request_stats(&self) -> Result<RequestStats> {
+		let _file_guard = self.log_file.lock().await;
 		let mut stats = RequestStats::default();
 
-		for line in lines {
+		self.for_each_line(|line| {
 			if let Ok(entry) = serde_json::from_str::<RequestLogEntry>(line) {
 				stats.total_requests += 1;
 
@@ -305,7 +790,7 @@ impl RequestLogger {
 					stats.total_response_time += response.response_time_ms;
 				}
 			}
-		}
+		}).await?;
 
 		if stats.total_requests > 0 {
 			stats.average_response_time = stats.total_response_time / (stats.total_requests as u64);
@@ -313,4 +798,155 @@ impl RequestLogger {
 
 		Ok(stats)
 	}
-}
\ No newline at end of file
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+	let mut name = path.as_os_str().to_owned();
+	name.push(".prune-tmp");
+	PathBuf::from(name)
+}
+
+/// Copy the kept lines of `path` to a temporary file and swap it in.
+/// Returns the file's summary and the entry counter after it.
+async fn prune_plain_file(path: &Path, policy: PrunePolicy, mut index: usize) -> Result<(PruneSummary, usize)> {
+	let mut summary = PruneSummary::default();
+	let tmp = temp_path(path);
+
+	let mut reader = tokio::io::BufReader::new(tokio::fs::File::open(path).await?);
+	let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(&tmp).await?);
+	let mut buf = Vec::new();
+	while reader.read_until(b'\n', &mut buf).await? > 0 {
+		let line = trim_newline(&buf);
+		if policy.keep(&String::from_utf8_lossy(line), &mut index, &mut summary) {
+			writer.write_all(line).await?;
+			writer.write_all(b"\n").await?;
+		}
+		buf.clear();
+	}
+	writer.flush().await?;
+
+	tokio::fs::rename(&tmp, path).await?;
+	Ok((summary, index))
+}
+
+/// Same as [`prune_plain_file`] for a gzipped rotated file
+fn prune_gz_file(path: &Path, policy: PrunePolicy, mut index: usize) -> Result<(PruneSummary, usize)> {
+	use std::io::Write;
+
+	let mut summary = PruneSummary::default();
+	let tmp = temp_path(path);
+
+	let mut reader = std::io::BufReader::new(flate2::read::GzDecoder::new(std::fs::File::open(path)?));
+	let mut writer = flate2::write::GzEncoder::new(std::fs::File::create(&tmp)?, flate2::Compression::default());
+	let mut buf = Vec::new();
+	while reader.read_until(b'\n', &mut buf)? > 0 {
+		let line = trim_newline(&buf);
+		if policy.keep(&String::from_utf8_lossy(line), &mut index, &mut summary) {
+			writer.write_all(line)?;
+			writer.write_all(b"\n")?;
+		}
+		buf.clear();
+	}
+	writer.finish()?;
+
+	std::fs::rename(&tmp, path)?;
+	Ok((summary, index))
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+	let line = line.strip_suffix(b"\n").unwrap_or(line);
+	line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// `requests.log.3` / `requests.log.3.gz` → 3
+fn rotation_index(base_name: &str, file_name: &str) -> Option<usize> {
+	let suffix = file_name.strip_prefix(base_name)?.strip_prefix('.')?;
+	suffix.strip_suffix(".gz").unwrap_or(suffix).parse().ok()
+}
+
+/// The active log and its rotated siblings, oldest first. Rotated files are
+/// found on disk, so readers see them whatever the current rotation settings.
+fn log_files(path: &Path) -> Vec<PathBuf> {
+	let base_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+	let dir = match path.parent() {
+		Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+		_ => PathBuf::from("."),
+	};
+
+	let mut rotated: Vec<(usize, PathBuf)> = std::fs::read_dir(&dir)
+		.map(|entries| {
+			entries
+				.flatten()
+				.filter_map(|entry| {
+					let index = rotation_index(&base_name, &entry.file_name().to_string_lossy())?;
+					Some((index, entry.path()))
+				})
+				.collect()
+		})
+		.unwrap_or_default();
+	rotated.sort_by_key(|(index, _)| std::cmp::Reverse(*index));
+
+	let mut files: Vec<PathBuf> = rotated.into_iter().map(|(_, path)| path).collect();
+	files.push(path.to_path_buf());
+	files
+}
+
+fn rotated_path(path: &Path, index: usize, compressed: bool) -> PathBuf {
+	let mut name = path.as_os_str().to_owned();
+	name.push(format!(".{}", index));
+	if compressed {
+		name.push(".gz");
+	}
+	PathBuf::from(name)
+}
+
+/// Shift `log.N` to `log.N+1`, dropping files beyond `keep`, then move the
+/// active file to `log.1` (gzipped when configured)
+fn rotate_files(path: &Path, rotation: &LogRotation) -> std::io::Result<()> {
+	let remove = |p: PathBuf| match std::fs::remove_file(&p) {
+		Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+		_ => Ok(()),
+	};
+
+	if rotation.keep == 0 {
+		return remove(path.to_path_buf());
+	}
+
+	for compressed in [false, true] {
+		remove(rotated_path(path, rotation.keep, compressed))?;
+	}
+	for index in (1..rotation.keep).rev() {
+		for compressed in [false, true] {
+			let from = rotated_path(path, index, compressed);
+			if from.exists() {
+				std::fs::rename(&from, rotated_path(path, index + 1, compressed))?;
+			}
+		}
+	}
+
+	let first = rotated_path(path, 1, false);
+	std::fs::rename(path, &first)?;
+
+	if rotation.compress {
+		let mut input = std::fs::File::open(&first)?;
+		let output = std::fs::File::create(rotated_path(path, 1, true))?;
+		let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+		std::io::copy(&mut input, &mut encoder)?;
+		encoder.finish()?;
+		std::fs::remove_file(&first)?;
+	}
+	Ok(())
+}
+
+/// Header and body bytes of `request`
+fn request_size(request: &HttpRequest) -> u64 {
+	request.headers.iter().map(|(k, v)| (k.len() + v.len() + 4) as u64).sum::<u64>() + request.body.len() as u64
+}
+
+/// The first 1000 characters of a request body, cut on a character boundary
+fn body_preview(body: &str) -> String {
+	match body.char_indices().nth(1000) {
+		Some((end, _)) => format!("{}...", &body[..end]),
+		None => body.to_string(),
+	}
+}
@@ -1,28 +1,88 @@
+mod access;
+mod assertion;
+mod auth;
+mod baseline;
+mod bench;
+mod bpf;
+mod browser_cookies;
+mod cache_analyzer;
 mod cli;
+mod collection;
 mod config;
+mod content_encoding;
 mod cookie_manager;
+mod curl;
+mod dissector;
+mod dns;
+mod doctor;
+mod download;
+mod endpoints;
+mod export;
+mod flows;
+mod fuzz;
+mod geoip;
+mod har;
+mod hosts;
+mod interfaces;
+mod ndjson;
 mod network;
+mod notify;
+mod openapi;
+mod output;
+mod pac;
+mod pagination;
+mod quic;
+mod recurring;
 mod http_client;
+mod jsonpath;
 mod logger;
+mod mitm;
+mod mock;
+mod pager;
 mod performance_analyzer;
+mod postman;
+mod proxy_cache;
+mod redact;
+mod replay;
+mod response_diff;
+mod rewrite;
+mod rules;
+mod script;
+mod security;
+mod sequence;
+mod shaping;
+mod shutdown;
+mod stats;
+mod system_proxy;
+mod telemetry;
+mod template;
+mod timeline;
+mod tls_decrypt;
+mod tls_inspector;
+mod traceroute;
+mod transparent;
+mod watch;
+mod websocket;
 
 use anyhow::Result;
 use clap::Parser;
-use cli::{Cli, Commands, CookieAction};
+use cli::{CacheAction, Cli, Commands, ConfigAction, CookieAction, LogsAction, ProxyAction};
 use config::Config;
 use cookie_manager::CookieManager;
-use http_client::{HttpClient, HttpRequestBuilder};
-use logger::RequestLogger;
-use network::{HttpParser, PacketMonitor};
+use http_client::{HttpClient, HttpRequestBuilder, HttpVersion, RequestBody, RetryPolicy};
+use logger::{LogQuery, LogRotation, RequestLogEntry, RequestLogger};
+use network::{ExchangeTracker, HttpParser, PacketMonitor};
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
 use tracing::{debug, error, info, trace, warn};
 use tracing_subscriber::{fmt, EnvFilter};
 
 #[tokio::main]
 async fn main() -> Result<()> {
 	let cli = Cli::parse();
+	output::set_json(cli.json);
 
 	let log_level = cli.log_level.unwrap_or_else(|| "info".to_string());
 
@@ -33,63 +93,703 @@ async fn main() -> Result<()> {
 		}
 	};
 
+	// Diagnostics go to stderr so stdout stays clean for scripted use (e.g. `request --extract`)
 	fmt()
 		.with_env_filter(env_filter)
 		.with_level(true)
 		.with_target(true)
+		.with_writer(std::io::stderr)
 		.pretty()
 		.init();
 
-	println!("Riddler 正在启动，日志级别: {}", log_level);
+	eprintln!("Riddler 正在启动，日志级别: {}", log_level);
 	info!("Starting Riddler with log level: {}", log_level);
 	debug!("Debug logging enabled");
 
-	let config = Config::default();
+	let config_path = cli.config.clone().unwrap_or_else(|| std::path::PathBuf::from(config::DEFAULT_CONFIG_FILE));
+	// `config init` creates the file, so it must not be required to exist yet
+	let creating_config = matches!(cli.command, Commands::Config { action: ConfigAction::Init { .. } });
+	let mut config = if creating_config {
+		Config::default()
+	} else {
+		Config::load_or_default(&config_path, cli.config.is_some())?
+	};
 
 
-	let cookie_manager = Arc::new(CookieManager::new(config.storage.cookie_cache_path.clone()));
-	let http_client = Arc::new(HttpClient::new(cookie_manager.clone())?);
-	let logger = Arc::new(RequestLogger::new(&config.storage.request_log_path).await?);
+	let cookie_path = match cli.session {
+		Some(ref session) => {
+			info!("Using session '{}'", session);
+			cookie_manager::session_path(&config.storage.cookie_cache_path, session)?
+		}
+		None => config.storage.cookie_cache_path.clone(),
+	};
+	let cookie_manager = Arc::new(CookieManager::new(cookie_path));
+	if let Some(endpoint) = cli.otlp_endpoint {
+		config.telemetry.enabled = true;
+		config.telemetry.endpoint = Some(endpoint);
+	}
+	// Dropped when main returns, flushing the spans not exported yet
+	let _telemetry = config.telemetry.enabled.then(|| telemetry::Telemetry::init(&config.telemetry)).transpose()?;
+	let scripts = cli.script.as_deref().map(script::ScriptHooks::load).transpose()?.map(Arc::new);
+	let http_client = Arc::new(
+		HttpClient::new(cookie_manager.clone())?
+			.with_raw_bodies(cli.raw_body)
+			.with_trace_export(config.telemetry.enabled)?
+			.with_scripts(scripts.clone()),
+	);
+	// Monitor and proxy runs count what they log per host as they go
+	let session = match &cli.command {
+		Commands::Monitor { .. } => Some("monitor"),
+		Commands::Proxy { action: None, .. } => Some("proxy"),
+		_ => None,
+	}.map(|command| Arc::new(hosts::SessionRecorder::new(&config.storage.sessions_path, command)));
+	let logger = Arc::new(
+		RequestLogger::new(&config.storage.request_log_path).await?
+			.with_rotation(LogRotation::from_config(&config.storage))
+			.with_session(session.clone())
+			.with_redaction(if config.redaction.enabled && !cli.no_redact {
+				Some(redact::Redactor::from_config(&config.redaction)?)
+			} else {
+				None
+			}),
+	);
 
 
 	if let Err(e) = cookie_manager.load_from_file().await {
 		warn!("Failed to load cookies from file: {}", e);
 	}
 
+	if !matches!(cli.command, Commands::Cookie { .. }) {
+		warn_expiring_cookies(&cookie_manager, &cli.watch_cookie_domains);
+	}
+
 	match cli.command {
-		Commands::Monitor { interface, filter, replay } => {
-			start_monitor(interface, filter, replay, cookie_manager.clone(), http_client.clone(), logger.clone()).await?;
+		Commands::Monitor {
+			interface, filter, preset, replay, save_pcap, rotate_size,
+			match_host, match_path, match_method, match_header, output, output_to, geoip_db, rules,
+			tls_keylog, read_pcap,
+		} => {
+			let options = MonitorOptions {
+				replay,
+				request_filter: network::RequestFilter::new(match_host, match_path, match_method, match_header)?,
+				save_pcap: save_pcap.map(|path| network::PcapSaveOptions {
+					path,
+					rotate_bytes: rotate_size.map(|mb| mb * 1024 * 1024),
+				}),
+				queue_capacity: config.network.queue_capacity,
+				ndjson_target: (output == ndjson::MonitorOutput::Ndjson).then_some(output_to),
+				geoip: (!geoip_db.is_empty()).then(|| geoip::GeoIp::open(&geoip_db)).transpose()?,
+				rules: rules
+					.map(|path| rules::RuleSet::load(&path))
+					.transpose()?
+					.map(|rules| rules.with_notifier(notify::Notifier::new(&config.notifications, http_client.clone()))),
+				tls_keylog: tls_keylog.map(tls_decrypt::KeyLog::open).transpose()?,
+				read_pcap,
+				raw_body: cli.raw_body,
+			};
+			let interfaces = if interface.is_empty() { vec![config.network.interface.clone()] } else { interface };
+			let filter = bpf::build_filter(&preset, filter, &config.network.monitor_filter)?;
+			start_monitor(interfaces, filter, options, http_client.clone(), logger.clone()).await?;
 		}
 
-		Commands::Request { method, url, headers, body, timeout } => {
-			send_manual_request(method, url, headers, body, timeout, http_client.clone(), logger.clone()).await?;
+		Commands::Request {
+			method, url, headers, body, body_file, timeout, json_pretty, extract,
+			follow_pagination, next_link_header, next_field, max_pages, no_follow, output: download_path, resume, assertions, auth, retry, connection,
+		} => {
+			let assertions = assertions.iter()
+				.map(|source| assertion::Assertion::parse(source))
+				.collect::<Result<Vec<_>>>()?;
+			let http_client = connection_client(&connection, &http_client, &cookie_manager)?;
+			let request = template_context(cli.env_file.as_deref())?.expand_request(&HttpRequestBuilder {
+				method,
+				url,
+				headers: cli::parse_headers(headers),
+				body: read_body(body, body_file.as_deref())?,
+				timeout_seconds: timeout.unwrap_or(config.http.timeout_seconds),
+				follow_redirects: !no_follow,
+				verify_ssl: !connection.insecure,
+				retry: retry.policy(),
+				auth: auth.auth()?,
+				http_version: connection.http_version,
+			})?;
+			let output = ResponseOutput { json_pretty, extract };
+			if let Some(output) = download_path {
+				let options = download::DownloadOptions { output, resume };
+				download_to_file(request, options, http_client.clone(), logger.clone()).await?;
+			} else if follow_pagination {
+				let pagination = pagination::PaginationOptions { next_link_header, next_field, max_pages };
+				send_paginated_request(request, output, pagination, http_client.clone(), logger.clone()).await?;
+			} else {
+				send_manual_request(request, output, &assertions, http_client.clone(), logger.clone()).await?;
+			}
 		}
 
 		Commands::Cookie { action } => {
 			handle_cookie_command(action, cookie_manager.clone()).await?;
 		}
 
-		Commands::Logs { limit, source, query, stats, path } => {
-			if let Some(ref custom_path) = path {
-				println!("使用自定义日志文件: {}", custom_path);
+		Commands::Logs {
+			limit, source, query, regex, page, page_size, pager, id, as_curl, follow, top, security_report, stats, path,
+			export_har, format: export_format, output, infer_openapi, action,
+		} => {
+			let query = LogQuery::new(source, &query, regex)?;
+
+			let format = if output::json() {
+				LogFormat::Json
+			} else if as_curl {
+				LogFormat::Curl(cookie_manager.clone())
+			} else {
+				LogFormat::Text
+			};
+			let view = if let Some(id) = id {
+				LogView::Entry(id)
+			} else if pager {
+				if output::json() {
+					return Err(anyhow::anyhow!("--pager cannot be combined with --json"));
+				}
+				LogView::Pager
+			} else if follow {
+				LogView::Follow(limit)
+			} else if let Some(page) = page {
+				LogView::Page { page, page_size }
+			} else {
+				LogView::Recent(limit)
+			};
+
+			let log_path = path.clone().unwrap_or_else(|| config.storage.request_log_path.clone());
+			if let Some(LogsAction::Timeline { since, output }) = action {
+				let timeline_logger = RequestLogger::new(&log_path).await?;
+				export_timeline(query, &since, &output, &timeline_logger).await?;
+			} else if let Some(LogsAction::Sequence { since, output, format, limit }) = action {
+				let sequence_logger = RequestLogger::new(&log_path).await?;
+				export_sequence(query, &since, &output, format, limit, &sequence_logger).await?;
+			} else if let Some(LogsAction::Prune { older_than, keep_last }) = action {
+				let cutoff = match older_than {
+					Some(age) => Some(chrono::Utc::now() - timeline::parse_since(&age)?),
+					None => None,
+				};
+				let prune_logger = match path {
+					Some(ref custom_path) => Arc::new(RequestLogger::new(custom_path).await?),
+					None => logger.clone(),
+				};
+				let summary = prune_logger.prune(cutoff, keep_last).await?;
+				say!("✓ Pruned {}: removed {} entries, kept {}", log_path, summary.removed, summary.kept);
+				if summary.invalid > 0 {
+					say!("  Dropped {} unparseable lines", summary.invalid);
+				}
+				if summary.files_deleted > 0 {
+					say!("  Deleted {} empty rotated files", summary.files_deleted);
+				}
+			} else if let Some(output) = infer_openapi {
+				let entries = RequestLogger::new(&log_path).await?.matching_entries(&query).await?;
+				let summary = openapi::write(&entries, &output)?;
+				say!("✓ Inferred {} operations on {} paths of {} host(s) from {} entries: {}",
+					summary.operations, summary.paths, summary.hosts, summary.entries, output.display());
+			} else if let Some(output) = export_har {
+				let entries = RequestLogger::new(&log_path).await?.matching_entries(&query).await?;
+				let count = har::export(&entries, &output)?;
+				say!("✓ Exported {} entries to {}", count, output.display());
+			} else if let Some(export_format) = export_format {
+				let entries = RequestLogger::new(&log_path).await?.matching_entries(&query).await?;
+				let count = export::export(&entries, &export_format, output.as_deref())?;
+				if let Some(output) = output {
+					say!("✓ Exported {} entries to {}", count, output.display());
+				}
+			} else if let Some(top) = top {
+				let entries = RequestLogger::new(&log_path).await?.matching_entries(&query).await?;
+				if output::json() {
+					output::emit(&endpoints::json_report(endpoints::aggregate(&entries), top))?;
+				} else {
+					endpoints::print_report(endpoints::aggregate(&entries), top);
+				}
+			} else if security_report {
+				let entries = RequestLogger::new(&log_path).await?.matching_entries(&query).await?;
+				let findings = security::scan(&entries);
+				if output::json() {
+					output::emit(&serde_json::json!({ "entries": entries.len(), "findings": findings }))?;
+				} else {
+					security::print_report(&findings, entries.len());
+				}
+			} else if let Some(ref custom_path) = path {
+				say!("使用自定义日志文件: {}", custom_path);
 				let custom_logger = Arc::new(RequestLogger::new(custom_path).await?);
-				show_logs(limit, source, query, stats, custom_logger).await?;
+				show_logs(query, view, format, stats, custom_logger).await?;
+			} else {
+				say!("使用默认日志文件: {}", config.storage.request_log_path);
+				show_logs(query, view, format, stats, logger.clone()).await?;
+			}
+		}
+
+		Commands::Replay {
+			limit, source, count, delay, mode, parallel, rps, max_rps, respect_rate_limits, shuffle, preserve_timing, headers,
+			rewrite_host, rewrite_header, strip_header, diff, har, every, cron, runs,
+		} => {
+			if rps.is_some_and(|rps| !(rps > 0.0 && rps.is_finite())) {
+				return Err(anyhow::anyhow!("--rps must be a positive number"));
+			}
+			if max_rps.is_some_and(|rps| !(rps > 0.0 && rps.is_finite())) {
+				return Err(anyhow::anyhow!("--max-rps must be a positive number"));
+			}
+			let headers = cli::parse_headers(headers);
+			let rewrite = replay::RewriteRules {
+				host: rewrite_host,
+				set_headers: cli::parse_headers(rewrite_header),
+				strip_headers: strip_header,
+			};
+			let options = replay::ReplayOptions {
+				count, delay, mode, parallel, rps, max_rps, respect_rate_limits, shuffle, preserve_timing, headers, rewrite, diff,
+			};
+			let schedule = match (every, cron) {
+				(Some(every), _) => Some(recurring::Schedule::every(&every)?),
+				(None, Some(cron)) => Some(recurring::Schedule::cron(&cron)?),
+				(None, None) => None,
+			};
+			if let Some(schedule) = schedule {
+				// The set is chosen once, so later runs do not pick up their own replays
+				let targets = match har {
+					Some(har_path) => har::replay_targets(&har_path)?,
+					None => logged_replay_targets(limit, source.as_deref(), &logger).await?,
+				};
+				if targets.is_empty() {
+					return Err(anyhow::anyhow!("No requests found to replay"));
+				}
+				let recurring = recurring::RecurringOptions {
+					schedule,
+					runs,
+					history_path: std::path::PathBuf::from(&config.storage.replay_history_path),
+					notifier: notify::Notifier::new(&config.notifications, http_client.clone()),
+				};
+				recurring::run(targets, options, recurring, http_client.clone(), logger.clone()).await?;
+			} else {
+				let started = std::time::Instant::now();
+				let sends = if let Some(har_path) = har {
+					let targets = har::replay_targets(&har_path)?;
+					if targets.is_empty() {
+						say!("No HTTP requests found in {}", har_path.display());
+						Vec::new()
+					} else {
+						say!("Loaded {} requests from {}", targets.len(), har_path.display());
+						replay::run(targets, options, http_client.clone(), logger.clone()).await?
+					}
+				} else {
+					replay_requests(limit, source, options, http_client.clone(), logger.clone()).await?
+				};
+				let notifier = notify::Notifier::new(&config.notifications, http_client.clone());
+				if !sends.is_empty() && notifier.announces_replay(started.elapsed()) {
+					notifier.send(&replay::finished_notification("Replay", &sends, started.elapsed())).await;
+				}
+				if output::json() {
+					output::emit(&sends)?;
+				}
+			}
+		}
+
+		Commands::Fuzz {
+			from_log, source, generators, targets, payloads, skip_header, max_mutations, concurrency, rps, timeout,
+			size_threshold, rewrite_host, dry_run, fail_on_findings,
+		} => {
+			if rps.is_some_and(|rps| !(rps > 0.0 && rps.is_finite())) {
+				return Err(anyhow::anyhow!("--rps must be a positive number"));
+			}
+			// Lines are kept verbatim: leading spaces and '#' can be part of a payload
+			let payloads = match payloads {
+				Some(path) => std::fs::read_to_string(&path)
+					.map_err(|e| anyhow::anyhow!("Unable to read payloads {}: {}", path.display(), e))?
+					.lines()
+					.filter(|line| !line.is_empty())
+					.map(str::to_string)
+					.collect(),
+				None => Vec::new(),
+			};
+			let options = fuzz::FuzzOptions {
+				generators,
+				targets,
+				payloads,
+				skip_headers: skip_header,
+				max_mutations,
+				concurrency,
+				rps,
+				timeout,
+				size_threshold,
+				rewrite: replay::RewriteRules { host: rewrite_host, ..Default::default() },
+				dry_run,
+			};
+			let requests = logged_replay_targets(from_log, source.as_deref(), &logger).await?;
+			if requests.is_empty() {
+				say!("No requests found to fuzz");
+			} else {
+				let reports = fuzz::run(requests, options, http_client.clone()).await?;
+				if output::json() {
+					output::emit(&reports)?;
+				}
+				let findings: usize = reports.iter().map(|report| report.findings.len()).sum();
+				if fail_on_findings && findings > 0 {
+					return Err(anyhow::anyhow!("Fuzzing found {} anomalies", findings));
+				}
+			}
+		}
+
+		Commands::Hosts { sessions, .. } if sessions => {
+			let sessions = hosts::load_sessions(std::path::Path::new(&config.storage.sessions_path))?;
+			if output::json() {
+				output::emit(&sessions)?;
+			} else if sessions.is_empty() {
+				println!("No monitor or proxy sessions recorded yet in {}", config.storage.sessions_path);
+			} else {
+				hosts::print_sessions(&sessions);
+			}
+		}
+
+		Commands::Hosts { session: Some(id), sort, top, .. } => {
+			show_session(&config.storage.sessions_path, &id, sort, top)?;
+		}
+
+		Commands::Hosts { source, sort, top, interface, duration, path, .. } => {
+			let logger = match path {
+				Some(custom_path) => Arc::new(RequestLogger::new(&custom_path).await?),
+				None => logger.clone(),
+			};
+			let query = LogQuery { source, ..Default::default() };
+			let live = interface.map(|interface| (interface, config.network.monitor_filter.clone(), duration));
+			show_hosts(query, sort, top, live, logger).await?;
+		}
+
+		Commands::Flows { interface, filter, preset, duration, refresh, sort, top, idle_timeout } => {
+			let interfaces = if interface.is_empty() { vec![config.network.interface.clone()] } else { interface };
+			let filter = bpf::build_filter(&preset, filter, &config.network.monitor_filter)?;
+			let options = FlowOptions {
+				duration: (duration > 0).then(|| tokio::time::Duration::from_secs(duration)),
+				refresh: tokio::time::Duration::from_secs(refresh.max(1)),
+				sort,
+				top,
+				idle_timeout: chrono::Duration::seconds(idle_timeout as i64),
+			};
+			show_flows(interfaces, filter, options).await?;
+		}
+
+		Commands::AnalyzeCache { url, from_logs, no_revalidate, path } => {
+			let logger = match path {
+				Some(custom_path) => Arc::new(RequestLogger::new(&custom_path).await?),
+				None => logger.clone(),
+			};
+			analyze_cache(url, from_logs, !no_revalidate, http_client.clone(), logger).await?;
+		}
+
+		Commands::Diff { url_a, url_b, log_id, method, headers, body, ignore_header, ignore_path, path, fail_on_diff } => {
+			let sides = if let (Some(url_a), Some(url_b)) = (url_a, url_b) {
+				let context = template_context(cli.env_file.as_deref())?;
+				let mut sides = Vec::new();
+				for url in [url_a, url_b] {
+					let request = context.expand_request(&HttpRequestBuilder {
+						method: method.clone(),
+						url,
+						headers: cli::parse_headers(headers.clone()),
+						body: body.clone().map(RequestBody::Text),
+						timeout_seconds: config.http.timeout_seconds,
+						follow_redirects: true,
+						verify_ssl: true,
+						retry: RetryPolicy::default(),
+						auth: None,
+						http_version: HttpVersion::Auto,
+					})?;
+					sides.push(fetch_diff_side(request, &http_client, &logger).await?);
+				}
+				sides
+			} else {
+				let [id_a, id_b] = <[String; 2]>::try_from(log_id)
+					.map_err(|ids| anyhow::anyhow!("Give --log-id exactly twice, got {}", ids.len()))?;
+				let logger = match path {
+					Some(custom_path) => Arc::new(RequestLogger::new(&custom_path).await?),
+					None => logger.clone(),
+				};
+				let mut sides = Vec::new();
+				for id in [id_a, id_b] {
+					let entry = log_entry_by_id(&logger, &id, &LogQuery::default()).await?;
+					let response = entry.response.clone()
+						.ok_or_else(|| anyhow::anyhow!("Log entry #{} has no response to compare", entry.id))?;
+					sides.push(DiffSide::new(Some(entry.id), entry.request.method, entry.request.url, response));
+				}
+				sides
+			};
+			let [a, b] = <[DiffSide; 2]>::try_from(sides).map_err(|_| anyhow::anyhow!("Expected two responses"))?;
+			diff_responses(a, b, &ignore_header, &ignore_path, fail_on_diff)?;
+		}
+
+		Commands::TlsInfo { target, url, fail_within_days, timeout } => {
+			let target = target.or(url).ok_or_else(|| anyhow::anyhow!("No TLS target given"))?;
+			tls_info(target, fail_within_days, timeout).await?;
+		}
+
+		Commands::Import { file, output, run } => {
+			let collection = postman::import(&file)?;
+			let output = output.unwrap_or_else(|| file.with_extension("riddler.json"));
+			collection.save(&output)?;
+			println!("✅ Imported {} requests from '{}' into {}",
+				collection.requests.len(), collection.name, output.display());
+
+			if run {
+				let run = CollectionRun { file: output, folder: None, vars: Vec::new(), delay: 0, timeout: config.http.timeout_seconds };
+				run_collection(run, cli.env_file.as_deref(), http_client.clone(), logger.clone()).await?;
+			}
+		}
+
+		Commands::Run { file, folder, vars, delay, timeout } => {
+			let run = CollectionRun { file, folder, vars, delay, timeout: timeout.unwrap_or(config.http.timeout_seconds) };
+			run_collection(run, cli.env_file.as_deref(), http_client.clone(), logger.clone()).await?;
+		}
+
+		Commands::Dns { name, servers, types, count, timeout } => {
+			dns_diagnostics(name, servers, types, count, timeout).await?;
+		}
+
+		Commands::Interfaces => {
+			let interfaces = interfaces::list()?;
+			if output::json() {
+				output::emit(&interfaces)?;
 			} else {
-				println!("使用默认日志文件: {}", config.storage.request_log_path);
-				show_logs(limit, source, query, stats, logger.clone()).await?;
+				interfaces::print_interfaces(&interfaces, &config.network.interface);
+			}
+		}
+
+		Commands::Doctor { interface } => {
+			let mut config = config.clone();
+			if let Some(interface) = interface {
+				config.network.interface = interface;
 			}
+
+			println!("🩺 Checking the Riddler runtime environment...\n");
+			let checks = doctor::run_checks(&config).await;
+			doctor::print_checks(&checks);
+
+			let failed = checks.iter().filter(|c| c.status == doctor::CheckStatus::Fail).count();
+			let warned = checks.iter().filter(|c| c.status == doctor::CheckStatus::Warn).count();
+			println!("\n{} checks: {} failed, {} warnings", checks.len(), failed, warned);
+			if failed > 0 {
+				return Err(anyhow::anyhow!("{} environment check(s) failed", failed));
+			}
+		}
+
+		Commands::Config { action } => {
+			handle_config_command(action, &config, &config_path)?;
+		}
+
+		Commands::Cache { action } => {
+			let cache = proxy_cache::ProxyCache::open(std::path::Path::new(&config.proxy.cache_dir), config.storage.max_cache_size)?;
+			handle_cache_command(action, &cache)?;
 		}
 
-		Commands::Replay { limit, source, count, delay, mode } => {
-			replay_requests(limit, source, count, delay, mode, http_client.clone(), logger.clone()).await?;
+		Commands::Proxy {
+			address, port, mitm, transparent, mock, rewrite, delay, jitter, throttle, drop_rate, seed,
+			allow_client, deny_client, allow_host, block_host, auth, cache, serve_pac, system_proxy, action,
+		} => {
+			let address = address.unwrap_or_else(|| config.proxy.bind_address.to_string());
+			let port = port.unwrap_or(config.proxy.bind_port);
+			let backup_path = std::path::PathBuf::from(&config.proxy.system_proxy_backup);
+			if let Some(action) = action {
+				handle_proxy_action(action, &address, port, &backup_path)?;
+				return Ok(());
+			}
+			let mut proxy_config = config.proxy.clone();
+			proxy_config.allow_clients.extend(allow_client);
+			proxy_config.deny_clients.extend(deny_client);
+			proxy_config.allow_hosts.extend(allow_host);
+			proxy_config.block_hosts.extend(block_host);
+			if auth.is_some() {
+				proxy_config.auth = auth;
+			}
+			if transparent {
+				if !transparent::SUPPORTED {
+					return Err(anyhow::anyhow!("--transparent needs Linux (iptables/nftables) or macOS/BSD (pf)"));
+				}
+				// Redirected clients do not know they talk to a proxy and never authenticate
+				if proxy_config.auth.is_some() {
+					return Err(anyhow::anyhow!("--transparent cannot be combined with proxy authentication (--auth or proxy.auth)"));
+				}
+			}
+			let access = access::AccessControl::new(&proxy_config)?;
+			let pac = serve_pac.then(|| pac::PacFile::new(format!("{}:{}", reachable_host(&address), port), proxy_config.allow_hosts.clone()));
+			let mocks = match mock {
+				Some(path) => {
+					let mocks = mock::MockSet::load(&path)?;
+					info!("Loaded {} mocks from {}", mocks.len(), path.display());
+					Some(Arc::new(mocks))
+				}
+				None => None,
+			};
+			let rewrites = match rewrite {
+				Some(path) => {
+					let rewrites = rewrite::RewriteRules::load(&path)?;
+					info!("Loaded {} rewrite rules from {}", rewrites.len(), path.display());
+					Some(Arc::new(rewrites))
+				}
+				None => None,
+			};
+			let cache = if cache {
+				let cache = proxy_cache::ProxyCache::open(std::path::Path::new(&config.proxy.cache_dir), config.storage.max_cache_size)?;
+				println!("💾 响应缓存已启用: {} (已有 {} 条), 可用 cache stats 查看命中情况", cache.dir().display(), cache.len());
+				Some(Arc::new(cache))
+			} else {
+				None
+			};
+			// Plain HTTP always goes through the interceptor; only --mitm lets
+			// it decrypt TLS as well
+			let ca = mitm.then(|| mitm::CertificateAuthority::load_or_create(
+				std::path::Path::new(&config.proxy.ca_cert_path),
+				std::path::Path::new(&config.proxy.ca_key_path),
+			)).transpose()?;
+			let interceptor = Arc::new(
+				mitm::Interceptor::new(ca, logger.clone())?
+					.with_raw_bodies(cli.raw_body)
+					.with_scripts(scripts.clone())
+					.with_mocks(mocks.clone())
+					.with_rewrites(rewrites.clone())
+					.with_cache(cache),
+			);
+			let shaping = shaping::Shaping::parse(delay.as_deref(), jitter.as_deref(), throttle.as_deref(), drop_rate.as_deref(), seed)?;
+			let shaper = shaping.is_active().then(|| Arc::new(shaping::Shaper::new(shaping)));
+			let context = Arc::new(ProxyContext { interceptor, mocks, rewrites, access, pac, transparent });
+			let shutdown = CancellationToken::new();
+			shutdown::cancel_on_signal(shutdown.clone());
+			if system_proxy {
+				let target = system_proxy_target(&address, port, serve_pac);
+				system_proxy::enable(&target, &backup_path)?;
+				println!("🖥️  系统代理已指向 {}, 退出时恢复", target.describe());
+				let result = start_proxy(address, port, context, shaper, shutdown).await;
+				let platform = system_proxy::disable(&backup_path)?;
+				println!("🖥️  已恢复 {} 系统代理设置", platform);
+				result?;
+			} else {
+				start_proxy(address, port, context, shaper, shutdown).await?;
+			}
 		}
 
-		Commands::Proxy { address, port } => {
-			start_proxy(address, port).await?;
+		Commands::Analyze {
+			url, url_file, iterations, report, duration, concurrency, warmup, fresh_connections, cold_vs_warm,
+			traceroute, watch, interval, window, sla_p95, sla_error_rate, webhook, notify,
+			save_baseline, compare, regression_threshold, connection,
+		} => {
+			let mut http_client = connection_client(&connection, &http_client, &cookie_manager)?;
+			if fresh_connections {
+				http_client = Arc::new(http_client.with_fresh_connections());
+			}
+			let mut urls = url;
+			if let Some(path) = url_file {
+				urls.extend(read_url_file(&path)?);
+			}
+			if urls.is_empty() {
+				return Err(anyhow::anyhow!("No URLs to analyze"));
+			}
+			let duration = duration.map(|d| timeline::parse_since(&d)?.to_std().map_err(anyhow::Error::from)).transpose()?;
+
+			if watch {
+				if output::json() {
+					return Err(anyhow::anyhow!("--watch cannot be combined with --json"));
+				}
+				let options = watch::WatchOptions {
+					interval: timeline::parse_since(&interval)?.to_std()?,
+					window,
+					sla: watch::Sla {
+						p95_ms: sla_p95.as_deref().map(watch::parse_millis).transpose()?,
+						error_rate: sla_error_rate,
+					},
+					notifier: notify::Notifier::new(&config.notifications, http_client.clone())
+						.with_webhook(webhook)
+						.with_desktop(notify),
+					verify_ssl: !connection.insecure,
+					http_version: connection.http_version,
+				};
+				if options.interval.is_zero() {
+					return Err(anyhow::anyhow!("--interval must be greater than zero"));
+				}
+				watch::run(&urls, &options, http_client).await?;
+				return Ok(());
+			}
+
+			let mut summaries = Vec::new();
+			for (i, url) in urls.iter().enumerate() {
+				// One report per target, numbered when there are several
+				let report_path = |name: &str| match urls.len() {
+					1 => format!("{}.json", name),
+					_ => format!("{}_{}.json", name, i + 1),
+				};
+				if warmup > 0 {
+					say!("🔥 Sending {} warmup request(s) to {}", warmup, url);
+					performance_analyzer::PerformanceAnalyzer::new(http_client.clone())
+						.warm_up(url, warmup, !connection.insecure, connection.http_version)
+						.await;
+				}
+				let summary = match duration {
+					Some(duration) => run_load_test(
+						url.clone(), concurrency, duration, report.then(|| report_path("load_test_report")),
+						!connection.insecure, connection.http_version, http_client.clone(),
+					).await?,
+					None => analyze_performance(
+						url.clone(), iterations, report.then(|| report_path("performance_report")),
+						!connection.insecure, connection.http_version, traceroute, http_client.clone(),
+					).await?,
+				};
+				if let (true, Some(warm)) = (cold_vs_warm, &summary.latency) {
+					compare_connections(url, iterations, !connection.insecure, connection.http_version, http_client.clone(), warm).await?;
+				}
+				summaries.push(summary);
+			}
+			if summaries.len() > 1 {
+				say!("\n{}", performance_analyzer::comparison_table(&summaries));
+			}
+
+			if let Some(path) = save_baseline {
+				baseline::Baseline::from_summaries(&summaries).save(&path)?;
+				say!("💾 Baseline saved to: {}", path.display());
+			}
+			let mut changes = Vec::new();
+			let mut regressions = 0;
+			if let Some(path) = compare {
+				let baseline = baseline::Baseline::load(&path)?;
+				changes = baseline.compare(&summaries, regression_threshold);
+				say!();
+				regressions = baseline::print_comparison(&baseline, &changes, regression_threshold);
+			}
+			if output::json() {
+				output::emit(&serde_json::json!({ "targets": summaries, "baseline_changes": changes }))?;
+			}
+			if regressions > 0 {
+				return Err(anyhow::anyhow!("{} latency percentile(s) regressed beyond {}%", regressions, regression_threshold));
+			}
 		}
 
-		Commands::Analyze { url, iterations, report } => {
-			analyze_performance(url, iterations, report, http_client.clone()).await?;
+		Commands::Bench { url, connections, duration, warmup, method, headers, body, timeout, connection } => {
+			if connections == 0 {
+				return Err(anyhow::anyhow!("--connections must be at least 1"));
+			}
+			let duration = timeline::parse_since(&duration)?.to_std()?;
+			if duration.is_zero() {
+				return Err(anyhow::anyhow!("--duration must be longer than zero"));
+			}
+			let options = bench::BenchOptions {
+				connections,
+				duration,
+				warmup: warmup.map(|warmup| timeline::parse_since(&warmup)?.to_std().map_err(anyhow::Error::from)).transpose()?.unwrap_or_default(),
+				timeout: std::time::Duration::from_secs(timeout.max(1)),
+			};
+			let request = template_context(cli.env_file.as_deref())?.expand_request(&HttpRequestBuilder {
+				method,
+				url,
+				headers: cli::parse_headers(headers),
+				body: body.map(RequestBody::Text),
+				timeout_seconds: timeout,
+				follow_redirects: true,
+				verify_ssl: !connection.insecure,
+				retry: RetryPolicy::default(),
+				auth: None,
+				http_version: connection.http_version,
+			})?;
+			let http_client = connection_client(&connection, &http_client, &cookie_manager)?;
+			let report = bench::run(&request, &options, &http_client).await?;
+			if output::json() {
+				output::emit(&report)?;
+			} else {
+				bench::print_report(&report);
+			}
 		}
 	}
 
@@ -97,70 +797,133 @@ async fn main() -> Result<()> {
 	if let Err(e) = cookie_manager.save_to_file().await {
 		error!("Failed to save cookies: {}", e);
 	}
+	if let Some(session) = session {
+		match session.finish() {
+			Ok(()) => info!("Capture session saved to {}", config.storage.sessions_path),
+			Err(e) => error!("Failed to save the capture session: {}", e),
+		}
+	}
 
 	Ok(())
 }
 
+/// The shared client, or a new one sharing its cookies when `--proxy` or
+/// `--cacert` is given
+fn connection_client(
+	connection: &cli::ConnectionArgs,
+	http_client: &Arc<HttpClient>,
+	cookie_manager: &Arc<CookieManager>,
+) -> Result<Arc<HttpClient>> {
+	if connection.proxy.is_none() && connection.cacert.is_none() && connection.resolve.is_empty() {
+		return Ok(http_client.clone());
+	}
+
+	let mut client = HttpClient::new(cookie_manager.clone())?
+		.with_raw_bodies(http_client.keeps_raw_bodies())
+		.with_trace_export(http_client.exports_traces())?
+		.with_scripts(http_client.scripts());
+	if let Some(proxy) = connection.proxy_settings() {
+		info!("Using proxy {}", proxy.url);
+		client = client.with_proxy(&proxy)?;
+	}
+	if let Some(path) = &connection.cacert {
+		client = client.with_ca_cert(path)?;
+	}
+	if !connection.resolve.is_empty() {
+		client = client.with_resolve(connection.resolve_overrides()?)?;
+	}
+	Ok(Arc::new(client))
+}
+
+struct MonitorOptions {
+	replay: bool,
+	/// Requests it rejects are neither logged, streamed nor replayed
+	request_filter: network::RequestFilter,
+	save_pcap: Option<network::PcapSaveOptions>,
+	queue_capacity: usize,
+	/// Stream exchanges as NDJSON to this target (see `ndjson::NdjsonSink`)
+	ndjson_target: Option<String>,
+	geoip: Option<geoip::GeoIp>,
+	/// Alerting rules checked against every request that passes `request_filter`
+	rules: Option<rules::RuleSet>,
+	/// Decrypt TLS sessions with these secrets and parse the HTTP inside
+	tls_keylog: Option<tls_decrypt::KeyLog>,
+	/// Saved capture to read instead of the interfaces
+	read_pcap: Option<std::path::PathBuf>,
+	/// Log response bodies without undoing their Content-Encoding
+	raw_body: bool,
+}
+
 async fn start_monitor(
-	interface: String,
+	interfaces: Vec<String>,
 	filter: String,
-	replay: bool,
-	_cookie_manager: Arc<CookieManager>,
+	options: MonitorOptions,
 	http_client: Arc<HttpClient>,
 	logger: Arc<RequestLogger>,
 ) -> Result<()> {
-	if interface.starts_with("<请用") {
+	let MonitorOptions {
+		replay, request_filter, save_pcap, queue_capacity, ndjson_target, geoip, mut rules, tls_keylog, read_pcap, raw_body,
+	} = options;
+	let mut tls_decryptor = tls_keylog.map(tls_decrypt::TlsDecryptor::new);
+	if let Some(rules) = &rules {
+		info!("Loaded {} alerting rule(s)", rules.len());
+	}
+	if interfaces.iter().any(|interface| interface.starts_with("<请用")) {
 		eprintln!("错误: 未指定网络接口。请使用--interface参数指定有效的网络接口。");
-		println!("可用网络接口列表:");
+		eprintln!("可用网络接口列表:");
 
 		for (i, device) in config::list_available_interfaces().iter().enumerate() {
-			println!("  {}: {}", i+1, device);
+			eprintln!("  {}: {}", i+1, device);
 		}
 
+		eprintln!("运行 'riddler interfaces' 可查看各接口的地址与状态。");
 		return Err(anyhow::anyhow!("未指定有效网络接口"));
 	}
 
+	// A capture file replaces the interfaces and needs no privileges
+	let interfaces = if read_pcap.is_some() { Vec::new() } else { interfaces };
+	let interface = read_pcap.as_ref().map_or_else(|| interfaces.join(", "), |path| path.display().to_string());
 	info!("Starting network monitor on {} with filter: {}", interface, filter);
 	debug!("Initializing packet monitor with detailed logging");
 
-	#[cfg(unix)]
-	{
-		if !cfg!(target_os = "macos") && unsafe { libc::geteuid() } != 0 {
-			eprintln!("\n⚠️  警告: 在 Linux 上监控网络通常需要 root 权限！");
-			eprintln!("请使用 sudo 运行此命令。\n");
+	let mut warnings: Vec<String> = Vec::new();
+	for warning in interfaces.iter().flat_map(|interface| doctor::monitor_warnings(interface)) {
+		if !warnings.contains(&warning) {
+			warnings.push(warning);
 		}
 	}
-
-	#[cfg(target_os = "windows")]
-	if interface == "en0" {
-		println!("注意: 在Windows上默认使用'en0'接口名称可能无效。建议使用--interface参数指定正确的接口名称。");
-		println!("常见Windows网络接口名称通常是UUID格式，例如'\\Device\\NPF_{GUID}'");
-		println!("请运行 'riddler monitor --help' 获取更多信息");
+	for warning in &warnings {
+		eprintln!("\n⚠️  警告: {}", warning);
 	}
-
-	#[cfg(target_os = "linux")]
-	if interface == "en0" {
-		println!("注意: 在Linux上默认使用'en0'接口名称可能无效。建议使用--interface参数指定正确的接口名称。");
-		println!("常见Linux网络接口名称: 'eth0', 'wlan0', 'ens33' 等。");
-		println!("可以通过'ip link'命令查看系统上的可用接口");
+	if !warnings.is_empty() {
+		eprintln!("运行 'riddler doctor' 可检查完整的运行环境。\n");
 	}
 
-	let (packet_tx, mut packet_rx) = mpsc::unbounded_channel();
-	let monitor = Arc::new(PacketMonitor::new(interface.clone(), filter.clone(), packet_tx));
+	let mut ndjson = match &ndjson_target {
+		Some(target) => Some(ndjson::NdjsonSink::open(target).await?.with_redaction(logger.redactor().cloned())),
+		None => None,
+	};
+
+	let (packet_tx, mut packet_rx) = mpsc::channel(queue_capacity.max(1));
+	let monitor = Arc::new(PacketMonitor::new(interfaces.clone(), filter.clone(), packet_tx)
+		.with_save_pcap(save_pcap)
+		.with_read_file(read_pcap));
+	let capture_stats = monitor.stats();
 
 	info!("Network monitor created, starting monitor...");
 
-	if !config::interface_exists(&interface) {
-		eprintln!("错误: 指定的网络接口 '{}' 不存在", interface);
-		println!("可用网络接口列表:");
+	if let Some(missing) = interfaces.iter().find(|interface| !config::interface_exists(interface)) {
+		eprintln!("错误: 指定的网络接口 '{}' 不存在", missing);
+		eprintln!("可用网络接口列表:");
 		for (i, device) in config::list_available_interfaces().iter().enumerate() {
-			println!("  {}: {}", i+1, device);
+			eprintln!("  {}: {}", i+1, device);
 		}
+		eprintln!("运行 'riddler interfaces' 可查看各接口的地址与状态。");
 		return Err(anyhow::anyhow!("指定的网络接口不存在"));
 	}
 
-	if !config::validate_bpf_filter(&filter) {
-		return Err(anyhow::anyhow!("无效的 BPF 过滤器语法: {}", filter));
+	if let Err(e) = config::validate_bpf_filter(&filter) {
+		return Err(anyhow::anyhow!("无效的 BPF 过滤器语法: {}\n  {}", filter, e));
 	}
 
 	let monitor_handle = match monitor.start_monitor().await {
@@ -175,139 +938,54 @@ async fn start_monitor(
 		}
 	};
 
-	println!("Packet monitor started.");
-	println!("Ctrl + C then 'q' and Enter to quit");
-
-
-	let (shutdown_tx, mut shutdown_rx) = mpsc::unbounded_channel();
-	let monitor_for_signal = monitor.clone();
-	let monitor_for_keyboard = monitor.clone();
-	let monitor_for_unix = monitor.clone();
-	let shutdown_tx_clone = shutdown_tx.clone();
-	let shutdown_tx_keyboard = shutdown_tx.clone();
-
-
-	tokio::spawn(async move {
-		use tokio::io::{AsyncBufReadExt, BufReader};
-
-		let stdin = tokio::io::stdin();
-		let reader = BufReader::new(stdin);
-		let mut lines = reader.lines();
-
-		loop {
-			match lines.next_line().await {
-				Ok(Some(line)) => {
-					let input = line.trim().to_lowercase();
-					if input == "q" || input == "quit" || input == "exit" {
-						info!("User requested quit via keyboard input");
-						monitor_for_keyboard.shutdown();
-						monitor_for_keyboard.release_sender();
-						let _ = shutdown_tx_keyboard.send(());
-						break;
-					} else if !input.is_empty() {
-						println!("Unknown command '{}'. Press Ctrl + C then q and Enter to quit.", input);
-					}
-				}
-				Ok(None) => {
-					info!("Stdin closed, shutting down...");
-					monitor_for_keyboard.shutdown();
-					monitor_for_keyboard.release_sender();
-					let _ = shutdown_tx_keyboard.send(());
-					break;
-				}
-				Err(e) => {
-					error!("Error reading from stdin: {}", e);
-					break;
-				}
-			}
-		}
-	});
-
-
-	tokio::spawn(async move {
-		match tokio::signal::ctrl_c().await {
-			Ok(()) => {
-				info!("Ctrl+C received, shutting down");
-				monitor_for_signal.shutdown();
-				monitor_for_signal.release_sender();
-				let _ = shutdown_tx.send(());
-
-
-				tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-				std::process::exit(0);
-			}
-			Err(err) => {
-				error!("Unable to listen for Ctrl+C signal: {}", err);
-			}
-		}
-	});
-
-
-	#[cfg(unix)]
-	{
-		tokio::spawn(async move {
-			use tokio::signal::unix::{signal, SignalKind};
-
-			let mut sigint = signal(SignalKind::interrupt()).expect("Failed to create SIGINT handler");
-			let mut sigterm = signal(SignalKind::terminate()).expect("Failed to create SIGTERM handler");
+	eprintln!("Packet monitor started.");
+	eprintln!("Press Ctrl + C, or enter 'q', to quit");
 
-			tokio::select! {
-				_ = sigint.recv() => {
-					info!("SIGINT received, shutting down");
-					monitor_for_unix.shutdown();
-					monitor_for_unix.release_sender();
-					let _ = shutdown_tx_clone.send(());
-
-					tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-					std::process::exit(0);
-				}
-				_ = sigterm.recv() => {
-					info!("SIGTERM received, shutting down");
-					monitor_for_unix.shutdown();
-					monitor_for_unix.release_sender();
-					let _ = shutdown_tx_clone.send(());
-
-					tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
-					std::process::exit(0);
-				}
-			}
-		});
-	}
 
+	let shutdown = CancellationToken::new();
+	shutdown::cancel_on_signal(shutdown.clone());
+	shutdown::cancel_on_quit_command(shutdown.clone());
+	// Set once shutdown has begun: the capture loops are stopping and the
+	// packets they already queued are still processed
+	let mut draining = false;
 
 	let _http_parser = network::HttpParser::new();
+	// Requests without a captured response after this long are logged on their own
+	const RESPONSE_TIMEOUT_SECS: i64 = 30;
+	let mut exchanges = ExchangeTracker::new();
+	let mut requests = network::RequestAssembler::new();
+	let mut hostnames = dns::HostnameCache::default();
+	// Destination connection IDs of QUIC handshakes already reported; clients
+	// repeat their Initial packets until the server answers
+	let mut quic_connections: std::collections::HashSet<Vec<u8>> = std::collections::HashSet::new();
+	let mut decrypted_packets: std::collections::VecDeque<network::NetworkPacket> = std::collections::VecDeque::new();
 	let _http_request_count = 0;
 	let mut _http_payload_packets = 0;
 	let mut packet_count = 0;
+	let mut filtered_requests = 0usize;
+	let mut rule_alerts = 0usize;
 	let mut exit_reason = "unknown";
 
 	info!("HTTP监控已启动，等待捕获HTTP请求...");
 	info!("如果没有看到任何网络包被捕获，请尝试生成一些HTTP流量 (例如访问 http://example.com)");
 
-	println!("监控已启动。开始监听网络流量，日志将显示在这里...");
+	eprintln!("监控已启动。开始监听网络流量，日志将显示在这里...");
 	debug!("Main loop starting, waiting for packets...");
 	loop {
 
-		match shutdown_rx.try_recv() {
-			Ok(_) => {
-				info!("Shutdown signal received, stopping monitor");
-				exit_reason = "shutdown_signal";
-				break;
-			}
-			Err(mpsc::error::TryRecvError::Disconnected) => {
-				info!("Shutdown channel closed");
-				exit_reason = "shutdown_channel_closed";
-				break;
-			}
-			Err(mpsc::error::TryRecvError::Empty) => {
-
-			}
+		if shutdown.is_cancelled() && !draining {
+			info!("Shutdown signal received, stopping monitor");
+			monitor.shutdown();
+			exit_reason = "shutdown_signal";
+			draining = true;
 		}
 
-
-		if monitor_handle.is_finished() {
+		// A finished capture file, or a stopped capture, may still have packets queued
+		if monitor_handle.is_finished() && packet_rx.is_empty() && decrypted_packets.is_empty() {
 			info!("Monitor task completed");
-			exit_reason = "monitor_task_finished";
+			if !draining {
+				exit_reason = "monitor_task_finished";
+			}
 			break;
 		}
 
@@ -317,22 +995,109 @@ async fn start_monitor(
 		let mut channel_closed = false;
 
 		while batch_processed < MAX_BATCH_SIZE {
-			match packet_rx.try_recv() {
+			// Plaintext of decrypted TLS records goes through the same parsing
+			let next = match decrypted_packets.pop_front() {
+				Some(packet) => Ok(packet),
+				None => packet_rx.try_recv().inspect(|_| capture_stats.record_processed()),
+			};
+			match next {
 				Ok(packet) => {
 					packet_count += 1;
 					batch_processed += 1;
 
-					debug!("Received packet #{} from {}:{}",
-						packet_count, packet.src_ip, packet.src_port);
-
-					if let Some(http_request) = HttpParser::parse_http_request(&packet) {
-						info!("Monitored HTTP request #{}: {} {}", packet_count, http_request.method, http_request.url);
+					debug!("Received packet #{} on {} from {}:{}",
+						packet_count, packet.interface, packet.src_ip, packet.src_port);
 
+					if let Some(decryptor) = tls_decryptor.as_mut()
+						&& packet.application_protocol != Some("TLS")
+						&& let Some(plaintext) = decryptor.process(&packet)
+					{
+						decrypted_packets.push_back(plaintext);
+					}
 
-						if let Err(e) = logger.log_request(&http_request, "monitored").await {
-							error!("Failed to log request: {}", e);
+					if let Some(message) = HttpParser::parse_dns(&packet) {
+						let (name, kind) = message.questions.first().cloned().unwrap_or_default();
+						if message.is_response {
+							hostnames.record(&message);
+							let addresses: Vec<_> = message.answers.iter().filter_map(|r| r.address()).map(|ip| ip.to_string()).collect();
+							info!("DNS {} {} → {} ({})", dns::type_name(kind), name,
+								if addresses.is_empty() { "-".to_string() } else { addresses.join(", ") }, message.rcode_name());
+						} else {
+							debug!("DNS query {} {} from {}:{}", dns::type_name(kind), name, packet.src_ip, packet.src_port);
+						}
+					} else if packet.application_protocol == Some("QUIC")
+						&& packet.dst_port == 443
+						&& let Some(initial) = quic::parse_client_initial(&packet.payload)
+					{
+						if quic_connections.len() >= 10_000 {
+							quic_connections.clear();
+						}
+						if !quic_connections.insert(initial.destination_cid.clone()) {
+							continue;
+						}
+						let location = geoip.as_ref()
+							.and_then(|geoip| geoip.lookup(&packet.dst_ip))
+							.map_or_else(String::new, |geo| format!(" [{}]", geo));
+						info!("{} connection attempt from {}:{} to {} ({}){}, ALPN {}",
+							if initial.is_http3() { "HTTP/3" } else { "QUIC" },
+							packet.src_ip, packet.src_port,
+							initial.server_name.as_deref().unwrap_or("unknown server"), packet.dst_ip, location,
+							if initial.alpn.is_empty() { "-".to_string() } else { initial.alpn.join(",") });
+						if let Some(sink) = ndjson.as_mut()
+							&& let Err(e) = sink.emit(ndjson::MonitorEvent::quic(&packet, &initial)).await
+						{
+							error!("Failed to stream QUIC connection: {}", e);
+						}
+					} else if packet.protocol == "UDP" {
+						match dissector::dissect(&packet) {
+							Some(dissector) => debug!("{} {}:{} → {}:{} ({} bytes): {}",
+								dissector.name(), packet.src_ip, packet.src_port, packet.dst_ip, packet.dst_port,
+								packet.payload_size, dissector.summarize(&packet).unwrap_or_default()),
+							None => trace!("UDP {}:{} → {}:{} ({} bytes)",
+								packet.src_ip, packet.src_port, packet.dst_ip, packet.dst_port, packet.payload_size),
+						}
+					} else if let Some(http_response) = HttpParser::parse_http_response(&packet) {
+						match exchanges.match_response(&packet) {
+							Some((http_request, sent_at)) => {
+								info!("Monitored HTTP response #{}: {} for {} {}",
+									packet_count, http_response.status, http_request.method, http_request.url);
+								let response = http_response.into_response_info(&http_request, sent_at, raw_body);
+								if let Some(sink) = ndjson.as_mut()
+									&& let Err(e) = sink.emit(ndjson::MonitorEvent::response(&http_request, &response)).await
+								{
+									error!("Failed to stream response: {}", e);
+								}
+								if let Err(e) = logger.log_request_response(&http_request, &response, "monitored").await {
+									error!("Failed to log request/response pair: {}", e);
+								}
+							}
+							None => debug!("HTTP response {} from {}:{} has no captured request",
+								http_response.status, packet.src_ip, packet.src_port),
+						}
+					} else if let Some(mut http_request) = requests.push(&packet) {
+						http_request.resolved_host = hostnames.lookup(&http_request.destination_ip).map(str::to_string);
+						http_request.geo = geoip.as_ref().and_then(|geoip| geoip.lookup(&http_request.destination_ip));
+						if !request_filter.matches(&http_request) {
+							filtered_requests += 1;
+							debug!("Request {} {} rejected by --match filters", http_request.method, http_request.url);
+							continue;
+						}
+						match &http_request.geo {
+							Some(geo) => info!("Monitored HTTP request #{}: {} {} [{}]", packet_count, http_request.method, http_request.url, geo),
+							None => info!("Monitored HTTP request #{}: {} {}", packet_count, http_request.method, http_request.url),
+						}
+						if let Some(sink) = ndjson.as_mut()
+							&& let Err(e) = sink.emit(ndjson::MonitorEvent::request(&http_request)).await
+						{
+							error!("Failed to stream request: {}", e);
+						}
+						if let Some(rules) = rules.as_mut() {
+							rule_alerts += rules.check(&http_request, &http_client);
 						}
 
+						// Logged together with its response, or alone once it times out
+						exchanges.track_request(&packet, http_request.clone());
+
 
 						if replay {
 							match http_client.replay_request(&http_request).await {
@@ -367,206 +1132,1435 @@ async fn start_monitor(
 		}
 
 
-		if channel_closed {
-			break;
-		}
+		let incomplete = requests.expire(chrono::Duration::seconds(RESPONSE_TIMEOUT_SECS));
+		filtered_requests += log_incomplete_requests(incomplete, &hostnames, geoip.as_ref(), &request_filter, &logger).await;
+
+		for http_request in exchanges.expire(chrono::Duration::seconds(RESPONSE_TIMEOUT_SECS)) {
+			if let Err(e) = logger.log_request(&http_request, "monitored").await {
+				error!("Failed to log request: {}", e);
+			}
+		}
+
+		if channel_closed {
+			break;
+		}
+
+
+
+
+		tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+	}
+
+	info!("Main processing loop ended (reason: {})", exit_reason);
+
+	filtered_requests += log_incomplete_requests(requests.drain(), &hostnames, geoip.as_ref(), &request_filter, &logger).await;
+	for http_request in exchanges.drain() {
+		if let Err(e) = logger.log_request(&http_request, "monitored").await {
+			error!("Failed to log request: {}", e);
+		}
+	}
+
+
+	if !monitor_handle.is_finished() {
+		info!("Waiting for packet monitor task to finish...");
+		if let Err(e) = monitor_handle.await {
+			error!("Error waiting for monitor task: {}", e);
+		}
+	}
+
+	info!("Monitored {} packets", packet_count);
+	eprintln!("捕获统计: {}", capture_stats.summary());
+	if !request_filter.is_empty() {
+		eprintln!("被 --match 过滤掉的请求: {}", filtered_requests);
+	}
+	if rules.is_some() {
+		eprintln!("触发的告警规则: {}", rule_alerts);
+	}
+	if let Some(decryptor) = &tls_decryptor {
+		eprintln!("解密的 TLS 会话: {}", decryptor.decrypted_sessions());
+	}
+
+	Ok(())
+}
+
+/// Body for `--body TEXT`, or raw bytes for `--body-file PATH` and `--body -` (stdin)
+/// Log requests whose body never completed with the part that arrived; they
+/// are never replayed. Returns how many `request_filter` rejected.
+async fn log_incomplete_requests(
+	incomplete: Vec<network::HttpRequest>,
+	hostnames: &dns::HostnameCache,
+	geoip: Option<&geoip::GeoIp>,
+	request_filter: &network::RequestFilter,
+	logger: &RequestLogger,
+) -> usize {
+	let mut filtered = 0;
+	for mut http_request in incomplete {
+		http_request.resolved_host = hostnames.lookup(&http_request.destination_ip).map(str::to_string);
+		http_request.geo = geoip.and_then(|geoip| geoip.lookup(&http_request.destination_ip));
+		if !request_filter.matches(&http_request) {
+			filtered += 1;
+			continue;
+		}
+		warn!("Request {} {} is missing part of its body, logging the {} bytes received",
+			http_request.method, http_request.url, http_request.body.len());
+		if let Err(e) = logger.log_request(&http_request, "monitored").await {
+			error!("Failed to log request: {}", e);
+		}
+	}
+	filtered
+}
+
+fn read_body(body: Option<String>, body_file: Option<&std::path::Path>) -> Result<Option<RequestBody>> {
+	use std::io::Read;
+
+	if let Some(path) = body_file {
+		let bytes = std::fs::read(path)
+			.map_err(|e| anyhow::anyhow!("Unable to read body file {}: {}", path.display(), e))?;
+		return Ok(Some(RequestBody::Bytes(bytes)));
+	}
+	if body.as_deref() == Some("-") {
+		let mut bytes = Vec::new();
+		std::io::stdin().read_to_end(&mut bytes)?;
+		return Ok(Some(RequestBody::Bytes(bytes)));
+	}
+	Ok(body.map(RequestBody::Text))
+}
+
+/// Placeholder context for `request`, with the variables of `--env-file`
+fn template_context(env_file: Option<&std::path::Path>) -> Result<template::TemplateContext> {
+	let mut context = template::TemplateContext::new();
+	if let Some(path) = env_file {
+		for (name, value) in template::read_env_file(path)? {
+			context.set(&name, value);
+		}
+	}
+	Ok(context)
+}
+
+/// How `request` prints the response
+struct ResponseOutput {
+	json_pretty: bool,
+	/// JSONPath to print instead of the full response
+	extract: Option<String>,
+}
+
+async fn send_manual_request(
+	request: HttpRequestBuilder,
+	output: ResponseOutput,
+	assertions: &[assertion::Assertion],
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<()> {
+	let HttpRequestBuilder { method, url, headers: parsed_headers, .. } = request.clone();
+	let body = request.body_text();
+
+	info!("Sending {} request to {}", method, url);
+
+	match http_client.send_request(request).await {
+		Ok(response) => {
+			if let Err(e) = logger.log_manual_request_response(
+				&method,
+				&url,
+				parsed_headers,
+				&body,
+				&response,
+			).await {
+				error!("Failed to log manual request: {}", e);
+			}
+			let results = assertion::evaluate_all(assertions, &response);
+
+			// Extraction output is meant for scripts, so print nothing but the values
+			if output.extract.is_some() {
+				print_response_body(&response.body, &output)?;
+				return check_assertions(&results, true);
+			}
+
+			say!("✅ Response Status: {}", response.status);
+			if let Some(remote_addr) = &response.remote_addr {
+				say!("🌐 Connected to: {} ({})", remote_addr, response.http_version);
+			}
+			if response.attempts.len() > 1 {
+				say!("🔁 Attempts: {}", format_attempts(&response.attempts));
+			}
+			if let Some(location) = redirect_location(&response) {
+				say!("↪️  Redirect (not followed): {}", location);
+			}
+			say!("📝 Response Headers:");
+			for (key, value) in &response.headers {
+				say!("  {}: {}", key, value);
+			}
+			say!("📄 Response Body:");
+			print_response_body(&response.body, &output)?;
+			say!("⏱️  Response Time: {}ms", response.response_time_ms);
+
+			if output::json() {
+				output::emit(&serde_json::json!({
+					"request": { "method": method, "url": url },
+					"response": response,
+					"assertions": results,
+				}))?;
+			}
+			check_assertions(&results, false)?;
+			say!("✅ Request completed successfully!");
+		}
+		Err(e) => {
+			error!("❌ Request failed: {}", e);
+			say!("❌ Request failed: {}", e);
+			if output::json() {
+				output::emit(&serde_json::json!({
+					"request": { "method": method, "url": url },
+					"error": format!("{:#}", e),
+				}))?;
+			}
+			if output.extract.is_some() || !assertions.is_empty() {
+				return Err(e);
+			}
+		}
+	}
+
+	Ok(())
+}
+
+/// Report the results of `--assert` checks and fail if any of them does not
+/// hold. With `quiet` only the failures are reported, on stderr.
+fn check_assertions(results: &[assertion::AssertionResult], quiet: bool) -> Result<()> {
+	if results.is_empty() {
+		return Ok(());
+	}
+	let failed = if quiet {
+		let failures: Vec<_> = results.iter().filter(|r| !r.passed).collect();
+		for result in &failures {
+			eprintln!("❌ Assertion failed: {} (actual: {})", result.assertion, result.actual);
+		}
+		failures.len()
+	} else {
+		say!("🧪 Assertions:");
+		assertion::print_report(results)
+	};
+	if failed > 0 {
+		return Err(anyhow::anyhow!("{} of {} assertion(s) failed", failed, results.len()));
+	}
+	Ok(())
+}
+
+async fn download_to_file(
+	request: HttpRequestBuilder,
+	options: download::DownloadOptions,
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<()> {
+	say!("⬇️  Downloading {} to {}", request.url, options.output.display());
+	let summary = download::download(&http_client, request.clone(), &options).await
+		.map_err(|e| anyhow::anyhow!("{} (rerun with --resume to continue a partial file)", e))?;
+
+	let mut response = summary.response.clone();
+	response.body = format!("[{} bytes saved to {}]", summary.file_size(), options.output.display());
+	if let Err(e) = logger.log_manual_request_response(
+		&request.method,
+		&request.url,
+		request.headers.clone(),
+		&request.body_text(),
+		&response,
+	).await {
+		error!("Failed to log download: {}", e);
+	}
+
+	say!("✅ Response Status: {}", summary.response.status);
+	if summary.downloaded == 0 && summary.resumed_from > 0 {
+		say!("📦 Already complete: {}", hosts::format_bytes(summary.file_size()));
+	} else if summary.resumed_from > 0 {
+		say!("📦 Saved {} ({} resumed from byte {})",
+			hosts::format_bytes(summary.file_size()), hosts::format_bytes(summary.downloaded), summary.resumed_from);
+	} else {
+		say!("📦 Saved {}", hosts::format_bytes(summary.file_size()));
+	}
+	say!("🔑 SHA-256: {}", summary.sha256);
+	say!("⏱️  Response Time: {}ms", summary.response.response_time_ms);
+	if output::json() {
+		output::emit(&serde_json::json!({
+			"request": { "method": request.method, "url": request.url },
+			"status": summary.response.status,
+			"path": options.output,
+			"size": summary.file_size(),
+			"downloaded": summary.downloaded,
+			"resumed_from": summary.resumed_from,
+			"sha256": summary.sha256,
+			"response_time_ms": summary.response.response_time_ms,
+		}))?;
+	}
+	Ok(())
+}
+
+/// `503 (120ms, waited 500ms) → 200 (95ms)`
+fn format_attempts(attempts: &[http_client::AttemptOutcome]) -> String {
+	attempts
+		.iter()
+		.map(|attempt| {
+			let outcome = match (attempt.status, &attempt.error) {
+				(Some(status), _) => status.to_string(),
+				(None, Some(error)) => format!("error: {}", error),
+				(None, None) => "error".to_string(),
+			};
+			if attempt.delay_ms > 0 {
+				format!("{} ({}ms, waited {}ms)", outcome, attempt.elapsed_ms, attempt.delay_ms)
+			} else {
+				format!("{} ({}ms)", outcome, attempt.elapsed_ms)
+			}
+		})
+		.collect::<Vec<_>>()
+		.join(" → ")
+}
+
+/// Absolute target of a 3xx response that was returned instead of followed
+fn redirect_location(response: &http_client::HttpResponseInfo) -> Option<String> {
+	if !(300..400).contains(&response.status) {
+		return None;
+	}
+	let location = response.headers.get("location")?;
+	Some(url::Url::parse(&response.final_url)
+		.and_then(|base| base.join(location))
+		.map(|url| url.to_string())
+		.unwrap_or_else(|_| location.clone()))
+}
+
+async fn send_paginated_request(
+	request: HttpRequestBuilder,
+	output: ResponseOutput,
+	pagination: pagination::PaginationOptions,
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<()> {
+	let quiet = output.extract.is_some();
+
+	info!("Following pagination from {} (max {} pages)", request.url, pagination.max_pages);
+
+	let pages = pagination::fetch_all(&http_client, request, &pagination, &mut |number, page| {
+		if !quiet {
+			say!("📄 Page {}: {} ({}ms) {}",
+				number, page.response.status, page.response.response_time_ms, page.request.url);
+		}
+	}).await?;
+
+	for page in &pages {
+		if let Err(e) = logger.log_manual_request_response(
+			&page.request.method,
+			&page.request.url,
+			page.request.headers.clone(),
+			&page.request.body_text(),
+			&page.response,
+		).await {
+			error!("Failed to log manual request: {}", e);
+		}
+	}
+
+	let body = pagination::aggregate_bodies(&pages);
+	if !quiet {
+		let total_ms: u64 = pages.iter().map(|p| p.response.response_time_ms).sum();
+		say!("✅ Fetched {} pages in {}ms", pages.len(), total_ms);
+		say!("📄 Aggregated Body:");
+	}
+	print_response_body(&body, &output)?;
+	if output::json() && !quiet {
+		let pages: Vec<_> = pages.iter()
+			.map(|page| serde_json::json!({
+				"url": page.request.url,
+				"status": page.response.status,
+				"response_time_ms": page.response.response_time_ms,
+			}))
+			.collect();
+		output::emit(&serde_json::json!({
+			"pages": pages,
+			"body": serde_json::from_str::<serde_json::Value>(&body).unwrap_or(serde_json::Value::String(body)),
+		}))?;
+	}
+	Ok(())
+}
+
+/// Print a response body honouring `--json-pretty` and `--extract`
+fn print_response_body(body: &str, output: &ResponseOutput) -> Result<()> {
+	if let Some(ref expression) = output.extract {
+		let values = jsonpath::extract(body, expression)?;
+		if values.is_empty() {
+			return Err(anyhow::anyhow!("JSONPath '{}' matched nothing", expression));
+		}
+		if output::json() {
+			return output::emit(&values);
+		}
+		for value in &values {
+			if output.json_pretty && (value.is_object() || value.is_array()) {
+				println!("{}", serde_json::to_string_pretty(value)?);
+			} else {
+				println!("{}", jsonpath::value_to_string(value));
+			}
+		}
+		return Ok(());
+	}
+
+	match serde_json::from_str::<serde_json::Value>(body) {
+		Ok(json) if output.json_pretty => say!("{}", serde_json::to_string_pretty(&json)?),
+		_ => say!("{}", body),
+	}
+	Ok(())
+}
+
+async fn handle_cookie_command(
+	action: CookieAction,
+	cookie_manager: Arc<CookieManager>,
+) -> Result<()> {
+	match action {
+		CookieAction::List { domain } => {
+			let cookies = cookie_manager.list_cookies(domain.as_deref());
+			if output::json() {
+				return output::emit(&cookies);
+			}
+			for cookie in cookies {
+				println!("{}={} (domain: {}, path: {})",
+						cookie.name, cookie.value, cookie.domain, cookie.path);
+			}
+		}
+
+		CookieAction::Add { cookie, url } => {
+			let parsed_url = url::Url::parse(&url)?;
+			cookie_manager.add_cookie(&parsed_url, &cookie)?;
+			cookie_manager.save_to_file().await?;
+			println!("Cookie added successfully");
+		}
+
+		CookieAction::Delete { domain, name } => {
+			match cookie_manager.remove(&domain, &name) {
+				Some(cookie) => {
+					cookie_manager.save_to_file().await?;
+					println!("Deleted cookie {} (domain: {})", cookie.name, cookie.domain);
+				}
+				None => return Err(anyhow::anyhow!("No cookie named '{}' for domain {}", name, domain)),
+			}
+		}
+
+		CookieAction::Set { domain, name, value, expires, path } => {
+			let expires = expires.as_deref().map(cookie_manager::parse_expires).transpose()?;
+			// Removed first: a new path makes it a different cookie
+			let (mut cookie, created) = match cookie_manager.remove(&domain, &name) {
+				Some(cookie) => (cookie, false),
+				None => {
+					let value = value.clone()
+						.ok_or_else(|| anyhow::anyhow!("Cookie '{}' does not exist for {}; pass --value to create it", name, domain))?;
+					let cookie = cookie_manager::CookieEntry {
+						name: name.clone(),
+						value,
+						domain: domain.clone(),
+						path: "/".to_string(),
+						expires: None,
+						secure: false,
+						http_only: false,
+						same_site: None,
+						// `.example.com` also covers subdomains, see `CookieManager::insert`
+						host_only: !domain.starts_with('.'),
+						created: 0,
+					};
+					(cookie, true)
+				}
+			};
+
+			if let Some(value) = value {
+				cookie.value = value;
+			}
+			if let Some(path) = path {
+				cookie.path = path;
+			}
+			if let Some(expires) = expires {
+				cookie.expires = expires;
+			}
+
+			println!("{} cookie {}={} (domain: {}, path: {})",
+					if created { "Created" } else { "Updated" }, cookie.name, cookie.value, cookie.domain, cookie.path);
+			cookie_manager.insert(cookie);
+			cookie_manager.save_to_file().await?;
+		}
+
+		CookieAction::ImportBrowser { browser, domain, profile } => {
+			let import = tokio::task::spawn_blocking(move || {
+				browser_cookies::import(browser, profile.as_deref(), domain.as_deref())
+			}).await??;
+
+			let imported = import.cookies.len();
+			for cookie in import.cookies {
+				cookie_manager.insert(cookie);
+			}
+			cookie_manager.save_to_file().await?;
+
+			println!("Imported {} cookies from {}", imported, import.database.display());
+			if import.expired > 0 {
+				println!("Skipped {} expired cookies", import.expired);
+			}
+			if import.undecryptable > 0 {
+				println!("⚠️  Skipped {} cookies that could not be decrypted (is the keyring unlocked?)", import.undecryptable);
+			}
+		}
+
+		CookieAction::Headers { set, unset } => {
+			for name in &unset {
+				if !cookie_manager.remove_default_header(name) {
+					println!("No default header named {}", name);
+				}
+			}
+			for (name, value) in cli::parse_headers(set) {
+				cookie_manager.set_default_header(&name, &value);
+			}
+			cookie_manager.save_to_file().await?;
+
+			let headers = cookie_manager.default_headers();
+			if headers.is_empty() {
+				println!("No default headers");
+			}
+			for (name, value) in headers {
+				println!("{}: {}", name, value);
+			}
+		}
+
+		CookieAction::Report { domain, within_hours } => {
+			print_cookie_report(&cookie_manager, domain.as_deref(), within_hours);
+		}
+
+		CookieAction::Clean => {
+			cookie_manager.clear_expired();
+			cookie_manager.save_to_file().await?;
+			println!("Expired cookies cleared");
+		}
+
+		CookieAction::Clear => {
+			cookie_manager.clear_all();
+			cookie_manager.save_to_file().await?;
+			println!("All cookies cleared");
+		}
+	}
+
+	Ok(())
+}
+
+fn print_cookie_report(cookie_manager: &CookieManager, domain: Option<&str>, within_hours: u64) {
+	let now = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.unwrap()
+		.as_secs();
+	let threshold = (within_hours * 3600) as i64;
+
+	let mut by_domain: std::collections::BTreeMap<String, Vec<_>> = std::collections::BTreeMap::new();
+	for cookie in cookie_manager.list_cookies(domain) {
+		by_domain.entry(cookie.domain.clone()).or_default().push(cookie);
+	}
+
+	if by_domain.is_empty() {
+		println!("No cookies stored");
+		return;
+	}
+
+	let (mut session, mut expiring, mut expired) = (0, 0, 0);
+	println!("=== Cookie Expiry Report ===");
+	for (domain, mut cookies) in by_domain {
+		// Soonest expiry first, session cookies last
+		cookies.sort_by_key(|c| (c.expires.is_none(), c.expires));
+		println!("\n🌐 {} ({} cookies)", domain, cookies.len());
+
+		for cookie in cookies {
+			let status = match cookie.seconds_until_expiry(now) {
+				None => {
+					session += 1;
+					"session (expires when the browser closes)".to_string()
+				}
+				Some(left) if left <= 0 => {
+					expired += 1;
+					format!("❌ expired {} ago", cookie_manager::format_time_left(left))
+				}
+				Some(left) if left <= threshold => {
+					expiring += 1;
+					format!("⚠️  expires in {}", cookie_manager::format_time_left(left))
+				}
+				Some(left) => format!("expires in {}", cookie_manager::format_time_left(left)),
+			};
+			println!("  {:<30} {:<12} {}", cookie.name, cookie.path, status);
+		}
+	}
+
+	println!("\n{} session, {} expiring within {}h, {} expired", session, expiring, within_hours, expired);
+	if expired > 0 {
+		println!("💡 Run 'riddler cookie clean' to remove expired cookies");
+	}
+}
+
+/// Print a warning for each watched domain with cookies about to expire
+fn warn_expiring_cookies(cookie_manager: &CookieManager, domains: &[String]) {
+	for domain in domains {
+		let expiring = cookie_manager.expiring_cookies(domain, 24 * 3600);
+		if expiring.is_empty() {
+			continue;
+		}
+		let names: Vec<&str> = expiring.iter().map(|c| c.name.as_str()).collect();
+		eprintln!("⚠️  {} cookie(s) for {} expire within 24h: {}", expiring.len(), domain, names.join(", "));
+	}
+}
+
+async fn export_timeline(query: LogQuery, since: &str, output: &std::path::Path, logger: &RequestLogger) -> Result<()> {
+	let cutoff = chrono::Utc::now() - timeline::parse_since(since)?;
+	let entries: Vec<RequestLogEntry> = logger.matching_entries(&query).await?
+		.into_iter()
+		.filter(|entry| entry.timestamp >= cutoff)
+		.collect();
+
+	let count = timeline::export(&entries, output)?;
+	say!("✓ Timeline of {} requests written to {}", count, output.display());
+	Ok(())
+}
+
+async fn export_sequence(
+	query: LogQuery,
+	since: &str,
+	output: &std::path::Path,
+	format: Option<sequence::SequenceFormat>,
+	limit: usize,
+	logger: &RequestLogger,
+) -> Result<()> {
+	let cutoff = chrono::Utc::now() - timeline::parse_since(since)?;
+	let mut entries: Vec<RequestLogEntry> = logger.matching_entries(&query).await?
+		.into_iter()
+		.filter(|entry| entry.timestamp >= cutoff)
+		.collect();
+	let skipped = entries.len().saturating_sub(limit);
+	entries.drain(..skipped);
+
+	let format = format.unwrap_or_else(|| sequence::SequenceFormat::from_path(output));
+	let count = sequence::export(&entries, output, format)?;
+	say!("✓ Sequence diagram of {} requests written to {}", count, output.display());
+	if skipped > 0 {
+		say!("  {} older requests left out; raise --limit or narrow --since to include them", skipped);
+	}
+	Ok(())
+}
+
+/// How `logs` presents the matching entries
+enum LogView {
+	/// The `n` most recent entries
+	Recent(usize),
+	/// One fixed-size page, page 1 holding the most recent entries
+	Page { page: usize, page_size: usize },
+	/// Interactive full-screen browser over every match
+	Pager,
+	/// The single entry whose id starts with the given prefix
+	Entry(String),
+	/// The most recent entries, then new ones as they are appended
+	Follow(usize),
+}
+
+/// How each entry is printed
+enum LogFormat {
+	Text,
+	/// One JSON object per line (`--json`)
+	Json,
+	/// A `curl` command line, adding stored cookies for the URL
+	Curl(Arc<CookieManager>),
+}
+
+impl LogFormat {
+	fn print(&self, log: &RequestLogEntry) {
+		match self {
+			LogFormat::Text => print_log_entry(log),
+			LogFormat::Json => {
+				if let Err(e) = output::emit_line(log) {
+					error!("Failed to print log entry: {}", e);
+				}
+			}
+			LogFormat::Curl(cookie_manager) => {
+				let cookies = url::Url::parse(&log.request.url)
+					.map(|url| cookie_manager.get_cookies_for_url(&url))
+					.unwrap_or_default();
+				println!("# #{} {} [{}]", log.id, log.timestamp, log.source);
+				println!("{}\n", curl::to_curl(log, &cookies));
+			}
+		}
+	}
+}
+
+async fn show_logs(
+	query: LogQuery,
+	view: LogView,
+	format: LogFormat,
+	show_stats: bool,
+	logger: Arc<RequestLogger>,
+) -> Result<()> {
+	if show_stats {
+		let stats = logger.get_request_stats().await?;
+		if output::json() {
+			return output::emit(&stats);
+		}
+		say!("=== Request Statistics ===");
+		say!("Total Requests: {}", stats.total_requests);
+		say!("Monitored: {}, Manual: {}, Replay: {}",
+				stats.monitored_requests, stats.manual_requests, stats.replay_requests);
+		say!("Successful: {}, Failed: {}", stats.successful_requests, stats.failed_requests);
+		say!("Average Response Time: {}ms", stats.average_response_time);
+
+		say!("\nMethods:");
+		for (method, count) in &stats.methods {
+			say!("  {}: {}", method, count);
+		}
+		say!();
+		return Ok(());
+	}
+
+	match view {
+		LogView::Recent(limit) => {
+			for log in logger.query_logs(&query, 0, limit).await? {
+				format.print(&log);
+			}
+		}
+		LogView::Follow(limit) => {
+			for log in logger.query_logs(&query, 0, limit).await? {
+				format.print(&log);
+			}
+			say!("--- Waiting for new entries (Ctrl+C to stop) ---");
+			tokio::select! {
+				result = logger.follow(&query, |log| format.print(log)) => result?,
+				_ = tokio::signal::ctrl_c() => {}
+			}
+		}
+		LogView::Page { page, page_size } => {
+			let page_size = page_size.max(1);
+			let total = logger.count_logs(&query).await?;
+			let pages = total.div_ceil(page_size).max(1);
+			let page = page.clamp(1, pages);
+
+			for log in logger.query_logs(&query, (page - 1) * page_size, page_size).await? {
+				format.print(&log);
+			}
+			say!("Page {}/{} ({} matching entries, newest first)", page, pages, total);
+		}
+		LogView::Pager => {
+			let entries = logger.matching_entries(&query).await?;
+			if entries.is_empty() {
+				say!("No matching log entries");
+				return Ok(());
+			}
+			tokio::task::spawn_blocking(move || pager::LogPager::new(entries).run()).await??;
+		}
+		LogView::Entry(id) => {
+			let entry = log_entry_by_id(&logger, &id, &query).await?;
+			format.print(&entry);
+		}
+	}
+
+	Ok(())
+}
+
+/// The single entry matching `query` whose id starts with `id` (a leading `#` is ignored)
+async fn log_entry_by_id(logger: &RequestLogger, id: &str, query: &LogQuery) -> Result<RequestLogEntry> {
+	let id = id.trim_start_matches('#');
+	let mut found: Vec<RequestLogEntry> = logger.matching_entries(query).await?
+		.into_iter()
+		.filter(|entry| entry.id.starts_with(id))
+		.collect();
+	match found.len() {
+		0 => Err(anyhow::anyhow!("No log entry with id '{}'", id)),
+		1 => Ok(found.remove(0)),
+		n => Err(anyhow::anyhow!("Id '{}' matches {} entries, use more characters", id, n)),
+	}
+}
+
+fn print_log_entry(log: &RequestLogEntry) {
+	say!("=== {} [{}] #{} ===", log.timestamp, log.source, log.id);
+	say!("{} {} ({}:{})",
+			log.request.method,
+			log.request.url,
+			log.request.source_ip,
+			log.request.source_port);
+
+	if !log.request.body_preview.is_empty() {
+		say!("Body Preview: {}", log.request.body_preview);
+	}
+
+	if let Some(ref response) = log.response {
+		say!("Response: {} ({}ms)", response.status, response.response_time_ms);
+		if response.attempts.len() > 1 {
+			say!("Attempts: {}", format_attempts(&response.attempts));
+		}
+	}
+	say!();
+}
+
+async fn show_hosts(
+	query: LogQuery,
+	sort: hosts::HostSort,
+	top: Option<usize>,
+	live: Option<(String, String, u64)>,
+	logger: Arc<RequestLogger>,
+) -> Result<()> {
+	let mut table = hosts::HostTable::new();
+	let entries = logger.matching_entries(&query).await?;
+	for entry in &entries {
+		table.record_entry(entry);
+	}
+
+	if let Some((interface, filter, duration)) = live {
+		if !config::interface_exists(&interface) {
+			return Err(anyhow::anyhow!("指定的网络接口不存在: {}", interface));
+		}
+
+		let (packet_tx, mut packet_rx) = mpsc::channel(network::DEFAULT_QUEUE_CAPACITY);
+		let monitor = PacketMonitor::new(vec![interface.clone()], filter, packet_tx);
+		let handle = monitor.start_monitor().await?;
+		println!("Capturing live traffic on {} for {}s (Ctrl+C to stop early)...", interface, duration);
+
+		let deadline = tokio::time::Instant::now() + tokio::time::Duration::from_secs(duration);
+		let mut packets = 0usize;
+		loop {
+			tokio::select! {
+				packet = packet_rx.recv() => match packet {
+					Some(packet) => {
+						packets += 1;
+						table.record_packet(&packet);
+					}
+					None => break,
+				},
+				_ = tokio::time::sleep_until(deadline) => break,
+				_ = tokio::signal::ctrl_c() => break,
+			}
+		}
+
+		monitor.shutdown();
+		monitor.release_sender();
+		if let Err(e) = handle.await {
+			error!("Error waiting for monitor task: {}", e);
+		}
+		println!("Captured {} packets", packets);
+	}
+
+	let mut stats = table.into_sorted(&sort);
+	if let Some(top) = top {
+		stats.truncate(top);
+	}
+
+	if stats.is_empty() {
+		println!("No traffic recorded yet");
+		return Ok(());
+	}
+
+	println!("=== Per-Host Statistics ({} log entries) ===", entries.len());
+	hosts::print_table(&stats);
+	Ok(())
+}
+
+/// The counters a monitor or proxy run kept per host; `id` is `latest` or a session ID
+fn show_session(path: &str, id: &str, sort: hosts::HostSort, top: Option<usize>) -> Result<()> {
+	let sessions = hosts::load_sessions(std::path::Path::new(path))?;
+	let session = if id == "latest" {
+		sessions.last()
+	} else {
+		sessions.iter().find(|session| session.id == id)
+	}.ok_or_else(|| match id {
+		"latest" => anyhow::anyhow!("No monitor or proxy sessions recorded yet in {}", path),
+		id => anyhow::anyhow!("No session '{}' in {} (see hosts --sessions)", id, path),
+	})?;
+
+	let mut stats = session.sorted_hosts(&sort);
+	if let Some(top) = top {
+		stats.truncate(top);
+	}
+	if output::json() {
+		let hosts: Vec<_> = stats.iter()
+			.map(|(host, counters)| serde_json::json!({ "host": host, "counters": counters, "mean_latency_ms": counters.mean_latency() }))
+			.collect();
+		return output::emit(&serde_json::json!({
+			"id": session.id, "command": session.command, "started": session.started,
+			"updated": session.updated, "finished": session.finished, "hosts": hosts,
+		}));
+	}
+
+	let state = session.finished.map_or_else(
+		|| format!("running, updated {}", session.updated.format("%H:%M:%S")),
+		|finished| format!("finished {}", finished.format("%Y-%m-%d %H:%M:%S")),
+	);
+	println!("=== Per-Host Statistics: {} session {} (started {}, {}) ===",
+		session.command, session.id, session.started.format("%Y-%m-%d %H:%M:%S"), state);
+	if stats.is_empty() {
+		println!("No traffic recorded in this session");
+		return Ok(());
+	}
+	hosts::print_session_table(&stats);
+	Ok(())
+}
+
+struct FlowOptions {
+	/// Capture until Ctrl+C when `None`
+	duration: Option<tokio::time::Duration>,
+	refresh: tokio::time::Duration,
+	sort: flows::FlowSort,
+	top: usize,
+	idle_timeout: chrono::Duration,
+}
+
+async fn show_flows(interfaces: Vec<String>, filter: String, options: FlowOptions) -> Result<()> {
+	if let Some(missing) = interfaces.iter().find(|interface| !config::interface_exists(interface)) {
+		return Err(anyhow::anyhow!("指定的网络接口不存在: {}", missing));
+	}
+
+	let interface = interfaces.join(", ");
+	let (packet_tx, mut packet_rx) = mpsc::channel(network::DEFAULT_QUEUE_CAPACITY);
+	let monitor = PacketMonitor::new(interfaces, filter, packet_tx);
+	let capture_stats = monitor.stats();
+	let handle = monitor.start_monitor().await?;
+	eprintln!("Tracking flows on {} (Ctrl+C to stop)...", interface);
+
+	let mut table = network::FlowTable::new();
+	let started = tokio::time::Instant::now();
+	let deadline = options.duration.map(|duration| started + duration);
+	let mut redraw = tokio::time::interval(options.refresh);
+	redraw.tick().await;
+	let clear_screen = std::io::IsTerminal::is_terminal(&std::io::stdout());
+	let mut packets = 0usize;
+
+	let print_snapshot = |table: &network::FlowTable, packets: usize| {
+		if clear_screen {
+			print!("\x1b[2J\x1b[H");
+		}
+		println!("=== Flows on {} ({} active, {} packets, {}s) ===",
+			interface, table.len(), packets, started.elapsed().as_secs());
+		if table.is_empty() {
+			println!("No traffic captured yet");
+			return;
+		}
+		flows::print_flows(&flows::sorted_flows(table, &options.sort, options.top));
+		println!("\n=== Top Talkers ===");
+		flows::print_top_talkers(&table.top_talkers(options.top));
+	};
+
+	loop {
+		tokio::select! {
+			packet = packet_rx.recv() => match packet {
+				Some(packet) => {
+					packets += 1;
+					capture_stats.record_processed();
+					table.record(&packet);
+				}
+				None => break,
+			},
+			_ = redraw.tick() => {
+				table.expire(options.idle_timeout);
+				print_snapshot(&table, packets);
+			}
+			_ = async {
+				match deadline {
+					Some(deadline) => tokio::time::sleep_until(deadline).await,
+					None => std::future::pending().await,
+				}
+			} => break,
+			_ = tokio::signal::ctrl_c() => break,
+		}
+	}
+
+	monitor.shutdown();
+	monitor.release_sender();
+	if let Err(e) = handle.await {
+		error!("Error waiting for monitor task: {}", e);
+	}
+
+	table.expire(options.idle_timeout);
+	print_snapshot(&table, packets);
+	eprintln!("{}", capture_stats.summary());
+	Ok(())
+}
+
+async fn analyze_cache(
+	url: Option<String>,
+	from_logs: Option<usize>,
+	revalidate: bool,
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<()> {
+	use cache_analyzer::CacheAnalyzer;
+
+	let analyzer = CacheAnalyzer::new(http_client);
+
+	if let Some(url) = url {
+		println!("🔍 Analyzing cache behavior of: {}\n", url);
+		let analysis = analyzer.analyze_url(&url, revalidate).await?;
+		cache_analyzer::print_analysis(&analysis);
+		return Ok(());
+	}
+
+	let limit = from_logs.unwrap_or(10);
+	let mut seen = std::collections::HashSet::new();
+	let mut analyses = Vec::new();
+	for entry in logger.matching_entries(&LogQuery::default()).await?.iter().rev() {
+		if analyses.len() >= limit {
+			break;
+		}
+		if let Some(ref response) = entry.response
+			&& seen.insert(entry.request.url.clone())
+		{
+			analyses.push(cache_analyzer::analyze_response(&entry.request.url, response));
+		}
+	}
+
+	if analyses.is_empty() {
+		println!("No logged responses to analyze");
+		return Ok(());
+	}
+
+	println!("🔍 Analyzing {} logged responses\n", analyses.len());
+	for mut analysis in analyses {
+		if revalidate && let Err(e) = analyzer.revalidate(&mut analysis).await {
+			warn!("Revalidation of {} failed: {}", analysis.url, e);
+		}
+		cache_analyzer::print_analysis(&analysis);
+	}
+
+	Ok(())
+}
+
+/// One of the two responses `diff` compares
+#[derive(serde::Serialize)]
+struct DiffSide {
+	/// Log entry id when loaded from the log
+	#[serde(skip_serializing_if = "Option::is_none")]
+	id: Option<String>,
+	method: String,
+	url: String,
+	status: u16,
+	response_time_ms: u64,
+	#[serde(skip)]
+	response: http_client::HttpResponseInfo,
+}
 
+impl DiffSide {
+	fn new(id: Option<String>, method: String, url: String, response: http_client::HttpResponseInfo) -> Self {
+		Self { id, method, url, status: response.status, response_time_ms: response.response_time_ms, response }
+	}
 
+	fn describe(&self) -> String {
+		let id = self.id.as_ref().map(|id| format!("#{} ", id)).unwrap_or_default();
+		format!("{}{} {} → {} ({}ms)", id, self.method, self.url, self.status, self.response_time_ms)
+	}
+}
 
+/// Send `request` for `diff`, logging it like `request` does
+async fn fetch_diff_side(request: HttpRequestBuilder, http_client: &HttpClient, logger: &RequestLogger) -> Result<DiffSide> {
+	let response = http_client.send_request(request.clone()).await
+		.map_err(|e| anyhow::anyhow!("{} {} failed: {:#}", request.method, request.url, e))?;
+	if let Err(e) = logger.log_manual_request_response(&request.method, &request.url, request.headers.clone(), &request.body_text(), &response).await {
+		error!("Failed to log manual request: {}", e);
+	}
+	Ok(DiffSide::new(None, request.method, request.url, response))
+}
 
-		tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+fn diff_responses(a: DiffSide, b: DiffSide, ignore_headers: &[String], ignore_paths: &[String], fail_on_diff: bool) -> Result<()> {
+	let mut diff = response_diff::ResponseDiff::compare(&a.response, &b.response);
+	diff.ignore(ignore_headers, ignore_paths);
+
+	if output::json() {
+		output::emit(&serde_json::json!({
+			"a": &a,
+			"b": &b,
+			"identical": diff.is_empty(),
+			"body_truncated": diff.body_truncated,
+			"changes": diff.records(),
+		}))?;
+	} else {
+		println!("🔀 A: {}", a.describe());
+		println!("   B: {}\n", b.describe());
+		if diff.is_empty() {
+			println!("   = responses are identical");
+			if diff.body_truncated {
+				println!("   (a body was truncated in the log; compared the first 1000 characters)");
+			}
+		} else {
+			print!("{}", diff);
+			let changes = diff.records().len();
+			println!("\n{} difference(s)", changes);
+		}
 	}
 
-	info!("Main processing loop ended (reason: {})", exit_reason);
+	if fail_on_diff && !diff.is_empty() {
+		return Err(anyhow::anyhow!("The responses differ"));
+	}
+	Ok(())
+}
 
+async fn tls_info(target: String, fail_within_days: Option<i64>, timeout: u64) -> Result<()> {
+	let (host, port) = tls_inspector::parse_target(&target)?;
+	println!("🔐 Inspecting TLS of {}:{}\n", host, port);
 
-	if !monitor_handle.is_finished() {
-		info!("Waiting for packet monitor task to finish...");
-		if let Err(e) = monitor_handle.await {
-			error!("Error waiting for monitor task: {}", e);
+	let report = tls_inspector::inspect(&host, port, std::time::Duration::from_secs(timeout)).await?;
+	tls_inspector::print_report(&report);
+
+	if let Some(threshold) = fail_within_days
+		&& let Some(leaf) = report.chain.first()
+	{
+		let days = leaf.days_until_expiry();
+		if days < threshold {
+			return Err(anyhow::anyhow!(
+				"Certificate for {} expires in {} days (threshold: {} days)", host, days, threshold
+			));
 		}
+		println!("✅ Certificate valid for more than {} days", threshold);
 	}
 
-	info!("Monitored {} packets", packet_count);
-	info!("Monitored {} packets total", packet_count);
+	Ok(())
+}
 
+async fn dns_diagnostics(
+	name: String,
+	servers: Vec<String>,
+	types: Vec<dns::RecordType>,
+	count: u32,
+	timeout: u64,
+) -> Result<()> {
+	let timeout = std::time::Duration::from_secs(timeout.max(1));
+	let servers = if servers.is_empty() {
+		let resolvers = dns::system_resolvers();
+		if resolvers.is_empty() {
+			return Err(anyhow::anyhow!("No nameservers found in /etc/resolv.conf, pass them with --server"));
+		}
+		resolvers
+	} else {
+		servers.iter().map(|s| dns::parse_server(s)).collect::<Result<Vec<_>>>()?
+	};
 
-	if exit_reason == "shutdown_signal" {
-		std::process::exit(0);
+	println!("🔎 Resolving {} with {} resolver(s), {} quer{} per record type\n",
+		name, servers.len(), count, if count == 1 { "y" } else { "ies" });
+	match dns::system_lookup(&name, timeout).await {
+		Ok((elapsed, ips)) => println!("System lookup: {}ms → {}\n", elapsed,
+			ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>().join(", ")),
+		Err(e) => println!("System lookup: ❌ {}\n", e),
 	}
 
+	let reports = dns::diagnose(&name, &servers, &types, count, timeout).await;
+	dns::print_report(&name, &reports);
+
+	if reports.iter().all(|r| r.average_ms().is_none()) {
+		return Err(anyhow::anyhow!("No resolver answered for {}", name));
+	}
 	Ok(())
 }
 
-async fn send_manual_request(
-	method: String,
-	url: String,
-	headers: Vec<String>,
-	body: Option<String>,
+struct CollectionRun {
+	file: std::path::PathBuf,
+	folder: Option<String>,
+	/// `NAME=VALUE` overrides from `--var`
+	vars: Vec<String>,
+	delay: u64,
 	timeout: u64,
+}
+
+async fn run_collection(
+	run: CollectionRun,
+	env_file: Option<&std::path::Path>,
 	http_client: Arc<HttpClient>,
 	logger: Arc<RequestLogger>,
 ) -> Result<()> {
-	let parsed_headers = cli::parse_headers(headers);
-
-	let request = HttpRequestBuilder {
-		method: method.clone(),
-		url: url.clone(),
-		headers: parsed_headers.clone(),
-		body: body.clone(),
-		timeout_seconds: timeout,
-		follow_redirects: true,
-		verify_ssl: true,
-	};
+	let CollectionRun { file, folder, vars, delay, timeout } = run;
+	let collection = collection::Collection::load(&file)?;
+	let env = env_file.map(template::read_env_file).transpose()?.unwrap_or_default();
+
+	let mut variables = std::collections::HashMap::new();
+	for var in vars {
+		let (name, value) = var.split_once('=')
+			.ok_or_else(|| anyhow::anyhow!("Invalid --var '{}', expected NAME=VALUE", var))?;
+		variables.insert(name.trim().to_string(), value.to_string());
+	}
 
-	info!("Sending {} request to {}", method, url);
+	let options = collection::RunOptions { folder, env, variables, delay, timeout };
+	let failures = collection::run(&collection, &options, &http_client, &logger).await?;
+	if failures > 0 {
+		return Err(anyhow::anyhow!("{} collection request(s) failed", failures));
+	}
+	Ok(())
+}
 
-	match http_client.send_request(request).await {
-		Ok(response) => {
-			println!("✅ Response Status: {}", response.status);
-			println!("📝 Response Headers:");
-			for (key, value) in &response.headers {
-				println!("  {}: {}", key, value);
+fn handle_config_command(action: ConfigAction, config: &Config, path: &std::path::Path) -> Result<()> {
+	match action {
+		ConfigAction::Init { force } => {
+			if path.exists() && !force {
+				return Err(anyhow::anyhow!("{} already exists (use --force to overwrite)", path.display()));
 			}
-			println!("📄 Response Body:");
-			println!("{}", response.body);
-			println!("⏱️  Response Time: {}ms", response.response_time_ms);
+			std::fs::write(path, config.to_toml()?)?;
+			println!("✅ Wrote default configuration to {}", path.display());
+		}
 
-			if let Err(e) = logger.log_manual_request_response(
-				&method,
-				&url,
-				parsed_headers,
-				&body.clone().unwrap_or_default(),
-				&response,
-			).await {
-				error!("Failed to log manual request: {}", e);
+		ConfigAction::Show => {
+			if path.exists() {
+				println!("# Loaded from {}", path.display());
+			} else {
+				println!("# {} not found, showing defaults", path.display());
 			}
-
-			println!("✅ Request completed successfully!");
-		}
-		Err(e) => {
-			error!("❌ Request failed: {}", e);
-			println!("❌ Request failed: {}", e);
+			print!("{}", config.to_toml()?);
 		}
 	}
 
 	Ok(())
 }
 
-async fn handle_cookie_command(
-	action: CookieAction,
-	cookie_manager: Arc<CookieManager>,
-) -> Result<()> {
+fn handle_cache_command(action: CacheAction, cache: &proxy_cache::ProxyCache) -> Result<()> {
 	match action {
-		CookieAction::List { domain } => {
-			let cookies = cookie_manager.list_cookies(domain.as_deref());
-			for cookie in cookies {
-				println!("{}={} (domain: {}, path: {})",
-						cookie.name, cookie.value, cookie.domain, cookie.path);
+		CacheAction::Stats => {
+			let report = cache.report();
+			if output::json() {
+				return output::emit(&report);
+			}
+			let counters = &report.counters;
+			println!("=== Proxy Cache: {} ===", report.dir);
+			println!("Entries: {} ({} fresh, {} stale), {:.1} KB", report.entries, report.fresh, report.stale, report.bytes as f64 / 1024.0);
+			let answered = counters.hits + counters.revalidated + counters.misses;
+			let hit_ratio = if answered == 0 { 0.0 } else { (counters.hits + counters.revalidated) as f64 / answered as f64 * 100.0 };
+			println!("Hits: {}, revalidated: {}, misses: {} ({:.1}% served from cache)", counters.hits, counters.revalidated, counters.misses, hit_ratio);
+			println!("Stored: {}, invalidated: {}, 304 to clients: {}", counters.stored, counters.invalidated, counters.not_modified);
+			if !report.hosts.is_empty() {
+				println!("\nEntries per host:");
+				let mut hosts: Vec<_> = report.hosts.iter().collect();
+				hosts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+				for (host, entries) in hosts {
+					println!("  {:<40} {}", host, entries);
+				}
+			}
+			if !counters.not_stored.is_empty() {
+				println!("\nNot cached:");
+				for (reason, count) in &counters.not_stored {
+					println!("  {:<40} {}", reason, count);
+				}
 			}
 		}
 
-		CookieAction::Add { cookie, url } => {
-			let parsed_url = url::Url::parse(&url)?;
-			cookie_manager.add_cookie(&parsed_url, &cookie)?;
-			cookie_manager.save_to_file().await?;
-			println!("Cookie added successfully");
-		}
-
-		CookieAction::Clean => {
-			cookie_manager.clear_expired();
-			cookie_manager.save_to_file().await?;
-			println!("Expired cookies cleared");
-		}
-
-		CookieAction::Clear => {
-			cookie_manager.clear_all();
-			cookie_manager.save_to_file().await?;
-			println!("All cookies cleared");
+		CacheAction::Clear { host } => {
+			let removed = cache.clear(host.as_deref())?;
+			match host {
+				Some(host) => println!("Removed {} cached responses of {}", removed, host),
+				None => println!("Removed {} cached responses and reset the counters", removed),
+			}
 		}
 	}
 
 	Ok(())
 }
 
-async fn show_logs(
-	limit: usize,
-	source: Option<String>,
-	query: Option<String>,
-	show_stats: bool,
-	logger: Arc<RequestLogger>,
-) -> Result<()> {
-	if show_stats {
-		let stats = logger.get_request_stats().await?;
-		println!("=== Request Statistics ===");
-		println!("Total Requests: {}", stats.total_requests);
-		println!("Monitored: {}, Manual: {}, Replay: {}",
-				stats.monitored_requests, stats.manual_requests, stats.replay_requests);
-		println!("Successful: {}, Failed: {}", stats.successful_requests, stats.failed_requests);
-		println!("Average Response Time: {}ms", stats.average_response_time);
+/// Everything a proxy connection is served with
+struct ProxyContext {
+	interceptor: Arc<mitm::Interceptor>,
+	mocks: Option<Arc<mock::MockSet>>,
+	rewrites: Option<Arc<rewrite::RewriteRules>>,
+	access: access::AccessControl,
+	pac: Option<pac::PacFile>,
+	/// Clients were redirected by the firewall rather than configured to use the proxy
+	transparent: bool,
+}
 
-		println!("\nMethods:");
-		for (method, count) in &stats.methods {
-			println!("  {}: {}", method, count);
-		}
-		println!();
-		return Ok(());
+/// The host clients reach a proxy bound to `address` by: the bind address,
+/// or loopback when bound to every interface
+fn reachable_host(address: &str) -> String {
+	match address.parse::<std::net::IpAddr>() {
+		Ok(ip) if ip.is_unspecified() => "127.0.0.1".to_string(),
+		Ok(std::net::IpAddr::V6(ip)) => format!("[{}]", ip),
+		_ => address.to_string(),
 	}
+}
 
-	let logs = if let Some(search_query) = query {
-		logger.search_logs(&search_query, limit).await?
+fn system_proxy_target(address: &str, port: u16, pac: bool) -> system_proxy::Target {
+	let host = reachable_host(address);
+	if pac {
+		system_proxy::Target::Pac(format!("http://{}:{}{}", host, port, pac::PAC_PATH))
 	} else {
-		logger.read_recent_logs(limit).await?
-	};
-
-	for log in logs {
-		if let Some(ref filter_source) = source {
-			if log.source != *filter_source {
-				continue;
-			}
-		}
-
-		println!("=== {} [{}] ===", log.timestamp, log.source);
-		println!("{} {} ({}:{})",
-				log.request.method,
-				log.request.url,
-				log.request.source_ip,
-				log.request.source_port);
+		system_proxy::Target::Proxy { host, port }
+	}
+}
 
-		if !log.request.body_preview.is_empty() {
-			println!("Body Preview: {}", log.request.body_preview);
+fn handle_proxy_action(action: ProxyAction, address: &str, port: u16, backup_path: &std::path::Path) -> Result<()> {
+	match action {
+		ProxyAction::EnableSystem { pac } => {
+			let target = system_proxy_target(address, port, pac);
+			system_proxy::enable(&target, backup_path)?;
+			println!("🖥️  系统代理已指向 {}", target.describe());
+			println!("   原设置已保存到 {}, 使用 proxy disable-system 恢复", backup_path.display());
 		}
-
-		if let Some(ref response) = log.response {
-			println!("Response: {} ({}ms)", response.status, response.response_time_ms);
+		ProxyAction::DisableSystem => {
+			let platform = system_proxy::disable(backup_path)?;
+			println!("🖥️  已恢复 {} 系统代理设置", platform);
 		}
-		println!();
 	}
-
 	Ok(())
 }
 
-async fn start_proxy(address: String, port: u16) -> Result<()> {
+/// Serve proxy clients until `shutdown` is cancelled, then give the
+/// connections in flight `PROXY_DRAIN_TIMEOUT` to finish
+async fn start_proxy(
+	address: String,
+	port: u16,
+	context: Arc<ProxyContext>,
+	shaper: Option<Arc<shaping::Shaper>>,
+	shutdown: CancellationToken,
+) -> Result<()> {
+	// Tunnels and WebSockets can stay open indefinitely; they are cut off after this
+	const PROXY_DRAIN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 	println!("Starting HTTP/HTTPS proxy server on {}:{}", address, port);
+	let ProxyContext { interceptor, mocks, rewrites, access, pac, transparent } = &*context;
+	let decrypts_tls = interceptor.decrypts_tls();
+	let loopback = address == "localhost" || address.parse::<std::net::IpAddr>().is_ok_and(|ip| ip.is_loopback());
+	if !loopback && !access.is_restricted() && !transparent {
+		println!("⚠️  代理监听在 {} 上且未限制客户端, 任何能访问该地址的人都可以使用它; 建议配合 --allow-client 或 --auth", address);
+	}
+	if access.requires_auth() {
+		println!("🔐 需要代理认证 (Basic Proxy-Authorization), 未认证的请求返回 407");
+	}
+	if *transparent {
+		println!("🪞 透明代理模式: 只处理被 iptables/pf 重定向到 {} 的连接, 按原始目的地址转发", port);
+		if !decrypts_tls {
+			println!("   明文 HTTP 会被记录, TLS 连接原样转发; 使用 --mitm 以解密 HTTPS");
+		}
+	}
+	if decrypts_tls {
+		println!("🔓 MITM 模式已启用: HTTPS 流量将被解密并记录 (来源: proxy)");
+	}
+	if let Some(mocks) = &mocks {
+		println!("🎭 {} 条 mock 规则已启用, 匹配的请求不会发往源站", mocks.len());
+		if !decrypts_tls {
+			println!("   HTTPS 隧道未解密, 只有明文 HTTP 请求会被 mock; 使用 --mitm 以 mock HTTPS 接口");
+		}
+	}
+	if let Some(rewrites) = &rewrites {
+		println!("✏️  {} 条改写规则已启用", rewrites.len());
+		if !decrypts_tls {
+			println!("   HTTPS 隧道未解密, 其中只有只匹配主机的 url.host 规则 (重定向隧道) 生效; 使用 --mitm 以改写 HTTPS 请求头、URL 与状态码");
+		}
+	}
+	if let Some(shaper) = &shaper {
+		println!("🐢 网络劣化已启用: {}", shaper.describe());
+	}
+	if pac.is_some() {
+		println!("🧭 PAC 文件: http://{}:{}{}", reachable_host(&address), port, pac::PAC_PATH);
+	}
 
 	use tokio::net::TcpListener;
 
 	let listener = TcpListener::bind(format!("{}:{}", address, port)).await?;
 	info!("Proxy server listening on {}:{}", address, port);
 
+	let connections = TaskTracker::new();
 	loop {
-		let (stream, addr) = listener.accept().await?;
+		let (stream, addr) = tokio::select! {
+			accepted = listener.accept() => accepted?,
+			_ = shutdown.cancelled() => break,
+		};
 		info!("New connection from: {}", addr);
 
-		tokio::spawn(async move {
-			if let Err(e) = handle_proxy_connection(stream).await {
+		let context = context.clone();
+		let shaper = shaper.clone();
+		connections.spawn(async move {
+			if let Err(e) = serve_proxy_client(stream, addr, context, shaper).await {
 				error!("Proxy connection error: {}", e);
 			}
 		});
 	}
+
+	drop(listener);
+	connections.close();
+	if !connections.is_empty() {
+		eprintln!("等待 {} 个进行中的代理连接结束...", connections.len());
+	}
+	if tokio::time::timeout(PROXY_DRAIN_TIMEOUT, connections.wait()).await.is_err() {
+		warn!("{} proxy connections still open after {}s, closing them", connections.len(), PROXY_DRAIN_TIMEOUT.as_secs());
+	}
+	info!("Proxy server on {}:{} stopped", address, port);
+	Ok(())
+}
+
+/// Find where a redirected client meant to go, put the shaper in front of
+/// the client and serve it
+async fn serve_proxy_client(
+	stream: tokio::net::TcpStream,
+	addr: std::net::SocketAddr,
+	context: Arc<ProxyContext>,
+	shaper: Option<Arc<shaping::Shaper>>,
+) -> Result<()> {
+	// Looked up first: the shaper hides the socket the lookup needs
+	let destination = if context.transparent {
+		Some(transparent::original_destination(&stream).await?)
+	} else {
+		None
+	};
+
+	let Some(shaper) = &shaper else {
+		return match destination {
+			Some(destination) => handle_transparent_connection(stream, addr, destination, context).await,
+			None => handle_proxy_connection(stream, addr, context).await,
+		};
+	};
+	let Some(shaped) = shaper.shape(stream) else {
+		info!("Dropped connection from {} (--drop-rate)", addr);
+		return Ok(());
+	};
+	match destination {
+		Some(destination) => handle_transparent_connection(shaped, addr, destination, context).await,
+		None => handle_proxy_connection(shaped, addr, context).await,
+	}
+}
+
+/// Serve a client the firewall redirected to the proxy on its way to
+/// `destination`: HTTP goes through the interceptor, TLS too with --mitm,
+/// and anything else is relayed to the destination as it is
+async fn handle_transparent_connection<S>(
+	stream: S,
+	addr: std::net::SocketAddr,
+	destination: std::net::SocketAddr,
+	context: Arc<ProxyContext>,
+) -> Result<()>
+where
+	S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+	use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+	let ProxyContext { interceptor, access, .. } = &*context;
+
+	// Clients speak first in HTTP and TLS; the peeked bytes stay buffered
+	let mut reader = BufReader::new(stream);
+	let first = reader.fill_buf().await?;
+	if first.is_empty() {
+		return Ok(());
+	}
+	let protocol = transparent::Protocol::sniff(first);
+	let host = match &protocol {
+		transparent::Protocol::Tls { server_name: Some(host) } | transparent::Protocol::Http { host: Some(host) } => host.clone(),
+		_ => destination.ip().to_string(),
+	};
+	debug!("Redirected connection from {} to {} ({}): {:?}", addr, destination, host, protocol);
+
+	if let Err(denial) = access.check_client(addr.ip()).and_then(|()| access.check_host(&host)) {
+		warn!("Refused redirected connection from {} to {} ({}): {:?}", addr, destination, host, denial);
+		if matches!(protocol, transparent::Protocol::Http { .. }) {
+			reader.get_mut().write_all(&denial.response()).await?;
+		}
+		return Ok(());
+	}
+
+	match protocol {
+		transparent::Protocol::Http { .. } => interceptor.serve_plain(reader, addr, access, destination).await,
+		transparent::Protocol::Tls { .. } if interceptor.decrypts_tls() => {
+			interceptor.intercept(reader, addr, access, &host, destination.port()).await
+		}
+		_ => {
+			info!("Relaying redirected connection from {} to {} ({})", addr, destination, host);
+			let mut upstream = tokio::net::TcpStream::connect(destination).await?;
+			tokio::io::copy_bidirectional(&mut reader, &mut upstream).await?;
+			Ok(())
+		}
+	}
 }
 
-async fn handle_proxy_connection(mut stream: tokio::net::TcpStream) -> Result<()> {
+/// Serve one client of the proxy; `stream` is the client's socket, or the
+/// shaped pipe in front of it
+async fn handle_proxy_connection<S>(
+	mut stream: S,
+	addr: std::net::SocketAddr,
+	context: Arc<ProxyContext>,
+) -> Result<()>
+where
+	S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
 	use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 	use tokio::net::TcpStream;
 
@@ -582,6 +2576,61 @@ async fn handle_proxy_connection(mut stream: tokio::net::TcpStream) -> Result<()
 	let method = parts[0];
 	let target = parts[1];
 
+	let mut headers = Vec::new();
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line).await? == 0 || line.trim().is_empty() {
+			break;
+		}
+		headers.push(line);
+	}
+
+	let ProxyContext { interceptor, rewrites, access, pac, .. } = &*context;
+	let host_header = headers.iter().find_map(|line| {
+		line.split_once(':')
+			.filter(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+			.map(|(_, value)| value.trim().to_string())
+	});
+
+	// The PAC file is addressed to the proxy itself, not relayed, so only
+	// the client rules apply: browsers fetch it without proxy credentials
+	if let Some(pac) = pac
+		&& method == "GET"
+		&& target.split('?').next() == Some(pac::PAC_PATH)
+	{
+		if let Err(denial) = access.check_client(addr.ip()) {
+			warn!("Refused {} {} from {}: {:?}", method, target, addr, denial);
+			stream.write_all(&denial.response()).await?;
+			return Ok(());
+		}
+		info!("Serving {} to {}", pac::PAC_PATH, addr);
+		stream.write_all(&pac.response(host_header.as_deref())).await?;
+		return Ok(());
+	}
+
+	// Absolute-form targets are what clients send to a proxy. An origin-form
+	// request was meant for the proxy itself, and forwarding it by its Host
+	// header could send it straight back here.
+	if method != "CONNECT" && target.starts_with('/') {
+		warn!("Refused {} {} from {}: not a proxy request", method, target, addr);
+		let message = "Riddler is a proxy: request absolute URLs through it";
+		stream.write_all(format!(
+			"HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+			message.len(), message
+		).as_bytes()).await?;
+		return Ok(());
+	}
+	let destination = if method == "CONNECT" {
+		target.rsplit_once(':').map_or(target, |(host, _)| host).to_string()
+	} else {
+		url::Url::parse(target).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default()
+	};
+	if let Err(denial) = access.check(addr.ip(), &headers, &destination) {
+		warn!("Refused {} {} from {}: {:?}", method, target, addr, denial);
+		stream.write_all(&denial.response()).await?;
+		return Ok(());
+	}
+
 	if method == "CONNECT" {
 
 		let host_port: Vec<&str> = target.split(':').collect();
@@ -594,7 +2643,19 @@ async fn handle_proxy_connection(mut stream: tokio::net::TcpStream) -> Result<()
 
 		info!("CONNECT request to {}:{}", host, port);
 
+		if interceptor.decrypts_tls() {
+			let host = host.to_string();
+			stream.write_all(b"HTTP/1.1 200 Connection Established\r\n\r\n").await?;
+			return interceptor.intercept(stream, addr, access, &host, port).await;
+		}
 
+		let (host, port) = match rewrites.as_ref().and_then(|rewrites| rewrites.tunnel_target(host, port)) {
+			Some((target_host, target_port)) => {
+				info!("Redirecting tunnel to {}:{} to {}:{}", host, port, target_host, target_port);
+				(target_host, target_port)
+			}
+			None => (host.to_string(), port),
+		};
 		match TcpStream::connect(format!("{}:{}", host, port)).await {
 			Ok(target_stream) => {
 
@@ -602,7 +2663,7 @@ async fn handle_proxy_connection(mut stream: tokio::net::TcpStream) -> Result<()
 				stream.write_all(response.as_bytes()).await?;
 
 
-				let (mut client_read, mut client_write) = stream.into_split();
+				let (mut client_read, mut client_write) = tokio::io::split(stream);
 				let (mut target_read, mut target_write) = target_stream.into_split();
 
 				tokio::spawn(async move {
@@ -623,23 +2684,14 @@ async fn handle_proxy_connection(mut stream: tokio::net::TcpStream) -> Result<()
 
 		info!("HTTP request: {} {}", method, target);
 
-
-		let mut headers = Vec::new();
-		loop {
-			let mut line = String::new();
-			reader.read_line(&mut line).await?;
-			if line.trim().is_empty() {
-				break;
-			}
-			headers.push(line);
+		// Mocks, rewrites, the cache, scripts and WebSocket upgrades are all
+		// the interceptor's; the body and any later requests are still in `reader`
+		let mut head = request_line.clone().into_bytes();
+		for line in &headers {
+			head.extend_from_slice(line.as_bytes());
 		}
-
-
-		let response = format!(
-			"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 27\r\n\r\nProxy handled {} request",
-			method
-		);
-		stream.write_all(response.as_bytes()).await?;
+		head.extend_from_slice(b"\r\n");
+		return interceptor.serve_proxied(reader, addr, access, head).await;
 	}
 
 	Ok(())
@@ -648,27 +2700,34 @@ async fn handle_proxy_connection(mut stream: tokio::net::TcpStream) -> Result<()
 async fn replay_requests(
 	limit: usize,
 	source: Option<String>,
-	count: usize,
-	delay: u64,
-	mode: cli::ReplayMode,
+	options: replay::ReplayOptions,
 	http_client: Arc<HttpClient>,
 	logger: Arc<RequestLogger>,
-) -> Result<()> {
-	info!("Starting request replay - limit: {}, count: {}, delay: {}ms, mode: {:?}", limit, count, delay, mode);
+) -> Result<Vec<replay::SendRecord>> {
+	info!("Starting request replay - limit: {}, options: {:?}", limit, options);
+
+	let requests_to_replay = logged_replay_targets(limit, source.as_deref(), &logger).await?;
+	if requests_to_replay.is_empty() {
+		say!("No requests found to replay");
+		return Ok(Vec::new());
+	}
+
+	say!("Found {} requests to replay", requests_to_replay.len());
 
+	replay::run(requests_to_replay, options, http_client, logger).await
+}
 
+/// The `limit` most recent logged requests, optionally only those of `source`,
+/// ready to be sent again
+async fn logged_replay_targets(limit: usize, source: Option<&str>, logger: &RequestLogger) -> Result<Vec<replay::ReplayTarget>> {
 	let logs = logger.read_recent_logs(limit).await?;
-	let mut requests_to_replay = Vec::new();
+	let mut targets = Vec::new();
 
 	for log in logs {
-
-		if let Some(ref filter_source) = source {
-			if log.source != *filter_source {
-				continue;
-			}
+		if source.is_some_and(|source| log.source != source) {
+			continue;
 		}
 
-
 		let request = HttpRequestBuilder {
 			method: log.request.method.clone(),
 			url: log.request.url.clone(),
@@ -676,120 +2735,110 @@ async fn replay_requests(
 			body: if log.request.body_preview.is_empty() {
 				None
 			} else {
-				Some(log.request.body_preview.clone())
+				Some(RequestBody::Text(log.request.body_preview.clone()))
 			},
 			timeout_seconds: 30,
 			follow_redirects: true,
 			verify_ssl: true,
+			retry: RetryPolicy::default(),
+			auth: None,
+			http_version: HttpVersion::Auto,
 		};
 
-		requests_to_replay.push(request);
-	}
-
-	if requests_to_replay.is_empty() {
-		println!("No requests found to replay");
-		return Ok(());
+		targets.push(replay::ReplayTarget {
+			request,
+			captured_at: log.timestamp,
+			original: log.response.clone(),
+		});
 	}
+	Ok(targets)
+}
 
-	println!("Found {} requests to replay", requests_to_replay.len());
-
-	match mode {
-		cli::ReplayMode::Sequential => {
-			for (i, request) in requests_to_replay.iter().enumerate() {
-				println!("\n=== Replaying Request {} ===", i + 1);
-				println!("{} {}", request.method, request.url);
-
-				for replay_num in 1..=count {
-					println!("Replay {}/{}", replay_num, count);
-
-					match http_client.send_request(request.clone()).await {
-						Ok(response) => {
-							println!("✅ Response: {} ({}ms)", response.status, response.response_time_ms);
-
-							if let Err(e) = logger.log_replay_request_response(&request, &response).await {
-								error!("Failed to log replay: {}", e);
-							}
-						}
-						Err(e) => {
-							println!("❌ Error: {}", e);
-						}
-					}
-
-					if replay_num < count && delay > 0 {
-						tokio::time::sleep(Duration::from_millis(delay)).await;
-					}
-				}
-
-				if i < requests_to_replay.len() - 1 && delay > 0 {
-					tokio::time::sleep(Duration::from_millis(delay * 2)).await;
-				}
-			}
-		},
-		cli::ReplayMode::Interleaved => {
-			// 按顺序轮流放出请求n次 (A1->B1->A2->B2)
-			println!("\n=== Replaying Requests in Interleaved Mode ===");
-
-			for replay_num in 1..=count {
-				println!("\n--- Replay Round {}/{} ---", replay_num, count);
-
-				for (i, request) in requests_to_replay.iter().enumerate() {
-					println!("Request {}: {} {}", i + 1, request.method, request.url);
+/// Non-empty lines of a `--url-file`, skipping `#` comments
+fn read_url_file(path: &std::path::Path) -> Result<Vec<String>> {
+	let content = std::fs::read_to_string(path)
+		.map_err(|e| anyhow::anyhow!("Unable to read URL file {}: {}", path.display(), e))?;
+	Ok(content.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(str::to_string)
+		.collect())
+}
 
-					match http_client.send_request(request.clone()).await {
-						Ok(response) => {
-							println!("✅ Response: {} ({}ms)", response.status, response.response_time_ms);
+async fn run_load_test(
+	url: String,
+	concurrency: usize,
+	duration: std::time::Duration,
+	report_path: Option<String>,
+	verify_ssl: bool,
+	http_version: HttpVersion,
+	http_client: Arc<HttpClient>,
+) -> Result<performance_analyzer::TargetSummary> {
+	use performance_analyzer::PerformanceAnalyzer;
 
-							if let Err(e) = logger.log_replay_request_response(&request, &response).await {
-								error!("Failed to log replay: {}", e);
-							}
-						}
-						Err(e) => {
-							println!("❌ Error: {}", e);
-						}
-					}
+	say!("🔥 Load testing {} with {} worker(s) for {}s...\n", url, concurrency, duration.as_secs());
 
-					if i < requests_to_replay.len() - 1 && delay > 0 {
-						tokio::time::sleep(Duration::from_millis(delay)).await;
-					}
-				}
+	let analyzer = PerformanceAnalyzer::new(http_client);
+	let result = analyzer.run_load_test(&url, concurrency, duration, verify_ssl, http_version).await?;
+	say!("{}", analyzer.generate_load_report(&result));
 
-				if replay_num < count && delay > 0 {
-					tokio::time::sleep(Duration::from_millis(delay * 2)).await;
-				}
-			}
+	if let Some(report_path) = report_path {
+		match tokio::fs::write(&report_path, serde_json::to_string_pretty(&result)?).await {
+			Ok(_) => say!("📄 Detailed report saved to: {}", report_path),
+			Err(e) => say!("⚠️ Failed to save report: {}", e),
 		}
 	}
+	Ok(performance_analyzer::TargetSummary::from_load_test(&result))
+}
 
-	println!("\n✓ Replay completed!");
+/// Repeat the iterations on fresh connections and compare them with the pooled run
+async fn compare_connections(
+	url: &str,
+	iterations: u32,
+	verify_ssl: bool,
+	http_version: HttpVersion,
+	http_client: Arc<HttpClient>,
+	warm: &performance_analyzer::LatencyStats,
+) -> Result<()> {
+	say!("\n🧊 Repeating {} iterations on fresh connections...\n", iterations);
+	let analyzer = performance_analyzer::PerformanceAnalyzer::new(http_client).with_fresh_connections();
+	let cold = analyzer.run_performance_test(url, iterations, verify_ssl, http_version).await?;
+	say!("{}", performance_analyzer::connection_comparison(&cold, warm));
 	Ok(())
 }
 
 async fn analyze_performance(
 	url: String,
 	iterations: u32,
-	generate_report: bool,
+	report_path: Option<String>,
+	verify_ssl: bool,
+	http_version: HttpVersion,
+	trace_route: bool,
 	http_client: Arc<HttpClient>,
-) -> Result<()> {
+) -> Result<performance_analyzer::TargetSummary> {
 	use performance_analyzer::PerformanceAnalyzer;
 
-	println!("🔍 Starting performance analysis for: {}", url);
-	println!("📊 Running {} test iterations...\n", iterations);
+	say!("🔍 Starting performance analysis for: {}", url);
+	say!("📊 Running {} test iterations...\n", iterations);
 
-	let analyzer = PerformanceAnalyzer::new(http_client);
+	let analyzer = PerformanceAnalyzer::new(http_client).with_traceroute(trace_route);
 
 
-	match analyzer.run_performance_test(&url, iterations).await {
+	match analyzer.run_performance_test(&url, iterations, verify_ssl, http_version).await {
 		Ok(analyses) => {
 			if analyses.is_empty() {
-				println!("❌ No successful requests completed");
-				return Ok(());
+				say!("❌ No successful requests completed");
+				return Ok(performance_analyzer::TargetSummary::from_analyses(&url, iterations, &analyses));
 			}
 
 
 			for (i, analysis) in analyses.iter().enumerate() {
-				println!("=== Test {} Results ===", i + 1);
-				println!("Response Time: {}ms", analysis.metrics.total_time_ms);
-				println!("Status: HTTP {}",
+				say!("=== Test {} Results ===", i + 1);
+				say!("Response Time: {}ms", analysis.metrics.total_time_ms);
+				if !analysis.protocol.is_empty() {
+					say!("Protocol: {}", analysis.protocol);
+				}
+				say!("Status: HTTP {}",
 					match analysis.severity {
 						performance_analyzer::PerformanceSeverity::Excellent => "✅ Excellent",
 						performance_analyzer::PerformanceSeverity::Good => "✅ Good",
@@ -800,24 +2849,34 @@ async fn analyze_performance(
 				);
 
 				if analysis.metrics.total_time_ms > 6000 {
-					println!("🚨 CRITICAL: Response time exceeded 6 seconds!");
+					say!("🚨 CRITICAL: Response time exceeded 6 seconds!");
 				}
-				println!();
+				say!();
 			}
 
 
 			let summary = analyzer.generate_summary_report(&analyses);
-			println!("{}", summary);
+			say!("{}", summary);
 
-			if generate_report {
+			if let Some(analysis) = analyses.iter().find(|a| !a.route.is_empty()) {
+				say!("🛰️  Route to {} (traced because of critical response times):", url);
+				say!("{}", traceroute::format_hops(&analysis.route));
+				say!("{}\n", performance_analyzer::route_verdict(&analysis.route));
+			}
 
-				let report_path = "performance_report.json";
+			if let Some(report_path) = report_path {
+				let report = performance_analyzer::PerformanceReport {
+					summary: performance_analyzer::LatencyStats::from_samples(
+						&analyses.iter().map(|a| a.metrics.total_time_ms).collect::<Vec<_>>(),
+					),
+					analyses: analyses.clone(),
+				};
 				match tokio::fs::write(
-					report_path,
-					serde_json::to_string_pretty(&analyses)?
+					&report_path,
+					serde_json::to_string_pretty(&report)?
 				).await {
-					Ok(_) => println!("📄 Detailed report saved to: {}", report_path),
-					Err(e) => println!("⚠️ Failed to save report: {}", e),
+					Ok(_) => say!("📄 Detailed report saved to: {}", report_path),
+					Err(e) => say!("⚠️ Failed to save report: {}", e),
 				}
 			}
 
@@ -827,48 +2886,48 @@ async fn analyze_performance(
 				.collect();
 
 			if !slow_requests.is_empty() {
-				println!("\n🔍 ANALYSIS OF 6000ms+ RESPONSE TIMES:");
-				println!("Found {} requests with critical response times", slow_requests.len());
+				say!("\n🔍 ANALYSIS OF 6000ms+ RESPONSE TIMES:");
+				say!("Found {} requests with critical response times", slow_requests.len());
 
 				for analysis in slow_requests {
-					println!("\n{}", analysis.analysis);
-					println!("Recommendations:");
+					say!("\n{}", analysis.analysis);
+					say!("Recommendations:");
 					for rec in &analysis.recommendations {
-						println!("• {}", rec);
+						say!("• {}", rec);
 					}
 				}
 
-				println!("\n📋 COMMON FACTORS CAUSING 6000ms+ RESPONSE TIMES:");
-				println!("1. 🌐 Network Latency Issues:");
-				println!("   - High RTT (Round Trip Time) to target server");
-				println!("   - Geographic distance to server location");
-				println!("   - Network congestion or packet loss");
-
-				println!("2. 🖥️ Server-Side Performance:");
-				println!("   - Server overload or high resource utilization");
-				println!("   - Slow database queries or backend processing");
-				println!("   - Insufficient server capacity");
-
-				println!("3. 🔗 Connection Issues:");
-				println!("   - DNS resolution delays");
-				println!("   - TCP connection establishment overhead");
-				println!("   - TLS handshake delays");
-
-				println!("4. 🚦 ISP or Infrastructure:");
-				println!("   - Internet Service Provider throttling");
-				println!("   - Routing inefficiencies");
-				println!("   - CDN or proxy server delays");
-
-				println!("5. 📦 Data Transfer:");
-				println!("   - Large response payloads");
-				println!("   - Lack of compression (gzip/brotli)");
-				println!("   - Inefficient data serialization");
+				say!("\n📋 COMMON FACTORS CAUSING 6000ms+ RESPONSE TIMES:");
+				say!("1. 🌐 Network Latency Issues:");
+				say!("   - High RTT (Round Trip Time) to target server");
+				say!("   - Geographic distance to server location");
+				say!("   - Network congestion or packet loss");
+
+				say!("2. 🖥️ Server-Side Performance:");
+				say!("   - Server overload or high resource utilization");
+				say!("   - Slow database queries or backend processing");
+				say!("   - Insufficient server capacity");
+
+				say!("3. 🔗 Connection Issues:");
+				say!("   - DNS resolution delays");
+				say!("   - TCP connection establishment overhead");
+				say!("   - TLS handshake delays");
+
+				say!("4. 🚦 ISP or Infrastructure:");
+				say!("   - Internet Service Provider throttling");
+				say!("   - Routing inefficiencies");
+				say!("   - CDN or proxy server delays");
+
+				say!("5. 📦 Data Transfer:");
+				say!("   - Large response payloads");
+				say!("   - Lack of compression (gzip/brotli)");
+				say!("   - Inefficient data serialization");
 			}
+			Ok(performance_analyzer::TargetSummary::from_analyses(&url, iterations, &analyses))
 		}
 		Err(e) => {
-			println!("❌ Performance analysis failed: {}", e);
+			say!("❌ Performance analysis failed: {}", e);
+			Ok(performance_analyzer::TargetSummary::from_analyses(&url, iterations, &[]))
 		}
 	}
-
-	Ok(())
 }
\ No newline at end of file
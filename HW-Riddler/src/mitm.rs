@@ -0,0 +1,623 @@
+use crate::access::AccessControl;
+use crate::logger::RequestLogger;
+use crate::mock::MockSet;
+use crate::proxy_cache::ProxyCache;
+use crate::rewrite::RewriteRules;
+use crate::network::{HttpParser, HttpRequest};
+use crate::script::{ScriptHooks, ScriptRequest, ScriptResponse};
+use crate::websocket;
+use anyhow::{anyhow, Context, Result};
+use chrono::Datelike;
+use dashmap::DashMap;
+use rcgen::{
+	BasicConstraints, Certificate, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+	KeyUsagePurpose,
+};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, error, info, warn};
+
+/// Headers that only describe one hop; the proxy sets its own
+const HOP_BY_HOP: [&str; 9] = [
+	"connection", "proxy-connection", "proxy-authorization", "keep-alive", "te", "trailer", "transfer-encoding", "upgrade", "host",
+];
+
+/// Request heads larger than this are rejected
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+/// Largest request body buffered for forwarding; bigger ones get a 413
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+/// Longest chunk-size or trailer line of a chunked body
+const MAX_LINE_SIZE: usize = 8 * 1024;
+
+/// A request body over `MAX_BODY_SIZE`, refused before it is buffered
+#[derive(Debug)]
+struct BodyTooLarge;
+
+impl std::fmt::Display for BodyTooLarge {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "Request body exceeds {} bytes", MAX_BODY_SIZE)
+	}
+}
+
+impl std::error::Error for BodyTooLarge {}
+
+/// Local CA that signs a certificate for every intercepted host
+pub struct CertificateAuthority {
+	ca: Certificate,
+	/// Server configs already minted, keyed by host
+	configs: DashMap<String, Arc<rustls::ServerConfig>>,
+}
+
+impl CertificateAuthority {
+	/// Load the CA from `cert_path`/`key_path`, generating and saving a new one
+	/// if either file is missing
+	pub fn load_or_create(cert_path: &Path, key_path: &Path) -> Result<Self> {
+		let (cert_pem, key_pem) = if cert_path.exists() && key_path.exists() {
+			(
+				std::fs::read_to_string(cert_path).with_context(|| format!("Unable to read {}", cert_path.display()))?,
+				std::fs::read_to_string(key_path).with_context(|| format!("Unable to read {}", key_path.display()))?,
+			)
+		} else {
+			let (cert_pem, key_pem) = Self::generate()?;
+			std::fs::write(cert_path, &cert_pem).with_context(|| format!("Unable to write {}", cert_path.display()))?;
+			std::fs::write(key_path, &key_pem).with_context(|| format!("Unable to write {}", key_path.display()))?;
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::PermissionsExt;
+				std::fs::set_permissions(key_path, std::fs::Permissions::from_mode(0o600))?;
+			}
+
+			println!("🔐 已生成 MITM 根证书: {}", cert_path.display());
+			println!("   请将其导入浏览器或系统的受信任根证书, 或在 curl 中使用 --cacert {}", cert_path.display());
+			println!("   私钥保存在 {}, 请妥善保管", key_path.display());
+			(cert_pem, key_pem)
+		};
+
+		let key = KeyPair::from_pem(&key_pem).map_err(|e| anyhow!("Invalid CA key {}: {}", key_path.display(), e))?;
+		let params = CertificateParams::from_ca_cert_pem(&cert_pem, key)
+			.map_err(|e| anyhow!("Invalid CA certificate {}: {}", cert_path.display(), e))?;
+
+		Ok(Self {
+			ca: Certificate::from_params(params)?,
+			configs: DashMap::new(),
+		})
+	}
+
+	fn generate() -> Result<(String, String)> {
+		let year = chrono::Utc::now().year();
+		let mut params = CertificateParams::default();
+		params.distinguished_name.push(DnType::CommonName, "HW-Riddler MITM CA");
+		params.distinguished_name.push(DnType::OrganizationName, "HW-Riddler");
+		params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+		params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign, KeyUsagePurpose::DigitalSignature];
+		params.not_before = rcgen::date_time_ymd(year - 1, 1, 1);
+		params.not_after = rcgen::date_time_ymd(year + 10, 1, 1);
+
+		let cert = Certificate::from_params(params)?;
+		Ok((cert.serialize_pem()?, cert.serialize_private_key_pem()))
+	}
+
+	/// TLS server config presenting a certificate for `host` signed by the CA
+	pub fn server_config(&self, host: &str) -> Result<Arc<rustls::ServerConfig>> {
+		if let Some(config) = self.configs.get(host) {
+			return Ok(config.clone());
+		}
+
+		// Clients reject leaf certificates valid for more than about a year
+		let now = chrono::Utc::now();
+		let mut params = CertificateParams::new(vec![host.to_string()]);
+		params.distinguished_name.push(DnType::CommonName, host);
+		params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+		params.not_before = rcgen::date_time_ymd(now.year(), now.month() as u8, 1);
+		params.not_after = rcgen::date_time_ymd(now.year() + 1, now.month() as u8, 1);
+
+		let leaf = Certificate::from_params(params)?;
+		let cert_der = leaf.serialize_der_with_signer(&self.ca)?;
+
+		let mut config = rustls::ServerConfig::builder()
+			.with_safe_defaults()
+			.with_no_client_auth()
+			.with_single_cert(vec![rustls::Certificate(cert_der)], rustls::PrivateKey(leaf.serialize_private_key_der()))?;
+		// Only HTTP/1.1 is parsed, so never negotiate h2
+		config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+		let config = Arc::new(config);
+		self.configs.insert(host.to_string(), config.clone());
+		debug!("Minted interception certificate for {}", host);
+		Ok(config)
+	}
+}
+
+/// Terminates TLS on CONNECT tunnels, forwards the decrypted requests
+/// upstream and logs each exchange with source `proxy`. Without a CA it only
+/// serves plain HTTP: requests sent to the proxy and `proxy --transparent`.
+pub struct Interceptor {
+	ca: Option<CertificateAuthority>,
+	upstream: reqwest::Client,
+	logger: Arc<RequestLogger>,
+	/// Log bodies without undoing their Content-Encoding
+	raw_bodies: bool,
+	/// `--script` hooks run on every intercepted exchange
+	scripts: Option<Arc<ScriptHooks>>,
+	/// Canned responses answering matching requests instead of the origin
+	mocks: Option<Arc<MockSet>>,
+	/// Header, URL and status rewrites applied around forwarding
+	rewrites: Option<Arc<RewriteRules>>,
+	/// Stored responses reused while fresh (`proxy --cache`)
+	cache: Option<Arc<ProxyCache>>,
+}
+
+/// A response from the origin, or the stored copy the cache answers with
+pub struct UpstreamResponse {
+	pub status: u16,
+	/// In order, with lower-case names; repeated headers stay separate
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+}
+
+impl Interceptor {
+	pub fn new(ca: Option<CertificateAuthority>, logger: Arc<RequestLogger>) -> Result<Self> {
+		// Redirects and compression are passed through to the client untouched
+		let upstream = reqwest::Client::builder()
+			.redirect(reqwest::redirect::Policy::none())
+			.connect_timeout(std::time::Duration::from_secs(10))
+			.build()?;
+
+		Ok(Self { ca, upstream, logger, raw_bodies: false, scripts: None, mocks: None, rewrites: None, cache: None })
+	}
+
+	/// Log response bodies as received instead of decoded (`--raw-body`)
+	pub fn with_raw_bodies(mut self, raw: bool) -> Self {
+		self.raw_bodies = raw;
+		self
+	}
+
+	/// Let the hooks of a `--script` rewrite or block requests before they go
+	/// upstream and responses before the client gets them
+	pub fn with_scripts(mut self, scripts: Option<Arc<ScriptHooks>>) -> Self {
+		self.scripts = scripts;
+		self
+	}
+
+	/// Answer requests matching one of `mocks` without contacting the origin
+	pub fn with_mocks(mut self, mocks: Option<Arc<MockSet>>) -> Self {
+		self.mocks = mocks;
+		self
+	}
+
+	/// Rewrite requests before they are forwarded and their responses before
+	/// the client gets them
+	pub fn with_rewrites(mut self, rewrites: Option<Arc<RewriteRules>>) -> Self {
+		self.rewrites = rewrites;
+		self
+	}
+
+	/// Answer from `cache` while a stored response may be reused, and store
+	/// what the origin returns
+	pub fn with_cache(mut self, cache: Option<Arc<ProxyCache>>) -> Self {
+		self.cache = cache;
+		self
+	}
+
+	/// Whether TLS connections can be terminated, i.e. a CA was given
+	pub fn decrypts_tls(&self) -> bool {
+		self.ca.is_some()
+	}
+
+	/// Handle an established CONNECT tunnel to `host:port` as a TLS server
+	pub async fn intercept<S>(&self, stream: S, client: SocketAddr, access: &AccessControl, host: &str, port: u16) -> Result<()>
+	where
+		S: AsyncRead + AsyncWrite + Unpin,
+	{
+		let ca = self.ca.as_ref().ok_or_else(|| anyhow!("TLS interception needs --mitm"))?;
+		let acceptor = tokio_rustls::TlsAcceptor::from(ca.server_config(host)?);
+		let tls = acceptor.accept(stream).await
+			.with_context(|| format!("TLS handshake with client for {} failed (is the Riddler CA trusted?)", host))?;
+		info!("Intercepting TLS connection to {}:{}", host, port);
+
+		self.serve(tls, client, access, None, |parsed| upstream_url(parsed, host, port)).await
+	}
+
+	/// Handle a client using the proxy for plain HTTP, starting with the
+	/// request whose `head` was already read; its targets are absolute URLs
+	pub async fn serve_proxied<S>(&self, stream: S, client: SocketAddr, access: &AccessControl, head: Vec<u8>) -> Result<()>
+	where
+		S: AsyncRead + AsyncWrite + Unpin,
+	{
+		self.serve(stream, client, access, Some(head), str::to_string).await
+	}
+
+	/// Handle a plain HTTP connection redirected to the proxy, whose client
+	/// meant to reach `destination` (`proxy --transparent`)
+	pub async fn serve_plain<S>(&self, stream: S, client: SocketAddr, access: &AccessControl, destination: SocketAddr) -> Result<()>
+	where
+		S: AsyncRead + AsyncWrite + Unpin,
+	{
+		info!("Intercepting HTTP connection to {}", destination);
+		self.serve(stream, client, access, None, |parsed| plain_url(parsed, destination)).await
+	}
+
+	/// Forward and log the requests arriving on `stream` until the client
+	/// closes it, after `first` when its head was already read; `url_of`
+	/// turns the URL the parser built into the upstream one. Every request
+	/// names its own host, so each one is checked against `access`, not just
+	/// the host the connection was opened for.
+	async fn serve<S>(
+		&self,
+		stream: S,
+		client: SocketAddr,
+		access: &AccessControl,
+		mut first: Option<Vec<u8>>,
+		url_of: impl Fn(&str) -> String,
+	) -> Result<()>
+	where
+		S: AsyncRead + AsyncWrite + Unpin,
+	{
+		let (read, mut write) = tokio::io::split(stream);
+		let mut reader = BufReader::new(read);
+
+		loop {
+			let head = match first.take() {
+				Some(head) => head,
+				None => match read_head(&mut reader).await? {
+					Some(head) => head,
+					None => break,
+				},
+			};
+			let Some(mut request) = HttpParser::parse_http_request_bytes(&head) else {
+				warn!("Unparseable request from {}, closing", client);
+				break;
+			};
+			request.url = url_of(&request.url);
+			let host = url::Url::parse(&request.url).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default();
+			if let Err(denial) = access.check_host(&host) {
+				warn!("Refused {} {} from {}: {:?}", request.method, request.url, client, denial);
+				write.write_all(&denial.response()).await?;
+				break;
+			}
+			request.source_ip = client.ip().to_string();
+			request.source_port = client.port();
+			request.body = match read_body(&mut reader, &request.headers).await {
+				Ok(body) => body,
+				Err(e) if e.is::<BodyTooLarge>() => {
+					warn!("Refused {} {} from {}: {}", request.method, request.url, client, e);
+					write.write_all(&error_response(413, &e.to_string())).await?;
+					break;
+				}
+				Err(e) => return Err(e),
+			};
+
+			if websocket::is_upgrade(request.headers.iter().map(|(name, value)| (name.as_str(), value.as_str()))) {
+				return self.websocket(request, reader, write).await;
+			}
+			let close = request.headers.get("connection").is_some_and(|v| v.eq_ignore_ascii_case("close"));
+			let response = self.forward(&request).await;
+			write.write_all(&response).await?;
+			write.flush().await?;
+
+			if close {
+				break;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Pass a WebSocket upgrade inside the tunnel on to the origin, log the
+	/// handshake like any exchange and then relay and log the frames
+	async fn websocket<R, W>(&self, mut request: HttpRequest, reader: R, mut write: W) -> Result<()>
+	where
+		R: AsyncRead + Unpin,
+		W: AsyncWrite + Unpin,
+	{
+		let sent_at = chrono::Utc::now();
+		if let Some(rewrites) = &self.rewrites {
+			rewrites.apply_request(&request.method, &mut request.url, &mut request.headers);
+		}
+		info!("Intercepted WebSocket upgrade to {}", request.url);
+
+		let url = url::Url::parse(&request.url)?;
+		let headers: Vec<(String, String)> = request.headers.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+		let upstream = match websocket::handshake(&url, &headers).await {
+			Ok(upstream) => upstream,
+			Err(e) => {
+				error!("WebSocket upgrade to {} failed: {:#}", request.url, e);
+				write.write_all(&error_response(502, &format!("WebSocket upgrade failed: {:#}", e))).await?;
+				return Ok(());
+			}
+		};
+		write.write_all(&upstream.response).await?;
+		write.flush().await?;
+		self.log_exchange(&request, &upstream.response, sent_at).await;
+		if upstream.status != 101 {
+			return Ok(());
+		}
+		websocket::relay(reader, write, upstream.stream, &request.url).await
+	}
+
+	/// Send `request` upstream and return the raw HTTP/1.1 response for the client
+	async fn forward(&self, request: &HttpRequest) -> Vec<u8> {
+		let sent_at = chrono::Utc::now();
+		info!("Intercepted {} {}", request.method, request.url);
+
+		let mut request = request.clone();
+		let mut seen = None;
+		if let Some(scripts) = &self.scripts {
+			let mut parts = ScriptRequest {
+				method: request.method.clone(),
+				url: request.url.clone(),
+				headers: request.headers.clone(),
+				body: request.body.clone(),
+			};
+			if let Err(e) = scripts.on_request(&mut parts) {
+				warn!("Blocked {} {}: {:#}", request.method, request.url, e);
+				return error_response(403, &format!("{:#}", e));
+			}
+			request.method = parts.method.clone();
+			request.url = parts.url.clone();
+			// Parsed header names are lower case, which the filters below rely on
+			request.headers = parts.headers.iter().map(|(name, value)| (name.to_ascii_lowercase(), value.clone())).collect();
+			request.body = parts.body.clone();
+			seen = Some(parts);
+		}
+		let applied = self.rewrites.as_ref()
+			.map(|rewrites| rewrites.apply_request(&request.method, &mut request.url, &mut request.headers));
+		let request = &request;
+
+		if let Some(mock) = self.mocks.as_ref().and_then(|mocks| mocks.find(&request.method, &request.url)) {
+			info!("Answering {} {} with mock '{}'", request.method, request.url, mock.name);
+			tokio::time::sleep(mock.latency).await;
+			let raw = mock.raw_response(&request.method);
+			self.log_exchange(request, &raw, sent_at).await;
+			return raw;
+		}
+
+		let fetched = match &self.cache {
+			Some(cache) => cache.exchange(request, |request| async move { self.fetch(&request).await }).await,
+			None => self.fetch(request).await,
+		};
+		let UpstreamResponse { mut status, mut headers, mut body } = match fetched {
+			Ok(response) => response,
+			Err(raw) => return raw,
+		};
+
+		if let (Some(rewrites), Some(applied)) = (&self.rewrites, &applied) {
+			rewrites.apply_response(applied, &mut status, &mut headers);
+		}
+		if let (Some(scripts), Some(seen)) = (&self.scripts, &seen) {
+			// Repeated headers such as Set-Cookie reach the script as one value
+			// with a line per header
+			let mut joined: HashMap<String, String> = HashMap::new();
+			for (name, value) in &headers {
+				joined.entry(name.clone())
+					.and_modify(|joined| {
+						joined.push('\n');
+						joined.push_str(value);
+					})
+					.or_insert_with(|| value.clone());
+			}
+			let mut parts = ScriptResponse { status, headers: joined, body };
+			if let Err(e) = scripts.on_response(seen, &mut parts) {
+				warn!("Blocked response to {} {}: {:#}", request.method, request.url, e);
+				return error_response(502, &format!("{:#}", e));
+			}
+			status = parts.status;
+			headers = parts.headers.iter()
+				.flat_map(|(name, value)| value.lines().map(|line| (name.to_ascii_lowercase(), line.to_string())))
+				.collect();
+			body = parts.body;
+		}
+
+		let reason = reqwest::StatusCode::from_u16(status).ok().and_then(|status| status.canonical_reason()).unwrap_or("");
+		let mut head = format!("HTTP/1.1 {} {}\r\n", status, reason);
+		for (name, value) in &headers {
+			let keep_length = request.method == "HEAD" && name == "content-length";
+			if !HOP_BY_HOP.contains(&name.as_str()) && (name != "content-length" || keep_length) {
+				head.push_str(&format!("{}: {}\r\n", name, value));
+			}
+		}
+		if request.method != "HEAD" {
+			head.push_str(&format!("content-length: {}\r\n", body.len()));
+		}
+		head.push_str("\r\n");
+
+		let mut raw = head.into_bytes();
+		raw.extend_from_slice(&body);
+
+		self.log_exchange(request, &raw, sent_at).await;
+		raw
+	}
+
+	/// Send `request` to the origin; `Err` holds the raw error response for the client
+	async fn fetch(&self, request: &HttpRequest) -> Result<UpstreamResponse, Vec<u8>> {
+		let method = reqwest::Method::from_bytes(request.method.as_bytes())
+			.map_err(|_| error_response(400, &format!("Unsupported method {}", request.method)))?;
+
+		let mut builder = self.upstream.request(method, &request.url);
+		for (name, value) in &request.headers {
+			// Without Accept-Encoding the logged bodies stay readable
+			if !HOP_BY_HOP.contains(&name.as_str()) && name != "content-length" && name != "accept-encoding" {
+				builder = builder.header(name, value);
+			}
+		}
+		if !request.body.is_empty() {
+			builder = builder.body(request.body.clone());
+		}
+
+		let response = builder.send().await.map_err(|e| {
+			error!("Upstream request to {} failed: {}", request.url, e);
+			error_response(502, &format!("Upstream request failed: {}", e))
+		})?;
+
+		let status = response.status().as_u16();
+		let headers = response.headers().iter()
+			.map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+			.collect();
+		let body = response.bytes().await
+			.map_err(|e| error_response(502, &format!("Reading upstream response failed: {}", e)))?
+			.to_vec();
+		Ok(UpstreamResponse { status, headers, body })
+	}
+
+	async fn log_exchange(&self, request: &HttpRequest, raw: &[u8], sent_at: chrono::DateTime<chrono::Utc>) {
+		if let Some(parsed) = HttpParser::parse_http_response_bytes(raw, chrono::Utc::now()) {
+			let info = parsed.into_response_info(request, sent_at, self.raw_bodies);
+			if let Err(e) = self.logger.log_request_response(request, &info, "proxy").await {
+				error!("Failed to log intercepted exchange: {}", e);
+			}
+		}
+	}
+}
+
+/// Read a request head up to and including the blank line; `None` on a clean EOF
+async fn read_head<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+	let mut head = Vec::new();
+	loop {
+		// Stop one byte past the limit, so a line without a newline cannot grow unbounded
+		let limit = (MAX_HEAD_SIZE + 1).saturating_sub(head.len()) as u64;
+		let read = (&mut *reader).take(limit).read_until(b'\n', &mut head).await?;
+		if read == 0 {
+			return if head.is_empty() { Ok(None) } else { Err(anyhow!("Connection closed mid-request")) };
+		}
+		if head.ends_with(b"\r\n\r\n") || head == b"\r\n" || head.ends_with(b"\n\n") {
+			return Ok(Some(head));
+		}
+		if head.len() > MAX_HEAD_SIZE {
+			return Err(anyhow!("Request head exceeds {} bytes", MAX_HEAD_SIZE));
+		}
+	}
+}
+
+/// Read a request body framed by Content-Length or chunked transfer encoding
+async fn read_body<R: AsyncBufRead + Unpin>(
+	reader: &mut R,
+	headers: &std::collections::HashMap<String, String>,
+) -> Result<Vec<u8>> {
+	let chunked = headers.get("transfer-encoding").is_some_and(|v| v.to_lowercase().contains("chunked"));
+	if !chunked {
+		let length: usize = headers.get("content-length").and_then(|v| v.trim().parse().ok()).unwrap_or(0);
+		if length > MAX_BODY_SIZE {
+			return Err(BodyTooLarge.into());
+		}
+		let mut body = vec![0; length];
+		reader.read_exact(&mut body).await?;
+		return Ok(body);
+	}
+
+	let mut body = Vec::new();
+	loop {
+		let size_line = read_line(reader).await?;
+		let size_hex = size_line.split(';').next().unwrap_or("").trim();
+		let size = usize::from_str_radix(size_hex, 16).map_err(|_| anyhow!("Invalid chunk size '{}'", size_hex))?;
+
+		if size == 0 {
+			// Skip trailers up to the terminating blank line
+			loop {
+				if read_line(reader).await?.trim().is_empty() {
+					return Ok(body);
+				}
+			}
+		}
+
+		let start = body.len();
+		let total = start.checked_add(size).filter(|total| *total <= MAX_BODY_SIZE).ok_or(BodyTooLarge)?;
+		body.resize(total, 0);
+		reader.read_exact(&mut body[start..]).await?;
+		read_line(reader).await?;
+	}
+}
+
+/// One line of a chunked body, empty at EOF
+async fn read_line<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<String> {
+	let mut line = Vec::new();
+	(&mut *reader).take(MAX_LINE_SIZE as u64).read_until(b'\n', &mut line).await?;
+	if line.len() == MAX_LINE_SIZE && !line.ends_with(b"\n") {
+		return Err(anyhow!("Chunked body line exceeds {} bytes", MAX_LINE_SIZE));
+	}
+	Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// The parser builds `http://<Host header><path>`; point it at the tunnel's HTTPS origin
+fn upstream_url(parsed: &str, host: &str, port: u16) -> String {
+	match url::Url::parse(parsed) {
+		Ok(mut url) if url.host_str().is_some() => {
+			let _ = url.set_scheme("https");
+			if url.port().is_none() && port != 443 {
+				let _ = url.set_port(Some(port));
+			}
+			url.to_string()
+		}
+		_ => {
+			let path = parsed.strip_prefix("http://").unwrap_or(parsed);
+			let authority = if port == 443 { host.to_string() } else { format!("{}:{}", host, port) };
+			format!("https://{}{}", authority, path)
+		}
+	}
+}
+
+/// Requests without a Host header go to the address the client connected to
+fn plain_url(parsed: &str, destination: SocketAddr) -> String {
+	match parsed.strip_prefix("http://") {
+		Some(path) if path.starts_with('/') => format!("http://{}{}", destination, path),
+		_ => parsed.to_string(),
+	}
+}
+
+fn error_response(status: u16, message: &str) -> Vec<u8> {
+	let reason = reqwest::StatusCode::from_u16(status).ok().and_then(|status| status.canonical_reason()).unwrap_or("");
+	format!(
+		"HTTP/1.1 {} {}\r\ncontent-type: text/plain\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+		status, reason, message.len(), message
+	).into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::ProxyConfig;
+	use tokio::net::TcpListener;
+
+	#[tokio::test]
+	async fn proxied_requests_reach_the_origin_rewritten() {
+		let dir = std::env::temp_dir().join(format!("riddler-mitm-test-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let rules = dir.join("rewrites.yaml");
+		std::fs::write(&rules, "rewrites:\n  - request_headers:\n      remove: [User-Agent]\n      set: { X-Debug: \"1\" }\n").unwrap();
+		let logger = Arc::new(RequestLogger::new(dir.join("requests.log").to_str().unwrap()).await.unwrap());
+		let interceptor = Interceptor::new(None, logger).unwrap()
+			.with_rewrites(Some(Arc::new(RewriteRules::load(&rules).unwrap())));
+		let access = AccessControl::new(&ProxyConfig::default()).unwrap();
+
+		// The origin answers one request and hands back the head it received
+		let origin = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let port = origin.local_addr().unwrap().port();
+		let received = tokio::spawn(async move {
+			let (stream, _) = origin.accept().await.unwrap();
+			let mut reader = BufReader::new(stream);
+			let head = read_head(&mut reader).await.unwrap().unwrap();
+			reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok").await.unwrap();
+			String::from_utf8(head).unwrap().to_ascii_lowercase()
+		});
+
+		let (mut client, proxy_side) = tokio::io::duplex(64 * 1024);
+		let head = format!(
+			"GET http://127.0.0.1:{port}/path HTTP/1.1\r\nHost: 127.0.0.1:{port}\r\nUser-Agent: test\r\n\
+				Proxy-Connection: keep-alive\r\nProxy-Authorization: Basic dXNlcjpwdw==\r\nConnection: close\r\n\r\n"
+		);
+		interceptor.serve_proxied(proxy_side, "127.0.0.1:40000".parse().unwrap(), &access, head.into_bytes()).await.unwrap();
+		let mut response = Vec::new();
+		client.read_to_end(&mut response).await.unwrap();
+
+		let forwarded = received.await.unwrap();
+		assert!(forwarded.starts_with("get /path http/1.1\r\n"), "{forwarded}");
+		assert!(forwarded.contains("\r\nx-debug: 1\r\n"), "{forwarded}");
+		assert!(!forwarded.contains("user-agent: test"), "{forwarded}");
+		assert!(!forwarded.contains("proxy-"), "{forwarded}");
+		let response = String::from_utf8(response).unwrap();
+		assert!(response.starts_with("HTTP/1.1 200 OK\r\n") && response.ends_with("\r\n\r\nok"), "{response}");
+		std::fs::remove_dir_all(&dir).ok();
+	}
+}
@@ -0,0 +1,157 @@
+use crate::network::glob_match;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::Duration;
+
+/// A mocks file as given to `proxy --mock`
+#[derive(Debug, Deserialize)]
+struct MockFile {
+	mocks: Vec<MockConfig>,
+}
+
+/// Which requests a mock or proxy rewrite rule applies to
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RequestMatcher {
+	/// Request method, any when missing
+	method: Option<String>,
+	/// Host name glob such as `*.example.com`, any when missing
+	host: Option<String>,
+	/// Path glob such as `/api/users/*`, matched without the query string
+	path: Option<String>,
+}
+
+impl RequestMatcher {
+	pub fn matches(&self, method: &str, url: &url::Url) -> bool {
+		let host = url.host_str().unwrap_or("").to_lowercase();
+		self.method.as_ref().is_none_or(|expected| expected.eq_ignore_ascii_case(method))
+			&& self.matches_host(&host)
+			&& self.path.as_ref().is_none_or(|pattern| glob_match(pattern, url.path()))
+	}
+
+	pub fn matches_host(&self, host: &str) -> bool {
+		self.host.as_ref().is_none_or(|pattern| glob_match(&pattern.to_lowercase(), &host.to_lowercase()))
+	}
+
+	/// Whether only the host is constrained, so a CONNECT tunnel can be judged
+	pub fn host_only(&self) -> bool {
+		self.method.is_none() && self.path.is_none()
+	}
+}
+
+#[derive(Debug, Deserialize)]
+struct MockConfig {
+	name: Option<String>,
+	#[serde(flatten)]
+	matcher: RequestMatcher,
+	#[serde(default = "default_status")]
+	status: u16,
+	#[serde(default)]
+	headers: BTreeMap<String, String>,
+	#[serde(default)]
+	body: String,
+	/// Read the body from this file instead, relative to the mocks file
+	body_file: Option<String>,
+	/// Wait this long before answering, e.g. `800ms`, `1.5s` or milliseconds
+	latency: Option<Latency>,
+}
+
+fn default_status() -> u16 {
+	200
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Latency {
+	Millis(u64),
+	Text(String),
+}
+
+/// A canned response and the requests it answers
+#[derive(Debug)]
+pub struct Mock {
+	pub name: String,
+	matcher: RequestMatcher,
+	pub status: u16,
+	pub headers: Vec<(String, String)>,
+	pub body: Vec<u8>,
+	pub latency: Duration,
+}
+
+/// Mocks tried in file order, the first match answers
+pub struct MockSet {
+	mocks: Vec<Mock>,
+}
+
+impl MockSet {
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.with_context(|| format!("Unable to read mocks file {}", path.display()))?;
+		let file: MockFile = serde_yaml::from_str(&content)
+			.with_context(|| format!("{} is not a valid mocks file", path.display()))?;
+		let base = path.parent().unwrap_or(Path::new("."));
+
+		let mocks = file.mocks
+			.into_iter()
+			.enumerate()
+			.map(|(i, config)| {
+				let name = config.name.unwrap_or_else(|| format!("mock #{}", i + 1));
+				let body = match &config.body_file {
+					Some(file) => std::fs::read(base.join(file))
+						.with_context(|| format!("Unable to read body_file {} of '{}'", file, name))?,
+					None => config.body.into_bytes(),
+				};
+				let latency = match config.latency {
+					None => 0,
+					Some(Latency::Millis(ms)) => ms,
+					Some(Latency::Text(text)) => crate::watch::parse_millis(&text)
+						.with_context(|| format!("Invalid latency of '{}'", name))?,
+				};
+				if !(100..=999).contains(&config.status) {
+					return Err(anyhow!("Invalid status {} of '{}'", config.status, name));
+				}
+				Ok(Mock {
+					matcher: config.matcher,
+					status: config.status,
+					headers: config.headers.into_iter().collect(),
+					body,
+					latency: Duration::from_millis(latency),
+					name,
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+		Ok(Self { mocks })
+	}
+
+	pub fn len(&self) -> usize {
+		self.mocks.len()
+	}
+
+	/// The first mock answering `method` `url`
+	pub fn find(&self, method: &str, url: &str) -> Option<&Mock> {
+		let url = url::Url::parse(url).ok()?;
+		self.mocks.iter().find(|mock| mock.matcher.matches(method, &url))
+	}
+}
+
+impl Mock {
+	/// The canned response as a raw HTTP/1.1 response; `X-Riddler-Mock` names
+	/// the mock so stubbed answers are easy to tell apart in the client
+	pub fn raw_response(&self, method: &str) -> Vec<u8> {
+		let reason = reqwest::StatusCode::from_u16(self.status).ok().and_then(|status| status.canonical_reason()).unwrap_or("");
+		let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason);
+		for (name, value) in &self.headers {
+			if !name.eq_ignore_ascii_case("content-length") {
+				head.push_str(&format!("{}: {}\r\n", name, value));
+			}
+		}
+		head.push_str(&format!("x-riddler-mock: {}\r\ncontent-length: {}\r\n\r\n", self.name, self.body.len()));
+
+		let mut raw = head.into_bytes();
+		if method != "HEAD" {
+			raw.extend_from_slice(&self.body);
+		}
+		raw
+	}
+}
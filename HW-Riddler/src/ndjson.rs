@@ -0,0 +1,176 @@
+use crate::geoip::GeoInfo;
+use crate::http_client::HttpResponseInfo;
+use crate::network::{HttpRequest, NetworkPacket};
+use crate::quic::ClientInitial;
+use crate::redact::Redactor;
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::info;
+
+/// Captured bodies are cut to this many characters in the stream
+const MAX_BODY_CHARS: usize = 4096;
+
+#[derive(clap::ValueEnum, Clone, Debug, Default, PartialEq)]
+pub enum MonitorOutput {
+	/// Log exchanges to the request log only
+	#[default]
+	Log,
+	/// Also stream every request and response as one JSON object per line
+	Ndjson,
+}
+
+/// One line of the stream. Requests are emitted as soon as they are parsed,
+/// responses once they are matched to their request.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MonitorEvent<'a> {
+	Request {
+		timestamp: String,
+		method: &'a str,
+		url: Cow<'a, str>,
+		headers: Cow<'a, HashMap<String, String>>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		body: Option<String>,
+		source_ip: &'a str,
+		source_port: u16,
+		destination_ip: &'a str,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		resolved_host: Option<&'a str>,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		geo: Option<&'a GeoInfo>,
+	},
+	Response {
+		timestamp: String,
+		method: &'a str,
+		url: Cow<'a, str>,
+		status: u16,
+		time_ms: u64,
+		headers: Cow<'a, HashMap<String, String>>,
+		source_ip: &'a str,
+		source_port: u16,
+	},
+	/// A QUIC handshake, reported once per connection. The exchanges inside
+	/// are encrypted, so this is all that is visible of HTTP/3 traffic.
+	Quic {
+		timestamp: String,
+		source_ip: &'a str,
+		source_port: u16,
+		destination_ip: &'a str,
+		destination_port: u16,
+		version: u32,
+		#[serde(skip_serializing_if = "Option::is_none")]
+		server_name: Option<&'a str>,
+		alpn: &'a [String],
+		http3: bool,
+	},
+}
+
+impl<'a> MonitorEvent<'a> {
+	pub fn request(request: &'a HttpRequest) -> Self {
+		let body = (!request.body.is_empty()).then(|| {
+			String::from_utf8_lossy(&request.body).chars().take(MAX_BODY_CHARS).collect()
+		});
+		MonitorEvent::Request {
+			timestamp: now(),
+			method: &request.method,
+			url: Cow::Borrowed(&request.url),
+			headers: Cow::Borrowed(&request.headers),
+			body,
+			source_ip: &request.source_ip,
+			source_port: request.source_port,
+			destination_ip: &request.destination_ip,
+			resolved_host: request.resolved_host.as_deref(),
+			geo: request.geo.as_ref(),
+		}
+	}
+
+	pub fn response(request: &'a HttpRequest, response: &'a HttpResponseInfo) -> Self {
+		MonitorEvent::Response {
+			timestamp: now(),
+			method: &request.method,
+			url: Cow::Borrowed(&request.url),
+			status: response.status,
+			time_ms: response.response_time_ms,
+			headers: Cow::Borrowed(&response.headers),
+			source_ip: &request.source_ip,
+			source_port: request.source_port,
+		}
+	}
+
+	pub fn quic(packet: &'a NetworkPacket, initial: &'a ClientInitial) -> Self {
+		MonitorEvent::Quic {
+			timestamp: now(),
+			source_ip: &packet.src_ip,
+			source_port: packet.src_port,
+			destination_ip: &packet.dst_ip,
+			destination_port: packet.dst_port,
+			version: initial.version,
+			server_name: initial.server_name.as_deref(),
+			alpn: &initial.alpn,
+			http3: initial.is_http3(),
+		}
+	}
+}
+
+fn now() -> String {
+	chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// Where the stream goes: `-` for stdout, `tcp://HOST:PORT`, `unix:PATH`
+/// (Unix only) or a file that is appended to
+pub struct NdjsonSink {
+	writer: Box<dyn AsyncWrite + Unpin + Send>,
+	target: String,
+	/// Masks events the same way as the request log
+	redactor: Option<Redactor>,
+}
+
+impl NdjsonSink {
+	pub async fn open(target: &str) -> Result<Self> {
+		let writer: Box<dyn AsyncWrite + Unpin + Send> = if target == "-" {
+			Box::new(tokio::io::stdout())
+		} else if let Some(address) = target.strip_prefix("tcp://") {
+			Box::new(tokio::net::TcpStream::connect(address).await
+				.with_context(|| format!("Unable to connect to {}", address))?)
+		} else if let Some(path) = target.strip_prefix("unix:") {
+			connect_unix(path).await?
+		} else {
+			Box::new(tokio::fs::OpenOptions::new().create(true).append(true).open(target).await
+				.with_context(|| format!("Unable to open {}", target))?)
+		};
+		info!("Streaming monitored HTTP exchanges as NDJSON to {}", target);
+		Ok(Self { writer, target: target.to_string(), redactor: None })
+	}
+
+	pub fn with_redaction(mut self, redactor: Option<Redactor>) -> Self {
+		self.redactor = redactor;
+		self
+	}
+
+	/// Write and flush one line, so consumers see every event immediately
+	pub async fn emit(&mut self, mut event: MonitorEvent<'_>) -> Result<()> {
+		if let Some(redactor) = &self.redactor {
+			redactor.apply_event(&mut event);
+		}
+		let mut line = serde_json::to_vec(&event)?;
+		line.push(b'\n');
+		let target = &self.target;
+		self.writer.write_all(&line).await.map_err(|e| anyhow!("Unable to write to {}: {}", target, e))?;
+		self.writer.flush().await.map_err(|e| anyhow!("Unable to write to {}: {}", target, e))
+	}
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &str) -> Result<Box<dyn AsyncWrite + Unpin + Send>> {
+	let stream = tokio::net::UnixStream::connect(path).await
+		.with_context(|| format!("Unable to connect to socket {}", path))?;
+	Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(path: &str) -> Result<Box<dyn AsyncWrite + Unpin + Send>> {
+	Err(anyhow!("Unix sockets are not supported on this platform: {}", path))
+}
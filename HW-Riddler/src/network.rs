@@ -1,14 +1,17 @@
 use anyhow::{anyhow, Result};
 use pcap::{Capture, Device};
 use pnet::packet::{
-	ethernet::{EtherTypes, EthernetPacket},
+	ethernet::{EtherType, EtherTypes},
 	ip::IpNextHeaderProtocols,
 	ipv4::Ipv4Packet,
 	tcp::TcpPacket,
+	udp::UdpPacket,
 	Packet,
 };
-use std::collections::HashMap;
-use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}, Mutex};
+use crate::http_client::HttpResponseInfo;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering}, Mutex};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn, trace};
 
@@ -18,12 +21,20 @@ pub struct NetworkPacket {
 	pub dst_ip: String,
 	pub src_port: u16,
 	pub dst_port: u16,
-	pub _protocol: String,
+	/// `TCP` or `UDP`
+	pub protocol: String,
+	/// Protocol inside UDP named by a [`crate::dissector::UdpDissector`], e.g. `DNS`
+	pub application_protocol: Option<&'static str>,
 	pub payload: Vec<u8>,
-	pub _timestamp: chrono::DateTime<chrono::Utc>,
-	pub _tcp_seq: Option<u32>,
+	/// Payload length on the wire, which exceeds `payload.len()` when the
+	/// capture snapshot length cut the packet short
+	pub payload_size: usize,
+	pub timestamp: chrono::DateTime<chrono::Utc>,
+	pub tcp_seq: Option<u32>,
 	pub _tcp_ack: Option<u32>,
-	pub _tcp_flags: Option<u8>,
+	pub tcp_flags: Option<u8>,
+	/// Capture device the packet was seen on
+	pub interface: String,
 }
 
 #[derive(Debug, Clone)]
@@ -34,108 +45,575 @@ pub struct HttpRequest {
 	pub body: Vec<u8>,
 	pub source_ip: String,
 	pub source_port: u16,
+	/// Server address the request was captured going to
+	pub destination_ip: String,
+	/// Name the destination address was looked up as, from captured DNS answers
+	pub resolved_host: Option<String>,
+	/// Location and AS of the destination address, from `--geoip-db`
+	pub geo: Option<crate::geoip::GeoInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+	pub status: u16,
+	pub headers: HashMap<String, String>,
+	/// Each `Set-Cookie` header value
+	pub cookies: Vec<String>,
+	/// Body bytes carried in the same segment as the headers
+	pub body: Vec<u8>,
+	pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// TCP 4-tuple seen from the client side: (client ip, client port, server ip, server port)
+type ConnectionKey = (String, u16, String, u16);
+
+/// Pairs captured responses with the request sent on the same TCP connection.
+///
+/// Requests are queued per connection so pipelined requests are matched in
+/// order; requests that never see a response are handed back by `expire`.
+#[derive(Default)]
+pub struct ExchangeTracker {
+	pending: HashMap<ConnectionKey, VecDeque<(HttpRequest, chrono::DateTime<chrono::Utc>)>>,
+}
+
+impl ExchangeTracker {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn track_request(&mut self, packet: &NetworkPacket, request: HttpRequest) {
+		let key = (packet.src_ip.clone(), packet.src_port, packet.dst_ip.clone(), packet.dst_port);
+		self.pending.entry(key).or_default().push_back((request, packet.timestamp));
+	}
+
+	/// Take the oldest outstanding request of the connection `packet` answers
+	pub fn match_response(&mut self, packet: &NetworkPacket) -> Option<(HttpRequest, chrono::DateTime<chrono::Utc>)> {
+		let key = (packet.dst_ip.clone(), packet.dst_port, packet.src_ip.clone(), packet.src_port);
+		let queue = self.pending.get_mut(&key)?;
+		let matched = queue.pop_front();
+		if queue.is_empty() {
+			self.pending.remove(&key);
+		}
+		matched
+	}
+
+	/// Remove and return requests that have waited longer than `max_age`
+	pub fn expire(&mut self, max_age: chrono::Duration) -> Vec<HttpRequest> {
+		let cutoff = chrono::Utc::now() - max_age;
+		let mut expired = Vec::new();
+		self.pending.retain(|_, queue| {
+			while queue.front().is_some_and(|(_, sent)| *sent < cutoff) {
+				expired.extend(queue.pop_front().map(|(request, _)| request));
+			}
+			!queue.is_empty()
+		});
+		expired
+	}
+
+	/// Remove and return every outstanding request
+	pub fn drain(&mut self) -> Vec<HttpRequest> {
+		self.pending.drain().flat_map(|(_, queue)| queue.into_iter().map(|(request, _)| request)).collect()
+	}
+}
+
+/// Connection state inferred from the TCP flags seen so far. UDP flows are
+/// always `Active`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlowState {
+	Opening,
+	Established,
+	Closing,
+	Closed,
+	Active,
+}
+
+impl std::fmt::Display for FlowState {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let name = match self {
+			FlowState::Opening => "SYN",
+			FlowState::Established => "ESTABLISHED",
+			FlowState::Closing => "CLOSING",
+			FlowState::Closed => "CLOSED",
+			FlowState::Active => "ACTIVE",
+		};
+		f.write_str(name)
+	}
+}
+
+/// Protocol and the two endpoints in sorted order, so both directions of a
+/// connection map to the same flow
+type FlowKey = (String, (String, u16), (String, u16));
+
+/// Traffic of one connection (TCP) or address/port pair (UDP)
+#[derive(Debug, Clone)]
+pub struct Flow {
+	pub protocol: String,
+	/// The side that sent the SYN; without one, the side with the higher port
+	pub client: (String, u16),
+	pub server: (String, u16),
+	pub packets_out: u64,
+	pub packets_in: u64,
+	/// Payload bytes sent by the client
+	pub bytes_out: u64,
+	pub bytes_in: u64,
+	pub state: FlowState,
+	pub first_seen: chrono::DateTime<chrono::Utc>,
+	pub last_seen: chrono::DateTime<chrono::Utc>,
+	/// Which sides have sent a FIN: bit 0 client, bit 1 server
+	fins: u8,
+}
+
+impl Flow {
+	pub fn packets(&self) -> u64 {
+		self.packets_out + self.packets_in
+	}
+
+	pub fn bytes(&self) -> u64 {
+		self.bytes_out + self.bytes_in
+	}
+
+	pub fn duration(&self) -> chrono::Duration {
+		self.last_seen - self.first_seen
+	}
+
+	fn update_state(&mut self, flags: u8, from_client: bool) {
+		const FIN: u8 = 0x01;
+		const SYN: u8 = 0x02;
+		const RST: u8 = 0x04;
+		const ACK: u8 = 0x10;
+
+		if flags & RST != 0 {
+			self.state = FlowState::Closed;
+		} else if flags & FIN != 0 {
+			self.fins |= if from_client { 1 } else { 2 };
+			self.state = if self.fins == 3 { FlowState::Closed } else { FlowState::Closing };
+		} else if flags & SYN != 0 {
+			self.state = FlowState::Opening;
+		} else if self.state == FlowState::Opening && flags & ACK != 0 {
+			self.state = FlowState::Established;
+		}
+	}
+}
+
+/// Flows of a capture keyed by 5-tuple, for `riddler flows`
+#[derive(Default)]
+pub struct FlowTable {
+	flows: HashMap<FlowKey, Flow>,
+}
+
+impl FlowTable {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn record(&mut self, packet: &NetworkPacket) {
+		let source = (packet.src_ip.clone(), packet.src_port);
+		let destination = (packet.dst_ip.clone(), packet.dst_port);
+		let key = if source <= destination {
+			(packet.protocol.clone(), source.clone(), destination.clone())
+		} else {
+			(packet.protocol.clone(), destination.clone(), source.clone())
+		};
+
+		let flow = self.flows.entry(key).or_insert_with(|| {
+			// A SYN without ACK comes from the client, a SYN+ACK from the server
+			let from_client = match packet.tcp_flags {
+				Some(flags) if flags & 0x02 != 0 => flags & 0x10 == 0,
+				_ => packet.src_port >= packet.dst_port,
+			};
+			let (client, server) = if from_client {
+				(source.clone(), destination.clone())
+			} else {
+				(destination.clone(), source.clone())
+			};
+			Flow {
+				protocol: packet.protocol.clone(),
+				client,
+				server,
+				packets_out: 0,
+				packets_in: 0,
+				bytes_out: 0,
+				bytes_in: 0,
+				// Connections already open when the capture started never show a SYN
+				state: if packet.protocol == "TCP" { FlowState::Established } else { FlowState::Active },
+				first_seen: packet.timestamp,
+				last_seen: packet.timestamp,
+				fins: 0,
+			}
+		});
+
+		let from_client = flow.client == source;
+		if from_client {
+			flow.packets_out += 1;
+			flow.bytes_out += packet.payload_size as u64;
+		} else {
+			flow.packets_in += 1;
+			flow.bytes_in += packet.payload_size as u64;
+		}
+		flow.last_seen = flow.last_seen.max(packet.timestamp);
+		if let Some(flags) = packet.tcp_flags {
+			flow.update_state(flags, from_client);
+		}
+	}
+
+	/// Drop flows idle for longer than `idle` and closed flows after a short
+	/// grace period, returning how many were removed
+	pub fn expire(&mut self, idle: chrono::Duration) -> usize {
+		let now = chrono::Utc::now();
+		let before = self.flows.len();
+		self.flows.retain(|_, flow| {
+			let timeout = if flow.state == FlowState::Closed { chrono::Duration::seconds(5).min(idle) } else { idle };
+			now - flow.last_seen <= timeout
+		});
+		before - self.flows.len()
+	}
+
+	pub fn len(&self) -> usize {
+		self.flows.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.flows.is_empty()
+	}
+
+	pub fn flows(&self) -> impl Iterator<Item = &Flow> {
+		self.flows.values()
+	}
+
+	/// Per-address totals over all flows as (address, bytes sent, bytes
+	/// received, flows), busiest first
+	pub fn top_talkers(&self, limit: usize) -> Vec<(String, u64, u64, usize)> {
+		let mut talkers: HashMap<&str, (u64, u64, usize)> = HashMap::new();
+		for flow in self.flows.values() {
+			let client = talkers.entry(&flow.client.0).or_default();
+			client.0 += flow.bytes_out;
+			client.1 += flow.bytes_in;
+			client.2 += 1;
+			let server = talkers.entry(&flow.server.0).or_default();
+			server.0 += flow.bytes_in;
+			server.1 += flow.bytes_out;
+			server.2 += 1;
+		}
+		let mut talkers: Vec<_> = talkers.into_iter()
+			.map(|(address, (sent, received, flows))| (address.to_string(), sent, received, flows))
+			.collect();
+		talkers.sort_by_key(|(_, sent, received, _)| std::cmp::Reverse(sent + received));
+		talkers.truncate(limit);
+		talkers
+	}
+}
+
+impl HttpRequest {
+	/// Lower-cased host name from the Host header, or the URL, without port
+	pub fn host(&self) -> String {
+		self.headers.get("host")
+			.map(|host| host.rsplit_once(':').filter(|(_, port)| port.parse::<u16>().is_ok()).map_or(host.as_str(), |(name, _)| name))
+			.map(str::to_string)
+			.or_else(|| url::Url::parse(&self.url).ok().and_then(|u| u.host_str().map(str::to_string)))
+			.unwrap_or_default()
+			.to_lowercase()
+	}
+}
+
+impl HttpResponse {
+	/// Convert to the logged response form, timing it against when the request was seen.
+	/// The body is decoded per its Content-Encoding unless `raw_body` is set.
+	pub fn into_response_info(self, request: &HttpRequest, sent_at: chrono::DateTime<chrono::Utc>, raw_body: bool) -> HttpResponseInfo {
+		let decoded = if raw_body { None } else { crate::content_encoding::decode_body(&self.headers, &self.body) };
+		let body = String::from_utf8_lossy(decoded.as_deref().unwrap_or(&self.body));
+		let body = if body.len() > 1000 {
+			format!("{}...", body.chars().take(1000).collect::<String>())
+		} else {
+			body.into_owned()
+		};
+
+		HttpResponseInfo {
+			status: self.status,
+			cookies: self.cookies,
+			headers: self.headers,
+			body,
+			response_time_ms: (self.timestamp - sent_at).num_milliseconds().max(0) as u64,
+			final_url: request.url.clone(),
+			attempts: Vec::new(),
+			http_version: String::new(),
+			remote_addr: None,
+		}
+	}
+}
+
+/// Where `monitor --save-pcap` writes captured packets
+#[derive(Debug, Clone)]
+pub struct PcapSaveOptions {
+	pub path: PathBuf,
+	/// Start a new file once the current one reaches this many bytes
+	pub rotate_bytes: Option<u64>,
+}
+
+impl PcapSaveOptions {
+	/// `capture.pcap` → `capture.eth0.pcap`, so that each device of a
+	/// multi-interface capture writes its own file
+	fn for_interface(&self, interface: &str) -> Self {
+		let interface: String = interface.chars()
+			.map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+			.collect();
+		let stem = self.path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+		let name = match self.path.extension() {
+			Some(ext) => format!("{}.{}.{}", stem, interface, ext.to_string_lossy()),
+			None => format!("{}.{}", stem, interface),
+		};
+		Self { path: self.path.with_file_name(name), rotate_bytes: self.rotate_bytes }
+	}
+}
+
+/// Writes packets to a pcap savefile, rolling over to `name.1.pcap`,
+/// `name.2.pcap`, ... when size rotation is enabled
+struct PcapWriter {
+	options: PcapSaveOptions,
+	savefile: pcap::Savefile,
+	index: usize,
+	written: u64,
+}
+
+impl PcapWriter {
+	/// Global pcap file header size
+	const FILE_HEADER: u64 = 24;
+	/// Per-record header size
+	const RECORD_HEADER: u64 = 16;
+
+	fn open(cap: &Capture<pcap::Active>, options: PcapSaveOptions) -> Result<Self> {
+		let savefile = cap.savefile(&options.path)
+			.map_err(|e| anyhow!("Unable to create {}: {}", options.path.display(), e))?;
+		info!("Saving captured packets to {}", options.path.display());
+		Ok(Self { options, savefile, index: 0, written: Self::FILE_HEADER })
+	}
+
+	fn write(&mut self, packet: &pcap::Packet) {
+		self.savefile.write(packet);
+		self.written += Self::RECORD_HEADER + packet.header.caplen as u64;
+	}
+
+	fn needs_rotation(&self) -> bool {
+		self.options.rotate_bytes.is_some_and(|limit| self.written >= limit)
+	}
+
+	fn rotate(&mut self, cap: &Capture<pcap::Active>) {
+		let path = Self::rotated_path(&self.options.path, self.index + 1);
+		match cap.savefile(&path) {
+			Ok(savefile) => {
+				let _ = self.savefile.flush();
+				self.savefile = savefile;
+				self.index += 1;
+				self.written = Self::FILE_HEADER;
+				eprintln!("抓包文件已轮转: {}", path.display());
+			}
+			Err(e) => error!("Failed to rotate capture file to {}: {}", path.display(), e),
+		}
+	}
+
+	fn flush(&mut self) {
+		if let Err(e) = self.savefile.flush() {
+			warn!("Failed to flush capture file: {}", e);
+		}
+	}
+
+	/// `capture.pcap` → `capture.1.pcap` for index 1
+	fn rotated_path(base: &Path, index: usize) -> PathBuf {
+		if index == 0 {
+			return base.to_path_buf();
+		}
+		let stem = base.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+		let name = match base.extension() {
+			Some(ext) => format!("{}.{}.{}", stem, index, ext.to_string_lossy()),
+			None => format!("{}.{}", stem, index),
+		};
+		base.with_file_name(name)
+	}
+}
+
+/// Packets that can wait in the channel between capture and processing
+/// before new ones are dropped
+pub const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+
+/// Counters shared by the capture thread and the packet consumer
+#[derive(Debug, Default)]
+pub struct CaptureStats {
+	/// Parsed TCP packets handed to the channel
+	pub queued: AtomicU64,
+	/// Packets dropped because the channel was full
+	pub dropped: AtomicU64,
+	/// Packets the consumer took off the channel
+	pub processed: AtomicU64,
+	/// Dropped by the kernel because the capture buffer was full
+	pub kernel_dropped: AtomicU64,
+	/// Dropped by the interface or its driver
+	pub interface_dropped: AtomicU64,
+}
+
+impl CaptureStats {
+	pub fn record_processed(&self) {
+		self.processed.fetch_add(1, Ordering::Relaxed);
+	}
+
+	pub fn summary(&self) -> String {
+		format!("queued {}, processed {}, dropped {} (queue full), kernel dropped {}, interface dropped {}",
+			self.queued.load(Ordering::Relaxed),
+			self.processed.load(Ordering::Relaxed),
+			self.dropped.load(Ordering::Relaxed),
+			self.kernel_dropped.load(Ordering::Relaxed),
+			self.interface_dropped.load(Ordering::Relaxed))
+	}
+}
+
+/// What one interface's capture thread shares with the monitor
+struct CaptureSettings {
+	filter: String,
+	shutdown_flag: Arc<AtomicBool>,
+	is_releasing: Arc<AtomicBool>,
+	retry_count: Arc<AtomicUsize>,
+	stats: Arc<CaptureStats>,
+	sender: mpsc::Sender<NetworkPacket>,
+	save_pcap: Option<PcapSaveOptions>,
 }
 
 pub struct PacketMonitor {
-	interface: String,
+	interfaces: Vec<String>,
 	filter: String,
-	packet_sender: Arc<Mutex<Option<mpsc::UnboundedSender<NetworkPacket>>>>,
+	save_pcap: Option<PcapSaveOptions>,
+	/// Read packets from this capture file instead of the interfaces
+	read_file: Option<PathBuf>,
+	packet_sender: Arc<Mutex<Option<mpsc::Sender<NetworkPacket>>>>,
 	shutdown_flag: Arc<AtomicBool>,
-	max_memory_usage: usize,
+	stats: Arc<CaptureStats>,
 	retry_count: Arc<AtomicUsize>,
 	is_releasing: Arc<AtomicBool>,
 }
 
 impl PacketMonitor {
+	/// Packets are dropped and counted, rather than buffered without limit,
+	/// when the consumer of `packet_sender` falls behind. Each interface gets
+	/// its own capture loop; all of them feed `packet_sender`.
 	pub fn new(
-		interface: String,
+		interfaces: Vec<String>,
 		filter: String,
-		packet_sender: mpsc::UnboundedSender<NetworkPacket>,
+		packet_sender: mpsc::Sender<NetworkPacket>,
 	) -> Self {
 		Self {
-			interface,
+			interfaces,
 			filter,
+			save_pcap: None,
+			read_file: None,
 			packet_sender: Arc::new(Mutex::new(Some(packet_sender))),
 			shutdown_flag: Arc::new(AtomicBool::new(false)),
-			max_memory_usage: 100 * 1024 * 1024,
+			stats: Arc::new(CaptureStats::default()),
 			retry_count: Arc::new(AtomicUsize::new(0)),
 			is_releasing: Arc::new(AtomicBool::new(false)),
 		}
 	}
 
+	pub fn stats(&self) -> Arc<CaptureStats> {
+		self.stats.clone()
+	}
+
+	/// Also write every captured packet to a pcap file
+	pub fn with_save_pcap(mut self, options: Option<PcapSaveOptions>) -> Self {
+		self.save_pcap = options;
+		self
+	}
+
+	/// Replay a saved capture file rather than capturing live
+	pub fn with_read_file(mut self, path: Option<PathBuf>) -> Self {
+		self.read_file = path;
+		self
+	}
+
+	/// Start one capture loop per interface. The returned handle completes once
+	/// every loop has stopped.
 	pub async fn start_monitor(&self) -> Result<tokio::task::JoinHandle<()>> {
 		self.retry_count.store(0, Ordering::SeqCst);
 
-		let device = match Device::list() {
-			Ok(devices) => {
-				devices.into_iter()
-					.find(|d| d.name == self.interface)
-					.ok_or_else(|| anyhow!("Interface '{}' not found. Available interfaces: {:?}",
-										self.interface,
-										Device::list().map_or_else(
-											|_| vec!["<error listing devices>".to_string()],
-											|devs| devs.into_iter().map(|d| d.name).collect()
-										)))
-			},
+		if let Some(path) = self.read_file.clone() {
+			let sender = {
+				let guard = self.packet_sender.lock().unwrap();
+				guard.as_ref().ok_or_else(|| anyhow!("Packet sender not available"))?.clone()
+			};
+			let mut cap = Capture::from_file(&path)
+				.map_err(|e| anyhow!("Unable to open capture file {}: {}", path.display(), e))?;
+			cap.filter(&self.filter, true)
+				.map_err(|e| anyhow!("Invalid BPF filter '{}': {}", self.filter, e))?;
+			let shutdown_flag = self.shutdown_flag.clone();
+			let stats = self.stats.clone();
+			info!("Reading packets from {}", path.display());
+			return Ok(tokio::task::spawn_blocking(move || {
+				Self::run_file_loop(cap, path, shutdown_flag, stats, sender)
+			}));
+		}
+
+		let available = match Device::list() {
+			Ok(devices) => devices,
 			Err(e) => {
 				let err_str = e.to_string().to_lowercase();
 				if err_str.contains("permission") || err_str.contains("privileges") {
-					Err(anyhow!("Insufficient privileges to list network interfaces. Please run with sudo/administrator privileges."))
+					return Err(anyhow!("Insufficient privileges to list network interfaces. Please run with sudo/administrator privileges."));
 				} else {
-					Err(anyhow!("Failed to list network interfaces: {}", e))
+					return Err(anyhow!("Failed to list network interfaces: {}", e));
 				}
 			}
-		}?;
+		};
 
-		info!("Starting packet monitor on interface: {} with address: {:?}",
-			self.interface, device.addresses);
+		let mut devices = Vec::with_capacity(self.interfaces.len());
+		for interface in &self.interfaces {
+			let device = available.iter()
+				.find(|d| &d.name == interface)
+				.cloned()
+				.ok_or_else(|| anyhow!("Interface '{}' not found. Available interfaces: {:?}",
+									interface,
+									available.iter().map(|d| d.name.as_str()).collect::<Vec<_>>()))?;
+			info!("Starting packet monitor on interface: {} with address: {:?}",
+				interface, device.addresses);
+			devices.push(device);
+		}
 
 		let sender = {
 			let guard = self.packet_sender.lock().unwrap();
 			guard.as_ref().ok_or_else(|| anyhow!("Packet sender not available"))?.clone()
 		};
 
-		let retry_count = self.retry_count.clone();
-		let shutdown_flag = self.shutdown_flag.clone();
-		let is_releasing = self.is_releasing.clone();
-		let interface = self.interface.clone();
-		let filter = self.filter.clone();
-		let max_memory_usage = self.max_memory_usage;
-
-		let handle = tokio::task::spawn_blocking(move || {
-			Self::run_capture_loop(
-				device,
-				interface,
-				filter,
-				shutdown_flag,
-				is_releasing,
-				retry_count,
-				max_memory_usage,
-				sender,
-			)
-		});
+		let mut handles = Vec::with_capacity(devices.len());
+		for device in devices {
+			let retry_count = self.retry_count.clone();
+			let shutdown_flag = self.shutdown_flag.clone();
+			let is_releasing = self.is_releasing.clone();
+			let interface = device.name.clone();
+			let filter = self.filter.clone();
+			let stats = self.stats.clone();
+			let sender = sender.clone();
+			let save_pcap = match &self.save_pcap {
+				Some(options) if self.interfaces.len() > 1 => Some(options.for_interface(&interface)),
+				options => options.clone(),
+			};
+
+			let settings = CaptureSettings { filter, shutdown_flag, is_releasing, retry_count, stats, sender, save_pcap };
+			handles.push(tokio::task::spawn_blocking(move || Self::run_capture_loop(device, interface, settings)));
+		}
 
-		Ok(handle)
+		Ok(tokio::spawn(async move {
+			for handle in handles {
+				if let Err(e) = handle.await {
+					error!("Capture loop failed: {}", e);
+				}
+			}
+		}))
 	}
 
-	fn run_capture_loop(
-		device: Device,
-		interface: String,
-		filter: String,
-		shutdown_flag: Arc<AtomicBool>,
-		is_releasing: Arc<AtomicBool>,
-		retry_count: Arc<AtomicUsize>,
-		max_memory_usage: usize,
-		sender: mpsc::UnboundedSender<NetworkPacket>,
-	) {
-		println!("网络捕获开始于接口: {}", interface);
+	fn run_capture_loop(device: Device, interface: String, settings: CaptureSettings) {
+		let CaptureSettings { filter, shutdown_flag, is_releasing, retry_count, stats, sender, save_pcap } = settings;
+		eprintln!("网络捕获开始于接口: {}", interface);
 		info!("Packet monitor loop started on interface: {}", interface);
 		info!("Using filter: {}", filter);
 
 		let mut packet_count = 0;
 		let mut current_retries = 0;
 		const MAX_RETRIES: usize = 3;
-		let mut current_memory_usage = 0;
 
 		let stats_interval = std::time::Duration::from_secs(5);
 		let mut stats_timer = std::time::Instant::now();
@@ -144,7 +622,7 @@ impl PacketMonitor {
 
 		let mut cap = match Self::init_capture(&device, &filter) {
 			Ok(cap) => {
-				println!("成功初始化网络捕获 ({})", interface);
+				eprintln!("成功初始化网络捕获 ({})", interface);
 				info!("Successfully initialized capture on {}", interface);
 				cap
 			},
@@ -160,18 +638,42 @@ impl PacketMonitor {
 			}
 		};
 
+		let mut link_type = cap.get_datalink();
+		// Kernel drop totals of `cap` already added to the shared `stats`
+		let mut reported_drops = (0, 0);
+
+		let mut pcap_writer = match save_pcap.map(|options| PcapWriter::open(&cap, options)).transpose() {
+			Ok(writer) => writer,
+			Err(e) => {
+				error!("{}", e);
+				return;
+			}
+		};
+
 		let mut last_packet_time = std::time::Instant::now();
 
 		loop {
 			if last_packet_time.elapsed().as_secs() > 30 {
-				info!("No packets received in the last 30 seconds. Make sure your filter '{}' is correct.", filter);
+				info!("No packets received on {} in the last 30 seconds. Make sure your filter '{}' is correct.", interface, filter);
 				last_packet_time = std::time::Instant::now();
 			}
 
 			if stats_timer.elapsed() >= stats_interval {
+				Self::record_kernel_drops(&mut cap, &stats, &mut reported_drops);
 				if packet_count_since_last_stats > 0 {
-					println!("已捕获 {} 个数据包 ({} 个HTTP包)",
-							packet_count_since_last_stats, http_count_since_last_stats);
+					eprintln!("已捕获 {} 个数据包 ({} 个HTTP包) [{}]",
+							packet_count_since_last_stats, http_count_since_last_stats, interface);
+				}
+				let dropped = stats.dropped.load(Ordering::Relaxed)
+					+ stats.kernel_dropped.load(Ordering::Relaxed)
+					+ stats.interface_dropped.load(Ordering::Relaxed);
+				if dropped > 0 {
+					eprintln!("⚠️  已丢弃 {} 个数据包: {}", dropped, stats.summary());
+				} else {
+					debug!("Capture statistics: {}", stats.summary());
+				}
+				if let Some(ref mut writer) = pcap_writer {
+					writer.flush();
 				}
 				stats_timer = std::time::Instant::now();
 				packet_count_since_last_stats = 0;
@@ -199,7 +701,20 @@ impl PacketMonitor {
 						debug!("Monitored {} packets", packet_count);
 					}
 
-					if let Some(network_packet) = Self::parse_packet(packet.data) {
+					if let Some(ref mut writer) = pcap_writer {
+						writer.write(&packet);
+					}
+					let parsed = Self::parse_packet(packet.data, link_type);
+
+					// Rotate only once the packet borrowed from the capture is no longer used
+					if let Some(ref mut writer) = pcap_writer
+						&& writer.needs_rotation()
+					{
+						writer.rotate(&cap);
+					}
+
+					if let Some(mut network_packet) = parsed {
+						network_packet.interface = interface.clone();
 						debug!("Captured packet from {}:{} to {}:{} (payload: {} bytes)",
 							network_packet.src_ip, network_packet.src_port,
 							network_packet.dst_ip, network_packet.dst_port,
@@ -215,24 +730,21 @@ impl PacketMonitor {
 							http_count_since_last_stats += 1;
 						}
 
-						let packet_size =
-							network_packet.payload.len() +
-							network_packet.src_ip.len() +
-							network_packet.dst_ip.len() +
-							std::mem::size_of::<NetworkPacket>();
-
-						if current_memory_usage + packet_size > max_memory_usage {
-							warn!("Memory limit reached ({} bytes), dropping packet", max_memory_usage);
-							continue;
-						}
-
-						current_memory_usage += packet_size;
-
-						if let Err(e) = sender.send(network_packet) {
-							error!("Failed to send packet: {}", e);
-							break;
-						} else {
-							debug!("Packet sent successfully to processor");
+						match sender.try_send(network_packet) {
+							Ok(()) => {
+								stats.queued.fetch_add(1, Ordering::Relaxed);
+								debug!("Packet sent successfully to processor");
+							}
+							Err(mpsc::error::TrySendError::Full(_)) => {
+								// Warn on the first drop and then every 1000th
+								if stats.dropped.fetch_add(1, Ordering::Relaxed).is_multiple_of(1000) {
+									warn!("Packet queue full, dropping packets (processing is falling behind)");
+								}
+							}
+							Err(mpsc::error::TrySendError::Closed(_)) => {
+								error!("Failed to send packet: receiver closed");
+								break;
+							}
 						}
 					} else {
 						trace!("Received packet #{}, but does not match expected protocols", packet_count);
@@ -253,8 +765,11 @@ impl PacketMonitor {
 
 						match Self::init_capture(&device, &filter) {
 							Ok(new_cap) => {
-								info!("Successfully reinitialized capture");
+								info!("Successfully reinitialized capture on {}", interface);
+								Self::record_kernel_drops(&mut cap, &stats, &mut reported_drops);
 								cap = new_cap;
+								link_type = cap.get_datalink();
+								reported_drops = (0, 0);
 							}
 							Err(e) => {
 								error!("Failed to reinitialize capture: {}", e);
@@ -271,10 +786,64 @@ impl PacketMonitor {
 			}
 		}
 
-		info!("Packet monitor loop ended, monitored {} packets total", packet_count);
+		Self::record_kernel_drops(&mut cap, &stats, &mut reported_drops);
+		if let Some(ref mut writer) = pcap_writer {
+			writer.flush();
+		}
+		info!("Packet monitor loop ended on {}, monitored {} packets total", interface, packet_count);
+		info!("Capture statistics: {}", stats.summary());
 		info!("Packet processing errors/retries: {}", retry_count.load(Ordering::SeqCst));
 	}
 
+	/// Parse every packet of a capture file. Packets wait for room in the
+	/// channel instead of being dropped, as nothing is lost by slowing down.
+	fn run_file_loop(
+		mut cap: Capture<pcap::Offline>,
+		path: PathBuf,
+		shutdown_flag: Arc<AtomicBool>,
+		stats: Arc<CaptureStats>,
+		sender: mpsc::Sender<NetworkPacket>,
+	) {
+		let link_type = cap.get_datalink();
+		let interface = path.display().to_string();
+		let mut packet_count = 0;
+
+		while !shutdown_flag.load(Ordering::SeqCst) {
+			match cap.next_packet() {
+				Ok(packet) => {
+					packet_count += 1;
+					let Some(mut network_packet) = Self::parse_packet(packet.data, link_type) else { continue };
+					network_packet.interface = interface.clone();
+					if sender.blocking_send(network_packet).is_err() {
+						error!("Failed to send packet: receiver closed");
+						break;
+					}
+					stats.queued.fetch_add(1, Ordering::Relaxed);
+				}
+				Err(pcap::Error::NoMorePackets) => break,
+				Err(e) => {
+					error!("Error reading {}: {}", interface, e);
+					break;
+				}
+			}
+		}
+
+		eprintln!("已读取 {} 个数据包 ({})", packet_count, interface);
+		info!("Capture file {} done, {} packets", interface, packet_count);
+	}
+
+	/// Add the drops pcap counted on `cap` since the last call. pcap reports
+	/// totals since the capture was opened, while `stats` is shared by the
+	/// captures of all interfaces.
+	fn record_kernel_drops(cap: &mut Capture<pcap::Active>, stats: &CaptureStats, reported: &mut (u64, u64)) {
+		if let Ok(kernel) = cap.stats() {
+			let (dropped, if_dropped) = (kernel.dropped as u64, kernel.if_dropped as u64);
+			stats.kernel_dropped.fetch_add(dropped.saturating_sub(reported.0), Ordering::Relaxed);
+			stats.interface_dropped.fetch_add(if_dropped.saturating_sub(reported.1), Ordering::Relaxed);
+			*reported = (dropped.max(reported.0), if_dropped.max(reported.1));
+		}
+	}
+
 	fn init_capture(device: &Device, filter: &str) -> Result<Capture<pcap::Active>> {
 		let mut cap = Capture::from_device(device.clone())?
 			.promisc(true)
@@ -306,12 +875,29 @@ impl PacketMonitor {
 		self.is_releasing.store(false, Ordering::SeqCst);
 	}
 
-	fn parse_packet(data: &[u8]) -> Option<NetworkPacket> {
-		let ethernet = EthernetPacket::new(data)?;
+	/// EtherType and payload of a link-layer frame. Captures on the Linux `any`
+	/// device carry cooked (SLL) headers instead of Ethernet ones.
+	fn link_payload(data: &[u8], link_type: pcap::Linktype) -> Option<(EtherType, &[u8])> {
+		// Offsets of the protocol field and of the payload
+		let (protocol, offset) = match link_type {
+			pcap::Linktype::ETHERNET => (12, 14),
+			pcap::Linktype::LINUX_SLL => (14, 16),
+			pcap::Linktype::LINUX_SLL2 => (0, 20),
+			_ => {
+				trace!("Unsupported link type: {:?}", link_type);
+				return None;
+			}
+		};
+		let ethertype = u16::from_be_bytes(data.get(protocol..protocol + 2)?.try_into().ok()?);
+		Some((EtherType(ethertype), data.get(offset..)?))
+	}
+
+	fn parse_packet(data: &[u8], link_type: pcap::Linktype) -> Option<NetworkPacket> {
+		let (ethertype, payload) = Self::link_payload(data, link_type)?;
 
-		match ethernet.get_ethertype() {
+		match ethertype {
 			EtherTypes::Ipv4 => {
-				let ipv4 = Ipv4Packet::new(ethernet.payload())?;
+				let ipv4 = Ipv4Packet::new(payload)?;
 
 				match ipv4.get_next_level_protocol() {
 					IpNextHeaderProtocols::Tcp => {
@@ -326,14 +912,39 @@ impl PacketMonitor {
 							dst_ip: ipv4.get_destination().to_string(),
 							src_port: tcp.get_source(),
 							dst_port: tcp.get_destination(),
-							_protocol: "TCP".to_string(),
+							protocol: "TCP".to_string(),
+							application_protocol: None,
 							payload: tcp.payload().to_vec(),
-							_timestamp: chrono::Utc::now(),
-							_tcp_seq: tcp_seq,
+							payload_size: (ipv4.get_total_length() as usize)
+								.saturating_sub(ipv4.get_header_length() as usize * 4 + tcp.get_data_offset() as usize * 4),
+							timestamp: chrono::Utc::now(),
+							tcp_seq,
 							_tcp_ack: tcp_ack,
-							_tcp_flags: tcp_flags,
+							tcp_flags,
+							interface: String::new(),
 						})
 					},
+					IpNextHeaderProtocols::Udp => {
+						let udp = UdpPacket::new(ipv4.payload())?;
+
+						let mut packet = NetworkPacket {
+							src_ip: ipv4.get_source().to_string(),
+							dst_ip: ipv4.get_destination().to_string(),
+							src_port: udp.get_source(),
+							dst_port: udp.get_destination(),
+							protocol: "UDP".to_string(),
+							application_protocol: None,
+							payload: udp.payload().to_vec(),
+							payload_size: (udp.get_length() as usize).saturating_sub(8),
+							timestamp: chrono::Utc::now(),
+							tcp_seq: None,
+							_tcp_ack: None,
+							tcp_flags: None,
+							interface: String::new(),
+						};
+						packet.application_protocol = crate::dissector::dissect(&packet).map(|dissector| dissector.name());
+						Some(packet)
+					},
 					_ => {
 						debug!("Unsupported IPv4 protocol: {:?}", ipv4.get_next_level_protocol());
 						None
@@ -345,16 +956,265 @@ impl PacketMonitor {
 				None
 			},
 			_ => {
-				trace!("Unsupported EtherType: {:?}", ethernet.get_ethertype());
+				trace!("Unsupported EtherType: {:?}", ethertype);
 				None
 			}
 		}
 	}
 }
 
+/// How the end of a request body is found (RFC 9112 §6.3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+	/// No body
+	None,
+	ContentLength(usize),
+	Chunked,
+}
+
+impl BodyFraming {
+	/// Chunked transfer coding takes precedence over Content-Length
+	pub fn of(headers: &HashMap<String, String>) -> Self {
+		if headers.get("transfer-encoding").is_some_and(|v| v.to_lowercase().contains("chunked")) {
+			return BodyFraming::Chunked;
+		}
+		match headers.get("content-length").and_then(|v| v.trim().parse().ok()) {
+			Some(length) if length > 0 => BodyFraming::ContentLength(length),
+			_ => BodyFraming::None,
+		}
+	}
+
+	/// The body held by `raw`, the bytes following the head, and whether it
+	/// is complete
+	pub fn decode(self, raw: &[u8]) -> (Vec<u8>, bool) {
+		match self {
+			BodyFraming::None => (Vec::new(), true),
+			BodyFraming::ContentLength(length) => (raw[..raw.len().min(length)].to_vec(), raw.len() >= length),
+			BodyFraming::Chunked => Self::decode_chunked(raw),
+		}
+	}
+
+	fn decode_chunked(raw: &[u8]) -> (Vec<u8>, bool) {
+		let mut body = Vec::new();
+		let mut pos = 0;
+		loop {
+			let Some(line_end) = raw[pos..].windows(2).position(|w| w == b"\r\n").map(|i| pos + i) else {
+				return (body, false);
+			};
+			let size_line = String::from_utf8_lossy(&raw[pos..line_end]);
+			let Ok(size) = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16) else {
+				// Malformed; keep what was decoded instead of waiting for more
+				return (body, true);
+			};
+			// The last chunk; trailers are ignored
+			if size == 0 {
+				return (body, true);
+			}
+
+			let start = line_end + 2;
+			// A size no buffer can hold is as malformed as one that is not hex
+			let Some(end) = start.checked_add(size) else {
+				return (body, true);
+			};
+			match raw.get(start..end) {
+				Some(chunk) => body.extend_from_slice(chunk),
+				None => {
+					body.extend_from_slice(&raw[start.min(raw.len())..]);
+					return (body, false);
+				}
+			}
+			// `end` is within `raw`, so this cannot overflow
+			pos = end + 2;
+			if pos > raw.len() {
+				return (body, false);
+			}
+		}
+	}
+}
+
+/// Requests whose body continues past their first segment, by connection
+#[derive(Default)]
+pub struct RequestAssembler {
+	pending: HashMap<ConnectionKey, PendingRequest>,
+}
+
+struct PendingRequest {
+	request: HttpRequest,
+	framing: BodyFraming,
+	/// Body bytes as sent, before chunked decoding
+	raw_body: Vec<u8>,
+	/// `TLS` when the request came out of a decrypted session, whose
+	/// encrypted segments share the connection
+	application_protocol: Option<&'static str>,
+	started: chrono::DateTime<chrono::Utc>,
+}
+
+impl RequestAssembler {
+	/// Raw body bytes collected before a request is handed over regardless
+	const MAX_BODY: usize = 4 * 1024 * 1024;
+
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Feed a client segment. A request is returned once its body is
+	/// complete: from the segment carrying its head when the body fits, or
+	/// from the later segment of the same connection that ends it.
+	pub fn push(&mut self, packet: &NetworkPacket) -> Option<HttpRequest> {
+		let key = (packet.src_ip.clone(), packet.src_port, packet.dst_ip.clone(), packet.dst_port);
+		if let Some(mut pending) = self.pending.remove(&key) {
+			if packet.payload.is_empty() || packet.application_protocol != pending.application_protocol {
+				self.pending.insert(key, pending);
+				return None;
+			}
+			pending.raw_body.extend_from_slice(&packet.payload);
+			let (body, complete) = pending.framing.decode(&pending.raw_body);
+			pending.request.body = body;
+			if complete || pending.raw_body.len() >= Self::MAX_BODY {
+				return Some(pending.request);
+			}
+			self.pending.insert(key, pending);
+			return None;
+		}
+
+		let request = HttpParser::parse_http_request(packet)?;
+		let framing = BodyFraming::of(&request.headers);
+		let raw_body = HttpParser::head_end(&packet.payload).map_or_else(Vec::new, |end| packet.payload[end..].to_vec());
+		if framing.decode(&raw_body).1 {
+			return Some(request);
+		}
+		debug!("Waiting for the rest of the body of {} {}", request.method, request.url);
+		self.pending.insert(key, PendingRequest {
+			request,
+			framing,
+			raw_body,
+			application_protocol: packet.application_protocol,
+			started: packet.timestamp,
+		});
+		None
+	}
+
+	/// Remove and return requests still missing part of their body after
+	/// `max_age`, with the part that arrived
+	pub fn expire(&mut self, max_age: chrono::Duration) -> Vec<HttpRequest> {
+		let cutoff = chrono::Utc::now() - max_age;
+		let expired: Vec<ConnectionKey> = self.pending.iter()
+			.filter(|(_, pending)| pending.started < cutoff)
+			.map(|(key, _)| key.clone())
+			.collect();
+		expired.into_iter().filter_map(|key| self.pending.remove(&key)).map(|pending| pending.request).collect()
+	}
+
+	/// Remove and return every request still waiting for its body
+	pub fn drain(&mut self) -> Vec<HttpRequest> {
+		self.pending.drain().map(|(_, pending)| pending.request).collect()
+	}
+}
+
+/// Application-layer filter for parsed requests, for what BPF cannot express.
+/// Values given for the same option are alternatives; every option given must
+/// match. Patterns may use `*` as a wildcard and ignore case.
+#[derive(Debug, Clone, Default)]
+pub struct RequestFilter {
+	hosts: Vec<String>,
+	/// Without a `*` a path pattern matches as a prefix
+	paths: Vec<String>,
+	methods: Vec<String>,
+	/// Header name and, for `NAME:VALUE`, the value pattern
+	headers: Vec<(String, Option<String>)>,
+}
+
+impl RequestFilter {
+	pub fn new(hosts: Vec<String>, paths: Vec<String>, methods: Vec<String>, headers: Vec<String>) -> Result<Self> {
+		let headers = headers
+			.into_iter()
+			.map(|header| {
+				let (name, value) = match header.split_once(':') {
+					Some((name, value)) => (name, Some(value.trim().to_lowercase())),
+					None => (header.as_str(), None),
+				};
+				let name = name.trim().to_lowercase();
+				if name.is_empty() {
+					return Err(anyhow!("Invalid --match-header '{}', expected NAME or NAME:VALUE", header));
+				}
+				Ok((name, value))
+			})
+			.collect::<Result<Vec<_>>>()?;
+
+		Ok(Self {
+			hosts: hosts.iter().map(|h| h.to_lowercase()).collect(),
+			paths: paths.iter().map(|p| p.to_lowercase()).collect(),
+			methods: methods.iter().map(|m| m.to_uppercase()).collect(),
+			headers,
+		})
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.hosts.is_empty() && self.paths.is_empty() && self.methods.is_empty() && self.headers.is_empty()
+	}
+
+	pub fn matches(&self, request: &HttpRequest) -> bool {
+		let url = url::Url::parse(&request.url).ok();
+		let host = request.host();
+		let path = url.as_ref().map(|u| u.path().to_lowercase()).unwrap_or_default();
+
+		(self.hosts.is_empty() || self.hosts.iter().any(|pattern| glob_match(pattern, &host)))
+			&& (self.paths.is_empty() || self.paths.iter().any(|pattern| match pattern.contains('*') {
+				true => glob_match(pattern, &path),
+				false => path.starts_with(pattern.as_str()),
+			}))
+			&& (self.methods.is_empty() || self.methods.iter().any(|method| request.method.eq_ignore_ascii_case(method)))
+			&& self.headers.iter().all(|(name, value)| match (request.headers.get(name), value) {
+				(Some(actual), Some(pattern)) => glob_match(pattern, &actual.to_lowercase()),
+				(Some(_), None) => true,
+				(None, _) => false,
+			})
+	}
+}
+
+/// Whole-string match where `*` stands for any sequence of characters
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let text: Vec<char> = text.chars().collect();
+	let (mut p, mut t) = (0, 0);
+	// Position of the last `*` and the text position it was tried at
+	let mut backtrack: Option<(usize, usize)> = None;
+
+	while t < text.len() {
+		if p < pattern.len() && pattern[p] == '*' {
+			backtrack = Some((p, t));
+			p += 1;
+		} else if p < pattern.len() && pattern[p] == text[t] {
+			p += 1;
+			t += 1;
+		} else if let Some((star, tried)) = backtrack {
+			p = star + 1;
+			t = tried + 1;
+			backtrack = Some((star, tried + 1));
+		} else {
+			return false;
+		}
+	}
+	pattern[p..].iter().all(|&c| c == '*')
+}
+
 pub struct HttpParser {}
 
 impl HttpParser {
+	/// Decode a DNS query or response carried over UDP port 53
+	pub fn parse_dns(packet: &NetworkPacket) -> Option<crate::dns::DnsMessage> {
+		if packet.application_protocol != Some("DNS") {
+			return None;
+		}
+		match crate::dns::DnsMessage::parse(&packet.payload) {
+			Ok(message) => Some(message),
+			Err(e) => {
+				debug!("Undecodable DNS packet from {}:{}: {}", packet.src_ip, packet.src_port, e);
+				None
+			}
+		}
+	}
+
 	pub fn new() -> Self {
 		Self {}
 	}
@@ -480,16 +1340,6 @@ impl HttpParser {
 			format!("{}://{}{}", scheme, host, path)
 		};
 
-		let mut _content_length = 0;
-		if let Some(cl) = headers.get("content-length") {
-			if let Ok(len) = cl.parse::<usize>() {
-				_content_length = len;
-			}
-		}
-
-		let _chunked_encoding = headers.get("transfer-encoding")
-			.map_or(false, |v| v.to_lowercase().contains("chunked"));
-
 		Some(HttpRequest {
 			method: _method,
 			url: url,
@@ -497,9 +1347,73 @@ impl HttpParser {
 			body: Vec::new(),
 			source_ip: String::new(),
 			source_port: 0,
+			destination_ip: String::new(),
+			resolved_host: None,
+			geo: None,
 		})
 	}
 
+	pub fn is_http_response(data: &[u8]) -> bool {
+		data.starts_with(b"HTTP/1.0 ") || data.starts_with(b"HTTP/1.1 ")
+	}
+
+	/// Parse an `HTTP/1.x <status>` response head from the start of the payload
+	pub fn parse_http_response(packet: &NetworkPacket) -> Option<HttpResponse> {
+		let response = HttpParser::parse_http_response_bytes(&packet.payload, packet.timestamp)?;
+		debug!("Parsed HTTP response {} from {}:{}", response.status, packet.src_ip, packet.src_port);
+		Some(response)
+	}
+
+	/// Parse a raw response (head plus whatever body follows it) received at `timestamp`
+	pub fn parse_http_response_bytes(data: &[u8], timestamp: chrono::DateTime<chrono::Utc>) -> Option<HttpResponse> {
+		if !HttpParser::is_http_response(data) {
+			return None;
+		}
+
+		let header_end = data
+			.windows(4)
+			.position(|w| w == b"\r\n\r\n")
+			.map_or(data.len(), |p| p + 4);
+		let head = String::from_utf8_lossy(&data[..header_end]);
+		let mut lines = head.lines();
+
+		let status = lines.next()?.split_whitespace().nth(1)?.parse::<u16>().ok()?;
+		let mut headers: HashMap<String, String> = HashMap::new();
+		let mut cookies = Vec::new();
+		for line in lines.take_while(|l| !l.trim().is_empty()) {
+			if let Some((key, value)) = line.split_once(':') {
+				let key = key.trim().to_lowercase();
+				let value = value.trim().to_string();
+				if key == "set-cookie" {
+					cookies.push(value.clone());
+				}
+				// Repeated headers such as Set-Cookie are kept comma-joined
+				headers.entry(key)
+					.and_modify(|existing| *existing = format!("{}, {}", existing, value))
+					.or_insert(value);
+			}
+		}
+
+		Some(HttpResponse {
+			status,
+			headers,
+			cookies,
+			body: data[header_end..].to_vec(),
+			timestamp,
+		})
+	}
+
+	/// Offset just past the blank line ending a message head
+	pub fn head_end(data: &[u8]) -> Option<usize> {
+		data.windows(4).position(|w| w == b"\r\n\r\n").map(|p| p + 4)
+	}
+
+	/// Parse a raw request head that did not come from a captured packet,
+	/// e.g. one decrypted by the MITM proxy
+	pub fn parse_http_request_bytes(data: &[u8]) -> Option<HttpRequest> {
+		HttpParser::parse_http_request_from_string(&String::from_utf8_lossy(data))
+	}
+
 	pub fn parse_http_request(packet: &NetworkPacket) -> Option<HttpRequest> {
 		debug!("Attempting to parse HTTP request from packet: {:?}", packet.src_port);
 
@@ -518,8 +1432,18 @@ impl HttpParser {
 			if payload_str.len() > 50 { &payload_str[..50] } else { &payload_str });
 
 		if let Some(mut request) = HttpParser::parse_http_request_from_string(&payload_str) {
+			// Whatever part of the body shares the segment with the head
+			if let Some(head_end) = HttpParser::head_end(&packet.payload) {
+				request.body = BodyFraming::of(&request.headers).decode(&packet.payload[head_end..]).0;
+			}
 			request.source_ip = packet.src_ip.clone();
 			request.source_port = packet.src_port;
+			request.destination_ip = packet.dst_ip.clone();
+			if packet.application_protocol == Some("TLS")
+				&& let Some(rest) = request.url.strip_prefix("http://")
+			{
+				request.url = format!("https://{}", rest);
+			}
 			debug!("Successfully parsed HTTP request: {} {}", request.method, request.url);
 			return Some(request);
 		} else {
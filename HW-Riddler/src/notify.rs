@@ -0,0 +1,213 @@
+use crate::config::NotificationsConfig;
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpVersion, RequestBody, RetryPolicy};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+	Info,
+	#[default]
+	Warning,
+	Critical,
+}
+
+impl Severity {
+	pub fn icon(self) -> &'static str {
+		match self {
+			Severity::Info => "ℹ️",
+			Severity::Warning => "⚠️",
+			Severity::Critical => "🚨",
+		}
+	}
+
+	/// Attachment colour in Slack
+	fn color(self) -> &'static str {
+		match self {
+			Severity::Info => "#2eb67d",
+			Severity::Warning => "#ecb22e",
+			Severity::Critical => "#e01e5a",
+		}
+	}
+}
+
+/// Something worth telling the user about while they are not watching the
+/// terminal. Webhooks receive it as JSON with the event-specific details next
+/// to the common fields; `text` makes it show up as a message in Slack and
+/// Mattermost incoming webhooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+	pub text: String,
+	/// `monitor.rule`, `analyze.sla` or `replay.finished`
+	pub event: &'static str,
+	pub severity: Severity,
+	pub timestamp: DateTime<Utc>,
+	#[serde(flatten)]
+	pub details: Map<String, Value>,
+}
+
+impl Notification {
+	pub fn new(event: &'static str, severity: Severity, text: String) -> Self {
+		Self { text, event, severity, timestamp: Utc::now(), details: Map::new() }
+	}
+
+	/// Add the fields of `details`, which must serialize to an object. Fields
+	/// named like the common ones are dropped.
+	pub fn with(mut self, details: &impl Serialize) -> Self {
+		match serde_json::to_value(details) {
+			Ok(Value::Object(fields)) => {
+				self.details = fields.into_iter()
+					.filter(|(key, _)| !matches!(key.as_str(), "text" | "event" | "severity" | "timestamp"))
+					.collect();
+			}
+			Ok(_) => warn!("Notification details of {} are not an object", self.event),
+			Err(e) => warn!("Unable to encode notification details: {}", e),
+		}
+		self
+	}
+}
+
+/// Delivers notifications to the channels of the `[notifications]` config
+/// section. Delivery failures are logged and never stop the command.
+#[derive(Clone)]
+pub struct Notifier {
+	desktop: bool,
+	webhooks: Vec<String>,
+	slack_webhook: Option<String>,
+	replay_min: Duration,
+	http_client: Arc<HttpClient>,
+}
+
+impl Notifier {
+	pub fn new(config: &NotificationsConfig, http_client: Arc<HttpClient>) -> Self {
+		Self {
+			desktop: config.desktop,
+			webhooks: config.webhooks.clone(),
+			slack_webhook: config.slack_webhook.clone().filter(|url| !url.is_empty()),
+			replay_min: Duration::from_secs(config.replay_min_seconds),
+			http_client,
+		}
+	}
+
+	/// Show desktop notifications even when the config does not ask for them
+	pub fn with_desktop(mut self, desktop: bool) -> Self {
+		self.desktop |= desktop;
+		self
+	}
+
+	/// Also POST to `webhook`, next to the configured ones
+	pub fn with_webhook(mut self, webhook: Option<String>) -> Self {
+		self.webhooks.extend(webhook);
+		self
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.desktop || !self.webhooks.is_empty() || self.slack_webhook.is_some()
+	}
+
+	/// Whether a replay that took `elapsed` is long enough to be announced
+	pub fn announces_replay(&self, elapsed: Duration) -> bool {
+		self.is_enabled() && elapsed >= self.replay_min
+	}
+
+	/// Deliver `notification` to every channel
+	pub async fn send(&self, notification: &Notification) {
+		if self.desktop {
+			show_desktop(notification).await;
+		}
+		for webhook in &self.webhooks {
+			if let Err(e) = post_json(&self.http_client, webhook, notification).await {
+				warn!("Unable to deliver notification to {}: {}", webhook, e);
+			}
+		}
+		if let Some(webhook) = &self.slack_webhook
+			&& let Err(e) = post_json(&self.http_client, webhook, &slack_message(notification)).await
+		{
+			warn!("Unable to deliver notification to Slack: {}", e);
+		}
+	}
+
+	/// Deliver in the background, for callers that must not wait on a slow receiver
+	pub fn spawn(&self, notification: Notification) {
+		if !self.is_enabled() {
+			return;
+		}
+		let notifier = self.clone();
+		tokio::spawn(async move { notifier.send(&notification).await });
+	}
+}
+
+/// POST `body` as JSON; also used for the webhooks of `monitor --rules`
+pub async fn post_json(http_client: &HttpClient, url: &str, body: &impl Serialize) -> Result<()> {
+	let request = HttpRequestBuilder {
+		method: "POST".to_string(),
+		url: url.to_string(),
+		headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+		body: Some(RequestBody::Text(serde_json::to_string(body)?)),
+		timeout_seconds: 10,
+		follow_redirects: true,
+		verify_ssl: true,
+		retry: RetryPolicy::default(),
+		auth: None,
+		http_version: HttpVersion::Auto,
+	};
+	let response = http_client.send_request(request).await?;
+	if response.status >= 400 {
+		return Err(anyhow!("webhook answered HTTP {}", response.status));
+	}
+	info!("Notification delivered to {}", url);
+	Ok(())
+}
+
+/// The text as the message and the details as fields of a coloured attachment
+fn slack_message(notification: &Notification) -> Value {
+	let fields: Vec<Value> = notification.details.iter()
+		.filter(|(_, value)| !value.is_null())
+		.map(|(key, value)| {
+			let value = match value {
+				Value::String(text) => text.clone(),
+				Value::Array(items) => items.iter()
+					.map(|item| item.as_str().map_or_else(|| item.to_string(), str::to_string))
+					.collect::<Vec<_>>()
+					.join(", "),
+				other => other.to_string(),
+			};
+			json!({ "title": key, "value": value, "short": value.len() <= 40 })
+		})
+		.collect();
+	json!({
+		"text": notification.text,
+		"attachments": [{
+			"color": notification.severity.color(),
+			"fields": fields,
+			"footer": format!("Riddler · {}", notification.event),
+			"ts": notification.timestamp.timestamp(),
+		}],
+	})
+}
+
+/// Through the notification service of the platform (D-Bus on Linux, the
+/// notification center on macOS, toasts on Windows), which may block
+async fn show_desktop(notification: &Notification) {
+	let (summary, body) = (format!("Riddler: {}", notification.event), notification.text.clone());
+	let result = tokio::task::spawn_blocking(move || {
+		notify_rust::Notification::new()
+			.appname("Riddler")
+			.summary(&summary)
+			.body(&body)
+			.show()
+			.map(|_| ())
+	})
+	.await;
+	match result {
+		Ok(Ok(())) => {}
+		Ok(Err(e)) => warn!("Unable to show desktop notification: {}", e),
+		Err(e) => warn!("Desktop notification task failed: {}", e),
+	}
+}
@@ -0,0 +1,552 @@
+use crate::endpoints::{identifier_kind, is_uuid, IdentifierKind};
+use crate::logger::RequestLogEntry;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+/// What `logs --infer-openapi` found
+pub struct InferSummary {
+	pub entries: usize,
+	pub hosts: usize,
+	pub paths: usize,
+	pub operations: usize,
+}
+
+/// An OpenAPI 3.0 document; fields are declared in the order tools print them
+#[derive(Serialize)]
+struct Document {
+	openapi: &'static str,
+	info: Info,
+	servers: Vec<Server>,
+	paths: BTreeMap<String, PathItem>,
+}
+
+#[derive(Serialize)]
+struct Info {
+	title: String,
+	description: String,
+	version: &'static str,
+}
+
+#[derive(Serialize, Clone, PartialEq)]
+struct Server {
+	url: String,
+}
+
+#[derive(Serialize, Default)]
+struct PathItem {
+	/// Only when not every server of the document serves this path
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	servers: Vec<Server>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	parameters: Vec<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	get: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	put: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	post: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	delete: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	options: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	head: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	patch: Option<Operation>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	trace: Option<Operation>,
+}
+
+#[derive(Serialize)]
+struct Operation {
+	summary: String,
+	#[serde(rename = "operationId")]
+	operation_id: String,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	tags: Vec<String>,
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	parameters: Vec<Value>,
+	#[serde(rename = "requestBody", skip_serializing_if = "Option::is_none")]
+	request_body: Option<Value>,
+	responses: BTreeMap<String, Value>,
+	/// Number of logged requests the operation was inferred from
+	#[serde(rename = "x-riddler-samples")]
+	samples: usize,
+}
+
+/// Everything logged for one path template
+#[derive(Default)]
+struct ObservedPath {
+	/// Names and kinds of the `{...}` segments, in order
+	parameters: Vec<(String, IdentifierKind)>,
+	servers: BTreeSet<String>,
+	operations: BTreeMap<String, ObservedOperation>,
+}
+
+/// Everything logged for one method on one path template
+#[derive(Default)]
+struct ObservedOperation {
+	samples: usize,
+	servers: BTreeSet<String>,
+	/// Query parameter → in how many requests it occurred, and its values
+	query: BTreeMap<String, (usize, Schema)>,
+	/// Media type → schema of the bodies sent
+	request_bodies: BTreeMap<String, Schema>,
+	/// Status → media type (empty without a body) → schema of the bodies received
+	responses: BTreeMap<u16, BTreeMap<String, Schema>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+	Boolean,
+	Integer,
+	Number,
+	String,
+	Array,
+	Object,
+}
+
+/// A JSON schema merged from every value observed at one place
+#[derive(Default)]
+struct Schema {
+	kinds: BTreeSet<Kind>,
+	nullable: bool,
+	/// First value of each scalar kind, shown as the example
+	examples: BTreeMap<Kind, Value>,
+	/// Format shared by every string seen, `None` once they disagree
+	format: Option<&'static str>,
+	strings: usize,
+	/// Number of objects observed, and per property in how many it occurred
+	objects: usize,
+	properties: BTreeMap<String, (usize, Schema)>,
+	items: Option<Box<Schema>>,
+}
+
+/// Infer an OpenAPI document from `entries` and write it to `path`, as JSON
+/// for a `.json` file and YAML otherwise
+pub fn write(entries: &[RequestLogEntry], path: &Path) -> Result<InferSummary> {
+	let (document, summary) = infer(entries);
+	let content = if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+		serde_json::to_string_pretty(&document)?
+	} else {
+		serde_yaml::to_string(&document)?
+	};
+	std::fs::write(path, content).with_context(|| format!("Unable to write {}", path.display()))?;
+	Ok(summary)
+}
+
+fn infer(entries: &[RequestLogEntry]) -> (Document, InferSummary) {
+	let mut paths: BTreeMap<String, ObservedPath> = BTreeMap::new();
+	let mut servers = BTreeSet::new();
+	let mut used = 0;
+
+	for entry in entries {
+		let Ok(url) = url::Url::parse(&entry.request.url) else { continue };
+		let method = entry.request.method.to_lowercase();
+		if !matches!(url.scheme(), "http" | "https") || !is_operation_method(&method) {
+			continue;
+		}
+		used += 1;
+		let server = url[..url::Position::BeforePath].to_string();
+		servers.insert(server.clone());
+
+		let (template, parameters) = template_path(url.path());
+		let observed_path = paths.entry(template).or_default();
+		observed_path.parameters = parameters;
+		observed_path.servers.insert(server.clone());
+
+		let operation = observed_path.operations.entry(method).or_default();
+		operation.samples += 1;
+		operation.servers.insert(server);
+		for (name, value) in url.query_pairs() {
+			let (occurrences, schema) = operation.query.entry(name.into_owned()).or_default();
+			*occurrences += 1;
+			schema.observe(&scalar(&value));
+		}
+
+		let body = &entry.request.body_preview;
+		if !body.is_empty() {
+			let media_type = media_type(&entry.request.headers).unwrap_or_else(|| guess_media_type(body));
+			operation.request_bodies.entry(media_type.clone()).or_default().observe_body(&media_type, body);
+		}
+
+		if let Some(response) = &entry.response {
+			let bodies = operation.responses.entry(response.status).or_default();
+			if response.body.is_empty() {
+				bodies.entry(String::new()).or_default();
+			} else {
+				let media_type = media_type(&response.headers).unwrap_or_else(|| guess_media_type(&response.body));
+				bodies.entry(media_type.clone()).or_default().observe_body(&media_type, &response.body);
+			}
+		}
+	}
+
+	let several_servers = servers.len() > 1;
+	let mut operation_ids = BTreeSet::new();
+	let mut operations = 0;
+	let mut document_paths = BTreeMap::new();
+	for (template, observed) in paths {
+		let mut item = PathItem {
+			parameters: observed.parameters.iter().map(|(name, kind)| path_parameter(name, *kind)).collect(),
+			..Default::default()
+		};
+		if observed.servers.len() < servers.len() {
+			item.servers = observed.servers.iter().map(|url| Server { url: url.clone() }).collect();
+		}
+		for (method, observed_operation) in observed.operations {
+			let operation = observed_operation.render(&method, &template, several_servers, &mut operation_ids);
+			operations += 1;
+			let slot = match method.as_str() {
+				"get" => &mut item.get,
+				"put" => &mut item.put,
+				"post" => &mut item.post,
+				"delete" => &mut item.delete,
+				"options" => &mut item.options,
+				"head" => &mut item.head,
+				"patch" => &mut item.patch,
+				_ => &mut item.trace,
+			};
+			*slot = Some(operation);
+		}
+		document_paths.insert(template, item);
+	}
+
+	let title = match servers.iter().next() {
+		Some(server) if !several_servers => format!("{} (inferred)", server.split_once("://").map_or(server.as_str(), |(_, host)| host)),
+		_ => "Inferred API".to_string(),
+	};
+	let first = entries.iter().map(|entry| entry.timestamp).min();
+	let last = entries.iter().map(|entry| entry.timestamp).max();
+	let description = match (first, last) {
+		(Some(first), Some(last)) => format!(
+			"Inferred by Riddler from {} logged requests between {} and {}.",
+			used, first.format("%Y-%m-%d %H:%M:%S UTC"), last.format("%Y-%m-%d %H:%M:%S UTC")
+		),
+		_ => "Inferred by Riddler; no requests were logged.".to_string(),
+	};
+
+	let summary = InferSummary { entries: used, hosts: servers.len(), paths: document_paths.len(), operations };
+	let document = Document {
+		openapi: "3.0.3",
+		info: Info { title, description, version: "1.0.0" },
+		servers: servers.into_iter().map(|url| Server { url }).collect(),
+		paths: document_paths,
+	};
+	(document, summary)
+}
+
+impl ObservedOperation {
+	fn render(self, method: &str, template: &str, tag_by_server: bool, operation_ids: &mut BTreeSet<String>) -> Operation {
+		let parameters = self.query.iter()
+			.map(|(name, (occurrences, schema))| json!({
+				"name": name,
+				"in": "query",
+				"required": *occurrences == self.samples,
+				"schema": schema.render(),
+			}))
+			.collect();
+
+		let request_body = (!self.request_bodies.is_empty()).then(|| {
+			let content: Map<String, Value> = self.request_bodies.iter()
+				.map(|(media_type, schema)| (media_type.clone(), json!({ "schema": schema.render() })))
+				.collect();
+			json!({ "content": content })
+		});
+
+		let mut responses: BTreeMap<String, Value> = self.responses.iter()
+			.map(|(status, bodies)| {
+				let description = reqwest::StatusCode::from_u16(*status).ok()
+					.and_then(|status| status.canonical_reason())
+					.map_or_else(|| format!("Status {}", status), str::to_string);
+				let content: Map<String, Value> = bodies.iter()
+					.filter(|(media_type, _)| !media_type.is_empty())
+					.map(|(media_type, schema)| (media_type.clone(), json!({ "schema": schema.render() })))
+					.collect();
+				let response = if content.is_empty() {
+					json!({ "description": description })
+				} else {
+					json!({ "description": description, "content": content })
+				};
+				(status.to_string(), response)
+			})
+			.collect();
+		// A response is mandatory even when none was captured
+		if responses.is_empty() {
+			responses.insert("default".to_string(), json!({ "description": "No response was captured" }));
+		}
+
+		let mut operation_id = operation_id(method, template);
+		let base = operation_id.clone();
+		let mut n = 2;
+		while !operation_ids.insert(operation_id.clone()) {
+			operation_id = format!("{}{}", base, n);
+			n += 1;
+		}
+
+		Operation {
+			summary: format!("{} {}", method.to_uppercase(), template),
+			operation_id,
+			tags: if tag_by_server {
+				self.servers.iter().map(|server| server.split_once("://").map_or(server.as_str(), |(_, host)| host).to_string()).collect()
+			} else {
+				Vec::new()
+			},
+			parameters,
+			request_body,
+			responses,
+			samples: self.samples,
+		}
+	}
+}
+
+impl Schema {
+	fn observe(&mut self, value: &Value) {
+		let kind = match value {
+			Value::Null => {
+				self.nullable = true;
+				return;
+			}
+			Value::Bool(_) => Kind::Boolean,
+			Value::Number(number) if number.is_f64() => Kind::Number,
+			Value::Number(_) => Kind::Integer,
+			Value::String(string) => {
+				let format = string_format(string);
+				if self.strings == 0 {
+					self.format = format;
+				} else if self.format != format {
+					self.format = None;
+				}
+				self.strings += 1;
+				Kind::String
+			}
+			Value::Array(items) => {
+				let schema = self.items.get_or_insert_with(Default::default);
+				for item in items {
+					schema.observe(item);
+				}
+				Kind::Array
+			}
+			Value::Object(properties) => {
+				self.objects += 1;
+				for (name, value) in properties {
+					let (occurrences, schema) = self.properties.entry(name.clone()).or_default();
+					*occurrences += 1;
+					schema.observe(value);
+				}
+				Kind::Object
+			}
+		};
+		self.kinds.insert(kind);
+		if !matches!(kind, Kind::Array | Kind::Object) {
+			self.examples.entry(kind).or_insert_with(|| value.clone());
+		}
+	}
+
+	/// Observe a body of `media_type`: JSON and form fields are described, anything else is a string
+	fn observe_body(&mut self, media_type: &str, body: &str) {
+		if media_type.contains("json") {
+			// Bodies cut short in the log do not parse, and leave the schema open
+			if let Ok(value) = serde_json::from_str::<Value>(body) {
+				self.observe(&value);
+			}
+		} else if media_type == "application/x-www-form-urlencoded" {
+			let fields: Map<String, Value> = url::form_urlencoded::parse(body.as_bytes())
+				.map(|(name, value)| (name.into_owned(), scalar(&value)))
+				.collect();
+			self.observe(&Value::Object(fields));
+		} else {
+			self.kinds.insert(Kind::String);
+		}
+	}
+
+	fn render(&self) -> Value {
+		let mut kinds = self.kinds.clone();
+		if kinds.contains(&Kind::Number) {
+			kinds.remove(&Kind::Integer);
+		}
+		let mut alternatives: Vec<Value> = kinds.iter().map(|kind| self.render_kind(*kind)).collect();
+		let mut schema = match alternatives.len() {
+			0 => json!({}),
+			1 => alternatives.remove(0),
+			_ => json!({ "oneOf": alternatives }),
+		};
+		if self.nullable {
+			schema["nullable"] = json!(true);
+		}
+		schema
+	}
+
+	fn render_kind(&self, kind: Kind) -> Value {
+		let mut schema = Map::new();
+		let name = match kind {
+			Kind::Boolean => "boolean",
+			Kind::Integer => "integer",
+			Kind::Number => "number",
+			Kind::String => "string",
+			Kind::Array => "array",
+			Kind::Object => "object",
+		};
+		schema.insert("type".to_string(), json!(name));
+		match kind {
+			Kind::String => {
+				if let Some(format) = self.format {
+					schema.insert("format".to_string(), json!(format));
+				}
+			}
+			Kind::Array => {
+				let items = self.items.as_ref().map_or_else(|| json!({}), |items| items.render());
+				schema.insert("items".to_string(), items);
+			}
+			Kind::Object => {
+				if !self.properties.is_empty() {
+					let properties: Map<String, Value> = self.properties.iter()
+						.map(|(name, (_, schema))| (name.clone(), schema.render()))
+						.collect();
+					schema.insert("properties".to_string(), Value::Object(properties));
+				}
+				// Present in every object observed
+				let required: Vec<&String> = self.properties.iter()
+					.filter(|(_, (occurrences, _))| *occurrences == self.objects)
+					.map(|(name, _)| name)
+					.collect();
+				if !required.is_empty() {
+					schema.insert("required".to_string(), json!(required));
+				}
+			}
+			_ => {}
+		}
+		if let Some(example) = self.examples.get(&kind) {
+			schema.insert("example".to_string(), example.clone());
+		}
+		Value::Object(schema)
+	}
+}
+
+/// `/users/42/orders/7` → `/users/{userId}/orders/{orderId}`, naming each
+/// identifier after the collection before it
+fn template_path(path: &str) -> (String, Vec<(String, IdentifierKind)>) {
+	let mut parameters: Vec<(String, IdentifierKind)> = Vec::new();
+	let mut previous: Option<&str> = None;
+	let segments: Vec<String> = path.split('/')
+		.map(|segment| {
+			let Some(kind) = identifier_kind(segment) else {
+				if !segment.is_empty() {
+					previous = Some(segment);
+				}
+				return segment.to_string();
+			};
+			let base = match previous.map(|collection| camel_case(&singular(collection))) {
+				Some(base) if !base.is_empty() => format!("{}Id", base),
+				_ => "id".to_string(),
+			};
+			let mut name = base.clone();
+			let mut n = 2;
+			while parameters.iter().any(|(existing, _)| *existing == name) {
+				name = format!("{}{}", base, n);
+				n += 1;
+			}
+			parameters.push((name.clone(), kind));
+			format!("{{{}}}", name)
+		})
+		.collect();
+	(segments.join("/"), parameters)
+}
+
+fn path_parameter(name: &str, kind: IdentifierKind) -> Value {
+	let schema = match kind {
+		IdentifierKind::Number => json!({ "type": "integer" }),
+		IdentifierKind::Uuid => json!({ "type": "string", "format": "uuid" }),
+		IdentifierKind::Hex => json!({ "type": "string", "pattern": "^[0-9a-fA-F]+$" }),
+	};
+	json!({ "name": name, "in": "path", "required": true, "schema": schema })
+}
+
+/// `get` + `/users/{userId}/orders` → `getUsersByUserIdOrders`
+fn operation_id(method: &str, template: &str) -> String {
+	let mut id = method.to_string();
+	for segment in template.split('/').filter(|segment| !segment.is_empty()) {
+		match segment.strip_prefix('{').and_then(|segment| segment.strip_suffix('}')) {
+			Some(parameter) => {
+				id.push_str("By");
+				id.push_str(&capitalize(parameter));
+			}
+			None => id.push_str(&capitalize(&camel_case(segment))),
+		}
+	}
+	id
+}
+
+fn singular(word: &str) -> String {
+	if let Some(stem) = word.strip_suffix("ies") {
+		format!("{}y", stem)
+	} else if word.ends_with('s') && !word.ends_with("ss") {
+		word[..word.len() - 1].to_string()
+	} else {
+		word.to_string()
+	}
+}
+
+/// `order-items` → `orderItems`
+fn camel_case(word: &str) -> String {
+	let mut parts = word.split(|c: char| !c.is_ascii_alphanumeric()).filter(|part| !part.is_empty());
+	let mut result = parts.next().map(|part| part.to_lowercase()).unwrap_or_default();
+	for part in parts {
+		result.push_str(&capitalize(&part.to_lowercase()));
+	}
+	result
+}
+
+fn capitalize(word: &str) -> String {
+	let mut chars = word.chars();
+	chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+}
+
+fn is_operation_method(method: &str) -> bool {
+	matches!(method, "get" | "put" | "post" | "delete" | "options" | "head" | "patch" | "trace")
+}
+
+/// The media type of `Content-Type` in `headers`, without parameters
+fn media_type(headers: &HashMap<String, String>) -> Option<String> {
+	headers.iter()
+		.find(|(name, _)| name.eq_ignore_ascii_case("content-type"))
+		.map(|(_, value)| value.split(';').next().unwrap_or("").trim().to_lowercase())
+		.filter(|media_type| !media_type.is_empty())
+}
+
+fn guess_media_type(body: &str) -> String {
+	if serde_json::from_str::<Value>(body).is_ok() { "application/json" } else { "text/plain" }.to_string()
+}
+
+/// A query or form value as the JSON scalar it looks like
+fn scalar(value: &str) -> Value {
+	if let Ok(integer) = value.parse::<i64>() {
+		json!(integer)
+	} else if let Ok(number) = value.parse::<f64>()
+		&& number.is_finite()
+	{
+		json!(number)
+	} else if value == "true" || value == "false" {
+		json!(value == "true")
+	} else {
+		json!(value)
+	}
+}
+
+fn string_format(value: &str) -> Option<&'static str> {
+	if chrono::DateTime::parse_from_rfc3339(value).is_ok() {
+		Some("date-time")
+	} else if chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+		Some("date")
+	} else if is_uuid(value) {
+		Some("uuid")
+	} else if value.starts_with("http://") || value.starts_with("https://") {
+		Some("uri")
+	} else if value.split_once('@').is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.') && !value.contains(' ')) {
+		Some("email")
+	} else {
+		None
+	}
+}
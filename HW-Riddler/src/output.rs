@@ -0,0 +1,40 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the global `--json` flag
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(enabled: bool) {
+	JSON.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether commands print JSON on stdout and their human-readable text on stderr
+pub fn json() -> bool {
+	JSON.load(Ordering::Relaxed)
+}
+
+/// Print `value` as the JSON result of the command on stdout
+pub fn emit<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+	println!("{}", serde_json::to_string_pretty(value)?);
+	Ok(())
+}
+
+/// Print `value` as one line of a stream of JSON objects on stdout
+pub fn emit_line<T: Serialize + ?Sized>(value: &T) -> Result<()> {
+	println!("{}", serde_json::to_string(value)?);
+	Ok(())
+}
+
+/// `println!` for human-readable output: on stdout normally, on stderr with
+/// `--json` so that stdout only carries JSON
+#[macro_export]
+macro_rules! say {
+	($($arg:tt)*) => {
+		if $crate::output::json() {
+			eprintln!($($arg)*);
+		} else {
+			println!($($arg)*);
+		}
+	};
+}
@@ -0,0 +1,92 @@
+/// The proxy auto-config file `proxy --serve-pac` answers at `/proxy.pac`
+pub struct PacFile {
+	/// `host:port` of the proxy when the request for the file does not say
+	/// how the client reached it
+	default_proxy: String,
+	/// Only hosts matching these globs go through the proxy; all when empty
+	hosts: Vec<String>,
+}
+
+/// Path the PAC file is served at
+pub const PAC_PATH: &str = "/proxy.pac";
+
+impl PacFile {
+	pub fn new(default_proxy: String, hosts: Vec<String>) -> Self {
+		Self { default_proxy, hosts }
+	}
+
+	/// The PAC script, pointing at `reached_as` (the Host the client fetched
+	/// the file from) so that a proxy bound to 0.0.0.0 is named by an address
+	/// the client can actually use
+	pub fn render(&self, reached_as: Option<&str>) -> String {
+		// The Host header is client input and lands inside a JS string literal
+		let proxy = reached_as.filter(|host| is_authority(host)).unwrap_or(&self.default_proxy);
+		let mut script = String::from("function FindProxyForURL(url, host) {\n");
+		script.push_str("\tif (isPlainHostName(host) || host == \"localhost\" || shExpMatch(host, \"127.*\") || host == \"[::1]\")\n");
+		script.push_str("\t\treturn \"DIRECT\";\n");
+		if !self.hosts.is_empty() {
+			let conditions: Vec<String> = self.hosts.iter()
+				.map(|pattern| format!("shExpMatch(host, \"{}\")", pattern.replace('\\', "\\\\").replace('"', "\\\"")))
+				.collect();
+			script.push_str(&format!("\tif (!({}))\n", conditions.join(" || ")));
+			script.push_str("\t\treturn \"DIRECT\";\n");
+		}
+		// Falling back to DIRECT keeps clients online when the proxy is stopped
+		script.push_str(&format!("\treturn \"PROXY {}; DIRECT\";\n}}\n", proxy));
+		script
+	}
+
+	/// The raw HTTP response carrying the script
+	pub fn response(&self, reached_as: Option<&str>) -> Vec<u8> {
+		let script = self.render(reached_as);
+		format!(
+			"HTTP/1.1 200 OK\r\nContent-Type: application/x-ns-proxy-autoconfig\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n{}",
+			script.len(), script
+		).into_bytes()
+	}
+}
+
+/// Whether `value` is a bare `host[:port]`: a DNS name, an IPv4 address or a
+/// bracketed IPv6 address, optionally followed by a numeric port
+fn is_authority(value: &str) -> bool {
+	let port_ok = |port: Option<&str>| port.is_none_or(|port| port.parse::<u16>().is_ok());
+	if let Some(rest) = value.strip_prefix('[') {
+		let Some((ip, after)) = rest.split_once(']') else { return false };
+		return ip.parse::<std::net::Ipv6Addr>().is_ok()
+			&& (after.is_empty() || after.starts_with(':'))
+			&& port_ok(after.strip_prefix(':'));
+	}
+	let (host, port) = match value.rsplit_once(':') {
+		Some((host, port)) => (host, Some(port)),
+		None => (value, None),
+	};
+	!host.is_empty()
+		&& host.len() <= 253
+		&& host.split('.').all(|label| !label.is_empty() && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-'))
+		&& port_ok(port)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn pac() -> PacFile {
+		PacFile::new("10.0.0.1:8080".into(), Vec::new())
+	}
+
+	#[test]
+	fn names_the_host_the_file_was_fetched_from() {
+		assert!(pac().render(Some("proxy.lan:8080")).contains("\"PROXY proxy.lan:8080; DIRECT\""));
+		assert!(pac().render(Some("[::1]:8080")).contains("\"PROXY [::1]:8080; DIRECT\""));
+	}
+
+	#[test]
+	fn falls_back_when_the_host_header_is_not_an_authority() {
+		let script = pac().render(Some("evil\"; alert(1); \"x:80"));
+		assert!(script.contains("\"PROXY 10.0.0.1:8080; DIRECT\""));
+		assert!(!script.contains("alert"));
+		for host in ["", "a:b", "host:99999", "[::1", "[::1]x", "a..b"] {
+			assert!(pac().render(Some(host)).contains("10.0.0.1:8080"), "{host}");
+		}
+	}
+}
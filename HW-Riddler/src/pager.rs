@@ -0,0 +1,199 @@
+use crate::logger::RequestLogEntry;
+use anyhow::Result;
+use crossterm::{
+	cursor::{Hide, MoveTo, Show},
+	event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+	execute, queue,
+	style::{Attribute, Print, SetAttribute},
+	terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{Write, stdout};
+
+enum View {
+	List,
+	Detail { scroll: usize },
+}
+
+/// Full-screen log browser: j/k to move, Enter to expand an entry, q to quit
+pub struct LogPager {
+	entries: Vec<RequestLogEntry>,
+	selected: usize,
+	top: usize,
+	view: View,
+}
+
+impl LogPager {
+	pub fn new(entries: Vec<RequestLogEntry>) -> Self {
+		let selected = entries.len().saturating_sub(1);
+		Self {
+			entries,
+			selected,
+			top: 0,
+			view: View::List,
+		}
+	}
+
+	/// Take over the terminal until the user quits. The terminal is restored
+	/// even if drawing fails half way.
+	pub fn run(mut self) -> Result<()> {
+		terminal::enable_raw_mode()?;
+		execute!(stdout(), EnterAlternateScreen, Hide)?;
+
+		let result = self.event_loop();
+
+		execute!(stdout(), Show, LeaveAlternateScreen)?;
+		terminal::disable_raw_mode()?;
+		result
+	}
+
+	fn event_loop(&mut self) -> Result<()> {
+		loop {
+			let (width, height) = terminal::size()?;
+			let body_height = (height as usize).saturating_sub(2).max(1);
+			self.draw(width as usize, body_height)?;
+
+			let Event::Key(KeyEvent { code, modifiers, kind, .. }) = event::read()? else {
+				continue;
+			};
+			if kind != KeyEventKind::Press {
+				continue;
+			}
+			if code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL) {
+				return Ok(());
+			}
+
+			match self.view {
+				View::List => match code {
+					KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+					KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+					KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+					KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown => self.move_selection(body_height as isize),
+					KeyCode::Char('b') | KeyCode::PageUp => self.move_selection(-(body_height as isize)),
+					KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+					KeyCode::Char('G') | KeyCode::End => self.selected = self.entries.len().saturating_sub(1),
+					KeyCode::Enter if !self.entries.is_empty() => self.view = View::Detail { scroll: 0 },
+					_ => {}
+				},
+				View::Detail { ref mut scroll } => match code {
+					KeyCode::Char('q') | KeyCode::Esc | KeyCode::Enter | KeyCode::Backspace => self.view = View::List,
+					KeyCode::Char('j') | KeyCode::Down => *scroll += 1,
+					KeyCode::Char('k') | KeyCode::Up => *scroll = scroll.saturating_sub(1),
+					KeyCode::Char(' ') | KeyCode::PageDown => *scroll += body_height,
+					KeyCode::Char('b') | KeyCode::PageUp => *scroll = scroll.saturating_sub(body_height),
+					_ => {}
+				},
+			}
+		}
+	}
+
+	fn move_selection(&mut self, delta: isize) {
+		let last = self.entries.len().saturating_sub(1) as isize;
+		self.selected = (self.selected as isize + delta).clamp(0, last.max(0)) as usize;
+	}
+
+	fn draw(&mut self, width: usize, body_height: usize) -> Result<()> {
+		let mut out = stdout();
+		queue!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+
+		let (title, lines, highlighted) = match self.view {
+			View::List => {
+				// Keep the selection inside the visible window
+				if self.selected < self.top {
+					self.top = self.selected;
+				} else if self.selected >= self.top + body_height {
+					self.top = self.selected + 1 - body_height;
+				}
+
+				let lines: Vec<String> = self.entries[self.top..]
+					.iter()
+					.take(body_height)
+					.map(summary_line)
+					.collect();
+				let title = format!(
+					"Riddler logs — {}/{}   j/k move  space/b page  enter expand  q quit",
+					if self.entries.is_empty() { 0 } else { self.selected + 1 },
+					self.entries.len()
+				);
+				(title, lines, Some(self.selected - self.top))
+			}
+			View::Detail { ref mut scroll } => {
+				let all = detail_lines(&self.entries[self.selected]);
+				*scroll = (*scroll).min(all.len().saturating_sub(body_height));
+				let lines = all.iter().skip(*scroll).take(body_height).cloned().collect();
+				let title = format!(
+					"Entry {}/{}   j/k scroll  enter/q back",
+					self.selected + 1,
+					self.entries.len()
+				);
+				(title, lines, None)
+			}
+		};
+
+		queue!(out, SetAttribute(Attribute::Reverse), Print(truncate(&title, width)), SetAttribute(Attribute::Reset), Print("\r\n"))?;
+		for (i, line) in lines.iter().enumerate() {
+			if highlighted == Some(i) {
+				queue!(out, SetAttribute(Attribute::Bold), Print("> "), Print(truncate(line, width.saturating_sub(2))), SetAttribute(Attribute::Reset))?;
+			} else {
+				let indent = if highlighted.is_some() { "  " } else { "" };
+				queue!(out, Print(indent), Print(truncate(line, width.saturating_sub(indent.len()))))?;
+			}
+			queue!(out, Print("\r\n"))?;
+		}
+
+		out.flush()?;
+		Ok(())
+	}
+}
+
+fn summary_line(entry: &RequestLogEntry) -> String {
+	let status = entry.response.as_ref().map_or_else(
+		|| "-".to_string(),
+		|r| format!("{} {}ms", r.status, r.response_time_ms),
+	);
+	format!(
+		"{} [{}] {} {} -> {}",
+		entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+		entry.source,
+		entry.request.method,
+		entry.request.url,
+		status
+	)
+}
+
+fn detail_lines(entry: &RequestLogEntry) -> Vec<String> {
+	let mut lines = vec![
+		format!("=== {} [{}] ===", entry.timestamp, entry.source),
+		format!("{} {}", entry.request.method, entry.request.url),
+		format!("Client: {}:{}", entry.request.source_ip, entry.request.source_port),
+		String::new(),
+		"Request Headers:".to_string(),
+	];
+
+	let mut headers: Vec<_> = entry.request.headers.iter().collect();
+	headers.sort();
+	lines.extend(headers.into_iter().map(|(k, v)| format!("  {}: {}", k, v)));
+
+	if !entry.request.body_preview.is_empty() {
+		lines.push(String::new());
+		lines.push("Request Body:".to_string());
+		lines.extend(entry.request.body_preview.lines().map(str::to_string));
+	}
+
+	if let Some(ref response) = entry.response {
+		lines.push(String::new());
+		lines.push(format!("Response: {} ({}ms) {}", response.status, response.response_time_ms, response.final_url));
+		lines.push("Response Headers:".to_string());
+		let mut headers: Vec<_> = response.headers.iter().collect();
+		headers.sort();
+		lines.extend(headers.into_iter().map(|(k, v)| format!("  {}: {}", k, v)));
+		lines.push(String::new());
+		lines.push("Response Body:".to_string());
+		lines.extend(response.body.lines().map(str::to_string));
+	}
+
+	lines
+}
+
+fn truncate(line: &str, width: usize) -> String {
+	line.chars().take(width).collect()
+}
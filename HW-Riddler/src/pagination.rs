@@ -0,0 +1,131 @@
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo};
+use crate::jsonpath::{self, JsonPath};
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashSet;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub struct PaginationOptions {
+	/// Response header carrying RFC 5988 links, usually `Link`
+	pub next_link_header: String,
+	/// JSONPath of a next-page URL inside the body, tried when the header has no `rel="next"`
+	pub next_field: Option<String>,
+	pub max_pages: usize,
+}
+
+pub struct Page {
+	pub request: HttpRequestBuilder,
+	pub response: HttpResponseInfo,
+}
+
+/// Fetch `first` and keep following next-page links until there are none,
+/// a page fails, a URL repeats, or `max_pages` is reached.
+pub async fn fetch_all(
+	http_client: &HttpClient,
+	first: HttpRequestBuilder,
+	options: &PaginationOptions,
+	on_page: &mut dyn FnMut(usize, &Page),
+) -> Result<Vec<Page>> {
+	let next_field = options.next_field.as_deref().map(JsonPath::parse).transpose()?;
+	let mut pages = Vec::new();
+	let mut visited = HashSet::new();
+	let mut request = first;
+
+	loop {
+		visited.insert(request.url.clone());
+		let response = http_client.send_request(request.clone()).await?;
+		let success = (200..300).contains(&response.status);
+		let page = Page { request, response };
+		on_page(pages.len() + 1, &page);
+
+		let next = if success {
+			next_url(&page, options, next_field.as_ref())
+		} else {
+			warn!("Stopping pagination: page {} returned {}", pages.len() + 1, page.response.status);
+			None
+		};
+		let template = page.request.clone();
+		pages.push(page);
+
+		let Some(next) = next else {
+			break;
+		};
+		if pages.len() >= options.max_pages {
+			info!("Stopping pagination after --max-pages {}", options.max_pages);
+			break;
+		}
+		if visited.contains(&next) {
+			warn!("Stopping pagination: next page {} was already fetched", next);
+			break;
+		}
+
+		request = HttpRequestBuilder { url: next, ..template };
+	}
+
+	Ok(pages)
+}
+
+fn next_url(page: &Page, options: &PaginationOptions, next_field: Option<&JsonPath>) -> Option<String> {
+	let from_header = page.response.headers
+		.iter()
+		.find(|(k, _)| k.eq_ignore_ascii_case(&options.next_link_header))
+		.and_then(|(_, v)| next_from_link_header(v));
+
+	let next = from_header.or_else(|| {
+		let path = next_field?;
+		let body: Value = serde_json::from_str(&page.response.body).ok()?;
+		path.select(&body)
+			.into_iter()
+			.find(|v| !v.is_null())
+			.map(jsonpath::value_to_string)
+			.filter(|s| !s.is_empty())
+	})?;
+
+	// Links may be relative to the page they came from
+	url::Url::parse(&page.response.final_url)
+		.and_then(|base| base.join(&next))
+		.map(|u| u.to_string())
+		.ok()
+}
+
+/// Extract the `rel="next"` target from an RFC 5988 `Link` header value, e.g.
+/// `<https://api.example.com/items?page=2>; rel="next", <...>; rel="last"`
+pub fn next_from_link_header(value: &str) -> Option<String> {
+	value.split(',').find_map(|link| {
+		let mut parts = link.split(';');
+		let target = parts.next()?.trim().strip_prefix('<')?.strip_suffix('>')?;
+		let is_next = parts.any(|param| {
+			let param = param.trim();
+			param.split_once('=').is_some_and(|(name, rel)| {
+				name.trim().eq_ignore_ascii_case("rel")
+					&& rel.trim().trim_matches('"').split_whitespace().any(|r| r.eq_ignore_ascii_case("next"))
+			})
+		});
+		is_next.then(|| target.to_string())
+	})
+}
+
+/// Combine page bodies: JSON arrays are concatenated, other JSON pages are
+/// collected into an array, and non-JSON bodies are joined with newlines.
+pub fn aggregate_bodies(pages: &[Page]) -> String {
+	let parsed: Option<Vec<Value>> = pages
+		.iter()
+		.map(|p| serde_json::from_str::<Value>(&p.response.body).ok())
+		.collect();
+
+	match parsed {
+		Some(values) if values.iter().all(Value::is_array) => {
+			let items: Vec<Value> = values
+				.into_iter()
+				.flat_map(|v| match v {
+					Value::Array(items) => items,
+					_ => Vec::new(),
+				})
+				.collect();
+			Value::Array(items).to_string()
+		}
+		Some(values) => Value::Array(values).to_string(),
+		None => pages.iter().map(|p| p.response.body.as_str()).collect::<Vec<_>>().join("\n"),
+	}
+}
@@ -1,17 +1,28 @@
-use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo};
-use anyhow::Result;
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo, HttpVersion, RetryPolicy};
+use crate::stats::percentile;
+use crate::tls_inspector;
+use crate::traceroute::{self, Hop};
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
 use tracing::{info, warn};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
+	/// Connection setup measured on a fresh connection of its own; `None` when
+	/// the phase does not apply (IP literal, plain HTTP) or could not be measured
 	pub dns_resolution_ms: Option<u64>,
 	pub tcp_connect_ms: Option<u64>,
 	pub tls_handshake_ms: Option<u64>,
-	pub request_send_ms: u64,
+	/// The request opened a connection of its own instead of taking one from
+	/// the pool, so `first_byte_ms` includes the setup above
+	#[serde(default)]
+	pub fresh_connection: bool,
+	/// From sending the request until the response headers arrived
 	pub first_byte_ms: u64,
 	pub response_download_ms: u64,
 	pub total_time_ms: u64,
@@ -29,10 +40,164 @@ pub struct NetworkConditions {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PerformanceAnalysis {
 	pub url: String,
+	/// Protocol the response arrived over, e.g. `HTTP/2.0`
+	#[serde(default)]
+	pub protocol: String,
+	#[serde(default)]
+	pub status: u16,
 	pub metrics: PerformanceMetrics,
 	pub analysis: String,
 	pub recommendations: Vec<String>,
 	pub severity: PerformanceSeverity,
+	/// Network path to the host, traced for critical responses when enabled
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub route: Vec<Hop>,
+}
+
+/// Response time distribution over all iterations of a test
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LatencyStats {
+	pub count: usize,
+	pub min_ms: u64,
+	pub max_ms: u64,
+	pub mean_ms: f64,
+	/// Population standard deviation
+	pub std_dev_ms: f64,
+	pub p50_ms: u64,
+	pub p90_ms: u64,
+	pub p95_ms: u64,
+	pub p99_ms: u64,
+}
+
+impl LatencyStats {
+	pub fn from_samples(samples: &[u64]) -> Option<Self> {
+		if samples.is_empty() {
+			return None;
+		}
+		let mut sorted = samples.to_vec();
+		sorted.sort_unstable();
+
+		let count = sorted.len();
+		let mean = sorted.iter().sum::<u64>() as f64 / count as f64;
+		let variance = sorted.iter().map(|&ms| (ms as f64 - mean).powi(2)).sum::<f64>() / count as f64;
+
+		Some(Self {
+			count,
+			min_ms: sorted[0],
+			max_ms: sorted[count - 1],
+			mean_ms: mean,
+			std_dev_ms: variance.sqrt(),
+			p50_ms: percentile(&sorted, 0.50),
+			p90_ms: percentile(&sorted, 0.90),
+			p95_ms: percentile(&sorted, 0.95),
+			p99_ms: percentile(&sorted, 0.99),
+		})
+	}
+}
+
+/// Outcome of `analyze --duration`: requests sent back to back by
+/// `concurrency` workers for a fixed time window
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoadTestResult {
+	pub url: String,
+	pub concurrency: usize,
+	pub elapsed_ms: u64,
+	pub total_requests: usize,
+	pub requests_per_second: f64,
+	/// Responses per status code
+	pub status_counts: BTreeMap<u16, usize>,
+	/// Requests that got no response, per error message
+	pub errors: BTreeMap<String, usize>,
+	/// Over all requests that got a response
+	pub latency: Option<LatencyStats>,
+	#[serde(skip)]
+	pub latencies_ms: Vec<u64>,
+}
+
+impl LoadTestResult {
+	/// Requests without a response or with a 4xx/5xx status
+	pub fn error_count(&self) -> usize {
+		self.errors.values().sum::<usize>()
+			+ self.status_counts.iter().filter(|(status, _)| **status >= 400).map(|(_, count)| count).sum::<usize>()
+	}
+}
+
+/// One target's row in the comparison of `analyze --url a --url b`
+#[derive(Debug, Clone, Serialize)]
+pub struct TargetSummary {
+	pub url: String,
+	pub requests: usize,
+	/// Requests without a response or with a 4xx/5xx status
+	pub errors: usize,
+	pub latency: Option<LatencyStats>,
+}
+
+impl TargetSummary {
+	/// `iterations` requests were attempted; `analyses` holds those that got a response
+	pub fn from_analyses(url: &str, iterations: u32, analyses: &[PerformanceAnalysis]) -> Self {
+		let failed = (iterations as usize).saturating_sub(analyses.len());
+		let times: Vec<u64> = analyses.iter().map(|a| a.metrics.total_time_ms).collect();
+		Self {
+			url: url.to_string(),
+			requests: iterations as usize,
+			errors: failed + analyses.iter().filter(|a| a.status >= 400).count(),
+			latency: LatencyStats::from_samples(&times),
+		}
+	}
+
+	pub fn from_load_test(result: &LoadTestResult) -> Self {
+		Self {
+			url: result.url.clone(),
+			requests: result.total_requests,
+			errors: result.error_count(),
+			latency: result.latency.clone(),
+		}
+	}
+
+	pub fn error_rate(&self) -> f64 {
+		self.errors as f64 * 100.0 / self.requests.max(1) as f64
+	}
+}
+
+/// Targets ranked by p95 latency, then error rate; targets without any
+/// response come last
+pub fn comparison_table(summaries: &[TargetSummary]) -> String {
+	let mut ranked: Vec<&TargetSummary> = summaries.iter().collect();
+	ranked.sort_by(|a, b| {
+		let p95 = |s: &TargetSummary| s.latency.as_ref().map_or(u64::MAX, |l| l.p95_ms);
+		p95(a).cmp(&p95(b)).then(a.error_rate().total_cmp(&b.error_rate()))
+	});
+
+	let ms = |value: Option<u64>| value.map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+	let url_width = ranked.iter().map(|s| s.url.len()).max().unwrap_or(3).clamp(3, 60);
+
+	let mut table = String::new();
+	table.push_str("=== TARGET COMPARISON (by p95) ===\n\n");
+	table.push_str(&format!(
+		"{:<4} {:<url_width$} {:>8} {:>8} {:>8} {:>8} {:>8}\n",
+		"#", "URL", "p50", "p95", "p99", "mean", "errors"
+	));
+	for (rank, summary) in ranked.iter().enumerate() {
+		let latency = summary.latency.as_ref();
+		table.push_str(&format!(
+			"{:<4} {:<url_width$} {:>8} {:>8} {:>8} {:>8} {:>7.1}%\n",
+			rank + 1,
+			summary.url,
+			ms(latency.map(|l| l.p50_ms)),
+			ms(latency.map(|l| l.p95_ms)),
+			ms(latency.map(|l| l.p99_ms)),
+			ms(latency.map(|l| l.mean_ms.round() as u64)),
+			summary.error_rate()
+		));
+	}
+	table
+}
+
+/// What `analyze --report` writes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerformanceReport {
+	pub summary: Option<LatencyStats>,
+	pub analyses: Vec<PerformanceAnalysis>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,37 +211,90 @@ pub enum PerformanceSeverity {
 
 pub struct PerformanceAnalyzer {
 	http_client: Arc<HttpClient>,
+	/// Trace the route to hosts whose responses are critically slow
+	traceroute: bool,
 }
 
 impl PerformanceAnalyzer {
 	pub fn new(http_client: Arc<HttpClient>) -> Self {
-		Self { http_client }
+		Self { http_client, traceroute: false }
+	}
+
+	/// An analyzer whose requests each open a new connection
+	pub fn with_fresh_connections(&self) -> Self {
+		Self { http_client: Arc::new(self.http_client.with_fresh_connections()), traceroute: self.traceroute }
+	}
+
+	pub fn with_traceroute(mut self, enabled: bool) -> Self {
+		self.traceroute = enabled;
+		self
+	}
+
+	/// Send `count` unmeasured requests so that DNS caches, the connection pool
+	/// and server-side caches are warm before the test starts
+	pub async fn warm_up(&self, url: &str, count: u32, verify_ssl: bool, http_version: HttpVersion) {
+		info!("Sending {} warmup request(s) to {}", count, url);
+		for i in 1..=count {
+			let request = probe_request(url, verify_ssl, http_version);
+			if let Err(e) = self.http_client.send_request(request).await {
+				warn!("Warmup request {} failed: {}", i, e);
+			}
+		}
 	}
 
 	pub async fn analyze_request(&self, request: &HttpRequestBuilder) -> Result<PerformanceAnalysis> {
 		info!("Starting performance analysis for: {}", request.url);
 
-		let overall_start = Instant::now();
+		let timeout = Duration::from_secs(request.timeout_seconds.max(1));
+		let phases = match measure_connection_phases(&request.url, timeout).await {
+			Ok(phases) => phases,
+			Err(e) => {
+				warn!("Unable to measure connection setup for {}: {}", request.url, e);
+				ConnectionPhases::default()
+			}
+		};
 
-		let response = self.http_client.send_request(request.clone()).await?;
+		let overall_start = Instant::now();
+		let mut streaming = self.http_client.send_streaming(request).await?;
+		let first_byte_ms = overall_start.elapsed().as_millis() as u64;
+
+		let mut response = HttpResponseInfo::from_head(&streaming, 0);
+		let mut body = Vec::new();
+		while let Some(chunk) = tokio::time::timeout(timeout, streaming.chunk())
+			.await
+			.map_err(|_| anyhow!("Timed out reading response body"))??
+		{
+			body.extend_from_slice(&chunk);
+		}
+		response.body = String::from_utf8_lossy(&body).into_owned();
 
 		let total_time = overall_start.elapsed().as_millis() as u64;
+		response.response_time_ms = total_time;
 
-		let metrics = self.build_metrics(&response, total_time);
+		let metrics = self.build_metrics(&response, total_time, first_byte_ms, &phases);
 		let analysis = self.generate_analysis(&metrics, &response);
 		let recommendations = self.generate_recommendations(&metrics);
 		let severity = self.determine_severity(total_time);
 
 		Ok(PerformanceAnalysis {
 			url: request.url.clone(),
+			protocol: response.http_version.clone(),
+			status: response.status,
 			metrics,
 			analysis,
 			recommendations,
 			severity,
+			route: Vec::new(),
 		})
 	}
 
-	fn build_metrics(&self, response: &HttpResponseInfo, total_time: u64) -> PerformanceMetrics {
+	fn build_metrics(
+		&self,
+		response: &HttpResponseInfo,
+		total_time: u64,
+		first_byte_ms: u64,
+		phases: &ConnectionPhases,
+	) -> PerformanceMetrics {
 		let response_size = response.body.len();
 
 		let estimated_bandwidth = if total_time > 0 && response_size > 0 {
@@ -107,22 +325,23 @@ impl PerformanceAnalyzer {
 			latency_factors.push("Large response payload".to_string());
 		}
 
-		let (dns_time, tcp_time, tls_time) = estimate_connection_times(total_time, response.final_url.starts_with("https"));
-
-		let first_byte_time = if total_time > 0 {
-			total_time / 3
-		} else {
-			0
-		};
-		let download_time = total_time.saturating_sub(first_byte_time);
+		if let Some(dns) = phases.dns_ms.filter(|&ms| ms > 100) {
+			latency_factors.push(format!("Slow DNS resolution ({}ms)", dns));
+		}
+		if let Some(tcp) = phases.tcp_ms.filter(|&ms| ms > 150) {
+			latency_factors.push(format!("High network round-trip time (TCP connect {}ms)", tcp));
+		}
+		if let Some(tls) = phases.tls_ms.filter(|&ms| ms > 300) {
+			latency_factors.push(format!("Slow TLS handshake ({}ms)", tls));
+		}
 
 		PerformanceMetrics {
-			dns_resolution_ms: dns_time,
-			tcp_connect_ms: tcp_time,
-			tls_handshake_ms: tls_time,
-			request_send_ms: total_time.min(50),
-			first_byte_ms: first_byte_time,
-			response_download_ms: download_time,
+			dns_resolution_ms: phases.dns_ms,
+			tcp_connect_ms: phases.tcp_ms,
+			tls_handshake_ms: phases.tls_ms,
+			fresh_connection: !self.http_client.reuses_connections(),
+			first_byte_ms,
+			response_download_ms: total_time.saturating_sub(first_byte_ms),
 			total_time_ms: total_time,
 			response_size_bytes: response_size,
 			network_conditions: NetworkConditions {
@@ -157,6 +376,32 @@ impl PerformanceAnalyzer {
 			));
 		}
 
+		analysis.push_str(&format!(
+			"• Time to First Byte: {}ms, Download: {}ms\n",
+			metrics.first_byte_ms,
+			metrics.response_download_ms
+		));
+		let phase = |ms: Option<u64>| ms.map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+		analysis.push_str(&format!(
+			"• New Connection Setup: DNS {}, TCP {}, TLS {}\n",
+			phase(metrics.dns_resolution_ms),
+			phase(metrics.tcp_connect_ms),
+			phase(metrics.tls_handshake_ms)
+		));
+		let setup_ms: u64 = [metrics.dns_resolution_ms, metrics.tcp_connect_ms, metrics.tls_handshake_ms]
+			.iter()
+			.flatten()
+			.sum();
+		if metrics.fresh_connection {
+			analysis.push_str(&format!(
+				"• Fresh connection: ~{}ms of the TTFB is setup, ~{}ms server processing\n",
+				setup_ms.min(metrics.first_byte_ms),
+				metrics.first_byte_ms.saturating_sub(setup_ms)
+			));
+		} else {
+			analysis.push_str("• Pooled connection: once warm, TTFB is server processing plus one round trip\n");
+		}
+
 		if metrics.total_time_ms > 6000 {
 			analysis.push_str("\n⚠️  CRITICAL PERFORMANCE ISSUE DETECTED:\n");
 			analysis.push_str("Response time exceeds 6 seconds, indicating severe performance problems.\n");
@@ -225,7 +470,13 @@ impl PerformanceAnalyzer {
 		}
 	}
 
-	pub async fn run_performance_test(&self, url: &str, iterations: u32) -> Result<Vec<PerformanceAnalysis>> {
+	pub async fn run_performance_test(
+		&self,
+		url: &str,
+		iterations: u32,
+		verify_ssl: bool,
+		http_version: HttpVersion,
+	) -> Result<Vec<PerformanceAnalysis>> {
 		let mut results = Vec::new();
 
 		info!("Running performance test with {} iterations for: {}", iterations, url);
@@ -233,16 +484,7 @@ impl PerformanceAnalyzer {
 		for i in 1..=iterations {
 			info!("Iteration {}/{}", i, iterations);
 
-			let request = HttpRequestBuilder {
-				method: "GET".to_string(),
-				url: url.to_string(),
-				headers: HashMap::new(),
-				body: None,
-				timeout_seconds: 30,
-				follow_redirects: true,
-				verify_ssl: true,
-			};
-
+			let request = probe_request(url, verify_ssl, http_version);
 			match self.analyze_request(&request).await {
 				Ok(analysis) => {
 					info!("Iteration {} completed: {}ms", i, analysis.metrics.total_time_ms);
@@ -258,29 +500,161 @@ impl PerformanceAnalyzer {
 			}
 		}
 
+		if self.traceroute && results.iter().any(|a| matches!(a.severity, PerformanceSeverity::Critical)) {
+			self.attach_route(url, &mut results).await;
+		}
+
 		Ok(results)
 	}
 
+	/// Trace the path to the host once and add it, with a verdict on whether
+	/// the network or the server is slow, to every critical analysis
+	async fn attach_route(&self, url: &str, analyses: &mut [PerformanceAnalysis]) {
+		let Some(host) = url::Url::parse(url).ok().and_then(|u| u.host_str().map(str::to_string)) else {
+			return;
+		};
+		let host = host.trim_start_matches('[').trim_end_matches(']');
+		let hops = match traceroute::trace(host, traceroute::TraceOptions::default()).await {
+			Ok(hops) => hops,
+			Err(e) => {
+				warn!("Unable to trace the route to {}: {}", host, e);
+				return;
+			}
+		};
+
+		let verdict = route_verdict(&hops);
+		for analysis in analyses.iter_mut().filter(|a| matches!(a.severity, PerformanceSeverity::Critical)) {
+			analysis.analysis.push_str(&format!("\nNetwork Path ({} hops):\n• {}\n", hops.len(), verdict));
+			analysis.route = hops.clone();
+		}
+	}
+
+	/// Keep `concurrency` requests in flight until `duration` has passed.
+	/// Requests still running at the deadline are allowed to finish.
+	pub async fn run_load_test(
+		&self,
+		url: &str,
+		concurrency: usize,
+		duration: Duration,
+		verify_ssl: bool,
+		http_version: HttpVersion,
+	) -> Result<LoadTestResult> {
+		let request = probe_request(url, verify_ssl, http_version);
+		let concurrency = concurrency.max(1);
+		info!("Load testing {} with {} workers for {}s", url, concurrency, duration.as_secs());
+
+		let started = Instant::now();
+		let deadline = started + duration;
+		let mut workers = JoinSet::new();
+		for _ in 0..concurrency {
+			let http_client = self.http_client.clone();
+			let request = request.clone();
+			workers.spawn(async move {
+				let mut outcomes = Vec::new();
+				while Instant::now() < deadline {
+					let start = Instant::now();
+					let outcome = http_client.send_request(request.clone()).await
+						.map(|response| response.status)
+						.map_err(|e| e.to_string());
+					outcomes.push((outcome, start.elapsed().as_millis() as u64));
+				}
+				outcomes
+			});
+		}
+
+		let mut result = LoadTestResult { url: url.to_string(), concurrency, ..Default::default() };
+		while let Some(outcomes) = workers.join_next().await {
+			let outcomes = outcomes.map_err(|e| anyhow!("Load test worker failed: {}", e))?;
+			for (outcome, latency_ms) in outcomes {
+				result.total_requests += 1;
+				match outcome {
+					Ok(status) => {
+						*result.status_counts.entry(status).or_default() += 1;
+						result.latencies_ms.push(latency_ms);
+					}
+					Err(e) => *result.errors.entry(e).or_default() += 1,
+				}
+			}
+		}
+
+		let elapsed = started.elapsed();
+		result.elapsed_ms = elapsed.as_millis() as u64;
+		result.requests_per_second = result.total_requests as f64 / elapsed.as_secs_f64().max(0.001);
+		result.latency = LatencyStats::from_samples(&result.latencies_ms);
+		Ok(result)
+	}
+
+	pub fn generate_load_report(&self, result: &LoadTestResult) -> String {
+		let mut report = String::new();
+		report.push_str("=== LOAD TEST SUMMARY ===\n\n");
+		report.push_str(&format!("Target: {}\n", result.url));
+		report.push_str(&format!("Concurrency: {}\n", result.concurrency));
+		report.push_str(&format!(
+			"Requests: {} in {:.1}s ({:.2} req/s)\n",
+			result.total_requests,
+			result.elapsed_ms as f64 / 1000.0,
+			result.requests_per_second
+		));
+
+		let errors = result.error_count();
+		report.push_str(&format!(
+			"Errors: {} ({:.1}%)\n",
+			errors,
+			errors as f64 * 100.0 / result.total_requests.max(1) as f64
+		));
+
+		report.push_str("\nResponses by Status:\n");
+		for (status, count) in &result.status_counts {
+			let marker = if *status >= 400 { "❌" } else { "✅" };
+			report.push_str(&format!("• {} {}: {}\n", marker, status, count));
+		}
+		for (error, count) in &result.errors {
+			report.push_str(&format!("• ❌ {}: {}\n", error, count));
+		}
+
+		if let Some(stats) = &result.latency {
+			report.push_str(&format!(
+				"\nLatency: min {}ms | p50 {}ms | p90 {}ms | p95 {}ms | p99 {}ms | max {}ms (mean {:.0}ms, std dev {:.1}ms)\n",
+				stats.min_ms, stats.p50_ms, stats.p90_ms, stats.p95_ms, stats.p99_ms, stats.max_ms,
+				stats.mean_ms, stats.std_dev_ms
+			));
+			report.push_str("\nLatency Histogram:\n");
+			for line in histogram(&result.latencies_ms, 8) {
+				report.push_str(&format!("  {}\n", line));
+			}
+		}
+
+		report
+	}
+
 	pub fn generate_summary_report(&self, analyses: &[PerformanceAnalysis]) -> String {
 		if analyses.is_empty() {
 			return "No performance data available".to_string();
 		}
 
-		let total_requests = analyses.len();
 		let response_times: Vec<u64> = analyses.iter()
 			.map(|a| a.metrics.total_time_ms)
 			.collect();
-
-		let avg_time = response_times.iter().sum::<u64>() / total_requests as u64;
-		let min_time = response_times.iter().min().unwrap_or(&0);
-		let max_time = response_times.iter().max().unwrap_or(&0);
+		let Some(stats) = LatencyStats::from_samples(&response_times) else {
+			return "No performance data available".to_string();
+		};
+		let max_time = stats.max_ms;
 
 		let mut report = String::new();
 		report.push_str("=== PERFORMANCE ANALYSIS SUMMARY ===\n\n");
-		report.push_str(&format!("Total Requests: {}\n", total_requests));
-		report.push_str(&format!("Average Response Time: {}ms\n", avg_time));
-		report.push_str(&format!("Minimum Response Time: {}ms\n", min_time));
-		report.push_str(&format!("Maximum Response Time: {}ms\n", max_time));
+		report.push_str(&format!("Total Requests: {}\n", stats.count));
+		report.push_str(&format!("Average Response Time: {:.0}ms (std dev {:.1}ms)\n", stats.mean_ms, stats.std_dev_ms));
+		report.push_str(&format!("Minimum Response Time: {}ms\n", stats.min_ms));
+		report.push_str(&format!("Maximum Response Time: {}ms\n", stats.max_ms));
+		report.push_str(&format!(
+			"Percentiles: p50 {}ms | p90 {}ms | p95 {}ms | p99 {}ms\n",
+			stats.p50_ms, stats.p90_ms, stats.p95_ms, stats.p99_ms
+		));
+
+		report.push_str("\nLatency Histogram:\n");
+		for line in histogram(&response_times, 8) {
+			report.push_str(&format!("  {}\n", line));
+		}
 
 		let excellent = analyses.iter().filter(|a| matches!(a.severity, PerformanceSeverity::Excellent)).count();
 		let good = analyses.iter().filter(|a| matches!(a.severity, PerformanceSeverity::Good)).count();
@@ -295,7 +669,7 @@ impl PerformanceAnalyzer {
 		report.push_str(&format!("• Poor (1000-3000ms): {}\n", poor));
 		report.push_str(&format!("• Critical (>3000ms): {}\n", critical));
 
-		if max_time > &6000 {
+		if max_time > 6000 {
 			report.push_str("\n⚠️  CRITICAL PERFORMANCE ISSUES DETECTED!\n");
 			report.push_str("Some requests exceeded 6 seconds response time.\n");
 		}
@@ -304,14 +678,159 @@ impl PerformanceAnalyzer {
 	}
 }
 
-fn estimate_connection_times(total_time: u64, is_https: bool) -> (Option<u64>, Option<u64>, Option<u64>) {
-	if total_time == 0 {
-		return (None, None, None);
+/// Whether the traced path explains a slow response: the round trip to the
+/// last answering hop is what the network costs, the rest is the server
+pub fn route_verdict(hops: &[Hop]) -> String {
+	let Some((last, rtt)) = hops.iter().rev().find_map(|hop| Some((hop, hop.average_rtt_ms()?))) else {
+		return "No hop answered; ICMP is probably filtered on the path".to_string();
+	};
+	// The largest increase between consecutive answering hops
+	let mut previous = 0.0;
+	let mut jump: Option<(u8, f64)> = None;
+	for hop in hops {
+		if let Some(average) = hop.average_rtt_ms() {
+			if jump.is_none_or(|(_, largest)| average - previous > largest) {
+				jump = Some((hop.ttl, average - previous));
+			}
+			previous = average;
+		}
+	}
+
+	let mut verdict = format!("Round trip to hop {} ({}): {:.1}ms", last.ttl,
+		last.address.as_deref().unwrap_or("*"), rtt);
+	if rtt > 300.0 {
+		verdict.push_str(" - the network path itself is slow");
+		if let Some((ttl, increase)) = jump.filter(|&(_, increase)| increase > 100.0) {
+			verdict.push_str(&format!(", latency jumps by {:.0}ms at hop {}", increase, ttl));
+		}
+	} else {
+		verdict.push_str(" - the network is fast, so the delay is most likely server side");
+	}
+	verdict
+}
+
+/// Compare requests that each opened a new connection with the pooled run of
+/// the same target, attributing the difference to connection setup
+pub fn connection_comparison(cold: &[PerformanceAnalysis], warm: &LatencyStats) -> String {
+	let mut report = String::from("=== COLD vs WARM CONNECTIONS ===\n\n");
+	let Some(cold_stats) = LatencyStats::from_samples(
+		&cold.iter().map(|a| a.metrics.total_time_ms).collect::<Vec<_>>(),
+	) else {
+		report.push_str("No request on a fresh connection succeeded\n");
+		return report;
+	};
+
+	report.push_str(&format!("{:<22} {:>8} {:>8} {:>8}\n", "", "p50", "p95", "mean"));
+	for (label, stats) in [("Cold (new connection)", &cold_stats), ("Warm (pooled)", warm)] {
+		report.push_str(&format!("{:<22} {:>6}ms {:>6}ms {:>6.0}ms\n", label, stats.p50_ms, stats.p95_ms, stats.mean_ms));
+	}
+
+	let setup_cost = cold_stats.p50_ms.saturating_sub(warm.p50_ms);
+	report.push_str(&format!(
+		"\nConnection setup cost: {}ms per request ({:.0}% of a cold request)\n",
+		setup_cost,
+		setup_cost as f64 * 100.0 / cold_stats.p50_ms.max(1) as f64
+	));
+	let median = |phase: fn(&PerformanceMetrics) -> Option<u64>| {
+		let mut samples: Vec<u64> = cold.iter().filter_map(|a| phase(&a.metrics)).collect();
+		samples.sort_unstable();
+		if samples.is_empty() { "-".to_string() } else { format!("{}ms", percentile(&samples, 0.5)) }
+	};
+	report.push_str(&format!(
+		"Measured setup (median): DNS {}, TCP {}, TLS {}\n",
+		median(|m| m.dns_resolution_ms),
+		median(|m| m.tcp_connect_ms),
+		median(|m| m.tls_handshake_ms)
+	));
+	report.push_str(&format!("Server processing and transfer (warm p50): {}ms\n", warm.p50_ms));
+	if setup_cost * 2 > cold_stats.p50_ms {
+		report.push_str("\nMost of a cold request is connection setup: keep connections alive and reuse them.\n");
+	}
+	report
+}
+
+/// The plain GET request sent by each iteration of a test
+pub(crate) fn probe_request(url: &str, verify_ssl: bool, http_version: HttpVersion) -> HttpRequestBuilder {
+	HttpRequestBuilder {
+		method: "GET".to_string(),
+		url: url.to_string(),
+		headers: HashMap::new(),
+		body: None,
+		timeout_seconds: 30,
+		follow_redirects: true,
+		verify_ssl,
+		retry: RetryPolicy::default(),
+		auth: None,
+		http_version,
 	}
+}
 
-	let dns_time = Some(total_time.min(100) / 2);
-	let tcp_time = Some(total_time.min(200) / 4);
-	let tls_time = if is_https { Some(total_time.min(300) / 3) } else { None };
+/// One line per equal-width bucket between the fastest and slowest sample,
+/// e.g. `120-150ms │██████ 4`
+fn histogram(samples: &[u64], buckets: usize) -> Vec<String> {
+	const BAR_WIDTH: usize = 30;
+	let (Some(&min), Some(&max)) = (samples.iter().min(), samples.iter().max()) else {
+		return Vec::new();
+	};
+	let span = max - min + 1;
+	let width = span.div_ceil(buckets.max(1) as u64);
+	let buckets = span.div_ceil(width) as usize;
+
+	let mut counts = vec![0usize; buckets];
+	for &sample in samples {
+		counts[(((sample - min) / width) as usize).min(buckets - 1)] += 1;
+	}
+	let largest = counts.iter().copied().max().unwrap_or(1).max(1);
+	let label_width = format!("{}-{}ms", max, max).len();
+
+	counts.iter().enumerate().map(|(i, &count)| {
+		let low = min + i as u64 * width;
+		let high = if i + 1 == buckets { max } else { low + width - 1 };
+		let bar = "█".repeat((count * BAR_WIDTH).div_ceil(largest));
+		format!("{:>label_width$} │{} {}", format!("{}-{}ms", low, high), bar, count)
+	}).collect()
+}
+
+/// Durations of the steps that open a connection to the target
+#[derive(Debug, Clone, Default)]
+struct ConnectionPhases {
+	dns_ms: Option<u64>,
+	tcp_ms: Option<u64>,
+	tls_ms: Option<u64>,
+}
+
+/// Resolve the host of `url`, connect to it and, for https, complete a TLS
+/// handshake, timing each step. This uses a connection of its own because
+/// reqwest reuses pooled connections and does not report setup times.
+async fn measure_connection_phases(url: &str, timeout: Duration) -> Result<ConnectionPhases> {
+	let url = url::Url::parse(url)?;
+	let host = url.host_str().ok_or_else(|| anyhow!("No host in {}", url))?;
+	let host = host.trim_start_matches('[').trim_end_matches(']');
+	let port = url.port_or_known_default().ok_or_else(|| anyhow!("No port for {}", url))?;
+	let mut phases = ConnectionPhases::default();
+
+	let start = Instant::now();
+	let address = tokio::time::timeout(timeout, tokio::net::lookup_host((host, port)))
+		.await
+		.map_err(|_| anyhow!("DNS lookup for {} timed out", host))??
+		.next()
+		.ok_or_else(|| anyhow!("{} did not resolve to any address", host))?;
+	if host.parse::<std::net::IpAddr>().is_err() {
+		phases.dns_ms = Some(start.elapsed().as_millis() as u64);
+	}
+
+	let start = Instant::now();
+	let tcp = tokio::time::timeout(timeout, TcpStream::connect(address))
+		.await
+		.map_err(|_| anyhow!("TCP connect to {} timed out", address))??;
+	phases.tcp_ms = Some(start.elapsed().as_millis() as u64);
+
+	if url.scheme() == "https" {
+		let handshake = tokio::time::timeout(timeout, tls_inspector::timed_handshake(tcp, host))
+			.await
+			.map_err(|_| anyhow!("TLS handshake with {} timed out", host))??;
+		phases.tls_ms = Some(handshake.as_millis() as u64);
+	}
 
-	(dns_time, tcp_time, tls_time)
+	Ok(phases)
 }
\ No newline at end of file
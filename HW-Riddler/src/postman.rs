@@ -0,0 +1,213 @@
+use crate::collection::{Collection, CollectionRequest};
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::warn;
+
+/// Postman dynamic variables with a built-in riddler equivalent
+const DYNAMIC_VARIABLES: [(&str, &str); 4] = [
+	("{{$guid}}", "{{uuid}}"),
+	("{{$randomUUID}}", "{{uuid}}"),
+	("{{$timestamp}}", "{{timestamp}}"),
+	("{{$randomInt}}", "{{random:int:0-1000}}"),
+];
+
+/// Convert a Postman v2.1 collection export into a riddler collection.
+///
+/// Folders are flattened into each request's `folder` path, collection
+/// variables are kept as `{{name}}` variables, and collection- or
+/// folder-level auth is inherited by requests that do not set their own.
+pub fn import(path: &Path) -> Result<Collection> {
+	let content = std::fs::read_to_string(path)
+		.with_context(|| format!("Unable to read {}", path.display()))?;
+	let root: Value = serde_json::from_str(&content)
+		.with_context(|| format!("{} is not valid JSON", path.display()))?;
+
+	let info = root.get("info").ok_or_else(|| anyhow!("Missing 'info': not a Postman collection"))?;
+	if let Some(schema) = info.get("schema").and_then(Value::as_str)
+		&& !schema.contains("v2.1")
+	{
+		warn!("Collection schema is {}, only v2.1 is fully supported", schema);
+	}
+
+	let variables = root.get("variable")
+		.and_then(Value::as_array)
+		.map(|vars| {
+			vars.iter()
+				.filter(|v| !v.get("disabled").and_then(Value::as_bool).unwrap_or(false))
+				.filter_map(|v| Some((v.get("key")?.as_str()?.to_string(), scalar_to_string(v.get("value")?))))
+				.collect()
+		})
+		.unwrap_or_default();
+
+	let mut collection = Collection {
+		name: info.get("name").and_then(Value::as_str).unwrap_or("Imported collection").to_string(),
+		variables,
+		requests: Vec::new(),
+	};
+
+	let items = root.get("item").and_then(Value::as_array).cloned().unwrap_or_default();
+	collect_items(&items, None, root.get("auth"), &mut collection.requests)?;
+	Ok(collection)
+}
+
+fn collect_items(items: &[Value], folder: Option<&str>, auth: Option<&Value>, out: &mut Vec<CollectionRequest>) -> Result<()> {
+	for item in items {
+		let name = item.get("name").and_then(Value::as_str).unwrap_or("Unnamed").to_string();
+		let auth = item.get("auth").or(auth);
+
+		if let Some(children) = item.get("item").and_then(Value::as_array) {
+			let path = match folder {
+				Some(parent) => format!("{}/{}", parent, name),
+				None => name,
+			};
+			collect_items(children, Some(&path), auth, out)?;
+		} else if let Some(request) = item.get("request") {
+			out.push(convert_request(name, folder, request, auth)?);
+		}
+	}
+	Ok(())
+}
+
+fn convert_request(name: String, folder: Option<&str>, request: &Value, inherited_auth: Option<&Value>) -> Result<CollectionRequest> {
+	// A request may be saved as just its URL
+	if let Some(url) = request.as_str() {
+		return Ok(CollectionRequest {
+			name,
+			folder: folder.map(str::to_string),
+			method: "GET".to_string(),
+			url: map_dynamic(url),
+			headers: BTreeMap::new(),
+			body: None,
+			extract: BTreeMap::new(),
+			assertions: Vec::new(),
+		});
+	}
+
+	let method = request.get("method").and_then(Value::as_str).unwrap_or("GET").to_uppercase();
+	let url = match request.get("url") {
+		Some(Value::String(url)) => url.clone(),
+		Some(url) => url.get("raw").and_then(Value::as_str).map(str::to_string)
+			.ok_or_else(|| anyhow!("Request '{}' has a URL without 'raw'", name))?,
+		None => return Err(anyhow!("Request '{}' has no URL", name)),
+	};
+
+	let mut headers: BTreeMap<String, String> = request.get("header")
+		.and_then(Value::as_array)
+		.map(|headers| {
+			headers.iter()
+				.filter(|h| !h.get("disabled").and_then(Value::as_bool).unwrap_or(false))
+				.filter_map(|h| Some((h.get("key")?.as_str()?.to_string(), map_dynamic(&scalar_to_string(h.get("value")?)))))
+				.collect()
+		})
+		.unwrap_or_default();
+
+	let body = request.get("body").and_then(|body| convert_body(&name, body, &mut headers));
+
+	if let Some(auth) = request.get("auth").or(inherited_auth) {
+		apply_auth(&name, auth, &mut headers);
+	}
+
+	Ok(CollectionRequest {
+		name,
+		folder: folder.map(str::to_string),
+		method,
+		url: map_dynamic(&url),
+		headers,
+		body: body.map(|b| map_dynamic(&b)),
+		extract: BTreeMap::new(),
+		assertions: Vec::new(),
+	})
+}
+
+fn convert_body(name: &str, body: &Value, headers: &mut BTreeMap<String, String>) -> Option<String> {
+	let mut set_content_type = |value: &str| {
+		if !headers.keys().any(|k| k.eq_ignore_ascii_case("content-type")) {
+			headers.insert("Content-Type".to_string(), value.to_string());
+		}
+	};
+
+	match body.get("mode").and_then(Value::as_str)? {
+		"raw" => {
+			let raw = body.get("raw").and_then(Value::as_str)?.to_string();
+			if body.pointer("/options/raw/language").and_then(Value::as_str) == Some("json") {
+				set_content_type("application/json");
+			}
+			Some(raw)
+		}
+		mode @ ("urlencoded" | "formdata") => {
+			if mode == "formdata" {
+				warn!("Request '{}': form-data is sent as urlencoded and file fields are dropped", name);
+			}
+			let mut form = url::form_urlencoded::Serializer::new(String::new());
+			for field in body.get(mode).and_then(Value::as_array).into_iter().flatten() {
+				let enabled = !field.get("disabled").and_then(Value::as_bool).unwrap_or(false);
+				let is_text = field.get("type").and_then(Value::as_str).unwrap_or("text") == "text";
+				if let (true, true, Some(key)) = (enabled, is_text, field.get("key").and_then(Value::as_str)) {
+					form.append_pair(key, &field.get("value").map(scalar_to_string).unwrap_or_default());
+				}
+			}
+			set_content_type("application/x-www-form-urlencoded");
+			Some(form.finish())
+		}
+		"graphql" => {
+			let graphql = body.get("graphql")?;
+			let variables = graphql.get("variables")
+				.and_then(Value::as_str)
+				.and_then(|v| serde_json::from_str::<Value>(v).ok())
+				.unwrap_or(Value::Null);
+			set_content_type("application/json");
+			Some(serde_json::json!({ "query": graphql.get("query"), "variables": variables }).to_string())
+		}
+		other => {
+			warn!("Request '{}': body mode '{}' is not supported and was skipped", name, other);
+			None
+		}
+	}
+}
+
+fn apply_auth(name: &str, auth: &Value, headers: &mut BTreeMap<String, String>) {
+	// Auth parameters are stored as [{ "key": ..., "value": ... }] under the auth type
+	let auth_type = auth.get("type").and_then(Value::as_str).unwrap_or("noauth");
+	let param = |key: &str| {
+		auth.get(auth_type)
+			.and_then(Value::as_array)
+			.and_then(|params| params.iter().find(|p| p.get("key").and_then(Value::as_str) == Some(key)))
+			.and_then(|p| p.get("value"))
+			.map(scalar_to_string)
+			.unwrap_or_default()
+	};
+
+	match auth_type {
+		"noauth" => {}
+		"bearer" => {
+			headers.insert("Authorization".to_string(), format!("Bearer {}", param("token")));
+		}
+		"basic" => {
+			let credentials = format!("{}:{}", param("username"), param("password"));
+			if credentials.contains("{{") {
+				warn!("Request '{}': basic auth credentials use variables, which are encoded before substitution", name);
+			}
+			let encoded = base64::engine::general_purpose::STANDARD.encode(credentials);
+			headers.insert("Authorization".to_string(), format!("Basic {}", encoded));
+		}
+		"apikey" if param("in") != "query" => {
+			headers.insert(param("key"), param("value"));
+		}
+		other => warn!("Request '{}': auth type '{}' is not supported and was skipped", name, other),
+	}
+}
+
+fn scalar_to_string(value: &Value) -> String {
+	match value {
+		Value::String(s) => s.clone(),
+		Value::Null => String::new(),
+		other => other.to_string(),
+	}
+}
+
+fn map_dynamic(input: &str) -> String {
+	DYNAMIC_VARIABLES.iter().fold(input.to_string(), |acc, (from, to)| acc.replace(from, to))
+}
@@ -0,0 +1,512 @@
+use crate::cache_analyzer::{parse_http_date, CacheHeaders, HEURISTICALLY_CACHEABLE};
+use crate::mitm::UpstreamResponse;
+use crate::network::{glob_match, HttpRequest};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Counters kept next to the entries, so `cache stats` sees them after the proxy exits
+const STATS_FILE: &str = "stats.json";
+
+/// Headers a 304 Not Modified carries over from the response it stands for (RFC 9110 §15.4.5)
+const NOT_MODIFIED_HEADERS: [&str; 8] = [
+	"cache-control", "content-location", "date", "etag", "expires", "vary", "last-modified", "age",
+];
+
+/// A stored response; the body is kept in a file of its own next to it
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry {
+	url: String,
+	status: u16,
+	headers: Vec<(String, String)>,
+	/// The request headers named by `Vary`, with the values the response was
+	/// selected for
+	vary: Vec<(String, Option<String>)>,
+	/// When the request went out and the response came back, for the age
+	/// calculation of RFC 9111 §4.2.3
+	request_time: DateTime<Utc>,
+	response_time: DateTime<Utc>,
+}
+
+/// What the cache did since it was created or last cleared
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheCounters {
+	/// Answered from a fresh stored response
+	pub hits: u64,
+	/// Stale entries the origin confirmed with 304 Not Modified
+	pub revalidated: u64,
+	/// Sent to the origin because nothing reusable was stored
+	pub misses: u64,
+	/// Answered with 304 because the client's own validator matched
+	pub not_modified: u64,
+	pub stored: u64,
+	/// Entries dropped after an unsafe request changed the resource
+	pub invalidated: u64,
+	/// Responses and requests the cache could not use, by reason
+	pub not_stored: BTreeMap<String, u64>,
+}
+
+/// What `cache stats` prints
+#[derive(Debug, Serialize)]
+pub struct CacheReport {
+	pub dir: String,
+	pub entries: usize,
+	pub fresh: usize,
+	pub stale: usize,
+	pub bytes: u64,
+	/// Entries per host
+	pub hosts: BTreeMap<String, usize>,
+	pub counters: CacheCounters,
+}
+
+/// A stored response as loaded for a request
+struct Cached {
+	entry: Entry,
+	body: Vec<u8>,
+	headers: CacheHeaders,
+	lifetime: u64,
+	age: u64,
+}
+
+/// A shared HTTP cache for the proxy following RFC 9111 (formerly RFC 7234):
+/// responses are stored per URL while Cache-Control, Expires and the status
+/// allow it, reused while fresh and revalidated with their ETag or
+/// Last-Modified once stale
+pub struct ProxyCache {
+	dir: PathBuf,
+	/// Oldest entries are evicted beyond this many; 0 keeps everything
+	max_entries: usize,
+	counters: Mutex<CacheCounters>,
+}
+
+impl ProxyCache {
+	pub fn open(dir: &Path, max_entries: usize) -> Result<Self> {
+		std::fs::create_dir_all(dir).with_context(|| format!("Unable to create cache directory {}", dir.display()))?;
+		let counters = std::fs::read_to_string(dir.join(STATS_FILE))
+			.ok()
+			.and_then(|content| serde_json::from_str(&content).ok())
+			.unwrap_or_default();
+		Ok(Self { dir: dir.to_path_buf(), max_entries, counters: Mutex::new(counters) })
+	}
+
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	pub fn len(&self) -> usize {
+		self.entry_paths().len()
+	}
+
+	/// Answer `request` from the cache where that is allowed, otherwise through
+	/// `fetch`, storing or invalidating according to what the origin returns
+	pub async fn exchange<F, Fut>(&self, request: &HttpRequest, fetch: F) -> Result<UpstreamResponse, Vec<u8>>
+	where
+		F: Fn(HttpRequest) -> Fut,
+		Fut: Future<Output = Result<UpstreamResponse, Vec<u8>>>,
+	{
+		let method = request.method.as_str();
+		if !matches!(method, "GET" | "HEAD") {
+			let response = fetch(request.clone()).await?;
+			if !matches!(method, "OPTIONS" | "TRACE") && response.status < 400 {
+				self.invalidate(request, &response);
+			}
+			return Ok(response);
+		}
+
+		let directives = CacheHeaders::from_response(&request.headers);
+		if let Some(reason) = bypass_reason(request, &directives) {
+			self.count(|counters| {
+				counters.misses += 1;
+				*counters.not_stored.entry(reason.to_string()).or_default() += 1;
+			});
+			return fetch(request.clone()).await;
+		}
+
+		let cached = self.load(&request.url).filter(|cached| cached.entry.selected_by(&request.headers));
+		if let Some(cached) = &cached
+			&& cached.satisfies(&directives)
+		{
+			debug!("Cache hit for {} (age {}s of {}s)", request.url, cached.age, cached.lifetime);
+			self.count(|counters| counters.hits += 1);
+			return Ok(self.answer(request, cached.response(), "HIT"));
+		}
+		if directives.has("only-if-cached") {
+			self.count(|counters| counters.misses += 1);
+			return Ok(UpstreamResponse {
+				status: 504,
+				headers: vec![("content-type".to_string(), "text/plain".to_string())],
+				body: b"Not in cache (only-if-cached)".to_vec(),
+			});
+		}
+		if method == "HEAD" {
+			self.count(|counters| counters.misses += 1);
+			return fetch(request.clone()).await;
+		}
+
+		// The client's validators are answered from the stored copy, so the
+		// origin is asked for a full response, or to confirm the stored one
+		let mut upstream = request.clone();
+		upstream.headers.retain(|name, _| name != "if-none-match" && name != "if-modified-since");
+		if let Some(cached) = &cached {
+			if let Some(etag) = &cached.headers.etag {
+				upstream.headers.insert("if-none-match".to_string(), etag.clone());
+			}
+			if let Some(last_modified) = &cached.headers.last_modified {
+				upstream.headers.insert("if-modified-since".to_string(), last_modified.clone());
+			}
+		}
+
+		let request_time = Utc::now();
+		let response = fetch(upstream).await?;
+		if response.status == 304
+			&& let Some(cached) = cached
+		{
+			debug!("Origin confirmed the stored response for {}", request.url);
+			self.count(|counters| counters.revalidated += 1);
+			let refreshed = self.freshen(cached, request_time, &response);
+			return Ok(self.answer(request, refreshed, "REVALIDATED"));
+		}
+		self.count(|counters| counters.misses += 1);
+		self.store(request, request_time, &response);
+		Ok(self.answer(request, response, "MISS"))
+	}
+
+	/// Entry counts, sizes and counters for `cache stats`
+	pub fn report(&self) -> CacheReport {
+		let mut report = CacheReport {
+			dir: self.dir.display().to_string(),
+			entries: 0,
+			fresh: 0,
+			stale: 0,
+			bytes: 0,
+			hosts: BTreeMap::new(),
+			counters: self.counters.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+		};
+		for path in self.entry_paths() {
+			let Some(cached) = read_entry(&path) else {
+				continue;
+			};
+			report.entries += 1;
+			report.bytes += cached.body.len() as u64;
+			if cached.lifetime > cached.age {
+				report.fresh += 1;
+			} else {
+				report.stale += 1;
+			}
+			let host = url::Url::parse(&cached.entry.url).ok().and_then(|url| url.host_str().map(str::to_string)).unwrap_or_default();
+			*report.hosts.entry(host).or_default() += 1;
+		}
+		report
+	}
+
+	/// Remove the entries of hosts matching `host`, or everything including the
+	/// counters; returns how many entries were removed
+	pub fn clear(&self, host: Option<&str>) -> Result<usize> {
+		let mut removed = 0;
+		for path in self.entry_paths() {
+			if let Some(pattern) = host {
+				let entry_host = read_entry(&path)
+					.and_then(|cached| url::Url::parse(&cached.entry.url).ok())
+					.and_then(|url| url.host_str().map(str::to_lowercase));
+				if !entry_host.is_some_and(|entry_host| glob_match(&pattern.to_lowercase(), &entry_host)) {
+					continue;
+				}
+			}
+			std::fs::remove_file(&path).with_context(|| format!("Unable to remove {}", path.display()))?;
+			let _ = std::fs::remove_file(path.with_extension("body"));
+			removed += 1;
+		}
+		if host.is_none() {
+			*self.counters.lock().unwrap_or_else(|e| e.into_inner()) = CacheCounters::default();
+			let _ = std::fs::remove_file(self.dir.join(STATS_FILE));
+		}
+		Ok(removed)
+	}
+
+	fn load(&self, url: &str) -> Option<Cached> {
+		read_entry(&self.entry_path(url))
+	}
+
+	fn store(&self, request: &HttpRequest, request_time: DateTime<Utc>, response: &UpstreamResponse) {
+		let headers = cache_headers(&response.headers);
+		if let Err(reason) = storable(response.status, &headers) {
+			debug!("Not caching {}: {}", request.url, reason);
+			self.count(|counters| *counters.not_stored.entry(reason).or_default() += 1);
+			return;
+		}
+
+		let entry = Entry {
+			url: request.url.clone(),
+			status: response.status,
+			headers: response.headers.clone(),
+			vary: headers.vary.iter().map(|name| (name.clone(), request.headers.get(name).cloned())).collect(),
+			request_time,
+			response_time: Utc::now(),
+		};
+		match self.write(&entry, Some(&response.body)) {
+			Ok(()) => {
+				self.count(|counters| counters.stored += 1);
+				self.evict();
+			}
+			Err(e) => warn!("Unable to cache {}: {:#}", request.url, e),
+		}
+	}
+
+	/// Update a stored response with the headers of the 304 that confirmed it
+	/// (RFC 9111 §4.3.4)
+	fn freshen(&self, cached: Cached, request_time: DateTime<Utc>, not_modified: &UpstreamResponse) -> UpstreamResponse {
+		let Cached { mut entry, body, .. } = cached;
+		let updated: Vec<&(String, String)> = not_modified.headers.iter()
+			.filter(|(name, _)| name != "content-length" && name != "transfer-encoding")
+			.collect();
+		entry.headers.retain(|(name, _)| !updated.iter().any(|(updated, _)| updated == name));
+		entry.headers.extend(updated.into_iter().cloned());
+		entry.request_time = request_time;
+		entry.response_time = Utc::now();
+		if let Err(e) = self.write(&entry, None) {
+			warn!("Unable to update cached {}: {:#}", entry.url, e);
+		}
+		UpstreamResponse { status: entry.status, headers: entry.headers, body }
+	}
+
+	/// Drop what an unsafe request may have changed: its URL and the same-origin
+	/// Location and Content-Location of the response (RFC 9111 §4.4)
+	fn invalidate(&self, request: &HttpRequest, response: &UpstreamResponse) {
+		let mut urls = vec![request.url.clone()];
+		if let Ok(base) = url::Url::parse(&request.url) {
+			for (_, value) in response.headers.iter().filter(|(name, _)| name == "location" || name == "content-location") {
+				if let Ok(target) = base.join(value)
+					&& target.origin() == base.origin()
+				{
+					urls.push(target.to_string());
+				}
+			}
+		}
+		for url in urls {
+			let path = self.entry_path(&url);
+			if std::fs::remove_file(&path).is_ok() {
+				let _ = std::fs::remove_file(path.with_extension("body"));
+				debug!("Invalidated cached {} after {} {}", url, request.method, request.url);
+				self.count(|counters| counters.invalidated += 1);
+			}
+		}
+	}
+
+	/// The response for the client: 304 when its own validator matches, and
+	/// `X-Riddler-Cache` telling where the answer came from
+	fn answer(&self, request: &HttpRequest, mut response: UpstreamResponse, state: &str) -> UpstreamResponse {
+		if response.status == 200 && client_validator_matches(&request.headers, &response.headers) {
+			self.count(|counters| counters.not_modified += 1);
+			response.status = 304;
+			response.headers.retain(|(name, _)| NOT_MODIFIED_HEADERS.contains(&name.as_str()));
+			response.body.clear();
+		}
+		if request.method == "HEAD" {
+			response.body.clear();
+		}
+		response.headers.push(("x-riddler-cache".to_string(), state.to_string()));
+		response
+	}
+
+	/// Keep at most `max_entries`, removing those stored longest ago
+	fn evict(&self) {
+		let paths = self.entry_paths();
+		if self.max_entries == 0 || paths.len() <= self.max_entries {
+			return;
+		}
+		let mut entries: Vec<(DateTime<Utc>, PathBuf)> = paths.into_iter()
+			.map(|path| {
+				let stored = read_meta(&path).map_or(DateTime::<Utc>::MIN_UTC, |entry| entry.response_time);
+				(stored, path)
+			})
+			.collect();
+		entries.sort();
+		let excess = entries.len() - self.max_entries;
+		for (_, path) in entries.into_iter().take(excess) {
+			let _ = std::fs::remove_file(&path);
+			let _ = std::fs::remove_file(path.with_extension("body"));
+		}
+		debug!("Evicted {} cache entries beyond storage.max_cache_size", excess);
+	}
+
+	fn write(&self, entry: &Entry, body: Option<&[u8]>) -> Result<()> {
+		let path = self.entry_path(&entry.url);
+		if let Some(body) = body {
+			std::fs::write(path.with_extension("body"), body)?;
+		}
+		std::fs::write(&path, serde_json::to_vec(entry)?)?;
+		Ok(())
+	}
+
+	fn entry_path(&self, url: &str) -> PathBuf {
+		self.dir.join(format!("{:x}.json", Sha256::digest(url)))
+	}
+
+	fn entry_paths(&self) -> Vec<PathBuf> {
+		std::fs::read_dir(&self.dir)
+			.map(|entries| {
+				entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+					.filter(|path| {
+						path.extension().is_some_and(|extension| extension == "json")
+							&& path.file_name().is_some_and(|name| name != STATS_FILE)
+					})
+					.collect()
+			})
+			.unwrap_or_default()
+	}
+
+	fn count(&self, update: impl FnOnce(&mut CacheCounters)) {
+		let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+		update(&mut counters);
+		if let Err(e) = serde_json::to_vec_pretty(&*counters).map_err(anyhow::Error::from)
+			.and_then(|json| std::fs::write(self.dir.join(STATS_FILE), json).map_err(anyhow::Error::from))
+		{
+			debug!("Unable to save cache counters: {}", e);
+		}
+	}
+}
+
+impl Entry {
+	/// Whether the request headers named by `Vary` match the stored request
+	fn selected_by(&self, headers: &HashMap<String, String>) -> bool {
+		self.vary.iter().all(|(name, value)| headers.get(name) == value.as_ref())
+	}
+}
+
+impl Cached {
+	/// Whether the stored response may be reused without asking the origin,
+	/// given the request's own Cache-Control (RFC 9111 §4.2 and §5.2.1)
+	fn satisfies(&self, request: &CacheHeaders) -> bool {
+		let stored = &self.headers;
+		let request_no_cache = request.has("no-cache") || (request.cache_control.is_empty() && request.pragma_no_cache);
+		if stored.has("no-cache") || request_no_cache {
+			return false;
+		}
+		if request.seconds("max-age").is_some_and(|max_age| self.age > max_age) {
+			return false;
+		}
+		let remaining = self.lifetime as i64 - self.age as i64;
+		if request.seconds("min-fresh").is_some_and(|min_fresh| remaining < min_fresh as i64) {
+			return false;
+		}
+		if remaining > 0 {
+			return true;
+		}
+		// Stale responses are only served when the client accepts them with
+		// max-stale, and never when the origin demands revalidation
+		let must_revalidate = stored.has("must-revalidate") || stored.has("proxy-revalidate") || stored.has("s-maxage");
+		!must_revalidate
+			&& request.cache_control.get("max-stale").is_some_and(|max_stale| {
+				max_stale.as_ref().and_then(|seconds| seconds.parse::<i64>().ok()).is_none_or(|max_stale| -remaining <= max_stale)
+			})
+	}
+
+	/// The stored response with its current `Age`
+	fn response(&self) -> UpstreamResponse {
+		let mut headers: Vec<(String, String)> = self.entry.headers.iter().filter(|(name, _)| name != "age").cloned().collect();
+		headers.push(("age".to_string(), self.age.to_string()));
+		UpstreamResponse { status: self.entry.status, headers, body: self.body.clone() }
+	}
+}
+
+fn read_meta(path: &Path) -> Option<Entry> {
+	serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+}
+
+fn read_entry(path: &Path) -> Option<Cached> {
+	let entry = read_meta(path)?;
+	let body = std::fs::read(path.with_extension("body")).ok()?;
+	let headers = cache_headers(&entry.headers);
+	let lifetime = headers.freshness_lifetime(entry.status).0.unwrap_or(0);
+	let age = current_age(&entry, &headers);
+	Some(Cached { entry, body, headers, lifetime, age })
+}
+
+/// RFC 9111 §4.2.3: the age when the response arrived plus the time stored
+fn current_age(entry: &Entry, headers: &CacheHeaders) -> u64 {
+	let date = headers.date.as_deref().and_then(parse_http_date).unwrap_or(entry.response_time);
+	let apparent_age = (entry.response_time - date).num_seconds().max(0);
+	let response_delay = (entry.response_time - entry.request_time).num_seconds().max(0);
+	let corrected_age = headers.age.unwrap_or(0) as i64 + response_delay;
+	let resident_time = (Utc::now() - entry.response_time).num_seconds().max(0);
+	(apparent_age.max(corrected_age) + resident_time) as u64
+}
+
+/// Repeated headers joined the way `CacheHeaders` expects them
+fn cache_headers(headers: &[(String, String)]) -> CacheHeaders {
+	let mut joined: HashMap<String, String> = HashMap::new();
+	for (name, value) in headers {
+		joined.entry(name.clone())
+			.and_modify(|joined| {
+				joined.push_str(", ");
+				joined.push_str(value);
+			})
+			.or_insert_with(|| value.clone());
+	}
+	CacheHeaders::from_response(&joined)
+}
+
+/// Why a GET or HEAD is sent to the origin without looking at the cache
+fn bypass_reason(request: &HttpRequest, directives: &CacheHeaders) -> Option<&'static str> {
+	if directives.has("no-store") {
+		Some("request Cache-Control: no-store")
+	} else if request.headers.contains_key("authorization") {
+		// Responses to authenticated requests are per user (RFC 9111 §3.5)
+		Some("request has Authorization")
+	} else if request.headers.contains_key("range") {
+		Some("range request")
+	} else if request.headers.contains_key("if-match") || request.headers.contains_key("if-unmodified-since") {
+		Some("request has If-Match/If-Unmodified-Since")
+	} else {
+		None
+	}
+}
+
+/// Whether a shared cache may store a response, or why not (RFC 9111 §3)
+fn storable(status: u16, headers: &CacheHeaders) -> Result<(), String> {
+	if headers.has("no-store") {
+		return Err("Cache-Control: no-store".to_string());
+	}
+	if headers.has("private") {
+		return Err("Cache-Control: private".to_string());
+	}
+	if headers.vary.iter().any(|name| name == "*") {
+		return Err("Vary: *".to_string());
+	}
+	if status == 206 || status == 304 {
+		return Err(format!("status {}", status));
+	}
+	let explicit = headers.has("public") || headers.has("max-age") || headers.has("s-maxage") || headers.expires.is_some();
+	if !explicit && !HEURISTICALLY_CACHEABLE.contains(&status) {
+		return Err(format!("status {} without explicit freshness", status));
+	}
+	let fresh = headers.freshness_lifetime(status).0.is_some_and(|lifetime| lifetime > 0);
+	if !fresh && headers.etag.is_none() && headers.last_modified.is_none() {
+		return Err("no freshness lifetime and no validator".to_string());
+	}
+	Ok(())
+}
+
+/// Whether the client's If-None-Match or If-Modified-Since holds for `headers`
+fn client_validator_matches(request: &HashMap<String, String>, headers: &[(String, String)]) -> bool {
+	let header = |name: &str| headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str());
+	if let Some(tags) = request.get("if-none-match") {
+		// Weak comparison (RFC 9110 §8.8.3.2): W/ prefixes are ignored
+		let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+		return tags.trim() == "*"
+			|| header("etag").is_some_and(|etag| tags.split(',').any(|tag| weak(tag) == weak(etag)));
+	}
+	match (
+		request.get("if-modified-since").and_then(|since| parse_http_date(since)),
+		header("last-modified").and_then(parse_http_date),
+	) {
+		(Some(since), Some(last_modified)) => last_modified <= since,
+		_ => false,
+	}
+}
@@ -0,0 +1,386 @@
+use aes::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use aes::Aes128;
+use sha2::{Digest, Sha256};
+
+/// QUIC version 1 (RFC 9000)
+pub const VERSION_1: u32 = 0x0000_0001;
+
+/// Salt the version 1 Initial secrets are derived with (RFC 9001 §5.2)
+const INITIAL_SALT_V1: [u8; 20] = [
+	0x38, 0x76, 0x2c, 0xf7, 0xf5, 0x59, 0x34, 0xb3, 0x4d, 0x17,
+	0x9a, 0xe6, 0xa4, 0xc8, 0x0c, 0xad, 0xcc, 0xbb, 0x7f, 0x0a,
+];
+
+/// What a client announces in its first Initial packet. Initial packets are
+/// encrypted with keys derived from the destination connection ID alone, so
+/// any observer can read the ClientHello inside them.
+#[derive(Debug, Clone)]
+pub struct ClientInitial {
+	pub version: u32,
+	pub destination_cid: Vec<u8>,
+	/// Server Name Indication, missing when the ClientHello continues in a
+	/// later packet
+	pub server_name: Option<String>,
+	/// ALPN protocols offered, `h3` for HTTP/3
+	pub alpn: Vec<String>,
+}
+
+impl ClientInitial {
+	pub fn is_http3(&self) -> bool {
+		self.alpn.iter().any(|protocol| protocol == "h3" || protocol.starts_with("h3-"))
+	}
+}
+
+/// Decrypt a client's version 1 Initial packet and read the SNI and ALPN of
+/// the ClientHello it carries. The AEAD tag is not checked: a forged packet
+/// at worst yields a wrong name in the monitor output.
+pub fn parse_client_initial(datagram: &[u8]) -> Option<ClientInitial> {
+	let first = *datagram.first()?;
+	// Long header, fixed bit, packet type Initial
+	if first & 0xf0 != 0xc0 {
+		return None;
+	}
+	let version = u32::from_be_bytes(datagram.get(1..5)?.try_into().ok()?);
+	if version != VERSION_1 {
+		return None;
+	}
+
+	let mut pos = 5;
+	let dcid_len = *datagram.get(pos)? as usize;
+	let destination_cid = datagram.get(pos + 1..pos + 1 + dcid_len)?.to_vec();
+	pos += 1 + dcid_len;
+	let scid_len = *datagram.get(pos)? as usize;
+	pos += 1 + scid_len;
+	let token_len = read_varint(datagram, &mut pos)? as usize;
+	pos = pos.checked_add(token_len)?;
+	let length = read_varint(datagram, &mut pos)? as usize;
+	let pn_offset = pos;
+	// Further packets may be coalesced into the same datagram
+	let end = pn_offset.checked_add(length)?.min(datagram.len());
+
+	// The sample starts 4 bytes after the packet number, whatever its length
+	let sample = datagram.get(pn_offset.checked_add(4)?..pn_offset.checked_add(20)?)?;
+	let mut header = datagram.get(..pn_offset + 4)?.to_vec();
+	let keys = InitialKeys::client(&destination_cid);
+	let mask = keys.header_protection_mask(sample);
+	header[0] ^= mask[0] & 0x0f;
+	let pn_len = (header[0] & 0x03) as usize + 1;
+	for i in 0..pn_len {
+		header[pn_offset + i] ^= mask[1 + i];
+	}
+
+	let mut nonce = keys.iv;
+	for (i, byte) in header[pn_offset..pn_offset + pn_len].iter().enumerate() {
+		nonce[12 - pn_len + i] ^= byte;
+	}
+	let mut payload = datagram.get(pn_offset + pn_len..end.checked_sub(16)?)?.to_vec();
+	keys.decrypt(&nonce, &mut payload);
+
+	let crypto = crypto_stream(&payload)?;
+	let (server_name, alpn) = parse_client_hello(&crypto)?;
+	Some(ClientInitial { version, destination_cid, server_name, alpn })
+}
+
+struct InitialKeys {
+	key: [u8; 16],
+	iv: [u8; 12],
+	hp: [u8; 16],
+}
+
+impl InitialKeys {
+	fn client(destination_cid: &[u8]) -> Self {
+		let initial_secret = hmac_sha256(&INITIAL_SALT_V1, destination_cid);
+		let secret = hkdf_expand_label(&initial_secret, "client in", 32);
+		let mut keys = InitialKeys { key: [0; 16], iv: [0; 12], hp: [0; 16] };
+		keys.key.copy_from_slice(&hkdf_expand_label(&secret, "quic key", 16));
+		keys.iv.copy_from_slice(&hkdf_expand_label(&secret, "quic iv", 12));
+		keys.hp.copy_from_slice(&hkdf_expand_label(&secret, "quic hp", 16));
+		keys
+	}
+
+	fn header_protection_mask(&self, sample: &[u8]) -> [u8; 16] {
+		let mut block = GenericArray::clone_from_slice(sample);
+		Aes128::new(GenericArray::from_slice(&self.hp)).encrypt_block(&mut block);
+		block.into()
+	}
+
+	/// AES-128-GCM without the tag check is AES-CTR starting at counter 2
+	fn decrypt(&self, nonce: &[u8; 12], payload: &mut [u8]) {
+		let cipher = Aes128::new(GenericArray::from_slice(&self.key));
+		for (counter, chunk) in (2u32..).zip(payload.chunks_mut(16)) {
+			let mut block = GenericArray::clone_from_slice(&[&nonce[..], &counter.to_be_bytes()].concat());
+			cipher.encrypt_block(&mut block);
+			for (byte, key) in chunk.iter_mut().zip(block.iter()) {
+				*byte ^= key;
+			}
+		}
+	}
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+	let mut block = [0u8; 64];
+	if key.len() > block.len() {
+		block[..32].copy_from_slice(&Sha256::digest(key));
+	} else {
+		block[..key.len()].copy_from_slice(key);
+	}
+	let inner = Sha256::new()
+		.chain_update(block.map(|b| b ^ 0x36))
+		.chain_update(message)
+		.finalize();
+	Sha256::new()
+		.chain_update(block.map(|b| b ^ 0x5c))
+		.chain_update(inner)
+		.finalize()
+		.into()
+}
+
+/// TLS 1.3 HKDF-Expand-Label with an empty context, for outputs of at most
+/// one SHA-256 block
+fn hkdf_expand_label(secret: &[u8], label: &str, length: usize) -> Vec<u8> {
+	let label = format!("tls13 {}", label);
+	let mut info = Vec::with_capacity(label.len() + 5);
+	info.extend_from_slice(&(length as u16).to_be_bytes());
+	info.push(label.len() as u8);
+	info.extend_from_slice(label.as_bytes());
+	info.push(0);
+	info.push(1);
+	hmac_sha256(secret, &info)[..length].to_vec()
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Option<u64> {
+	let first = *data.get(*pos)?;
+	let len = 1usize << (first >> 6);
+	let bytes = data.get(*pos..pos.checked_add(len)?)?;
+	*pos += len;
+	Some(bytes[1..].iter().fold((first & 0x3f) as u64, |value, &byte| (value << 8) | byte as u64))
+}
+
+/// Reassemble the start of the CRYPTO stream from the frames of a decrypted
+/// Initial payload. Clients may split and shuffle the ClientHello across
+/// several CRYPTO frames.
+fn crypto_stream(payload: &[u8]) -> Option<Vec<u8>> {
+	let mut frames = Vec::new();
+	let mut pos = 0;
+	while pos < payload.len() {
+		match read_varint(payload, &mut pos)? {
+			// PADDING, PING
+			0x00 | 0x01 => {}
+			// ACK, with ECN counts for 0x03
+			kind @ (0x02 | 0x03) => {
+				read_varint(payload, &mut pos)?;
+				read_varint(payload, &mut pos)?;
+				let ranges = read_varint(payload, &mut pos)?;
+				read_varint(payload, &mut pos)?;
+				let extra = ranges * 2 + if kind == 0x03 { 3 } else { 0 };
+				for _ in 0..extra {
+					read_varint(payload, &mut pos)?;
+				}
+			}
+			0x06 => {
+				let offset = read_varint(payload, &mut pos)? as usize;
+				let length = read_varint(payload, &mut pos)? as usize;
+				let data_end = pos.checked_add(length)?;
+				frames.push((offset, payload.get(pos..data_end)?));
+				pos = data_end;
+			}
+			_ => break,
+		}
+	}
+
+	frames.sort_by_key(|(offset, _)| *offset);
+	let mut stream = Vec::new();
+	for (offset, data) in frames {
+		if offset > stream.len() {
+			break;
+		}
+		if offset.saturating_add(data.len()) > stream.len() {
+			stream.extend_from_slice(&data[stream.len() - offset..]);
+		}
+	}
+	(!stream.is_empty()).then_some(stream)
+}
+
+/// SNI and ALPN of a (possibly truncated) TLS ClientHello handshake message
+pub(crate) fn parse_client_hello(message: &[u8]) -> Option<(Option<String>, Vec<String>)> {
+	if *message.first()? != 0x01 {
+		return None;
+	}
+	// Type, 24-bit length, legacy version, random
+	let mut pos = 4 + 2 + 32;
+	pos += 1 + *message.get(pos)? as usize;
+	pos += 2 + u16::from_be_bytes(message.get(pos..pos + 2)?.try_into().ok()?) as usize;
+	pos += 1 + *message.get(pos)? as usize;
+	pos += 2;
+
+	let mut server_name = None;
+	let mut alpn = Vec::new();
+	while let Some(header) = message.get(pos..pos + 4) {
+		let kind = u16::from_be_bytes([header[0], header[1]]);
+		let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+		let Some(data) = message.get(pos + 4..pos + 4 + len) else { break };
+		pos += 4 + len;
+		match kind {
+			// server_name: list length, name type, name length, name
+			0x0000 => {
+				let name_len = u16::from_be_bytes(data.get(3..5)?.try_into().ok()?) as usize;
+				server_name = Some(String::from_utf8_lossy(data.get(5..5 + name_len)?).into_owned());
+			}
+			// application_layer_protocol_negotiation: list length, then length-prefixed names
+			0x0010 => {
+				let mut at = 2;
+				while let Some(&name_len) = data.get(at) {
+					let Some(name) = data.get(at + 1..at + 1 + name_len as usize) else { break };
+					alpn.push(String::from_utf8_lossy(name).into_owned());
+					at += 1 + name_len as usize;
+				}
+			}
+			_ => {}
+		}
+	}
+	Some((server_name, alpn))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const DCID: [u8; 8] = [0x83, 0x94, 0xc8, 0xf0, 0x3e, 0x51, 0x57, 0x08];
+
+	fn client_hello(server_name: &str, alpn: &[&str]) -> Vec<u8> {
+		let mut sni = vec![0x00];
+		sni.extend_from_slice(&(server_name.len() as u16).to_be_bytes());
+		sni.extend_from_slice(server_name.as_bytes());
+		let mut sni_list = (sni.len() as u16).to_be_bytes().to_vec();
+		sni_list.extend_from_slice(&sni);
+
+		let mut names = Vec::new();
+		for name in alpn {
+			names.push(name.len() as u8);
+			names.extend_from_slice(name.as_bytes());
+		}
+		let mut alpn_list = (names.len() as u16).to_be_bytes().to_vec();
+		alpn_list.extend_from_slice(&names);
+
+		let mut extensions = Vec::new();
+		for (kind, data) in [(0x0000u16, sni_list), (0x0010, alpn_list)] {
+			extensions.extend_from_slice(&kind.to_be_bytes());
+			extensions.extend_from_slice(&(data.len() as u16).to_be_bytes());
+			extensions.extend_from_slice(&data);
+		}
+
+		// Legacy version, random, empty session ID, one cipher suite, null compression
+		let mut body = vec![0x03, 0x03];
+		body.extend_from_slice(&[0x11; 32]);
+		body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+		body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+		body.extend_from_slice(&extensions);
+
+		let mut message = vec![0x01];
+		message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+		message.extend_from_slice(&body);
+		message
+	}
+
+	/// CRYPTO frame at `offset`, with two-byte varints for offset and length
+	fn crypto_frame(offset: usize, data: &[u8]) -> Vec<u8> {
+		let mut frame = vec![0x06];
+		frame.extend_from_slice(&(0x4000 | offset as u16).to_be_bytes());
+		frame.extend_from_slice(&(0x4000 | data.len() as u16).to_be_bytes());
+		frame.extend_from_slice(data);
+		frame
+	}
+
+	/// Protect `payload` the way a client does, with a four-byte packet number
+	fn seal_initial(payload: &[u8]) -> Vec<u8> {
+		let packet_number = 2u32.to_be_bytes();
+		let mut packet = vec![0xc3];
+		packet.extend_from_slice(&VERSION_1.to_be_bytes());
+		packet.push(DCID.len() as u8);
+		packet.extend_from_slice(&DCID);
+		// Empty source connection ID and token
+		packet.extend_from_slice(&[0x00, 0x00]);
+		let length = packet_number.len() + payload.len() + 16;
+		packet.extend_from_slice(&(0x4000 | length as u16).to_be_bytes());
+		let pn_offset = packet.len();
+		packet.extend_from_slice(&packet_number);
+
+		let keys = InitialKeys::client(&DCID);
+		let mut nonce = keys.iv;
+		for (i, byte) in packet_number.iter().enumerate() {
+			nonce[8 + i] ^= byte;
+		}
+		let mut sealed = payload.to_vec();
+		keys.decrypt(&nonce, &mut sealed);
+		packet.extend_from_slice(&sealed);
+		packet.extend_from_slice(&[0; 16]);
+
+		let mask = keys.header_protection_mask(&packet[pn_offset + 4..pn_offset + 20]);
+		packet[0] ^= mask[0] & 0x0f;
+		for i in 0..packet_number.len() {
+			packet[pn_offset + i] ^= mask[1 + i];
+		}
+		packet
+	}
+
+	#[test]
+	fn derives_rfc9001_client_keys() {
+		let keys = InitialKeys::client(&DCID);
+		assert_eq!(keys.key, [0x1f, 0x36, 0x96, 0x13, 0xdd, 0x76, 0xd5, 0x46, 0x77, 0x30, 0xef, 0xcb, 0xe3, 0xb1, 0xa2, 0x2d]);
+		assert_eq!(keys.iv, [0xfa, 0x04, 0x4b, 0x2f, 0x42, 0xa3, 0xfd, 0x3b, 0x46, 0xfb, 0x25, 0x5c]);
+		assert_eq!(keys.hp, [0x9f, 0x50, 0x44, 0x9e, 0x04, 0xa0, 0xe8, 0x10, 0x28, 0x3a, 0x1e, 0x99, 0x33, 0xad, 0xed, 0xd2]);
+	}
+
+	#[test]
+	fn reads_sni_and_alpn_from_initial() {
+		let mut payload = crypto_frame(0, &client_hello("example.com", &["h3"]));
+		payload.resize(1100, 0);
+		let initial = parse_client_initial(&seal_initial(&payload)).expect("valid Initial");
+		assert_eq!(initial.destination_cid, DCID);
+		assert_eq!(initial.server_name.as_deref(), Some("example.com"));
+		assert_eq!(initial.alpn, ["h3"]);
+		assert!(initial.is_http3());
+	}
+
+	#[test]
+	fn truncated_initials_do_not_panic() {
+		let mut payload = crypto_frame(0, &client_hello("example.com", &["h3"]));
+		payload.resize(1100, 0);
+		let packet = seal_initial(&payload);
+		for len in 0..packet.len() {
+			let _ = parse_client_initial(&packet[..len]);
+		}
+	}
+
+	#[test]
+	fn oversized_token_length_is_rejected() {
+		let mut packet = vec![0xc3];
+		packet.extend_from_slice(&VERSION_1.to_be_bytes());
+		packet.push(DCID.len() as u8);
+		packet.extend_from_slice(&DCID);
+		packet.push(0x00);
+		// Eight-byte varint with the largest token length QUIC can encode
+		packet.extend_from_slice(&[0xff; 8]);
+		packet.extend_from_slice(&[0x00; 64]);
+		assert!(parse_client_initial(&packet).is_none());
+	}
+
+	#[test]
+	fn reassembles_shuffled_crypto_frames() {
+		let hello = client_hello("example.com", &["h3"]);
+		let (head, tail) = hello.split_at(20);
+		let mut payload = crypto_frame(head.len(), tail);
+		payload.push(0x01);
+		payload.extend_from_slice(&crypto_frame(0, head));
+		assert_eq!(crypto_stream(&payload).as_deref(), Some(&hello[..]));
+	}
+
+	#[test]
+	fn crypto_frame_longer_than_payload_is_rejected() {
+		let mut payload = vec![0x06, 0x00];
+		payload.extend_from_slice(&[0xff; 8]);
+		payload.extend_from_slice(b"hello");
+		assert!(crypto_stream(&payload).is_none());
+		assert!(crypto_stream(&crypto_frame(0, b"hello")[..6]).is_none());
+	}
+}
@@ -0,0 +1,255 @@
+use crate::http_client::HttpClient;
+use crate::logger::RequestLogger;
+use crate::notify::Notifier;
+use crate::replay::{self, ReplayOptions, ReplayTarget, SendRecord};
+use crate::{output, say};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// Runs shown in the trend sparkline
+const TREND_RUNS: usize = 20;
+
+/// When the replay set is run again
+pub enum Schedule {
+	/// A fixed interval between run starts, the first run starting right away
+	Every(chrono::Duration),
+	/// A cron expression in local time (`minute hour day month weekday`)
+	Cron { expression: String, cron: Box<croner::Cron> },
+}
+
+impl Schedule {
+	/// `30s`, `5m`, `1h`, `1d`
+	pub fn every(value: &str) -> Result<Self> {
+		let interval = crate::timeline::parse_since(value)?;
+		if interval <= chrono::Duration::zero() {
+			return Err(anyhow!("--every must be longer than zero"));
+		}
+		Ok(Schedule::Every(interval))
+	}
+
+	pub fn cron(expression: &str) -> Result<Self> {
+		let cron = croner::Cron::new(expression)
+			.with_seconds_optional()
+			.parse()
+			.map_err(|e| anyhow!("Invalid cron expression '{}': {}", expression, e))?;
+		Ok(Schedule::Cron { expression: expression.to_string(), cron: Box::new(cron) })
+	}
+
+	pub fn describe(&self) -> String {
+		match self {
+			Schedule::Every(interval) => format!("every {}", format_interval(*interval)),
+			Schedule::Cron { expression, .. } => format!("cron '{}'", expression),
+		}
+	}
+
+	/// Start of the run following one that started at `previous` (`None` for the first run)
+	fn next(&self, previous: Option<DateTime<Local>>) -> Result<DateTime<Local>> {
+		let now = Local::now();
+		match self {
+			Schedule::Every(interval) => Ok(match previous {
+				// A run that took longer than the interval is followed right away
+				Some(previous) => (previous + *interval).max(now),
+				None => now,
+			}),
+			Schedule::Cron { expression, cron } => cron
+				.find_next_occurrence(&now, false)
+				.map_err(|e| anyhow!("Cron expression '{}' has no next run: {}", expression, e)),
+		}
+	}
+}
+
+pub struct RecurringOptions {
+	pub schedule: Schedule,
+	/// Stop after this many runs; run until interrupted when `None`
+	pub runs: Option<usize>,
+	/// One JSON line per run is appended here
+	pub history_path: PathBuf,
+	/// Announces runs that took long enough, see [`Notifier::announces_replay`]
+	pub notifier: Notifier,
+}
+
+/// Outcome of one scheduled run, as appended to the history file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+	pub run: usize,
+	pub schedule: String,
+	pub started: DateTime<Utc>,
+	pub duration_ms: u64,
+	pub sent: usize,
+	/// Responses with a status below 400
+	pub succeeded: usize,
+	/// Responses with a 4xx/5xx status
+	pub http_errors: usize,
+	/// Sends that got no response at all
+	pub failed: usize,
+	/// With `--diff`: responses that succeeded originally and fail now
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub regressions: Option<usize>,
+	pub p50_ms: Option<u64>,
+	pub p95_ms: Option<u64>,
+}
+
+impl RunRecord {
+	fn new(run: usize, schedule: &Schedule, started: DateTime<Utc>, sends: &[SendRecord]) -> Self {
+		let mut latencies: Vec<u64> = sends.iter().filter_map(|send| send.response_time_ms).collect();
+		latencies.sort_unstable();
+		let percentile = |p| (!latencies.is_empty()).then(|| crate::stats::percentile(&latencies, p));
+		Self {
+			run,
+			schedule: schedule.describe(),
+			started,
+			duration_ms: (Utc::now() - started).num_milliseconds().max(0) as u64,
+			sent: sends.len(),
+			succeeded: sends.iter().filter(|send| send.status.is_some_and(|status| status < 400)).count(),
+			http_errors: sends.iter().filter(|send| send.status.is_some_and(|status| status >= 400)).count(),
+			failed: sends.iter().filter(|send| send.status.is_none()).count(),
+			regressions: sends.iter().any(|send| send.regression.is_some())
+				.then(|| sends.iter().filter(|send| send.regression == Some(true)).count()),
+			p50_ms: percentile(0.50),
+			p95_ms: percentile(0.95),
+		}
+	}
+
+	fn success_rate(&self) -> f64 {
+		if self.sent == 0 { 0.0 } else { self.succeeded as f64 * 100.0 / self.sent as f64 }
+	}
+}
+
+/// Replay `targets` on `schedule` until the run limit is reached or the process
+/// is interrupted, appending each run to the history and printing the
+/// success-rate trend. An interrupted run is not recorded.
+pub async fn run(
+	targets: Vec<ReplayTarget>,
+	options: ReplayOptions,
+	recurring: RecurringOptions,
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<Vec<RunRecord>> {
+	let shutdown = CancellationToken::new();
+	crate::shutdown::cancel_on_signal(shutdown.clone());
+
+	say!(
+		"🔁 Replaying {} requests {}{}; results are appended to {}. Press Ctrl+C to stop.",
+		targets.len(),
+		recurring.schedule.describe(),
+		recurring.runs.map(|runs| format!(" for {} runs", runs)).unwrap_or_default(),
+		recurring.history_path.display()
+	);
+
+	let mut records: Vec<RunRecord> = Vec::new();
+	let mut previous_start = None;
+	while recurring.runs.is_none_or(|runs| records.len() < runs) {
+		let start = recurring.schedule.next(previous_start)?;
+		if start > Local::now() {
+			say!("\n⏳ Next run at {}", start.format("%Y-%m-%d %H:%M:%S"));
+			let wait = (start - Local::now()).to_std().unwrap_or_default();
+			tokio::select! {
+				_ = tokio::time::sleep(wait) => {}
+				_ = shutdown.cancelled() => break,
+			}
+		}
+		previous_start = Some(start);
+
+		let run = records.len() + 1;
+		say!("\n━━━ Run {} ({}) ━━━", run, Local::now().format("%H:%M:%S"));
+		let started = Utc::now();
+		let clock = std::time::Instant::now();
+		let sends = tokio::select! {
+			sends = replay::run(targets.clone(), options.clone(), http_client.clone(), logger.clone()) => sends?,
+			_ = shutdown.cancelled() => {
+				say!("\nRun {} interrupted, not recorded", run);
+				break;
+			}
+		};
+
+		let record = RunRecord::new(run, &recurring.schedule, started, &sends);
+		if recurring.notifier.announces_replay(clock.elapsed()) {
+			recurring.notifier.send(&replay::finished_notification(&format!("Replay run {}", run), &sends, clock.elapsed())).await;
+		}
+		if let Err(e) = append_history(&recurring.history_path, &record) {
+			warn!("{:#}", e);
+		}
+		if output::json() {
+			output::emit_line(&record)?;
+		}
+		records.push(record);
+		print_trend(&records);
+	}
+
+	if !records.is_empty() {
+		let sent: usize = records.iter().map(|record| record.sent).sum();
+		let succeeded: usize = records.iter().map(|record| record.succeeded).sum();
+		say!(
+			"\n✓ {} runs, {} of {} sends succeeded ({:.1}%)",
+			records.len(),
+			succeeded,
+			sent,
+			if sent == 0 { 0.0 } else { succeeded as f64 * 100.0 / sent as f64 }
+		);
+	}
+	Ok(records)
+}
+
+fn append_history(path: &Path, record: &RunRecord) -> Result<()> {
+	let mut file = std::fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)
+		.with_context(|| format!("Unable to open replay history {}", path.display()))?;
+	writeln!(file, "{}", serde_json::to_string(record)?)
+		.with_context(|| format!("Unable to write replay history {}", path.display()))
+}
+
+/// The latest run next to the success rates of the recent ones
+fn print_trend(records: &[RunRecord]) {
+	let Some(latest) = records.last() else { return };
+	let recent = &records[records.len().saturating_sub(TREND_RUNS)..];
+	let sparkline: String = recent.iter().map(|record| spark(record.success_rate())).collect();
+	let average = recent.iter().map(RunRecord::success_rate).sum::<f64>() / recent.len() as f64;
+
+	let change = match records.len().checked_sub(2).map(|i| latest.success_rate() - records[i].success_rate()) {
+		Some(delta) if delta.abs() >= 0.05 => format!(" ({:+.1})", delta),
+		_ => String::new(),
+	};
+	let latency = latest.p95_ms.map(|p95| format!(", p95 {}ms", p95)).unwrap_or_default();
+	let regressions = match latest.regressions {
+		Some(n) if n > 0 => format!(", {} regression(s)", n),
+		_ => String::new(),
+	};
+
+	say!(
+		"📈 Run {}: {}/{} succeeded ({:.1}%{}){}{} | last {} runs {} avg {:.1}%",
+		latest.run,
+		latest.succeeded,
+		latest.sent,
+		latest.success_rate(),
+		change,
+		latency,
+		regressions,
+		recent.len(),
+		sparkline,
+		average
+	);
+}
+
+/// A block from ▁ (0%) to █ (100%)
+fn spark(rate: f64) -> char {
+	const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+	BLOCKS[((rate / 100.0 * 7.0).round() as usize).min(7)]
+}
+
+fn format_interval(interval: chrono::Duration) -> String {
+	let seconds = interval.num_seconds();
+	match seconds {
+		s if s % 86_400 == 0 => format!("{}d", s / 86_400),
+		s if s % 3_600 == 0 => format!("{}h", s / 3_600),
+		s if s % 60 == 0 => format!("{}m", s / 60),
+		s => format!("{}s", s),
+	}
+}
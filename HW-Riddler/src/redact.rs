@@ -0,0 +1,170 @@
+use crate::config::RedactionConfig;
+use crate::logger::RequestLogEntry;
+use crate::ndjson::MonitorEvent;
+use anyhow::{anyhow, Result};
+use regex::{Captures, Regex};
+use std::collections::HashMap;
+
+/// Replacement for a masked value
+pub const MASK: &str = "[REDACTED]";
+
+/// Masks credentials and card numbers in log entries before they are written
+#[derive(Debug, Clone)]
+pub struct Redactor {
+	/// Lower-cased header names whose values are masked
+	headers: Vec<String>,
+	/// `key=value`, `key: value` and `"key": "value"` where the key contains a sensitive word
+	field_value: Option<Regex>,
+	/// Sensitive words, lower-cased, for URL query parameters
+	fields: Vec<String>,
+	card_number: Option<Regex>,
+}
+
+impl Redactor {
+	pub fn from_config(config: &RedactionConfig) -> Result<Self> {
+		let fields: Vec<String> = config.body_fields.iter().map(|f| f.to_lowercase()).collect();
+		let field_value = if fields.is_empty() {
+			None
+		} else {
+			let words = fields.iter().map(|f| regex::escape(f)).collect::<Vec<_>>().join("|");
+			let pattern = format!(r#"(?i)(["']?[\w.-]*(?:{})[\w.-]*["']?\s*[:=]\s*["']?)([^"'&\s,;}}]+)"#, words);
+			Some(Regex::new(&pattern).map_err(|e| anyhow!("Invalid redaction field list: {}", e))?)
+		};
+
+		Ok(Self {
+			headers: config.headers.iter().map(|h| h.to_lowercase()).collect(),
+			field_value,
+			fields,
+			card_number: config.mask_card_numbers.then(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").expect("valid regex")),
+		})
+	}
+
+	pub fn apply(&self, entry: &mut RequestLogEntry) {
+		self.redact_headers(&mut entry.request.headers);
+		entry.request.url = self.redact_url(&entry.request.url);
+		entry.request.body_preview = self.redact_text(&entry.request.body_preview);
+
+		if let Some(ref mut response) = entry.response {
+			self.redact_headers(&mut response.headers);
+			if self.headers.iter().any(|h| h == "set-cookie") {
+				for cookie in response.cookies.iter_mut() {
+					*cookie = mask_cookie_pairs(cookie, true);
+				}
+			}
+			response.body = self.redact_text(&response.body);
+			response.final_url = self.redact_url(&response.final_url);
+		}
+	}
+
+	/// Mask an event of the `monitor --output ndjson` stream
+	pub fn apply_event(&self, event: &mut MonitorEvent<'_>) {
+		match event {
+			MonitorEvent::Request { url, headers, body, .. } => {
+				*url = self.redact_url(url).into();
+				self.redact_headers(headers.to_mut());
+				if let Some(body) = body {
+					*body = self.redact_text(body);
+				}
+			}
+			MonitorEvent::Response { url, headers, .. } => {
+				*url = self.redact_url(url).into();
+				self.redact_headers(headers.to_mut());
+			}
+			MonitorEvent::Quic { .. } => {}
+		}
+	}
+
+	fn redact_headers(&self, headers: &mut HashMap<String, String>) {
+		for (name, value) in headers.iter_mut() {
+			let name = name.to_lowercase();
+			if !self.headers.contains(&name) {
+				continue;
+			}
+			*value = match name.as_str() {
+				// Keep the cookie names, they help debugging and are not secret
+				"cookie" => mask_cookie_pairs(value, false),
+				"set-cookie" => mask_cookie_pairs(value, true),
+				// Keep the scheme of "Bearer xyz" / "Basic xyz"
+				_ => match value.split_once(' ') {
+					Some((scheme, _)) if !scheme.is_empty() => format!("{} {}", scheme, MASK),
+					_ => MASK.to_string(),
+				},
+			};
+		}
+	}
+
+	fn redact_url(&self, url: &str) -> String {
+		let Ok(mut parsed) = url::Url::parse(url) else {
+			return self.redact_text(url);
+		};
+		let mut changed = false;
+		let pairs: Vec<(String, String)> = parsed
+			.query_pairs()
+			.map(|(k, v)| {
+				let sensitive = self.fields.iter().any(|f| k.to_lowercase().contains(f.as_str()));
+				let value = if sensitive { MASK.to_string() } else { self.mask_cards(&v) };
+				changed |= value != v;
+				(k.into_owned(), value)
+			})
+			.collect();
+
+		// Re-encoding may change the query's spelling, so only do it when needed
+		if changed {
+			parsed.query_pairs_mut().clear().extend_pairs(pairs);
+		}
+		parsed.to_string()
+	}
+
+	fn redact_text(&self, text: &str) -> String {
+		let text = match self.field_value {
+			Some(ref regex) => regex.replace_all(text, |caps: &Captures| format!("{}{}", &caps[1], MASK)).into_owned(),
+			None => text.to_string(),
+		};
+		self.mask_cards(&text)
+	}
+
+	/// Replace digit runs that pass the Luhn check, keeping the last four digits
+	fn mask_cards(&self, text: &str) -> String {
+		let Some(ref regex) = self.card_number else {
+			return text.to_string();
+		};
+		regex
+			.replace_all(text, |caps: &Captures| {
+				let digits: String = caps[0].chars().filter(|c| c.is_ascii_digit()).collect();
+				if luhn_valid(&digits) {
+					format!("{}{}", MASK, &digits[digits.len() - 4..])
+				} else {
+					caps[0].to_string()
+				}
+			})
+			.into_owned()
+	}
+}
+
+/// `a=1; b=2` → `a=[REDACTED]; b=[REDACTED]`. For Set-Cookie only the first
+/// pair is the cookie, the rest are attributes and stay readable.
+fn mask_cookie_pairs(value: &str, set_cookie: bool) -> String {
+	value
+		.split(';')
+		.enumerate()
+		.map(|(i, pair)| match pair.split_once('=') {
+			Some((name, _)) if !set_cookie || i == 0 => format!("{}={}", name, MASK),
+			_ => pair.to_string(),
+		})
+		.collect::<Vec<_>>()
+		.join(";")
+}
+
+fn luhn_valid(digits: &str) -> bool {
+	if !(13..=19).contains(&digits.len()) {
+		return false;
+	}
+	let sum: u32 = digits
+		.chars()
+		.rev()
+		.filter_map(|c| c.to_digit(10))
+		.enumerate()
+		.map(|(i, d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+		.sum();
+	sum.is_multiple_of(10)
+}
@@ -0,0 +1,711 @@
+use crate::cli::ReplayMode;
+use crate::http_client::{HttpClient, HttpRequestBuilder, HttpResponseInfo};
+use crate::logger::RequestLogger;
+use crate::notify::{Notification, Severity};
+use crate::response_diff::ResponseDiff;
+use crate::say;
+use crate::stats::percentile;
+use crate::template::TemplateContext;
+use anyhow::{anyhow, Result};
+use rand::seq::SliceRandom;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::error;
+
+/// A request selected for replay together with when it was originally captured
+#[derive(Debug, Clone)]
+pub struct ReplayTarget {
+	pub request: HttpRequestBuilder,
+	pub captured_at: chrono::DateTime<chrono::Utc>,
+	/// The response recorded with the capture, compared against with `--diff`
+	pub original: Option<HttpResponseInfo>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayOptions {
+	pub count: usize,
+	pub delay: u64,
+	pub mode: ReplayMode,
+	pub parallel: usize,
+	/// Upper bound on request starts per second
+	pub rps: Option<f64>,
+	/// Safety cap on starts per second; the lower of this and `rps` applies
+	pub max_rps: Option<f64>,
+	/// Slow down and pause when the target answers 429, or 503 with Retry-After
+	pub respect_rate_limits: bool,
+	pub shuffle: bool,
+	pub preserve_timing: bool,
+	/// Added to every replayed request; placeholders are expanded on each send
+	pub headers: HashMap<String, String>,
+	pub rewrite: RewriteRules,
+	/// Compare each response with the originally captured one
+	pub diff: bool,
+}
+
+/// Edits applied to each request before it is sent, e.g. to point captured
+/// production traffic at a staging host with staging credentials
+#[derive(Debug, Clone, Default)]
+pub struct RewriteRules {
+	/// `host[:port]` or `scheme://host[:port]`
+	pub host: Option<String>,
+	/// Replace (case-insensitively) or add these headers
+	pub set_headers: HashMap<String, String>,
+	/// Remove these headers, matched case-insensitively
+	pub strip_headers: Vec<String>,
+}
+
+impl RewriteRules {
+	pub fn apply(&self, request: &mut HttpRequestBuilder) -> Result<()> {
+		if let Some(ref host) = self.host {
+			request.url = rewrite_url(&request.url, host)?;
+			let authority = url::Url::parse(&request.url)
+				.ok()
+				.and_then(|url| url.host_str().map(|h| match url.port() {
+					Some(port) => format!("{}:{}", h, port),
+					None => h.to_string(),
+				}));
+			if let Some(authority) = authority {
+				for (name, value) in request.headers.iter_mut() {
+					if name.eq_ignore_ascii_case("host") {
+						*value = authority.clone();
+					}
+				}
+			}
+		}
+
+		request.headers.retain(|name, _| {
+			!self.strip_headers.iter().any(|strip| strip.eq_ignore_ascii_case(name))
+				&& !self.set_headers.keys().any(|set| set.eq_ignore_ascii_case(name))
+		});
+		request.headers.extend(self.set_headers.clone());
+		Ok(())
+	}
+}
+
+/// Swap the host (and port, and scheme if given) of `url`. Without an explicit
+/// port the scheme's default port is used.
+fn rewrite_url(url: &str, host: &str) -> Result<String> {
+	let mut url = url::Url::parse(url).map_err(|e| anyhow!("Cannot rewrite invalid URL '{}': {}", url, e))?;
+	let target = if host.contains("://") { host.to_string() } else { format!("{}://{}", url.scheme(), host) };
+	let target = url::Url::parse(&target).map_err(|e| anyhow!("Invalid --rewrite-host '{}': {}", host, e))?;
+	let new_host = target.host_str().ok_or_else(|| anyhow!("Invalid --rewrite-host '{}': no host", host))?;
+
+	url.set_scheme(target.scheme()).map_err(|_| anyhow!("Cannot switch '{}' to scheme {}", url, target.scheme()))?;
+	url.set_host(Some(new_host))?;
+	url.set_port(target.port()).map_err(|_| anyhow!("Cannot set a port on '{}'", url))?;
+	Ok(url.to_string())
+}
+
+/// Outcome of one send, as `replay --json` reports it
+#[derive(Debug, Clone, Serialize)]
+pub struct SendRecord {
+	/// 1-based position of the request in the replay set
+	pub request: usize,
+	/// 1-based repetition of the request
+	pub round: usize,
+	pub method: String,
+	pub url: String,
+	pub status: Option<u16>,
+	pub response_time_ms: Option<u64>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+	/// With `--diff`: a response that succeeded originally now fails
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub regression: Option<bool>,
+}
+
+impl SendRecord {
+	fn new(request: usize, round: usize, sent: &HttpRequestBuilder, result: &Result<HttpResponseInfo>, regression: Option<bool>) -> Self {
+		Self {
+			request,
+			round,
+			method: sent.method.clone(),
+			url: sent.url.clone(),
+			status: result.as_ref().ok().map(|response| response.status),
+			response_time_ms: result.as_ref().ok().map(|response| response.response_time_ms),
+			error: result.as_ref().err().map(|e| format!("{:#}", e)),
+			regression,
+		}
+	}
+}
+
+/// Announcement of a finished replay for the `[notifications]` channels, a
+/// warning when any send failed or regressed
+pub fn finished_notification(label: &str, sends: &[SendRecord], elapsed: Duration) -> Notification {
+	let count = |matches: fn(&SendRecord) -> bool| sends.iter().filter(|send| matches(send)).count();
+	let succeeded = count(|send| send.status.is_some_and(|status| status < 400));
+	let http_errors = count(|send| send.status.is_some_and(|status| status >= 400));
+	let failed = count(|send| send.status.is_none());
+	let regressions = count(|send| send.regression == Some(true));
+
+	let mut text = format!(
+		"🔁 {} finished in {}: {}/{} succeeded",
+		label,
+		crate::cache_analyzer::format_duration(elapsed.as_secs()),
+		succeeded,
+		sends.len()
+	);
+	for (n, what) in [(http_errors, "HTTP error(s)"), (failed, "failed"), (regressions, "regression(s)")] {
+		if n > 0 {
+			text.push_str(&format!(", {} {}", n, what));
+		}
+	}
+	let severity = if http_errors + failed + regressions > 0 { Severity::Warning } else { Severity::Info };
+	Notification::new("replay.finished", severity, text).with(&serde_json::json!({
+		"sent": sends.len(),
+		"succeeded": succeeded,
+		"http_errors": http_errors,
+		"failed": failed,
+		"regressions": regressions,
+		"duration_ms": elapsed.as_millis() as u64,
+	}))
+}
+
+/// One send of one request: `target` indexes the replay set, `round` is 1-based
+struct ReplayJob {
+	target: usize,
+	round: usize,
+	start_offset: Option<Duration>,
+}
+
+/// Replay `targets` and return every send in the order it finished
+pub async fn run(
+	mut targets: Vec<ReplayTarget>,
+	options: ReplayOptions,
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<Vec<SendRecord>> {
+	if options.shuffle {
+		targets.shuffle(&mut rand::thread_rng());
+	}
+
+	// Surface template errors (e.g. a missing environment variable) before anything is sent
+	for target in &targets {
+		prepare(&target.request, &options)?;
+	}
+
+	let paced = options.preserve_timing || options.rps.is_some() || options.max_rps.is_some() || options.respect_rate_limits;
+	let sends = if options.parallel <= 1 && !paced {
+		match options.mode {
+			ReplayMode::Sequential => run_sequential(&targets, &options, &http_client, &logger).await?,
+			ReplayMode::Interleaved => run_interleaved(&targets, &options, &http_client, &logger).await?,
+		}
+	} else {
+		run_scheduled(targets, &options, http_client, logger).await?
+	};
+
+	if options.diff {
+		match sends.iter().filter(|send| send.regression == Some(true)).count() {
+			0 => say!("\n✓ No regressions against the original responses"),
+			n => say!("\n⚠️  {} regression(s): responses that succeeded originally now fail", n),
+		}
+	}
+	say!("\n✓ Replay completed!");
+	Ok(sends)
+}
+
+/// The request as it should go out on this send: extra headers merged in,
+/// rewrite rules applied and placeholders expanded, so `{{uuid}}` and friends
+/// differ between sends
+fn prepare(request: &HttpRequestBuilder, options: &ReplayOptions) -> Result<HttpRequestBuilder> {
+	let mut request = request.clone();
+	request.headers.extend(options.headers.clone());
+	options.rewrite.apply(&mut request)?;
+	TemplateContext::new().expand_request(&request)
+}
+
+/// Diff `response` against the captured one, formatted for printing, and
+/// whether it is a regression
+fn compare_with_original(original: Option<&HttpResponseInfo>, response: &HttpResponseInfo) -> (String, bool) {
+	match original {
+		Some(original) => {
+			let diff = ResponseDiff::compare(original, response);
+			(diff.to_string(), diff.is_regression())
+		}
+		None => ("   (no original response to compare)\n".to_string(), false),
+	}
+}
+
+async fn run_sequential(
+	targets: &[ReplayTarget],
+	options: &ReplayOptions,
+	http_client: &HttpClient,
+	logger: &RequestLogger,
+) -> Result<Vec<SendRecord>> {
+	let (count, delay) = (options.count, options.delay);
+	let mut sends = Vec::new();
+
+	for (i, target) in targets.iter().enumerate() {
+		say!("\n=== Replaying Request {} ===", i + 1);
+		say!("{} {}", target.request.method, target.request.url);
+
+		for replay_num in 1..=count {
+			say!("Replay {}/{}", replay_num, count);
+
+			let request = prepare(&target.request, options)?;
+			let result = http_client.send_request(request.clone()).await;
+			let mut regression = None;
+			match &result {
+				Ok(response) => {
+					say!("✅ Response: {} ({}ms)", response.status, response.response_time_ms);
+					if options.diff {
+						let (diff, regressed) = compare_with_original(target.original.as_ref(), response);
+						say!("{}", diff.trim_end());
+						regression = Some(regressed);
+					}
+
+					if let Err(e) = logger.log_replay_request_response(&request, response).await {
+						error!("Failed to log replay: {}", e);
+					}
+				}
+				Err(e) => {
+					say!("❌ Error: {}", e);
+				}
+			}
+			sends.push(SendRecord::new(i + 1, replay_num, &request, &result, regression));
+
+			if replay_num < count && delay > 0 {
+				tokio::time::sleep(Duration::from_millis(delay)).await;
+			}
+		}
+
+		if i < targets.len() - 1 && delay > 0 {
+			tokio::time::sleep(Duration::from_millis(delay * 2)).await;
+		}
+	}
+
+	Ok(sends)
+}
+
+async fn run_interleaved(
+	targets: &[ReplayTarget],
+	options: &ReplayOptions,
+	http_client: &HttpClient,
+	logger: &RequestLogger,
+) -> Result<Vec<SendRecord>> {
+	let (count, delay) = (options.count, options.delay);
+	let mut sends = Vec::new();
+
+	// 按顺序轮流放出请求n次 (A1->B1->A2->B2)
+	say!("\n=== Replaying Requests in Interleaved Mode ===");
+
+	for replay_num in 1..=count {
+		say!("\n--- Replay Round {}/{} ---", replay_num, count);
+
+		for (i, target) in targets.iter().enumerate() {
+			say!("Request {}: {} {}", i + 1, target.request.method, target.request.url);
+
+			let request = prepare(&target.request, options)?;
+			let result = http_client.send_request(request.clone()).await;
+			let mut regression = None;
+			match &result {
+				Ok(response) => {
+					say!("✅ Response: {} ({}ms)", response.status, response.response_time_ms);
+					if options.diff {
+						let (diff, regressed) = compare_with_original(target.original.as_ref(), response);
+						say!("{}", diff.trim_end());
+						regression = Some(regressed);
+					}
+
+					if let Err(e) = logger.log_replay_request_response(&request, response).await {
+						error!("Failed to log replay: {}", e);
+					}
+				}
+				Err(e) => {
+					say!("❌ Error: {}", e);
+				}
+			}
+			sends.push(SendRecord::new(i + 1, replay_num, &request, &result, regression));
+
+			if i < targets.len() - 1 && delay > 0 {
+				tokio::time::sleep(Duration::from_millis(delay)).await;
+			}
+		}
+
+		if replay_num < count && delay > 0 {
+			tokio::time::sleep(Duration::from_millis(delay * 2)).await;
+		}
+	}
+
+	Ok(sends)
+}
+
+/// Build the send order for the chosen mode, attaching start offsets when the
+/// original capture timing should be reproduced.
+fn plan_jobs(targets: &[ReplayTarget], options: &ReplayOptions) -> Vec<ReplayJob> {
+	let mut jobs = Vec::with_capacity(targets.len() * options.count);
+
+	if options.preserve_timing {
+		// Gaps between consecutive captures, in capture order; with --shuffle the
+		// requests are permuted but the gap sequence stays the same
+		let mut captured: Vec<_> = targets.iter().map(|t| t.captured_at).collect();
+		captured.sort();
+		let offsets: Vec<Duration> = captured
+			.iter()
+			.map(|at| (*at - captured[0]).to_std().unwrap_or_default())
+			.collect();
+		let round_span = offsets.last().copied().unwrap_or_default() + Duration::from_millis(options.delay);
+
+		for round in 1..=options.count {
+			let round_start = round_span * (round as u32 - 1);
+			for (target, offset) in offsets.iter().enumerate() {
+				jobs.push(ReplayJob { target, round, start_offset: Some(round_start + *offset) });
+			}
+		}
+		return jobs;
+	}
+
+	match options.mode {
+		ReplayMode::Sequential => {
+			for target in 0..targets.len() {
+				for round in 1..=options.count {
+					jobs.push(ReplayJob { target, round, start_offset: None });
+				}
+			}
+		}
+		ReplayMode::Interleaved => {
+			for round in 1..=options.count {
+				for target in 0..targets.len() {
+					jobs.push(ReplayJob { target, round, start_offset: None });
+				}
+			}
+		}
+	}
+	jobs
+}
+
+/// Launch replays in planned order with at most `parallel` requests in flight,
+/// starting no more than `rps` per second when a rate is set
+async fn run_scheduled(
+	targets: Vec<ReplayTarget>,
+	options: &ReplayOptions,
+	http_client: Arc<HttpClient>,
+	logger: Arc<RequestLogger>,
+) -> Result<Vec<SendRecord>> {
+	let jobs = plan_jobs(&targets, options);
+	let parallel = options.parallel.max(1);
+	let ceiling = match (options.rps, options.max_rps) {
+		(Some(rps), Some(max_rps)) => Some(rps.min(max_rps)),
+		(rps, max_rps) => rps.or(max_rps),
+	};
+
+	let pacing = match ceiling {
+		Some(rps) if options.respect_rate_limits => format!("up to {} req/s, adapting to rate limits", rps),
+		Some(rps) => format!("{} req/s", rps),
+		None if options.respect_rate_limits => "adapting to rate limits".to_string(),
+		None if options.preserve_timing => "original timing".to_string(),
+		None => "as fast as possible".to_string(),
+	};
+	say!("\n=== Replaying {} sends (parallel: {}, {}) ===", jobs.len(), parallel, pacing);
+
+	// Delay rather than burst after a stall, so waiting on a permit never
+	// results in a catch-up spike above the requested rate
+	let adaptive = options.respect_rate_limits.then(|| Arc::new(Mutex::new(RateController::new(ceiling))));
+	let mut ticker = ceiling.filter(|_| adaptive.is_none()).map(|rps| {
+		let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / rps));
+		ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		ticker
+	});
+
+	let semaphore = Arc::new(Semaphore::new(parallel));
+	let started = Instant::now();
+	let mut tasks = JoinSet::new();
+
+	for job in jobs {
+		if let Some(offset) = job.start_offset {
+			tokio::time::sleep_until(started + offset).await;
+		}
+
+		let permit = semaphore.clone().acquire_owned().await?;
+		if let Some(ticker) = ticker.as_mut() {
+			ticker.tick().await;
+		}
+		if let Some(controller) = &adaptive {
+			// A throttled response may extend the pause while waiting
+			loop {
+				let next = controller.lock().unwrap().next_start(Instant::now());
+				let Some(at) = next else { break };
+				tokio::time::sleep_until(at).await;
+			}
+			controller.lock().unwrap().started(Instant::now());
+		}
+		let request = prepare(&targets[job.target].request, options)?;
+		let http_client = http_client.clone();
+		let logger = logger.clone();
+		let controller = adaptive.clone();
+		let label = format!("#{}.{}", job.target + 1, job.round);
+		let original = options.diff.then(|| targets[job.target].original.clone());
+
+		tasks.spawn(async move {
+			let _permit = permit;
+			let result = http_client.send_request(request.clone()).await;
+			let outcome = match &result {
+				Ok(response) => {
+					// One print per response so concurrent output is not interleaved
+					let (diff, regression) = match &original {
+						Some(original) => compare_with_original(original.as_ref(), response),
+						None => (String::new(), false),
+					};
+					let line = format!("✅ [{}] {} {} -> {} ({}ms)\n{}",
+						label, request.method, request.url, response.status, response.response_time_ms, diff);
+					say!("{}", line.trim_end());
+					if let Some(controller) = &controller {
+						controller.lock().unwrap().observe(response, Instant::now());
+					}
+
+					if let Err(e) = logger.log_replay_request_response(&request, response).await {
+						error!("Failed to log replay: {}", e);
+					}
+					Some(SendOutcome { status: response.status, latency_ms: response.response_time_ms, regression })
+				}
+				Err(e) => {
+					say!("❌ [{}] {} {} -> {}", label, request.method, request.url, e);
+					None
+				}
+			};
+			let regression = original.is_some().then(|| outcome.as_ref().is_some_and(|outcome| outcome.regression));
+			(SendRecord::new(job.target + 1, job.round, &request, &result, regression), outcome)
+		});
+	}
+
+	let mut summary = LoadSummary::default();
+	let mut sends = Vec::new();
+	while let Some(result) = tasks.join_next().await {
+		match result {
+			Ok((send, outcome)) => {
+				summary.record(outcome);
+				sends.push(send);
+			}
+			Err(e) => error!("Replay task panicked: {}", e),
+		}
+	}
+
+	let elapsed = started.elapsed();
+	summary.print(elapsed);
+	if let Some(controller) = adaptive {
+		controller.lock().unwrap().print(sends.len(), elapsed);
+	}
+	Ok(sends)
+}
+
+/// Sends in a row without throttling after which the rate is raised
+const CLEAN_WINDOW: usize = 10;
+/// Factor the rate is raised by after a clean window
+const RATE_INCREASE: f64 = 1.1;
+/// The rate is never lowered below this many starts per second
+const MIN_RPS: f64 = 0.1;
+/// Pause after a throttled response without Retry-After, doubled each time
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// Window the rate is measured over when none was set
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Adapts the start rate of a scheduled replay to the target's rate limiting:
+/// on 429, or 503 with Retry-After, new sends pause for Retry-After (or an
+/// exponential backoff) and the rate is halved; every `CLEAN_WINDOW` sends in
+/// a row that are not throttled raise it again, up to the ceiling
+struct RateController {
+	/// Current starts per second; `None` until the first throttle when no rate was set
+	rate: Option<f64>,
+	ceiling: Option<f64>,
+	paused_until: Option<Instant>,
+	backoff: Duration,
+	clean_streak: usize,
+	last_start: Option<Instant>,
+	recent_starts: VecDeque<Instant>,
+	throttled: usize,
+	paused: Duration,
+	/// Rates at which a throttle arrived
+	throttled_rates: Vec<f64>,
+	/// Rates that completed a clean window
+	clean_rates: Vec<f64>,
+}
+
+impl RateController {
+	fn new(ceiling: Option<f64>) -> Self {
+		Self {
+			rate: ceiling,
+			ceiling,
+			paused_until: None,
+			backoff: INITIAL_BACKOFF,
+			clean_streak: 0,
+			last_start: None,
+			recent_starts: VecDeque::new(),
+			throttled: 0,
+			paused: Duration::ZERO,
+			throttled_rates: Vec::new(),
+			clean_rates: Vec::new(),
+		}
+	}
+
+	/// When the next send may start, `None` if it may start now
+	fn next_start(&self, now: Instant) -> Option<Instant> {
+		let spaced = match (self.last_start, self.rate) {
+			(Some(last), Some(rate)) => Some(last + Duration::from_secs_f64(1.0 / rate)),
+			_ => None,
+		};
+		[self.paused_until, spaced].into_iter().flatten().max().filter(|at| *at > now)
+	}
+
+	fn started(&mut self, now: Instant) {
+		self.last_start = Some(now);
+		self.recent_starts.push_back(now);
+		while self.recent_starts.front().is_some_and(|start| now.duration_since(*start) > RATE_WINDOW) {
+			self.recent_starts.pop_front();
+		}
+	}
+
+	/// Starts per second over the last `RATE_WINDOW`
+	fn observed_rate(&self, now: Instant) -> Option<f64> {
+		let first = self.recent_starts.front()?;
+		let span = now.duration_since(*first).as_secs_f64().max(1.0);
+		Some(self.recent_starts.len() as f64 / span)
+	}
+
+	fn observe(&mut self, response: &HttpResponseInfo, now: Instant) {
+		let retry_after = response.headers.get("retry-after")
+			.and_then(|value| crate::http_client::parse_retry_after(value))
+			.map(Duration::from_millis);
+		let throttled = response.status == 429 || (response.status == 503 && retry_after.is_some());
+		if !throttled {
+			self.clean_streak += 1;
+			if self.clean_streak >= CLEAN_WINDOW {
+				self.clean_streak = 0;
+				self.backoff = INITIAL_BACKOFF;
+				if let Some(rate) = self.rate {
+					self.clean_rates.push(rate);
+					let raised = rate * RATE_INCREASE;
+					self.rate = Some(self.ceiling.map_or(raised, |ceiling| raised.min(ceiling)));
+				}
+			}
+			return;
+		}
+
+		self.throttled += 1;
+		self.clean_streak = 0;
+		// Responses to sends already in flight when the pause began belong
+		// to the same burst: only a longer Retry-After counts
+		if let Some(until) = self.paused_until.filter(|until| *until > now) {
+			if let Some(extended) = retry_after.map(|wait| now + wait).filter(|at| *at > until) {
+				self.paused += extended - until;
+				self.paused_until = Some(extended);
+			}
+			return;
+		}
+
+		let current = self.rate.or_else(|| self.observed_rate(now)).unwrap_or(1.0);
+		let lowered = (current / 2.0).max(MIN_RPS);
+		self.throttled_rates.push(current);
+		self.rate = Some(lowered);
+
+		let pause = retry_after.unwrap_or_else(|| {
+			let pause = self.backoff;
+			self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+			pause
+		});
+		self.paused_until = Some(now + pause);
+		self.paused += pause;
+		say!("⏸️  {} received, pausing {:.1}s and lowering the rate to {:.2} req/s", response.status, pause.as_secs_f64(), lowered);
+	}
+
+	/// The highest rate that completed a clean window below the lowest rate
+	/// that was throttled; `None` when nothing was throttled
+	fn sustainable_rate(&self) -> Option<f64> {
+		let lowest_throttled = self.throttled_rates.iter().copied().reduce(f64::min)?;
+		Some(self.clean_rates.iter().copied()
+			.filter(|rate| *rate < lowest_throttled)
+			.reduce(f64::max)
+			.unwrap_or_else(|| self.rate.unwrap_or(MIN_RPS)))
+	}
+
+	fn print(&self, sent: usize, elapsed: Duration) {
+		if self.throttled == 0 {
+			let reached = self.rate.unwrap_or(sent as f64 / elapsed.as_secs_f64().max(0.001));
+			say!("Rate limits: never throttled at up to {:.2} req/s", reached);
+			return;
+		}
+		say!("Rate limits: {} throttled responses, paused {:.1}s in total", self.throttled, self.paused.as_secs_f64());
+		if let Some(rate) = self.sustainable_rate() {
+			say!(
+				"Sustainable rate: ~{:.2} req/s (throttled at {:.2} req/s and above)",
+				rate,
+				self.throttled_rates.iter().copied().reduce(f64::min).unwrap_or(rate)
+			);
+		}
+	}
+}
+
+/// Result of one scheduled send that got a response
+struct SendOutcome {
+	status: u16,
+	latency_ms: u64,
+	regression: bool,
+}
+
+/// Aggregate results of a scheduled replay
+#[derive(Default)]
+struct LoadSummary {
+	latencies_ms: Vec<u64>,
+	/// Responses with a 4xx/5xx status
+	http_errors: usize,
+	/// Sends that got no response at all (connect failure, timeout, ...)
+	failed: usize,
+}
+
+impl LoadSummary {
+	fn record(&mut self, outcome: Option<SendOutcome>) {
+		match outcome {
+			Some(outcome) => {
+				if outcome.status >= 400 {
+					self.http_errors += 1;
+				}
+				self.latencies_ms.push(outcome.latency_ms);
+			}
+			None => self.failed += 1,
+		}
+	}
+
+	fn print(&mut self, elapsed: Duration) {
+		let total = self.latencies_ms.len() + self.failed;
+		let errors = self.failed + self.http_errors;
+		let secs = elapsed.as_secs_f64();
+
+		say!(
+			"\nSent {} requests in {:.2}s: {} succeeded, {} failed",
+			total,
+			secs,
+			self.latencies_ms.len(),
+			self.failed
+		);
+		if total == 0 {
+			return;
+		}
+
+		say!("Throughput: {:.2} req/s", total as f64 / secs.max(0.001));
+		say!(
+			"Error rate: {:.1}% ({} without response, {} with 4xx/5xx status)",
+			errors as f64 * 100.0 / total as f64,
+			self.failed,
+			self.http_errors
+		);
+
+		if !self.latencies_ms.is_empty() {
+			self.latencies_ms.sort_unstable();
+			let sorted = &self.latencies_ms;
+			say!(
+				"Latency (ms): min {} | p50 {} | p90 {} | p95 {} | p99 {} | max {}",
+				sorted[0],
+				percentile(sorted, 0.50),
+				percentile(sorted, 0.90),
+				percentile(sorted, 0.95),
+				percentile(sorted, 0.99),
+				sorted[sorted.len() - 1]
+			);
+		}
+	}
+}
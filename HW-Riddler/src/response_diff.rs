@@ -0,0 +1,223 @@
+use crate::http_client::HttpResponseInfo;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Headers that legitimately differ between two sends of the same request
+const VOLATILE_HEADERS: [&str; 13] = [
+	"date", "age", "expires", "last-modified", "etag", "set-cookie", "content-length",
+	"connection", "keep-alive", "transfer-encoding", "x-request-id", "cf-ray", "server-timing",
+];
+
+/// Logged bodies are cut at this many characters, see `logger.rs`
+const LOGGED_BODY_LIMIT: usize = 1000;
+const MAX_BODY_CHANGES: usize = 20;
+const MAX_VALUE_WIDTH: usize = 80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+	Added(String),
+	Removed(String),
+	Changed(String, String),
+}
+
+/// Differences between an original response and a replayed one
+#[derive(Debug, Default)]
+pub struct ResponseDiff {
+	pub status: Option<(u16, u16)>,
+	/// Keyed by lower-cased header name
+	pub headers: Vec<(String, Change)>,
+	/// Keyed by JSON path (`$.data[0].id`) or `line N` for non-JSON bodies
+	pub body: Vec<(String, Change)>,
+	/// A body was truncated in the log, so only the prefixes were compared
+	pub body_truncated: bool,
+}
+
+impl ResponseDiff {
+	pub fn compare(original: &HttpResponseInfo, replayed: &HttpResponseInfo) -> Self {
+		let mut diff = Self {
+			status: (original.status != replayed.status).then_some((original.status, replayed.status)),
+			headers: diff_headers(original, replayed),
+			..Default::default()
+		};
+
+		let truncated = |body: &str| body.chars().count() > LOGGED_BODY_LIMIT && body.ends_with("...");
+		if truncated(&original.body) || truncated(&replayed.body) {
+			diff.body_truncated = true;
+			let original: String = original.body.chars().take(LOGGED_BODY_LIMIT).collect();
+			let replayed: String = replayed.body.chars().take(LOGGED_BODY_LIMIT).collect();
+			diff_lines(&original, &replayed, &mut diff.body);
+		} else {
+			match (serde_json::from_str::<Value>(&original.body), serde_json::from_str::<Value>(&replayed.body)) {
+				(Ok(a), Ok(b)) => diff_json("$".to_string(), &a, &b, &mut diff.body),
+				_ => diff_lines(&original.body, &replayed.body, &mut diff.body),
+			}
+		}
+		diff
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.status.is_none() && self.headers.is_empty() && self.body.is_empty()
+	}
+
+	/// A response that succeeded originally but now fails
+	pub fn is_regression(&self) -> bool {
+		self.status.is_some_and(|(original, replayed)| original < 400 && replayed >= 400)
+	}
+
+	/// Drop changes of these headers (case-insensitive) and of these body
+	/// paths, including everything below them
+	pub fn ignore(&mut self, headers: &[String], paths: &[String]) {
+		self.headers.retain(|(name, _)| !headers.iter().any(|ignored| ignored.eq_ignore_ascii_case(name)));
+		self.body.retain(|(path, _)| {
+			!paths.iter().any(|ignored| {
+				path.strip_prefix(ignored.as_str())
+					.is_some_and(|rest| rest.is_empty() || rest.starts_with('.') || rest.starts_with('['))
+			})
+		});
+	}
+
+	/// Every difference as a flat list, for JSON output
+	pub fn records(&self) -> Vec<ChangeRecord> {
+		let mut records = Vec::new();
+		if let Some((a, b)) = self.status {
+			records.push(ChangeRecord { location: "status".to_string(), kind: "changed", a: Some(a.to_string()), b: Some(b.to_string()) });
+		}
+		let headers = self.headers.iter().map(|(name, change)| (format!("header {}", name), change));
+		let body = self.body.iter().map(|(path, change)| (format!("body {}", path), change));
+		for (location, change) in headers.chain(body) {
+			let (kind, a, b) = match change {
+				Change::Added(value) => ("added", None, Some(value.clone())),
+				Change::Removed(value) => ("removed", Some(value.clone()), None),
+				Change::Changed(old, new) => ("changed", Some(old.clone()), Some(new.clone())),
+			};
+			records.push(ChangeRecord { location, kind, a, b });
+		}
+		records
+	}
+}
+
+/// One difference, `a` and `b` being the values on either side
+#[derive(Debug, Serialize)]
+pub struct ChangeRecord {
+	pub location: String,
+	pub kind: &'static str,
+	pub a: Option<String>,
+	pub b: Option<String>,
+}
+
+impl fmt::Display for ResponseDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.is_empty() {
+			let note = if self.body_truncated { " (first 1000 body characters)" } else { "" };
+			return writeln!(f, "   = identical to original{}", note);
+		}
+
+		if let Some((original, replayed)) = self.status {
+			let flag = if self.is_regression() { "  ⚠️ REGRESSION" } else { "" };
+			writeln!(f, "   ~ status: {} → {}{}", original, replayed, flag)?;
+		}
+		for (name, change) in &self.headers {
+			write_change(f, &format!("header {}", name), change)?;
+		}
+		for (path, change) in self.body.iter().take(MAX_BODY_CHANGES) {
+			write_change(f, &format!("body {}", path), change)?;
+		}
+		if self.body.len() > MAX_BODY_CHANGES {
+			writeln!(f, "   … and {} more body changes", self.body.len() - MAX_BODY_CHANGES)?;
+		}
+		if self.body_truncated {
+			writeln!(f, "   (a body was truncated in the log; compared the first 1000 characters)")?;
+		}
+		Ok(())
+	}
+}
+
+fn write_change(f: &mut fmt::Formatter<'_>, label: &str, change: &Change) -> fmt::Result {
+	match change {
+		Change::Added(value) => writeln!(f, "   + {}: {}", label, shorten(value)),
+		Change::Removed(value) => writeln!(f, "   - {}: {}", label, shorten(value)),
+		Change::Changed(old, new) => writeln!(f, "   ~ {}: {} → {}", label, shorten(old), shorten(new)),
+	}
+}
+
+fn diff_headers(original: &HttpResponseInfo, replayed: &HttpResponseInfo) -> Vec<(String, Change)> {
+	let lower = |info: &HttpResponseInfo| -> BTreeMap<String, String> {
+		info.headers
+			.iter()
+			.map(|(k, v)| (k.to_lowercase(), v.clone()))
+			.filter(|(k, _)| !VOLATILE_HEADERS.contains(&k.as_str()))
+			.collect()
+	};
+	let (a, b) = (lower(original), lower(replayed));
+	let names: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+
+	names
+		.into_iter()
+		.filter_map(|name| {
+			let change = match (a.get(name), b.get(name)) {
+				(Some(old), Some(new)) if old != new => Change::Changed(old.clone(), new.clone()),
+				(Some(old), None) => Change::Removed(old.clone()),
+				(None, Some(new)) => Change::Added(new.clone()),
+				_ => return None,
+			};
+			Some((name.clone(), change))
+		})
+		.collect()
+}
+
+/// Structural comparison: object key order and whitespace do not matter
+fn diff_json(path: String, a: &Value, b: &Value, out: &mut Vec<(String, Change)>) {
+	match (a, b) {
+		(Value::Object(a), Value::Object(b)) => {
+			let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+			for key in keys {
+				let child = format!("{}.{}", path, key);
+				match (a.get(key), b.get(key)) {
+					(Some(x), Some(y)) => diff_json(child, x, y, out),
+					(Some(x), None) => out.push((child, Change::Removed(x.to_string()))),
+					(None, Some(y)) => out.push((child, Change::Added(y.to_string()))),
+					(None, None) => {}
+				}
+			}
+		}
+		(Value::Array(a), Value::Array(b)) => {
+			for i in 0..a.len().max(b.len()) {
+				let child = format!("{}[{}]", path, i);
+				match (a.get(i), b.get(i)) {
+					(Some(x), Some(y)) => diff_json(child, x, y, out),
+					(Some(x), None) => out.push((child, Change::Removed(x.to_string()))),
+					(None, Some(y)) => out.push((child, Change::Added(y.to_string()))),
+					(None, None) => {}
+				}
+			}
+		}
+		_ if a != b => out.push((path, Change::Changed(a.to_string(), b.to_string()))),
+		_ => {}
+	}
+}
+
+fn diff_lines(a: &str, b: &str, out: &mut Vec<(String, Change)>) {
+	if a == b {
+		return;
+	}
+	let (a, b): (Vec<&str>, Vec<&str>) = (a.lines().collect(), b.lines().collect());
+	for i in 0..a.len().max(b.len()) {
+		let label = format!("line {}", i + 1);
+		match (a.get(i), b.get(i)) {
+			(Some(x), Some(y)) if x != y => out.push((label, Change::Changed(x.to_string(), y.to_string()))),
+			(Some(x), None) => out.push((label, Change::Removed(x.to_string()))),
+			(None, Some(y)) => out.push((label, Change::Added(y.to_string()))),
+			_ => {}
+		}
+	}
+}
+
+fn shorten(value: &str) -> String {
+	if value.chars().count() > MAX_VALUE_WIDTH {
+		format!("{}…", value.chars().take(MAX_VALUE_WIDTH - 1).collect::<String>())
+	} else {
+		value.to_string()
+	}
+}
@@ -0,0 +1,250 @@
+use crate::mock::RequestMatcher;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::{debug, warn};
+
+/// A rewrite rules file as given to `proxy --rewrite`
+#[derive(Debug, Deserialize)]
+struct RewriteFile {
+	rewrites: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleConfig {
+	name: Option<String>,
+	#[serde(flatten)]
+	matcher: RequestMatcher,
+	url: Option<UrlConfig>,
+	#[serde(default)]
+	request_headers: HeaderConfig,
+	#[serde(default)]
+	response_headers: HeaderConfig,
+	status: Option<StatusConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UrlConfig {
+	/// New `host[:port]`
+	host: Option<String>,
+	/// New scheme, `http` or `https`
+	scheme: Option<String>,
+	/// Regex replaced in the full URL by `replace`, which may use `$1` groups
+	pattern: Option<String>,
+	#[serde(default)]
+	replace: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HeaderConfig {
+	/// Headers set, replacing every existing value
+	#[serde(default)]
+	set: BTreeMap<String, String>,
+	#[serde(default)]
+	remove: Vec<String>,
+	#[serde(default)]
+	replace: Vec<HeaderReplaceConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HeaderReplaceConfig {
+	name: String,
+	pattern: String,
+	#[serde(default)]
+	with: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusConfig {
+	/// Only this status is rewritten, any when missing
+	from: Option<u16>,
+	to: u16,
+}
+
+struct HeaderOps {
+	set: Vec<(String, String)>,
+	remove: Vec<String>,
+	replace: Vec<(String, Regex, String)>,
+}
+
+struct Rule {
+	name: String,
+	matcher: RequestMatcher,
+	host: Option<String>,
+	scheme: Option<String>,
+	url_pattern: Option<(Regex, String)>,
+	request_headers: HeaderOps,
+	response_headers: HeaderOps,
+	status: Option<StatusConfig>,
+}
+
+/// Match/replace rules the proxy applies to requests before forwarding them
+/// and to the responses before returning them. Every matching rule applies,
+/// in file order.
+pub struct RewriteRules {
+	rules: Vec<Rule>,
+}
+
+/// What the rules did to a request, to apply the response side later
+pub struct Applied {
+	rules: Vec<usize>,
+}
+
+impl RewriteRules {
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.with_context(|| format!("Unable to read rewrite rules file {}", path.display()))?;
+		let file: RewriteFile = serde_yaml::from_str(&content)
+			.with_context(|| format!("{} is not a valid rewrite rules file", path.display()))?;
+
+		let rules = file.rewrites
+			.into_iter()
+			.enumerate()
+			.map(|(i, config)| {
+				let name = config.name.unwrap_or_else(|| format!("rewrite #{}", i + 1));
+				let url = config.url;
+				let url_pattern = url.as_ref()
+					.and_then(|url| url.pattern.as_ref().map(|pattern| (pattern, url.replace.clone())))
+					.map(|(pattern, replace)| Ok::<_, anyhow::Error>((Regex::new(pattern)?, replace)))
+					.transpose()
+					.with_context(|| format!("Invalid url pattern in rewrite '{}'", name))?;
+				let scheme = url.as_ref().and_then(|url| url.scheme.clone());
+				if let Some(scheme) = &scheme
+					&& scheme != "http"
+					&& scheme != "https"
+				{
+					return Err(anyhow!("Invalid scheme '{}' in rewrite '{}': expected http or https", scheme, name));
+				}
+				if let Some(status) = &config.status
+					&& !(100..=999).contains(&status.to)
+				{
+					return Err(anyhow!("Invalid status {} in rewrite '{}'", status.to, name));
+				}
+				Ok(Rule {
+					request_headers: HeaderOps::compile(config.request_headers)
+						.with_context(|| format!("Invalid request_headers in rewrite '{}'", name))?,
+					response_headers: HeaderOps::compile(config.response_headers)
+						.with_context(|| format!("Invalid response_headers in rewrite '{}'", name))?,
+					host: url.as_ref().and_then(|url| url.host.clone()),
+					scheme,
+					url_pattern,
+					matcher: config.matcher,
+					status: config.status,
+					name,
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+		Ok(Self { rules })
+	}
+
+	pub fn len(&self) -> usize {
+		self.rules.len()
+	}
+
+	/// Where a plain CONNECT tunnel to `host:port` should go instead: the
+	/// `url.host` of the last rule that matches on the host alone
+	pub fn tunnel_target(&self, host: &str, port: u16) -> Option<(String, u16)> {
+		let rule = self.rules.iter()
+			.rev()
+			.find(|rule| rule.host.is_some() && rule.matcher.host_only() && rule.matcher.matches_host(host))?;
+		let target = rule.host.as_deref()?;
+		Some(match split_port(target) {
+			(name, Some(target_port)) => (name.to_string(), target_port),
+			(name, None) => (name.to_string(), port),
+		})
+	}
+
+	/// Rewrite the URL and headers of a request (header names in lower case)
+	pub fn apply_request(&self, method: &str, url: &mut String, headers: &mut HashMap<String, String>) -> Applied {
+		let Ok(parsed) = url::Url::parse(url) else {
+			return Applied { rules: Vec::new() };
+		};
+		let matched: Vec<usize> = (0..self.rules.len())
+			.filter(|&i| self.rules[i].matcher.matches(method, &parsed))
+			.collect();
+
+		for &i in &matched {
+			let rule = &self.rules[i];
+			if let Some((pattern, replace)) = &rule.url_pattern {
+				*url = pattern.replace_all(url, replace.as_str()).into_owned();
+			}
+			if rule.host.is_some() || rule.scheme.is_some() {
+				match rewrite_origin(url, rule.scheme.as_deref(), rule.host.as_deref()) {
+					Some(rewritten) => *url = rewritten,
+					None => warn!("Rewrite '{}' produced an invalid URL for {}", rule.name, url),
+				}
+			}
+
+			let mut list: Vec<(String, String)> = headers.drain().collect();
+			rule.request_headers.apply(&mut list);
+			headers.extend(list);
+			debug!("Rewrite '{}' applied to {} {}", rule.name, method, url);
+		}
+		Applied { rules: matched }
+	}
+
+	/// Rewrite the status and headers of the response to a request `applied` was
+	/// returned for
+	pub fn apply_response(&self, applied: &Applied, status: &mut u16, headers: &mut Vec<(String, String)>) {
+		for &i in &applied.rules {
+			let rule = &self.rules[i];
+			if let Some(rewrite) = &rule.status
+				&& rewrite.from.is_none_or(|from| from == *status)
+			{
+				*status = rewrite.to;
+			}
+			rule.response_headers.apply(headers);
+		}
+	}
+}
+
+impl HeaderOps {
+	fn compile(config: HeaderConfig) -> Result<Self> {
+		Ok(Self {
+			set: config.set.into_iter().map(|(name, value)| (name.to_lowercase(), value)).collect(),
+			remove: config.remove.into_iter().map(|name| name.to_lowercase()).collect(),
+			replace: config.replace
+				.into_iter()
+				.map(|replace| Ok((replace.name.to_lowercase(), Regex::new(&replace.pattern)?, replace.with)))
+				.collect::<Result<Vec<_>>>()?,
+		})
+	}
+
+	/// Remove, then replace within values, then set; `headers` has lower-case names
+	fn apply(&self, headers: &mut Vec<(String, String)>) {
+		headers.retain(|(name, _)| !self.remove.contains(name));
+		for (name, pattern, with) in &self.replace {
+			for (_, value) in headers.iter_mut().filter(|(header, _)| header == name) {
+				*value = pattern.replace_all(value, with.as_str()).into_owned();
+			}
+		}
+		for (name, value) in &self.set {
+			headers.retain(|(header, _)| header != name);
+			headers.push((name.clone(), value.clone()));
+		}
+	}
+}
+
+/// `url` with its scheme and/or `host[:port]` replaced
+fn rewrite_origin(url: &str, scheme: Option<&str>, host: Option<&str>) -> Option<String> {
+	let mut parsed = url::Url::parse(url).ok()?;
+	if let Some(host) = host {
+		let (name, port) = split_port(host);
+		parsed.set_host(Some(name)).ok()?;
+		parsed.set_port(port).ok()?;
+	}
+	if let Some(scheme) = scheme {
+		parsed.set_scheme(scheme).ok()?;
+	}
+	Some(parsed.to_string())
+}
+
+/// `host[:port]` as the host and the port, if one is given
+fn split_port(host: &str) -> (&str, Option<u16>) {
+	match host.rsplit_once(':').map(|(name, port)| (name, port.parse::<u16>())) {
+		Some((name, Ok(port))) => (name, Some(port)),
+		_ => (host, None),
+	}
+}
@@ -0,0 +1,507 @@
+use crate::http_client::HttpClient;
+use crate::network::{glob_match, HttpRequest};
+use crate::notify::{self, Notification, Notifier, Severity};
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// A rules file as given to `monitor --rules`
+#[derive(Debug, Deserialize)]
+struct RuleFile {
+	rules: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleConfig {
+	name: String,
+	/// Condition in the rule language, see [`Expr`]
+	when: String,
+	#[serde(default)]
+	severity: Severity,
+	/// Print the alert to stderr; on unless the rule has another action
+	console: Option<bool>,
+	/// POST the alert as JSON to this URL
+	webhook: Option<String>,
+	/// Run this shell command with the alert in `RIDDLER_*` variables
+	exec: Option<String>,
+	/// Also send the alert to the `[notifications]` channels of the config
+	#[serde(default = "default_notify")]
+	notify: bool,
+	/// Seconds during which the rule does not fire again
+	#[serde(default)]
+	cooldown: u64,
+}
+
+fn default_notify() -> bool {
+	true
+}
+
+struct Rule {
+	name: String,
+	condition: Expr,
+	severity: Severity,
+	console: bool,
+	webhook: Option<String>,
+	exec: Option<String>,
+	notify: bool,
+	cooldown: Duration,
+	last_fired: Option<Instant>,
+}
+
+/// Rules evaluated against every request the monitor handles
+pub struct RuleSet {
+	rules: Vec<Rule>,
+	notifier: Option<Notifier>,
+}
+
+/// What a rule reports when it fires, also the webhook body. `text` makes it
+/// show up as a message in Slack and Mattermost incoming webhooks.
+#[derive(Debug, Serialize)]
+struct Alert<'a> {
+	text: String,
+	rule: &'a str,
+	severity: Severity,
+	method: &'a str,
+	url: &'a str,
+	host: String,
+	source_ip: &'a str,
+	destination_ip: &'a str,
+	timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl RuleSet {
+	pub fn load(path: &Path) -> Result<Self> {
+		let content = std::fs::read_to_string(path)
+			.with_context(|| format!("Unable to read rules file {}", path.display()))?;
+		let file: RuleFile = serde_yaml::from_str(&content)
+			.with_context(|| format!("{} is not a valid rules file", path.display()))?;
+
+		let rules = file.rules
+			.into_iter()
+			.map(|config| {
+				let condition = Expr::parse(&config.when)
+					.with_context(|| format!("Invalid condition in rule '{}'", config.name))?;
+				Ok(Rule {
+					console: config.console.unwrap_or(config.webhook.is_none() && config.exec.is_none()),
+					name: config.name,
+					condition,
+					severity: config.severity,
+					webhook: config.webhook,
+					exec: config.exec,
+					notify: config.notify,
+					cooldown: Duration::from_secs(config.cooldown),
+					last_fired: None,
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+		Ok(Self { rules, notifier: None })
+	}
+
+	/// Send alerts of rules with `notify` on through `notifier`
+	pub fn with_notifier(mut self, notifier: Notifier) -> Self {
+		self.notifier = Some(notifier);
+		self
+	}
+
+	pub fn len(&self) -> usize {
+		self.rules.len()
+	}
+
+	/// Fire the actions of every rule `request` matches, returning how many
+	/// fired. Webhooks, commands and notifications run in the background so a
+	/// slow receiver does not hold up packet processing.
+	pub fn check(&mut self, request: &HttpRequest, http_client: &Arc<HttpClient>) -> usize {
+		let mut fired = 0;
+		for rule in &mut self.rules {
+			if !rule.condition.eval(request) {
+				continue;
+			}
+			if rule.last_fired.is_some_and(|last| last.elapsed() < rule.cooldown) {
+				continue;
+			}
+			rule.last_fired = Some(Instant::now());
+			fired += 1;
+
+			let alert = Alert {
+				text: format!("{} Rule '{}' matched {} {} from {}", rule.severity.icon(), rule.name,
+					request.method, request.url, request.source_ip),
+				rule: &rule.name,
+				severity: rule.severity,
+				method: &request.method,
+				url: &request.url,
+				host: request.host(),
+				source_ip: &request.source_ip,
+				destination_ip: &request.destination_ip,
+				timestamp: chrono::Utc::now(),
+			};
+
+			if rule.console {
+				eprintln!("{}", alert.text);
+			}
+			if let Some(webhook) = &rule.webhook {
+				match serde_json::to_value(&alert) {
+					Ok(body) => {
+						let (http_client, webhook) = (http_client.clone(), webhook.clone());
+						tokio::spawn(async move {
+							if let Err(e) = notify::post_json(&http_client, &webhook, &body).await {
+								warn!("Unable to deliver alert to {}: {}", webhook, e);
+							}
+						});
+					}
+					Err(e) => warn!("Unable to encode alert: {}", e),
+				}
+			}
+			if let Some(command) = &rule.exec {
+				run_command(command, &alert);
+			}
+			if rule.notify
+				&& let Some(notifier) = &self.notifier
+			{
+				notifier.spawn(Notification::new("monitor.rule", rule.severity, alert.text.clone()).with(&alert));
+			}
+		}
+		fired
+	}
+}
+
+fn run_command(command: &str, alert: &Alert<'_>) {
+	let mut process = if cfg!(windows) {
+		let mut process = tokio::process::Command::new("cmd");
+		process.args(["/C", command]);
+		process
+	} else {
+		let mut process = tokio::process::Command::new("sh");
+		process.args(["-c", command]);
+		process
+	};
+	process
+		.env("RIDDLER_RULE", alert.rule)
+		.env("RIDDLER_MESSAGE", &alert.text)
+		.env("RIDDLER_METHOD", alert.method)
+		.env("RIDDLER_URL", alert.url)
+		.env("RIDDLER_HOST", &alert.host)
+		.env("RIDDLER_SOURCE_IP", alert.source_ip)
+		.env("RIDDLER_DESTINATION_IP", alert.destination_ip);
+
+	let command = command.to_string();
+	tokio::spawn(async move {
+		match process.status().await {
+			Ok(status) if !status.success() => warn!("Alert command '{}' exited with {}", command, status),
+			Ok(_) => {}
+			Err(e) => warn!("Unable to run alert command '{}': {}", command, e),
+		}
+	});
+}
+
+/// A request attribute a condition tests
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+	Method,
+	Url,
+	Host,
+	Path,
+	Body,
+	SourceIp,
+	DestinationIp,
+	ResolvedHost,
+	/// ISO country code from `--geoip-db`
+	Country,
+	/// Lower-cased header name
+	Header(String),
+}
+
+impl Field {
+	fn value(&self, request: &HttpRequest) -> Option<String> {
+		let value = match self {
+			Field::Method => request.method.clone(),
+			Field::Url => request.url.clone(),
+			Field::Host => request.host(),
+			Field::Path => url::Url::parse(&request.url).map(|url| url.path().to_string()).unwrap_or_default(),
+			Field::Body => String::from_utf8_lossy(&request.body).into_owned(),
+			Field::SourceIp => request.source_ip.clone(),
+			Field::DestinationIp => request.destination_ip.clone(),
+			Field::ResolvedHost => request.resolved_host.clone()?,
+			Field::Country => request.geo.as_ref()?.country.clone()?,
+			Field::Header(name) => request.headers.get(name)?.clone(),
+		};
+		(!value.is_empty()).then_some(value)
+	}
+}
+
+#[derive(Debug)]
+enum Test {
+	Equals(String),
+	Contains(String),
+	StartsWith(String),
+	EndsWith(String),
+	Matches(Regex),
+	/// Any of the patterns, where `*` matches any characters
+	In(Vec<String>),
+	Present,
+}
+
+/// A rule condition. Tests compare a field with a value, ignoring case except
+/// in `matches`:
+///
+/// `method == POST && (path starts_with /admin || url contains debug=1)`
+/// `header authorization present && host !in [api.example.com, *.corp]`
+///
+/// Fields are `method`, `url`, `host`, `path`, `body`, `source_ip`,
+/// `destination_ip`, `resolved_host`, `country` and `header NAME`; tests are
+/// `==`, `!=`, `contains`, `starts_with`, `ends_with`, `matches` (a regex),
+/// `in [...]`, `!in [...]`, `present` and `absent`. Values with spaces or
+/// special characters go in double quotes.
+#[derive(Debug)]
+enum Expr {
+	And(Box<Expr>, Box<Expr>),
+	Or(Box<Expr>, Box<Expr>),
+	Not(Box<Expr>),
+	Test(Field, Test),
+}
+
+impl Expr {
+	fn parse(source: &str) -> Result<Expr> {
+		let tokens = tokenize(source)?;
+		let mut parser = Parser { tokens: &tokens, pos: 0 };
+		let expr = parser.or()?;
+		match parser.peek() {
+			None => Ok(expr),
+			Some(token) => Err(anyhow!("unexpected '{}'", token.text())),
+		}
+	}
+
+	fn eval(&self, request: &HttpRequest) -> bool {
+		match self {
+			Expr::And(left, right) => left.eval(request) && right.eval(request),
+			Expr::Or(left, right) => left.eval(request) || right.eval(request),
+			Expr::Not(inner) => !inner.eval(request),
+			Expr::Test(field, test) => {
+				let Some(value) = field.value(request) else {
+					return false;
+				};
+				let lower = value.to_lowercase();
+				match test {
+					Test::Equals(expected) => lower == *expected,
+					Test::Contains(part) => lower.contains(part.as_str()),
+					Test::StartsWith(prefix) => lower.starts_with(prefix.as_str()),
+					Test::EndsWith(suffix) => lower.ends_with(suffix.as_str()),
+					Test::Matches(regex) => regex.is_match(&value),
+					Test::In(patterns) => patterns.iter().any(|pattern| glob_match(pattern, &lower)),
+					Test::Present => true,
+				}
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+	And,
+	Or,
+	Not,
+	Open,
+	Close,
+	OpenList,
+	CloseList,
+	Comma,
+	Word(String),
+	/// A double-quoted value, never taken for a keyword
+	Quoted(String),
+}
+
+impl Token {
+	fn text(&self) -> &str {
+		match self {
+			Token::And => "&&",
+			Token::Or => "||",
+			Token::Not => "!",
+			Token::Open => "(",
+			Token::Close => ")",
+			Token::OpenList => "[",
+			Token::CloseList => "]",
+			Token::Comma => ",",
+			Token::Word(word) | Token::Quoted(word) => word,
+		}
+	}
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+	let mut tokens = Vec::new();
+	let mut chars = source.chars().peekable();
+	while let Some(&c) = chars.peek() {
+		match c {
+			_ if c.is_whitespace() => {
+				chars.next();
+			}
+			'(' | ')' | '[' | ']' | ',' => {
+				chars.next();
+				tokens.push(match c {
+					'(' => Token::Open,
+					')' => Token::Close,
+					'[' => Token::OpenList,
+					']' => Token::CloseList,
+					_ => Token::Comma,
+				});
+			}
+			'"' => {
+				chars.next();
+				let mut value = String::new();
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => value.extend(chars.next()),
+						Some(c) => value.push(c),
+						None => return Err(anyhow!("unterminated string in '{}'", source)),
+					}
+				}
+				tokens.push(Token::Quoted(value));
+			}
+			_ => {
+				let mut word = String::new();
+				while let Some(&c) = chars.peek() {
+					if c.is_whitespace() || "()[],\"".contains(c) {
+						break;
+					}
+					word.push(c);
+					chars.next();
+				}
+				match word.as_str() {
+					"&&" => tokens.push(Token::And),
+					"||" => tokens.push(Token::Or),
+					"!" => tokens.push(Token::Not),
+					"!=" | "!in" => tokens.push(Token::Word(word)),
+					// `!method == GET`
+					_ if word.starts_with('!') => {
+						tokens.push(Token::Not);
+						tokens.push(Token::Word(word[1..].to_string()));
+					}
+					_ => tokens.push(Token::Word(word)),
+				}
+			}
+		}
+	}
+	Ok(tokens)
+}
+
+struct Parser<'a> {
+	tokens: &'a [Token],
+	pos: usize,
+}
+
+impl Parser<'_> {
+	fn peek(&self) -> Option<&Token> {
+		self.tokens.get(self.pos)
+	}
+
+	fn next(&mut self) -> Result<&Token> {
+		let token = self.tokens.get(self.pos).ok_or_else(|| anyhow!("condition ends too early"))?;
+		self.pos += 1;
+		Ok(token)
+	}
+
+	fn expect(&mut self, expected: Token) -> Result<()> {
+		let token = self.next()?;
+		if *token != expected {
+			return Err(anyhow!("expected '{}', found '{}'", expected.text(), token.text()));
+		}
+		Ok(())
+	}
+
+	fn or(&mut self) -> Result<Expr> {
+		let mut expr = self.and()?;
+		while self.peek() == Some(&Token::Or) {
+			self.pos += 1;
+			expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+		}
+		Ok(expr)
+	}
+
+	fn and(&mut self) -> Result<Expr> {
+		let mut expr = self.unary()?;
+		while self.peek() == Some(&Token::And) {
+			self.pos += 1;
+			expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+		}
+		Ok(expr)
+	}
+
+	fn unary(&mut self) -> Result<Expr> {
+		match self.peek() {
+			Some(Token::Not) => {
+				self.pos += 1;
+				Ok(Expr::Not(Box::new(self.unary()?)))
+			}
+			Some(Token::Open) => {
+				self.pos += 1;
+				let expr = self.or()?;
+				self.expect(Token::Close)?;
+				Ok(expr)
+			}
+			_ => self.test(),
+		}
+	}
+
+	fn word(&mut self) -> Result<String> {
+		match self.next()? {
+			Token::Word(word) => Ok(word.clone()),
+			token => Err(anyhow!("expected a word, found '{}'", token.text())),
+		}
+	}
+
+	fn value(&mut self) -> Result<String> {
+		match self.next()? {
+			Token::Word(value) | Token::Quoted(value) => Ok(value.clone()),
+			token => Err(anyhow!("expected a value, found '{}'", token.text())),
+		}
+	}
+
+	fn test(&mut self) -> Result<Expr> {
+		let field = match self.word()?.to_lowercase().as_str() {
+			"method" => Field::Method,
+			"url" => Field::Url,
+			"host" => Field::Host,
+			"path" => Field::Path,
+			"body" => Field::Body,
+			"source_ip" => Field::SourceIp,
+			"destination_ip" => Field::DestinationIp,
+			"resolved_host" => Field::ResolvedHost,
+			"country" => Field::Country,
+			"header" => Field::Header(self.value()?.to_lowercase()),
+			other => return Err(anyhow!("unknown field '{}'", other)),
+		};
+
+		let operator = self.word()?.to_lowercase();
+		let test = |test| Expr::Test(field.clone(), test);
+		Ok(match operator.as_str() {
+			"==" => test(Test::Equals(self.value()?.to_lowercase())),
+			"!=" => Expr::Not(Box::new(test(Test::Equals(self.value()?.to_lowercase())))),
+			"contains" => test(Test::Contains(self.value()?.to_lowercase())),
+			"starts_with" => test(Test::StartsWith(self.value()?.to_lowercase())),
+			"ends_with" => test(Test::EndsWith(self.value()?.to_lowercase())),
+			"matches" => {
+				let pattern = self.value()?;
+				test(Test::Matches(Regex::new(&pattern).with_context(|| format!("invalid regex '{}'", pattern))?))
+			}
+			"in" => test(Test::In(self.list()?)),
+			"!in" => Expr::Not(Box::new(test(Test::In(self.list()?)))),
+			"present" => test(Test::Present),
+			"absent" => Expr::Not(Box::new(test(Test::Present))),
+			other => return Err(anyhow!("unknown test '{}'", other)),
+		})
+	}
+
+	fn list(&mut self) -> Result<Vec<String>> {
+		self.expect(Token::OpenList)?;
+		let mut values = vec![self.value()?.to_lowercase()];
+		while self.peek() == Some(&Token::Comma) {
+			self.pos += 1;
+			values.push(self.value()?.to_lowercase());
+		}
+		self.expect(Token::CloseList)?;
+		Ok(values)
+	}
+}
@@ -0,0 +1,214 @@
+use anyhow::{anyhow, Context, Result};
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, ImmutableString, Map, Scope, AST};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::{debug, info};
+
+/// A runaway hook fails after this many operations instead of hanging the request
+const MAX_OPERATIONS: u64 = 5_000_000;
+
+/// A request as `on_request` sees it: `#{ method, url, headers, body }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptRequest {
+	pub method: String,
+	pub url: String,
+	pub headers: HashMap<String, String>,
+	pub body: Vec<u8>,
+}
+
+/// A response as `on_response` sees it: `#{ status, headers, body }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptResponse {
+	pub status: u16,
+	pub headers: HashMap<String, String>,
+	pub body: Vec<u8>,
+}
+
+/// The `on_request` and `on_response` functions of a `--script` file. A hook
+/// gets the message as a map and returns it, changed or not; returning `()`
+/// keeps it as it was and `throw` aborts the exchange.
+pub struct ScriptHooks {
+	engine: Engine,
+	ast: AST,
+	has_on_request: bool,
+	has_on_response: bool,
+}
+
+impl ScriptHooks {
+	pub fn load(path: &Path) -> Result<Self> {
+		let engine = engine();
+		let ast = engine.compile_file(path.to_path_buf())
+			.map_err(|e| anyhow!("Unable to load script {}: {}", path.display(), e))?;
+
+		let defines = |name: &str| ast.iter_functions().any(|function| function.name == name);
+		let has_on_request = defines("on_request");
+		let has_on_response = defines("on_response");
+		if !has_on_request && !has_on_response {
+			return Err(anyhow!("Script {} defines neither on_request nor on_response", path.display()));
+		}
+
+		// Top-level statements run once, e.g. to log that the script is active
+		engine.run_ast(&ast).map_err(|e| anyhow!("Script {} failed: {}", path.display(), e))?;
+		info!("Loaded script {} (on_request: {}, on_response: {})", path.display(), has_on_request, has_on_response);
+		Ok(Self { engine, ast, has_on_request, has_on_response })
+	}
+
+	/// Run `on_request`, applying what it returns to `request`
+	pub fn on_request(&self, request: &mut ScriptRequest) -> Result<()> {
+		if !self.has_on_request {
+			return Ok(());
+		}
+		let returned = self.call("on_request", (request_map(request),))?;
+		if let Some(map) = returned {
+			apply_request(&map, request)?;
+		}
+		Ok(())
+	}
+
+	/// Run `on_response` for the answer to `request`, applying what it returns
+	/// to `response`
+	pub fn on_response(&self, request: &ScriptRequest, response: &mut ScriptResponse) -> Result<()> {
+		if !self.has_on_response {
+			return Ok(());
+		}
+		let returned = self.call("on_response", (request_map(request), response_map(response)))?;
+		if let Some(map) = returned {
+			apply_response(&map, response)?;
+		}
+		Ok(())
+	}
+
+	/// The map `hook` returned, or `None` for `()`
+	fn call(&self, hook: &str, args: impl rhai::FuncArgs) -> Result<Option<Map>> {
+		// Top-level statements already ran once in `load`
+		let options = CallFnOptions::new().eval_ast(false);
+		let result: Dynamic = self.engine.call_fn_with_options(options, &mut Scope::new(), &self.ast, hook, args)
+			.map_err(|e| match *e {
+				EvalAltResult::ErrorRuntime(reason, _) => anyhow!("Aborted by script {}: {}", hook, reason),
+				e => anyhow!("Script {} failed: {}", hook, e),
+			})?;
+		if result.is_unit() {
+			return Ok(None);
+		}
+		let type_name = result.type_name();
+		result.try_cast::<Map>()
+			.map(Some)
+			.ok_or_else(|| anyhow!("Script {} must return the map it was given or (), not {}", hook, type_name))
+	}
+}
+
+fn engine() -> Engine {
+	let mut engine = Engine::new();
+	engine.set_max_operations(MAX_OPERATIONS);
+	// stdout may carry the result of the command, so script output goes to stderr
+	engine.on_print(|text| eprintln!("[script] {}", text));
+	engine.on_debug(|text, _, position| debug!("[script] {} at {}", text, position));
+
+	engine.register_fn("env", |name: &str| -> Dynamic {
+		std::env::var(name).map(Dynamic::from).unwrap_or(Dynamic::UNIT)
+	});
+	engine.register_fn("read_file", |path: &str| -> Result<String, Box<EvalAltResult>> {
+		std::fs::read_to_string(path)
+			.map(|content| content.trim_end().to_string())
+			.map_err(|e| format!("Unable to read {}: {}", path, e).into())
+	});
+	engine.register_fn("timestamp", || chrono::Utc::now().timestamp());
+	engine.register_fn("timestamp_ms", || chrono::Utc::now().timestamp_millis());
+	engine.register_fn("sha256", |data: &str| format!("{:x}", Sha256::digest(data)));
+	engine.register_fn("hmac_sha256", |key: &str, data: &str| {
+		let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+		mac.update(data.as_bytes());
+		format!("{:x}", mac.finalize().into_bytes())
+	});
+	engine.register_fn("base64_encode", |data: &str| base64::engine::general_purpose::STANDARD.encode(data));
+	engine.register_fn("base64_decode", |data: &str| -> Result<String, Box<EvalAltResult>> {
+		base64::engine::general_purpose::STANDARD.decode(data.trim())
+			.map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+			.map_err(|e| format!("Invalid base64: {}", e).into())
+	});
+	engine
+}
+
+fn headers_map(headers: &HashMap<String, String>) -> Map {
+	headers.iter().map(|(name, value)| (name.as_str().into(), Dynamic::from(value.clone()))).collect()
+}
+
+fn request_map(request: &ScriptRequest) -> Map {
+	let mut map = Map::new();
+	map.insert("method".into(), request.method.clone().into());
+	map.insert("url".into(), request.url.clone().into());
+	map.insert("headers".into(), headers_map(&request.headers).into());
+	map.insert("body".into(), String::from_utf8_lossy(&request.body).into_owned().into());
+	map
+}
+
+fn response_map(response: &ScriptResponse) -> Map {
+	let mut map = Map::new();
+	map.insert("status".into(), (response.status as i64).into());
+	map.insert("headers".into(), headers_map(&response.headers).into());
+	map.insert("body".into(), String::from_utf8_lossy(&response.body).into_owned().into());
+	map
+}
+
+fn apply_request(map: &Map, request: &mut ScriptRequest) -> Result<()> {
+	if let Some(method) = map.get("method") {
+		request.method = string_field(method, "method")?.to_uppercase();
+	}
+	if let Some(url) = map.get("url") {
+		request.url = string_field(url, "url")?;
+	}
+	if let Some(headers) = map.get("headers") {
+		request.headers = headers_field(headers)?;
+	}
+	if let Some(body) = map.get("body") {
+		apply_body(body, &mut request.body)?;
+	}
+	Ok(())
+}
+
+fn apply_response(map: &Map, response: &mut ScriptResponse) -> Result<()> {
+	if let Some(status) = map.get("status") {
+		response.status = status.as_int().ok()
+			.and_then(|status| u16::try_from(status).ok())
+			.filter(|status| (100..=999).contains(status))
+			.context("Script set response status to something other than a status code")?;
+	}
+	if let Some(headers) = map.get("headers") {
+		response.headers = headers_field(headers)?;
+	}
+	if let Some(body) = map.get("body") {
+		apply_body(body, &mut response.body)?;
+	}
+	Ok(())
+}
+
+/// Replace `body` only if the script changed its text, so binary bodies that
+/// are not valid UTF-8 pass through untouched
+fn apply_body(value: &Dynamic, body: &mut Vec<u8>) -> Result<()> {
+	if value.is_unit() {
+		body.clear();
+		return Ok(());
+	}
+	let text = string_field(value, "body")?;
+	if text != String::from_utf8_lossy(body) {
+		*body = text.into_bytes();
+	}
+	Ok(())
+}
+
+fn string_field(value: &Dynamic, field: &str) -> Result<String> {
+	value.clone().into_immutable_string()
+		.map(|text: ImmutableString| text.to_string())
+		.map_err(|type_name| anyhow!("Script set {} to a {}, expected a string", field, type_name))
+}
+
+fn headers_field(value: &Dynamic) -> Result<HashMap<String, String>> {
+	let headers = value.read_lock::<Map>().context("Script set headers to something other than a map")?;
+	headers.iter()
+		.filter(|(_, value)| !value.is_unit())
+		.map(|(name, value)| Ok((name.to_string(), value.to_string())))
+		.collect()
+}
@@ -0,0 +1,260 @@
+use crate::logger::RequestLogEntry;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+	Low,
+	Medium,
+	High,
+	Critical,
+}
+
+impl std::fmt::Display for Severity {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Severity::Low => "LOW",
+			Severity::Medium => "MEDIUM",
+			Severity::High => "HIGH",
+			Severity::Critical => "CRITICAL",
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeakKind {
+	BasicAuth,
+	BearerToken,
+	ApiKey,
+	Password,
+	SessionCookie,
+	/// A value in the shape of a well-known provider's secret, e.g. an AWS
+	/// access key id
+	KnownSecret(&'static str),
+}
+
+impl LeakKind {
+	/// Severity of the leak when it travels over plaintext HTTP
+	fn plaintext_severity(self) -> Severity {
+		match self {
+			LeakKind::BasicAuth | LeakKind::Password => Severity::Critical,
+			LeakKind::BearerToken | LeakKind::ApiKey | LeakKind::KnownSecret(_) => Severity::High,
+			LeakKind::SessionCookie => Severity::Medium,
+		}
+	}
+}
+
+impl std::fmt::Display for LeakKind {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			LeakKind::BasicAuth => f.write_str("Basic auth credentials"),
+			LeakKind::BearerToken => f.write_str("Bearer token"),
+			LeakKind::ApiKey => f.write_str("API key"),
+			LeakKind::Password => f.write_str("Password field"),
+			LeakKind::SessionCookie => f.write_str("Session cookie"),
+			LeakKind::KnownSecret(name) => f.write_str(name),
+		}
+	}
+}
+
+/// All occurrences of one kind of leak towards one host
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+	pub kind: LeakKind,
+	pub severity: Severity,
+	pub host: String,
+	/// Sent unencrypted, readable by anyone on the path
+	pub plaintext: bool,
+	/// Where the secret was found, e.g. `header authorization` or `body field password`
+	pub locations: Vec<String>,
+	pub count: usize,
+	pub example_url: String,
+	/// Basic auth user names; never the secrets themselves
+	pub users: Vec<String>,
+	pub first_seen: DateTime<Utc>,
+	pub last_seen: DateTime<Utc>,
+}
+
+const API_KEY_HEADERS: &[&str] = &["x-api-key", "api-key", "apikey", "x-auth-token", "x-access-token", "private-token"];
+const API_KEY_PARAMS: &[&str] = &["api_key", "apikey", "api-key", "access_token", "token", "key", "secret", "client_secret"];
+const PASSWORD_FIELDS: &[&str] = &["password", "passwd", "pwd", "pass", "passphrase"];
+const SESSION_COOKIE_WORDS: &[&str] = &["sess", "token", "auth", "jwt", "sid"];
+
+/// `name=value` form fields and `"name": value` JSON members of a body
+static BODY_FIELD: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(r#"(?i)(?:^|[&{,\s])["']?([\w.-]+)["']?\s*[:=]\s*["']?([^"'&,}\s]*)"#).expect("valid regex")
+});
+
+static KNOWN_SECRETS: Lazy<Vec<(&'static str, Regex)>> = Lazy::new(|| {
+	[
+		("AWS access key", r"\bAKIA[0-9A-Z]{16}\b"),
+		("GitHub token", r"\bgh[pousr]_[A-Za-z0-9]{36,}\b"),
+		("Slack token", r"\bxox[abposr]-[A-Za-z0-9-]{10,}\b"),
+		("Stripe secret key", r"\b[sr]k_live_[A-Za-z0-9]{20,}\b"),
+		("Private key", r"-----BEGIN [A-Z ]*PRIVATE KEY-----"),
+	]
+	.into_iter()
+	.map(|(name, pattern)| (name, Regex::new(pattern).expect("valid regex")))
+	.collect()
+});
+
+/// Credentials and secrets in the requests of `entries`, grouped by kind and
+/// host, most severe first. Leaks over HTTPS are reported as low severity:
+/// the network path is safe but the secret still ended up in the log.
+pub fn scan(entries: &[RequestLogEntry]) -> Vec<Finding> {
+	let mut findings: HashMap<(LeakKind, String, bool), Finding> = HashMap::new();
+
+	for entry in entries {
+		let request = &entry.request;
+		let url = url::Url::parse(&request.url).ok();
+		let plaintext = url.as_ref().is_some_and(|url| url.scheme() == "http");
+		let host = url.as_ref().and_then(|url| url.host_str()).unwrap_or("unknown").to_lowercase();
+
+		for (kind, location, user) in leaks(entry, url.as_ref()) {
+			let finding = findings
+				.entry((kind, host.clone(), plaintext))
+				.or_insert_with(|| Finding {
+					kind,
+					severity: if plaintext { kind.plaintext_severity() } else { Severity::Low },
+					host: host.clone(),
+					plaintext,
+					locations: Vec::new(),
+					count: 0,
+					example_url: request.url.split('?').next().unwrap_or_default().to_string(),
+					users: Vec::new(),
+					first_seen: entry.timestamp,
+					last_seen: entry.timestamp,
+				});
+			finding.count += 1;
+			finding.first_seen = finding.first_seen.min(entry.timestamp);
+			finding.last_seen = finding.last_seen.max(entry.timestamp);
+			if !finding.locations.contains(&location) {
+				finding.locations.push(location);
+			}
+			if let Some(user) = user
+				&& !finding.users.contains(&user)
+			{
+				finding.users.push(user);
+			}
+		}
+	}
+
+	let mut findings: Vec<Finding> = findings.into_values().collect();
+	findings.sort_by(|a, b| b.severity.cmp(&a.severity).then(b.count.cmp(&a.count)).then_with(|| a.host.cmp(&b.host)));
+	findings
+}
+
+/// Every leak in one entry as (kind, location, Basic auth user)
+fn leaks(entry: &RequestLogEntry, url: Option<&url::Url>) -> Vec<(LeakKind, String, Option<String>)> {
+	let request = &entry.request;
+	let mut leaks = Vec::new();
+
+	for (name, value) in &request.headers {
+		let name = name.to_lowercase();
+		let location = format!("header {}", name);
+		if name == "authorization" || name == "proxy-authorization" {
+			match value.split_once(' ') {
+				Some((scheme, credentials)) if scheme.eq_ignore_ascii_case("basic") => {
+					leaks.push((LeakKind::BasicAuth, location, basic_auth_user(credentials)));
+				}
+				Some((scheme, _)) if scheme.eq_ignore_ascii_case("bearer") => {
+					leaks.push((LeakKind::BearerToken, location, None));
+				}
+				_ => leaks.push((LeakKind::ApiKey, location, None)),
+			}
+		} else if API_KEY_HEADERS.contains(&name.as_str()) {
+			leaks.push((LeakKind::ApiKey, location, None));
+		} else if name == "cookie" {
+			for cookie in value.split(';').filter_map(|pair| pair.split_once('=')).map(|(name, _)| name.trim().to_lowercase()) {
+				if SESSION_COOKIE_WORDS.iter().any(|word| cookie.contains(word)) {
+					leaks.push((LeakKind::SessionCookie, format!("cookie {}", cookie), None));
+				}
+			}
+		}
+	}
+
+	if let Some(url) = url {
+		for (name, value) in url.query_pairs() {
+			let name = name.to_lowercase();
+			if value.is_empty() {
+				continue;
+			}
+			if PASSWORD_FIELDS.contains(&name.as_str()) {
+				leaks.push((LeakKind::Password, format!("query parameter {}", name), None));
+			} else if API_KEY_PARAMS.contains(&name.as_str()) {
+				leaks.push((LeakKind::ApiKey, format!("query parameter {}", name), None));
+			}
+		}
+	}
+
+	for captures in BODY_FIELD.captures_iter(&request.body_preview) {
+		let name = captures[1].to_lowercase();
+		if !captures[2].is_empty() && PASSWORD_FIELDS.iter().any(|field| name == *field || name.ends_with(&format!("_{}", field))) {
+			leaks.push((LeakKind::Password, format!("body field {}", name), None));
+		}
+	}
+
+	let texts = [request.url.as_str(), request.body_preview.as_str()];
+	for (name, pattern) in KNOWN_SECRETS.iter() {
+		if texts.iter().any(|text| pattern.is_match(text))
+			|| request.headers.values().any(|value| pattern.is_match(value))
+		{
+			leaks.push((LeakKind::KnownSecret(name), "url, body or headers".to_string(), None));
+		}
+	}
+
+	leaks
+}
+
+/// User name of `Basic` credentials; `None` when they were redacted
+fn basic_auth_user(credentials: &str) -> Option<String> {
+	let decoded = base64::engine::general_purpose::STANDARD.decode(credentials.trim()).ok()?;
+	let decoded = String::from_utf8(decoded).ok()?;
+	decoded.split_once(':').map(|(user, _)| user.to_string())
+}
+
+pub fn print_report(findings: &[Finding], entries: usize) {
+	println!("=== Security Report ({} log entries) ===", entries);
+	if findings.is_empty() {
+		println!("✅ No credentials or secrets found");
+		return;
+	}
+
+	let mut totals: Vec<(Severity, usize)> = Vec::new();
+	for finding in findings {
+		match totals.iter_mut().find(|(severity, _)| *severity == finding.severity) {
+			Some((_, count)) => *count += finding.count,
+			None => totals.push((finding.severity, finding.count)),
+		}
+	}
+	println!("{}\n", totals.iter().map(|(severity, count)| format!("{}: {}", severity, count)).collect::<Vec<_>>().join("  "));
+
+	for finding in findings {
+		let icon = match finding.severity {
+			Severity::Critical => "🚨",
+			Severity::High => "🔴",
+			Severity::Medium => "🟠",
+			Severity::Low => "🔵",
+		};
+		println!("{} [{}] {} sent to {} over {} ({} request{})",
+			icon, finding.severity, finding.kind, finding.host,
+			if finding.plaintext { "plaintext HTTP" } else { "HTTPS" },
+			finding.count, if finding.count == 1 { "" } else { "s" });
+		println!("   in: {}", finding.locations.join(", "));
+		if !finding.users.is_empty() {
+			println!("   users: {}", finding.users.join(", "));
+		}
+		println!("   e.g. {}  ({} – {})", finding.example_url,
+			finding.first_seen.format("%Y-%m-%d %H:%M:%S"), finding.last_seen.format("%Y-%m-%d %H:%M:%S"));
+	}
+
+	if findings.iter().any(|finding| finding.plaintext) {
+		println!("\nCredentials sent over plaintext HTTP can be read by anyone on the network path; move these endpoints to HTTPS and rotate the exposed secrets.");
+	}
+}
@@ -0,0 +1,201 @@
+use crate::logger::RequestLogEntry;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Longest path shown on an arrow; diagrams become unreadable beyond that
+const MAX_LABEL: usize = 60;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SequenceFormat {
+	Mermaid,
+	#[value(name = "plantuml")]
+	PlantUml,
+}
+
+impl SequenceFormat {
+	/// PlantUML for `.puml`/`.plantuml`/`.pu`, Mermaid otherwise
+	pub fn from_path(path: &Path) -> Self {
+		let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_ascii_lowercase();
+		match extension.as_str() {
+			"puml" | "plantuml" | "pu" => SequenceFormat::PlantUml,
+			_ => SequenceFormat::Mermaid,
+		}
+	}
+}
+
+/// One request and, when captured, its response
+struct Exchange {
+	start: DateTime<Utc>,
+	client: usize,
+	host: usize,
+	label: String,
+	/// Status and duration of the response
+	response: Option<(u16, u64)>,
+}
+
+/// Clients and hosts in order of first appearance, clients first
+struct Participants {
+	names: Vec<String>,
+	clients: usize,
+}
+
+impl Participants {
+	fn index(&mut self, name: &str) -> usize {
+		self.names.iter().position(|n| n == name).unwrap_or_else(|| {
+			self.names.push(name.to_string());
+			self.names.len() - 1
+		})
+	}
+}
+
+/// Who sent the request: the client address seen by the monitor or proxy,
+/// or Riddler itself for `request` and `replay`
+fn client_name(entry: &RequestLogEntry) -> String {
+	match entry.request.source_ip.as_str() {
+		"" | "manual" | "replay" => format!("Riddler ({})", entry.source),
+		ip => ip.to_string(),
+	}
+}
+
+/// `host[:port]` and `path[?…]` of the request URL
+fn host_and_path(url: &str) -> (String, String) {
+	match url::Url::parse(url) {
+		Ok(parsed) => {
+			let host = match parsed.port() {
+				Some(port) => format!("{}:{}", parsed.host_str().unwrap_or("?"), port),
+				None => parsed.host_str().unwrap_or("?").to_string(),
+			};
+			let path = if parsed.query().is_some() { format!("{}?…", parsed.path()) } else { parsed.path().to_string() };
+			(host, path)
+		}
+		Err(_) => ("?".to_string(), url.to_string()),
+	}
+}
+
+/// Render the chronological request/response flow between clients and hosts
+/// as a sequence diagram and write it to `output`; Mermaid is wrapped in a
+/// code fence when the file ends in `.md`. Returns the number of requests.
+pub fn export(entries: &[RequestLogEntry], output: &Path, format: SequenceFormat) -> Result<usize> {
+	if entries.is_empty() {
+		return Err(anyhow!("No log entries in the selected window"));
+	}
+
+	// Clients first so they end up on the left of every host
+	let mut participants = Participants { names: Vec::new(), clients: 0 };
+	let mut sorted: Vec<&RequestLogEntry> = entries.iter().collect();
+	// Entries are logged once the response arrives; order by when the request started
+	let start = |entry: &RequestLogEntry| {
+		entry.timestamp - Duration::milliseconds(entry.response.as_ref().map_or(0, |r| r.response_time_ms) as i64)
+	};
+	sorted.sort_by_key(|entry| start(entry));
+	for entry in &sorted {
+		participants.index(&client_name(entry));
+	}
+	participants.clients = participants.names.len();
+
+	let exchanges: Vec<Exchange> = sorted.iter()
+		.map(|entry| {
+			let (host, path) = host_and_path(&entry.request.url);
+			Exchange {
+				start: start(entry),
+				client: participants.index(&client_name(entry)),
+				host: participants.index(&host),
+				label: truncate(&format!("{} {}", entry.request.method, path), MAX_LABEL),
+				response: entry.response.as_ref().map(|r| (r.status, r.response_time_ms)),
+			}
+		})
+		.collect();
+
+	let content = match format {
+		SequenceFormat::Mermaid => {
+			let diagram = render_mermaid(&participants, &exchanges);
+			let is_markdown = output.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("md"));
+			if is_markdown { format!("```mermaid\n{}```\n", diagram) } else { diagram }
+		}
+		SequenceFormat::PlantUml => render_plantuml(&participants, &exchanges),
+	};
+
+	std::fs::write(output, content)
+		.map_err(|e| anyhow!("Unable to write {}: {}", output.display(), e))?;
+	Ok(exchanges.len())
+}
+
+fn title(exchanges: &[Exchange]) -> String {
+	let first = exchanges.first().map(|e| e.start.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default();
+	let last = exchanges.last().map(|e| e.start.format("%H:%M:%S").to_string()).unwrap_or_default();
+	format!("Riddler: {} requests, {} - {} UTC", exchanges.len(), first, last)
+}
+
+fn render_mermaid(participants: &Participants, exchanges: &[Exchange]) -> String {
+	let mut out = String::from("sequenceDiagram\n");
+	let _ = writeln!(out, "\ttitle {}", mermaid_text(&title(exchanges)));
+	out.push_str("\tautonumber\n");
+	for (i, name) in participants.names.iter().enumerate() {
+		let kind = if i < participants.clients { "actor" } else { "participant" };
+		let _ = writeln!(out, "\t{} P{} as {}", kind, i, mermaid_text(name));
+	}
+	for exchange in exchanges {
+		let (client, host) = (exchange.client, exchange.host);
+		match exchange.response {
+			Some((status, ms)) => {
+				let _ = writeln!(out, "\tP{}->>P{}: {}", client, host, mermaid_text(&exchange.label));
+				let _ = writeln!(out, "\tP{}-->>P{}: {} ({} ms)", host, client, status, ms);
+			}
+			// No response captured: a lost message
+			None => {
+				let _ = writeln!(out, "\tP{}-xP{}: {}", client, host, mermaid_text(&exchange.label));
+			}
+		}
+	}
+	out
+}
+
+fn render_plantuml(participants: &Participants, exchanges: &[Exchange]) -> String {
+	let mut out = String::from("@startuml\n");
+	let _ = writeln!(out, "title {}", title(exchanges));
+	out.push_str("autonumber\n");
+	for (i, name) in participants.names.iter().enumerate() {
+		let kind = if i < participants.clients { "actor" } else { "participant" };
+		let _ = writeln!(out, "{} \"{}\" as P{}", kind, name.replace('"', "'"), i);
+	}
+	for exchange in exchanges {
+		let (client, host) = (exchange.client, exchange.host);
+		match exchange.response {
+			Some((status, ms)) => {
+				let _ = writeln!(out, "P{} -> P{} : {}", client, host, plantuml_text(&exchange.label));
+				let _ = writeln!(out, "P{} --> P{} : {} ({} ms)", host, client, status, ms);
+			}
+			None => {
+				let _ = writeln!(out, "P{} ->x P{} : {}", client, host, plantuml_text(&exchange.label));
+			}
+		}
+	}
+	out.push_str("@enduml\n");
+	out
+}
+
+/// Mermaid ends statements at `;` and reads `#…;` as entity codes
+fn mermaid_text(text: &str) -> String {
+	text.chars()
+		.map(|c| match c {
+			'#' => "#35;".to_string(),
+			';' => "#59;".to_string(),
+			c => c.to_string(),
+		})
+		.collect()
+}
+
+/// A backslash followed by `n` would start a new line in the label
+fn plantuml_text(text: &str) -> String {
+	text.replace('\\', "\\\\")
+}
+
+fn truncate(text: &str, max: usize) -> String {
+	if text.chars().count() > max {
+		format!("{}…", text.chars().take(max - 1).collect::<String>())
+	} else {
+		text.to_string()
+	}
+}
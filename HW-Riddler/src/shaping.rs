@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::debug;
+
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// How `proxy --delay/--jitter/--throttle/--drop-rate` degrade client connections
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Shaping {
+	/// Added to every server-to-client flight, so once per round trip
+	pub delay: Duration,
+	/// The delay varies by up to this much either way
+	pub jitter: Duration,
+	/// Bytes per second in each direction of a connection
+	pub throttle: Option<u64>,
+	/// Fraction of connections closed as soon as they are accepted
+	pub drop_rate: f64,
+	/// Seed of the jitter and drop decisions, so a run can be repeated
+	pub seed: u64,
+}
+
+impl Shaping {
+	pub fn parse(delay: Option<&str>, jitter: Option<&str>, throttle: Option<&str>, drop_rate: Option<&str>, seed: u64) -> Result<Self> {
+		let millis = |value: Option<&str>| -> Result<Duration> {
+			Ok(Duration::from_millis(value.map(crate::watch::parse_millis).transpose()?.unwrap_or(0)))
+		};
+		Ok(Self {
+			delay: millis(delay)?,
+			jitter: millis(jitter)?,
+			throttle: throttle.map(parse_rate).transpose()?,
+			drop_rate: drop_rate.map(parse_percent).transpose()?.unwrap_or(0.0),
+			seed,
+		})
+	}
+
+	pub fn is_active(&self) -> bool {
+		!self.delay.is_zero() || !self.jitter.is_zero() || self.throttle.is_some() || self.drop_rate > 0.0
+	}
+
+	pub fn describe(&self) -> String {
+		let mut parts = Vec::new();
+		match (self.delay.as_millis(), self.jitter.as_millis()) {
+			(0, 0) => {}
+			(delay, 0) => parts.push(format!("delay {}ms", delay)),
+			(delay, jitter) => parts.push(format!("delay {}ms ± {}ms", delay, jitter)),
+		}
+		if let Some(rate) = self.throttle {
+			parts.push(format!("throttle {:.1} KB/s", rate as f64 / 1024.0));
+		}
+		if self.drop_rate > 0.0 {
+			parts.push(format!("drop {:.2}% of connections", self.drop_rate * 100.0));
+		}
+		format!("{} (seed {})", parts.join(", "), self.seed)
+	}
+}
+
+/// A bandwidth such as `512kbps`, `2mbps` (bits) or `64KB/s`, `1MB/s` (bytes),
+/// in bytes per second
+fn parse_rate(value: &str) -> Result<u64> {
+	let invalid = || anyhow!("Invalid bandwidth '{}': expected e.g. 512kbps, 2mbps or 64KB/s", value);
+	let trimmed = value.trim();
+	let split = trimmed.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(trimmed.len());
+	let (number, unit) = trimmed.split_at(split);
+	let number: f64 = number.parse().map_err(|_| invalid())?;
+	let bytes_per_second = match unit.trim() {
+		"bps" => number / 8.0,
+		"kbps" | "Kbps" => number * 1000.0 / 8.0,
+		"mbps" | "Mbps" => number * 1_000_000.0 / 8.0,
+		"gbps" | "Gbps" => number * 1_000_000_000.0 / 8.0,
+		"" | "B/s" => number,
+		"KB/s" | "kB/s" => number * 1024.0,
+		"MB/s" => number * 1024.0 * 1024.0,
+		_ => return Err(invalid()),
+	};
+	if bytes_per_second < 1.0 {
+		return Err(anyhow!("Bandwidth '{}' is below one byte per second", value));
+	}
+	Ok(bytes_per_second as u64)
+}
+
+/// A percentage such as `1%` or `0.5`, as a fraction
+fn parse_percent(value: &str) -> Result<f64> {
+	let number = value.trim().trim_end_matches('%').trim();
+	match number.parse::<f64>() {
+		Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(percent / 100.0),
+		_ => Err(anyhow!("Invalid drop rate '{}': expected a percentage between 0 and 100, e.g. 1%", value)),
+	}
+}
+
+/// Applies a `Shaping` to the connections of the proxy
+pub struct Shaper {
+	shaping: Shaping,
+	/// Draws in accept order, which makes the decisions reproducible
+	rng: Mutex<StdRng>,
+}
+
+impl Shaper {
+	pub fn new(shaping: Shaping) -> Self {
+		Self { shaping, rng: Mutex::new(StdRng::seed_from_u64(shaping.seed)) }
+	}
+
+	pub fn describe(&self) -> String {
+		self.shaping.describe()
+	}
+
+	/// The stream the proxy should serve `client` through, or `None` when the
+	/// connection is dropped. The client's traffic is relayed through a
+	/// shaped pipe by background tasks.
+	pub fn shape(&self, client: TcpStream) -> Option<DuplexStream> {
+		let (drop, connection_seed) = {
+			let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+			(rng.gen_bool(self.shaping.drop_rate), rng.r#gen::<u64>())
+		};
+		if drop {
+			return None;
+		}
+
+		let (proxy_side, shaped_side) = tokio::io::duplex(CHUNK_SIZE * 4);
+		let (client_read, client_write) = client.into_split();
+		let (shaped_read, shaped_write) = tokio::io::split(shaped_side);
+		let shaping = self.shaping;
+		tokio::spawn(async move {
+			if let Err(e) = relay(client_read, shaped_write, shaping.throttle).await {
+				debug!("Shaped upload ended: {}", e);
+			}
+		});
+		tokio::spawn(async move {
+			if let Err(e) = delayed_relay(shaped_read, client_write, shaping, connection_seed).await {
+				debug!("Shaped download ended: {}", e);
+			}
+		});
+		Some(proxy_side)
+	}
+}
+
+/// Paces writes to at most `rate` bytes per second
+struct Throttle {
+	rate: Option<u64>,
+	next_free: Instant,
+}
+
+impl Throttle {
+	fn new(rate: Option<u64>) -> Self {
+		Self { rate, next_free: Instant::now() }
+	}
+
+	/// Write `data`, in slices of about a tenth of a second each so the pace is even
+	async fn write<W: AsyncWriteExt + Unpin>(&mut self, writer: &mut W, data: &[u8]) -> std::io::Result<()> {
+		let Some(rate) = self.rate else {
+			return writer.write_all(data).await;
+		};
+		let slice = ((rate / 10) as usize).clamp(1, CHUNK_SIZE);
+		for piece in data.chunks(slice) {
+			tokio::time::sleep_until(self.next_free).await;
+			writer.write_all(piece).await?;
+			self.next_free = self.next_free.max(Instant::now()) + Duration::from_secs_f64(piece.len() as f64 / rate as f64);
+		}
+		Ok(())
+	}
+}
+
+async fn relay<R, W>(mut reader: R, mut writer: W, rate: Option<u64>) -> std::io::Result<()>
+where
+	R: AsyncReadExt + Unpin,
+	W: AsyncWriteExt + Unpin,
+{
+	let mut throttle = Throttle::new(rate);
+	let mut buffer = vec![0; CHUNK_SIZE];
+	loop {
+		let read = reader.read(&mut buffer).await?;
+		if read == 0 {
+			return writer.shutdown().await;
+		}
+		throttle.write(&mut writer, &buffer[..read]).await?;
+	}
+}
+
+/// Relay towards the client, holding each chunk back by the delay from when it
+/// was read. Later chunks keep flowing meanwhile, so the delay adds latency
+/// without limiting throughput.
+async fn delayed_relay<R, W>(mut reader: R, mut writer: W, shaping: Shaping, seed: u64) -> std::io::Result<()>
+where
+	R: AsyncReadExt + Unpin + Send + 'static,
+	W: AsyncWriteExt + Unpin,
+{
+	let (sender, mut receiver) = mpsc::channel::<(Instant, Vec<u8>)>(64);
+	let reading = tokio::spawn(async move {
+		let mut rng = StdRng::seed_from_u64(seed);
+		let jitter_ms = shaping.jitter.as_millis() as i64;
+		// Jitter never reorders bytes: a chunk is not due before the one ahead of it
+		let mut last_due = Instant::now();
+		let mut buffer = vec![0; CHUNK_SIZE];
+		loop {
+			let read = reader.read(&mut buffer).await?;
+			if read == 0 {
+				return Ok::<_, std::io::Error>(());
+			}
+			let offset = if jitter_ms > 0 { rng.gen_range(-jitter_ms..=jitter_ms) } else { 0 };
+			let delay = Duration::from_millis((shaping.delay.as_millis() as i64 + offset).max(0) as u64);
+			last_due = last_due.max(Instant::now() + delay);
+			if sender.send((last_due, buffer[..read].to_vec())).await.is_err() {
+				return Ok(());
+			}
+		}
+	});
+
+	let mut throttle = Throttle::new(shaping.throttle);
+	while let Some((due, chunk)) = receiver.recv().await {
+		tokio::time::sleep_until(due).await;
+		throttle.write(&mut writer, &chunk).await?;
+	}
+	writer.shutdown().await?;
+	reading.await.map_err(std::io::Error::other)?
+}
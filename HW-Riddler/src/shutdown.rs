@@ -0,0 +1,68 @@
+use std::io::BufRead;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// Cancel `token` on Ctrl+C, or SIGTERM on Unix. Long-running commands stop
+/// taking new work when it is cancelled, drain and flush what they have and
+/// return, so `main` still saves cookies and telemetry before exiting.
+pub fn cancel_on_signal(token: CancellationToken) {
+	tokio::spawn(async move {
+		if let Some(signal) = wait_for_signal().await {
+			info!("{} received, shutting down", signal);
+			token.cancel();
+		}
+	});
+}
+
+/// Cancel `token` once `q`, `quit` or `exit` is entered or stdin closes.
+/// A thread rather than a task: the runtime waits for blocking tasks when it
+/// shuts down, and a read of stdin never finishes on its own.
+pub fn cancel_on_quit_command(token: CancellationToken) {
+	std::thread::spawn(move || {
+		for line in std::io::stdin().lock().lines() {
+			match line {
+				Ok(line) => {
+					let input = line.trim().to_lowercase();
+					if input == "q" || input == "quit" || input == "exit" {
+						info!("User requested quit via keyboard input");
+						token.cancel();
+						return;
+					} else if !input.is_empty() {
+						eprintln!("Unknown command '{}'. Enter q to quit.", input);
+					}
+				}
+				Err(e) => {
+					error!("Error reading from stdin: {}", e);
+					return;
+				}
+			}
+		}
+		info!("Stdin closed, shutting down...");
+		token.cancel();
+	});
+}
+
+/// Name of the signal received, `None` if none can be listened for
+async fn wait_for_signal() -> Option<&'static str> {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{signal, SignalKind};
+
+		match signal(SignalKind::terminate()) {
+			Ok(mut sigterm) => {
+				return tokio::select! {
+					result = tokio::signal::ctrl_c() => result.ok().map(|_| "Ctrl+C"),
+					_ = sigterm.recv() => Some("SIGTERM"),
+				};
+			}
+			Err(e) => error!("Unable to listen for SIGTERM: {}", e),
+		}
+	}
+	match tokio::signal::ctrl_c().await {
+		Ok(()) => Some("Ctrl+C"),
+		Err(e) => {
+			error!("Unable to listen for Ctrl+C signal: {}", e);
+			None
+		}
+	}
+}
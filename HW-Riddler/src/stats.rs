@@ -0,0 +1,71 @@
+use crate::http_client::HttpResponseInfo;
+
+/// Requests, errors and response times of a group of logged exchanges, such
+/// as one host (`hosts`) or one endpoint (`logs --top`)
+#[derive(Debug, Clone, Default)]
+pub struct TrafficStats {
+	pub requests: usize,
+	/// Requests that got a response, the denominator of the error rate
+	pub responses: usize,
+	pub errors: usize,
+	pub latencies_ms: Vec<u64>,
+}
+
+impl TrafficStats {
+	/// Count one request, and its response when it got one
+	pub fn record(&mut self, response: Option<&HttpResponseInfo>) {
+		self.requests += 1;
+		if let Some(response) = response {
+			self.responses += 1;
+			if response.status >= 400 {
+				self.errors += 1;
+			}
+			self.latencies_ms.push(response.response_time_ms);
+		}
+	}
+
+	pub fn merge(&mut self, other: TrafficStats) {
+		self.requests += other.requests;
+		self.responses += other.responses;
+		self.errors += other.errors;
+		self.latencies_ms.extend(other.latencies_ms);
+	}
+
+	/// Share of answered requests with a 4xx/5xx status, in percent
+	pub fn error_rate(&self) -> f64 {
+		error_rate(self.errors as u64, self.responses as u64)
+	}
+
+	pub fn mean_latency(&self) -> Option<u64> {
+		if self.latencies_ms.is_empty() {
+			None
+		} else {
+			Some(self.latencies_ms.iter().sum::<u64>() / self.latencies_ms.len() as u64)
+		}
+	}
+
+	/// Nearest-rank 95th percentile
+	pub fn p95_latency(&self) -> Option<u64> {
+		if self.latencies_ms.is_empty() {
+			return None;
+		}
+		let mut sorted = self.latencies_ms.clone();
+		sorted.sort_unstable();
+		Some(percentile(&sorted, 0.95))
+	}
+}
+
+/// Nearest-rank percentile of an ascending, non-empty slice
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+	let rank = (sorted.len() as f64 * p).ceil() as usize;
+	sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// `errors` as a share of `responses`, in percent; 0 without responses
+pub fn error_rate(errors: u64, responses: u64) -> f64 {
+	if responses == 0 {
+		0.0
+	} else {
+		errors as f64 * 100.0 / responses as f64
+	}
+}
@@ -0,0 +1,277 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::Command;
+use tracing::{debug, warn};
+
+/// Per-user Internet settings read by WinINet, and with it most Windows applications
+const WINDOWS_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Internet Settings";
+
+/// Programs with their arguments, run one after another
+type Commands = Vec<Vec<String>>;
+
+/// What the OS proxy settings are pointed at
+pub enum Target {
+	/// The same HTTP and HTTPS proxy for everything
+	Proxy { host: String, port: u16 },
+	/// A proxy auto-config URL, e.g. the one of `proxy --serve-pac`
+	Pac(String),
+}
+
+/// The commands that put the settings back as they were, saved before
+/// anything is changed so `proxy disable-system` works from another process
+#[derive(Debug, Serialize, Deserialize)]
+struct Backup {
+	platform: String,
+	restore: Commands,
+}
+
+/// How the proxy is configured on this machine
+enum Platform {
+	/// `networksetup`, once for every enabled network service
+	MacOs(Vec<String>),
+	/// The Internet Settings registry key, through `reg`
+	Windows,
+	/// `gsettings` of `org.gnome.system.proxy`
+	Gnome,
+}
+
+impl Target {
+	pub fn describe(&self) -> String {
+		match self {
+			Target::Proxy { host, port } => format!("{}:{}", host, port),
+			Target::Pac(url) => format!("PAC {}", url),
+		}
+	}
+}
+
+/// Point the system proxy settings at `target`, saving the current ones to `backup_path`
+pub fn enable(target: &Target, backup_path: &Path) -> Result<()> {
+	if backup_path.exists() {
+		return Err(anyhow!(
+			"{} exists: the system proxy was already changed and not restored yet; run 'proxy disable-system' first",
+			backup_path.display()
+		));
+	}
+	let platform = Platform::detect()?;
+	let (apply, restore) = platform.plan(target)?;
+
+	let backup = Backup { platform: platform.name().to_string(), restore };
+	std::fs::write(backup_path, serde_json::to_string_pretty(&backup)?)
+		.with_context(|| format!("Unable to save the current proxy settings to {}", backup_path.display()))?;
+
+	for command in &apply {
+		if let Err(e) = run(command) {
+			// Leave nothing half configured
+			warn!("Changing the system proxy failed, restoring the previous settings");
+			disable(backup_path)?;
+			return Err(e);
+		}
+	}
+	Ok(())
+}
+
+/// Restore the settings saved by `enable`; returns the platform they were restored on
+pub fn disable(backup_path: &Path) -> Result<String> {
+	let content = std::fs::read_to_string(backup_path)
+		.with_context(|| format!("No saved proxy settings at {} (was 'proxy enable-system' run?)", backup_path.display()))?;
+	let backup: Backup = serde_json::from_str(&content)
+		.with_context(|| format!("{} is not a saved proxy settings file", backup_path.display()))?;
+
+	let mut failed = 0;
+	for command in &backup.restore {
+		if let Err(e) = run(command) {
+			warn!("{:#}", e);
+			failed += 1;
+		}
+	}
+	if failed > 0 {
+		return Err(anyhow!("{} of {} restore commands failed; {} was kept", failed, backup.restore.len(), backup_path.display()));
+	}
+	std::fs::remove_file(backup_path)?;
+	Ok(backup.platform)
+}
+
+impl Platform {
+	fn detect() -> Result<Self> {
+		match std::env::consts::OS {
+			"macos" => {
+				// The first line explains that '*' marks disabled services
+				let services = output(&["networksetup", "-listallnetworkservices"])?
+					.lines()
+					.skip(1)
+					.filter(|service| !service.starts_with('*') && !service.trim().is_empty())
+					.map(str::to_string)
+					.collect::<Vec<_>>();
+				if services.is_empty() {
+					return Err(anyhow!("networksetup lists no enabled network services"));
+				}
+				Ok(Platform::MacOs(services))
+			}
+			"windows" => Ok(Platform::Windows),
+			_ if output(&["gsettings", "get", "org.gnome.system.proxy", "mode"]).is_ok() => Ok(Platform::Gnome),
+			os => Err(anyhow!(
+				"Changing the system proxy is supported on macOS (networksetup), Windows (registry) and GNOME (gsettings), not on this {} system",
+				os
+			)),
+		}
+	}
+
+	fn name(&self) -> &'static str {
+		match self {
+			Platform::MacOs(_) => "macOS",
+			Platform::Windows => "Windows",
+			Platform::Gnome => "GNOME",
+		}
+	}
+
+	/// The commands pointing the settings at `target`, and those undoing them
+	fn plan(&self, target: &Target) -> Result<(Commands, Commands)> {
+		let mut apply = Vec::new();
+		let mut restore = Vec::new();
+		match self {
+			Platform::MacOs(services) => {
+				for service in services {
+					for kind in ["webproxy", "securewebproxy"] {
+						let current = output(&["networksetup", &format!("-get{}", kind), service])?;
+						let (server, port) = (field(&current, "Server"), field(&current, "Port"));
+						if !server.is_empty() {
+							restore.push(args(&["networksetup", &format!("-set{}", kind), service, &server, &port]));
+						}
+						restore.push(args(&["networksetup", &format!("-set{}state", kind), service, on_off(&current)]));
+					}
+					let current = output(&["networksetup", "-getautoproxyurl", service])?;
+					let url = field(&current, "URL");
+					if !url.is_empty() && url != "(null)" {
+						restore.push(args(&["networksetup", "-setautoproxyurl", service, &url]));
+					}
+					restore.push(args(&["networksetup", "-setautoproxystate", service, on_off(&current)]));
+
+					match target {
+						Target::Proxy { host, port } => {
+							let port = port.to_string();
+							apply.push(args(&["networksetup", "-setwebproxy", service, host, &port]));
+							apply.push(args(&["networksetup", "-setsecurewebproxy", service, host, &port]));
+							apply.push(args(&["networksetup", "-setautoproxystate", service, "off"]));
+						}
+						Target::Pac(url) => {
+							apply.push(args(&["networksetup", "-setautoproxyurl", service, url]));
+							apply.push(args(&["networksetup", "-setwebproxystate", service, "off"]));
+							apply.push(args(&["networksetup", "-setsecurewebproxystate", service, "off"]));
+						}
+					}
+				}
+			}
+
+			Platform::Windows => {
+				let auto_config = registry_value("AutoConfigURL");
+				match target {
+					Target::Proxy { host, port } => {
+						apply.push(args(&["reg", "add", WINDOWS_KEY, "/v", "ProxyEnable", "/t", "REG_DWORD", "/d", "1", "/f"]));
+						apply.push(args(&["reg", "add", WINDOWS_KEY, "/v", "ProxyServer", "/t", "REG_SZ", "/d", &format!("{}:{}", host, port), "/f"]));
+						apply.push(args(&["reg", "add", WINDOWS_KEY, "/v", "ProxyOverride", "/t", "REG_SZ", "/d", "<local>", "/f"]));
+						if auto_config.is_some() {
+							apply.push(args(&["reg", "delete", WINDOWS_KEY, "/v", "AutoConfigURL", "/f"]));
+						}
+					}
+					Target::Pac(url) => {
+						apply.push(args(&["reg", "add", WINDOWS_KEY, "/v", "AutoConfigURL", "/t", "REG_SZ", "/d", url, "/f"]));
+						apply.push(args(&["reg", "add", WINDOWS_KEY, "/v", "ProxyEnable", "/t", "REG_DWORD", "/d", "0", "/f"]));
+					}
+				}
+				// Values that did not exist are deleted again, but only if set
+				// above: deleting a missing value fails
+				for name in ["ProxyEnable", "ProxyServer", "ProxyOverride", "AutoConfigURL"] {
+					let current = if name == "AutoConfigURL" { auto_config.clone() } else { registry_value(name) };
+					match current {
+						Some((kind, data)) => restore.push(args(&["reg", "add", WINDOWS_KEY, "/v", name, "/t", &kind, "/d", &data, "/f"])),
+						None if apply.iter().any(|command| command[1] == "add" && command[4] == name) => {
+							restore.push(args(&["reg", "delete", WINDOWS_KEY, "/v", name, "/f"]));
+						}
+						None => {}
+					}
+				}
+			}
+
+			Platform::Gnome => {
+				let keys = [
+					("org.gnome.system.proxy", "autoconfig-url"),
+					("org.gnome.system.proxy.http", "host"),
+					("org.gnome.system.proxy.http", "port"),
+					("org.gnome.system.proxy.https", "host"),
+					("org.gnome.system.proxy.https", "port"),
+					// Last, so the restored mode applies to restored values
+					("org.gnome.system.proxy", "mode"),
+				];
+				for (schema, key) in keys {
+					let value = output(&["gsettings", "get", schema, key])?;
+					restore.push(args(&["gsettings", "set", schema, key, value.trim()]));
+				}
+				match target {
+					Target::Proxy { host, port } => {
+						let port = port.to_string();
+						for schema in ["org.gnome.system.proxy.http", "org.gnome.system.proxy.https"] {
+							apply.push(args(&["gsettings", "set", schema, "host", host]));
+							apply.push(args(&["gsettings", "set", schema, "port", &port]));
+						}
+						apply.push(args(&["gsettings", "set", "org.gnome.system.proxy", "mode", "manual"]));
+					}
+					Target::Pac(url) => {
+						apply.push(args(&["gsettings", "set", "org.gnome.system.proxy", "autoconfig-url", url]));
+						apply.push(args(&["gsettings", "set", "org.gnome.system.proxy", "mode", "auto"]));
+					}
+				}
+			}
+		}
+		Ok((apply, restore))
+	}
+}
+
+fn args(parts: &[&str]) -> Vec<String> {
+	parts.iter().map(|part| part.to_string()).collect()
+}
+
+/// `Name: value` from `networksetup -get...` output
+fn field(output: &str, name: &str) -> String {
+	output.lines()
+		.filter_map(|line| line.split_once(':'))
+		.find(|(key, _)| key.trim() == name)
+		.map(|(_, value)| value.trim().to_string())
+		.unwrap_or_default()
+}
+
+fn on_off(output: &str) -> &'static str {
+	if field(output, "Enabled") == "Yes" { "on" } else { "off" }
+}
+
+/// Type and data of a value under `WINDOWS_KEY`, `None` when it is not set
+fn registry_value(name: &str) -> Option<(String, String)> {
+	let output = output(&["reg", "query", WINDOWS_KEY, "/v", name]).ok()?;
+	// "    ProxyServer    REG_SZ    127.0.0.1:8080"
+	output.lines().find_map(|line| {
+		let mut parts = line.split_whitespace();
+		(parts.next()? == name).then_some(())?;
+		let kind = parts.next()?.to_string();
+		let data = parts.collect::<Vec<_>>().join(" ");
+		let data = match data.strip_prefix("0x") {
+			Some(hex) if kind == "REG_DWORD" => u32::from_str_radix(hex, 16).ok()?.to_string(),
+			_ => data,
+		};
+		Some((kind, data))
+	})
+}
+
+fn output(command: &[&str]) -> Result<String> {
+	let result = Command::new(command[0]).args(&command[1..]).output()
+		.with_context(|| format!("Unable to run {}", command[0]))?;
+	if !result.status.success() {
+		return Err(anyhow!("{} failed: {}", command.join(" "), String::from_utf8_lossy(&result.stderr).trim()));
+	}
+	Ok(String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+fn run(command: &[String]) -> Result<()> {
+	debug!("Running {:?}", command);
+	let parts: Vec<&str> = command.iter().map(String::as_str).collect();
+	output(&parts).map(|_| ())
+}
@@ -0,0 +1,194 @@
+use crate::config::TelemetryConfig;
+use crate::http_client::{HttpRequestBuilder, HttpResponseInfo};
+use anyhow::{anyhow, Result};
+use opentelemetry::trace::{Span, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::warn;
+
+const TRACER_NAME: &str = "riddler";
+
+tokio::task_local! {
+	/// DNS lookups made while sending the current attempt, see `TimedResolver`
+	static LOOKUPS: Arc<Mutex<Vec<DnsLookup>>>;
+}
+
+/// Exports the spans of `HttpClient` requests over OTLP/HTTP while alive;
+/// dropping it flushes the spans still queued
+pub struct Telemetry {
+	provider: SdkTracerProvider,
+}
+
+impl Telemetry {
+	/// Install the global tracer and W3C trace context propagation. Without an
+	/// endpoint the `OTEL_EXPORTER_OTLP_*` variables or the collector default
+	/// `http://localhost:4318/v1/traces` apply.
+	pub fn init(config: &TelemetryConfig) -> Result<Self> {
+		let mut exporter = opentelemetry_otlp::SpanExporter::builder().with_http();
+		if let Some(endpoint) = &config.endpoint {
+			exporter = exporter.with_endpoint(endpoint);
+		}
+		if !config.headers.is_empty() {
+			exporter = exporter.with_headers(config.headers.clone());
+		}
+		let exporter = exporter.build().map_err(|e| anyhow!("Unable to set up OTLP trace export: {}", e))?;
+
+		let provider = SdkTracerProvider::builder()
+			.with_batch_exporter(exporter)
+			.with_resource(opentelemetry_sdk::Resource::builder().with_service_name(config.service_name.clone()).build())
+			.build();
+		global::set_tracer_provider(provider.clone());
+		global::set_text_map_propagator(opentelemetry_sdk::propagation::TraceContextPropagator::new());
+		Ok(Self { provider })
+	}
+}
+
+impl Drop for Telemetry {
+	fn drop(&mut self) {
+		if let Err(e) = self.provider.shutdown() {
+			warn!("Failed to flush trace spans: {}", e);
+		}
+	}
+}
+
+/// Start the client span covering every attempt of `request`, as the
+/// context the attempts run in
+pub fn request_context(request: &HttpRequestBuilder) -> Context {
+	let method = request.method.to_uppercase();
+	let mut attributes = vec![
+		KeyValue::new("http.request.method", method.clone()),
+		KeyValue::new("url.full", request.url.clone()),
+	];
+	if let Ok(url) = url::Url::parse(&request.url) {
+		if let Some(host) = url.host_str() {
+			attributes.push(KeyValue::new("server.address", host.to_string()));
+		}
+		if let Some(port) = url.port_or_known_default() {
+			attributes.push(KeyValue::new("server.port", port as i64));
+		}
+	}
+	let span = global::tracer(TRACER_NAME)
+		.span_builder(method)
+		.with_kind(SpanKind::Client)
+		.with_attributes(attributes)
+		.start(&global::tracer(TRACER_NAME));
+	Context::current_with_span(span)
+}
+
+/// End the span of `request_context` with the outcome of the last attempt
+pub fn end_request(context: &Context, result: &Result<HttpResponseInfo>) {
+	let span = context.span();
+	match result {
+		Ok(response) => {
+			span.set_attribute(KeyValue::new("http.response.status_code", response.status as i64));
+			if !response.attempts.is_empty() {
+				span.set_attribute(KeyValue::new("http.request.resend_count", response.attempts.len() as i64 - 1));
+			}
+			// Client spans count 4xx as errors too
+			if response.status >= 400 {
+				span.set_status(Status::error(response.status.to_string()));
+			}
+		}
+		Err(e) => span.set_status(Status::error(format!("{:#}", e))),
+	}
+	span.end();
+}
+
+/// `traceparent` (and `tracestate`) headers continuing the current trace, for
+/// the server to link its spans to the request
+pub fn propagation_headers() -> HashMap<String, String> {
+	let mut headers = HashMap::new();
+	global::get_text_map_propagator(|propagator| propagator.inject_context(&Context::current(), &mut headers));
+	headers
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsLookup {
+	host: String,
+	start: SystemTime,
+	end: SystemTime,
+}
+
+/// When the phases of one attempt happened. reqwest does not report when a
+/// new connection's TCP and TLS setup ends, so that time is part of `ttfb`.
+pub struct AttemptTimings {
+	start: SystemTime,
+	lookups: Arc<Mutex<Vec<DnsLookup>>>,
+	head: Option<SystemTime>,
+}
+
+impl AttemptTimings {
+	pub fn start() -> Self {
+		Self { start: SystemTime::now(), lookups: Arc::default(), head: None }
+	}
+
+	/// Where `record_lookups` collects the DNS lookups of this attempt
+	pub fn lookups(&self) -> Arc<Mutex<Vec<DnsLookup>>> {
+		self.lookups.clone()
+	}
+
+	/// The response headers have arrived
+	pub fn head_received(&mut self) {
+		self.head = Some(SystemTime::now());
+	}
+
+	/// Add `dns`, `ttfb` and `download` spans for attempt number `attempt` to the
+	/// current request span; a failed attempt ends with its `ttfb` span
+	pub fn export(&self, attempt: u32, error: Option<&anyhow::Error>) {
+		let end = SystemTime::now();
+		let tracer = global::tracer(TRACER_NAME);
+		let context = Context::current();
+		let phase = |name: &str, start: SystemTime, end: SystemTime, attributes: Vec<KeyValue>| {
+			let mut span = tracer.span_builder(name.to_string())
+				.with_start_time(start)
+				.with_attributes(attributes)
+				.start_with_context(&tracer, &context);
+			span.set_attribute(KeyValue::new("riddler.attempt", attempt as i64));
+			span.end_with_timestamp(end);
+		};
+
+		let lookups = self.lookups.lock().map(|lookups| lookups.clone()).unwrap_or_default();
+		for lookup in &lookups {
+			phase("dns", lookup.start, lookup.end, vec![KeyValue::new("server.address", lookup.host.clone())]);
+		}
+		let connect_start = lookups.iter().map(|lookup| lookup.end).max().unwrap_or(self.start);
+		match self.head {
+			Some(head) => {
+				phase("ttfb", connect_start, head, Vec::new());
+				phase("download", head, end, Vec::new());
+			}
+			None => phase("ttfb", connect_start, end, error.map(|e| KeyValue::new("error.message", format!("{:#}", e))).into_iter().collect()),
+		}
+	}
+}
+
+/// Run `future`, collecting the lookups `TimedResolver` makes for it in `lookups`
+pub async fn record_lookups<F: Future>(lookups: Arc<Mutex<Vec<DnsLookup>>>, future: F) -> F::Output {
+	LOOKUPS.scope(lookups, future).await
+}
+
+/// The system resolver, timing each lookup for the attempt that triggered it.
+/// Lookups only happen for new connections, so pooled requests have no `dns` span.
+pub struct TimedResolver;
+
+impl reqwest::dns::Resolve for TimedResolver {
+	fn resolve(&self, name: hyper::client::connect::dns::Name) -> reqwest::dns::Resolving {
+		let lookups = LOOKUPS.try_with(Arc::clone).ok();
+		let host = name.as_str().to_string();
+		Box::pin(async move {
+			let start = SystemTime::now();
+			let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+			if let Some(lookups) = lookups
+				&& let Ok(mut lookups) = lookups.lock()
+			{
+				lookups.push(DnsLookup { host, start, end: SystemTime::now() });
+			}
+			Ok(Box::new(addresses.into_iter()) as reqwest::dns::Addrs)
+		})
+	}
+}
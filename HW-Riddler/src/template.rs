@@ -0,0 +1,128 @@
+use crate::http_client::{HttpRequestBuilder, RequestBody};
+use anyhow::{anyhow, Result};
+use rand::Rng;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Expands `{{...}}` placeholders in request fields right before sending.
+///
+/// Supported placeholders:
+/// - `{{env:NAME}}` — value of environment variable `NAME`
+/// - `{{uuid}}` — random version 4 UUID
+/// - `{{timestamp}}` — current Unix time in seconds
+/// - `{{random:int:MIN-MAX}}` — random integer in the inclusive range
+/// - `{{name}}` — a variable set on the context, e.g. a collection variable or
+///   one from `--env-file`, otherwise the environment variable `name`
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext {
+	vars: HashMap<String, String>,
+}
+
+impl TemplateContext {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn set(&mut self, name: &str, value: impl Into<String>) {
+		self.vars.insert(name.to_string(), value.into());
+	}
+
+	pub fn expand(&self, input: &str) -> Result<String> {
+		let mut output = String::with_capacity(input.len());
+		let mut rest = input;
+
+		while let Some(start) = rest.find("{{") {
+			output.push_str(&rest[..start]);
+			let after = &rest[start + 2..];
+			let end = after
+				.find("}}")
+				.ok_or_else(|| anyhow!("Unclosed placeholder in '{}'", input))?;
+			output.push_str(&self.resolve(after[..end].trim())?);
+			rest = &after[end + 2..];
+		}
+
+		output.push_str(rest);
+		Ok(output)
+	}
+
+	/// Expand the URL, header names and values, and body of a request
+	pub fn expand_request(&self, request: &HttpRequestBuilder) -> Result<HttpRequestBuilder> {
+		let headers = request.headers
+			.iter()
+			.map(|(k, v)| Ok((self.expand(k)?, self.expand(v)?)))
+			.collect::<Result<HashMap<_, _>>>()?;
+
+		Ok(HttpRequestBuilder {
+			url: self.expand(&request.url)?,
+			headers,
+			body: match &request.body {
+				Some(RequestBody::Text(text)) => Some(RequestBody::Text(self.expand(text)?)),
+				body => body.clone(),
+			},
+			..request.clone()
+		})
+	}
+
+	fn resolve(&self, placeholder: &str) -> Result<String> {
+		let mut parts = placeholder.splitn(3, ':');
+		match (parts.next(), parts.next(), parts.next()) {
+			(Some("env"), Some(name), None) => std::env::var(name)
+				.map_err(|_| anyhow!("Environment variable '{}' used in {{{{env:{}}}}} is not set", name, name)),
+			(Some("uuid"), None, None) => Ok(uuid_v4()),
+			(Some("timestamp"), None, None) => Ok(chrono::Utc::now().timestamp().to_string()),
+			(Some("random"), Some("int"), Some(range)) => random_int(range),
+			(Some(name), None, None) if self.vars.contains_key(name) => Ok(self.vars[name].clone()),
+			(Some(name), None, None) if std::env::var(name).is_ok() => Ok(std::env::var(name)?),
+			_ => Err(anyhow!("Unknown placeholder {{{{{}}}}}: not a variable or a set environment variable", placeholder)),
+		}
+	}
+}
+
+/// Variables from a dotenv-style file: `NAME=VALUE` lines, optionally
+/// prefixed with `export` and with the value in single or double quotes.
+/// Blank lines and `#` comments are skipped.
+pub fn read_env_file(path: &Path) -> Result<HashMap<String, String>> {
+	let content = std::fs::read_to_string(path)
+		.map_err(|e| anyhow!("Unable to read env file {}: {}", path.display(), e))?;
+
+	let mut vars = HashMap::new();
+	for (number, line) in content.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let line = line.strip_prefix("export ").unwrap_or(line);
+		let (name, value) = line.split_once('=')
+			.ok_or_else(|| anyhow!("{}:{}: expected NAME=VALUE", path.display(), number + 1))?;
+		let value = value.trim();
+		let value = value
+			.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+			.or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+			.unwrap_or(value);
+		vars.insert(name.trim().to_string(), value.to_string());
+	}
+	Ok(vars)
+}
+
+fn uuid_v4() -> String {
+	let mut bytes: [u8; 16] = rand::thread_rng().r#gen();
+	bytes[6] = (bytes[6] & 0x0f) | 0x40;
+	bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+	let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+	format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+fn random_int(range: &str) -> Result<String> {
+	let invalid = || anyhow!("Invalid range '{}' in {{{{random:int:MIN-MAX}}}}", range);
+
+	// Split on the first '-' that is not a leading sign, so negative minimums work
+	let split = range.char_indices().skip(1).find(|&(_, c)| c == '-').map(|(i, _)| i).ok_or_else(invalid)?;
+	let min: i64 = range[..split].trim().parse().map_err(|_| invalid())?;
+	let max: i64 = range[split + 1..].trim().parse().map_err(|_| invalid())?;
+	if min > max {
+		return Err(invalid());
+	}
+
+	Ok(rand::thread_rng().gen_range(min..=max).to_string())
+}
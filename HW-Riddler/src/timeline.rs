@@ -0,0 +1,219 @@
+use crate::logger::RequestLogEntry;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use std::fmt::Write;
+use std::path::Path;
+
+const WIDTH: f64 = 1200.0;
+const LABEL_WIDTH: f64 = 360.0;
+const AXIS_HEIGHT: f64 = 40.0;
+const ROW_HEIGHT: f64 = 18.0;
+const BAR_HEIGHT: f64 = 12.0;
+const TICKS: usize = 8;
+
+/// One bar of the timeline
+#[derive(Debug, Clone)]
+pub struct Span {
+	pub start: DateTime<Utc>,
+	pub duration_ms: u64,
+	pub status: Option<u16>,
+	pub label: String,
+	pub source: String,
+}
+
+impl Span {
+	/// Entries are logged once the response arrives, so the request started
+	/// `response_time_ms` before the entry timestamp
+	pub fn from_entry(entry: &RequestLogEntry) -> Self {
+		let duration_ms = entry.response.as_ref().map_or(0, |r| r.response_time_ms);
+		let path = url::Url::parse(&entry.request.url)
+			.map(|u| format!("{}{}", u.host_str().unwrap_or(""), u.path()))
+			.unwrap_or_else(|_| entry.request.url.clone());
+
+		Self {
+			start: entry.timestamp - Duration::milliseconds(duration_ms as i64),
+			duration_ms,
+			status: entry.response.as_ref().map(|r| r.status),
+			label: format!("{} {}", entry.request.method, path),
+			source: entry.source.clone(),
+		}
+	}
+
+	fn end(&self) -> DateTime<Utc> {
+		self.start + Duration::milliseconds(self.duration_ms as i64)
+	}
+}
+
+/// Parse a relative window such as `30s`, `15m`, `1h` or `2d`
+pub fn parse_since(value: &str) -> Result<Duration> {
+	let value = value.trim();
+	let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+	let (amount, unit) = value.split_at(split);
+	let amount: i64 = amount.parse()
+		.map_err(|_| anyhow!("Invalid duration '{}': expected e.g. 30s, 15m, 1h or 2d", value))?;
+
+	match unit {
+		"s" => Ok(Duration::seconds(amount)),
+		"m" => Ok(Duration::minutes(amount)),
+		"h" | "" => Ok(Duration::hours(amount)),
+		"d" => Ok(Duration::days(amount)),
+		other => Err(anyhow!("Invalid duration unit '{}': use s, m, h or d", other)),
+	}
+}
+
+/// Render the entries as SVG, or as an HTML page embedding the SVG when the
+/// output path ends in `.html`/`.htm`, and write it to `output`
+pub fn export(entries: &[RequestLogEntry], output: &Path) -> Result<usize> {
+	let mut spans: Vec<Span> = entries.iter().map(Span::from_entry).collect();
+	if spans.is_empty() {
+		return Err(anyhow!("No log entries in the selected window"));
+	}
+	spans.sort_by_key(|s| s.start);
+
+	let svg = render_svg(&spans);
+	let is_html = output
+		.extension()
+		.and_then(|e| e.to_str())
+		.is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+	let content = if is_html { render_html(&spans, &svg) } else { svg };
+
+	std::fs::write(output, content)
+		.map_err(|e| anyhow!("Unable to write {}: {}", output.display(), e))?;
+	Ok(spans.len())
+}
+
+pub fn render_svg(spans: &[Span]) -> String {
+	let first = spans.iter().map(|s| s.start).min().unwrap_or_else(Utc::now);
+	let last = spans.iter().map(Span::end).max().unwrap_or(first);
+	let total_ms = (last - first).num_milliseconds().max(1) as f64;
+	let plot_width = WIDTH - LABEL_WIDTH - 20.0;
+	let height = AXIS_HEIGHT + ROW_HEIGHT * spans.len() as f64 + 10.0;
+	let slow_threshold = slow_threshold_ms(spans);
+
+	let mut svg = String::new();
+	let _ = writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="11">"#, WIDTH, height);
+	let _ = writeln!(svg, r##"<rect width="100%" height="100%" fill="#ffffff"/>"##);
+
+	for i in 0..=TICKS {
+		let offset_ms = total_ms * i as f64 / TICKS as f64;
+		let x = LABEL_WIDTH + plot_width * i as f64 / TICKS as f64;
+		let _ = writeln!(svg, r##"<line x1="{x:.1}" y1="{}" x2="{x:.1}" y2="{height}" stroke="#e0e0e0"/>"##, AXIS_HEIGHT - 8.0);
+		let _ = writeln!(svg, r##"<text x="{x:.1}" y="{}" text-anchor="middle" fill="#555">+{}</text>"##, AXIS_HEIGHT - 14.0, format_offset(offset_ms));
+	}
+	let _ = writeln!(svg, r##"<text x="4" y="14" fill="#333">{} → {}</text>"##,
+		first.format("%Y-%m-%d %H:%M:%S%.3f UTC"), last.format("%H:%M:%S%.3f"));
+
+	for (row, span) in spans.iter().enumerate() {
+		let y = AXIS_HEIGHT + ROW_HEIGHT * row as f64;
+		let x = LABEL_WIDTH + plot_width * (span.start - first).num_milliseconds() as f64 / total_ms;
+		let width = (plot_width * span.duration_ms as f64 / total_ms).max(2.0);
+		let status = span.status.map_or("no response".to_string(), |s| s.to_string());
+		let outline = if slow_threshold.is_some_and(|t| span.duration_ms >= t) {
+			r##" stroke="#000" stroke-width="1.5""##
+		} else {
+			""
+		};
+
+		let _ = writeln!(svg, r##"<text x="4" y="{:.1}" fill="#333">{}</text>"##, y + BAR_HEIGHT - 2.0, escape(&truncate(&span.label, 48)));
+		let _ = writeln!(
+			svg,
+			r#"<rect x="{x:.1}" y="{:.1}" width="{width:.1}" height="{BAR_HEIGHT}" rx="2" fill="{}"{outline}><title>{} [{}] {} · {}ms · {}</title></rect>"#,
+			y + (ROW_HEIGHT - BAR_HEIGHT) / 2.0,
+			status_color(span.status),
+			escape(&span.label),
+			span.source,
+			status,
+			span.duration_ms,
+			span.start.format("%H:%M:%S%.3f"),
+		);
+	}
+
+	svg.push_str("</svg>\n");
+	svg
+}
+
+fn render_html(spans: &[Span], svg: &str) -> String {
+	let count = |range: std::ops::Range<u16>| spans.iter().filter(|s| s.status.is_some_and(|st| range.contains(&st))).count();
+	let no_response = spans.iter().filter(|s| s.status.is_none()).count();
+
+	format!(
+		r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Riddler request timeline</title>
+<style>
+body {{ font-family: sans-serif; margin: 20px; }}
+.legend span {{ display: inline-block; margin-right: 16px; }}
+.swatch {{ display: inline-block; width: 12px; height: 12px; margin-right: 4px; vertical-align: middle; }}
+</style>
+</head>
+<body>
+<h2>Request timeline ({} requests)</h2>
+<p class="legend">
+<span><i class="swatch" style="background:{}"></i>2xx: {}</span>
+<span><i class="swatch" style="background:{}"></i>3xx: {}</span>
+<span><i class="swatch" style="background:{}"></i>4xx: {}</span>
+<span><i class="swatch" style="background:{}"></i>5xx: {}</span>
+<span><i class="swatch" style="background:{}"></i>no response: {}</span>
+<span>Black outline: slowest 5%</span>
+</p>
+{}
+</body>
+</html>
+"##,
+		spans.len(),
+		status_color(Some(200)), count(200..300),
+		status_color(Some(300)), count(300..400),
+		status_color(Some(400)), count(400..500),
+		status_color(Some(500)), count(500..600),
+		status_color(None), no_response,
+		svg,
+	)
+}
+
+/// 95th percentile duration, used to outline slow outliers; `None` when
+/// there are too few requests for a percentile to mean anything
+fn slow_threshold_ms(spans: &[Span]) -> Option<u64> {
+	if spans.len() < 20 {
+		return None;
+	}
+	let mut durations: Vec<u64> = spans.iter().map(|s| s.duration_ms).collect();
+	durations.sort_unstable();
+	durations.get(durations.len() * 95 / 100).copied().filter(|&t| t > 0)
+}
+
+fn status_color(status: Option<u16>) -> &'static str {
+	match status {
+		Some(200..=299) => "#4caf50",
+		Some(300..=399) => "#2196f3",
+		Some(400..=499) => "#ff9800",
+		Some(500..=599) => "#f44336",
+		_ => "#9e9e9e",
+	}
+}
+
+fn format_offset(ms: f64) -> String {
+	if ms >= 60_000.0 {
+		format!("{:.1}m", ms / 60_000.0)
+	} else if ms >= 1000.0 {
+		format!("{:.1}s", ms / 1000.0)
+	} else {
+		format!("{:.0}ms", ms)
+	}
+}
+
+fn truncate(text: &str, max: usize) -> String {
+	if text.chars().count() > max {
+		format!("{}…", text.chars().take(max - 1).collect::<String>())
+	} else {
+		text.to_string()
+	}
+}
+
+fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
@@ -0,0 +1,507 @@
+use crate::network::NetworkPacket;
+use anyhow::{Context, Result};
+use hmac::{Hmac, Mac};
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use sha2::{Sha256, Sha384};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+const CONTENT_HANDSHAKE: u8 = 22;
+const CONTENT_APPLICATION_DATA: u8 = 23;
+
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const HANDSHAKE_SERVER_HELLO: u8 = 2;
+
+/// Connections followed at once; the least recently active is dropped beyond this
+const MAX_CONNECTIONS: usize = 4096;
+/// Segments held back while waiting for a missing one
+const MAX_OUT_OF_ORDER: usize = 64;
+/// Unparsed bytes after which a stream is considered out of sync; a record
+/// is at most 16 KiB plus overhead
+const MAX_BUFFER: usize = 64 * 1024;
+
+/// Secrets from an NSS key log file, as written by browsers and curl when
+/// `SSLKEYLOGFILE` is set. The file grows while they run, so it is read
+/// again whenever a secret is missing and its size has changed.
+pub struct KeyLog {
+	path: PathBuf,
+	/// Size of the file when it was last read
+	size: u64,
+	/// Secret by (label, client random): `CLIENT_RANDOM` holds TLS 1.2 master
+	/// secrets, `CLIENT_TRAFFIC_SECRET_0` and `SERVER_TRAFFIC_SECRET_0` the
+	/// TLS 1.3 application traffic secrets
+	secrets: HashMap<(String, Vec<u8>), Vec<u8>>,
+}
+
+impl KeyLog {
+	pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+		let mut keylog = KeyLog { path: path.as_ref().to_path_buf(), size: 0, secrets: HashMap::new() };
+		keylog.reload()?;
+		info!("Loaded {} TLS secrets from {}", keylog.secrets.len(), keylog.path.display());
+		Ok(keylog)
+	}
+
+	fn reload(&mut self) -> Result<()> {
+		let content = std::fs::read_to_string(&self.path)
+			.with_context(|| format!("Unable to read TLS key log {}", self.path.display()))?;
+		self.size = content.len() as u64;
+		for line in content.lines().filter(|line| !line.starts_with('#')) {
+			let mut fields = line.split_whitespace();
+			if let (Some(label), Some(random), Some(secret)) = (fields.next(), fields.next(), fields.next())
+				&& let (Some(random), Some(secret)) = (decode_hex(random), decode_hex(secret))
+			{
+				self.secrets.insert((label.to_string(), random), secret);
+			}
+		}
+		Ok(())
+	}
+
+	fn secret(&mut self, label: &str, client_random: &[u8]) -> Option<Vec<u8>> {
+		let key = (label.to_string(), client_random.to_vec());
+		if !self.secrets.contains_key(&key)
+			&& std::fs::metadata(&self.path).is_ok_and(|metadata| metadata.len() != self.size)
+			&& let Err(e) = self.reload()
+		{
+			warn!("{:#}", e);
+		}
+		self.secrets.get(&key).cloned()
+	}
+}
+
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+	if !text.len().is_multiple_of(2) {
+		return None;
+	}
+	(0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HashAlgorithm {
+	Sha256,
+	Sha384,
+}
+
+impl HashAlgorithm {
+	fn hmac(self, key: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+		match self {
+			HashAlgorithm::Sha256 => {
+				let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+				parts.iter().for_each(|part| mac.update(part));
+				mac.finalize().into_bytes().to_vec()
+			}
+			HashAlgorithm::Sha384 => {
+				let mut mac = Hmac::<Sha384>::new_from_slice(key).expect("HMAC accepts any key length");
+				parts.iter().for_each(|part| mac.update(part));
+				mac.finalize().into_bytes().to_vec()
+			}
+		}
+	}
+
+	/// TLS 1.2 PRF (RFC 5246 §5)
+	fn prf(self, secret: &[u8], label: &str, seed: &[u8], length: usize) -> Vec<u8> {
+		let seed = [label.as_bytes(), seed].concat();
+		let mut a = self.hmac(secret, &[&seed]);
+		let mut output = Vec::with_capacity(length);
+		while output.len() < length {
+			output.extend(self.hmac(secret, &[&a, &seed]));
+			a = self.hmac(secret, &[&a]);
+		}
+		output.truncate(length);
+		output
+	}
+
+	/// TLS 1.3 HKDF-Expand-Label with an empty context (RFC 8446 §7.1)
+	fn expand_label(self, secret: &[u8], label: &str, length: usize) -> Vec<u8> {
+		let label = format!("tls13 {}", label);
+		let mut info = Vec::with_capacity(label.len() + 4);
+		info.extend_from_slice(&(length as u16).to_be_bytes());
+		info.push(label.len() as u8);
+		info.extend_from_slice(label.as_bytes());
+		info.push(0);
+
+		let mut output = Vec::with_capacity(length);
+		let mut block = Vec::new();
+		for counter in 1u8.. {
+			if output.len() >= length {
+				break;
+			}
+			block = self.hmac(secret, &[&block, &info, &[counter]]);
+			output.extend_from_slice(&block);
+		}
+		output.truncate(length);
+		output
+	}
+}
+
+/// The AEAD cipher suites that can be decrypted; CBC suites are not supported
+#[derive(Debug, Clone, Copy)]
+struct CipherSuite {
+	algorithm: &'static aead::Algorithm,
+	hash: HashAlgorithm,
+	/// Implicit IV length of the TLS 1.2 key block
+	tls12_iv_len: usize,
+}
+
+impl CipherSuite {
+	fn from_id(id: u16) -> Option<Self> {
+		let (algorithm, hash, tls12_iv_len) = match id {
+			0x1301 | 0x009c | 0x009e | 0xc02b | 0xc02f => (&aead::AES_128_GCM, HashAlgorithm::Sha256, 4),
+			0x1302 | 0x009d | 0x009f | 0xc02c | 0xc030 => (&aead::AES_256_GCM, HashAlgorithm::Sha384, 4),
+			0x1303 | 0xcca8 | 0xcca9 | 0xccaa => (&aead::CHACHA20_POLY1305, HashAlgorithm::Sha256, 12),
+			_ => return None,
+		};
+		Some(CipherSuite { algorithm, hash, tls12_iv_len })
+	}
+}
+
+/// Decrypts the records one side of a connection sends
+struct RecordCipher {
+	key: LessSafeKey,
+	iv: Vec<u8>,
+	tls13: bool,
+	seq: u64,
+}
+
+impl RecordCipher {
+	fn new(suite: CipherSuite, key: &[u8], iv: Vec<u8>, tls13: bool) -> Option<Self> {
+		let key = LessSafeKey::new(UnboundKey::new(suite.algorithm, key).ok()?);
+		Some(RecordCipher { key, iv, tls13, seq: 0 })
+	}
+
+	/// Content type and plaintext of one record. The sequence number only
+	/// advances when the record authenticates.
+	fn decrypt(&mut self, header: &[u8; 5], body: &[u8]) -> Option<(u8, Vec<u8>)> {
+		let mut nonce = [0u8; 12];
+		let mut ciphertext = body;
+		if self.iv.len() == 4 {
+			// TLS 1.2 AES-GCM sends the last 8 nonce bytes with each record
+			nonce[..4].copy_from_slice(&self.iv);
+			nonce[4..].copy_from_slice(body.get(..8)?);
+			ciphertext = &body[8..];
+		} else {
+			nonce.copy_from_slice(&self.iv);
+			for (byte, seq) in nonce[4..].iter_mut().zip(self.seq.to_be_bytes()) {
+				*byte ^= seq;
+			}
+		}
+
+		let plaintext_len = ciphertext.len().checked_sub(self.key.algorithm().tag_len())?;
+		let aad = if self.tls13 {
+			header.to_vec()
+		} else {
+			let mut aad = self.seq.to_be_bytes().to_vec();
+			aad.extend_from_slice(&header[..3]);
+			aad.extend_from_slice(&(plaintext_len as u16).to_be_bytes());
+			aad
+		};
+
+		let mut buffer = ciphertext.to_vec();
+		let plaintext = self.key
+			.open_in_place(Nonce::assume_unique_for_key(nonce), Aad::from(aad), &mut buffer)
+			.ok()?;
+		self.seq += 1;
+
+		if self.tls13 {
+			// Content, then the real content type, then zero padding
+			let end = plaintext.iter().rposition(|&byte| byte != 0)?;
+			Some((plaintext[end], plaintext[..end].to_vec()))
+		} else {
+			Some((header[0], plaintext.to_vec()))
+		}
+	}
+}
+
+/// One direction of a connection, reassembled into TLS records
+#[derive(Default)]
+struct Stream {
+	next_seq: Option<u32>,
+	/// Segments that arrived ahead of `next_seq`
+	out_of_order: Vec<(u32, Vec<u8>)>,
+	buffer: Vec<u8>,
+	/// Records are encrypted from here on; for TLS 1.2 after ChangeCipherSpec
+	encrypted: bool,
+	cipher: Option<RecordCipher>,
+	/// TLS 1.3: a record opened with the application keys, so the encrypted
+	/// handshake is over
+	in_application: bool,
+	/// Lost sync or failed to decrypt; nothing more is read from this side
+	failed: bool,
+	finished: bool,
+}
+
+impl Stream {
+	fn push(&mut self, seq: u32, payload: &[u8]) {
+		let next = *self.next_seq.get_or_insert(seq);
+		if (seq.wrapping_sub(next) as i32) > 0 {
+			if self.out_of_order.len() < MAX_OUT_OF_ORDER {
+				self.out_of_order.push((seq, payload.to_vec()));
+			} else {
+				self.failed = true;
+			}
+			return;
+		}
+		self.append(seq, payload);
+
+		while let Some(index) = self.out_of_order.iter()
+			.position(|(seq, _)| (seq.wrapping_sub(self.next_seq.unwrap_or(*seq)) as i32) <= 0)
+		{
+			let (seq, payload) = self.out_of_order.swap_remove(index);
+			self.append(seq, &payload);
+		}
+	}
+
+	/// Add the part of an in-order or overlapping segment not seen yet
+	fn append(&mut self, seq: u32, payload: &[u8]) {
+		let Some(next) = self.next_seq else { return };
+		let seen = next.wrapping_sub(seq) as usize;
+		if let Some(new) = payload.get(seen..).filter(|new| !new.is_empty()) {
+			self.buffer.extend_from_slice(new);
+			self.next_seq = Some(next.wrapping_add(new.len() as u32));
+		}
+	}
+
+	/// Complete records buffered so far, as (header, body)
+	fn records(&mut self) -> Vec<([u8; 5], Vec<u8>)> {
+		let mut records = Vec::new();
+		let mut pos = 0;
+		while let Some(header) = self.buffer.get(pos..pos + 5) {
+			if !(CONTENT_CHANGE_CIPHER_SPEC..=CONTENT_APPLICATION_DATA).contains(&header[0]) || header[1] != 3 {
+				self.failed = true;
+				break;
+			}
+			let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+			let Some(body) = self.buffer.get(pos + 5..pos + 5 + len) else { break };
+			records.push(([header[0], header[1], header[2], header[3], header[4]], body.to_vec()));
+			pos += 5 + len;
+		}
+		self.buffer.drain(..pos);
+		if self.buffer.len() > MAX_BUFFER {
+			self.failed = true;
+		}
+		records
+	}
+}
+
+#[derive(Default)]
+struct Connection {
+	client_random: Option<Vec<u8>>,
+	server_random: Option<Vec<u8>>,
+	suite: Option<CipherSuite>,
+	tls13: bool,
+	client: Stream,
+	server: Stream,
+	/// Application data was decrypted
+	decrypted: bool,
+	last_seen: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Connection {
+	fn stream(&mut self, from_client: bool) -> &mut Stream {
+		if from_client { &mut self.client } else { &mut self.server }
+	}
+
+	/// Decrypted application data in the records this segment completes
+	fn receive(&mut self, from_client: bool, seq: u32, payload: &[u8], keylog: &mut KeyLog) -> Vec<u8> {
+		let stream = self.stream(from_client);
+		if stream.failed {
+			return Vec::new();
+		}
+		stream.push(seq, payload);
+		let records = stream.records();
+
+		let mut plaintext = Vec::new();
+		for (header, body) in records {
+			let encrypted = self.stream(from_client).encrypted
+				|| (self.tls13 && header[0] == CONTENT_APPLICATION_DATA);
+			match header[0] {
+				CONTENT_HANDSHAKE if !encrypted => self.read_handshake(&body),
+				// TLS 1.3 sends one only for middlebox compatibility
+				CONTENT_CHANGE_CIPHER_SPEC => self.stream(from_client).encrypted = !self.tls13,
+				_ if encrypted => {
+					if let Some((CONTENT_APPLICATION_DATA, data)) = self.decrypt(from_client, &header, &body, keylog) {
+						plaintext.extend_from_slice(&data);
+					}
+				}
+				_ => {}
+			}
+		}
+		plaintext
+	}
+
+	/// Pick the randoms, cipher suite and version out of the hello messages
+	fn read_handshake(&mut self, message: &[u8]) {
+		match message.first() {
+			Some(&HANDSHAKE_CLIENT_HELLO) => self.client_random = message.get(6..38).map(<[u8]>::to_vec),
+			Some(&HANDSHAKE_SERVER_HELLO) => {
+				self.server_random = message.get(6..38).map(<[u8]>::to_vec);
+				let Some(&session_id_len) = message.get(38) else { return };
+				let pos = 39 + session_id_len as usize;
+				let Some(suite) = message.get(pos..pos + 2).map(|id| u16::from_be_bytes([id[0], id[1]])) else { return };
+				self.suite = CipherSuite::from_id(suite);
+				if self.suite.is_none() {
+					debug!("TLS cipher suite {:#06x} cannot be decrypted", suite);
+				}
+
+				// supported_versions selecting TLS 1.3
+				let mut pos = pos + 2 + 1 + 2;
+				while let Some(header) = message.get(pos..pos + 4) {
+					let kind = u16::from_be_bytes([header[0], header[1]]);
+					let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+					if kind == 0x002b && message.get(pos + 4..pos + 6) == Some(&[0x03, 0x04]) {
+						self.tls13 = true;
+					}
+					pos += 4 + len;
+				}
+			}
+			_ => {}
+		}
+	}
+
+	fn decrypt(&mut self, from_client: bool, header: &[u8; 5], body: &[u8], keylog: &mut KeyLog) -> Option<(u8, Vec<u8>)> {
+		if self.stream(from_client).cipher.is_none() {
+			let cipher = self.cipher(from_client, keylog)?;
+			self.stream(from_client).cipher = Some(cipher);
+		}
+
+		let tls13 = self.tls13;
+		let stream = self.stream(from_client);
+		let cipher = stream.cipher.as_mut()?;
+		if tls13 && !stream.in_application {
+			// Handshake records are under keys that are not derived here; they
+			// fail to open until the first application record
+			let record = cipher.decrypt(header, body)?;
+			stream.in_application = true;
+			return Some(record);
+		}
+		let record = cipher.decrypt(header, body);
+		if record.is_none() {
+			debug!("TLS record failed to authenticate, no longer decrypting this direction");
+			stream.failed = true;
+		}
+		record
+	}
+
+	fn cipher(&self, from_client: bool, keylog: &mut KeyLog) -> Option<RecordCipher> {
+		let suite = self.suite?;
+		let client_random = self.client_random.as_deref()?;
+		let key_len = suite.algorithm.key_len();
+
+		if self.tls13 {
+			let label = if from_client { "CLIENT_TRAFFIC_SECRET_0" } else { "SERVER_TRAFFIC_SECRET_0" };
+			let secret = keylog.secret(label, client_random)?;
+			let key = suite.hash.expand_label(&secret, "key", key_len);
+			let iv = suite.hash.expand_label(&secret, "iv", 12);
+			return RecordCipher::new(suite, &key, iv, true);
+		}
+
+		let master_secret = keylog.secret("CLIENT_RANDOM", client_random)?;
+		let server_random = self.server_random.as_deref()?;
+		let iv_len = suite.tls12_iv_len;
+		// client and server write keys, then client and server IVs; AEAD
+		// suites have no MAC keys
+		let key_block = suite.hash.prf(&master_secret, "key expansion",
+			&[server_random, client_random].concat(), 2 * (key_len + iv_len));
+		let (keys, ivs) = key_block.split_at(2 * key_len);
+		let (key, iv) = if from_client {
+			(&keys[..key_len], &ivs[..iv_len])
+		} else {
+			(&keys[key_len..], &ivs[iv_len..])
+		};
+		RecordCipher::new(suite, key, iv.to_vec(), false)
+	}
+}
+
+/// TCP 4-tuple seen from the client side
+type ConnectionKey = (String, u16, String, u16);
+
+/// Follows the TLS connections in captured traffic and decrypts their
+/// application data with secrets from a [`KeyLog`]. Only connections whose
+/// handshake was captured can be decrypted.
+pub struct TlsDecryptor {
+	keylog: KeyLog,
+	connections: HashMap<ConnectionKey, Connection>,
+	decrypted_sessions: usize,
+}
+
+impl TlsDecryptor {
+	pub fn new(keylog: KeyLog) -> Self {
+		TlsDecryptor { keylog, connections: HashMap::new(), decrypted_sessions: 0 }
+	}
+
+	/// Connections whose application data was decrypted so far
+	pub fn decrypted_sessions(&self) -> usize {
+		self.decrypted_sessions
+	}
+
+	/// The plaintext of the records `packet` completes, as a packet with the
+	/// same addresses and `application_protocol` `TLS`
+	pub fn process(&mut self, packet: &NetworkPacket) -> Option<NetworkPacket> {
+		if packet.protocol != "TCP" {
+			return None;
+		}
+		let forward = (packet.src_ip.clone(), packet.src_port, packet.dst_ip.clone(), packet.dst_port);
+		let reverse = (packet.dst_ip.clone(), packet.dst_port, packet.src_ip.clone(), packet.src_port);
+		let (key, from_client) = if self.connections.contains_key(&forward) {
+			(forward, true)
+		} else if self.connections.contains_key(&reverse) {
+			(reverse, false)
+		} else if is_client_hello(&packet.payload) {
+			self.evict();
+			self.connections.insert(forward.clone(), Connection::default());
+			(forward, true)
+		} else {
+			return None;
+		};
+
+		let connection = self.connections.get_mut(&key)?;
+		connection.last_seen = Some(packet.timestamp);
+		let plaintext = match packet.tcp_seq {
+			Some(seq) if !packet.payload.is_empty() => connection.receive(from_client, seq, &packet.payload, &mut self.keylog),
+			_ => Vec::new(),
+		};
+
+		if !plaintext.is_empty() && !connection.decrypted {
+			connection.decrypted = true;
+			self.decrypted_sessions += 1;
+			info!("Decrypting TLS{} session {}:{} → {}:{}", if connection.tls13 { " 1.3" } else { " 1.2" },
+				key.0, key.1, key.2, key.3);
+		}
+
+		// FIN or RST
+		let flags = packet.tcp_flags.unwrap_or(0);
+		if flags & 0x04 != 0 {
+			self.connections.remove(&key);
+		} else if flags & 0x01 != 0 {
+			connection.stream(from_client).finished = true;
+			if connection.client.finished && connection.server.finished {
+				self.connections.remove(&key);
+			}
+		}
+
+		(!plaintext.is_empty()).then(|| NetworkPacket {
+			application_protocol: Some("TLS"),
+			payload_size: plaintext.len(),
+			payload: plaintext,
+			..packet.clone()
+		})
+	}
+
+	fn evict(&mut self) {
+		if self.connections.len() < MAX_CONNECTIONS {
+			return;
+		}
+		if let Some(oldest) = self.connections.iter()
+			.min_by_key(|(_, connection)| connection.last_seen)
+			.map(|(key, _)| key.clone())
+		{
+			self.connections.remove(&oldest);
+		}
+	}
+}
+
+/// A TLS handshake record starting with a ClientHello
+fn is_client_hello(payload: &[u8]) -> bool {
+	payload.len() > 5 && payload[0] == CONTENT_HANDSHAKE && payload[1] == 3 && payload[5] == HANDSHAKE_CLIENT_HELLO
+}
@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Result};
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tracing::info;
+use x509_parser::prelude::*;
+
+#[derive(Debug, Clone)]
+pub struct CertificateInfo {
+	pub subject: String,
+	pub issuer: String,
+	pub serial: String,
+	pub sans: Vec<String>,
+	pub not_before: chrono::DateTime<chrono::Utc>,
+	pub not_after: chrono::DateTime<chrono::Utc>,
+	pub key_type: String,
+	pub signature_algorithm: String,
+	pub is_ca: bool,
+}
+
+impl CertificateInfo {
+	pub fn days_until_expiry(&self) -> i64 {
+		(self.not_after - chrono::Utc::now()).num_days()
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsReport {
+	pub host: String,
+	pub port: u16,
+	pub protocol: String,
+	pub cipher_suite: String,
+	pub alpn: Option<String>,
+	pub chain: Vec<CertificateInfo>,
+	/// `Err` holds the reason the chain would be rejected by a normal client
+	pub verification: std::result::Result<(), String>,
+	pub ocsp_stapled: bool,
+	pub tcp_connect_ms: u64,
+	/// TLS handshake alone, after the TCP connection was established
+	pub handshake_ms: u64,
+}
+
+#[derive(Default)]
+struct Captured {
+	chain: Vec<Certificate>,
+	ocsp_response: Vec<u8>,
+	verification: Option<std::result::Result<(), String>>,
+}
+
+/// Verifier that records what the server presented and whether standard
+/// WebPKI validation accepts it, but never aborts the handshake, so expired
+/// or self-signed chains can still be inspected.
+struct CapturingVerifier {
+	inner: WebPkiVerifier,
+	captured: Arc<Mutex<Captured>>,
+}
+
+impl ServerCertVerifier for CapturingVerifier {
+	fn verify_server_cert(
+		&self,
+		end_entity: &Certificate,
+		intermediates: &[Certificate],
+		server_name: &ServerName,
+		scts: &mut dyn Iterator<Item = &[u8]>,
+		ocsp_response: &[u8],
+		now: SystemTime,
+	) -> std::result::Result<ServerCertVerified, rustls::Error> {
+		let verification = self.inner
+			.verify_server_cert(end_entity, intermediates, server_name, scts, ocsp_response, now)
+			.map(|_| ())
+			.map_err(|e| e.to_string());
+
+		let mut captured = self.captured.lock().unwrap();
+		captured.chain = std::iter::once(end_entity).chain(intermediates).cloned().collect();
+		captured.ocsp_response = ocsp_response.to_vec();
+		captured.verification = Some(verification);
+
+		Ok(ServerCertVerified::assertion())
+	}
+}
+
+/// Accepts `https://host[:port]/...` or a bare `host[:port]`
+pub fn parse_target(target: &str) -> Result<(String, u16)> {
+	let with_scheme = if target.contains("://") {
+		target.to_string()
+	} else {
+		format!("https://{}", target)
+	};
+	let url = url::Url::parse(&with_scheme)?;
+	let host = url.host_str().ok_or_else(|| anyhow!("No host in {}", target))?.to_string();
+	let port = url.port_or_known_default().unwrap_or(443);
+	Ok((host, port))
+}
+
+fn capturing_connector(captured: Arc<Mutex<Captured>>) -> TlsConnector {
+	let mut roots = RootCertStore::empty();
+	roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+		OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+	}));
+	let verifier = CapturingVerifier {
+		inner: WebPkiVerifier::new(roots, None),
+		captured,
+	};
+
+	let mut config = ClientConfig::builder()
+		.with_safe_defaults()
+		.with_custom_certificate_verifier(Arc::new(verifier))
+		.with_no_client_auth();
+	config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+	TlsConnector::from(Arc::new(config))
+}
+
+/// Time a TLS handshake with `host` over an already connected `tcp` stream.
+/// Like `inspect`, an untrusted certificate does not abort the handshake.
+pub async fn timed_handshake(tcp: TcpStream, host: &str) -> Result<Duration> {
+	let connector = capturing_connector(Arc::new(Mutex::new(Captured::default())));
+	let server_name = ServerName::try_from(host).map_err(|_| anyhow!("Invalid server name: {}", host))?;
+	let start = std::time::Instant::now();
+	connector.connect(server_name, tcp).await?;
+	Ok(start.elapsed())
+}
+
+pub async fn inspect(host: &str, port: u16, timeout: Duration) -> Result<TlsReport> {
+	info!("Inspecting TLS configuration of {}:{}", host, port);
+
+	let captured = Arc::new(Mutex::new(Captured::default()));
+	let connector = capturing_connector(captured.clone());
+	let server_name = ServerName::try_from(host).map_err(|_| anyhow!("Invalid server name: {}", host))?;
+
+	let start = std::time::Instant::now();
+	let tcp = tokio::time::timeout(timeout, TcpStream::connect((host, port)))
+		.await
+		.map_err(|_| anyhow!("TCP connect to {}:{} timed out after {}s", host, port, timeout.as_secs()))??;
+	let tcp_connect_ms = start.elapsed().as_millis() as u64;
+
+	let start = std::time::Instant::now();
+	let stream = tokio::time::timeout(timeout, connector.connect(server_name, tcp))
+		.await
+		.map_err(|_| anyhow!("TLS handshake with {}:{} timed out after {}s", host, port, timeout.as_secs()))??;
+	let handshake_ms = start.elapsed().as_millis() as u64;
+
+	let (_, connection) = stream.get_ref();
+	let protocol = connection.protocol_version()
+		.map_or_else(|| "unknown".to_string(), |v| format!("{:?}", v));
+	let cipher_suite = connection.negotiated_cipher_suite()
+		.map_or_else(|| "unknown".to_string(), |s| format!("{:?}", s.suite()));
+	let alpn = connection.alpn_protocol().map(|p| String::from_utf8_lossy(p).to_string());
+
+	let captured = std::mem::take(&mut *captured.lock().unwrap());
+	let chain = captured.chain.iter()
+		.map(|cert| parse_certificate(&cert.0))
+		.collect::<Result<Vec<_>>>()?;
+
+	Ok(TlsReport {
+		host: host.to_string(),
+		port,
+		protocol,
+		cipher_suite,
+		alpn,
+		chain,
+		verification: captured.verification.unwrap_or_else(|| Err("certificate was not verified".to_string())),
+		ocsp_stapled: !captured.ocsp_response.is_empty(),
+		tcp_connect_ms,
+		handshake_ms,
+	})
+}
+
+fn parse_certificate(der: &[u8]) -> Result<CertificateInfo> {
+	let (_, cert) = X509Certificate::from_der(der)
+		.map_err(|e| anyhow!("Unable to parse certificate: {}", e))?;
+
+	let sans = match cert.subject_alternative_name() {
+		Ok(Some(ext)) => ext.value.general_names.iter()
+			.map(|name| match name {
+				GeneralName::DNSName(dns) => format!("DNS:{}", dns),
+				GeneralName::IPAddress(ip) => format!("IP:{}", format_ip(ip)),
+				other => format!("{}", other),
+			})
+			.collect(),
+		_ => Vec::new(),
+	};
+
+	let key_type = match cert.public_key().parsed() {
+		Ok(x509_parser::public_key::PublicKey::RSA(rsa)) => format!("RSA {} bits", rsa.key_size()),
+		Ok(x509_parser::public_key::PublicKey::EC(ec)) => format!("EC {} bits", ec.key_size()),
+		Ok(x509_parser::public_key::PublicKey::DSA(_)) => "DSA".to_string(),
+		_ => oid_name(&cert.public_key().algorithm.algorithm),
+	};
+
+	let timestamp = |t: &ASN1Time| {
+		chrono::DateTime::from_timestamp(t.timestamp(), 0).unwrap_or_default()
+	};
+
+	Ok(CertificateInfo {
+		subject: cert.subject().to_string(),
+		issuer: cert.issuer().to_string(),
+		serial: cert.raw_serial_as_string(),
+		sans,
+		not_before: timestamp(&cert.validity().not_before),
+		not_after: timestamp(&cert.validity().not_after),
+		key_type,
+		signature_algorithm: oid_name(&cert.signature_algorithm.algorithm),
+		is_ca: cert.is_ca(),
+	})
+}
+
+fn oid_name(oid: &x509_parser::der_parser::oid::Oid) -> String {
+	oid2sn(oid, oid_registry()).map_or_else(|_| oid.to_id_string(), str::to_string)
+}
+
+fn format_ip(bytes: &[u8]) -> String {
+	match bytes.len() {
+		4 => std::net::Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).to_string(),
+		16 => {
+			let mut octets = [0u8; 16];
+			octets.copy_from_slice(bytes);
+			std::net::Ipv6Addr::from(octets).to_string()
+		}
+		_ => format!("{:?}", bytes),
+	}
+}
+
+pub fn print_report(report: &TlsReport) {
+	println!("=== TLS: {}:{} ===", report.host, report.port);
+	println!("Protocol: {}", report.protocol);
+	println!("Cipher Suite: {}", report.cipher_suite);
+	println!("ALPN: {}", report.alpn.as_deref().unwrap_or("-"));
+	println!("TCP Connect: {}ms, TLS Handshake: {}ms", report.tcp_connect_ms, report.handshake_ms);
+	println!("OCSP Stapling: {}", if report.ocsp_stapled { "✅ stapled response present" } else { "❌ not stapled" });
+	match report.verification {
+		Ok(()) => println!("Verification: ✅ trusted chain for {}", report.host),
+		Err(ref reason) => println!("Verification: ❌ {}", reason),
+	}
+
+	for (i, cert) in report.chain.iter().enumerate() {
+		let role = match i {
+			0 => "leaf",
+			_ if cert.is_ca => "intermediate CA",
+			_ => "intermediate",
+		};
+		println!("\n--- Certificate {} ({}) ---", i, role);
+		println!("Subject: {}", cert.subject);
+		println!("Issuer: {}", cert.issuer);
+		println!("Serial: {}", cert.serial);
+		if !cert.sans.is_empty() {
+			println!("SANs: {}", cert.sans.join(", "));
+		}
+		println!("Valid: {} → {}", cert.not_before.format("%Y-%m-%d %H:%M:%S UTC"), cert.not_after.format("%Y-%m-%d %H:%M:%S UTC"));
+		let days = cert.days_until_expiry();
+		let marker = match days {
+			d if d < 0 => "❌ expired",
+			0..=13 => "🚨",
+			14..=29 => "⚠️",
+			_ => "✅",
+		};
+		println!("Expires in: {} days {}", days, marker);
+		println!("Key: {}", cert.key_type);
+		println!("Signature: {}", cert.signature_algorithm);
+	}
+	println!();
+}
@@ -0,0 +1,135 @@
+use anyhow::{anyhow, Context, Result};
+use pnet::packet::icmp::{IcmpPacket, IcmpTypes};
+use pnet::packet::ip::IpNextHeaderProtocols;
+use pnet::packet::Packet;
+use pnet::transport::{icmp_packet_iter, transport_channel, TransportChannelType, TransportProtocol};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// First destination port of the probes, as in the classic traceroute
+const BASE_PORT: u16 = 33434;
+
+/// One TTL step on the path to the target
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hop {
+	pub ttl: u8,
+	/// The router that answered, `None` when every probe timed out
+	pub address: Option<String>,
+	/// Round-trip time of every answered probe
+	pub rtt_ms: Vec<f64>,
+}
+
+impl Hop {
+	pub fn average_rtt_ms(&self) -> Option<f64> {
+		(!self.rtt_ms.is_empty()).then(|| self.rtt_ms.iter().sum::<f64>() / self.rtt_ms.len() as f64)
+	}
+}
+
+pub struct TraceOptions {
+	pub max_hops: u8,
+	pub probes_per_hop: u8,
+	/// How long to wait for the answer to each probe
+	pub timeout: Duration,
+}
+
+impl Default for TraceOptions {
+	fn default() -> Self {
+		Self { max_hops: 30, probes_per_hop: 3, timeout: Duration::from_secs(1) }
+	}
+}
+
+/// Trace the route to `host` with UDP probes of increasing TTL, reading the
+/// ICMP time-exceeded and port-unreachable answers from a raw socket. IPv4
+/// only; like packet capture this needs root or CAP_NET_RAW.
+pub async fn trace(host: &str, options: TraceOptions) -> Result<Vec<Hop>> {
+	let target = tokio::net::lookup_host((host, 0))
+		.await
+		.with_context(|| format!("Unable to resolve {}", host))?
+		.find_map(|address| match address.ip() {
+			IpAddr::V4(ip) => Some(ip),
+			IpAddr::V6(_) => None,
+		})
+		.ok_or_else(|| anyhow!("{} has no IPv4 address to trace", host))?;
+	info!("Tracing route to {} ({})", host, target);
+
+	tokio::task::spawn_blocking(move || trace_blocking(target, &options)).await?
+}
+
+fn trace_blocking(target: Ipv4Addr, options: &TraceOptions) -> Result<Vec<Hop>> {
+	let (_tx, mut rx) = transport_channel(
+		4096,
+		TransportChannelType::Layer4(TransportProtocol::Ipv4(IpNextHeaderProtocols::Icmp)),
+	)
+	.map_err(|e| anyhow!("Unable to open an ICMP socket (traceroute needs root or CAP_NET_RAW): {}", e))?;
+	let mut icmp = icmp_packet_iter(&mut rx);
+	let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+
+	let mut hops = Vec::new();
+	let mut probe: u16 = 0;
+	for ttl in 1..=options.max_hops {
+		socket.set_ttl(ttl as u32)?;
+		let mut hop = Hop { ttl, address: None, rtt_ms: Vec::new() };
+		let mut reached = false;
+
+		for _ in 0..options.probes_per_hop.max(1) {
+			let port = BASE_PORT.wrapping_add(probe);
+			probe = probe.wrapping_add(1);
+			let sent = Instant::now();
+			socket.send_to(&[0u8; 32], SocketAddr::new(IpAddr::V4(target), port))?;
+
+			// Other ICMP traffic arrives on the same socket; wait for ours
+			while let Some(remaining) = options.timeout.checked_sub(sent.elapsed()) {
+				let Some((packet, from)) = icmp.next_with_timeout(remaining)? else {
+					break;
+				};
+				let Some(destination_reached) = answers_probe(&packet, target, port) else {
+					continue;
+				};
+				hop.address = Some(from.to_string());
+				hop.rtt_ms.push(sent.elapsed().as_secs_f64() * 1000.0);
+				reached |= destination_reached;
+				break;
+			}
+		}
+
+		debug!("Hop {}: {:?} {:?}", ttl, hop.address, hop.rtt_ms);
+		hops.push(hop);
+		if reached {
+			return Ok(hops);
+		}
+	}
+	Ok(hops)
+}
+
+/// Whether `packet` answers the probe sent to `target:port`: `Some(false)` for
+/// a router on the way, `Some(true)` once the target itself answered
+fn answers_probe(packet: &IcmpPacket, target: Ipv4Addr, port: u16) -> Option<bool> {
+	let reached = match packet.get_icmp_type() {
+		IcmpTypes::TimeExceeded => false,
+		IcmpTypes::DestinationUnreachable => true,
+		_ => return None,
+	};
+	// 4 unused bytes, then the IP header and first 8 bytes of our datagram
+	let original = packet.payload().get(4..)?;
+	let header_len = (*original.first()? & 0x0f) as usize * 4;
+	let destination = Ipv4Addr::new(*original.get(16)?, *original.get(17)?, *original.get(18)?, *original.get(19)?);
+	let udp = original.get(header_len..header_len + 4)?;
+	let destination_port = u16::from_be_bytes([udp[2], udp[3]]);
+	(destination == target && destination_port == port).then_some(reached)
+}
+
+/// One line per hop in the usual traceroute layout
+pub fn format_hops(hops: &[Hop]) -> String {
+	hops.iter()
+		.map(|hop| {
+			let rtts = hop.rtt_ms.iter().map(|rtt| format!("{:.1}ms", rtt)).collect::<Vec<_>>().join("  ");
+			match &hop.address {
+				Some(address) => format!("{:>2}  {:<16} {}", hop.ttl, address, rtts),
+				None => format!("{:>2}  *", hop.ttl),
+			}
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
@@ -0,0 +1,156 @@
+use anyhow::{anyhow, Result};
+use std::net::SocketAddr;
+use tokio::net::TcpStream;
+
+/// Whether `proxy --transparent` can recover original destinations here
+pub const SUPPORTED: bool = cfg!(any(target_os = "linux", target_os = "macos", target_os = "freebsd", target_os = "openbsd"));
+
+/// What the first bytes of a redirected connection look like
+#[derive(Debug, PartialEq)]
+pub enum Protocol {
+	/// A TLS ClientHello, with the server name it asks for
+	Tls { server_name: Option<String> },
+	/// An HTTP/1 request, with the host of its Host header (without the
+	/// port) when the header came with the first bytes
+	Http { host: Option<String> },
+	/// Anything else, relayed untouched
+	Other,
+}
+
+impl Protocol {
+	pub fn sniff(first: &[u8]) -> Self {
+		// Handshake record header, then the ClientHello message
+		if first.len() > 5 && first[0] == 0x16 && first[1] == 0x03 {
+			let server_name = crate::quic::parse_client_hello(&first[5..]).and_then(|(server_name, _)| server_name);
+			return Protocol::Tls { server_name };
+		}
+
+		let method = first.iter().take_while(|b| b.is_ascii_uppercase()).count();
+		if (3..=7).contains(&method) && first.get(method) == Some(&b' ') {
+			let head = String::from_utf8_lossy(first);
+			let host = head.lines()
+				.skip(1)
+				.take_while(|line| !line.is_empty())
+				.filter_map(|line| line.split_once(':'))
+				.find(|(name, _)| name.trim().eq_ignore_ascii_case("host"))
+				.map(|(_, value)| strip_port(value.trim()).to_string());
+			return Protocol::Http { host };
+		}
+		Protocol::Other
+	}
+}
+
+/// `example.com:8080` → `example.com`, `[::1]:8080` → `[::1]`
+fn strip_port(host: &str) -> &str {
+	match host.rsplit_once(':') {
+		Some((name, port)) if !name.is_empty() && port.bytes().all(|b| b.is_ascii_digit())
+			&& (!name.contains(':') || name.ends_with(']')) => name,
+		_ => host,
+	}
+}
+
+const NOT_REDIRECTED: &str = "connection was not redirected to the proxy; --transparent only serves connections redirected by iptables/pf";
+
+/// Where the client connected to before the firewall redirected the
+/// connection to the proxy
+pub async fn original_destination(stream: &TcpStream) -> Result<SocketAddr> {
+	let destination = lookup(stream).await?;
+	if destination == stream.local_addr()? {
+		return Err(anyhow!(NOT_REDIRECTED));
+	}
+	Ok(destination)
+}
+
+/// netfilter's NAT keeps the address a REDIRECT or DNAT replaced, readable
+/// with SO_ORIGINAL_DST (IP6T_SO_ORIGINAL_DST, the same number, for IPv6)
+#[cfg(target_os = "linux")]
+async fn lookup(stream: &TcpStream) -> Result<SocketAddr> {
+	use std::net::{Ipv4Addr, Ipv6Addr};
+	use std::os::fd::AsRawFd;
+
+	let fd = stream.as_raw_fd();
+	let ipv4 = match stream.local_addr()? {
+		SocketAddr::V4(_) => true,
+		SocketAddr::V6(local) => local.ip().to_ipv4_mapped().is_some(),
+	};
+	// ENOENT: the connection has no NAT entry, so nothing redirected it
+	let failed = || match std::io::Error::last_os_error() {
+		e if e.raw_os_error() == Some(libc::ENOENT) => anyhow!(NOT_REDIRECTED),
+		e => anyhow!("Unable to read the original destination: {}", e),
+	};
+	if ipv4 {
+		// SAFETY: sockaddr_in is plain data and getsockopt writes at most `len` bytes into it
+		let mut address: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+		let mut len = std::mem::size_of::<libc::sockaddr_in>() as libc::socklen_t;
+		let result = unsafe {
+			libc::getsockopt(fd, libc::SOL_IP, libc::SO_ORIGINAL_DST, (&mut address as *mut libc::sockaddr_in).cast(), &mut len)
+		};
+		if result != 0 {
+			return Err(failed());
+		}
+		Ok(SocketAddr::from((Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr)), u16::from_be(address.sin_port))))
+	} else {
+		// SAFETY: as above, with sockaddr_in6
+		let mut address: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+		let mut len = std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+		let result = unsafe {
+			libc::getsockopt(fd, libc::SOL_IPV6, libc::SO_ORIGINAL_DST, (&mut address as *mut libc::sockaddr_in6).cast(), &mut len)
+		};
+		if result != 0 {
+			return Err(failed());
+		}
+		Ok(SocketAddr::from((Ipv6Addr::from(address.sin6_addr.s6_addr), u16::from_be(address.sin6_port))))
+	}
+}
+
+/// pf keeps the original destination of an `rdr` in its state table, which
+/// `pfctl -s state` prints (needs root)
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+async fn lookup(stream: &TcpStream) -> Result<SocketAddr> {
+	use anyhow::Context;
+
+	let client = stream.peer_addr()?;
+	let output = tokio::process::Command::new("pfctl")
+		.args(["-s", "state"])
+		.output()
+		.await
+		.context("Unable to run pfctl")?;
+	if !output.status.success() {
+		return Err(anyhow!("pfctl -s state failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+	}
+	pf_state_lookup(&String::from_utf8_lossy(&output.stdout), client)
+		.ok_or_else(|| anyhow!("No pf state for the connection from {}; is it redirected with rdr?", client))
+}
+
+/// Find the state of `client` in `pfctl -s state` output, where the redirect
+/// target, original destination and client follow each other:
+///
+/// `ALL tcp 127.0.0.1:8080 <- 93.184.216.34:80 <- 192.168.1.5:51234  ESTABLISHED:ESTABLISHED` (macOS, OpenBSD)
+/// `all tcp 127.0.0.1:8080 (93.184.216.34:80) <- 192.168.1.5:51234  ESTABLISHED:ESTABLISHED` (FreeBSD)
+///
+/// IPv6 addresses are printed as `2001:db8::1[80]`.
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn pf_state_lookup(states: &str, client: SocketAddr) -> Option<SocketAddr> {
+	let client = SocketAddr::new(client.ip().to_canonical(), client.port());
+	states.lines().find_map(|line| {
+		let fields: Vec<&str> = line.split_whitespace().collect();
+		let at = fields.iter().position(|field| pf_address(field) == Some(client))?;
+		if at < 2 || fields[at - 1] != "<-" {
+			return None;
+		}
+		pf_address(fields[at - 2].trim_start_matches('(').trim_end_matches(')'))
+	})
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "openbsd"))]
+fn pf_address(field: &str) -> Option<SocketAddr> {
+	if let Some((ip, port)) = field.strip_suffix(']').and_then(|field| field.rsplit_once('[')) {
+		return Some(SocketAddr::new(ip.parse().ok()?, port.parse().ok()?));
+	}
+	field.parse().ok()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd", target_os = "openbsd")))]
+async fn lookup(_stream: &TcpStream) -> Result<SocketAddr> {
+	Err(anyhow!("--transparent needs Linux (iptables/nftables REDIRECT) or macOS/BSD (pf rdr)"))
+}
@@ -0,0 +1,162 @@
+use crate::http_client::{HttpClient, HttpVersion};
+use crate::notify::{Notification, Notifier, Severity};
+use crate::performance_analyzer::{self, LatencyStats};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Limits a target must stay within over the rolling window
+#[derive(Debug, Clone, Default)]
+pub struct Sla {
+	pub p95_ms: Option<u64>,
+	/// Percentage of failed probes (transport errors and 5xx)
+	pub error_rate: Option<f64>,
+}
+
+pub struct WatchOptions {
+	pub interval: Duration,
+	/// Number of most recent probes per target the SLA is evaluated over
+	pub window: usize,
+	pub sla: Sla,
+	/// Announces breaches and recoveries; `--webhook` and `--notify` are added to the config's channels
+	pub notifier: Notifier,
+	pub verify_ssl: bool,
+	pub http_version: HttpVersion,
+}
+
+/// Probe results of one target, oldest first
+#[derive(Default)]
+struct Window {
+	samples: VecDeque<Option<u64>>,
+	breached: bool,
+}
+
+/// Details of the notification sent when the SLA state of a target changes
+#[derive(Debug, Serialize)]
+struct Alert<'a> {
+	text: String,
+	url: &'a str,
+	state: &'static str,
+	violations: &'a [String],
+	p95_ms: Option<u64>,
+	error_rate: f64,
+	window: usize,
+	timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+impl Window {
+	fn push(&mut self, sample: Option<u64>, capacity: usize) {
+		if self.samples.len() == capacity {
+			self.samples.pop_front();
+		}
+		self.samples.push_back(sample);
+	}
+
+	fn latency(&self) -> Option<LatencyStats> {
+		LatencyStats::from_samples(&self.samples.iter().flatten().copied().collect::<Vec<_>>())
+	}
+
+	fn error_rate(&self) -> f64 {
+		let errors = self.samples.iter().filter(|s| s.is_none()).count();
+		errors as f64 * 100.0 / self.samples.len().max(1) as f64
+	}
+
+	/// Human readable description of every SLA limit currently exceeded
+	fn violations(&self, sla: &Sla) -> Vec<String> {
+		let mut violations = Vec::new();
+		let p95 = self.latency().map(|stats| stats.p95_ms);
+		if let (Some(limit), Some(p95)) = (sla.p95_ms, p95)
+			&& p95 > limit {
+			violations.push(format!("p95 {}ms > {}ms", p95, limit));
+		}
+		if let Some(limit) = sla.error_rate {
+			let rate = self.error_rate();
+			if rate > limit {
+				violations.push(format!("error rate {:.1}% > {}%", rate, limit));
+			}
+		}
+		violations
+	}
+}
+
+/// Probe every URL once per interval until Ctrl+C, printing the rolling
+/// statistics and alerting when a target starts or stops breaching the SLA
+pub async fn run(urls: &[String], options: &WatchOptions, http_client: Arc<HttpClient>) -> Result<()> {
+	let window_size = options.window.max(1);
+	let mut windows: HashMap<&str, Window> = HashMap::new();
+	let mut ticker = tokio::time::interval(options.interval);
+	ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+	println!("👀 Watching {} target(s) every {}s over the last {} probes (Ctrl+C to stop)\n",
+		urls.len(), options.interval.as_secs(), window_size);
+
+	loop {
+		tokio::select! {
+			_ = ticker.tick() => {},
+			_ = tokio::signal::ctrl_c() => break,
+		}
+
+		for url in urls {
+			let request = performance_analyzer::probe_request(url, options.verify_ssl, options.http_version);
+			let (sample, outcome) = match http_client.send_request(request).await {
+				Ok(response) if response.status < 500 => (Some(response.response_time_ms),
+					format!("HTTP {} in {}ms", response.status, response.response_time_ms)),
+				Ok(response) => (None, format!("HTTP {}", response.status)),
+				Err(e) => (None, format!("error: {}", e)),
+			};
+
+			let window = windows.entry(url.as_str()).or_default();
+			window.push(sample, window_size);
+			let p95 = window.latency().map_or_else(|| "-".to_string(), |stats| format!("{}ms", stats.p95_ms));
+			println!("[{}] {} {} (p95 {}, errors {:.1}%)",
+				chrono::Local::now().format("%H:%M:%S"), url, outcome, p95, window.error_rate());
+
+			let violations = window.violations(&options.sla);
+			let breached = !violations.is_empty();
+			if breached == window.breached {
+				continue;
+			}
+			window.breached = breached;
+
+			let alert = Alert {
+				text: if breached {
+					format!("🚨 SLA breached for {}: {}", url, violations.join(", "))
+				} else {
+					format!("✅ SLA recovered for {}", url)
+				},
+				url,
+				state: if breached { "breached" } else { "recovered" },
+				violations: &violations,
+				p95_ms: window.latency().map(|stats| stats.p95_ms),
+				error_rate: window.error_rate(),
+				window: window.samples.len(),
+				timestamp: chrono::Utc::now(),
+			};
+			println!("{}", alert.text);
+			let severity = if breached { Severity::Critical } else { Severity::Info };
+			options.notifier.send(&Notification::new("analyze.sla", severity, alert.text.clone()).with(&alert)).await;
+		}
+	}
+
+	println!("\nStopped watching");
+	Ok(())
+}
+
+/// A latency limit such as `800ms`, `1.5s` or plain milliseconds
+pub fn parse_millis(value: &str) -> Result<u64> {
+	let value = value.trim();
+	let invalid = || anyhow!("Invalid latency '{}': expected e.g. 800ms or 1.5s", value);
+	let ms = if let Some(ms) = value.strip_suffix("ms") {
+		ms.trim().parse::<f64>().map_err(|_| invalid())?
+	} else if let Some(seconds) = value.strip_suffix('s') {
+		seconds.trim().parse::<f64>().map_err(|_| invalid())? * 1000.0
+	} else {
+		value.parse::<f64>().map_err(|_| invalid())?
+	};
+	if ms < 0.0 {
+		return Err(invalid());
+	}
+	Ok(ms.round() as u64)
+}
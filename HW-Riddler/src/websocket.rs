@@ -0,0 +1,280 @@
+use anyhow::{anyhow, Context, Result};
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+use tracing::info;
+
+/// Payload bytes shown for each frame
+const PREVIEW_BYTES: usize = 120;
+
+/// Largest handshake response head accepted from the origin
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+/// Largest body of a refused upgrade passed on to the client
+const MAX_REFUSAL_BODY: usize = 4 * 1024 * 1024;
+
+/// Request headers not passed on to the origin. Without extensions the frames
+/// stay uncompressed, so their payloads can be previewed.
+const DROPPED_HEADERS: [&str; 3] = ["proxy-authorization", "proxy-connection", "sec-websocket-extensions"];
+
+/// The origin side of a proxied WebSocket, plain or TLS
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// The origin's answer to an upgrade request
+pub struct Upstream {
+	pub status: u16,
+	/// The raw response head, and the body if the upgrade was refused
+	pub response: Vec<u8>,
+	pub stream: BufReader<Box<dyn Stream>>,
+}
+
+/// Which way a frame travels
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+	ClientToServer,
+	ServerToClient,
+}
+
+/// Frames and payload bytes relayed one way
+#[derive(Debug, Default)]
+struct FrameStats {
+	frames: u64,
+	bytes: u64,
+}
+
+/// Whether a request asks to switch to the WebSocket protocol
+pub fn is_upgrade<'a>(mut headers: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+	headers.any(|(name, value)| name.trim().eq_ignore_ascii_case("upgrade") && value.trim().eq_ignore_ascii_case("websocket"))
+}
+
+/// Connect to the origin of `url` (`ws`/`http` in the clear, `wss`/`https`
+/// over TLS), send the upgrade request with `headers` and read the response
+pub async fn handshake(url: &url::Url, headers: &[(String, String)]) -> Result<Upstream> {
+	let host = url.host_str().ok_or_else(|| anyhow!("No host in {}", url))?;
+	let secure = matches!(url.scheme(), "wss" | "https");
+	let port = url.port().unwrap_or(if secure { 443 } else { 80 });
+
+	let tcp = TcpStream::connect((host, port)).await
+		.with_context(|| format!("Unable to connect to {}:{}", host, port))?;
+	let stream: Box<dyn Stream> = if secure {
+		let server_name = ServerName::try_from(host).map_err(|_| anyhow!("Invalid server name: {}", host))?;
+		Box::new(connector().connect(server_name, tcp).await
+			.with_context(|| format!("TLS handshake with {} failed", host))?)
+	} else {
+		Box::new(tcp)
+	};
+	let mut stream = BufReader::new(stream);
+
+	let mut head = format!("GET {} HTTP/1.1\r\n", &url[url::Position::BeforePath..]);
+	if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("host")) {
+		head.push_str(&format!("Host: {}\r\n", &url[url::Position::BeforeHost..url::Position::AfterPort]));
+	}
+	for (name, value) in headers {
+		if !DROPPED_HEADERS.iter().any(|dropped| name.eq_ignore_ascii_case(dropped)) {
+			head.push_str(&format!("{}: {}\r\n", name, value));
+		}
+	}
+	head.push_str("\r\n");
+	stream.write_all(head.as_bytes()).await?;
+	stream.flush().await?;
+
+	let mut response = Vec::new();
+	let mut content_length = 0;
+	loop {
+		let start = response.len();
+		if start >= MAX_HEAD_SIZE {
+			return Err(anyhow!("{} sent a WebSocket handshake response head over {} bytes", host, MAX_HEAD_SIZE));
+		}
+		// A line without a newline stops at the limit instead of growing unbounded
+		let remaining = (MAX_HEAD_SIZE - start) as u64;
+		if (&mut stream).take(remaining).read_until(b'\n', &mut response).await? == 0 {
+			return Err(anyhow!("{} closed the connection during the WebSocket handshake", host));
+		}
+		let line = String::from_utf8_lossy(&response[start..]).trim().to_string();
+		if line.is_empty() {
+			break;
+		}
+		if let Some((name, value)) = line.split_once(':')
+			&& name.trim().eq_ignore_ascii_case("content-length")
+		{
+			content_length = value.trim().parse().unwrap_or(0);
+		}
+	}
+	let status = String::from_utf8_lossy(&response)
+		.split_whitespace()
+		.nth(1)
+		.and_then(|status| status.parse().ok())
+		.ok_or_else(|| anyhow!("Invalid WebSocket handshake response from {}", host))?;
+
+	// A refused upgrade is an ordinary response, body included
+	if status != 101 && content_length > 0 {
+		if content_length > MAX_REFUSAL_BODY {
+			return Err(anyhow!("{} refused the WebSocket upgrade with a {} byte body, over the {} byte limit", host, content_length, MAX_REFUSAL_BODY));
+		}
+		let start = response.len();
+		response.resize(start + content_length, 0);
+		stream.read_exact(&mut response[start..]).await?;
+	}
+	Ok(Upstream { status, response, stream })
+}
+
+/// Relay frames between the client and the origin after a successful
+/// upgrade, logging the direction, opcode and a payload preview of each,
+/// until either side closes
+pub async fn relay<R, W>(client_read: R, client_write: W, server: BufReader<Box<dyn Stream>>, url: &str) -> Result<()>
+where
+	R: AsyncRead + Unpin,
+	W: AsyncWrite + Unpin,
+{
+	let url = &display_url(url);
+	info!("WebSocket to {} established", url);
+	let (server_read, server_write) = tokio::io::split(server);
+	let mut sent = FrameStats::default();
+	let mut received = FrameStats::default();
+	let result = tokio::select! {
+		result = pump(client_read, server_write, Direction::ClientToServer, url, &mut sent) => result,
+		result = pump(server_read, client_write, Direction::ServerToClient, url, &mut received) => result,
+	};
+	info!(
+		"WebSocket to {} closed: {} frames ({} B) sent, {} frames ({} B) received",
+		url, sent.frames, sent.bytes, received.frames, received.bytes
+	);
+	result
+}
+
+/// Forward frames from `reader` to `writer` unchanged, logging each one
+async fn pump<R, W>(mut reader: R, mut writer: W, direction: Direction, url: &str, stats: &mut FrameStats) -> Result<()>
+where
+	R: AsyncRead + Unpin,
+	W: AsyncWrite + Unpin,
+{
+	let mut buffer = vec![0; 16 * 1024];
+	// Continuation frames are previewed like the message they continue
+	let mut message_is_text = false;
+	loop {
+		let mut header = [0u8; 2];
+		match reader.read_exact(&mut header).await {
+			Ok(_) => {}
+			Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+				writer.shutdown().await.ok();
+				return Ok(());
+			}
+			Err(e) => return Err(e.into()),
+		}
+		let fin = header[0] & 0x80 != 0;
+		let opcode = header[0] & 0x0f;
+		let mut raw = header.to_vec();
+
+		let length = match header[1] & 0x7f {
+			126 => {
+				let mut extended = [0u8; 2];
+				reader.read_exact(&mut extended).await?;
+				raw.extend_from_slice(&extended);
+				u16::from_be_bytes(extended) as u64
+			}
+			127 => {
+				let mut extended = [0u8; 8];
+				reader.read_exact(&mut extended).await?;
+				raw.extend_from_slice(&extended);
+				u64::from_be_bytes(extended)
+			}
+			length => length as u64,
+		};
+		let mask = if header[1] & 0x80 != 0 {
+			let mut mask = [0u8; 4];
+			reader.read_exact(&mut mask).await?;
+			raw.extend_from_slice(&mask);
+			Some(mask)
+		} else {
+			None
+		};
+		writer.write_all(&raw).await?;
+
+		// Payloads are streamed through; only the start is kept, unmasked
+		let mut preview = Vec::new();
+		let mut remaining = length;
+		while remaining > 0 {
+			let chunk = remaining.min(buffer.len() as u64) as usize;
+			reader.read_exact(&mut buffer[..chunk]).await?;
+			writer.write_all(&buffer[..chunk]).await?;
+			let offset = (length - remaining) as usize;
+			for (i, byte) in buffer[..chunk].iter().enumerate().take(PREVIEW_BYTES.saturating_sub(preview.len())) {
+				preview.push(mask.map_or(*byte, |mask| byte ^ mask[(offset + i) % 4]));
+			}
+			remaining -= chunk as u64;
+		}
+		writer.flush().await?;
+
+		if opcode == 1 || opcode == 2 {
+			message_is_text = opcode == 1;
+		}
+		let text = opcode == 1 || (opcode == 0 && message_is_text) || opcode == 9 || opcode == 10;
+		let arrow = match direction {
+			Direction::ClientToServer => "→",
+			Direction::ServerToClient => "←",
+		};
+		info!(
+			"WS {} {} {}{} {} B {}",
+			arrow, url, opcode_name(opcode), if fin { "" } else { " (fragment)" }, length, describe(opcode, text, &preview, length)
+		);
+		stats.frames += 1;
+		stats.bytes += length;
+	}
+}
+
+/// `url` with the `ws`/`wss` scheme it is known by
+fn display_url(url: &str) -> String {
+	match url.split_once("://") {
+		Some(("http", rest)) => format!("ws://{}", rest),
+		Some(("https", rest)) => format!("wss://{}", rest),
+		_ => url.to_string(),
+	}
+}
+
+fn opcode_name(opcode: u8) -> String {
+	match opcode {
+		0 => "continuation".to_string(),
+		1 => "text".to_string(),
+		2 => "binary".to_string(),
+		8 => "close".to_string(),
+		9 => "ping".to_string(),
+		10 => "pong".to_string(),
+		other => format!("reserved(0x{:x})", other),
+	}
+}
+
+/// The payload preview: text as is, close frames as code and reason, anything else in hex
+fn describe(opcode: u8, text: bool, preview: &[u8], length: u64) -> String {
+	let truncated = if (preview.len() as u64) < length { "…" } else { "" };
+	if opcode == 8 {
+		return match preview {
+			[high, low, reason @ ..] => format!("code {} {}", u16::from_be_bytes([*high, *low]), String::from_utf8_lossy(reason)),
+			_ => String::new(),
+		};
+	}
+	if text {
+		format!("{:?}{}", String::from_utf8_lossy(preview), truncated)
+	} else {
+		let hex: Vec<String> = preview.iter().take(32).map(|byte| format!("{:02x}", byte)).collect();
+		let truncated = if (hex.len() as u64) < length { "…" } else { "" };
+		format!("{}{}", hex.join(" "), truncated)
+	}
+}
+
+fn connector() -> TlsConnector {
+	let mut roots = RootCertStore::empty();
+	roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+		OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+	}));
+	let mut config = ClientConfig::builder()
+		.with_safe_defaults()
+		.with_root_certificates(roots)
+		.with_no_client_auth();
+	// The upgrade is an HTTP/1.1 mechanism
+	config.alpn_protocols = vec![b"http/1.1".to_vec()];
+	TlsConnector::from(Arc::new(config))
+}
@@ -0,0 +1,127 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::BufRead;
+
+use crate::interrupt::{self, GenerationGuard};
+
+/// Context size assumed for remote models when trimming history
+const REMOTE_CONTEXT_LENGTH: usize = 8192;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChatMessage {
+	pub role: String,
+	pub content: String,
+}
+
+impl ChatMessage {
+	pub fn new(role: &str, content: impl Into<String>) -> Self {
+		Self {
+			role: role.to_string(),
+			content: content.into(),
+		}
+	}
+}
+
+/// Something that can turn a conversation into an answer.
+///
+/// Implementations are blocking and are driven from `spawn_blocking`; they must
+/// pass every generated piece of text to `on_token` and stop early once
+/// `interrupt::is_cancelled()` reports Ctrl+C.
+pub trait Backend: Send + Sync {
+	/// Short label used when reporting latency
+	fn name(&self) -> String;
+
+	/// Maximum number of tokens the model accepts, prompt and answer together
+	fn context_length(&self) -> usize;
+
+	/// Number of tokens `messages` take up in the prompt (may be an estimate)
+	fn count_tokens(&self, messages: &[ChatMessage]) -> Result<usize>;
+
+	fn generate(&self, messages: &[ChatMessage], max_tokens: usize, on_token: &mut dyn FnMut(&str)) -> Result<String>;
+}
+
+/// Any OpenAI-compatible `/chat/completions` endpoint (OpenAI, DeepSeek, Ollama, vLLM...)
+pub struct RemoteBackend {
+	api_base: String,
+	api_key: Option<String>,
+	model: String,
+}
+
+impl RemoteBackend {
+	pub fn new(api_base: &str, api_key: Option<String>, model: &str) -> Self {
+		Self {
+			api_base: api_base.trim_end_matches('/').to_string(),
+			api_key,
+			model: model.to_string(),
+		}
+	}
+}
+
+impl Backend for RemoteBackend {
+	fn name(&self) -> String {
+		format!("remote ({})", self.model)
+	}
+
+	fn context_length(&self) -> usize {
+		REMOTE_CONTEXT_LENGTH
+	}
+
+	fn count_tokens(&self, messages: &[ChatMessage]) -> Result<usize> {
+		// Without the remote tokenizer, ~4 characters per token is close enough for trimming
+		Ok(messages.iter().map(|m| m.content.chars().count() / 4 + 4).sum())
+	}
+
+	fn generate(&self, messages: &[ChatMessage], max_tokens: usize, on_token: &mut dyn FnMut(&str)) -> Result<String> {
+		let _guard = GenerationGuard::begin();
+
+		// Same agent as the model downloads, so the request honours the proxy health check
+		let agent = crate::proxy_config::create_agent()?;
+		let url = format!("{}/chat/completions", self.api_base);
+		let body = json!({
+			"model": self.model,
+			"messages": messages,
+			"max_tokens": max_tokens,
+			"temperature": 0.3,
+			"stream": true,
+		});
+
+		let mut request = agent.post(&url).set("Content-Type", "application/json");
+		if let Some(key) = &self.api_key {
+			request = request.set("Authorization", &format!("Bearer {}", key));
+		}
+		let response = match request.send_string(&body.to_string()) {
+			Ok(response) => response,
+			Err(ureq::Error::Status(code, response)) => {
+				let detail = response.into_string().unwrap_or_default();
+				anyhow::bail!("{} returned HTTP {}: {}", url, code, detail);
+			}
+			Err(e) => return Err(e).with_context(|| format!("Unable to reach {}", url)),
+		};
+
+		// Server-sent events: one `data: {json}` line per delta, terminated by `data: [DONE]`
+		let mut answer = String::new();
+		for line in std::io::BufReader::new(response.into_reader()).lines() {
+			if interrupt::is_cancelled() {
+				break;
+			}
+
+			let line = line?;
+			let Some(data) = line.strip_prefix("data:").map(str::trim) else {
+				continue;
+			};
+			if data == "[DONE]" {
+				break;
+			}
+
+			let chunk: serde_json::Value = serde_json::from_str(data)
+				.with_context(|| format!("Unexpected stream chunk: {}", data))?;
+			if let Some(token) = chunk["choices"][0]["delta"]["content"].as_str() {
+				on_token(token);
+				answer.push_str(token);
+			}
+		}
+
+		Ok(answer.trim().to_string())
+	}
+}
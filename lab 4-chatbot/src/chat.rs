@@ -0,0 +1,82 @@
+use anyhow::Result;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::backend::Backend;
+use crate::history::History;
+
+const HELP: &str = "Commands: /history shows the conversation, /reset clears it, /exit quits";
+
+/// Interactive multi-turn chat. Every answer is appended to the history file
+/// so the conversation continues where it left off on the next run.
+pub async fn run(backend: Arc<dyn Backend>, history_path: &Path) -> Result<()> {
+	let mut history = History::load(history_path)?;
+
+	println!("🍊 Mangosteen chat via {} ({} earlier turns loaded)", backend.name(), history.turns().len());
+	println!("{}", HELP);
+
+	let mut lines = BufReader::new(tokio::io::stdin()).lines();
+	loop {
+		print!("\n> ");
+		io::stdout().flush()?;
+
+		let Some(line) = lines.next_line().await? else {
+			break;
+		};
+		let input = line.trim();
+
+		match input {
+			"" => continue,
+			"/exit" | "/quit" => break,
+			"/help" => println!("{}", HELP),
+			"/reset" => {
+				history.reset();
+				history.save()?;
+				println!("🧹 Conversation history cleared");
+			}
+			"/history" => print_history(&history),
+			_ if input.starts_with('/') => println!("Unknown command '{}'. {}", input, HELP),
+			question => {
+				let references = crate::database::retrieve(question).await?;
+				let answer = crate::llm::answer_in_conversation(
+					backend.clone(),
+					question,
+					references,
+					history.turns().to_vec(),
+					crate::print_token(),
+				)
+				.await?;
+				println!();
+
+				if answer.dropped_turns > 0 {
+					println!("(left out the {} oldest turn(s) to fit the context window)", answer.dropped_turns);
+				}
+				if crate::interrupt::is_cancelled() {
+					println!("(generation interrupted, answer is partial)");
+				}
+
+				crate::report_latency(backend.as_ref(), &answer);
+
+				history.push(question, &answer.text);
+				history.save()?;
+			}
+		}
+	}
+
+	Ok(())
+}
+
+fn print_history(history: &History) {
+	if history.turns().is_empty() {
+		println!("No conversation yet");
+		return;
+	}
+
+	for (i, turn) in history.turns().iter().enumerate() {
+		println!("[{}] {}", i + 1, turn.timestamp.format("%Y-%m-%d %H:%M:%S"));
+		println!("  You: {}", turn.user);
+		println!("  Mangosteen: {}", turn.assistant);
+	}
+}
@@ -1,10 +1,35 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 #[derive(Debug, Parser)]
 #[command(name = "Mangosteen")]
 #[command(about = "Mangosteen is AI assistant which is tailored just for you", long_about = None)]
 pub struct Cli {
 	#[command(subcommand)]
 	pub command: Commands,
+
+	/// Only use already-downloaded model files, never touch the network
+	#[arg(long, global = true, env = "HF_HUB_OFFLINE")]
+	pub offline: bool,
+
+	/// Which model answers questions
+	#[arg(long, global = true, value_enum, default_value = "local")]
+	pub backend: BackendKind,
+
+	/// Base URL of the OpenAI-compatible API used by `--backend remote`
+	#[arg(long, global = true, env = "OPENAI_BASE_URL", default_value = "https://api.openai.com/v1")]
+	pub api_base: String,
+
+	/// Model name requested from the remote API (the key is read from OPENAI_API_KEY)
+	#[arg(long, global = true, env = "OPENAI_MODEL", default_value = "gpt-4o-mini")]
+	pub api_model: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+	/// Quantized Phi-2 running locally with candle
+	Local,
+	/// OpenAI-compatible chat completions endpoint, reached through the same proxy
+	Remote,
 }
 
 #[derive(Debug, Subcommand)]
@@ -19,4 +44,10 @@ pub enum Commands {
 	/// The content to remember
 		content: String,
 	},
+	/// Start an interactive chat that remembers earlier turns
+	Chat {
+	/// Where to keep the conversation between runs (defaults to ./chat_history.json)
+		#[arg(long)]
+		history: Option<PathBuf>,
+	},
 }
\ No newline at end of file
@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::proxy_config;
+
+const HUB_ENDPOINT: &str = "https://huggingface.co";
+const CHUNK_SIZE: u64 = 8 * 1024 * 1024;
+const MAX_RETRIES: u32 = 5;
+const BACKOFF_BASE: Duration = Duration::from_secs(1);
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// What the hub tells us about a file before we download it
+struct FileMetadata {
+	url: String,
+	size: Option<u64>,
+	sha256: Option<String>,
+}
+
+/// Directory holding downloaded model files, overridable with `MANGOSTEEN_MODEL_DIR`
+pub fn model_dir() -> PathBuf {
+	if let Ok(dir) = std::env::var("MANGOSTEEN_MODEL_DIR") {
+		return PathBuf::from(dir);
+	}
+	dirs::cache_dir()
+		.unwrap_or_else(std::env::temp_dir)
+		.join("mangosteen")
+		.join("models")
+}
+
+/// Final location of `filename` from `repo` inside the model directory
+pub fn cached_path(repo: &str, filename: &str) -> PathBuf {
+	model_dir().join(repo.replace('/', "--")).join(filename)
+}
+
+/// Forbid any network access; only files already on disk can be used
+pub fn set_offline(offline: bool) {
+	OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+pub fn is_offline() -> bool {
+	OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Look for an already-downloaded copy of a model file, either in our model
+/// directory or in the standard Hugging Face cache used by earlier versions.
+pub fn find_cached(repo: &str, filename: &str) -> Option<PathBuf> {
+	let path = cached_path(repo, filename);
+	if path.exists() {
+		return Some(path);
+	}
+	hf_hub::Cache::default()
+		.repo(hf_hub::Repo::model(repo.to_string()))
+		.get(filename)
+}
+
+/// Check that every `(repo, filename)` pair is available locally, listing all
+/// missing files at once instead of failing on the first model load.
+pub fn ensure_cached(files: &[(&str, &str)]) -> Result<()> {
+	let missing: Vec<String> = files
+		.iter()
+		.filter(|(repo, filename)| find_cached(repo, filename).is_none())
+		.map(|(repo, filename)| format!("  - {}/{} (expected at {})", repo, filename, cached_path(repo, filename).display()))
+		.collect();
+
+	if !missing.is_empty() {
+		anyhow::bail!(
+			"Offline mode: {} model file(s) are not cached:\n{}\nRun once without --offline to download them.",
+			missing.len(),
+			missing.join("\n")
+		);
+	}
+	Ok(())
+}
+
+/// Return the local path of a Hugging Face model file, downloading it first if needed.
+///
+/// Data is written to a `.part` file in fixed-size `Range` chunks so a stalled
+/// connection only costs the current chunk, and a later run resumes from
+/// whatever is already on disk.
+pub fn hub_file(repo: &str, filename: &str) -> Result<PathBuf> {
+	if let Some(path) = find_cached(repo, filename) {
+		return Ok(path);
+	}
+	if is_offline() {
+		anyhow::bail!("Offline mode: {}/{} is not cached and cannot be downloaded", repo, filename);
+	}
+
+	let path = cached_path(repo, filename);
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)
+			.with_context(|| format!("Unable to create model directory {}", parent.display()))?;
+	}
+
+	let url = format!("{}/{}/resolve/main/{}", HUB_ENDPOINT, repo, filename);
+	let metadata = with_retry(&format!("metadata for {}", filename), || fetch_metadata(&url))?;
+
+	let part_path = path.with_extension(part_extension(&path));
+	download_to(&metadata, &part_path, filename)?;
+	verify(&metadata, &part_path, filename)?;
+
+	fs::rename(&part_path, &path)
+		.with_context(|| format!("Unable to move {} into place", part_path.display()))?;
+	Ok(path)
+}
+
+fn part_extension(path: &Path) -> String {
+	match path.extension() {
+		Some(ext) => format!("{}.part", ext.to_string_lossy()),
+		None => "part".to_string(),
+	}
+}
+
+/// Resolve the download URL without following redirects, because the LFS size
+/// and checksum headers are only present on the hub's redirect response.
+fn fetch_metadata(url: &str) -> Result<FileMetadata> {
+	let agent = proxy_config::agent_builder()?.redirects(0).build();
+	let response = agent.head(url).call()?;
+
+	let header_u64 = |name: &str| response.header(name).and_then(|v| v.parse::<u64>().ok());
+	let size = header_u64("x-linked-size").or_else(|| header_u64("content-length"));
+	let sha256 = response
+		.header("x-linked-etag")
+		.map(|etag| etag.trim_matches('"').to_string())
+		.filter(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()));
+
+	let url = match response.header("location") {
+		Some(location) if location.starts_with('/') => format!("{}{}", HUB_ENDPOINT, location),
+		Some(location) => location.to_string(),
+		None => url.to_string(),
+	};
+
+	Ok(FileMetadata { url, size, sha256 })
+}
+
+fn download_to(metadata: &FileMetadata, part_path: &Path, filename: &str) -> Result<()> {
+	let agent = proxy_config::create_agent()?;
+	let mut file = OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(part_path)
+		.with_context(|| format!("Unable to open {}", part_path.display()))?;
+
+	let progress = ProgressBar::new(metadata.size.unwrap_or(0));
+	progress.set_style(
+		ProgressStyle::with_template("{msg} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {bytes_per_sec} ETA {eta}")
+			.unwrap_or_else(|_| ProgressStyle::default_bar())
+			.progress_chars("=> "),
+	);
+	progress.set_message(filename.to_string());
+
+	let mut offset = file.metadata()?.len();
+	if offset > 0 {
+		progress.println(format!("Resuming {} from {} bytes", filename, offset));
+	}
+	progress.set_position(offset);
+
+	match metadata.size {
+		Some(size) => {
+			if offset > size {
+				// Stale partial file from a different revision
+				file.set_len(0)?;
+				offset = 0;
+				progress.set_position(0);
+			}
+			while offset < size {
+				let end = (offset + CHUNK_SIZE).min(size) - 1;
+				with_retry(&format!("{} bytes {}-{}", filename, offset, end), || {
+					// Bytes of a failed attempt are kept, so retry from the file's current length
+					let start = file.metadata()?.len();
+					if start > end {
+						return Ok(());
+					}
+					fetch_range(&agent, &metadata.url, start, Some(end), &mut file, &progress)
+				})?;
+				offset = end + 1;
+			}
+		}
+		None => {
+			// No size advertised: stream to EOF, still resuming from the partial file
+			with_retry(filename, || {
+				let start = file.metadata()?.len();
+				fetch_range(&agent, &metadata.url, start, None, &mut file, &progress)
+			})?;
+		}
+	}
+
+	progress.finish_and_clear();
+	Ok(())
+}
+
+fn fetch_range(
+	agent: &ureq::Agent,
+	url: &str,
+	start: u64,
+	end: Option<u64>,
+	file: &mut File,
+	progress: &ProgressBar,
+) -> Result<()> {
+	let range = match end {
+		Some(end) => format!("bytes={}-{}", start, end),
+		None => format!("bytes={}-", start),
+	};
+	let response = agent.get(url).set("Range", &range).call()?;
+
+	if start > 0 && response.status() != 206 {
+		// Server ignored the range and sent the whole file; start over
+		file.set_len(0)?;
+		progress.set_position(0);
+	}
+
+	let mut reader = response.into_reader();
+	let mut buf = vec![0u8; 64 * 1024];
+	loop {
+		let read = reader.read(&mut buf)?;
+		if read == 0 {
+			break;
+		}
+		file.write_all(&buf[..read])?;
+		progress.inc(read as u64);
+	}
+	file.flush()?;
+	Ok(())
+}
+
+fn verify(metadata: &FileMetadata, path: &Path, filename: &str) -> Result<()> {
+	let actual_size = fs::metadata(path)?.len();
+	if let Some(size) = metadata.size
+		&& actual_size != size
+	{
+		anyhow::bail!("{} is {} bytes, expected {}", filename, actual_size, size);
+	}
+
+	if let Some(expected) = &metadata.sha256 {
+		let mut hasher = Sha256::new();
+		let mut file = File::open(path)?;
+		std::io::copy(&mut file, &mut hasher)?;
+		let actual = format!("{:x}", hasher.finalize());
+		if &actual != expected {
+			// A corrupt file can't be resumed, so drop it for the next attempt
+			fs::remove_file(path)?;
+			anyhow::bail!("Checksum mismatch for {}: expected {}, got {}", filename, expected, actual);
+		}
+	}
+
+	Ok(())
+}
+
+fn with_retry<T>(what: &str, mut operation: impl FnMut() -> Result<T>) -> Result<T> {
+	let mut attempt = 0;
+	loop {
+		match operation() {
+			Ok(value) => return Ok(value),
+			Err(e) if attempt + 1 < MAX_RETRIES => {
+				let delay = BACKOFF_BASE * 2u32.pow(attempt);
+				eprintln!("⚠️  Failed to fetch {} ({}), retrying in {}s", what, e, delay.as_secs());
+				std::thread::sleep(delay);
+				attempt += 1;
+			}
+			Err(e) => {
+				return Err(e.context(format!("Giving up on {} after {} attempts", what, MAX_RETRIES)));
+			}
+		}
+	}
+}
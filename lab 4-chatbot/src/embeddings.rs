@@ -2,19 +2,20 @@ use anyhow::{Context, Error as E, Result};
 use candle_core::{Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::bert::{BertModel, Config, DTYPE};
-use hf_hub::{api::sync::Api, Repo};
 use lazy_static::lazy_static;
 use tokenizers::{PaddingParams, Tokenizer};
 
 lazy_static! {
 	pub static ref AI: (BertModel, Tokenizer) = load_model().expect("Unable to load model");
 }
+pub const MODEL_REPO: &str = "BAAI/bge-small-en-v1.5";
+pub const MODEL_FILES: [&str; 3] = ["config.json", "tokenizer.json", "pytorch_model.bin"];
+
 pub fn load_model() -> Result<(BertModel, Tokenizer)> {
-	let api = Api::new()?.repo(Repo::model("BAAI/bge-small-en-v1.5".to_string()));
 	// Fetching the config, tokenizer and weights files
-	let config_filename = api.get("config.json")?;
-	let tokenizer_filename = api.get("tokenizer.json")?;
-	let weights_filename = api.get("pytorch_model.bin")?;
+	let config_filename = crate::download::hub_file(MODEL_REPO, "config.json")?;
+	let tokenizer_filename = crate::download::hub_file(MODEL_REPO, "tokenizer.json")?;
+	let weights_filename = crate::download::hub_file(MODEL_REPO, "pytorch_model.bin")?;
 	let config = std::fs::read_to_string(config_filename)?;
 	let config: Config = serde_json::from_str(&config)?;
 	let mut tokenizer =
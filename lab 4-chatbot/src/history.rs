@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// One question/answer exchange
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Turn {
+	pub user: String,
+	pub assistant: String,
+	pub timestamp: chrono::DateTime<chrono::Utc>,
+}
+
+/// Multi-turn chat history, persisted as JSON between runs
+pub struct History {
+	path: PathBuf,
+	turns: Vec<Turn>,
+}
+
+impl History {
+	/// Default location next to the database directory
+	pub fn default_path() -> PathBuf {
+		std::env::current_dir().unwrap().join("chat_history.json")
+	}
+
+	/// Load the history file, starting empty if it does not exist yet
+	pub fn load(path: &Path) -> Result<Self> {
+		let turns = if path.exists() {
+			let content = std::fs::read_to_string(path)
+				.with_context(|| format!("Unable to read chat history {}", path.display()))?;
+			serde_json::from_str(&content)
+				.with_context(|| format!("Chat history {} is corrupted", path.display()))?
+		} else {
+			Vec::new()
+		};
+
+		Ok(Self {
+			path: path.to_path_buf(),
+			turns,
+		})
+	}
+
+	pub fn save(&self) -> Result<()> {
+		let content = serde_json::to_string_pretty(&self.turns)?;
+		std::fs::write(&self.path, content)
+			.with_context(|| format!("Unable to write chat history {}", self.path.display()))
+	}
+
+	pub fn push(&mut self, user: &str, assistant: &str) {
+		self.turns.push(Turn {
+			user: user.to_string(),
+			assistant: assistant.to_string(),
+			timestamp: chrono::Utc::now(),
+		});
+	}
+
+	pub fn reset(&mut self) {
+		self.turns.clear();
+	}
+
+	pub fn turns(&self) -> &[Turn] {
+		&self.turns
+	}
+}
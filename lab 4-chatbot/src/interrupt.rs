@@ -0,0 +1,43 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static GENERATING: AtomicBool = AtomicBool::new(false);
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Install the Ctrl+C handler.
+///
+/// While tokens are being generated, Ctrl+C only asks the decoding loop to
+/// stop so the partial answer can still be returned; at any other time it
+/// exits the process as usual.
+pub fn install() {
+	tokio::spawn(async {
+		while tokio::signal::ctrl_c().await.is_ok() {
+			if GENERATING.load(Ordering::SeqCst) && !CANCELLED.swap(true, Ordering::SeqCst) {
+				eprintln!("\n⏹  Stopping generation...");
+			} else {
+				std::process::exit(130);
+			}
+		}
+	});
+}
+
+/// Marks a generation in progress for as long as it is alive
+pub struct GenerationGuard;
+
+impl GenerationGuard {
+	pub fn begin() -> Self {
+		CANCELLED.store(false, Ordering::SeqCst);
+		GENERATING.store(true, Ordering::SeqCst);
+		Self
+	}
+}
+
+impl Drop for GenerationGuard {
+	fn drop(&mut self) {
+		GENERATING.store(false, Ordering::SeqCst);
+	}
+}
+
+/// Whether the last generation was stopped with Ctrl+C
+pub fn is_cancelled() -> bool {
+	CANCELLED.load(Ordering::SeqCst)
+}
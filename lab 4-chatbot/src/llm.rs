@@ -3,20 +3,25 @@ use candle_core::{DType, Device, Tensor};
 use candle_transformers::generation::LogitsProcessor;
 use candle_transformers::models::quantized_mixformer::Config;
 use candle_transformers::models::quantized_mixformer::MixFormerSequentialForCausalLM as QMixFormer;
-use hf_hub::{api::sync::Api, Repo};
 use lazy_static::lazy_static;
 use serde_json::json;
+use std::sync::Arc;
+use std::time::Instant;
 use tokenizers::Tokenizer;
 
+use crate::backend::{Backend, ChatMessage};
 use crate::database::Content;
+use crate::history::Turn;
 lazy_static! {
 	pub static ref PHI: (QMixFormer, Tokenizer) =
 		load_model().expect("Unable to load model");
 }
+pub const MODEL_REPO: &str = "Demonthos/dolphin-2_6-phi-2-candle";
+pub const MODEL_FILES: [&str; 2] = ["tokenizer.json", "model-q4k.gguf"];
+
 pub fn load_model() -> Result<(QMixFormer, Tokenizer)> {
-	let api = Api::new()?.repo(Repo::model("Demonthos/dolphin-2_6-phi-2-candle".to_string()));
-	let tokenizer_filename = api.get("tokenizer.json")?;
-	let weights_filename = api.get("model-q4k.gguf")?;
+	let tokenizer_filename = crate::download::hub_file(MODEL_REPO, "tokenizer.json")?;
+	let weights_filename = crate::download::hub_file(MODEL_REPO, "model-q4k.gguf")?;
 	let tokenizer = Tokenizer::from_file(tokenizer_filename).map_err(E::msg)?;
 	let config = Config::v2();
 	let device = Device::Cpu;
@@ -54,7 +59,12 @@ impl TextGeneration {
 			device: device.clone(),
 		}
 	}
-	fn run(&mut self, prompt: &str, sample_len: usize) -> Result<String> {
+	/// Generate up to `sample_len` tokens, passing each decoded piece to
+	/// `on_token` as soon as it is sampled. Stops early on Ctrl+C and returns
+	/// what was generated so far.
+	fn run(&mut self, prompt: &str, sample_len: usize, on_token: &mut dyn FnMut(&str)) -> Result<String> {
+		let _guard = crate::interrupt::GenerationGuard::begin();
+
 		// Encode the prompt into tokens
 		let tokens = self.tokenizer.encode(prompt, true).map_err(E::msg)?;
 		let mut tokens = tokens.get_ids().to_vec();
@@ -66,6 +76,10 @@ impl TextGeneration {
 		// Loop over the sample length to generate the response
 		let mut response = String::new();
 		for index in 0..sample_len {
+			if crate::interrupt::is_cancelled() {
+				break;
+			}
+
 			// Get the context for the current iteration
 			let context_size = if index > 0 { 1 } else { tokens.len() };
 			let ctxt = &tokens[tokens.len().saturating_sub(context_size)..];
@@ -93,38 +107,137 @@ impl TextGeneration {
 			}
 
 			let token = self.tokenizer.decode(&[next_token], true).map_err(E::msg)?;
+			on_token(&token);
 			response += &token;
 		}
 		Ok(response.trim().to_string())
 	}
 }
 
-pub async fn answer_with_context(query: &str, references: Vec<Content>) -> Result<String> {
+/// Phi-2 was trained with a 2048 token context window
+pub const CONTEXT_LENGTH: usize = 2048;
+const SAMPLE_LEN: usize = 400;
+const SYSTEM_PROMPT: &str = "As a friendly and helpful AI assistant named Mangosteen. Your answer should be very concise and to the point. Do not repeat question or references.";
+
+/// The quantized Phi-2 model running on the CPU through candle
+pub struct LocalBackend;
+
+impl Backend for LocalBackend {
+	fn name(&self) -> String {
+		"local (phi-2 q4k)".to_string()
+	}
+
+	fn context_length(&self) -> usize {
+		CONTEXT_LENGTH
+	}
+
+	fn count_tokens(&self, messages: &[ChatMessage]) -> Result<usize> {
+		let (_, tokenizer) = &*PHI;
+		Ok(tokenizer.encode(to_chatml(messages), true).map_err(E::msg)?.len())
+	}
+
+	fn generate(&self, messages: &[ChatMessage], max_tokens: usize, on_token: &mut dyn FnMut(&str)) -> Result<String> {
+		let (model, tokenizer) = &*PHI;
+		let mut pipeline = TextGeneration::new(
+			model.clone(),
+			tokenizer.clone(),
+			398752958,
+			Some(0.3),
+			None,
+			1.1,
+			64,
+			&Device::Cpu,
+		);
+		pipeline.run(&to_chatml(messages), max_tokens, on_token)
+	}
+}
+
+/// Render messages in the ChatML format the dolphin fine-tune expects
+fn to_chatml(messages: &[ChatMessage]) -> String {
+	let mut prompt = String::new();
+	for message in messages {
+		prompt.push_str(&format!("<|im_start|>{}\n{}\n<|im_end|>\n", message.role, message.content));
+	}
+	prompt.push_str("<|im_start|>assistant\n");
+	prompt
+}
+
+/// A generated answer with the numbers needed to compare backends
+pub struct Answer {
+	pub text: String,
+	/// Oldest history turns left out of the prompt to fit the context window
+	pub dropped_turns: usize,
+	pub first_token_ms: Option<u128>,
+	pub total_ms: u128,
+}
+
+/// Answer `query` using the retrieved references, streaming tokens to `on_token`
+pub async fn answer_with_context(
+	backend: Arc<dyn Backend>,
+	query: &str,
+	references: Vec<Content>,
+	on_token: impl FnMut(&str) + Send + 'static,
+) -> Result<Answer> {
+	answer_in_conversation(backend, query, references, Vec::new(), on_token).await
+}
+
+/// Answer `query` as the next turn of a conversation.
+///
+/// The oldest turns are left out of the prompt until it fits in the model's
+/// context window together with room for the answer.
+pub async fn answer_in_conversation(
+	backend: Arc<dyn Backend>,
+	query: &str,
+	references: Vec<Content>,
+	turns: Vec<Turn>,
+	mut on_token: impl FnMut(&str) + Send + 'static,
+) -> Result<Answer> {
 	// Create the context for the prompt
 	let mut context = Vec::new();
 	for reference in references.clone() {
 		context.push(json!({"content": reference.content}))
 	}
 	let context = json!(context).to_string();
+	let query = query.to_string();
 
-	// Create the prompt
-	let prompt = format!(
-		"<|im_start|>system\nAs a friendly and helpful AI assistant named Mangosteen. Your answer should be very concise and to the point. Do not repeat question or references.\n<|im_end|>\n<|im_start|>user\nquestion: \"{question}\"\nreferences: \"{context}\"\n<|im_end|>\n<|im_start|>assistant\n",
-		context = context,
-		question = query
-	);
-
-	let (model, tokenizer) = &*PHI;
-	let mut pipeline = TextGeneration::new(
-		model.clone(),
-		tokenizer.clone(),
-		398752958,
-		Some(0.3),
-		None,
-		1.1,
-		64,
-		&Device::Cpu,
-	);
-	let response = pipeline.run(&prompt, 400)?;
-	Ok(response)
-}
\ No newline at end of file
+	// Generation blocks (CPU-bound locally, blocking HTTP remotely), keep it off the async runtime
+	tokio::task::spawn_blocking(move || {
+		let mut dropped_turns = 0;
+		let mut messages = build_messages(&query, &context, &turns);
+		while dropped_turns < turns.len()
+			&& backend.count_tokens(&messages)? + SAMPLE_LEN > backend.context_length()
+		{
+			dropped_turns += 1;
+			messages = build_messages(&query, &context, &turns[dropped_turns..]);
+		}
+
+		let start = Instant::now();
+		let mut first_token_ms = None;
+		let text = backend.generate(&messages, SAMPLE_LEN, &mut |token| {
+			first_token_ms.get_or_insert_with(|| start.elapsed().as_millis());
+			on_token(token);
+		})?;
+
+		Ok(Answer {
+			text,
+			dropped_turns,
+			first_token_ms,
+			total_ms: start.elapsed().as_millis(),
+		})
+	})
+	.await?
+}
+
+/// Create the conversation, replaying earlier turns before the current question
+fn build_messages(query: &str, context: &str, turns: &[Turn]) -> Vec<ChatMessage> {
+	let mut messages = vec![ChatMessage::new("system", SYSTEM_PROMPT)];
+	for turn in turns {
+		messages.push(ChatMessage::new("user", turn.user.clone()));
+		messages.push(ChatMessage::new("assistant", turn.assistant.clone()));
+	}
+	messages.push(ChatMessage::new(
+		"user",
+		format!("question: \"{question}\"\nreferences: \"{context}\"", context = context, question = query),
+	));
+	messages
+}
@@ -1,25 +1,60 @@
 use clap::Parser;
+use std::io::{self, Write};
+use std::sync::Arc;
 
+use backend::Backend;
+
+mod backend;
+mod chat;
 mod cli;
 mod database;
+mod download;
 mod embeddings;
+mod history;
+mod interrupt;
 mod llm;
 mod proxy_config;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-	// Initialize proxy settings
-	proxy_config::init_proxy();
-
 	let args = cli::Cli::parse();
+	interrupt::install();
+
+	if args.offline && args.backend == cli::BackendKind::Remote {
+		anyhow::bail!("--offline cannot be combined with --backend remote");
+	}
+
+	let _proxy_health = if args.offline {
+		download::set_offline(true);
+		download::ensure_cached(&required_model_files(&args.command, args.backend))?;
+		None
+	} else {
+		// Initialize proxy settings, falling back to a direct connection if it is down
+		proxy_config::init_proxy()?;
+		proxy_config::spawn_health_check()
+	};
+
+	let backend: Arc<dyn Backend> = match args.backend {
+		cli::BackendKind::Local => Arc::new(llm::LocalBackend),
+		cli::BackendKind::Remote => Arc::new(backend::RemoteBackend::new(
+			&args.api_base,
+			std::env::var("OPENAI_API_KEY").ok(),
+			&args.api_model,
+		)),
+	};
+
 	match args.command {
 		cli::Commands::Ask { query } => {
 			// Retrieve relevant content from database
 			let references = database::retrieve(&query).await?;
 
-			// Generate answer using LLM with context
-			let answer = llm::answer_with_context(&query, references).await?;
-			println!("Answer: {}", answer);
+			// Generate answer using LLM with context, printing tokens as they arrive
+			let answer = llm::answer_with_context(backend.clone(), &query, references, print_token()).await?;
+			println!();
+			if interrupt::is_cancelled() {
+				println!("(generation interrupted, answer is partial)");
+			}
+			report_latency(backend.as_ref(), &answer);
 		}
 		cli::Commands::Remember { content } => {
 			// Store the content in the database
@@ -27,6 +62,50 @@ async fn main() -> anyhow::Result<()> {
 			println!("✅ Content remembered successfully!");
 			println!("ID: {}", stored_content.id);
 		}
+		cli::Commands::Chat { history } => {
+			let history_path = history.unwrap_or_else(history::History::default_path);
+			chat::run(backend, &history_path).await?;
+		}
 	}
 	Ok(())
-}
\ No newline at end of file
+}
+
+/// Model files a command needs before it can run
+fn required_model_files(command: &cli::Commands, backend: cli::BackendKind) -> Vec<(&'static str, &'static str)> {
+	let mut files: Vec<_> = embeddings::MODEL_FILES
+		.iter()
+		.map(|file| (embeddings::MODEL_REPO, *file))
+		.collect();
+
+	let generates = matches!(command, cli::Commands::Ask { .. } | cli::Commands::Chat { .. });
+	if generates && backend == cli::BackendKind::Local {
+		files.extend(llm::MODEL_FILES.iter().map(|file| (llm::MODEL_REPO, *file)));
+	}
+	files
+}
+
+/// Print streamed tokens immediately, skipping the leading whitespace the model tends to emit.
+/// The "Answer:" prefix waits for the first token so it doesn't collide with model download progress.
+pub fn print_token() -> impl FnMut(&str) + Send + 'static {
+	let mut started = false;
+	move |token: &str| {
+		let token = if started { token } else { token.trim_start() };
+		if token.is_empty() {
+			return;
+		}
+		if !started {
+			print!("Answer: ");
+			started = true;
+		}
+		print!("{}", token);
+		let _ = io::stdout().flush();
+	}
+}
+
+/// Timing line printed after every answer, for comparing backends in the lab report
+pub fn report_latency(backend: &dyn Backend, answer: &llm::Answer) {
+	let first_token = answer
+		.first_token_ms
+		.map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+	println!("⏱️  {}: first token {}, total {}ms", backend.name(), first_token, answer.total_ms);
+}
@@ -1,45 +1,56 @@
 use std::env;
+use std::io::{Read, Write};
+use std::net::TcpStream;
 use std::sync::Once;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use url::Url;
 
 static INIT: Once = Once::new();
+static PROXY_ACTIVE: AtomicBool = AtomicBool::new(false);
 
-/// Create a ureq agent with proxy configuration for ureq 2.x
-// pub fn create_proxy_agent() -> Result<ureq::Agent, Box<dyn std::error::Error>> {
-//     let proxy_url = "http://127.0.0.1:7897";
+const DEFAULT_PROXY_URL: &str = "http://127.0.0.1:7897";
+const DEFAULT_CHECK_INTERVAL_SECS: u64 = 30;
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
 
-//     // Create proxy configuration for ureq 2.x
-//     let proxy = ureq::Proxy::new(proxy_url)?;
+/// Host used to verify that the proxy can actually open a tunnel, not just accept TCP
+const PROBE_TARGET: &str = "huggingface.co:443";
 
-//     // Create agent with proxy using ureq 2.x API
-//     let agent = ureq::AgentBuilder::new()
-//         .proxy(proxy)
-//         .build();
+/// Create a ureq agent builder that routes through the proxy when it is active.
+///
+/// The proxy is set explicitly rather than read from the environment so the
+/// agent follows the health check's decision at the time it is built.
+pub fn agent_builder() -> anyhow::Result<ureq::AgentBuilder> {
+    let mut builder = ureq::AgentBuilder::new()
+        .try_proxy_from_env(false)
+        .timeout_connect(Duration::from_secs(10))
+        .timeout_read(Duration::from_secs(30));
 
-//     Ok(agent)
-// }
+    if is_proxy_active() {
+        builder = builder.proxy(ureq::Proxy::new(proxy_url())?);
+    }
+
+    Ok(builder)
+}
 
-/// Set proxy environment variables for ureq and other HTTP clients
+/// Create a ureq agent with the current proxy configuration
+pub fn create_agent() -> anyhow::Result<ureq::Agent> {
+    Ok(agent_builder()?.build())
+}
+
+/// Proxy address, overridable with `HF_PROXY_URL`
+pub fn proxy_url() -> String {
+    env::var("HF_PROXY_URL").unwrap_or_else(|_| DEFAULT_PROXY_URL.to_string())
+}
+
+/// Route new clients through the proxy
 pub fn setup_proxy() {
-    let proxy_url = "http://127.0.0.1:7897";
-
-    // Set environment variables that ureq and other HTTP clients recognize
-    unsafe {
-        env::set_var("HTTP_PROXY", proxy_url);
-        env::set_var("HTTPS_PROXY", proxy_url);
-        env::set_var("http_proxy", proxy_url);
-        env::set_var("https_proxy", proxy_url);
-        env::set_var("ALL_PROXY", proxy_url);
-        env::set_var("all_proxy", proxy_url);
-
-        // Some applications also check these
-        env::set_var("HTTPS_PROXY_URL", proxy_url);
-        env::set_var("HTTP_PROXY_URL", proxy_url);
-    }
+    PROXY_ACTIVE.store(true, Ordering::SeqCst);
+}
 
-    // println!("Proxy configured: {}", proxy_url);
-    // println!("Environment variables set:");
-    // println!("  HTTP_PROXY: {}", env::var("HTTP_PROXY").unwrap_or_default());
-    // println!("  HTTPS_PROXY: {}", env::var("HTTPS_PROXY").unwrap_or_default());
+/// Let new clients connect directly
+pub fn clear_proxy() {
+    PROXY_ACTIVE.store(false, Ordering::SeqCst);
 }
 
 /// Check if proxy should be used based on environment variable
@@ -47,11 +58,147 @@ pub fn should_use_proxy() -> bool {
     env::var("HF_USE_PROXY").unwrap_or_else(|_| "true".to_string()) == "true"
 }
 
+/// Strict mode fails instead of falling back to a direct connection
+pub fn is_strict() -> bool {
+    env::var("HF_PROXY_STRICT").unwrap_or_else(|_| "false".to_string()) == "true"
+}
+
+/// Whether clients built now should go through the proxy
+pub fn is_proxy_active() -> bool {
+    PROXY_ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Interval between background health checks; `HF_PROXY_CHECK_INTERVAL=0` disables them
+pub fn check_interval() -> Option<Duration> {
+    let secs = env::var("HF_PROXY_CHECK_INTERVAL")
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_CHECK_INTERVAL_SECS);
+
+    if secs == 0 {
+        None
+    } else {
+        Some(Duration::from_secs(secs))
+    }
+}
+
+/// Connect to the proxy and ask it to open a tunnel to Hugging Face.
+///
+/// A plain TCP connect is not enough: Clash may be listening while its
+/// upstream is down, so the probe only succeeds on a `200` CONNECT reply.
+pub fn check_proxy(proxy_url: &str, timeout: Duration) -> Result<(), String> {
+    let url = Url::parse(proxy_url).map_err(|e| format!("invalid proxy URL {}: {}", proxy_url, e))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("proxy URL {} has no host", proxy_url))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or_else(|| format!("proxy URL {} has no port", proxy_url))?;
+    let authority = format!("{}:{}", host, port);
+
+    let addr = url
+        .socket_addrs(|| None)
+        .map_err(|e| format!("cannot resolve proxy address {}: {}", authority, e))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("proxy address {} resolved to nothing", authority))?;
+
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| format!("cannot connect to proxy {}: {}", authority, e))?;
+    stream.set_read_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(timeout)).map_err(|e| e.to_string())?;
+
+    let request = format!(
+        "CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n\r\n",
+        target = PROBE_TARGET
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| format!("proxy {} closed the connection: {}", authority, e))?;
+
+    let mut buf = [0u8; 64];
+    let read = stream
+        .read(&mut buf)
+        .map_err(|e| format!("no answer from proxy {}: {}", authority, e))?;
+    let status_line = String::from_utf8_lossy(&buf[..read]);
+
+    match status_line.split_whitespace().nth(1) {
+        Some("200") => Ok(()),
+        Some(status) => Err(format!("proxy {} refused tunnel to {} (HTTP {})", authority, PROBE_TARGET, status)),
+        None => Err(format!("proxy {} sent an invalid response", authority)),
+    }
+}
+
+/// Probe the proxy and enable it, or fall back to a direct connection.
+/// In strict mode an unreachable proxy is an error instead.
+fn apply_proxy_settings() -> anyhow::Result<()> {
+    let proxy_url = proxy_url();
+
+    match check_proxy(&proxy_url, CHECK_TIMEOUT) {
+        Ok(()) => {
+            setup_proxy();
+            Ok(())
+        }
+        Err(reason) if is_strict() => {
+            clear_proxy();
+            anyhow::bail!("Proxy {} is unreachable ({}) and HF_PROXY_STRICT=true", proxy_url, reason)
+        }
+        Err(reason) => {
+            eprintln!("⚠️  Proxy {} is unreachable ({}), falling back to a direct connection", proxy_url, reason);
+            clear_proxy();
+            Ok(())
+        }
+    }
+}
+
 /// Initialize proxy settings if needed (only once)
-pub fn init_proxy() {
+pub fn init_proxy() -> anyhow::Result<()> {
+    let mut result = Ok(());
     INIT.call_once(|| {
         if should_use_proxy() {
-            setup_proxy();
+            result = apply_proxy_settings();
         }
     });
+    result
+}
+
+/// Re-check the proxy periodically and switch between proxied and direct
+/// connections when its availability changes.
+pub fn spawn_health_check() -> Option<tokio::task::JoinHandle<()>> {
+    if !should_use_proxy() {
+        return None;
+    }
+    let interval = check_interval()?;
+
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick completes immediately and init_proxy has just probed
+        ticker.tick().await;
+
+        loop {
+            ticker.tick().await;
+
+            let proxy_url = proxy_url();
+            let probe_url = proxy_url.clone();
+            let result = match tokio::task::spawn_blocking(move || check_proxy(&probe_url, CHECK_TIMEOUT)).await {
+                Ok(result) => result,
+                Err(e) => Err(e.to_string()),
+            };
+
+            match (result, is_proxy_active()) {
+                (Ok(()), false) => {
+                    eprintln!("✅ Proxy {} is reachable again, switching back to it", proxy_url);
+                    setup_proxy();
+                }
+                (Err(reason), true) if is_strict() => {
+                    eprintln!("❌ Proxy {} became unreachable ({}), strict mode keeps using it", proxy_url, reason);
+                }
+                (Err(reason), true) => {
+                    eprintln!("⚠️  Proxy {} became unreachable ({}), falling back to a direct connection", proxy_url, reason);
+                    clear_proxy();
+                }
+                _ => {}
+            }
+        }
+    }))
 }